@@ -0,0 +1,11 @@
+-- migrations/002_product_ownership_history.sql
+-- Every change of a product's owner; orders keep their own seller_id
+CREATE TABLE product_ownership_history (
+                                           id UUID PRIMARY KEY,
+                                           product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                                           from_seller_id UUID NOT NULL REFERENCES users(id),
+                                           to_seller_id UUID NOT NULL REFERENCES users(id),
+                                           transferred_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_product_ownership_history_product ON product_ownership_history(product_id);
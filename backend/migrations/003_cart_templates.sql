@@ -0,0 +1,18 @@
+-- migrations/003_cart_templates.sql
+-- Named carts a user can save and re-apply later
+CREATE TABLE cart_templates (
+                                id UUID PRIMARY KEY,
+                                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                                name VARCHAR(100) NOT NULL,
+                                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                                UNIQUE(user_id, name)
+);
+
+CREATE INDEX idx_cart_templates_user ON cart_templates(user_id);
+
+CREATE TABLE cart_template_items (
+                                     template_id UUID NOT NULL REFERENCES cart_templates(id) ON DELETE CASCADE,
+                                     product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                                     quantity INTEGER NOT NULL CHECK (quantity > 0),
+                                     PRIMARY KEY (template_id, product_id)
+);
@@ -0,0 +1,14 @@
+-- migrations/005_persistent_carts.sql
+-- Carts are stored per user instead of in the cookie session
+CREATE TABLE carts (
+                       user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                       updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE cart_items (
+                            user_id UUID NOT NULL REFERENCES carts(user_id) ON DELETE CASCADE,
+                            product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                            quantity INTEGER NOT NULL CHECK (quantity > 0),
+                            added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                            PRIMARY KEY (user_id, product_id)
+);
@@ -0,0 +1,4 @@
+-- migrations/006_review_constraints.sql
+-- A buyer can review each order once
+CREATE UNIQUE INDEX idx_reviews_order_unique ON reviews(order_id);
+DROP INDEX IF EXISTS idx_reviews_order;
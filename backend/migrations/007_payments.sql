@@ -0,0 +1,19 @@
+-- migrations/007_payments.sql
+-- Payment states on orders
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'paid';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'failed';
+
+-- One row per payment attempt with the provider
+CREATE TABLE payments (
+                          id UUID PRIMARY KEY,
+                          order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+                          provider VARCHAR(20) NOT NULL,
+                          provider_payment_id VARCHAR(255) NOT NULL UNIQUE,
+                          amount DECIMAL(10, 2) NOT NULL,
+                          currency VARCHAR(3) NOT NULL,
+                          status VARCHAR(30) NOT NULL,
+                          created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                          updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_payments_order ON payments(order_id);
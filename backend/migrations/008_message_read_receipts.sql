@@ -0,0 +1,5 @@
+-- migrations/008_message_read_receipts.sql
+-- When the recipient read a message; NULL means unread
+ALTER TABLE messages ADD COLUMN read_at TIMESTAMPTZ;
+
+CREATE INDEX idx_messages_unread ON messages(conv_id, sender_id) WHERE read_at IS NULL;
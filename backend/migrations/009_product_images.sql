@@ -0,0 +1,17 @@
+-- migrations/009_product_images.sql
+-- Ordered image gallery per product; products.image_url mirrors the first image
+CREATE TABLE product_images (
+                                id UUID PRIMARY KEY,
+                                product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                                url TEXT NOT NULL,
+                                position INTEGER NOT NULL,
+                                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_product_images_product ON product_images(product_id, position);
+
+-- Existing single images become the first gallery entry
+INSERT INTO product_images (id, product_id, url, position)
+SELECT gen_random_uuid(), id, image_url, 0
+FROM products
+WHERE image_url IS NOT NULL AND image_url <> '';
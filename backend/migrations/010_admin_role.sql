@@ -0,0 +1,8 @@
+-- migrations/010_admin_role.sql
+-- Administrators and moderation state. Admins are promoted directly in the database.
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN suspended_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN suspension_reason TEXT;
+
+ALTER TABLE products ADD COLUMN taken_down_at TIMESTAMPTZ;
+ALTER TABLE products ADD COLUMN takedown_reason TEXT;
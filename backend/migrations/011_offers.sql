@@ -0,0 +1,22 @@
+-- migrations/011_offers.sql
+-- Price negotiation between a buyer and a product's seller
+CREATE TYPE offer_status AS ENUM ('pending', 'accepted', 'rejected', 'countered', 'ordered');
+
+CREATE TABLE offers (
+                        id UUID PRIMARY KEY,
+                        product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                        buyer_id UUID NOT NULL REFERENCES users(id),
+                        seller_id UUID NOT NULL REFERENCES users(id),
+                        proposed_by UUID NOT NULL REFERENCES users(id),
+                        price_per_unit DECIMAL(10, 2) NOT NULL CHECK (price_per_unit > 0),
+                        quantity INTEGER NOT NULL CHECK (quantity > 0),
+                        status offer_status NOT NULL DEFAULT 'pending',
+                        parent_offer_id UUID REFERENCES offers(id),
+                        order_id UUID REFERENCES orders(id),
+                        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_offers_buyer ON offers(buyer_id);
+CREATE INDEX idx_offers_seller ON offers(seller_id);
+CREATE INDEX idx_offers_product ON offers(product_id);
@@ -0,0 +1,25 @@
+-- migrations/012_addresses.sql
+-- Delivery address book; orders keep a snapshot of theirs
+CREATE TABLE addresses (
+                           id UUID PRIMARY KEY,
+                           user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                           label VARCHAR(50),
+                           recipient_name VARCHAR(255) NOT NULL,
+                           phone VARCHAR(20) NOT NULL,
+                           line1 VARCHAR(255) NOT NULL,
+                           line2 VARCHAR(255),
+                           city VARCHAR(100) NOT NULL,
+                           state VARCHAR(100) NOT NULL,
+                           postal_code VARCHAR(20) NOT NULL,
+                           country VARCHAR(2) NOT NULL DEFAULT 'IN',
+                           is_default BOOLEAN NOT NULL DEFAULT FALSE,
+                           created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                           updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_addresses_user ON addresses(user_id);
+-- At most one default address per user
+CREATE UNIQUE INDEX idx_addresses_user_default ON addresses(user_id) WHERE is_default;
+
+-- Existing orders predate addresses and keep NULL
+ALTER TABLE orders ADD COLUMN shipping_address JSONB;
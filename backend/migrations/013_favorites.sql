@@ -0,0 +1,9 @@
+-- migrations/013_favorites.sql
+CREATE TABLE favorites (
+                           user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                           product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                           created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                           PRIMARY KEY (user_id, product_id)
+);
+
+CREATE INDEX idx_favorites_product ON favorites(product_id);
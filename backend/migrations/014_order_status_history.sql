@@ -0,0 +1,19 @@
+-- migrations/014_order_status_history.sql
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'cancelled';
+
+-- Every status an order has been in; from_status is NULL for the initial row
+CREATE TABLE order_status_history (
+                                      id UUID PRIMARY KEY,
+                                      order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+                                      from_status order_status,
+                                      to_status order_status NOT NULL,
+                                      -- NULL when the change came from the system (e.g. a payment webhook)
+                                      changed_by UUID REFERENCES users(id),
+                                      changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_order_status_history_order ON order_status_history(order_id, changed_at);
+
+-- Existing orders start their history at their current status
+INSERT INTO order_status_history (id, order_id, from_status, to_status, changed_at)
+SELECT gen_random_uuid(), id, NULL, status, created_at FROM orders;
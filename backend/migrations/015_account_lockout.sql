@@ -0,0 +1,8 @@
+-- migrations/015_account_lockout.sql
+-- Consecutive failed logins per account
+CREATE TABLE login_failures (
+                                user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                                failed_attempts INTEGER NOT NULL DEFAULT 0,
+                                last_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                                locked_until TIMESTAMPTZ
+);
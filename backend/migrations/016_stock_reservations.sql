@@ -0,0 +1,11 @@
+-- migrations/016_stock_reservations.sql
+-- Time-limited holds on stock for items sitting in a cart
+CREATE TABLE stock_reservations (
+                                    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                                    product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                                    quantity INTEGER NOT NULL CHECK (quantity > 0),
+                                    expires_at TIMESTAMPTZ NOT NULL,
+                                    PRIMARY KEY (user_id, product_id)
+);
+
+CREATE INDEX idx_stock_reservations_product ON stock_reservations(product_id, expires_at);
@@ -0,0 +1,30 @@
+-- migrations/017_coupons.sql
+-- Seller-scoped discount codes
+CREATE TYPE discount_type AS ENUM ('percentage', 'fixed');
+
+CREATE TABLE coupons (
+                         id UUID PRIMARY KEY,
+                         seller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                         code VARCHAR(32) UNIQUE NOT NULL,
+                         discount_type discount_type NOT NULL,
+                         discount_value DECIMAL(10, 2) NOT NULL CHECK (discount_value > 0),
+                         min_order_value DECIMAL(10, 2),
+                         max_uses_per_user INTEGER CHECK (max_uses_per_user > 0),
+                         expires_at TIMESTAMPTZ,
+                         is_active BOOLEAN NOT NULL DEFAULT TRUE,
+                         created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                         CHECK (discount_type <> 'percentage' OR discount_value <= 100)
+);
+
+CREATE INDEX idx_coupons_seller ON coupons(seller_id);
+
+-- The coupon the buyer applied to their cart, used at checkout
+ALTER TABLE carts ADD COLUMN coupon_id UUID REFERENCES coupons(id) ON DELETE SET NULL;
+
+ALTER TABLE orders ADD COLUMN coupon_id UUID REFERENCES coupons(id) ON DELETE SET NULL;
+ALTER TABLE orders ADD COLUMN discount_amount DECIMAL(10, 2) NOT NULL DEFAULT 0;
+ALTER TABLE orders ADD COLUMN subtotal_price DECIMAL(10, 2);
+UPDATE orders SET subtotal_price = total_price;
+ALTER TABLE orders ALTER COLUMN subtotal_price SET NOT NULL;
+
+CREATE INDEX idx_orders_coupon_buyer ON orders(coupon_id, buyer_id) WHERE coupon_id IS NOT NULL;
@@ -0,0 +1,3 @@
+-- migrations/018_user_presence.sql
+-- When the user's last WebSocket connection closed
+ALTER TABLE users ADD COLUMN last_seen_at TIMESTAMPTZ;
@@ -0,0 +1,16 @@
+-- migrations/019_category_hierarchy.sql
+-- Categories form a tree; a category with products or subcategories can't be deleted.
+ALTER TABLE categories ADD COLUMN parent_id INTEGER REFERENCES categories(id) ON DELETE RESTRICT;
+
+CREATE INDEX idx_categories_parent ON categories(parent_id);
+
+-- The category and all of its descendants
+CREATE OR REPLACE FUNCTION category_subtree(root INTEGER)
+RETURNS SETOF INTEGER AS $$
+    WITH RECURSIVE subtree AS (
+        SELECT id FROM categories WHERE id = root
+        UNION
+        SELECT c.id FROM categories c JOIN subtree s ON c.parent_id = s.id
+    )
+    SELECT id FROM subtree
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,9 @@
+-- migrations/020_email_changes.sql
+-- A pending switch to a new email address, one per user
+CREATE TABLE email_changes (
+                               user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                               new_email VARCHAR(255) NOT NULL,
+                               token VARCHAR(8) NOT NULL,
+                               expires_at TIMESTAMPTZ NOT NULL,
+                               created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
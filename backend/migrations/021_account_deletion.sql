@@ -0,0 +1,3 @@
+-- migrations/021_account_deletion.sql
+-- Deleted accounts are anonymized in place
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ;
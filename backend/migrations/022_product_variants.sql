@@ -0,0 +1,51 @@
+-- migrations/022_product_variants.sql
+-- Pack sizes, units or grades of a product, each with its own price and stock
+CREATE TABLE product_variants (
+                                  id UUID PRIMARY KEY,
+                                  product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                                  name VARCHAR(100) NOT NULL,
+                                  price_per_unit DECIMAL(10, 2) NOT NULL CHECK (price_per_unit > 0),
+                                  stock_qty INTEGER NOT NULL DEFAULT 0 CHECK (stock_qty >= 0),
+                                  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                                  UNIQUE(product_id, name)
+);
+
+CREATE INDEX idx_product_variants_product ON product_variants(product_id);
+
+CREATE OR REPLACE FUNCTION sync_product_stock_from_variants()
+RETURNS TRIGGER AS $$
+DECLARE
+    changed_product UUID := COALESCE(NEW.product_id, OLD.product_id);
+BEGIN
+UPDATE products
+SET stock_qty = (SELECT COALESCE(SUM(stock_qty), 0) FROM product_variants WHERE product_id = changed_product)
+WHERE id = changed_product;
+RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_sync_product_stock_from_variants
+    AFTER INSERT OR UPDATE OF stock_qty OR DELETE ON product_variants
+    FOR EACH ROW
+    EXECUTE FUNCTION sync_product_stock_from_variants();
+
+-- Cart lines, holds and template lines are per product and variant. variant_id is
+-- NULL for products without variants, so uniqueness goes through COALESCE.
+ALTER TABLE cart_items ADD COLUMN variant_id UUID REFERENCES product_variants(id) ON DELETE CASCADE;
+ALTER TABLE cart_items DROP CONSTRAINT cart_items_pkey;
+CREATE UNIQUE INDEX idx_cart_items_line
+    ON cart_items(user_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'::uuid));
+
+ALTER TABLE stock_reservations ADD COLUMN variant_id UUID REFERENCES product_variants(id) ON DELETE CASCADE;
+ALTER TABLE stock_reservations DROP CONSTRAINT stock_reservations_pkey;
+CREATE UNIQUE INDEX idx_stock_reservations_line
+    ON stock_reservations(user_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'::uuid));
+
+ALTER TABLE cart_template_items ADD COLUMN variant_id UUID REFERENCES product_variants(id) ON DELETE CASCADE;
+ALTER TABLE cart_template_items DROP CONSTRAINT cart_template_items_pkey;
+CREATE UNIQUE INDEX idx_cart_template_items_line
+    ON cart_template_items(template_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'::uuid));
+
+-- Order items keep the variant's name in case the variant is later removed
+ALTER TABLE order_items ADD COLUMN variant_id UUID REFERENCES product_variants(id) ON DELETE SET NULL;
+ALTER TABLE order_items ADD COLUMN variant_name VARCHAR(100);
@@ -0,0 +1,27 @@
+-- migrations/023_two_factor.sql
+-- Optional TOTP two-factor authentication with recovery codes
+CREATE TABLE user_totp (
+                           user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                           secret VARCHAR(64) NOT NULL,
+                           enabled_at TIMESTAMPTZ,
+                           last_used_step BIGINT, -- time step of the last accepted code, so none is replayed
+                           created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE totp_recovery_codes (
+                                     id UUID PRIMARY KEY,
+                                     user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                                     code_hash VARCHAR(64) NOT NULL,
+                                     used_at TIMESTAMPTZ,
+                                     UNIQUE(user_id, code_hash)
+);
+
+-- Password-verified logins waiting for their second factor
+CREATE TABLE login_challenges (
+                                  id UUID PRIMARY KEY,
+                                  user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                                  expires_at TIMESTAMPTZ NOT NULL,
+                                  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_login_challenges_user ON login_challenges(user_id);
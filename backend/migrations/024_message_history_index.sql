@@ -0,0 +1,3 @@
+-- migrations/024_message_history_index.sql
+-- Message history is paged newest-first by (sent_at, id) within a conversation
+CREATE INDEX idx_messages_conv_history ON messages(conv_id, sent_at DESC, id DESC);
@@ -0,0 +1,11 @@
+-- migrations/025_cart_price_snapshot.sql
+-- The unit price a cart line was added at, so a price change can be shown before checkout
+ALTER TABLE cart_items ADD COLUMN price_when_added DECIMAL(10, 2);
+
+UPDATE cart_items ci
+SET price_when_added = COALESCE(
+    (SELECT v.price_per_unit FROM product_variants v WHERE v.id = ci.variant_id),
+    (SELECT p.price_per_unit FROM products p WHERE p.id = ci.product_id)
+);
+
+ALTER TABLE cart_items ALTER COLUMN price_when_added SET NOT NULL;
@@ -0,0 +1,5 @@
+-- migrations/026_product_soft_delete.sql
+-- Deleted products are hidden and restorable for 30 days
+ALTER TABLE products ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX idx_products_deleted_at ON products(deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,42 @@
+-- migrations/027_disputes.sql
+-- Buyer disputes over paid or delivered orders
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'disputed';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'refunded';
+
+CREATE TYPE dispute_status AS ENUM ('open', 'responded', 'refunded', 'rejected');
+
+CREATE TABLE disputes (
+                          id UUID PRIMARY KEY,
+                          order_id UUID NOT NULL UNIQUE REFERENCES orders(id) ON DELETE CASCADE,
+                          buyer_id UUID NOT NULL REFERENCES users(id),
+                          seller_id UUID NOT NULL REFERENCES users(id),
+                          -- Where the order goes back to if the dispute is rejected
+                          order_status_before order_status NOT NULL,
+                          reason TEXT NOT NULL,
+                          evidence_urls TEXT[] NOT NULL DEFAULT '{}',
+                          status dispute_status NOT NULL DEFAULT 'open',
+                          seller_response TEXT,
+                          seller_responded_at TIMESTAMPTZ,
+                          resolution_note TEXT,
+                          resolved_by UUID REFERENCES users(id),
+                          resolved_at TIMESTAMPTZ,
+                          created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_disputes_status ON disputes(status, created_at);
+
+-- Money returned to buyers; one row per refund, with the provider's id when it went
+-- back through the payment provider
+CREATE TABLE refunds (
+                         id UUID PRIMARY KEY,
+                         order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+                         dispute_id UUID REFERENCES disputes(id) ON DELETE SET NULL,
+                         amount DECIMAL(10, 2) NOT NULL CHECK (amount > 0),
+                         provider VARCHAR(20),
+                         provider_refund_id VARCHAR(255) UNIQUE,
+                         restocked BOOLEAN NOT NULL DEFAULT FALSE,
+                         created_by UUID REFERENCES users(id),
+                         created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_refunds_order ON refunds(order_id);
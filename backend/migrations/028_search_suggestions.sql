@@ -0,0 +1,7 @@
+-- migrations/028_search_suggestions.sql
+-- Trigram indexes for type-ahead suggestions
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_products_name_trgm ON products USING GIN (name gin_trgm_ops)
+    WHERE taken_down_at IS NULL AND deleted_at IS NULL;
+CREATE INDEX idx_categories_name_trgm ON categories USING GIN (name gin_trgm_ops);
@@ -0,0 +1,28 @@
+-- migrations/030_order_item_fulfillment.sql
+-- Shipping and delivering an order a few lines at a time
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'partially_shipped';
+
+CREATE TYPE fulfillment_status AS ENUM ('unfulfilled', 'shipped', 'delivered');
+
+ALTER TABLE order_items
+    ADD COLUMN fulfillment_status fulfillment_status NOT NULL DEFAULT 'unfulfilled',
+    ADD COLUMN shipped_at TIMESTAMPTZ,
+    ADD COLUMN delivered_at TIMESTAMPTZ;
+
+-- Existing orders shipped or delivered as a whole; a disputed one as it was before the dispute
+WITH fulfilled AS (
+    SELECT o.id,
+           COALESCE(d.order_status_before, o.status)::text as status,
+           (SELECT MAX(h.changed_at) FROM order_status_history h
+            WHERE h.order_id = o.id AND h.to_status = 'shipped') as shipped_at,
+           (SELECT MAX(h.changed_at) FROM order_status_history h
+            WHERE h.order_id = o.id AND h.to_status = 'delivered') as delivered_at
+    FROM orders o
+    LEFT JOIN disputes d ON d.order_id = o.id AND o.status IN ('disputed', 'refunded')
+)
+UPDATE order_items oi
+SET fulfillment_status = f.status::fulfillment_status,
+    shipped_at = COALESCE(f.shipped_at, f.delivered_at, NOW()),
+    delivered_at = CASE WHEN f.status = 'delivered' THEN COALESCE(f.delivered_at, NOW()) END
+FROM fulfilled f
+WHERE oi.order_id = f.id AND f.status IN ('shipped', 'delivered');
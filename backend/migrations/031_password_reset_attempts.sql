@@ -0,0 +1,9 @@
+-- migrations/031_password_reset_attempts.sql
+-- Hashed reset codes with a limit on wrong guesses
+DELETE FROM password_resets;
+
+ALTER TABLE password_resets
+    DROP COLUMN otp_code,
+    ADD COLUMN otp_hash VARCHAR(64) NOT NULL,
+    ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
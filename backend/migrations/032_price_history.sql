@@ -0,0 +1,13 @@
+-- migrations/032_price_history.sql
+-- Every change to a product's or a variant's price
+CREATE TABLE price_history (
+                               id UUID PRIMARY KEY,
+                               product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                               variant_id UUID REFERENCES product_variants(id) ON DELETE CASCADE,
+                               old_price DECIMAL(10,2) NOT NULL,
+                               new_price DECIMAL(10,2) NOT NULL,
+                               changed_by UUID NOT NULL REFERENCES users(id),
+                               changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_price_history_product ON price_history(product_id, changed_at DESC);
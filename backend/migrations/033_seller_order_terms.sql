@@ -0,0 +1,7 @@
+-- migrations/033_seller_order_terms.sql
+-- Sellers' minimum order value and delivery fee
+ALTER TABLE users
+    ADD COLUMN min_order_value DECIMAL(10, 2) NOT NULL DEFAULT 0 CHECK (min_order_value >= 0),
+    ADD COLUMN delivery_fee DECIMAL(10, 2) NOT NULL DEFAULT 0 CHECK (delivery_fee >= 0);
+
+ALTER TABLE orders ADD COLUMN delivery_fee DECIMAL(10, 2) NOT NULL DEFAULT 0;
@@ -0,0 +1,17 @@
+-- migrations/034_message_attachments.sql
+-- Image attachments on chat messages
+CREATE TABLE message_attachments (
+                                     id UUID PRIMARY KEY,
+                                     conv_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+                                     uploader_id UUID NOT NULL REFERENCES users(id),
+                                     url TEXT NOT NULL UNIQUE,
+                                     attachment_type VARCHAR(20) NOT NULL CHECK (attachment_type IN ('image')),
+                                     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_message_attachments_conv ON message_attachments(conv_id);
+
+ALTER TABLE messages
+    ADD COLUMN attachment_url TEXT,
+    ADD COLUMN attachment_type VARCHAR(20),
+    ADD CONSTRAINT messages_attachment_complete CHECK ((attachment_url IS NULL) = (attachment_type IS NULL));
@@ -0,0 +1,15 @@
+-- migrations/035_api_tokens.sql
+-- Personal access tokens, stored hashed
+CREATE TABLE api_tokens (
+                            id UUID PRIMARY KEY,
+                            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                            name VARCHAR(100) NOT NULL,
+                            token_hash VARCHAR(64) NOT NULL UNIQUE,
+                            token_prefix VARCHAR(16) NOT NULL,
+                            scopes TEXT[] NOT NULL,
+                            expires_at TIMESTAMPTZ,
+                            last_used_at TIMESTAMPTZ,
+                            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_api_tokens_user ON api_tokens(user_id, created_at DESC);
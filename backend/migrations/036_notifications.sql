@@ -0,0 +1,16 @@
+-- migrations/036_notifications.sql
+-- Each user's notification inbox
+CREATE TYPE notification_kind AS ENUM ('order', 'message', 'offer', 'low_stock');
+
+CREATE TABLE notifications (
+                               id UUID PRIMARY KEY,
+                               user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                               kind notification_kind NOT NULL,
+                               title VARCHAR(255) NOT NULL,
+                               data JSONB NOT NULL DEFAULT '{}',
+                               read_at TIMESTAMPTZ,
+                               created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notifications_user ON notifications(user_id, created_at DESC);
+CREATE INDEX idx_notifications_unread ON notifications(user_id) WHERE read_at IS NULL;
@@ -0,0 +1,18 @@
+-- migrations/037_inventory_movements.sql
+-- Every change to a product's or a variant's stock, and why
+CREATE TYPE inventory_reason AS ENUM ('order', 'cancellation', 'adjustment', 'import');
+
+CREATE TABLE inventory_movements (
+                                     id UUID PRIMARY KEY,
+                                     product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                                     variant_id UUID REFERENCES product_variants(id) ON DELETE CASCADE,
+                                     quantity_change INTEGER NOT NULL,
+                                     stock_after INTEGER NOT NULL,
+                                     reason inventory_reason NOT NULL,
+                                     note TEXT,
+                                     order_id UUID REFERENCES orders(id),
+                                     actor_id UUID REFERENCES users(id),
+                                     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_inventory_movements_product ON inventory_movements(product_id, created_at DESC);
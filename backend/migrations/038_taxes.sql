@@ -0,0 +1,31 @@
+-- migrations/038_taxes.sql
+-- Sales tax: sellers' tax ids, category rates and tax on order items
+ALTER TABLE users
+    ADD COLUMN tax_id VARCHAR(32),
+    ADD COLUMN tax_name VARCHAR(255);
+
+ALTER TABLE categories ADD COLUMN tax_rate DECIMAL(5, 2) CHECK (tax_rate >= 0 AND tax_rate <= 100);
+
+ALTER TABLE orders
+    ADD COLUMN tax_amount DECIMAL(10, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN seller_tax_id VARCHAR(32),
+    ADD COLUMN seller_tax_name VARCHAR(255);
+
+ALTER TABLE order_items
+    ADD COLUMN tax_rate DECIMAL(5, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN tax_amount DECIMAL(10, 2) NOT NULL DEFAULT 0;
+
+-- The rate that applies to products in the category
+CREATE OR REPLACE FUNCTION category_tax_rate(category INTEGER)
+RETURNS DECIMAL(5, 2) AS $$
+    WITH RECURSIVE ancestors AS (
+        SELECT id, parent_id, tax_rate, 0 AS depth FROM categories WHERE id = category
+        UNION ALL
+        SELECT c.id, c.parent_id, c.tax_rate, a.depth + 1
+        FROM categories c JOIN ancestors a ON c.id = a.parent_id
+    )
+    SELECT COALESCE(
+        (SELECT tax_rate FROM ancestors WHERE tax_rate IS NOT NULL ORDER BY depth LIMIT 1),
+        0
+    )
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,10 @@
+-- migrations/039_ws_tickets.sql
+-- One-time tickets for opening a WebSocket without the session cookie
+CREATE TABLE ws_tickets (
+                            token_hash VARCHAR(64) PRIMARY KEY,
+                            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                            expires_at TIMESTAMPTZ NOT NULL,
+                            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_ws_tickets_user ON ws_tickets(user_id);
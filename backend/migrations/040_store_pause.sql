@@ -0,0 +1,3 @@
+-- migrations/040_store_pause.sql
+-- Vacation mode for sellers
+ALTER TABLE users ADD COLUMN store_paused BOOLEAN NOT NULL DEFAULT FALSE;
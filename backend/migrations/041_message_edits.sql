@@ -0,0 +1,5 @@
+-- migrations/041_message_edits.sql
+-- Editing and deleting sent chat messages
+ALTER TABLE messages
+    ADD COLUMN edited_at TIMESTAMPTZ,
+    ADD COLUMN deleted_at TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+-- migrations/042_currencies.sql
+-- Product and order currencies
+ALTER TABLE products ADD COLUMN currency VARCHAR(3) NOT NULL DEFAULT 'INR' CHECK (currency ~ '^[A-Z]{3}$');
+
+ALTER TABLE orders ADD COLUMN currency VARCHAR(3) NOT NULL DEFAULT 'INR' CHECK (currency ~ '^[A-Z]{3}$');
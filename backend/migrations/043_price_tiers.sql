@@ -0,0 +1,24 @@
+-- migrations/043_price_tiers.sql
+-- Quantity price tiers per product
+CREATE TABLE price_tiers (
+    product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    min_qty INTEGER NOT NULL CHECK (min_qty > 1),
+    unit_price DECIMAL(10, 2) NOT NULL CHECK (unit_price > 0),
+    PRIMARY KEY (product_id, min_qty)
+);
+
+-- What each unit costs when buying `quantity` of the product, or of its variant
+CREATE OR REPLACE FUNCTION unit_price(product UUID, variant UUID, quantity INTEGER)
+RETURNS DECIMAL(10, 2) AS $$
+    SELECT COALESCE(
+        (SELECT v.price_per_unit FROM product_variants v WHERE v.id = variant),
+        (SELECT LEAST(
+                    p.price_per_unit,
+                    (SELECT t.unit_price FROM price_tiers t
+                     WHERE t.product_id = p.id AND t.min_qty <= quantity
+                     ORDER BY t.min_qty DESC
+                     LIMIT 1)
+                )
+         FROM products p WHERE p.id = product)
+    )
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,31 @@
+-- migrations/044_blocks_and_reports.sql
+-- Blocking and reporting users
+CREATE TABLE user_blocks (
+    blocker_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    blocked_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (blocker_id, blocked_id),
+    CHECK (blocker_id <> blocked_id)
+);
+
+CREATE INDEX idx_user_blocks_blocked ON user_blocks(blocked_id);
+
+CREATE TYPE report_status AS ENUM ('open', 'dismissed', 'actioned');
+
+CREATE TABLE user_reports (
+    id UUID PRIMARY KEY,
+    reporter_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reported_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    conv_id UUID REFERENCES conversations(id) ON DELETE SET NULL,
+    -- [{id, sender_id, content, attachment_url, sent_at, edited_at, deleted_at}], oldest first
+    message_context JSONB NOT NULL DEFAULT '[]',
+    status report_status NOT NULL DEFAULT 'open',
+    resolution_note TEXT,
+    resolved_by UUID REFERENCES users(id),
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (reporter_id <> reported_id)
+);
+
+CREATE INDEX idx_user_reports_status ON user_reports(status, created_at);
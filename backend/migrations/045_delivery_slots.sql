@@ -0,0 +1,17 @@
+-- migrations/045_delivery_slots.sql
+-- Delivery windows sellers offer and orders book
+CREATE TABLE delivery_slots (
+    id UUID PRIMARY KEY,
+    seller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    capacity INTEGER NOT NULL CHECK (capacity > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX idx_delivery_slots_seller ON delivery_slots(seller_id, starts_at);
+
+ALTER TABLE orders ADD COLUMN delivery_slot_id UUID REFERENCES delivery_slots(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_orders_delivery_slot ON orders(delivery_slot_id) WHERE delivery_slot_id IS NOT NULL;
@@ -0,0 +1,13 @@
+-- migrations/046_product_review.sql
+-- Admin review of new listings
+CREATE TYPE product_review_status AS ENUM ('pending_review', 'approved', 'rejected');
+
+ALTER TABLE products
+    ADD COLUMN review_status product_review_status NOT NULL DEFAULT 'approved',
+    ADD COLUMN review_reason TEXT,
+    ADD COLUMN reviewed_by UUID REFERENCES users(id),
+    ADD COLUMN reviewed_at TIMESTAMPTZ;
+
+CREATE INDEX idx_products_pending_review ON products(created_at) WHERE review_status = 'pending_review';
+
+ALTER TYPE notification_kind ADD VALUE 'product_review';
@@ -0,0 +1,26 @@
+-- migrations/047_seller_ledger.sql
+-- What the platform owes each seller
+CREATE TYPE ledger_entry_kind AS ENUM ('sale', 'refund', 'payout');
+
+CREATE TABLE ledger_entries (
+    id UUID PRIMARY KEY,
+    seller_id UUID NOT NULL REFERENCES users(id),
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    kind ledger_entry_kind NOT NULL,
+    -- The order total for sales and refunds, before commission
+    gross_amount DECIMAL(10, 2) NOT NULL,
+    commission_amount DECIMAL(10, 2) NOT NULL DEFAULT 0,
+    amount DECIMAL(10, 2) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    -- A payout's bank or transfer reference
+    reference TEXT,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (kind <> 'payout' OR amount < 0)
+);
+
+-- An order is credited and reversed at most once
+CREATE UNIQUE INDEX idx_ledger_entries_order ON ledger_entries(order_id, kind) WHERE order_id IS NOT NULL;
+CREATE INDEX idx_ledger_entries_seller ON ledger_entries(seller_id, created_at);
+
+ALTER TYPE notification_kind ADD VALUE 'payout';
@@ -0,0 +1,19 @@
+-- migrations/048_push_devices.sql
+-- Devices registered for push notifications, and users' push preferences
+CREATE TYPE push_platform AS ENUM ('android', 'ios', 'web');
+
+CREATE TABLE push_devices (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    platform push_platform NOT NULL,
+    -- A token belongs to one device, so registering it again moves it to the new user
+    token TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_push_devices_user ON push_devices(user_id);
+
+ALTER TABLE users
+    ADD COLUMN push_messages BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN push_order_updates BOOLEAN NOT NULL DEFAULT TRUE;
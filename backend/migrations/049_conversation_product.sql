@@ -0,0 +1,4 @@
+-- migrations/049_conversation_product.sql
+-- The listing a conversation is about
+ALTER TABLE conversations
+    ADD COLUMN product_id UUID REFERENCES products(id) ON DELETE SET NULL;
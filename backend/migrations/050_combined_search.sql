@@ -0,0 +1,13 @@
+-- migrations/050_combined_search.sql
+-- Search over seller and category names
+ALTER TABLE users
+    ADD COLUMN search_vector tsvector
+        GENERATED ALWAYS AS (to_tsvector('simple', COALESCE(name, ''))) STORED;
+
+ALTER TABLE categories
+    ADD COLUMN search_vector tsvector
+        GENERATED ALWAYS AS (to_tsvector('english', name)) STORED;
+
+CREATE INDEX idx_users_search_vector ON users USING GIN (search_vector) WHERE is_supplier;
+CREATE INDEX idx_users_name_trgm ON users USING GIN (name gin_trgm_ops) WHERE is_supplier;
+CREATE INDEX idx_categories_search_vector ON categories USING GIN (search_vector);
@@ -0,0 +1,40 @@
+-- migrations/051_recurring_orders.sql
+-- Standing orders and their runs
+CREATE TYPE recurring_order_status AS ENUM ('active', 'paused', 'cancelled');
+
+CREATE TABLE recurring_orders (
+    id UUID PRIMARY KEY,
+    buyer_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    seller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Runs fail until the buyer picks another address if this one is deleted
+    address_id UUID REFERENCES addresses(id) ON DELETE SET NULL,
+    interval_days INTEGER NOT NULL CHECK (interval_days BETWEEN 1 AND 90),
+    status recurring_order_status NOT NULL DEFAULT 'active',
+    next_run_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_recurring_orders_buyer ON recurring_orders(buyer_id);
+CREATE INDEX idx_recurring_orders_due ON recurring_orders(next_run_at) WHERE status = 'active';
+
+CREATE TABLE recurring_order_items (
+    recurring_order_id UUID NOT NULL REFERENCES recurring_orders(id) ON DELETE CASCADE,
+    product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    variant_id UUID REFERENCES product_variants(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0)
+);
+
+CREATE UNIQUE INDEX idx_recurring_order_items_line
+    ON recurring_order_items(recurring_order_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'));
+
+-- One per cycle: the order placed, or why there wasn't one
+CREATE TABLE recurring_order_runs (
+    id UUID PRIMARY KEY,
+    recurring_order_id UUID NOT NULL REFERENCES recurring_orders(id) ON DELETE CASCADE,
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(recurring_order_id, scheduled_for)
+);
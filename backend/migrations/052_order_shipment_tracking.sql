@@ -0,0 +1,19 @@
+-- migrations/052_order_shipment_tracking.sql
+-- Courier, tracking number and expected delivery of shipped orders
+ALTER TABLE orders
+    ADD COLUMN courier_name VARCHAR(100),
+    ADD COLUMN tracking_number VARCHAR(100),
+    ADD COLUMN expected_delivery_date DATE;
+
+CREATE TABLE order_shipment_updates (
+    id UUID PRIMARY KEY,
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    -- The shipment details as they stood after this update
+    courier_name VARCHAR(100),
+    tracking_number VARCHAR(100),
+    expected_delivery_date DATE,
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_order_shipment_updates_order ON order_shipment_updates(order_id, created_at);
@@ -0,0 +1,37 @@
+-- migrations/053_audit_log.sql
+-- Append-only audit log
+CREATE TYPE audit_action AS ENUM (
+    'login',
+    'login_failed',
+    'password_changed',
+    'password_reset_requested',
+    'password_reset',
+    'email_changed',
+    'two_factor_enabled',
+    'two_factor_disabled',
+    'settings_updated',
+    'role_changed',
+    'user_suspended',
+    'user_unsuspended',
+    'user_unlocked',
+    'product_price_changed',
+    'product_stock_changed',
+    'order_status_changed'
+);
+
+CREATE TABLE audit_log (
+    id UUID PRIMARY KEY,
+    -- Who did it; NULL for the system (the payment provider, the scheduler) or a
+    -- request made without a login, such as a password reset
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    action audit_action NOT NULL,
+    -- What it was done to: the user, product or order the action names
+    subject_id UUID,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_log_created ON audit_log(created_at DESC);
+CREATE INDEX idx_audit_log_actor ON audit_log(actor_id, created_at DESC);
+CREATE INDEX idx_audit_log_subject ON audit_log(subject_id, created_at DESC);
+CREATE INDEX idx_audit_log_action ON audit_log(action, created_at DESC);
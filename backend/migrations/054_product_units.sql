@@ -0,0 +1,7 @@
+-- migrations/054_product_units.sql
+-- Units of measure and sale increments
+CREATE TYPE product_unit AS ENUM ('piece', 'kg', 'gram', 'litre', 'dozen', 'bunch', 'crate', 'sack');
+
+ALTER TABLE products
+    ADD COLUMN unit product_unit NOT NULL DEFAULT 'piece',
+    ADD COLUMN min_increment INTEGER NOT NULL DEFAULT 1 CHECK (min_increment > 0);
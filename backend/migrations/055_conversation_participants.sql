@@ -0,0 +1,30 @@
+-- migrations/055_conversation_participants.sql
+-- Conversation participants, for group conversations
+ALTER TABLE conversations
+    ALTER COLUMN user1_id DROP NOT NULL,
+    ALTER COLUMN user2_id DROP NOT NULL,
+    ADD COLUMN is_group BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN owner_id UUID REFERENCES users(id),
+    ADD COLUMN title TEXT,
+    ADD CONSTRAINT conversations_pair_or_group CHECK (
+        (is_group AND user1_id IS NULL AND user2_id IS NULL AND owner_id IS NOT NULL)
+        OR (NOT is_group AND user1_id IS NOT NULL AND user2_id IS NOT NULL)
+    );
+
+-- A seller has one broadcast conversation
+CREATE UNIQUE INDEX idx_conversations_group_owner ON conversations(owner_id) WHERE is_group;
+
+CREATE TABLE conversation_participants (
+    conv_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_read_at TIMESTAMPTZ,
+    PRIMARY KEY (conv_id, user_id)
+);
+
+CREATE INDEX idx_conversation_participants_user ON conversation_participants(user_id);
+
+INSERT INTO conversation_participants (conv_id, user_id, joined_at)
+SELECT id, user1_id, last_updated FROM conversations
+UNION ALL
+SELECT id, user2_id, last_updated FROM conversations;
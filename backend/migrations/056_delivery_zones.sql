@@ -0,0 +1,45 @@
+-- migrations/056_delivery_zones.sql
+-- Where each supplier delivers
+CREATE TABLE seller_delivery_pincodes (
+    seller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Upper case without spaces, as normalize_pincode() leaves it
+    pincode VARCHAR(20) NOT NULL,
+    PRIMARY KEY (seller_id, pincode)
+);
+
+CREATE INDEX idx_seller_delivery_pincodes_pincode ON seller_delivery_pincodes(pincode);
+
+ALTER TABLE users
+    ADD COLUMN delivery_radius_km DOUBLE PRECISION CHECK (delivery_radius_km > 0);
+
+ALTER TABLE addresses
+    ADD COLUMN latitude DOUBLE PRECISION,
+    ADD COLUMN longitude DOUBLE PRECISION,
+    ADD CONSTRAINT addresses_location_check CHECK (
+        (latitude IS NULL) = (longitude IS NULL)
+        AND latitude BETWEEN -90 AND 90 AND longitude BETWEEN -180 AND 180
+    );
+
+CREATE OR REPLACE FUNCTION normalize_pincode(pincode TEXT) RETURNS TEXT AS $$
+    SELECT upper(regexp_replace(pincode, '\s', '', 'g'))
+$$ LANGUAGE sql IMMUTABLE STRICT;
+
+-- Whether the seller delivers to this pincode or point. A radius is measured from the
+-- seller's location, so it covers nothing until they set one or without coordinates
+-- to check.
+CREATE OR REPLACE FUNCTION delivers_to(seller_id UUID, pincode TEXT,
+                                       latitude DOUBLE PRECISION, longitude DOUBLE PRECISION)
+RETURNS BOOLEAN AS $$
+    SELECT CASE
+        WHEN u.delivery_radius_km IS NULL
+             AND NOT EXISTS(SELECT 1 FROM seller_delivery_pincodes z WHERE z.seller_id = u.id)
+            THEN TRUE
+        ELSE EXISTS(
+                 SELECT 1 FROM seller_delivery_pincodes z
+                 WHERE z.seller_id = u.id AND z.pincode = normalize_pincode($2)
+             )
+             OR COALESCE(distance_km(u.latitude, u.longitude, $3, $4) <= u.delivery_radius_km, FALSE)
+    END
+    FROM users u
+    WHERE u.id = $1
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,32 @@
+-- migrations/057_order_eta.sql
+-- Suppliers' lead times and orders' promised delivery
+ALTER TABLE users
+    ADD COLUMN lead_time_hours INTEGER NOT NULL DEFAULT 48 CHECK (lead_time_hours BETWEEN 1 AND 720);
+
+ALTER TABLE orders
+    ADD COLUMN expected_delivery_at TIMESTAMPTZ,
+    ADD COLUMN delivered_at TIMESTAMPTZ;
+
+-- When an order placed now with this seller and slot should arrive
+CREATE OR REPLACE FUNCTION order_eta(seller_id UUID, delivery_slot_id UUID) RETURNS TIMESTAMPTZ AS $$
+    SELECT COALESCE(
+        (SELECT ds.ends_at FROM delivery_slots ds WHERE ds.id = $2),
+        NOW() + make_interval(hours => (SELECT u.lead_time_hours FROM users u WHERE u.id = $1))
+    )
+$$ LANGUAGE sql STABLE;
+
+UPDATE orders o
+SET expected_delivery_at = COALESCE(
+    (SELECT ds.ends_at FROM delivery_slots ds WHERE ds.id = o.delivery_slot_id),
+    o.created_at + INTERVAL '48 hours'
+);
+
+UPDATE orders o
+SET delivered_at = (
+    SELECT MAX(h.changed_at) FROM order_status_history h
+    WHERE h.order_id = o.id AND h.to_status = 'delivered'
+)
+WHERE o.status IN ('delivered', 'disputed', 'refunded');
+
+CREATE INDEX idx_orders_seller_expected_delivery ON orders(seller_id, expected_delivery_at)
+    WHERE delivered_at IS NULL;
@@ -0,0 +1,57 @@
+-- migrations/059_rfqs.sql
+-- Requests for quote and suppliers' quotes
+CREATE TYPE rfq_status AS ENUM ('open', 'awarded', 'cancelled');
+
+CREATE TABLE rfqs (
+    id UUID PRIMARY KEY,
+    buyer_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- A product RFQ also carries its product's category, so that its other sellers can quote
+    product_id UUID REFERENCES products(id) ON DELETE SET NULL,
+    category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    target_price DECIMAL(10, 2) CHECK (target_price > 0),
+    note TEXT,
+    status rfq_status NOT NULL DEFAULT 'open',
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_rfqs_buyer ON rfqs(buyer_id, created_at DESC);
+CREATE INDEX idx_rfqs_open ON rfqs(expires_at) WHERE status = 'open';
+
+CREATE TABLE quotes (
+    id UUID PRIMARY KEY,
+    rfq_id UUID NOT NULL REFERENCES rfqs(id) ON DELETE CASCADE,
+    seller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    offer_id UUID NOT NULL UNIQUE REFERENCES offers(id) ON DELETE CASCADE,
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (rfq_id, seller_id)
+);
+
+ALTER TABLE rfqs ADD COLUMN awarded_quote_id UUID REFERENCES quotes(id) ON DELETE SET NULL;
+
+-- Whether the product can be quoted on the RFQ: it's the product asked for, or in the
+-- RFQ's category or one beneath it
+CREATE OR REPLACE FUNCTION rfq_covers(rfq UUID, product UUID) RETURNS BOOLEAN AS $$
+    SELECT EXISTS(
+        SELECT 1
+        FROM rfqs r, products p
+        WHERE r.id = rfq AND p.id = product
+          AND (p.id = r.product_id OR p.category_id IN (SELECT category_subtree(r.category_id)))
+    )
+$$ LANGUAGE SQL STABLE;
+
+-- Whether the seller has a live product the RFQ covers, so sees the RFQ and may quote
+CREATE OR REPLACE FUNCTION rfq_reaches(rfq UUID, seller UUID) RETURNS BOOLEAN AS $$
+    SELECT EXISTS(
+        SELECT 1
+        FROM products p
+        WHERE p.seller_id = seller AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved' AND rfq_covers(rfq, p.id)
+    )
+$$ LANGUAGE SQL STABLE;
+
+ALTER TYPE notification_kind ADD VALUE 'rfq';
@@ -0,0 +1,6 @@
+-- migrations/060_session_lifetimes.sql
+-- Sessions signed in before a user's password was last changed or reset are ended
+ALTER TABLE users ADD COLUMN password_changed_at TIMESTAMPTZ;
+
+-- "Remember me" is carried through the two-factor step of a login
+ALTER TABLE login_challenges ADD COLUMN remember_me BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,26 @@
+-- migrations/061_stock_locations.sql
+-- Suppliers' stock locations and the stock on hand at each
+CREATE TABLE locations (
+    id UUID PRIMARY KEY,
+    seller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    address TEXT,
+    -- Where orders ship from unless the seller picks another location
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_locations_seller_name ON locations(seller_id, LOWER(name));
+CREATE UNIQUE INDEX idx_locations_seller_default ON locations(seller_id) WHERE is_default;
+
+CREATE TABLE stock_by_location (
+    location_id UUID NOT NULL REFERENCES locations(id) ON DELETE CASCADE,
+    product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity >= 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (location_id, product_id)
+);
+
+CREATE INDEX idx_stock_by_location_product ON stock_by_location(product_id);
+
+ALTER TABLE order_items ADD COLUMN fulfilled_from_location_id UUID REFERENCES locations(id) ON DELETE SET NULL;
@@ -0,0 +1,11 @@
+-- migrations/062_order_comments.sql
+-- Notes between an order's buyer and seller
+CREATE TABLE order_comments (
+    id UUID PRIMARY KEY,
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    body TEXT NOT NULL CHECK (length(body) BETWEEN 1 AND 1000),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_order_comments_order ON order_comments(order_id, created_at);
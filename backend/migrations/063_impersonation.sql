@@ -0,0 +1,5 @@
+-- migrations/063_impersonation.sql
+-- Admin impersonation of users
+ALTER TYPE audit_action ADD VALUE 'impersonation_started';
+ALTER TYPE audit_action ADD VALUE 'impersonation_stopped';
+ALTER TYPE audit_action ADD VALUE 'impersonated_request';
@@ -0,0 +1,10 @@
+-- migrations/064_product_codes.sql
+-- Seller SKUs and barcodes on products
+ALTER TABLE products ADD COLUMN sku VARCHAR(64);
+ALTER TABLE products ADD COLUMN barcode VARCHAR(64);
+
+CREATE UNIQUE INDEX idx_products_seller_sku ON products(seller_id, LOWER(sku)) WHERE sku IS NOT NULL;
+CREATE UNIQUE INDEX idx_products_seller_barcode ON products(seller_id, barcode) WHERE barcode IS NOT NULL;
+
+-- Scanning looks a barcode up across every seller
+CREATE INDEX idx_products_barcode ON products(barcode) WHERE barcode IS NOT NULL;
@@ -0,0 +1,54 @@
+-- migrations/065_deals.sql
+-- Flash deals on products
+CREATE TABLE deals (
+    id UUID PRIMARY KEY,
+    product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    deal_price DECIMAL(10, 2) NOT NULL CHECK (deal_price > 0),
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    max_quantity INTEGER NOT NULL CHECK (max_quantity > 0),
+    -- Units ordered at the deal price; checkout claims them under the product's row lock
+    sold_quantity INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at > starts_at),
+    CHECK (sold_quantity BETWEEN 0 AND max_quantity)
+);
+
+CREATE INDEX idx_deals_product ON deals(product_id, starts_at);
+CREATE INDEX idx_deals_ends ON deals(ends_at);
+
+-- The product's deal on now with units left, as products show it
+CREATE OR REPLACE FUNCTION current_deal(product UUID)
+RETURNS JSON AS $$
+    SELECT json_build_object(
+        'id', d.id,
+        'deal_price', d.deal_price::text,
+        'ends_at', d.ends_at,
+        'remaining', d.max_quantity - d.sold_quantity
+    )
+    FROM deals d
+    WHERE d.product_id = product AND d.starts_at <= NOW() AND d.ends_at > NOW()
+      AND d.sold_quantity < d.max_quantity
+    LIMIT 1
+$$ LANGUAGE sql STABLE;
+
+-- As before, but a deal on now with at least `quantity` units left can beat the list
+-- and tier prices. A line bigger than what's left of a deal pays the usual price.
+CREATE OR REPLACE FUNCTION unit_price(product UUID, variant UUID, quantity INTEGER)
+RETURNS DECIMAL(10, 2) AS $$
+    SELECT COALESCE(
+        (SELECT v.price_per_unit FROM product_variants v WHERE v.id = variant),
+        (SELECT LEAST(
+                    p.price_per_unit,
+                    (SELECT t.unit_price FROM price_tiers t
+                     WHERE t.product_id = p.id AND t.min_qty <= quantity
+                     ORDER BY t.min_qty DESC
+                     LIMIT 1),
+                    (SELECT d.deal_price FROM deals d
+                     WHERE d.product_id = p.id AND d.starts_at <= NOW() AND d.ends_at > NOW()
+                       AND d.max_quantity - d.sold_quantity >= quantity
+                     LIMIT 1)
+                )
+         FROM products p WHERE p.id = product)
+    )
+$$ LANGUAGE sql STABLE;
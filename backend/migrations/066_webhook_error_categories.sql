@@ -0,0 +1,6 @@
+-- migrations/066_webhook_error_categories.sql
+-- Keep only the category of failed webhook attempts
+UPDATE webhook_deliveries
+SET last_error = CASE WHEN last_response_status IS NULL THEN 'request_failed' ELSE 'unexpected_status' END
+WHERE last_error IS NOT NULL
+  AND last_error NOT IN ('unexpected_status', 'timeout', 'connection_failed', 'blocked_address', 'request_failed');
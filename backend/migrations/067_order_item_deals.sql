@@ -0,0 +1,23 @@
+-- migrations/067_order_item_deals.sql
+-- The deal each order line was bought at, so cancelling it gives the units back
+ALTER TABLE order_items ADD COLUMN deal_id UUID REFERENCES deals(id) ON DELETE SET NULL;
+
+-- The deal that sets unit_price for the line, if any: one on now with units enough left
+-- that undercuts the list and tier prices. Variants aren't sold at deal prices.
+CREATE OR REPLACE FUNCTION unit_deal(product UUID, variant UUID, quantity INTEGER)
+RETURNS UUID AS $$
+    SELECT d.id
+    FROM products p
+    JOIN deals d ON d.product_id = p.id
+    WHERE p.id = product AND variant IS NULL
+      AND d.starts_at <= NOW() AND d.ends_at > NOW()
+      AND d.max_quantity - d.sold_quantity >= quantity
+      AND d.deal_price < LEAST(
+              p.price_per_unit,
+              (SELECT t.unit_price FROM price_tiers t
+               WHERE t.product_id = p.id AND t.min_qty <= quantity
+               ORDER BY t.min_qty DESC
+               LIMIT 1)
+          )
+    LIMIT 1
+$$ LANGUAGE sql STABLE;
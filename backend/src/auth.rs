@@ -1,13 +1,102 @@
 // auth.rs
 use actix_identity::IdentityExt;
 use actix_web::{
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header, Method},
+    web, Error, FromRequest, HttpRequest,
 };
 use futures_util::future::LocalBoxFuture;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
 use std::future::{ready, Ready};
+use std::rc::Rc;
+use uuid::Uuid;
 
 use crate::errors::AppError;
+use crate::utils::get_user_id;
+
+/// What an API token can be granted; a write scope also allows reads of the same resource
+pub const API_TOKEN_SCOPES: &[&str] = &["products:read", "products:write", "orders:read", "orders:write"];
+
+/// The signed-in user, from the session cookie or from an `Authorization: Bearer` API
+/// token. Handlers that take this instead of `Identity` are open to tokens; a token
+/// must carry the scope for the route's resource (the first segment after /api) and
+/// method, e.g. `products:write` for PUT /api/products/{id}.
+pub struct AuthUser {
+    pub id: Uuid,
+}
+
+impl FromRequest for AuthUser {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let bearer = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+
+        let Some(token) = bearer else {
+            let user_id = req
+                .get_identity()
+                .map_err(|_| AppError::Unauthorized)
+                .and_then(|identity| get_user_id(&identity));
+            return Box::pin(async move { Ok(AuthUser { id: user_id? }) });
+        };
+
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        let scope = required_scope(req);
+
+        Box::pin(async move {
+            let pool = pool.ok_or(AppError::InternalError)?;
+
+            let api_token = sqlx::query!(
+                r#"
+                SELECT t.id, t.user_id, t.scopes
+                FROM api_tokens t
+                JOIN users u ON t.user_id = u.id
+                WHERE t.token_hash = $1
+                  AND (t.expires_at IS NULL OR t.expires_at > NOW())
+                  AND u.suspended_at IS NULL AND u.deleted_at IS NULL
+                "#,
+                hash_api_token(&token)
+            )
+                .fetch_optional(pool.get_ref())
+                .await?
+                .ok_or(AppError::Unauthorized)?;
+
+            let scope = scope.ok_or(AppError::Forbidden)?;
+            let write_scope = scope.replace(":read", ":write");
+            if !api_token.scopes.iter().any(|granted| *granted == scope || *granted == write_scope) {
+                return Err(AppError::Forbidden);
+            }
+
+            sqlx::query!("UPDATE api_tokens SET last_used_at = NOW() WHERE id = $1", api_token.id)
+                .execute(pool.get_ref())
+                .await?;
+
+            Ok(AuthUser { id: api_token.user_id })
+        })
+    }
+}
+
+/// Tokens are stored as their SHA-256; they are random enough not to need a slow hash
+pub fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// The scope a token needs for this request, None for resources tokens can't reach
+fn required_scope(req: &HttpRequest) -> Option<String> {
+    let resource = req.path().strip_prefix("/api/")?.split('/').next()?;
+    if !matches!(resource, "products" | "orders") {
+        return None;
+    }
+
+    let access = if req.method() == Method::GET || req.method() == Method::HEAD { "read" } else { "write" };
+    Some(format!("{}:{}", resource, access))
+}
 
 // Middleware factory for requiring authentication
 pub struct RequireAuth;
@@ -52,8 +141,7 @@ where
             return Box::pin(async { Err(AppError::Unauthorized.into()) });
         }
 
-        let fut = self.service.call(req);
-        Box::pin(async move { fut.await })
+        Box::pin(self.service.call(req))
     }
 }
 
@@ -102,7 +190,77 @@ where
             return Box::pin(async { Err(AppError::Unauthorized.into()) });
         }
 
-        let fut = self.service.call(req);
-        Box::pin(async move { fut.await })
+        Box::pin(self.service.call(req))
+    }
+}
+
+// Middleware for requiring admin role; checks the database on every request
+pub struct RequireAdmin;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireAdmin
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequireAdminMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireAdminMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RequireAdminMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireAdminMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let user_id = req
+                .get_identity()
+                .ok()
+                .and_then(|identity| identity.id().ok())
+                .and_then(|id| Uuid::parse_str(&id).ok())
+                .ok_or(AppError::Unauthorized)?;
+
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or(AppError::InternalError)?;
+
+            // Suspended admins lose access too
+            let is_admin = sqlx::query_scalar!(
+                "SELECT is_admin AND suspended_at IS NULL FROM users WHERE id = $1",
+                user_id
+            )
+                .fetch_optional(pool.get_ref())
+                .await
+                .map_err(AppError::from)?
+                .flatten()
+                .unwrap_or(false);
+
+            if !is_admin {
+                return Err(AppError::Forbidden.into());
+            }
+
+            service.call(req).await
+        })
     }
-}
\ No newline at end of file
+}
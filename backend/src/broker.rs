@@ -0,0 +1,160 @@
+// broker.rs
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::ws::{self, deliver_local};
+
+const CHANNEL: &str = "streetsource:ws";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const PRESENCE_PREFIX: &str = "streetsource:online:";
+// A socket's presence lapses this long after the instance holding it last refreshed it,
+// so users on an instance that died stop counting as online
+const PRESENCE_TTL_SECONDS: u64 = 60;
+
+enum Broker {
+    InMemory,
+    Redis(redis::aio::MultiplexedConnection),
+}
+
+static BROKER: OnceLock<Broker> = OnceLock::new();
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    user_id: Uuid,
+    message: String,
+}
+
+/// Pick the broker for this process; call once at startup. With a Redis URL, payloads
+/// and presence go through Redis so every instance sees them; without one they stay in
+/// this process.
+pub async fn init(redis_url: Option<&str>) -> Result<(), redis::RedisError> {
+    let broker = match redis_url {
+        Some(url) => {
+            let client = redis::Client::open(url)?;
+            let publisher = client.get_multiplexed_async_connection().await?;
+            actix_web::rt::spawn(subscribe_forever(client));
+            tracing::info!("WebSocket fan-out using Redis pub/sub");
+            Broker::Redis(publisher)
+        }
+        None => {
+            tracing::info!("WebSocket fan-out using in-memory sessions (single node)");
+            Broker::InMemory
+        }
+    };
+
+    let _ = BROKER.set(broker);
+    Ok(())
+}
+
+/// Route a payload to a user wherever their socket is connected
+pub fn publish(user_id: Uuid, message: String) {
+    match BROKER.get() {
+        Some(Broker::Redis(conn)) => {
+            let mut conn = conn.clone();
+            let payload = match serde_json::to_string(&Envelope { user_id, message }) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::error!("Failed to encode broker envelope: {}", e);
+                    return;
+                }
+            };
+            actix_web::rt::spawn(async move {
+                if let Err(e) = conn.publish::<_, _, ()>(CHANNEL, payload).await {
+                    tracing::error!("Failed to publish to Redis: {}", e);
+                }
+            });
+        }
+        Some(Broker::InMemory) | None => deliver_local(user_id, &message),
+    }
+}
+
+/// Record that the user has a socket open; refreshed on every heartbeat
+pub fn mark_online(user_id: Uuid) {
+    if let Some(Broker::Redis(conn)) = BROKER.get() {
+        let mut conn = conn.clone();
+        actix_web::rt::spawn(async move {
+            let key = format!("{}{}", PRESENCE_PREFIX, user_id);
+            if let Err(e) = conn.set_ex::<_, _, ()>(key, 1, PRESENCE_TTL_SECONDS).await {
+                tracing::warn!(%user_id, "Failed to record presence in Redis: {}", e);
+            }
+        });
+    }
+}
+
+/// Record that the user's socket closed. A socket they still have on another instance
+/// puts the presence back at its next heartbeat.
+pub fn mark_offline(user_id: Uuid) {
+    if let Some(Broker::Redis(conn)) = BROKER.get() {
+        let mut conn = conn.clone();
+        actix_web::rt::spawn(async move {
+            let key = format!("{}{}", PRESENCE_PREFIX, user_id);
+            if let Err(e) = conn.del::<_, ()>(key).await {
+                tracing::warn!(%user_id, "Failed to clear presence in Redis: {}", e);
+            }
+        });
+    }
+}
+
+/// Whether the user has a socket open on any instance. If Redis can't be asked, only
+/// this instance's sockets count.
+pub async fn is_online(user_id: Uuid) -> bool {
+    match BROKER.get() {
+        Some(Broker::Redis(conn)) => {
+            let mut conn = conn.clone();
+            match conn.exists::<_, bool>(format!("{}{}", PRESENCE_PREFIX, user_id)).await {
+                Ok(online) => online,
+                Err(e) => {
+                    tracing::warn!(%user_id, "Failed to read presence from Redis: {}", e);
+                    ws::is_online(user_id)
+                }
+            }
+        }
+        Some(Broker::InMemory) | None => ws::is_online(user_id),
+    }
+}
+
+/// Whether payloads fan out through Redis rather than staying in-process
+pub fn is_distributed() -> bool {
+    matches!(BROKER.get(), Some(Broker::Redis(_)))
+}
+
+/// Round-trip to Redis for the readiness check; in-process delivery is always reachable
+pub async fn ping() -> Result<(), redis::RedisError> {
+    match BROKER.get() {
+        Some(Broker::Redis(conn)) => {
+            let mut conn = conn.clone();
+            redis::cmd("PING").query_async::<()>(&mut conn).await
+        }
+        Some(Broker::InMemory) | None => Ok(()),
+    }
+}
+
+/// Deliver every published payload to local sockets, reconnecting on failure
+async fn subscribe_forever(client: redis::Client) {
+    loop {
+        if let Err(e) = subscribe(&client).await {
+            tracing::error!("Redis subscription lost: {}", e);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe(client: &redis::Client) -> Result<(), redis::RedisError> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(CHANNEL).await?;
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let payload: String = msg.get_payload()?;
+        match serde_json::from_str::<Envelope>(&payload) {
+            Ok(envelope) => deliver_local(envelope.user_id, &envelope.message),
+            Err(e) => tracing::warn!("Ignoring malformed broker payload: {}", e),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,270 @@
+// cache.rs
+use redis::AsyncCommands;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::{CacheBackend, Config};
+use crate::errors::AppResult;
+
+const KEY_PREFIX: &str = "streetsource:cache:";
+/// Entries one instance holds in memory; past this, new responses aren't cached until
+/// older ones expire
+const MAX_MEMORY_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    /// Product listings; also emptied when categories change, as listings show their names
+    Products,
+    Categories,
+}
+
+impl Namespace {
+    const ALL: [Namespace; 2] = [Namespace::Products, Namespace::Categories];
+
+    fn name(self) -> &'static str {
+        match self {
+            Namespace::Products => "products",
+            Namespace::Categories => "categories",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+enum Store {
+    Memory(Mutex<MemoryStore>),
+    Redis(redis::aio::MultiplexedConnection),
+    Off,
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    generations: HashMap<Namespace, u64>,
+    entries: HashMap<(Namespace, u64, String), (Instant, String)>,
+}
+
+/// Counts since this instance started
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+/// The hottest catalog reads, kept in this process or in Redis shared by every instance.
+/// Entries last CACHE_TTL_SECONDS at most. Emptying a namespace bumps its generation,
+/// which is part of every key, so a response loaded before a change can't be stored as
+/// current. Redis errors count as misses; the cache never fails a request.
+pub struct Cache {
+    store: Store,
+    ttl: Duration,
+    counters: [Counters; Namespace::ALL.len()],
+}
+
+/// Build the cache selected by the configuration
+pub async fn from_config(config: &Config) -> Result<Arc<Cache>, redis::RedisError> {
+    let store = match (config.cache_backend, config.redis_url.as_deref()) {
+        (CacheBackend::Redis, Some(url)) => {
+            let client = redis::Client::open(url)?;
+            Store::Redis(client.get_multiplexed_async_connection().await?)
+        }
+        (CacheBackend::Off, _) => Store::Off,
+        // Config refuses CACHE_BACKEND=redis without REDIS_URL
+        (CacheBackend::Memory | CacheBackend::Redis, _) => Store::Memory(Mutex::new(MemoryStore::default())),
+    };
+
+    tracing::info!(ttl_seconds = config.cache_ttl_seconds, "Catalog cache in {:?}", config.cache_backend);
+    Ok(Arc::new(Cache {
+        store,
+        ttl: Duration::from_secs(config.cache_ttl_seconds),
+        counters: Default::default(),
+    }))
+}
+
+impl Cache {
+    /// The cached response for `key`, or the one `load` builds, which is then cached.
+    /// Errors from `load` are returned and not cached.
+    pub async fn fetch(
+        &self,
+        namespace: Namespace,
+        key: &str,
+        load: impl Future<Output = AppResult<Value>>,
+    ) -> AppResult<Value> {
+        if let Store::Off = self.store {
+            return load.await;
+        }
+
+        let generation = self.generation(namespace).await;
+        if let Some(generation) = generation
+            && let Some(value) = self.get(namespace, generation, key).await
+        {
+            self.counters[namespace.index()].hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+        self.counters[namespace.index()].misses.fetch_add(1, Ordering::Relaxed);
+
+        let value = load.await?;
+        if let Some(generation) = generation {
+            self.put(namespace, generation, key, &value).await;
+        }
+        Ok(value)
+    }
+
+    /// Drop everything cached in the namespace, on this and (with Redis) every instance
+    pub async fn invalidate(&self, namespace: Namespace) {
+        self.counters[namespace.index()].invalidations.fetch_add(1, Ordering::Relaxed);
+
+        match &self.store {
+            Store::Memory(store) => {
+                let mut store = store.lock().unwrap();
+                *store.generations.entry(namespace).or_default() += 1;
+                store.entries.retain(|(entry_namespace, _, _), _| *entry_namespace != namespace);
+            }
+            Store::Redis(conn) => {
+                let mut conn = conn.clone();
+                if let Err(e) = conn.incr::<_, _, ()>(generation_key(namespace), 1).await {
+                    tracing::error!(namespace = namespace.name(), "Failed to invalidate the Redis cache: {}", e);
+                }
+            }
+            Store::Off => {}
+        }
+    }
+
+    /// Hits, misses and invalidations per namespace since this instance started, for /health
+    pub fn stats(&self) -> Value {
+        let namespaces: serde_json::Map<String, Value> = Namespace::ALL
+            .iter()
+            .map(|namespace| {
+                let counters = &self.counters[namespace.index()];
+                let hits = counters.hits.load(Ordering::Relaxed);
+                let misses = counters.misses.load(Ordering::Relaxed);
+                let hit_rate = if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 };
+                (namespace.name().to_string(), json!({
+                    "hits": hits,
+                    "misses": misses,
+                    "hit_rate": (hit_rate * 1000.0).round() / 1000.0,
+                    "invalidations": counters.invalidations.load(Ordering::Relaxed)
+                }))
+            })
+            .collect();
+
+        let (backend, entries) = match &self.store {
+            Store::Memory(store) => ("memory", Some(store.lock().unwrap().entries.len())),
+            Store::Redis(_) => ("redis", None),
+            Store::Off => ("off", None),
+        };
+
+        json!({
+            "backend": backend,
+            "ttl_seconds": self.ttl.as_secs(),
+            "entries": entries,
+            "namespaces": namespaces
+        })
+    }
+
+    /// Whether entries are shared through Redis rather than kept in-process
+    pub fn is_distributed(&self) -> bool {
+        matches!(self.store, Store::Redis(_))
+    }
+
+    /// Round-trip to Redis for the readiness check; an in-process cache is always reachable
+    pub async fn ping(&self) -> Result<(), redis::RedisError> {
+        match &self.store {
+            Store::Redis(conn) => {
+                let mut conn = conn.clone();
+                redis::cmd("PING").query_async::<()>(&mut conn).await
+            }
+            Store::Memory(_) | Store::Off => Ok(()),
+        }
+    }
+
+    /// The namespace's current generation; None if Redis can't be asked, so nothing is
+    /// read or stored
+    async fn generation(&self, namespace: Namespace) -> Option<u64> {
+        match &self.store {
+            Store::Memory(store) => Some(store.lock().unwrap().generations.get(&namespace).copied().unwrap_or(0)),
+            Store::Redis(conn) => {
+                let mut conn = conn.clone();
+                match conn.get::<_, Option<u64>>(generation_key(namespace)).await {
+                    Ok(generation) => Some(generation.unwrap_or(0)),
+                    Err(e) => {
+                        tracing::warn!(namespace = namespace.name(), "Failed to read the Redis cache: {}", e);
+                        None
+                    }
+                }
+            }
+            Store::Off => None,
+        }
+    }
+
+    async fn get(&self, namespace: Namespace, generation: u64, key: &str) -> Option<Value> {
+        let text = match &self.store {
+            Store::Memory(store) => {
+                let store = store.lock().unwrap();
+                store
+                    .entries
+                    .get(&(namespace, generation, key.to_string()))
+                    .filter(|(stored_at, _)| stored_at.elapsed() < self.ttl)
+                    .map(|(_, text)| text.clone())
+            }
+            Store::Redis(conn) => {
+                let mut conn = conn.clone();
+                match conn.get::<_, Option<String>>(entry_key(namespace, generation, key)).await {
+                    Ok(text) => text,
+                    Err(e) => {
+                        tracing::warn!(namespace = namespace.name(), "Failed to read the Redis cache: {}", e);
+                        None
+                    }
+                }
+            }
+            Store::Off => None,
+        }?;
+
+        serde_json::from_str(&text).ok()
+    }
+
+    async fn put(&self, namespace: Namespace, generation: u64, key: &str, value: &Value) {
+        let text = value.to_string();
+
+        match &self.store {
+            Store::Memory(store) => {
+                let mut store = store.lock().unwrap();
+                // Stored after an invalidation it was loaded before; it would be stale
+                if store.generations.get(&namespace).copied().unwrap_or(0) != generation {
+                    return;
+                }
+                if store.entries.len() >= MAX_MEMORY_ENTRIES {
+                    let ttl = self.ttl;
+                    store.entries.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+                    if store.entries.len() >= MAX_MEMORY_ENTRIES {
+                        return;
+                    }
+                }
+                store.entries.insert((namespace, generation, key.to_string()), (Instant::now(), text));
+            }
+            Store::Redis(conn) => {
+                let mut conn = conn.clone();
+                let key = entry_key(namespace, generation, key);
+                if let Err(e) = conn.set_ex::<_, _, ()>(key, text, self.ttl.as_secs()).await {
+                    tracing::warn!(namespace = namespace.name(), "Failed to write the Redis cache: {}", e);
+                }
+            }
+            Store::Off => {}
+        }
+    }
+}
+
+fn generation_key(namespace: Namespace) -> String {
+    format!("{}{}:generation", KEY_PREFIX, namespace.name())
+}
+
+/// Entries of an old generation are never read again, and expire with their TTL
+fn entry_key(namespace: Namespace, generation: u64, key: &str) -> String {
+    format!("{}{}:{}:{}", KEY_PREFIX, namespace.name(), generation, key)
+}
@@ -0,0 +1,567 @@
+// config.rs
+use bigdecimal::{BigDecimal, Zero};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Application configuration, read once from the environment at startup
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database_url: String,
+    pub database_max_connections: u32,
+    pub database_min_connections: u32,
+    pub database_acquire_timeout_seconds: u64,
+    pub database_idle_timeout_seconds: u64,
+    pub database_max_lifetime_seconds: u64,
+    pub database_statement_timeout_ms: u64,
+    pub server_address: String,
+    pub shutdown_timeout_seconds: u64,
+    /// Largest JSON body (API requests, GraphQL, webhooks) accepted, in KB
+    pub json_body_limit_kb: usize,
+    /// Largest file an upload route accepts, in MB
+    pub max_file_size_mb: usize,
+    pub secret_key: String,
+    pub s3_bucket_name: String,
+    pub aws_region: String,
+    pub redis_url: Option<String>,
+    pub cache_backend: CacheBackend,
+    /// How long a cached product listing or category list is served before it's reloaded
+    pub cache_ttl_seconds: u64,
+    pub stripe_secret_key: Option<String>,
+    pub stripe_webhook_secret: Option<String>,
+    pub base_currency: String,
+    /// Units of the base currency one unit of each currency is worth; includes the base at 1
+    pub exchange_rates: HashMap<String, BigDecimal>,
+    pub login_max_attempts: i32,
+    pub login_lockout_minutes: i32,
+    /// A sign-in lasts at most this long, however active
+    pub session_timeout_hours: i64,
+    /// A sign-in ends after this long without a request
+    pub session_idle_timeout_minutes: i64,
+    /// How long a "remember me" sign-in lasts, idle or not
+    pub remember_me_days: i64,
+    pub cart_reservation_minutes: i32,
+    /// New products wait for an admin to approve them before they're listed
+    pub product_review_required: bool,
+    /// The platform's cut of each delivered order's goods, in percent
+    pub platform_commission_percent: BigDecimal,
+    pub email_provider: EmailProvider,
+    pub email_from: String,
+    pub ses_region: String,
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub push_provider: PushProvider,
+    /// Path to the Firebase service account key (JSON) FCM pushes are sent with
+    pub fcm_credentials_file: Option<String>,
+    pub storage_backend: StorageBackend,
+    /// Where uploads are written with STORAGE_BACKEND=local
+    pub local_storage_dir: String,
+    /// The URL the local directory is served at, for links to uploaded files
+    pub local_storage_url: String,
+    pub password_min_length: usize,
+    /// Kinds of character every new password needs at least one of
+    pub password_required_classes: Vec<CharacterClass>,
+    pub password_breach_check: BreachCheck,
+    /// Breached passwords, one per line, with PASSWORD_BREACH_CHECK=list
+    pub password_breach_list: Option<String>,
+    /// Let webhooks use plain http:// and point at this machine or the private network
+    pub webhook_allow_private_urls: bool,
+}
+
+/// A kind of character a password policy can require
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CharacterClass {
+    Letter,
+    Lowercase,
+    Uppercase,
+    Digit,
+    /// Anything but a letter or digit
+    Symbol,
+}
+
+/// Where new passwords are looked up among known breached ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreachCheck {
+    Off,
+    /// The file at PASSWORD_BREACH_LIST
+    List,
+    /// The Have I Been Pwned range API
+    Hibp,
+}
+
+/// Which transport outgoing email goes through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailProvider {
+    /// Write emails to the log instead of sending them (development)
+    Log,
+    Ses,
+    Smtp,
+}
+
+/// Which transport push notifications go through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PushProvider {
+    /// Write pushes to the log instead of sending them (development)
+    Log,
+    Fcm,
+}
+
+/// Where uploaded files are stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageBackend {
+    S3,
+    /// A directory served by the app under /uploads (development)
+    Local,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheBackend {
+    /// Each instance caches for itself
+    Memory,
+    /// Shared by every instance through REDIS_URL
+    Redis,
+    Off,
+}
+
+/// Every problem found while reading the environment, reported together
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration ({} problem(s)):", self.problems.len())?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// actix-web's cookie Key::from panics on anything shorter than this
+const MIN_SECRET_KEY_LEN: usize = 64;
+
+// The longest password accepted; PASSWORD_MIN_LENGTH can't exceed it
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut problems = vec![];
+
+        let database_url = required("DATABASE_URL", &mut problems);
+        if !database_url.is_empty()
+            && !database_url.starts_with("postgres://")
+            && !database_url.starts_with("postgresql://")
+        {
+            problems.push("DATABASE_URL must be a postgres:// or postgresql:// URL".to_string());
+        }
+
+        let database_max_connections = positive_number("DATABASE_MAX_CONNECTIONS", 5, &mut problems);
+        // Connections kept open even when idle
+        let database_min_connections = non_negative_number("DATABASE_MIN_CONNECTIONS", 0, &mut problems);
+        if database_min_connections > database_max_connections {
+            problems.push(format!(
+                "DATABASE_MIN_CONNECTIONS ({}) can't exceed DATABASE_MAX_CONNECTIONS ({})",
+                database_min_connections,
+                database_max_connections
+            ));
+        }
+        // How long a query waits for a free connection before failing with a 503
+        let database_acquire_timeout_seconds = positive_number("DATABASE_ACQUIRE_TIMEOUT_SECONDS", 30, &mut problems);
+        // Idle connections above the minimum are closed after this long, and every
+        // connection is replaced once it reaches the maximum lifetime
+        let database_idle_timeout_seconds = positive_number("DATABASE_IDLE_TIMEOUT_SECONDS", 600, &mut problems);
+        let database_max_lifetime_seconds = positive_number("DATABASE_MAX_LIFETIME_SECONDS", 1800, &mut problems);
+        // Postgres cancels any single statement that runs longer
+        let database_statement_timeout_ms = positive_number("DATABASE_STATEMENT_TIMEOUT_MS", 30_000, &mut problems);
+
+        let secret_key = required("SECRET_KEY", &mut problems);
+        if !secret_key.is_empty() && secret_key.len() < MIN_SECRET_KEY_LEN {
+            problems.push(format!(
+                "SECRET_KEY must be at least {} characters (got {})",
+                MIN_SECRET_KEY_LEN,
+                secret_key.len()
+            ));
+        }
+
+        let server_address = optional("SERVER_ADDRESS", "127.0.0.1:8080");
+        let port_is_valid = server_address
+            .rsplit_once(':')
+            .map(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+            .unwrap_or(false);
+        if !port_is_valid {
+            problems.push(format!(
+                "SERVER_ADDRESS must be in host:port form (got '{}')",
+                server_address
+            ));
+        }
+
+        // How long in-flight requests get to finish once shutdown starts
+        let shutdown_timeout_seconds = positive_number("SHUTDOWN_TIMEOUT_SECONDS", 30, &mut problems);
+
+        let s3_bucket_name = optional("S3_BUCKET_NAME", "streetsource-assets");
+        let aws_region = optional("AWS_REGION", "us-east-1");
+
+        let redis_url = env::var("REDIS_URL").ok().filter(|value| !value.trim().is_empty());
+        if let Some(url) = &redis_url
+            && !url.starts_with("redis://")
+            && !url.starts_with("rediss://")
+        {
+            problems.push("REDIS_URL must be a redis:// or rediss:// URL".to_string());
+        }
+
+        // Hot catalog reads (product listings, categories); see cache.rs
+        let cache_backend = match optional("CACHE_BACKEND", "memory").to_lowercase().as_str() {
+            "memory" => CacheBackend::Memory,
+            "redis" => CacheBackend::Redis,
+            "off" => CacheBackend::Off,
+            other => {
+                problems.push(format!("CACHE_BACKEND must be one of memory, redis, off (got '{}')", other));
+                CacheBackend::Memory
+            }
+        };
+        if cache_backend == CacheBackend::Redis && redis_url.is_none() {
+            problems.push("REDIS_URL must be set when CACHE_BACKEND=redis".to_string());
+        }
+        let cache_ttl_seconds = positive_number("CACHE_TTL_SECONDS", 30, &mut problems);
+
+        // Payments are optional, but a half-configured Stripe setup is a mistake
+        let stripe_secret_key = env::var("STRIPE_SECRET_KEY").ok().filter(|value| !value.trim().is_empty());
+        let stripe_webhook_secret = env::var("STRIPE_WEBHOOK_SECRET").ok().filter(|value| !value.trim().is_empty());
+        if stripe_secret_key.is_some() != stripe_webhook_secret.is_some() {
+            problems.push("STRIPE_SECRET_KEY and STRIPE_WEBHOOK_SECRET must be set together".to_string());
+        }
+
+        // Prices are in the base currency unless a product says otherwise; other currencies
+        // can be used once they have an exchange rate, e.g. EXCHANGE_RATES=USD=83.2,EUR=90.5
+        let base_currency = optional("BASE_CURRENCY", "INR").trim().to_uppercase();
+        if !is_currency_code(&base_currency) {
+            problems.push(format!("BASE_CURRENCY must be a 3-letter ISO code (got '{}')", base_currency));
+        }
+        let exchange_rates = exchange_rates(&base_currency, &mut problems);
+
+        // Failed logins within the window that lock an account, and for how long
+        let login_max_attempts = positive_number("LOGIN_MAX_ATTEMPTS", 5, &mut problems);
+        let login_lockout_minutes = positive_number("LOGIN_LOCKOUT_MINUTES", 15, &mut problems);
+
+        // How long a sign-in lasts; see sessions.rs
+        let session_timeout_hours = positive_number("SESSION_TIMEOUT_HOURS", 24, &mut problems);
+        let session_idle_timeout_minutes = positive_number("SESSION_IDLE_TIMEOUT_MINUTES", 120, &mut problems);
+        let remember_me_days = positive_number("REMEMBER_ME_DAYS", 30, &mut problems);
+
+        // How long adding to the cart holds stock for the buyer
+        let cart_reservation_minutes = positive_number("CART_RESERVATION_MINUTES", 15, &mut problems);
+
+        let product_review_required = flag("PRODUCT_REVIEW_REQUIRED", false, &mut problems);
+
+        // Taken from the goods of each delivered order before it is credited to the
+        // seller; delivery fees and tax go to the seller in full
+        let commission = optional("PLATFORM_COMMISSION_PERCENT", "0");
+        let platform_commission_percent = match BigDecimal::from_str(commission.trim()) {
+            Ok(percent) if percent >= BigDecimal::zero() && percent < 100 => percent,
+            _ => {
+                problems.push(format!(
+                    "PLATFORM_COMMISSION_PERCENT must be a number from 0 up to 100 (got '{}')",
+                    commission
+                ));
+                BigDecimal::zero()
+            }
+        };
+
+        let email_provider = match optional("EMAIL_PROVIDER", "log").to_lowercase().as_str() {
+            "log" => EmailProvider::Log,
+            "ses" => EmailProvider::Ses,
+            "smtp" => EmailProvider::Smtp,
+            other => {
+                problems.push(format!("EMAIL_PROVIDER must be one of log, ses, smtp (got '{}')", other));
+                EmailProvider::Log
+            }
+        };
+        let email_from = optional("EMAIL_FROM", "noreply@streetsource.com");
+        if !email_from.contains('@') {
+            problems.push(format!("EMAIL_FROM must be an email address (got '{}')", email_from));
+        }
+        let ses_region = optional("AWS_SES_REGION", &aws_region);
+
+        let smtp_host = env::var("SMTP_HOST").ok().filter(|value| !value.trim().is_empty());
+        let smtp_port = positive_number("SMTP_PORT", 587, &mut problems);
+        let smtp_username = env::var("SMTP_USERNAME").ok().filter(|value| !value.trim().is_empty());
+        let smtp_password = env::var("SMTP_PASSWORD").ok().filter(|value| !value.trim().is_empty());
+        if email_provider == EmailProvider::Smtp && smtp_host.is_none() {
+            problems.push("SMTP_HOST must be set when EMAIL_PROVIDER=smtp".to_string());
+        }
+        if smtp_username.is_some() != smtp_password.is_some() {
+            problems.push("SMTP_USERNAME and SMTP_PASSWORD must be set together".to_string());
+        }
+
+        let push_provider = match optional("PUSH_PROVIDER", "log").to_lowercase().as_str() {
+            "log" => PushProvider::Log,
+            "fcm" => PushProvider::Fcm,
+            other => {
+                problems.push(format!("PUSH_PROVIDER must be one of log, fcm (got '{}')", other));
+                PushProvider::Log
+            }
+        };
+        let fcm_credentials_file = env::var("FCM_CREDENTIALS_FILE").ok().filter(|value| !value.trim().is_empty());
+        if push_provider == PushProvider::Fcm && fcm_credentials_file.is_none() {
+            problems.push("FCM_CREDENTIALS_FILE must be set when PUSH_PROVIDER=fcm".to_string());
+        }
+
+        // Bodies are capped per kind of route: JSON everywhere, and separately the files
+        // sent to the upload routes
+        let json_body_limit_kb = positive_number("JSON_BODY_LIMIT_KB", 256, &mut problems);
+        let max_file_size_mb = positive_number("MAX_FILE_SIZE_MB", 5, &mut problems);
+
+        let storage_backend = match optional("STORAGE_BACKEND", "s3").to_lowercase().as_str() {
+            "s3" => StorageBackend::S3,
+            "local" => StorageBackend::Local,
+            other => {
+                problems.push(format!("STORAGE_BACKEND must be one of s3, local (got '{}')", other));
+                StorageBackend::S3
+            }
+        };
+        let local_storage_dir = optional("LOCAL_STORAGE_DIR", "./uploads");
+        let local_storage_url = optional("LOCAL_STORAGE_URL", &format!("http://{}/uploads", server_address));
+        if !local_storage_url.starts_with("http://") && !local_storage_url.starts_with("https://") {
+            problems.push(format!("LOCAL_STORAGE_URL must be an http(s) URL (got '{}')", local_storage_url));
+        }
+
+        // The rules new passwords are held to, e.g. PASSWORD_REQUIRED_CLASSES=lowercase,uppercase,digit
+        let password_min_length = positive_number("PASSWORD_MIN_LENGTH", 8, &mut problems);
+        if password_min_length > MAX_PASSWORD_LENGTH {
+            problems.push(format!("PASSWORD_MIN_LENGTH can't exceed {} (got {})", MAX_PASSWORD_LENGTH, password_min_length));
+        }
+        let password_required_classes = character_classes("PASSWORD_REQUIRED_CLASSES", "letter,digit", &mut problems);
+
+        let password_breach_check = match optional("PASSWORD_BREACH_CHECK", "off").to_lowercase().as_str() {
+            "off" => BreachCheck::Off,
+            "list" => BreachCheck::List,
+            "hibp" => BreachCheck::Hibp,
+            other => {
+                problems.push(format!("PASSWORD_BREACH_CHECK must be one of off, list, hibp (got '{}')", other));
+                BreachCheck::Off
+            }
+        };
+        let password_breach_list = env::var("PASSWORD_BREACH_LIST").ok().filter(|value| !value.trim().is_empty());
+        if password_breach_check == BreachCheck::List {
+            match &password_breach_list {
+                None => problems.push("PASSWORD_BREACH_LIST must be set when PASSWORD_BREACH_CHECK=list".to_string()),
+                Some(path) if !std::path::Path::new(path).is_file() => {
+                    problems.push(format!("PASSWORD_BREACH_LIST must be a readable file (got '{}')", path));
+                }
+                Some(_) => {}
+            }
+        }
+
+        // Sellers' webhooks must be public https:// URLs unless this is on (development)
+        let webhook_allow_private_urls = flag("WEBHOOK_ALLOW_PRIVATE_URLS", false, &mut problems);
+
+        if !problems.is_empty() {
+            return Err(ConfigError { problems });
+        }
+
+        Ok(Config {
+            database_url,
+            database_max_connections,
+            database_min_connections,
+            database_acquire_timeout_seconds,
+            database_idle_timeout_seconds,
+            database_max_lifetime_seconds,
+            database_statement_timeout_ms,
+            server_address,
+            shutdown_timeout_seconds,
+            json_body_limit_kb,
+            max_file_size_mb,
+            secret_key,
+            s3_bucket_name,
+            aws_region,
+            redis_url,
+            cache_backend,
+            cache_ttl_seconds,
+            stripe_secret_key,
+            stripe_webhook_secret,
+            base_currency,
+            exchange_rates,
+            login_max_attempts,
+            login_lockout_minutes,
+            session_timeout_hours,
+            session_idle_timeout_minutes,
+            remember_me_days,
+            cart_reservation_minutes,
+            product_review_required,
+            platform_commission_percent,
+            email_provider,
+            email_from,
+            ses_region,
+            smtp_host,
+            smtp_port,
+            smtp_username,
+            smtp_password,
+            push_provider,
+            fcm_credentials_file,
+            storage_backend,
+            local_storage_dir,
+            local_storage_url,
+            password_min_length,
+            password_required_classes,
+            password_breach_check,
+            password_breach_list,
+            webhook_allow_private_urls,
+        })
+    }
+
+    /// Pool sizing and timeouts from DATABASE_* settings
+    pub fn database_pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.database_max_connections)
+            .min_connections(self.database_min_connections)
+            .acquire_timeout(Duration::from_secs(self.database_acquire_timeout_seconds))
+            .idle_timeout(Duration::from_secs(self.database_idle_timeout_seconds))
+            .max_lifetime(Duration::from_secs(self.database_max_lifetime_seconds))
+    }
+
+    /// DATABASE_URL with the statement timeout set on every connection
+    pub fn database_connect_options(&self) -> Result<PgConnectOptions, sqlx::Error> {
+        Ok(PgConnectOptions::from_str(&self.database_url)?
+            .options([("statement_timeout", format!("{}ms", self.database_statement_timeout_ms))]))
+    }
+}
+
+/// Read a required variable, recording a problem if it is missing or blank
+fn required(name: &str, problems: &mut Vec<String>) -> String {
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value,
+        Ok(_) => {
+            problems.push(format!("{} is set but empty", name));
+            String::new()
+        }
+        Err(_) => {
+            problems.push(format!("{} must be set", name));
+            String::new()
+        }
+    }
+}
+
+/// EXCHANGE_RATES as CODE=rate pairs, each rate being the base currency units per unit
+fn exchange_rates(base_currency: &str, problems: &mut Vec<String>) -> HashMap<String, BigDecimal> {
+    let mut rates = HashMap::from([(base_currency.to_string(), BigDecimal::from(1))]);
+
+    let value = optional("EXCHANGE_RATES", "");
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let parsed = pair
+            .split_once('=')
+            .map(|(code, rate)| (code.trim().to_uppercase(), BigDecimal::from_str(rate.trim())))
+            .filter(|(code, _)| is_currency_code(code) && code != base_currency);
+
+        match parsed {
+            Some((code, Ok(rate))) if rate > BigDecimal::zero() => {
+                rates.insert(code, rate);
+            }
+            _ => problems.push(format!(
+                "EXCHANGE_RATES entries must look like USD=83.2, for currencies other than {} (got '{}')",
+                base_currency, pair
+            )),
+        }
+    }
+
+    rates
+}
+
+fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Read an optional positive integer, recording a problem if it doesn't parse
+fn positive_number<T>(name: &str, default: T, problems: &mut Vec<String>) -> T
+where
+    T: std::str::FromStr + PartialOrd + Default + Copy,
+{
+    match env::var(name).ok().filter(|value| !value.trim().is_empty()) {
+        None => default,
+        Some(value) => match value.trim().parse::<T>() {
+            Ok(number) if number > T::default() => number,
+            _ => {
+                problems.push(format!("{} must be a positive integer (got '{}')", name, value));
+                default
+            }
+        },
+    }
+}
+
+/// Read an optional integer that may be 0, recording a problem if it doesn't parse
+fn non_negative_number<T>(name: &str, default: T, problems: &mut Vec<String>) -> T
+where
+    T: std::str::FromStr + PartialOrd + Default + Copy,
+{
+    match env::var(name).ok().filter(|value| !value.trim().is_empty()) {
+        None => default,
+        Some(value) => match value.trim().parse::<T>() {
+            Ok(number) if number >= T::default() => number,
+            _ => {
+                problems.push(format!("{} must be a non-negative integer (got '{}')", name, value));
+                default
+            }
+        },
+    }
+}
+
+/// Read an optional comma-separated list of character classes (`none` for no
+/// requirement), recording a problem for any that isn't one
+fn character_classes(name: &str, default: &str, problems: &mut Vec<String>) -> Vec<CharacterClass> {
+    let value = optional(name, default);
+    let mut classes = vec![];
+
+    for class in value.split(',').map(|class| class.trim().to_lowercase()).filter(|class| !class.is_empty()) {
+        let class = match class.as_str() {
+            "letter" => CharacterClass::Letter,
+            "lowercase" => CharacterClass::Lowercase,
+            "uppercase" => CharacterClass::Uppercase,
+            "digit" => CharacterClass::Digit,
+            "symbol" => CharacterClass::Symbol,
+            "none" => continue,
+            other => {
+                problems.push(format!(
+                    "{} entries must be letter, lowercase, uppercase, digit or symbol (got '{}')",
+                    name, other
+                ));
+                continue;
+            }
+        };
+        if !classes.contains(&class) {
+            classes.push(class);
+        }
+    }
+
+    classes
+}
+
+/// Read an optional true/false switch, recording a problem if it isn't one
+fn flag(name: &str, default: bool, problems: &mut Vec<String>) -> bool {
+    match env::var(name).ok().filter(|value| !value.trim().is_empty()) {
+        None => default,
+        Some(value) => match value.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" => true,
+            "false" | "0" | "no" => false,
+            _ => {
+                problems.push(format!("{} must be true or false (got '{}')", name, value));
+                default
+            }
+        },
+    }
+}
+
+/// Read an optional variable, falling back to a default when missing or blank
+fn optional(name: &str, default: &str) -> String {
+    env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
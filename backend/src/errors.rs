@@ -1,8 +1,16 @@
 // errors.rs
 use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
 use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
 use thiserror::Error;
 
+/// Requests that gave up waiting for a database connection since startup
+static POOL_TIMEOUTS: AtomicU64 = AtomicU64::new(0);
+
+pub fn pool_timeouts() -> u64 {
+    POOL_TIMEOUTS.load(Ordering::Relaxed)
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Internal server error")]
@@ -23,31 +31,106 @@ pub enum AppError {
     #[error("Conflict: {0}")]
     Conflict(String),
 
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
 
     #[error("Password hash error")]
     PasswordHashError,
 
-    #[error("AWS error: {0}")]
-    AwsError(String),
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::storage::StorageError),
 
     #[error("Invalid OTP")]
     InvalidOtp,
 
     #[error("OTP expired")]
     OtpExpired,
+
+    #[error("Payment error: {0}")]
+    PaymentError(String),
+
+    #[error("Session error: {0}")]
+    SessionError(String),
+
+    #[error("Account locked, try again in {retry_after_secs} seconds")]
+    AccountLocked { retry_after_secs: i64 },
+
+    // One entry per rejected row of a bulk upload: {"row", "errors"}
+    #[error("{} row(s) failed validation", .0.len())]
+    InvalidRows(Vec<serde_json::Value>),
+
+    // One entry per invalid request field: {"field", "message"}, see validation.rs
+    #[error("{} field(s) failed validation", .0.len())]
+    ValidationFailed(Vec<serde_json::Value>),
+
+    // Cart lines that no longer match current stock or prices, see CartLineIssue
+    #[error("{} cart item(s) changed since they were added", .0.len())]
+    CartChanged(Vec<serde_json::Value>),
+
+    // One entry per seller who doesn't deliver to the address: {"seller_id", "seller_name", "message"}
+    #[error("{} seller(s) don't deliver to this address", .0.len())]
+    Undeliverable(Vec<serde_json::Value>),
 }
 
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
         let status_code = self.status_code();
         let error_message = match self {
+            AppError::DatabaseError(sqlx::Error::PoolTimedOut) => {
+                POOL_TIMEOUTS.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!("Timed out waiting for a database connection");
+                "Server is busy, try again shortly".to_string()
+            }
             AppError::DatabaseError(_) => "Database error occurred".to_string(),
             _ => self.to_string(),
         };
 
-        HttpResponse::build(status_code).json(json!({
+        let mut response = HttpResponse::build(status_code);
+        if let AppError::AccountLocked { retry_after_secs } = self {
+            response.insert_header(("Retry-After", retry_after_secs.to_string()));
+            return response.json(json!({
+                "error": error_message,
+                "code": status_code.as_u16(),
+                "retry_after": retry_after_secs
+            }));
+        }
+
+        if let AppError::InvalidRows(rows) = self {
+            return response.json(json!({
+                "error": error_message,
+                "code": status_code.as_u16(),
+                "rows": rows
+            }));
+        }
+
+        if let AppError::ValidationFailed(fields) = self {
+            return response.json(json!({
+                "error": error_message,
+                "code": status_code.as_u16(),
+                "fields": fields
+            }));
+        }
+
+        if let AppError::CartChanged(issues) = self {
+            return response.json(json!({
+                "error": error_message,
+                "code": status_code.as_u16(),
+                "issues": issues
+            }));
+        }
+
+        if let AppError::Undeliverable(sellers) = self {
+            return response.json(json!({
+                "error": error_message,
+                "code": status_code.as_u16(),
+                "sellers": sellers
+            }));
+        }
+
+        response.json(json!({
             "error": error_message,
             "code": status_code.as_u16()
         }))
@@ -61,10 +144,20 @@ impl ResponseError for AppError {
             AppError::Forbidden => StatusCode::FORBIDDEN,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            // Every connection stayed busy for the whole acquire timeout
+            AppError::DatabaseError(sqlx::Error::PoolTimedOut) => StatusCode::SERVICE_UNAVAILABLE,
             AppError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::PasswordHashError => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::AwsError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::StorageError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::InvalidOtp | AppError::OtpExpired => StatusCode::BAD_REQUEST,
+            AppError::PaymentError(_) => StatusCode::BAD_GATEWAY,
+            AppError::SessionError(_) => StatusCode::BAD_REQUEST,
+            AppError::AccountLocked { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::InvalidRows(_) => StatusCode::BAD_REQUEST,
+            AppError::ValidationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::CartChanged(_) => StatusCode::CONFLICT,
+            AppError::Undeliverable(_) => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -0,0 +1,258 @@
+// graphql/loaders.rs
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::Request;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::graphql::schema::{Category, Message, OrderItem, Product, User, Variant};
+
+/// Most rows a nested list (a seller's products, a conversation's messages) returns per parent
+pub const NESTED_LIST_LIMIT: i64 = 50;
+
+/// Loader errors are shared by every field waiting on the batch
+pub type LoaderError = Arc<AppError>;
+
+/// Attach a fresh set of loaders to the request
+pub fn register(request: Request, pool: &PgPool) -> Request {
+    request
+        .data(DataLoader::new(ProductLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(ProductsBySellerLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(ProductsByCategoryLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(VariantsByProductLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(UserLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(CategoryLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(ItemsByOrderLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(MessagesByConversationLoader(pool.clone()), tokio::spawn))
+}
+
+fn group_by<K: Hash + Eq, V>(rows: Vec<V>, key: impl Fn(&V) -> K) -> HashMap<K, Vec<V>> {
+    let mut groups: HashMap<K, Vec<V>> = HashMap::new();
+    for row in rows {
+        groups.entry(key(&row)).or_default().push(row);
+    }
+    groups
+}
+
+/// Products by id, including deleted and taken-down ones, which old orders still point at
+pub struct ProductLoader(PgPool);
+
+impl Loader<Uuid> for ProductLoader {
+    type Value = Product;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Product>, LoaderError> {
+        let products = sqlx::query_as!(
+            Product,
+            r#"
+            SELECT id, name, description, price_per_unit, currency, stock_qty, image_url, created_at, seller_id, category_id
+            FROM products
+            WHERE id = ANY($1)
+            "#,
+            keys
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(products.into_iter().map(|product| (product.id, product)).collect())
+    }
+}
+
+/// A seller's listed products, newest first
+pub struct ProductsBySellerLoader(PgPool);
+
+impl Loader<Uuid> for ProductsBySellerLoader {
+    type Value = Vec<Product>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Vec<Product>>, LoaderError> {
+        let products = sqlx::query_as!(
+            Product,
+            r#"
+            SELECT id as "id!", name as "name!", description, price_per_unit as "price_per_unit!", currency as "currency!",
+                   stock_qty as "stock_qty!", image_url, created_at as "created_at!",
+                   seller_id as "seller_id!", category_id as "category_id!"
+            FROM (
+                SELECT p.*, ROW_NUMBER() OVER (PARTITION BY p.seller_id ORDER BY p.created_at DESC, p.id DESC) as position
+                FROM products p
+                WHERE p.seller_id = ANY($1) AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+                  AND p.review_status = 'approved'
+            ) listed
+            WHERE position <= $2
+            ORDER BY created_at DESC, id DESC
+            "#,
+            keys,
+            NESTED_LIST_LIMIT
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(group_by(products, |product| product.seller_id))
+    }
+}
+
+/// A category's listed products, newest first
+pub struct ProductsByCategoryLoader(PgPool);
+
+impl Loader<i32> for ProductsByCategoryLoader {
+    type Value = Vec<Product>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Vec<Product>>, LoaderError> {
+        let products = sqlx::query_as!(
+            Product,
+            r#"
+            SELECT id as "id!", name as "name!", description, price_per_unit as "price_per_unit!", currency as "currency!",
+                   stock_qty as "stock_qty!", image_url, created_at as "created_at!",
+                   seller_id as "seller_id!", category_id as "category_id!"
+            FROM (
+                SELECT p.*, ROW_NUMBER() OVER (PARTITION BY p.category_id ORDER BY p.created_at DESC, p.id DESC) as position
+                FROM products p
+                WHERE p.category_id = ANY($1) AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+                  AND p.review_status = 'approved'
+            ) listed
+            WHERE position <= $2
+            ORDER BY created_at DESC, id DESC
+            "#,
+            keys,
+            NESTED_LIST_LIMIT
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(group_by(products, |product| product.category_id))
+    }
+}
+
+pub struct VariantsByProductLoader(PgPool);
+
+impl Loader<Uuid> for VariantsByProductLoader {
+    type Value = Vec<Variant>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Vec<Variant>>, LoaderError> {
+        let variants = sqlx::query_as!(
+            Variant,
+            r#"
+            SELECT id, product_id, name, price_per_unit, stock_qty
+            FROM product_variants
+            WHERE product_id = ANY($1)
+            ORDER BY created_at
+            "#,
+            keys
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(group_by(variants, |variant| variant.product_id))
+    }
+}
+
+/// Public profiles; email, phone and account state are never exposed
+pub struct UserLoader(PgPool);
+
+impl Loader<Uuid> for UserLoader {
+    type Value = User;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, User>, LoaderError> {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, name, is_supplier, rating, total_deliveries, profile_image_url, created_at
+            FROM users
+            WHERE id = ANY($1)
+            "#,
+            keys
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(users.into_iter().map(|user| (user.id, user)).collect())
+    }
+}
+
+pub struct CategoryLoader(PgPool);
+
+impl Loader<i32> for CategoryLoader {
+    type Value = Category;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Category>, LoaderError> {
+        let categories = sqlx::query_as!(
+            Category,
+            "SELECT id, name, parent_id FROM categories WHERE id = ANY($1)",
+            keys
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(categories.into_iter().map(|category| (category.id, category)).collect())
+    }
+}
+
+pub struct ItemsByOrderLoader(PgPool);
+
+impl Loader<Uuid> for ItemsByOrderLoader {
+    type Value = Vec<OrderItem>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Vec<OrderItem>>, LoaderError> {
+        let items = sqlx::query_as!(
+            OrderItem,
+            r#"
+            SELECT id, order_id, product_id, quantity, unit_price, tax_rate, tax_amount, variant_name,
+                   fulfillment_status::text as "fulfillment_status!"
+            FROM order_items
+            WHERE order_id = ANY($1)
+            "#,
+            keys
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(group_by(items, |item| item.order_id))
+    }
+}
+
+/// A conversation's latest messages, newest first
+pub struct MessagesByConversationLoader(PgPool);
+
+impl Loader<Uuid> for MessagesByConversationLoader {
+    type Value = Vec<Message>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Vec<Message>>, LoaderError> {
+        let messages = sqlx::query_as!(
+            Message,
+            r#"
+            SELECT id as "id!", conv_id as "conv_id!", sender_id as "sender_id!", content as "content!",
+                   attachment_url, attachment_type, sent_at as "sent_at!", read_at, edited_at, deleted_at
+            FROM (
+                SELECT m.*, ROW_NUMBER() OVER (PARTITION BY m.conv_id ORDER BY m.sent_at DESC) as position
+                FROM messages m
+                WHERE m.conv_id = ANY($1)
+            ) recent
+            WHERE position <= $2
+            ORDER BY sent_at DESC
+            "#,
+            keys,
+            NESTED_LIST_LIMIT
+        )
+            .fetch_all(&self.0)
+            .await
+            .map_err(|e| Arc::new(e.into()))?;
+
+        Ok(group_by(messages, |message| message.conv_id))
+    }
+}
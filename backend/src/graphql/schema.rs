@@ -0,0 +1,464 @@
+// graphql/schema.rs
+use actix_web::ResponseError;
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{ComplexObject, Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Result, Schema, SimpleObject};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::hash::Hash;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::graphql::loaders::{
+    CategoryLoader, ItemsByOrderLoader, LoaderError, MessagesByConversationLoader, ProductLoader,
+    ProductsByCategoryLoader, ProductsBySellerLoader, UserLoader, VariantsByProductLoader,
+};
+use crate::utils::Pagination;
+
+pub type AppSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Deepest nesting a query may use, and the most fields it may select, so one request
+/// can't fan out into an unbounded number of loads
+const MAX_QUERY_DEPTH: usize = 8;
+const MAX_QUERY_COMPLEXITY: usize = 500;
+
+/// The logged-in user making the request, if any, from the same session cookie as REST
+pub struct Viewer(pub Option<Uuid>);
+
+/// A read-only schema. Public data matches the REST listings; orders and conversations
+/// need a logged-in viewer and are only ever their own.
+pub fn build(pool: PgPool) -> AppSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(pool)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+/// The REST status code goes in `extensions.code`; database details stay in the logs
+fn gql_error(e: &AppError) -> async_graphql::Error {
+    let message = match e {
+        AppError::DatabaseError(db_error) => {
+            tracing::error!(error = %db_error, "GraphQL query failed");
+            "Database error occurred".to_string()
+        }
+        _ => e.to_string(),
+    };
+    let code = e.status_code().as_u16();
+    async_graphql::Error::new(message).extend_with(|_, extensions| extensions.set("code", code))
+}
+
+fn db_error(e: sqlx::Error) -> async_graphql::Error {
+    gql_error(&AppError::DatabaseError(e))
+}
+
+fn pool<'a>(ctx: &Context<'a>) -> &'a PgPool {
+    ctx.data_unchecked::<PgPool>()
+}
+
+fn viewer_id(ctx: &Context<'_>) -> Result<Uuid> {
+    ctx.data_unchecked::<Viewer>().0.ok_or_else(|| gql_error(&AppError::Unauthorized))
+}
+
+async fn load_one<L, K>(ctx: &Context<'_>, key: K) -> Result<Option<L::Value>>
+where
+    L: Loader<K, Error = LoaderError>,
+    K: Send + Sync + Hash + Eq + Clone + 'static,
+{
+    ctx.data_unchecked::<DataLoader<L>>()
+        .load_one(key)
+        .await
+        .map_err(|e| gql_error(&e))
+}
+
+/// A one-to-many relation; a parent with nothing loaded has an empty list
+async fn load_list<L, K, V>(ctx: &Context<'_>, key: K) -> Result<Vec<V>>
+where
+    L: Loader<K, Value = Vec<V>, Error = LoaderError>,
+    K: Send + Sync + Hash + Eq + Clone + 'static,
+    V: Send + Sync + Clone + 'static,
+{
+    Ok(load_one::<L, K>(ctx, key).await?.unwrap_or_default())
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Product {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub price_per_unit: BigDecimal,
+    pub currency: String,
+    pub stock_qty: i32,
+    pub image_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub seller_id: Uuid,
+    #[graphql(skip)]
+    pub category_id: i32,
+}
+
+#[ComplexObject]
+impl Product {
+    async fn seller(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        load_one::<UserLoader, _>(ctx, self.seller_id).await
+    }
+
+    async fn category(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        load_one::<CategoryLoader, _>(ctx, self.category_id).await
+    }
+
+    async fn variants(&self, ctx: &Context<'_>) -> Result<Vec<Variant>> {
+        load_list::<VariantsByProductLoader, _, _>(ctx, self.id).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+pub struct Variant {
+    pub id: Uuid,
+    #[graphql(skip)]
+    pub product_id: Uuid,
+    pub name: String,
+    pub price_per_unit: BigDecimal,
+    pub stock_qty: i32,
+}
+
+/// A user's public profile: sellers, buyers on the viewer's orders, chat partners
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct User {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub is_supplier: bool,
+    pub rating: Option<f64>,
+    pub total_deliveries: i32,
+    pub profile_image_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[ComplexObject]
+impl User {
+    /// The seller's listed products, newest first (at most 50)
+    async fn products(&self, ctx: &Context<'_>) -> Result<Vec<Product>> {
+        load_list::<ProductsBySellerLoader, _, _>(ctx, self.id).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[ComplexObject]
+impl Category {
+    async fn parent(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        match self.parent_id {
+            Some(parent_id) => load_one::<CategoryLoader, _>(ctx, parent_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Listed products directly in this category, newest first (at most 50)
+    async fn products(&self, ctx: &Context<'_>) -> Result<Vec<Product>> {
+        load_list::<ProductsByCategoryLoader, _, _>(ctx, self.id).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Order {
+    pub id: Uuid,
+    pub status: String,
+    pub subtotal_price: BigDecimal,
+    pub discount_amount: BigDecimal,
+    pub delivery_fee: BigDecimal,
+    pub tax_amount: BigDecimal,
+    pub total_price: BigDecimal,
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub buyer_id: Uuid,
+    #[graphql(skip)]
+    pub seller_id: Uuid,
+}
+
+#[ComplexObject]
+impl Order {
+    async fn buyer(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        load_one::<UserLoader, _>(ctx, self.buyer_id).await
+    }
+
+    async fn seller(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        load_one::<UserLoader, _>(ctx, self.seller_id).await
+    }
+
+    async fn items(&self, ctx: &Context<'_>) -> Result<Vec<OrderItem>> {
+        load_list::<ItemsByOrderLoader, _, _>(ctx, self.id).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct OrderItem {
+    pub id: Uuid,
+    #[graphql(skip)]
+    pub order_id: Uuid,
+    #[graphql(skip)]
+    pub product_id: Uuid,
+    pub quantity: i32,
+    pub unit_price: BigDecimal,
+    pub tax_rate: BigDecimal,
+    pub tax_amount: BigDecimal,
+    pub variant_name: Option<String>,
+    pub fulfillment_status: String,
+}
+
+#[ComplexObject]
+impl OrderItem {
+    async fn product(&self, ctx: &Context<'_>) -> Result<Option<Product>> {
+        load_one::<ProductLoader, _>(ctx, self.product_id).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Conversation {
+    pub id: Uuid,
+    pub last_updated: DateTime<Utc>,
+    pub unread_count: i64,
+    /// A seller's broadcast to their buyers rather than a one-to-one conversation
+    pub is_group: bool,
+    pub title: Option<String>,
+    #[graphql(skip)]
+    pub other_user_id: Option<Uuid>,
+    #[graphql(skip)]
+    pub product_id: Option<Uuid>,
+}
+
+#[ComplexObject]
+impl Conversation {
+    /// The other participant, or in a broadcast its seller; none for the seller's own
+    async fn other_user(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        match self.other_user_id {
+            Some(other_user_id) => load_one::<UserLoader, _>(ctx, other_user_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The listing the conversation was last started from, if any
+    async fn product(&self, ctx: &Context<'_>) -> Result<Option<Product>> {
+        match self.product_id {
+            Some(product_id) => load_one::<ProductLoader, _>(ctx, product_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The latest messages, newest first (at most 50)
+    async fn messages(&self, ctx: &Context<'_>) -> Result<Vec<Message>> {
+        load_list::<MessagesByConversationLoader, _, _>(ctx, self.id).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Message {
+    pub id: Uuid,
+    #[graphql(skip)]
+    pub conv_id: Uuid,
+    #[graphql(skip)]
+    pub sender_id: Uuid,
+    /// Empty once the message is deleted
+    pub content: String,
+    pub attachment_url: Option<String>,
+    pub attachment_type: Option<String>,
+    pub sent_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[ComplexObject]
+impl Message {
+    async fn sender(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        load_one::<UserLoader, _>(ctx, self.sender_id).await
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Listed products, newest first. `search` works as in the REST listing and
+    /// `categoryId` includes its subcategories.
+    async fn products(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+        category_id: Option<i32>,
+        page: Option<i32>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Product>> {
+        let pagination = Pagination::new(page, limit);
+        let search = search.map(|search| search.trim().to_string()).filter(|search| !search.is_empty());
+
+        sqlx::query_as!(
+            Product,
+            r#"
+            SELECT id, name, description, price_per_unit, currency, stock_qty, image_url, created_at, seller_id, category_id
+            FROM products
+            WHERE stock_qty > 0 AND taken_down_at IS NULL AND deleted_at IS NULL
+              AND review_status = 'approved'
+              AND seller_id NOT IN (SELECT id FROM users WHERE store_paused)
+              AND ($1::text IS NULL OR search_vector @@ websearch_to_tsquery('english', $1))
+              AND ($2::int IS NULL OR category_id IN (SELECT category_subtree($2)))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            search,
+            category_id,
+            pagination.limit,
+            pagination.offset
+        )
+            .fetch_all(pool(ctx))
+            .await
+            .map_err(db_error)
+    }
+
+    async fn product(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<Product>> {
+        sqlx::query_as!(
+            Product,
+            r#"
+            SELECT id, name, description, price_per_unit, currency, stock_qty, image_url, created_at, seller_id, category_id
+            FROM products
+            WHERE id = $1 AND taken_down_at IS NULL AND deleted_at IS NULL
+              AND review_status = 'approved'
+            "#,
+            id
+        )
+            .fetch_optional(pool(ctx))
+            .await
+            .map_err(db_error)
+    }
+
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+        sqlx::query_as!(Category, "SELECT id, name, parent_id FROM categories ORDER BY name")
+            .fetch_all(pool(ctx))
+            .await
+            .map_err(db_error)
+    }
+
+    async fn category(&self, ctx: &Context<'_>, id: i32) -> Result<Option<Category>> {
+        load_one::<CategoryLoader, _>(ctx, id).await
+    }
+
+    /// A storefront; suspended and deleted sellers have none
+    async fn seller(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<User>> {
+        sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, name, is_supplier, rating, total_deliveries, profile_image_url, created_at
+            FROM users
+            WHERE id = $1 AND is_supplier = TRUE AND suspended_at IS NULL AND deleted_at IS NULL
+            "#,
+            id
+        )
+            .fetch_optional(pool(ctx))
+            .await
+            .map_err(db_error)
+    }
+
+    /// The viewer's own profile
+    async fn me(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        load_one::<UserLoader, _>(ctx, viewer_id(ctx)?).await
+    }
+
+    /// The viewer's orders, newest first: the ones they bought, or with `asSeller`
+    /// the ones they sold
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] as_seller: bool,
+        page: Option<i32>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Order>> {
+        let user_id = viewer_id(ctx)?;
+        let pagination = Pagination::new(page, limit);
+
+        sqlx::query_as!(
+            Order,
+            r#"
+            SELECT id, status::text as "status!", subtotal_price, discount_amount, delivery_fee, tax_amount, total_price, currency,
+                   created_at, buyer_id, seller_id
+            FROM orders
+            WHERE CASE WHEN $2 THEN seller_id ELSE buyer_id END = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            user_id,
+            as_seller,
+            pagination.limit,
+            pagination.offset
+        )
+            .fetch_all(pool(ctx))
+            .await
+            .map_err(db_error)
+    }
+
+    /// One of the viewer's orders, as its buyer or seller
+    async fn order(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<Order>> {
+        let user_id = viewer_id(ctx)?;
+
+        sqlx::query_as!(
+            Order,
+            r#"
+            SELECT id, status::text as "status!", subtotal_price, discount_amount, delivery_fee, tax_amount, total_price, currency,
+                   created_at, buyer_id, seller_id
+            FROM orders
+            WHERE id = $1 AND (buyer_id = $2 OR seller_id = $2)
+            "#,
+            id,
+            user_id
+        )
+            .fetch_optional(pool(ctx))
+            .await
+            .map_err(db_error)
+    }
+
+    /// The viewer's conversations, most recently active first
+    async fn conversations(&self, ctx: &Context<'_>) -> Result<Vec<Conversation>> {
+        let user_id = viewer_id(ctx)?;
+
+        sqlx::query_as!(
+            Conversation,
+            r#"
+            SELECT c.id, c.last_updated,
+                   (
+                       SELECT COUNT(*) FROM messages m
+                       WHERE m.conv_id = c.id AND m.sender_id <> $1
+                         AND CASE
+                             WHEN c.is_group THEN m.sent_at > COALESCE(cp.last_read_at, '-infinity')
+                             ELSE m.read_at IS NULL
+                         END
+                   ) as "unread_count!",
+                   c.is_group, c.title,
+                   CASE
+                       WHEN c.is_group THEN NULLIF(c.owner_id, $1)
+                       WHEN c.user1_id = $1 THEN c.user2_id
+                       ELSE c.user1_id
+                   END as other_user_id,
+                   c.product_id
+            FROM conversation_participants cp
+            JOIN conversations c ON c.id = cp.conv_id
+            WHERE cp.user_id = $1
+            ORDER BY c.last_updated DESC
+            "#,
+            user_id
+        )
+            .fetch_all(pool(ctx))
+            .await
+            .map_err(db_error)
+    }
+}
@@ -0,0 +1,351 @@
+// handlers/account_handlers.rs
+use actix_identity::Identity;
+use actix_web::{http::header, web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::cache::{Cache, Namespace};
+use crate::errors::{AppError, AppResult};
+use crate::handlers::user_handlers::check_password;
+use crate::models::{Address, DeleteAccountRequest, OfferStatus, OrderStatus};
+use crate::utils::get_user_id;
+
+/// Scrub the user's personal data and close the account
+pub async fn delete_account(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    req: web::Json<DeleteAccountRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    check_password(pool.get_ref(), user_id, &req.password).await?;
+
+    let mut tx = pool.begin().await?;
+
+    // Orders still in flight need both parties reachable
+    let open_orders = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM orders
+        WHERE (buyer_id = $1 OR seller_id = $1) AND status IN ('pending', 'paid', 'partially_shipped', 'shipped', 'disputed')
+        "#,
+        user_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if open_orders > 0 {
+        return Err(AppError::Conflict(format!(
+            "Finish or cancel your {} open orders before deleting your account",
+            open_orders
+        )));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = 'deleted-' || id || '@deleted.invalid',
+            password_hash = '',
+            name = 'Deleted user',
+            phone = NULL,
+            profile_image_url = NULL,
+            deleted_at = NOW(),
+            -- Signs out the account's other sessions too
+            password_changed_at = NOW()
+        WHERE id = $1
+        "#,
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    // Personal records with no value to anyone else
+    sqlx::query!("DELETE FROM addresses WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM favorites WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM carts WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM cart_templates WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM recurring_orders WHERE buyer_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM stock_reservations WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM password_resets WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM email_changes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM user_totp WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM totp_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM login_challenges WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM api_tokens WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM notifications WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM push_devices WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Order rows stay for the other party; the delivery address snapshot goes
+    sqlx::query!(
+        "UPDATE orders SET shipping_address = NULL WHERE buyer_id = $1",
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    // A seller's listings and coupons leave the marketplace but remain for past orders
+    sqlx::query!(
+        "UPDATE products SET taken_down_at = NOW() WHERE seller_id = $1 AND taken_down_at IS NULL",
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("UPDATE coupons SET is_active = FALSE WHERE seller_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    // Nothing more is sent to the seller's endpoints, queued deliveries included
+    sqlx::query!("DELETE FROM webhooks WHERE seller_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "UPDATE recurring_orders SET status = 'cancelled', updated_at = NOW() WHERE seller_id = $1",
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    // The buyer's requests for quote are withdrawn; their quotes are offers, rejected below
+    sqlx::query!(
+        "UPDATE rfqs SET status = 'cancelled', updated_at = NOW() WHERE buyer_id = $1 AND status = 'open'",
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE offers SET status = $2, updated_at = NOW()
+        WHERE (buyer_id = $1 OR seller_id = $1) AND status IN ('pending', 'accepted')
+        "#,
+        user_id,
+        OfferStatus::Rejected as OfferStatus
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    // The other participant keeps the conversation, without this user's words;
+    // conversations where both sides are gone are removed entirely
+    sqlx::query!(
+        r#"
+        UPDATE messages SET content = '[message deleted]', attachment_url = NULL, attachment_type = NULL
+        WHERE sender_id = $1
+        "#,
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM message_attachments WHERE uploader_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM conversations c
+        USING users u1, users u2
+        WHERE c.user1_id = u1.id AND c.user2_id = u2.id
+          AND ($1 IN (c.user1_id, c.user2_id))
+          AND u1.deleted_at IS NOT NULL AND u2.deleted_at IS NOT NULL
+        "#,
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+    // A seller's broadcast goes with them, and a buyer leaves the broadcasts they were in
+    sqlx::query!("DELETE FROM conversations WHERE is_group AND owner_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM conversation_participants cp
+        USING conversations c
+        WHERE cp.conv_id = c.id AND c.is_group AND cp.user_id = $1
+        "#,
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    identity.logout();
+    tracing::info!(%user_id, "Account deleted");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Account deleted successfully"
+    })))
+}
+
+/// Everything the platform holds about the user, as a downloadable JSON archive
+pub async fn export_account(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let profile = sqlx::query!(
+        r#"
+        SELECT id, email, name, phone, is_supplier, rating, total_deliveries,
+               profile_image_url, created_at
+        FROM users
+        WHERE id = $1
+        "#,
+        user_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let addresses = sqlx::query_as!(
+        Address,
+        r#"
+        SELECT id, user_id, label, recipient_name, phone, line1, line2, city, state,
+               postal_code, country, latitude, longitude, is_default, created_at, updated_at
+        FROM addresses
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    // Orders on both sides of the marketplace, with their items
+    let orders = sqlx::query!(
+        r#"
+        SELECT o.id, o.buyer_id, o.seller_id, o.status as "status: OrderStatus",
+               o.subtotal_price, o.discount_amount, o.delivery_fee, o.tax_amount, o.total_price, o.currency, o.shipping_address, o.created_at,
+               COALESCE(
+                   (SELECT json_agg(json_build_object(
+                        'product_id', oi.product_id, 'product_name', p.name,
+                        'variant_name', oi.variant_name, 'quantity', oi.quantity, 'unit_price', oi.unit_price,
+                        'tax_rate', oi.tax_rate, 'tax_amount', oi.tax_amount))
+                    FROM order_items oi JOIN products p ON oi.product_id = p.id
+                    WHERE oi.order_id = o.id),
+                   '[]'::json
+               ) as "items!"
+        FROM orders o
+        WHERE o.buyer_id = $1 OR o.seller_id = $1
+        ORDER BY o.created_at
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let orders = orders.iter().map(|order| {
+        json!({
+            "id": order.id,
+            "role": if order.buyer_id == user_id { "buyer" } else { "seller" },
+            "buyer_id": order.buyer_id,
+            "seller_id": order.seller_id,
+            "status": order.status,
+            "subtotal_price": order.subtotal_price,
+            "discount_amount": order.discount_amount,
+            "delivery_fee": order.delivery_fee,
+            "tax_amount": order.tax_amount,
+            "total_price": order.total_price,
+            "currency": order.currency,
+            "shipping_address": order.shipping_address,
+            "created_at": order.created_at,
+            "items": order.items
+        })
+    }).collect::<Vec<_>>();
+
+    // Both sides of every conversation the user took part in
+    let messages = sqlx::query!(
+        r#"
+        SELECT m.id, m.conv_id, m.sender_id, m.content, m.attachment_url, m.sent_at
+        FROM messages m
+        JOIN conversation_participants cp ON cp.conv_id = m.conv_id
+        WHERE cp.user_id = $1
+        ORDER BY m.sent_at
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let messages = messages.iter().map(|message| {
+        json!({
+            "id": message.id,
+            "conv_id": message.conv_id,
+            "sender_id": message.sender_id,
+            "content": message.content,
+            "attachment_url": message.attachment_url,
+            "sent_at": message.sent_at
+        })
+    }).collect::<Vec<_>>();
+
+    let reviews = sqlx::query!(
+        r#"
+        SELECT id, order_id, seller_id, rating, comment, created_at
+        FROM reviews
+        WHERE buyer_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let reviews = reviews.iter().map(|review| {
+        json!({
+            "id": review.id,
+            "order_id": review.order_id,
+            "seller_id": review.seller_id,
+            "rating": review.rating,
+            "comment": review.comment,
+            "created_at": review.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"streetsource-export.json\""))
+        .json(json!({
+            "exported_at": chrono::Utc::now(),
+            "profile": {
+                "id": profile.id,
+                "email": profile.email,
+                "name": profile.name,
+                "phone": profile.phone,
+                "is_supplier": profile.is_supplier,
+                "rating": profile.rating,
+                "total_deliveries": profile.total_deliveries,
+                "profile_image_url": profile.profile_image_url,
+                "created_at": profile.created_at
+            },
+            "addresses": addresses,
+            "orders": orders,
+            "messages": messages,
+            "reviews": reviews
+        })))
+}
@@ -0,0 +1,249 @@
+// handlers/address_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{Address, CreateAddressRequest, UpdateAddressRequest};
+use crate::utils::{get_user_id, validate_location};
+use crate::validation::Validate;
+
+pub async fn get_addresses(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let addresses = sqlx::query_as!(
+        Address,
+        r#"
+        SELECT id, user_id, label, recipient_name, phone, line1, line2,
+               city, state, postal_code, country, latitude, longitude, is_default, created_at, updated_at
+        FROM addresses
+        WHERE user_id = $1
+        ORDER BY is_default DESC, created_at DESC
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "addresses": addresses
+    })))
+}
+
+pub async fn create_address(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<CreateAddressRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    req.validate()?;
+    let country = normalize_country(req.country.as_deref().unwrap_or("IN"));
+    let location = validate_location(req.latitude, req.longitude)?;
+
+    let mut tx = pool.begin().await?;
+
+    // A user's first address becomes the default
+    let has_addresses = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM addresses WHERE user_id = $1) as "exists!""#,
+        user_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let is_default = req.is_default.unwrap_or(false) || !has_addresses;
+    if is_default {
+        clear_default(&mut tx, user_id).await?;
+    }
+
+    let address = sqlx::query_as!(
+        Address,
+        r#"
+        INSERT INTO addresses (id, user_id, label, recipient_name, phone, line1, line2,
+                               city, state, postal_code, country, latitude, longitude, is_default)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        RETURNING id, user_id, label, recipient_name, phone, line1, line2,
+                  city, state, postal_code, country, latitude, longitude, is_default, created_at, updated_at
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        req.label,
+        req.recipient_name.trim(),
+        req.phone.trim(),
+        req.line1.trim(),
+        req.line2,
+        req.city.trim(),
+        req.state.trim(),
+        req.postal_code.trim(),
+        country,
+        location.map(|(latitude, _)| latitude),
+        location.map(|(_, longitude)| longitude),
+        is_default
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Address added successfully",
+        "address": address
+    })))
+}
+
+pub async fn update_address(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    address_id: web::Path<Uuid>,
+    req: web::Json<UpdateAddressRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let address_id = address_id.into_inner();
+    req.validate()?;
+    let country = req.country.as_deref().map(normalize_country);
+    let location = validate_location(req.latitude, req.longitude)?;
+
+    let mut tx = pool.begin().await?;
+
+    if req.is_default == Some(true) {
+        clear_default(&mut tx, user_id).await?;
+    }
+
+    let address = sqlx::query_as!(
+        Address,
+        r#"
+        UPDATE addresses
+        SET label = COALESCE($3, label),
+            recipient_name = COALESCE($4, recipient_name),
+            phone = COALESCE($5, phone),
+            line1 = COALESCE($6, line1),
+            line2 = COALESCE($7, line2),
+            city = COALESCE($8, city),
+            state = COALESCE($9, state),
+            postal_code = COALESCE($10, postal_code),
+            country = COALESCE($11, country),
+            latitude = COALESCE($12, latitude),
+            longitude = COALESCE($13, longitude),
+            is_default = COALESCE($14, is_default),
+            updated_at = NOW()
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, label, recipient_name, phone, line1, line2,
+                  city, state, postal_code, country, latitude, longitude, is_default, created_at, updated_at
+        "#,
+        address_id,
+        user_id,
+        req.label,
+        req.recipient_name.as_deref().map(str::trim),
+        req.phone.as_deref().map(str::trim),
+        req.line1.as_deref().map(str::trim),
+        req.line2,
+        req.city.as_deref().map(str::trim),
+        req.state.as_deref().map(str::trim),
+        req.postal_code.as_deref().map(str::trim),
+        country,
+        location.map(|(latitude, _)| latitude),
+        location.map(|(_, longitude)| longitude),
+        req.is_default
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Address not found".to_string()))?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Address updated successfully",
+        "address": address
+    })))
+}
+
+pub async fn delete_address(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    address_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let address_id = address_id.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let was_default = sqlx::query_scalar!(
+        "DELETE FROM addresses WHERE id = $1 AND user_id = $2 RETURNING is_default",
+        address_id,
+        user_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Address not found".to_string()))?;
+
+    // Promote the most recent remaining address so checkout always has a default
+    if was_default {
+        sqlx::query!(
+            r#"
+            UPDATE addresses SET is_default = TRUE, updated_at = NOW()
+            WHERE id = (
+                SELECT id FROM addresses WHERE user_id = $1
+                ORDER BY created_at DESC LIMIT 1
+            )
+            "#,
+            user_id
+        )
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Address deleted successfully"
+    })))
+}
+
+/// Resolve one of the buyer's addresses into the JSON snapshot stored on an order
+pub async fn shipping_snapshot(pool: &PgPool, buyer_id: Uuid, address_id: Uuid) -> AppResult<serde_json::Value> {
+    let address = sqlx::query!(
+        r#"
+        SELECT recipient_name, phone, line1, line2, city, state, postal_code, country, latitude, longitude
+        FROM addresses
+        WHERE id = $1 AND user_id = $2
+        "#,
+        address_id,
+        buyer_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Address not found in your address book".to_string()))?;
+
+    Ok(json!({
+        "recipient_name": address.recipient_name,
+        "phone": address.phone,
+        "line1": address.line1,
+        "line2": address.line2,
+        "city": address.city,
+        "state": address.state,
+        "postal_code": address.postal_code,
+        "country": address.country,
+        "latitude": address.latitude,
+        "longitude": address.longitude
+    }))
+}
+
+async fn clear_default(conn: &mut PgConnection, user_id: Uuid) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE addresses SET is_default = FALSE, updated_at = NOW() WHERE user_id = $1 AND is_default",
+        user_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Countries are stored as upper-case ISO 3166-1 alpha-2 codes
+fn normalize_country(country: &str) -> String {
+    country.trim().to_uppercase()
+}
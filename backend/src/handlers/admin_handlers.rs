@@ -0,0 +1,704 @@
+// handlers/admin_handlers.rs
+use actix_identity::Identity;
+use actix_session::Session;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::cache::{Cache, Namespace};
+use crate::errors::{AppError, AppResult};
+use crate::handlers::notification_handlers::notify;
+use crate::handlers::product_handlers::PRODUCT_RESTORE_DAYS;
+use crate::models::{AdminOrderQuery, AdminUserQuery, AuditAction, AuditLogQuery, FulfillmentStatus, ModerationRequest, NotificationKind, OrderStatus, PaginationQuery, ProductReviewStatus, ProductUnit, ReviewProductRequest};
+use crate::repositories::audit_repository::{self, AuditEntry};
+use crate::sessions;
+use crate::utils::{get_user_id, Pagination};
+
+const MAX_REVIEW_REASON: usize = 2000;
+
+pub async fn list_users(
+    pool: web::Data<PgPool>,
+    query: web::Query<AdminUserQuery>,
+) -> AppResult<HttpResponse> {
+    let pagination = Pagination::new(query.page, query.limit);
+    let search = query.search.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+    let users = sqlx::query!(
+        r#"
+        SELECT id, email, name, phone, is_supplier, is_admin, rating, total_deliveries,
+               suspended_at, suspension_reason, created_at
+        FROM users
+        WHERE ($1::text IS NULL OR email ILIKE '%' || $1 || '%' OR name ILIKE '%' || $1 || '%')
+          AND ($2::bool IS NULL OR (suspended_at IS NOT NULL) = $2)
+        ORDER BY created_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+        search,
+        query.suspended,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM users
+        WHERE ($1::text IS NULL OR email ILIKE '%' || $1 || '%' OR name ILIKE '%' || $1 || '%')
+          AND ($2::bool IS NULL OR (suspended_at IS NOT NULL) = $2)
+        "#,
+        search,
+        query.suspended
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let user_list = users.iter().map(|user| {
+        json!({
+            "id": user.id,
+            "email": user.email,
+            "name": user.name,
+            "phone": user.phone,
+            "is_supplier": user.is_supplier,
+            "is_admin": user.is_admin,
+            "rating": user.rating,
+            "total_deliveries": user.total_deliveries,
+            "suspended_at": user.suspended_at,
+            "suspension_reason": user.suspension_reason,
+            "created_at": user.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "users": user_list,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+pub async fn suspend_user(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+    req: web::Json<ModerationRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let user_id = user_id.into_inner();
+
+    if user_id == admin_id {
+        return Err(AppError::BadRequest("Admins cannot suspend themselves".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let updated = sqlx::query!(
+        "UPDATE users SET suspended_at = NOW(), suspension_reason = $2 WHERE id = $1",
+        user_id,
+        req.reason
+    )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    audit_repository::record(&mut *tx, AuditEntry {
+        actor_id: Some(admin_id),
+        action: AuditAction::UserSuspended,
+        subject_id: Some(user_id),
+        details: json!({ "reason": req.reason }),
+    }).await?;
+    tx.commit().await?;
+
+    tracing::info!(%admin_id, %user_id, "Admin suspended user");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "User suspended successfully"
+    })))
+}
+
+/// Lift a failed-login lock before it expires on its own
+pub async fn unlock_user(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let user_id = user_id.into_inner();
+
+    let user_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1) as "exists!""#,
+        user_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !user_exists {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!("DELETE FROM login_failures WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_repository::record(&mut *tx, AuditEntry {
+        actor_id: Some(admin_id),
+        action: AuditAction::UserUnlocked,
+        subject_id: Some(user_id),
+        details: json!({}),
+    }).await?;
+    tx.commit().await?;
+
+    tracing::info!(%admin_id, %user_id, "Admin unlocked user");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "User unlocked successfully"
+    })))
+}
+
+pub async fn unsuspend_user(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let user_id = user_id.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let updated = sqlx::query!(
+        "UPDATE users SET suspended_at = NULL, suspension_reason = NULL WHERE id = $1",
+        user_id
+    )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    audit_repository::record(&mut *tx, AuditEntry {
+        actor_id: Some(admin_id),
+        action: AuditAction::UserUnsuspended,
+        subject_id: Some(user_id),
+        details: json!({}),
+    }).await?;
+    tx.commit().await?;
+
+    tracing::info!(%admin_id, %user_id, "Admin lifted user suspension");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "User suspension lifted"
+    })))
+}
+
+/// Sign in as a user to reproduce a problem they report. The session keeps the admin's
+/// id, is flagged on every response, and audit-logs each change made in it; other admins
+/// can't be impersonated.
+pub async fn impersonate_user(
+    request: HttpRequest,
+    identity: Identity,
+    session: Session,
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let user_id = user_id.into_inner();
+
+    if user_id == admin_id {
+        return Err(AppError::BadRequest("Admins cannot impersonate themselves".to_string()));
+    }
+
+    let user = sqlx::query!(
+        "SELECT email, is_admin FROM users WHERE id = $1 AND deleted_at IS NULL",
+        user_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if user.is_admin {
+        return Err(AppError::Forbidden);
+    }
+
+    audit_repository::record(pool.get_ref(), AuditEntry {
+        actor_id: Some(admin_id),
+        action: AuditAction::ImpersonationStarted,
+        subject_id: Some(user_id),
+        details: json!({ "email": user.email }),
+    }).await?;
+
+    Identity::login(&request.extensions(), user_id.to_string())
+        .map_err(|e| AppError::SessionError(e.to_string()))?;
+    sessions::start_impersonation(&session, admin_id)?;
+
+    tracing::info!(%admin_id, %user_id, "Admin started impersonating user");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Impersonation started",
+        "user_id": user_id,
+        "impersonated_by": admin_id
+    })))
+}
+
+/// Return an impersonating admin to their own account
+pub async fn stop_impersonation(
+    request: HttpRequest,
+    identity: Identity,
+    session: Session,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let admin_id = sessions::stop_impersonation(&session)
+        .ok_or_else(|| AppError::BadRequest("Not impersonating anyone".to_string()))?;
+
+    Identity::login(&request.extensions(), admin_id.to_string())
+        .map_err(|e| AppError::SessionError(e.to_string()))?;
+
+    audit_repository::record(pool.get_ref(), AuditEntry {
+        actor_id: Some(admin_id),
+        action: AuditAction::ImpersonationStopped,
+        subject_id: Some(user_id),
+        details: json!({}),
+    }).await?;
+
+    tracing::info!(%admin_id, %user_id, "Admin stopped impersonating user");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Impersonation stopped",
+        "user_id": admin_id
+    })))
+}
+
+pub async fn take_down_product(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<ModerationRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let product_id = product_id.into_inner();
+
+    let updated = sqlx::query!(
+        "UPDATE products SET taken_down_at = NOW(), takedown_reason = $2 WHERE id = $1",
+        product_id,
+        req.reason
+    )
+        .execute(pool.get_ref())
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(AppError::NotFound("Product not found".to_string()));
+    }
+
+    cache.invalidate(Namespace::Products).await;
+    tracing::info!(%admin_id, %product_id, "Admin took down product");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product taken down successfully"
+    })))
+}
+
+pub async fn restore_product(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let product_id = product_id.into_inner();
+
+    let updated = sqlx::query!(
+        "UPDATE products SET taken_down_at = NULL, takedown_reason = NULL WHERE id = $1",
+        product_id
+    )
+        .execute(pool.get_ref())
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(AppError::NotFound("Product not found".to_string()));
+    }
+
+    cache.invalidate(Namespace::Products).await;
+    tracing::info!(%admin_id, %product_id, "Admin restored product");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product restored successfully"
+    })))
+}
+
+/// Listings waiting for review, oldest first
+pub async fn list_products_for_review(
+    pool: web::Data<PgPool>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let products = sqlx::query!(
+        r#"
+        SELECT p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+               p.unit as "unit: ProductUnit", p.min_increment, p.seller_id,
+               p.created_at, c.name as category_name, u.name as seller_name,
+               COALESCE(
+                   (SELECT json_agg(pi.url ORDER BY pi.position) FROM product_images pi WHERE pi.product_id = p.id),
+                   '[]'::json
+               ) as "images!"
+        FROM products p
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE p.review_status = 'pending_review' AND p.deleted_at IS NULL
+        ORDER BY p.created_at ASC
+        LIMIT $1 OFFSET $2
+        "#,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM products
+        WHERE review_status = 'pending_review' AND deleted_at IS NULL
+        "#
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let product_list = products.iter().map(|product| {
+        json!({
+            "id": product.id,
+            "name": product.name,
+            "description": product.description,
+            "price_per_unit": product.price_per_unit,
+            "currency": product.currency,
+            "stock_qty": product.stock_qty,
+            "unit": product.unit,
+            "min_increment": product.min_increment,
+            "category_name": product.category_name,
+            "seller_id": product.seller_id,
+            "seller_name": product.seller_name,
+            "images": product.images,
+            "created_at": product.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "products": product_list,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// Approve a listing waiting for review, or reject it with a reason the seller sees.
+/// Editing a rejected listing puts it back in the queue.
+pub async fn review_product(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<ReviewProductRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let product_id = product_id.into_inner();
+    let reason = req.reason.as_deref().map(str::trim).filter(|reason| !reason.is_empty());
+
+    match req.status {
+        ProductReviewStatus::PendingReview => {
+            return Err(AppError::BadRequest("status must be approved or rejected".to_string()));
+        }
+        ProductReviewStatus::Rejected if reason.is_none() => {
+            return Err(AppError::BadRequest("A reason is required to reject a product".to_string()));
+        }
+        _ => {}
+    }
+    if reason.is_some_and(|reason| reason.chars().count() > MAX_REVIEW_REASON) {
+        return Err(AppError::BadRequest(format!("reason must be at most {} characters", MAX_REVIEW_REASON)));
+    }
+
+    let product = sqlx::query!(
+        r#"
+        UPDATE products
+        SET review_status = $2, review_reason = $3, reviewed_by = $4, reviewed_at = NOW()
+        WHERE id = $1 AND review_status = 'pending_review' AND deleted_at IS NULL
+        RETURNING seller_id, name
+        "#,
+        product_id,
+        req.status as ProductReviewStatus,
+        reason,
+        admin_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let Some(product) = product else {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM products WHERE id = $1 AND deleted_at IS NULL) as "exists!""#,
+            product_id
+        )
+            .fetch_one(pool.get_ref())
+            .await?;
+
+        return Err(if exists {
+            AppError::Conflict("Product is not waiting for review".to_string())
+        } else {
+            AppError::NotFound("Product not found".to_string())
+        });
+    };
+
+    cache.invalidate(Namespace::Products).await;
+
+    let title = match req.status {
+        ProductReviewStatus::Approved => format!("\"{}\" was approved and is now listed", product.name),
+        _ => format!("\"{}\" was not approved", product.name),
+    };
+    notify(
+        pool.get_ref(),
+        product.seller_id,
+        NotificationKind::ProductReview,
+        &title,
+        json!({ "product_id": product_id, "status": req.status, "reason": reason }),
+    ).await?;
+    tracing::info!(%admin_id, %product_id, status = ?req.status, "Admin reviewed product");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product reviewed",
+        "review_status": req.status
+    })))
+}
+
+/// Permanently remove products deleted more than PRODUCT_RESTORE_DAYS ago. Products that
+/// orders still reference stay soft-deleted so order history keeps its product rows.
+pub async fn purge_deleted_products(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+
+    let mut tx = pool.begin().await?;
+
+    let purged = sqlx::query!(
+        r#"
+        DELETE FROM products p
+        WHERE p.deleted_at < NOW() - make_interval(days => $1)
+          AND NOT EXISTS(SELECT 1 FROM order_items oi WHERE oi.product_id = p.id)
+        "#,
+        PRODUCT_RESTORE_DAYS as i32
+    )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    let retained = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM products
+        WHERE deleted_at < NOW() - make_interval(days => $1)
+        "#,
+        PRODUCT_RESTORE_DAYS as i32
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    tracing::info!(%admin_id, purged, retained, "Admin purged deleted products");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": format!("Purged {} deleted products", purged),
+        "purged": purged,
+        "retained_for_orders": retained
+    })))
+}
+
+/// The audit log, newest first, filtered by user (by or about them), action and time
+pub async fn list_audit_log(
+    pool: web::Data<PgPool>,
+    query: web::Query<AuditLogQuery>,
+) -> AppResult<HttpResponse> {
+    let pagination = Pagination::new(query.page, query.limit);
+
+    if let (Some(from), Some(to)) = (query.from, query.to)
+        && from >= to
+    {
+        return Err(AppError::BadRequest("from must be before to".to_string()));
+    }
+
+    let (entries, total) = audit_repository::list(pool.get_ref(), &query, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "entries": entries,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+pub async fn list_orders(
+    pool: web::Data<PgPool>,
+    query: web::Query<AdminOrderQuery>,
+) -> AppResult<HttpResponse> {
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let orders = sqlx::query!(
+        r#"
+        SELECT o.id, o.buyer_id, o.seller_id, o.status as "status: OrderStatus",
+               o.total_price, o.currency, o.created_at,
+               b.name as buyer_name, s.name as seller_name
+        FROM orders o
+        JOIN users b ON o.buyer_id = b.id
+        JOIN users s ON o.seller_id = s.id
+        WHERE ($1::uuid IS NULL OR o.buyer_id = $1 OR o.seller_id = $1)
+          AND ($2::order_status IS NULL OR o.status = $2)
+        ORDER BY o.created_at DESC, o.id DESC
+        LIMIT $3 OFFSET $4
+        "#,
+        query.user_id,
+        query.status.clone() as Option<OrderStatus>,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM orders o
+        WHERE ($1::uuid IS NULL OR o.buyer_id = $1 OR o.seller_id = $1)
+          AND ($2::order_status IS NULL OR o.status = $2)
+        "#,
+        query.user_id,
+        query.status.clone() as Option<OrderStatus>
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let order_list = orders.iter().map(|order| {
+        json!({
+            "id": order.id,
+            "buyer_id": order.buyer_id,
+            "buyer_name": order.buyer_name,
+            "seller_id": order.seller_id,
+            "seller_name": order.seller_name,
+            "status": order.status,
+            "total_price": order.total_price,
+            "currency": order.currency,
+            "created_at": order.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "orders": order_list,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+pub async fn get_order(
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let order_id = order_id.into_inner();
+
+    let order = sqlx::query!(
+        r#"
+        SELECT o.id, o.buyer_id, o.seller_id, o.status as "status: OrderStatus",
+               o.subtotal_price, o.discount_amount, o.delivery_fee, o.tax_amount, o.total_price, o.currency, o.shipping_address, o.created_at,
+               ds.id as "delivery_slot_id?", ds.starts_at as "delivery_starts_at?", ds.ends_at as "delivery_ends_at?",
+               b.name as buyer_name, b.email as buyer_email,
+               s.name as seller_name, s.email as seller_email,
+               c.code as "coupon_code?"
+        FROM orders o
+        JOIN users b ON o.buyer_id = b.id
+        JOIN users s ON o.seller_id = s.id
+        LEFT JOIN coupons c ON o.coupon_id = c.id
+        LEFT JOIN delivery_slots ds ON o.delivery_slot_id = ds.id
+        WHERE o.id = $1
+        "#,
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    let items = sqlx::query!(
+        r#"
+        SELECT oi.product_id, oi.variant_name, oi.quantity, oi.unit_price, oi.tax_rate, oi.tax_amount,
+               oi.fulfillment_status as "fulfillment_status: FulfillmentStatus",
+               p.name as product_name, p.unit as "unit: ProductUnit"
+        FROM order_items oi
+        JOIN products p ON oi.product_id = p.id
+        WHERE oi.order_id = $1
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let payments = sqlx::query!(
+        r#"
+        SELECT provider, provider_payment_id, amount, currency, status, created_at
+        FROM payments
+        WHERE order_id = $1
+        ORDER BY created_at DESC
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": order.id,
+        "status": order.status,
+        "subtotal_price": order.subtotal_price,
+        "discount_amount": order.discount_amount,
+        "delivery_fee": order.delivery_fee,
+        "tax_amount": order.tax_amount,
+        "coupon_code": order.coupon_code,
+        "total_price": order.total_price,
+        "currency": order.currency,
+        "shipping_address": order.shipping_address,
+        "delivery_slot": order.delivery_slot_id.map(|id| json!({
+            "id": id,
+            "starts_at": order.delivery_starts_at,
+            "ends_at": order.delivery_ends_at
+        })),
+        "created_at": order.created_at,
+        "buyer": {
+            "id": order.buyer_id,
+            "name": order.buyer_name,
+            "email": order.buyer_email
+        },
+        "seller": {
+            "id": order.seller_id,
+            "name": order.seller_name,
+            "email": order.seller_email
+        },
+        "items": items.iter().map(|item| json!({
+            "product_id": item.product_id,
+            "product_name": item.product_name,
+            "variant_name": item.variant_name,
+            "quantity": item.quantity,
+            "unit": item.unit,
+            "unit_price": item.unit_price,
+            "tax_rate": item.tax_rate,
+            "tax_amount": item.tax_amount,
+            "fulfillment_status": item.fulfillment_status
+        })).collect::<Vec<_>>(),
+        "payments": payments.iter().map(|payment| json!({
+            "provider": payment.provider,
+            "provider_payment_id": payment.provider_payment_id,
+            "amount": payment.amount,
+            "currency": payment.currency,
+            "status": payment.status,
+            "created_at": payment.created_at
+        })).collect::<Vec<_>>()
+    })))
+}
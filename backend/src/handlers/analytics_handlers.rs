@@ -0,0 +1,489 @@
+// handlers/analytics_handlers.rs
+use actix_identity::Identity;
+use actix_web::{http::header, web, HttpResponse};
+use bigdecimal::BigDecimal;
+use bytes::BytesMut;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::errors::{AppError, AppResult};
+use crate::handlers::catalog_handlers::csv_line;
+use crate::models::{AnalyticsQuery, OrderStatus, PlatformReportQuery, ReportFormat};
+use crate::utils::get_user_id;
+
+const TOP_PRODUCTS: i64 = 10;
+const TOP_CATEGORIES: i64 = 10;
+
+const DAILY_COLUMNS: [&str; 5] = ["date", "new_users", "orders", "paid_orders", "active_suppliers"];
+
+/// One UTC day of the platform report
+#[derive(Default)]
+struct DailyFigures {
+    new_users: i64,
+    orders: i64,
+    paid_orders: i64,
+    active_suppliers: i64,
+    gmv: BTreeMap<String, BigDecimal>,
+}
+
+/// Revenue, order counts, best-selling products and repeat buyers for the supplier.
+/// Revenue counts orders that were paid for; order counts cover every status.
+/// `range` picks a preset window (7d, 30d, 90d, 365d or all; default 30d); `from`/`to`
+/// set an explicit one instead.
+pub async fn get_seller_analytics(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    query: web::Query<AnalyticsQuery>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+
+    let is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1",
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+
+    let (from, to) = date_range(query.range.as_deref(), query.from, query.to)?;
+
+    let revenue = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(total_price), 0) as "revenue!",
+               COALESCE(SUM(discount_amount), 0) as "discounts!",
+               COUNT(*) as "orders!",
+               COALESCE(ROUND(AVG(total_price), 2), 0) as "average_order_value!"
+        FROM orders
+        WHERE seller_id = $1
+          AND status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+          AND ($2::timestamptz IS NULL OR created_at >= $2)
+          AND created_at < $3
+        "#,
+        seller_id,
+        from,
+        to
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let status_counts = sqlx::query!(
+        r#"
+        SELECT status as "status: OrderStatus", COUNT(*) as "count!"
+        FROM orders
+        WHERE seller_id = $1
+          AND ($2::timestamptz IS NULL OR created_at >= $2)
+          AND created_at < $3
+        GROUP BY status
+        ORDER BY 2 DESC
+        "#,
+        seller_id,
+        from,
+        to
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total_orders: i64 = status_counts.iter().map(|row| row.count).sum();
+    let orders_by_status = status_counts.iter().map(|row| {
+        json!({
+            "status": row.status,
+            "count": row.count
+        })
+    }).collect::<Vec<_>>();
+
+    let daily = sqlx::query!(
+        r#"
+        SELECT (created_at AT TIME ZONE 'UTC')::date as "day!",
+               SUM(total_price) as "revenue!",
+               COUNT(*) as "orders!"
+        FROM orders
+        WHERE seller_id = $1
+          AND status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+          AND ($2::timestamptz IS NULL OR created_at >= $2)
+          AND created_at < $3
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+        seller_id,
+        from,
+        to
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let daily = daily.iter().map(|day| {
+        json!({
+            "date": day.day,
+            "revenue": day.revenue,
+            "orders": day.orders
+        })
+    }).collect::<Vec<_>>();
+
+    // Item revenue is before coupon discounts, which apply to whole orders
+    let top_products = sqlx::query!(
+        r#"
+        SELECT oi.product_id, p.name,
+               SUM(oi.quantity)::bigint as "units_sold!",
+               SUM(oi.quantity * oi.unit_price) as "revenue!",
+               COUNT(DISTINCT o.id) as "orders!"
+        FROM order_items oi
+        JOIN orders o ON oi.order_id = o.id
+        JOIN products p ON oi.product_id = p.id
+        WHERE o.seller_id = $1
+          AND o.status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+          AND ($2::timestamptz IS NULL OR o.created_at >= $2)
+          AND o.created_at < $3
+        GROUP BY oi.product_id, p.name
+        ORDER BY 4 DESC, 3 DESC
+        LIMIT $4
+        "#,
+        seller_id,
+        from,
+        to,
+        TOP_PRODUCTS
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let top_products = top_products.iter().map(|product| {
+        json!({
+            "product_id": product.product_id,
+            "name": product.name,
+            "units_sold": product.units_sold,
+            "revenue": product.revenue,
+            "orders": product.orders
+        })
+    }).collect::<Vec<_>>();
+
+    // A repeat buyer paid for two or more orders in the window; a returning buyer had
+    // also paid for one before it
+    let buyers = sqlx::query!(
+        r#"
+        WITH window_buyers AS (
+            SELECT buyer_id, COUNT(*) as orders
+            FROM orders
+            WHERE seller_id = $1
+              AND status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+              AND ($2::timestamptz IS NULL OR created_at >= $2)
+              AND created_at < $3
+            GROUP BY buyer_id
+        )
+        SELECT COUNT(*) as "buyers!",
+               COUNT(*) FILTER (WHERE orders >= 2) as "repeat_buyers!",
+               COUNT(*) FILTER (WHERE EXISTS(
+                   SELECT 1 FROM orders o
+                   WHERE o.seller_id = $1 AND o.buyer_id = window_buyers.buyer_id
+                     AND o.status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+                     AND o.created_at < $2
+               )) as "returning_buyers!"
+        FROM window_buyers
+        "#,
+        seller_id,
+        from,
+        to
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let repeat_rate = if buyers.buyers > 0 {
+        (buyers.repeat_buyers as f64 / buyers.buyers as f64 * 1000.0).round() / 1000.0
+    } else {
+        0.0
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "range": {
+            "from": from,
+            "to": to
+        },
+        "revenue": {
+            "total": revenue.revenue,
+            "discounts": revenue.discounts,
+            "paid_orders": revenue.orders,
+            "average_order_value": revenue.average_order_value,
+            "daily": daily
+        },
+        "orders": {
+            "total": total_orders,
+            "by_status": orders_by_status
+        },
+        "top_products": top_products,
+        "buyers": {
+            "total": buyers.buyers,
+            "repeat": buyers.repeat_buyers,
+            "returning": buyers.returning_buyers,
+            "repeat_rate": repeat_rate
+        }
+    })))
+}
+
+/// Platform-wide figures for admins, over the window and day by day (UTC): GMV (the
+/// total of orders paid for) per currency, new users, active suppliers (those with an
+/// order paid for), orders by status and the top categories. The window is chosen as
+/// for seller analytics. `format=csv` gives the daily figures as a spreadsheet, with a
+/// `gmv_<currency>` column per currency.
+pub async fn get_platform_report(
+    pool: web::Data<PgPool>,
+    query: web::Query<PlatformReportQuery>,
+) -> AppResult<HttpResponse> {
+    let (from, to) = date_range(query.range.as_deref(), query.from, query.to)?;
+
+    let totals = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM users
+             WHERE ($1::timestamptz IS NULL OR created_at >= $1) AND created_at < $2) as "new_users!",
+            (SELECT COUNT(DISTINCT seller_id) FROM orders
+             WHERE status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+               AND ($1::timestamptz IS NULL OR created_at >= $1) AND created_at < $2) as "active_suppliers!"
+        "#,
+        from,
+        to
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let gmv = sqlx::query!(
+        r#"
+        SELECT currency, SUM(total_price) as "gmv!", COUNT(*) as "orders!"
+        FROM orders
+        WHERE status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+          AND ($1::timestamptz IS NULL OR created_at >= $1)
+          AND created_at < $2
+        GROUP BY currency
+        ORDER BY 2 DESC
+        "#,
+        from,
+        to
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let status_counts = sqlx::query!(
+        r#"
+        SELECT status as "status: OrderStatus", COUNT(*) as "count!"
+        FROM orders
+        WHERE ($1::timestamptz IS NULL OR created_at >= $1)
+          AND created_at < $2
+        GROUP BY status
+        ORDER BY 2 DESC
+        "#,
+        from,
+        to
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let daily = daily_figures(pool.get_ref(), from, to).await?;
+
+    if query.format == ReportFormat::Csv {
+        let currencies: BTreeSet<&String> = gmv.iter().map(|row| &row.currency).collect();
+
+        let mut body = BytesMut::new();
+        body.extend_from_slice(&csv_line(
+            DAILY_COLUMNS
+                .iter()
+                .map(|column| column.to_string())
+                .chain(currencies.iter().map(|currency| format!("gmv_{}", currency))),
+        )?);
+        for (day, figures) in &daily {
+            body.extend_from_slice(&csv_line(
+                [
+                    day.to_string(),
+                    figures.new_users.to_string(),
+                    figures.orders.to_string(),
+                    figures.paid_orders.to_string(),
+                    figures.active_suppliers.to_string(),
+                ]
+                    .into_iter()
+                    .chain(currencies.iter().map(|currency| {
+                        figures.gmv.get(*currency).map(|amount| amount.to_string()).unwrap_or_else(|| "0".to_string())
+                    })),
+            )?);
+        }
+
+        return Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"platform-report.csv\""))
+            .body(body.freeze()));
+    }
+
+    // Item value is before coupon discounts, which apply to whole orders
+    let top_categories = sqlx::query!(
+        r#"
+        SELECT p.category_id, c.name, o.currency,
+               COUNT(DISTINCT o.id) as "orders!",
+               SUM(oi.quantity)::bigint as "units_sold!",
+               SUM(oi.quantity * oi.unit_price) as "gmv!"
+        FROM order_items oi
+        JOIN orders o ON oi.order_id = o.id
+        JOIN products p ON oi.product_id = p.id
+        JOIN categories c ON p.category_id = c.id
+        WHERE o.status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+          AND ($1::timestamptz IS NULL OR o.created_at >= $1)
+          AND o.created_at < $2
+        GROUP BY p.category_id, c.name, o.currency
+        ORDER BY 4 DESC, 6 DESC
+        LIMIT $3
+        "#,
+        from,
+        to,
+        TOP_CATEGORIES
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total_orders: i64 = status_counts.iter().map(|row| row.count).sum();
+    let paid_orders: i64 = gmv.iter().map(|row| row.orders).sum();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "range": {
+            "from": from,
+            "to": to
+        },
+        "gmv": gmv.iter().map(|row| json!({
+            "currency": row.currency,
+            "amount": row.gmv,
+            "paid_orders": row.orders
+        })).collect::<Vec<_>>(),
+        "new_users": totals.new_users,
+        "active_suppliers": totals.active_suppliers,
+        "orders": {
+            "total": total_orders,
+            "paid": paid_orders,
+            "by_status": status_counts.iter().map(|row| json!({
+                "status": row.status,
+                "count": row.count
+            })).collect::<Vec<_>>()
+        },
+        "top_categories": top_categories.iter().map(|category| json!({
+            "category_id": category.category_id,
+            "name": category.name,
+            "currency": category.currency,
+            "orders": category.orders,
+            "units_sold": category.units_sold,
+            "gmv": category.gmv
+        })).collect::<Vec<_>>(),
+        "daily": daily.iter().map(|(day, figures)| json!({
+            "date": day,
+            "new_users": figures.new_users,
+            "orders": figures.orders,
+            "paid_orders": figures.paid_orders,
+            "active_suppliers": figures.active_suppliers,
+            "gmv": figures.gmv
+        })).collect::<Vec<_>>()
+    })))
+}
+
+/// The platform's figures for every UTC day of the window, quiet days included. A
+/// window with no start begins on the first day anything happened.
+async fn daily_figures(
+    pool: &PgPool,
+    from: Option<DateTime<Utc>>,
+    to: DateTime<Utc>,
+) -> AppResult<BTreeMap<NaiveDate, DailyFigures>> {
+    let mut daily: BTreeMap<NaiveDate, DailyFigures> = BTreeMap::new();
+
+    let users = sqlx::query!(
+        r#"
+        SELECT (created_at AT TIME ZONE 'UTC')::date as "day!", COUNT(*) as "count!"
+        FROM users
+        WHERE ($1::timestamptz IS NULL OR created_at >= $1)
+          AND created_at < $2
+        GROUP BY 1
+        "#,
+        from,
+        to
+    )
+        .fetch_all(pool)
+        .await?;
+    for row in users {
+        daily.entry(row.day).or_default().new_users = row.count;
+    }
+
+    let orders = sqlx::query!(
+        r#"
+        SELECT (created_at AT TIME ZONE 'UTC')::date as "day!",
+               COUNT(*) as "orders!",
+               COUNT(*) FILTER (WHERE status IN ('paid', 'partially_shipped', 'shipped', 'delivered')) as "paid_orders!",
+               COUNT(DISTINCT seller_id) FILTER (WHERE status IN ('paid', 'partially_shipped', 'shipped', 'delivered'))
+                   as "active_suppliers!"
+        FROM orders
+        WHERE ($1::timestamptz IS NULL OR created_at >= $1)
+          AND created_at < $2
+        GROUP BY 1
+        "#,
+        from,
+        to
+    )
+        .fetch_all(pool)
+        .await?;
+    for row in orders {
+        let figures = daily.entry(row.day).or_default();
+        figures.orders = row.orders;
+        figures.paid_orders = row.paid_orders;
+        figures.active_suppliers = row.active_suppliers;
+    }
+
+    let gmv = sqlx::query!(
+        r#"
+        SELECT (created_at AT TIME ZONE 'UTC')::date as "day!", currency, SUM(total_price) as "gmv!"
+        FROM orders
+        WHERE status IN ('paid', 'partially_shipped', 'shipped', 'delivered')
+          AND ($1::timestamptz IS NULL OR created_at >= $1)
+          AND created_at < $2
+        GROUP BY 1, 2
+        "#,
+        from,
+        to
+    )
+        .fetch_all(pool)
+        .await?;
+    for row in gmv {
+        daily.entry(row.day).or_default().gmv.insert(row.currency, row.gmv);
+    }
+
+    let first_day = from.map(|from| from.date_naive()).or_else(|| daily.keys().next().copied());
+    let last_day = (to - Duration::microseconds(1)).date_naive();
+    if let Some(mut day) = first_day {
+        while day <= last_day {
+            daily.entry(day).or_default();
+            day = day.succ_opt().ok_or(AppError::InternalError)?;
+        }
+    }
+
+    Ok(daily)
+}
+
+/// The window to report on: `from` is None for all time, `to` is exclusive
+fn date_range(
+    range: Option<&str>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> AppResult<(Option<DateTime<Utc>>, DateTime<Utc>)> {
+    let to = to.unwrap_or_else(Utc::now);
+
+    let from = match (from, range) {
+        (Some(from), _) => Some(from),
+        (None, None | Some("30d")) => Some(to - Duration::days(30)),
+        (None, Some("7d")) => Some(to - Duration::days(7)),
+        (None, Some("90d")) => Some(to - Duration::days(90)),
+        (None, Some("365d")) => Some(to - Duration::days(365)),
+        (None, Some("all")) => None,
+        (None, Some(_)) => {
+            return Err(AppError::BadRequest(
+                "range must be one of 7d, 30d, 90d, 365d or all".to_string(),
+            ));
+        }
+    };
+
+    if from.is_some_and(|from| from >= to) {
+        return Err(AppError::BadRequest("from must be before to".to_string()));
+    }
+    Ok((from, to))
+}
@@ -1,21 +1,35 @@
 // handlers/auth_handlers.rs
 use actix_identity::Identity;
+use actix_session::Session;
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use argon2::password_hash::{rand_core::OsRng, SaltString};
 use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
 use rand::Rng;
 use serde_json::json;
+use sha2::Sha256;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::config::Config;
 use crate::errors::{AppError, AppResult};
-use crate::models::{LoginRequest, PasswordResetRequest, PasswordResetVerify, RegisterRequest, User};
+use crate::handlers::two_factor_handlers::{two_factor_enabled, verify_second_factor};
+use crate::mailer::{self, templates, EmailSender};
+use crate::passwords;
+use crate::models::{AuditAction, CartItem, LoginRequest, PasswordResetRequest, PasswordResetVerify, RegisterRequest, TwoFactorLoginRequest, User};
+use crate::repositories::audit_repository::{self, AuditEntry};
+use crate::repositories::cart_repository::{self, CART_SESSION_KEY};
+use crate::sessions;
 
 pub async fn register(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
     req: web::Json<RegisterRequest>,
 ) -> AppResult<HttpResponse> {
+    passwords::validate(&config, &*req, "password", &req.password).await?;
+
     // Check if email already exists
     let existing = sqlx::query!(
         "SELECT id FROM users WHERE email = $1",
@@ -28,13 +42,7 @@ pub async fn register(
         return Err(AppError::Conflict("Email already registered".to_string()));
     }
 
-    // Hash password
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(req.password.as_bytes(), &salt)
-        .map_err(|_| AppError::PasswordHashError)?
-        .to_string();
+    let password_hash = hash_password(&req.password)?;
 
     // Create user
     let user_id = Uuid::new_v4();
@@ -53,38 +61,201 @@ pub async fn register(
         .execute(pool.get_ref())
         .await?;
 
+    mailer::send_in_background(
+        mailer.into_inner(),
+        templates::welcome(&req.email, req.name.as_deref()),
+    );
+
     Ok(HttpResponse::Created().json(json!({
         "message": "Registration successful",
         "user_id": user_id
     })))
 }
 
+/// How long a password-verified login waits for its second factor
+const LOGIN_CHALLENGE_MINUTES: i32 = 5;
+
 pub async fn login(
     request: HttpRequest,
+    session: Session,
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
     req: web::Json<LoginRequest>,
 ) -> AppResult<HttpResponse> {
     // Find user by email
     let user = sqlx::query_as!(
         User,
-        "SELECT * FROM users WHERE email = $1",
+        r#"
+        SELECT id, email, password_hash, name, phone, is_supplier, rating, total_deliveries,
+               profile_image_url, created_at, is_admin, suspended_at
+        FROM users
+        WHERE email = $1 AND deleted_at IS NULL
+        "#,
         req.email
     )
         .fetch_optional(pool.get_ref())
         .await?
         .ok_or_else(|| AppError::Unauthorized)?;
 
+    // Locked accounts are rejected before the password is checked
+    if let Some(retry_after_secs) = lockout_remaining(pool.get_ref(), user.id).await? {
+        return Err(AppError::AccountLocked { retry_after_secs });
+    }
+
     // Verify password
-    let parsed_hash = PasswordHash::new(&user.password_hash)
-        .map_err(|_| AppError::PasswordHashError)?;
+    if !password_matches(&req.password, &user.password_hash)? {
+        if let Some(retry_after_secs) = record_failed_login(pool.get_ref(), &config, user.id).await? {
+            return Err(AppError::AccountLocked { retry_after_secs });
+        }
+        return Err(AppError::Unauthorized);
+    }
+
+    // A successful login resets the failure count
+    sqlx::query!("DELETE FROM login_failures WHERE user_id = $1", user.id)
+        .execute(pool.get_ref())
+        .await?;
+
+    // Suspended accounts keep their data but cannot sign in
+    if user.suspended_at.is_some() {
+        return Err(AppError::Forbidden);
+    }
+
+    // With two-factor authentication on, the session is only created by login_two_factor
+    if two_factor_enabled(pool.get_ref(), user.id).await? {
+        sqlx::query!(
+            "DELETE FROM login_challenges WHERE user_id = $1 AND expires_at <= NOW()",
+            user.id
+        )
+            .execute(pool.get_ref())
+            .await?;
+
+        let challenge_id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+            INSERT INTO login_challenges (id, user_id, expires_at, remember_me)
+            VALUES ($1, $2, NOW() + make_interval(mins => $3), $4)
+            "#,
+            challenge_id,
+            user.id,
+            LOGIN_CHALLENGE_MINUTES,
+            req.remember_me
+        )
+            .execute(pool.get_ref())
+            .await?;
+
+        return Ok(HttpResponse::Ok().json(json!({
+            "message": "Two-factor code required",
+            "2fa_required": true,
+            "challenge_id": challenge_id,
+            "expires_in": LOGIN_CHALLENGE_MINUTES * 60
+        })));
+    }
+
+    complete_login(&request, &session, pool.get_ref(), &user, req.remember_me).await
+}
+
+/// Second step of a login with two-factor authentication: an authenticator or recovery
+/// code for the challenge `login` returned. Wrong codes count towards the account lockout.
+pub async fn login_two_factor(
+    request: HttpRequest,
+    session: Session,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: web::Json<TwoFactorLoginRequest>,
+) -> AppResult<HttpResponse> {
+    let mut tx = pool.begin().await?;
+
+    let challenge = sqlx::query!(
+        "SELECT user_id, expires_at, remember_me FROM login_challenges WHERE id = $1 FOR UPDATE",
+        req.challenge_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    if challenge.expires_at <= Utc::now() {
+        sqlx::query!("DELETE FROM login_challenges WHERE id = $1", req.challenge_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        return Err(AppError::OtpExpired);
+    }
+
+    if let Some(retry_after_secs) = lockout_remaining(pool.get_ref(), challenge.user_id).await? {
+        return Err(AppError::AccountLocked { retry_after_secs });
+    }
 
-    let argon2 = Argon2::default();
-    argon2
-        .verify_password(req.password.as_bytes(), &parsed_hash)
-        .map_err(|_| AppError::Unauthorized)?;
+    if !verify_second_factor(&mut tx, challenge.user_id, &req.code).await? {
+        drop(tx);
+        if let Some(retry_after_secs) = record_failed_login(pool.get_ref(), &config, challenge.user_id).await? {
+            return Err(AppError::AccountLocked { retry_after_secs });
+        }
+        return Err(AppError::InvalidOtp);
+    }
 
+    sqlx::query!("DELETE FROM login_challenges WHERE id = $1", req.challenge_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    sqlx::query!("DELETE FROM login_failures WHERE user_id = $1", challenge.user_id)
+        .execute(pool.get_ref())
+        .await?;
+
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        SELECT id, email, password_hash, name, phone, is_supplier, rating, total_deliveries,
+               profile_image_url, created_at, is_admin, suspended_at
+        FROM users
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        challenge.user_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    if user.suspended_at.is_some() {
+        return Err(AppError::Forbidden);
+    }
+
+    complete_login(&request, &session, pool.get_ref(), &user, challenge.remember_me).await
+}
+
+/// Create the Identity session for an authenticated user
+async fn complete_login(
+    request: &HttpRequest,
+    session: &Session,
+    pool: &PgPool,
+    user: &User,
+    remember_me: bool,
+) -> AppResult<HttpResponse> {
     // Create session
-    Identity::login(&request.extensions(), user.id.to_string()).unwrap();
+    Identity::login(&request.extensions(), user.id.to_string())
+        .map_err(|e| AppError::SessionError(e.to_string()))?;
+    sessions::start(session, remember_me)?;
+
+    audit_repository::record(pool, AuditEntry {
+        actor_id: Some(user.id),
+        action: AuditAction::Login,
+        subject_id: Some(user.id),
+        details: json!({}),
+    }).await?;
+
+    // Carry over any cart built up in the cookie session before logging in
+    match session.get::<Vec<CartItem>>(CART_SESSION_KEY) {
+        Ok(Some(session_cart)) => {
+            cart_repository::merge_items(pool, user.id, &session_cart).await?;
+            session.remove(CART_SESSION_KEY);
+        }
+        Ok(None) => {}
+        // A tampered or outdated cart shouldn't block login; drop it
+        Err(e) => {
+            tracing::warn!("Discarding unreadable session cart: {}", e);
+            session.remove(CART_SESSION_KEY);
+        }
+    }
 
     Ok(HttpResponse::Ok().json(json!({
         "message": "Login successful",
@@ -92,11 +263,98 @@ pub async fn login(
             "id": user.id,
             "email": user.email,
             "name": user.name,
-            "is_supplier": user.is_supplier
+            "is_supplier": user.is_supplier,
+            "is_admin": user.is_admin
         }
     })))
 }
 
+/// Seconds left on an account's lock, if it is currently locked
+async fn lockout_remaining(pool: &PgPool, user_id: Uuid) -> AppResult<Option<i64>> {
+    let remaining = sqlx::query_scalar!(
+        r#"
+        SELECT CEIL(EXTRACT(EPOCH FROM locked_until - NOW()))::bigint as "secs!"
+        FROM login_failures
+        WHERE user_id = $1 AND locked_until > NOW()
+        "#,
+        user_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(remaining.map(|secs| secs.max(1)))
+}
+
+/// Count a failed login, locking the account once the limit is reached.
+/// Returns the lock duration in seconds if this failure triggered a lock.
+async fn record_failed_login(pool: &PgPool, config: &Config, user_id: Uuid) -> AppResult<Option<i64>> {
+    // Failures older than the window, or from before an expired lock, start a new count
+    let failed_attempts = sqlx::query_scalar!(
+        r#"
+        INSERT INTO login_failures (user_id, failed_attempts, last_failed_at)
+        VALUES ($1, 1, NOW())
+        ON CONFLICT (user_id) DO UPDATE SET
+            failed_attempts = CASE
+                WHEN login_failures.locked_until IS NOT NULL
+                  OR login_failures.last_failed_at < NOW() - make_interval(mins => $2)
+                THEN 1
+                ELSE login_failures.failed_attempts + 1
+            END,
+            last_failed_at = NOW(),
+            locked_until = NULL
+        RETURNING failed_attempts
+        "#,
+        user_id,
+        config.login_lockout_minutes
+    )
+        .fetch_one(pool)
+        .await?;
+
+    // Whoever tried isn't known, only the account they tried
+    let locked = failed_attempts >= config.login_max_attempts;
+    audit_repository::record(pool, AuditEntry {
+        actor_id: None,
+        action: AuditAction::LoginFailed,
+        subject_id: Some(user_id),
+        details: json!({ "failed_attempts": failed_attempts, "locked": locked }),
+    }).await?;
+
+    if !locked {
+        return Ok(None);
+    }
+
+    sqlx::query!(
+        "UPDATE login_failures SET locked_until = NOW() + make_interval(mins => $2) WHERE user_id = $1",
+        user_id,
+        config.login_lockout_minutes
+    )
+        .execute(pool)
+        .await?;
+
+    tracing::warn!(%user_id, failed_attempts, "Locked account after repeated failed logins");
+
+    Ok(Some(i64::from(config.login_lockout_minutes) * 60))
+}
+
+/// Argon2 hash of a password, salted
+pub fn hash_password(password: &str) -> AppResult<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|_| AppError::PasswordHashError)?
+        .to_string();
+
+    Ok(password_hash)
+}
+
+/// Whether `password` matches a stored Argon2 hash
+pub fn password_matches(password: &str, password_hash: &str) -> AppResult<bool> {
+    let parsed_hash = PasswordHash::new(password_hash)
+        .map_err(|_| AppError::PasswordHashError)?;
+
+    Ok(Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok())
+}
+
 pub async fn logout(user: Identity) -> AppResult<HttpResponse> {
     user.logout();
     Ok(HttpResponse::Ok().json(json!({
@@ -104,26 +362,54 @@ pub async fn logout(user: Identity) -> AppResult<HttpResponse> {
     })))
 }
 
+/// How long a password reset code stays valid
+const PASSWORD_RESET_OTP_MINUTES: i64 = 15;
+/// Wrong guesses before a reset code is thrown away
+const PASSWORD_RESET_MAX_ATTEMPTS: i32 = 5;
+/// Minimum time between two codes for the same account
+const PASSWORD_RESET_COOLDOWN_SECONDS: i64 = 60;
+
 pub async fn request_password_reset(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
     req: web::Json<PasswordResetRequest>,
 ) -> AppResult<HttpResponse> {
     // Find user by email
     let user = sqlx::query!(
-        "SELECT id FROM users WHERE email = $1",
+        "SELECT id FROM users WHERE email = $1 AND deleted_at IS NULL",
         req.email
     )
         .fetch_optional(pool.get_ref())
         .await?;
 
-    if let Some(user_record) = user {
+    // A code sent moments ago is still on its way; don't flood the inbox or reset the
+    // attempt count. The response stays the same so it reveals nothing about the account.
+    let recently_sent = match &user {
+        Some(user_record) => sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM password_resets
+                WHERE user_id = $1 AND created_at > NOW() - make_interval(secs => $2)
+            ) as "exists!"
+            "#,
+            user_record.id,
+            PASSWORD_RESET_COOLDOWN_SECONDS as f64
+        )
+            .fetch_one(pool.get_ref())
+            .await?,
+        None => false,
+    };
+
+    if recently_sent {
+        tracing::info!("Password reset requested again within the cooldown; no new code sent");
+    } else if let Some(user_record) = user {
         // Generate 6-digit OTP
         let otp: String = (0..6)
-            .map(|_| rand::thread_rng().gen_range(0..10).to_string())
+            .map(|_| rand::rng().random_range(0..10).to_string())
             .collect();
 
-        // Set expiry to 15 minutes from now
-        let expires_at = Utc::now() + Duration::minutes(15);
+        let expires_at = Utc::now() + Duration::minutes(PASSWORD_RESET_OTP_MINUTES);
 
         // Delete any existing OTP for this user
         sqlx::query!(
@@ -133,23 +419,32 @@ pub async fn request_password_reset(
             .execute(pool.get_ref())
             .await?;
 
-        // Store OTP
+        // Store only a hash of the OTP
         sqlx::query!(
             r#"
-            INSERT INTO password_resets (id, user_id, otp_code, expires_at)
+            INSERT INTO password_resets (id, user_id, otp_hash, expires_at)
             VALUES ($1, $2, $3, $4)
             "#,
             Uuid::new_v4(),
             user_record.id,
-            otp,
+            hex::encode(otp_mac(&config.secret_key, user_record.id, &otp)?.finalize().into_bytes()),
             expires_at
         )
             .execute(pool.get_ref())
             .await?;
 
-        // TODO: Send OTP via email (AWS SES or SMTP)
-        // For now, we'll just log it (remove in production)
-        log::info!("Password reset OTP for {}: {}", req.email, otp);
+        audit_repository::record(pool.get_ref(), AuditEntry {
+            actor_id: None,
+            action: AuditAction::PasswordResetRequested,
+            subject_id: Some(user_record.id),
+            details: json!({}),
+        }).await?;
+
+        // Sent in the background so a slow mail provider doesn't hold up the response
+        mailer::send_in_background(
+            mailer.into_inner(),
+            templates::password_reset(&req.email, &otp, PASSWORD_RESET_OTP_MINUTES),
+        );
     }
 
     // Always return success to avoid email enumeration
@@ -158,30 +453,37 @@ pub async fn request_password_reset(
     })))
 }
 
+/// Check the emailed code and set the new password. Every wrong guess counts against
+/// the code, which is invalidated after PASSWORD_RESET_MAX_ATTEMPTS of them.
 pub async fn verify_password_reset(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
     req: web::Json<PasswordResetVerify>,
 ) -> AppResult<HttpResponse> {
+    passwords::validate(&config, &*req, "new_password", &req.new_password).await?;
+
     // Find user by email
     let user = sqlx::query!(
-        "SELECT id FROM users WHERE email = $1",
+        "SELECT id FROM users WHERE email = $1 AND deleted_at IS NULL",
         req.email
     )
         .fetch_optional(pool.get_ref())
         .await?
         .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
 
-    // Find valid OTP
+    let mut tx = pool.begin().await?;
+
+    // Locked so concurrent guesses are all counted
     let reset = sqlx::query!(
         r#"
-        SELECT id, otp_code, expires_at
+        SELECT id, otp_hash, failed_attempts, expires_at
         FROM password_resets
-        WHERE user_id = $1 AND otp_code = $2
+        WHERE user_id = $1
+        FOR UPDATE
         "#,
-        user.id,
-        req.otp
+        user.id
     )
-        .fetch_optional(pool.get_ref())
+        .fetch_optional(&mut *tx)
         .await?
         .ok_or_else(|| AppError::InvalidOtp)?;
 
@@ -190,21 +492,48 @@ pub async fn verify_password_reset(
         return Err(AppError::OtpExpired);
     }
 
-    // Hash new password
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(req.new_password.as_bytes(), &salt)
-        .map_err(|_| AppError::PasswordHashError)?
-        .to_string();
+    let otp_matches = hex::decode(&reset.otp_hash)
+        .is_ok_and(|expected| {
+            otp_mac(&config.secret_key, user.id, req.otp.trim())
+                .is_ok_and(|mac| mac.verify_slice(&expected).is_ok())
+        });
 
-    // Update password
+    if !otp_matches {
+        let failed_attempts = reset.failed_attempts + 1;
+
+        if failed_attempts >= PASSWORD_RESET_MAX_ATTEMPTS {
+            sqlx::query!("DELETE FROM password_resets WHERE id = $1", reset.id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            tracing::warn!(user_id = %user.id, "Password reset code invalidated after too many wrong guesses");
+            return Err(AppError::BadRequest(
+                "Too many incorrect codes; request a new password reset".to_string(),
+            ));
+        }
+
+        sqlx::query!(
+            "UPDATE password_resets SET failed_attempts = $2 WHERE id = $1",
+            reset.id,
+            failed_attempts
+        )
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        return Err(AppError::InvalidOtp);
+    }
+
+    let password_hash = hash_password(&req.new_password)?;
+
+    // Update password, signing out every session
     sqlx::query!(
-        "UPDATE users SET password_hash = $1 WHERE id = $2",
+        "UPDATE users SET password_hash = $1, password_changed_at = NOW() WHERE id = $2",
         password_hash,
         user.id
     )
-        .execute(pool.get_ref())
+        .execute(&mut *tx)
         .await?;
 
     // Delete used OTP
@@ -212,10 +541,28 @@ pub async fn verify_password_reset(
         "DELETE FROM password_resets WHERE id = $1",
         reset.id
     )
-        .execute(pool.get_ref())
+        .execute(&mut *tx)
         .await?;
 
+    audit_repository::record(&mut *tx, AuditEntry {
+        actor_id: None,
+        action: AuditAction::PasswordReset,
+        subject_id: Some(user.id),
+        details: json!({}),
+    }).await?;
+
+    tx.commit().await?;
+
     Ok(HttpResponse::Ok().json(json!({
         "message": "Password reset successful"
     })))
+}
+
+/// A six-digit code is quick to brute-force from a bare hash, so it is keyed with the
+/// server secret; the user id ties the code to its account
+fn otp_mac(secret_key: &str, user_id: Uuid, otp: &str) -> AppResult<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes()).map_err(|_| AppError::InternalError)?;
+    mac.update(user_id.as_bytes());
+    mac.update(otp.as_bytes());
+    Ok(mac)
 }
\ No newline at end of file
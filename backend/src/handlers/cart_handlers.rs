@@ -7,24 +7,34 @@ use serde_json::json;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::config::Config;
 use crate::errors::{AppError, AppResult};
-use crate::models::{AddToCartRequest, CartItem, RemoveFromCartRequest};
+use crate::handlers::coupon_handlers::{cart_coupon, check_coupon, discount_for, seller_cart_subtotal};
+use crate::models::{
+    AddToCartRequest, CartItem, ProductUnit, RemoveFromCartRequest, ReplaceCartRequest, SaveCartTemplateRequest, SetCartQuantityRequest,
+};
+use crate::repositories::cart_repository::{self, CART_SESSION_KEY};
+use crate::repositories::product_repository;
+use crate::repositories::reservation_repository;
+use crate::utils::{get_user_id, get_viewer_id};
+use crate::validation::Validate;
 
-const CART_SESSION_KEY: &str = "cart";
+/// A guest's cart lives in the session cookie, so it is kept small
+const MAX_SESSION_CART_LINES: usize = 20;
 
+/// The user's cart, or a guest's session cart. A guest's lines hold no stock and carry no
+/// coupon until they log in and the cart is merged into their own.
 pub async fn get_cart(
-    identity: Identity,
+    identity: Option<Identity>,
     session: Session,
     pool: web::Data<PgPool>,
 ) -> AppResult<HttpResponse> {
-    if identity.id().is_err() {
-        return Err(AppError::Unauthorized);
-    }
-    // Get cart from session
-    let cart_items: Vec<CartItem> = session
-        .get::<Vec<CartItem>>(CART_SESSION_KEY)
-        .expect("Failed to get cart from session")
-        .unwrap_or_default();
+    let user_id = get_viewer_id(identity.as_ref())?;
+
+    let cart_items = match user_id {
+        Some(user_id) => cart_repository::get_items(pool.get_ref(), user_id).await?,
+        None => session_cart(&session),
+    };
 
     // Fetch product details for cart items
     let product_ids: Vec<Uuid> = cart_items.iter().map(|item| item.product_id).collect();
@@ -32,14 +42,17 @@ pub async fn get_cart(
     if product_ids.is_empty() {
         return Ok(HttpResponse::Ok().json(json!({
             "items": [],
+            "subtotal": 0.0,
+            "discount": 0.0,
+            "coupon": null,
             "total": 0.0
         })));
     }
 
     let products = sqlx::query!(
         r#"
-        SELECT p.id, p.name, p.price_per_unit, p.image_url, p.stock_qty,
-               u.name as seller_name
+        SELECT p.id, p.name, p.price_per_unit, p.unit as "unit: ProductUnit", p.min_increment, p.image_url,
+               u.name as seller_name, current_deal(p.id) as deal
         FROM products p
         JOIN users u ON p.seller_id = u.id
         WHERE p.id = ANY($1)
@@ -49,115 +62,613 @@ pub async fn get_cart(
         .fetch_all(pool.get_ref())
         .await?;
 
+    let variant_ids: Vec<Uuid> = cart_items.iter().filter_map(|item| item.variant_id).collect();
+    let variants = sqlx::query!(
+        "SELECT id, name, price_per_unit FROM product_variants WHERE id = ANY($1)",
+        &variant_ids
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let keys: Vec<_> = cart_items.iter().map(|item| (item.product_id, item.variant_id)).collect();
+    let mut conn = pool.acquire().await?;
+    let available = reservation_repository::available_stock(&mut conn, &keys, user_id).await?;
+    let prices = product_repository::line_prices(&mut conn, &cart_items).await?;
+    let reserved_until = match user_id {
+        Some(user_id) => reservation_repository::expiries(pool.get_ref(), user_id).await?,
+        None => Default::default(),
+    };
+
     let mut cart_details = vec![];
     let mut total = BigDecimal::from(0);
 
     for item in &cart_items {
         if let Some(product) = products.iter().find(|p| p.id == item.product_id) {
-            let subtotal = product.price_per_unit.clone() * item.quantity;
+            // A variant line is priced by its variant, and a product line by the price tier
+            // its quantity reaches or the deal on now, if there are enough units left of it
+            let variant = item.variant_id.and_then(|id| variants.iter().find(|v| v.id == id));
+            let list_price = variant.map_or(&product.price_per_unit, |v| &v.price_per_unit);
+            let key = (item.product_id, item.variant_id);
+            let price_per_unit = prices.get(&key).map_or(list_price, |price| &price.unit_price);
+            let subtotal = price_per_unit.clone() * item.quantity;
             total += subtotal.clone();
 
             cart_details.push(json!({
                 "product_id": product.id,
+                "variant_id": item.variant_id,
                 "name": product.name,
-                "price_per_unit": product.price_per_unit,
+                "variant_name": variant.map(|v| &v.name),
+                "price_per_unit": price_per_unit,
+                "list_price": list_price,
+                "deal": if item.variant_id.is_none() { product.deal.clone() } else { None },
                 "quantity": item.quantity,
+                "unit": product.unit,
+                "min_increment": product.min_increment,
                 "subtotal": subtotal,
                 "image_url": product.image_url,
                 "seller_name": product.seller_name,
-                "available_stock": product.stock_qty
+                "available_stock": available.get(&key).copied().unwrap_or(0),
+                "reserved_until": reserved_until.get(&key)
             }));
         }
     }
 
+    // Preview the coupon's discount; one that no longer applies is shown with its reason
+    let mut discount = BigDecimal::from(0);
+    let applied = match user_id {
+        Some(user_id) => cart_coupon(&mut conn, user_id).await?.map(|coupon| (user_id, coupon)),
+        None => None,
+    };
+    let coupon = match applied {
+        Some((user_id, coupon)) => {
+            let subtotal = seller_cart_subtotal(&mut conn, user_id, coupon.seller_id).await?;
+            let problem = match check_coupon(&mut conn, &coupon, user_id, &subtotal).await {
+                Ok(()) => None,
+                Err(AppError::BadRequest(reason)) => Some(reason),
+                Err(e) => return Err(e),
+            };
+            if problem.is_none() {
+                discount = discount_for(&coupon, &subtotal);
+            }
+            json!({
+                "code": coupon.code,
+                "discount": discount,
+                "error": problem
+            })
+        }
+        None => serde_json::Value::Null,
+    };
+
     Ok(HttpResponse::Ok().json(json!({
         "items": cart_details,
-        "total": total
+        "subtotal": total,
+        "discount": discount,
+        "coupon": coupon,
+        "total": &total - &discount
     })))
 }
 
+/// Add to the user's cart, or to a guest's session cart when nobody is logged in
 pub async fn add_to_cart(
-    _identity: Identity, // Ensure user is logged in
+    identity: Option<Identity>,
     session: Session,
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
     req: web::Json<AddToCartRequest>,
 ) -> AppResult<HttpResponse> {
-    // Verify product exists and has stock
-    let product = sqlx::query!(
-        "SELECT stock_qty FROM products WHERE id = $1",
-        req.product_id
+    req.validate()?;
+    check_store_open(pool.get_ref(), req.product_id).await?;
+
+    let Some(user_id) = get_viewer_id(identity.as_ref())? else {
+        return add_to_session_cart(&session, pool.get_ref(), &req).await;
+    };
+
+    // Adds to the quantity if the product is already in the cart and holds the stock
+    // for it; fails if the product or variant is missing, a product with variants is
+    // added without one, or other carts hold too much of it
+    cart_repository::add_item(
+        pool.get_ref(),
+        user_id,
+        req.product_id,
+        req.variant_id,
+        req.quantity,
+        config.cart_reservation_minutes,
     )
-        .fetch_optional(pool.get_ref())
-        .await.expect("Failed to fetch product from database")
-        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+        .await?;
+
+    let cart_size = cart_repository::count_items(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Item added to cart",
+        "cart_size": cart_size
+    })))
+}
 
-    if product.stock_qty < req.quantity {
-        return Err(AppError::BadRequest("Insufficient stock".to_string()));
+/// Set a line's quantity outright rather than by how much it changes; 0 removes it. The
+/// whole quantity is checked against stock and held, as if it had been added at once.
+pub async fn set_cart_quantity(
+    identity: Option<Identity>,
+    session: Session,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<SetCartQuantityRequest>,
+) -> AppResult<HttpResponse> {
+    req.validate()?;
+    let product_id = product_id.into_inner();
+    if req.quantity > 0 {
+        check_store_open(pool.get_ref(), product_id).await?;
     }
 
-    // Get current cart
-    let mut cart_items: Vec<CartItem> = session
-        .get::<Vec<CartItem>>(CART_SESSION_KEY)
-        .expect("Failed to get cart from session")
-        .unwrap_or_default();
+    let item = CartItem {
+        product_id,
+        variant_id: req.variant_id,
+        quantity: req.quantity,
+    };
+    let Some(user_id) = get_viewer_id(identity.as_ref())? else {
+        return set_session_cart_quantity(&session, pool.get_ref(), item).await;
+    };
 
-    // Update quantity if product already in cart
-    if let Some(existing_item) = cart_items.iter_mut().find(|item| item.product_id == req.product_id) {
-        existing_item.quantity += req.quantity;
-    } else {
-        cart_items.push(CartItem {
-            product_id: req.product_id,
-            quantity: req.quantity,
-        });
+    cart_repository::set_quantity(
+        pool.get_ref(),
+        user_id,
+        item.product_id,
+        item.variant_id,
+        item.quantity,
+        config.cart_reservation_minutes,
+    )
+        .await?;
+
+    let cart_size = cart_repository::count_items(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Cart updated",
+        "quantity": item.quantity,
+        "cart_size": cart_size
+    })))
+}
+
+/// Replace the whole cart with the given lines. Every line is checked as if it had been
+/// added on its own; if any fails, the cart is left as it was. The coupon stays.
+pub async fn replace_cart(
+    identity: Option<Identity>,
+    session: Session,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: web::Json<ReplaceCartRequest>,
+) -> AppResult<HttpResponse> {
+    req.validate()?;
+    let ReplaceCartRequest { items } = req.into_inner();
+
+    for (index, item) in items.iter().enumerate() {
+        check_store_open(pool.get_ref(), item.product_id)
+            .await
+            .map_err(|e| cart_repository::about_item(index, e))?;
     }
 
-    // Save cart back to session
-    session.insert(CART_SESSION_KEY, &cart_items)
-        .expect("Failed to save cart to session");
+    let Some(user_id) = get_viewer_id(identity.as_ref())? else {
+        return replace_session_cart(&session, pool.get_ref(), items).await;
+    };
+
+    cart_repository::replace_items(pool.get_ref(), user_id, &items, config.cart_reservation_minutes).await?;
 
     Ok(HttpResponse::Ok().json(json!({
-        "message": "Item added to cart",
-        "cart_size": cart_items.len()
+        "message": "Cart replaced",
+        "cart_size": items.len()
     })))
 }
 
+/// A paused seller takes no new orders, so their products can't be added to carts;
+/// a missing product is left to the stock check to report
+async fn check_store_open(pool: &PgPool, product_id: Uuid) -> AppResult<()> {
+    let seller = sqlx::query!(
+        r#"
+        SELECT u.name, u.store_paused
+        FROM products p
+        JOIN users u ON p.seller_id = u.id
+        WHERE p.id = $1
+        "#,
+        product_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    match seller {
+        Some(seller) if seller.store_paused => Err(AppError::BadRequest(format!(
+            "{} is on vacation and not taking orders right now",
+            seller.name.as_deref().unwrap_or("This seller")
+        ))),
+        _ => Ok(()),
+    }
+}
+
 pub async fn remove_from_cart(
-    _identity: Identity,
+    identity: Option<Identity>,
     session: Session,
-    req: web::Json<RemoveFromCartRequest>, // Reusing same struct
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: web::Json<RemoveFromCartRequest>,
 ) -> AppResult<HttpResponse> {
-    // Get current cart
-    let mut cart_items: Vec<CartItem> = session
-        .get::<Vec<CartItem>>(CART_SESSION_KEY)
-        .expect("Failed to get cart from session")
-        .unwrap_or_default();
+    let Some(user_id) = get_viewer_id(identity.as_ref())? else {
+        return remove_from_session_cart(&session, &req);
+    };
 
-    if req.quantity.is_none() {
+    match req.quantity {
         // Remove item from cart
-        cart_items.retain(|item| item.product_id != req.product_id);
-    }
-    else {
-        // Update quantity if item exists
-        if let Some(item) = cart_items.iter_mut().find(|item| item.product_id == req.product_id) {
-            if let Some(quantity) = req.quantity {
-                if item.quantity > quantity {
-                    item.quantity -= quantity;
-                } else {
-                    // If quantity is more than available, remove the item
-                    cart_items.retain(|i| i.product_id != req.product_id);
-                }
-            }
-        } else {
-            return Err(AppError::NotFound("Item not found in cart".to_string()));
+        None => cart_repository::remove_item(pool.get_ref(), user_id, req.product_id, req.variant_id).await?,
+        // Reduce quantity if item exists; removing more than is in the cart drops the item
+        Some(quantity) => {
+            let current = cart_repository::get_quantity(pool.get_ref(), user_id, req.product_id, req.variant_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Item not found in cart".to_string()))?;
+
+            cart_repository::set_quantity(
+                pool.get_ref(),
+                user_id,
+                req.product_id,
+                req.variant_id,
+                current - quantity,
+                config.cart_reservation_minutes,
+            )
+                .await?;
+        }
+    }
+
+    let cart_size = cart_repository::count_items(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Item removed from cart",
+        "cart_size": cart_size
+    })))
+}
+
+/// A guest's cart from the session; one that can't be read is treated as empty
+fn session_cart(session: &Session) -> Vec<CartItem> {
+    session.get::<Vec<CartItem>>(CART_SESSION_KEY).unwrap_or_else(|e| {
+        tracing::warn!("Discarding unreadable session cart: {}", e);
+        None
+    }).unwrap_or_default()
+}
+
+fn save_session_cart(session: &Session, items: &[CartItem]) -> AppResult<()> {
+    session
+        .insert(CART_SESSION_KEY, items)
+        .map_err(|e| AppError::SessionError(e.to_string()))
+}
+
+/// Same checks as a user's cart, against stock left after everyone's holds, but nothing is held
+async fn add_to_session_cart(session: &Session, pool: &PgPool, req: &AddToCartRequest) -> AppResult<HttpResponse> {
+    let mut items = session_cart(session);
+    let line = items
+        .iter()
+        .position(|item| item.product_id == req.product_id && item.variant_id == req.variant_id);
+    let in_cart = line.map_or(0, |index| items[index].quantity);
+
+    if line.is_none() && items.len() >= MAX_SESSION_CART_LINES {
+        return Err(AppError::BadRequest(format!(
+            "A guest cart can hold at most {} items; log in to add more",
+            MAX_SESSION_CART_LINES
+        )));
+    }
+
+    let mut conn = pool.acquire().await?;
+    reservation_repository::check_guest_quantity(&mut conn, req.product_id, req.variant_id, in_cart + req.quantity)
+        .await?;
+
+    match line {
+        Some(index) => items[index].quantity += req.quantity,
+        None => items.push(CartItem {
+            product_id: req.product_id,
+            variant_id: req.variant_id,
+            quantity: req.quantity,
+        }),
+    }
+    save_session_cart(session, &items)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Item added to cart",
+        "cart_size": items.len()
+    })))
+}
+
+async fn set_session_cart_quantity(session: &Session, pool: &PgPool, item: CartItem) -> AppResult<HttpResponse> {
+    let mut items = session_cart(session);
+    let line = items
+        .iter()
+        .position(|line| line.product_id == item.product_id && line.variant_id == item.variant_id);
+    let quantity = item.quantity;
+
+    if quantity == 0 {
+        if let Some(index) = line {
+            items.remove(index);
+        }
+    } else {
+        if line.is_none() && items.len() >= MAX_SESSION_CART_LINES {
+            return Err(AppError::BadRequest(format!(
+                "A guest cart can hold at most {} items; log in to add more",
+                MAX_SESSION_CART_LINES
+            )));
+        }
+
+        let mut conn = pool.acquire().await?;
+        reservation_repository::check_guest_quantity(&mut conn, item.product_id, item.variant_id, quantity).await?;
+
+        match line {
+            Some(index) => items[index].quantity = quantity,
+            None => items.push(item),
         }
     }
+    save_session_cart(session, &items)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Cart updated",
+        "quantity": quantity,
+        "cart_size": items.len()
+    })))
+}
+
+async fn replace_session_cart(session: &Session, pool: &PgPool, items: Vec<CartItem>) -> AppResult<HttpResponse> {
+    if items.len() > MAX_SESSION_CART_LINES {
+        return Err(AppError::BadRequest(format!(
+            "A guest cart can hold at most {} items; log in to add more",
+            MAX_SESSION_CART_LINES
+        )));
+    }
+
+    let mut conn = pool.acquire().await?;
+    for (index, item) in items.iter().enumerate() {
+        reservation_repository::check_guest_quantity(&mut conn, item.product_id, item.variant_id, item.quantity)
+            .await
+            .map_err(|e| cart_repository::about_item(index, e))?;
+    }
+    save_session_cart(session, &items)?;
 
-    // Save cart back to session
-    session.insert(CART_SESSION_KEY, &cart_items)
-        .expect("Failed to save cart to session");
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Cart replaced",
+        "cart_size": items.len()
+    })))
+}
+
+fn remove_from_session_cart(session: &Session, req: &RemoveFromCartRequest) -> AppResult<HttpResponse> {
+    let mut items = session_cart(session);
+    let index = items
+        .iter()
+        .position(|item| item.product_id == req.product_id && item.variant_id == req.variant_id)
+        .ok_or_else(|| AppError::NotFound("Item not found in cart".to_string()))?;
+
+    match req.quantity {
+        Some(quantity) if quantity < items[index].quantity => items[index].quantity -= quantity,
+        _ => {
+            items.remove(index);
+        }
+    }
+    save_session_cart(session, &items)?;
 
     Ok(HttpResponse::Ok().json(json!({
         "message": "Item removed from cart",
-        "cart_size": cart_items.len()
+        "cart_size": items.len()
+    })))
+}
+
+/// Recheck every line against current stock and prices and fix the cart to match: lines that
+/// can't be bought are dropped, quantities cut to the stock left and new prices accepted.
+/// The issues are returned so the buyer can review the changes before checking out.
+pub async fn validate_cart(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let issues = cart_repository::reconcile(pool.get_ref(), user_id, config.cart_reservation_minutes).await?;
+    let cart_size = cart_repository::count_items(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": if issues.is_empty() { "Cart is up to date" } else { "Cart updated to match current stock and prices" },
+        "valid": issues.is_empty(),
+        "issues": issues,
+        "cart_size": cart_size
+    })))
+}
+
+pub async fn save_cart_as_template(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<SaveCartTemplateRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest("Template name must be 1-100 characters".to_string()));
+    }
+
+    let cart_items = cart_repository::get_items(pool.get_ref(), user_id).await?;
+
+    if cart_items.is_empty() {
+        return Err(AppError::BadRequest("Cart is empty".to_string()));
+    }
+
+    let existing = sqlx::query_scalar!(
+        "SELECT id FROM cart_templates WHERE user_id = $1 AND name = $2",
+        user_id,
+        name
+    )
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    if existing.is_some() {
+        return Err(AppError::Conflict("A template with this name already exists".to_string()));
+    }
+
+    let template_id = Uuid::new_v4();
+    let product_ids: Vec<Uuid> = cart_items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<Option<Uuid>> = cart_items.iter().map(|item| item.variant_id).collect();
+    let quantities: Vec<i32> = cart_items.iter().map(|item| item.quantity).collect();
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO cart_templates (id, user_id, name) VALUES ($1, $2, $3)",
+        template_id,
+        user_id,
+        name
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO cart_template_items (template_id, product_id, variant_id, quantity)
+        SELECT $1, item.product_id, item.variant_id, item.quantity
+        FROM UNNEST($2::uuid[], $3::uuid[], $4::int[]) AS item(product_id, variant_id, quantity)
+        "#,
+        template_id,
+        &product_ids,
+        &variant_ids as &[Option<Uuid>],
+        &quantities
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Cart saved as template",
+        "template_id": template_id
+    })))
+}
+
+pub async fn get_cart_templates(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let templates = sqlx::query!(
+        r#"
+        SELECT t.id, t.name, t.created_at,
+               COUNT(ti.product_id) as "item_count!"
+        FROM cart_templates t
+        LEFT JOIN cart_template_items ti ON ti.template_id = t.id
+        WHERE t.user_id = $1
+        GROUP BY t.id
+        ORDER BY t.created_at DESC
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let template_list = templates.iter().map(|template| {
+        json!({
+            "id": template.id,
+            "name": template.name,
+            "item_count": template.item_count,
+            "created_at": template.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "templates": template_list
+    })))
+}
+
+pub async fn apply_cart_template(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    template_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let template_id = template_id.into_inner();
+
+    let owner_id = sqlx::query_scalar!(
+        "SELECT user_id FROM cart_templates WHERE id = $1",
+        template_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Template not found".to_string()))?;
+
+    if owner_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let template_items = sqlx::query!(
+        r#"
+        SELECT ti.product_id, ti.variant_id, ti.quantity, p.name, v.name as "variant_name?", p.min_increment
+        FROM cart_template_items ti
+        JOIN products p ON ti.product_id = p.id
+        LEFT JOIN product_variants v ON ti.variant_id = v.id
+        WHERE ti.template_id = $1
+        "#,
+        template_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let cart_items = cart_repository::get_items(pool.get_ref(), user_id).await?;
+
+    let keys: Vec<_> = template_items.iter().map(|item| (item.product_id, item.variant_id)).collect();
+    let mut conn = pool.acquire().await?;
+    let available = reservation_repository::available_stock(&mut conn, &keys, Some(user_id)).await?;
+
+    let mut updates = vec![];
+    let mut unavailable = vec![];
+    let mut adjusted = vec![];
+
+    for item in &template_items {
+        let stock = available.get(&(item.product_id, item.variant_id)).copied().unwrap_or(0);
+        let stock = reservation_repository::whole_increments(stock, item.min_increment);
+        if stock <= 0 {
+            unavailable.push(json!({
+                "product_id": item.product_id,
+                "variant_id": item.variant_id,
+                "name": item.name,
+                "variant_name": item.variant_name,
+                "requested_quantity": item.quantity
+            }));
+            continue;
+        }
+
+        // Merge with what is already in the cart, never exceeding stock not held by others
+        let in_cart = cart_items
+            .iter()
+            .find(|cart_item| cart_item.product_id == item.product_id && cart_item.variant_id == item.variant_id)
+            .map(|cart_item| cart_item.quantity)
+            .unwrap_or(0);
+        let wanted = in_cart + item.quantity;
+        let quantity = reservation_repository::whole_increments(wanted, item.min_increment)
+            .max(item.min_increment)
+            .min(stock);
+
+        if quantity != wanted {
+            adjusted.push(json!({
+                "product_id": item.product_id,
+                "variant_id": item.variant_id,
+                "name": item.name,
+                "variant_name": item.variant_name,
+                "requested_quantity": wanted,
+                "quantity": quantity
+            }));
+        }
+
+        updates.push(CartItem {
+            product_id: item.product_id,
+            variant_id: item.variant_id,
+            quantity,
+        });
+    }
+
+    cart_repository::set_quantities(pool.get_ref(), user_id, &updates, config.cart_reservation_minutes).await?;
+
+    let cart_size = cart_repository::count_items(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Template applied to cart",
+        "cart_size": cart_size,
+        "adjusted_items": adjusted,
+        "unavailable_items": unavailable
     })))
-}
\ No newline at end of file
+}
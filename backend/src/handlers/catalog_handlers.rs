@@ -0,0 +1,345 @@
+// handlers/catalog_handlers.rs
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpResponse};
+use bigdecimal::{BigDecimal, Zero};
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::cache::{Cache, Namespace};
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+use crate::handlers::product_handlers::initial_review_status;
+use crate::handlers::upload_handlers::malformed_upload;
+use crate::models::ProductReviewStatus;
+
+const MAX_IMPORT_SIZE: usize = 2 * 1024 * 1024; // 2 MB
+const MAX_IMPORT_ROWS: usize = 5000;
+const EXPORT_PAGE_SIZE: i64 = 500;
+const EXPORT_COLUMNS: [&str; 8] = [
+    "id", "name", "description", "price_per_unit", "stock_qty", "category_id", "image_url", "created_at",
+];
+
+/// A validated CSV row, ready to insert
+struct ImportRow {
+    name: String,
+    description: Option<String>,
+    price_per_unit: BigDecimal,
+    stock_qty: i32,
+    category_id: i32,
+    image_url: Option<String>,
+}
+
+/// Create products from an uploaded CSV (multipart field `file`). Every row is validated
+/// first; if any row is invalid nothing is inserted and the errors are reported per row.
+pub async fn import_products(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    config: web::Data<Config>,
+    mut payload: Multipart,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    require_supplier(pool.get_ref(), seller_id).await?;
+
+    let data = read_csv_upload(&mut payload).await?;
+
+    let category_ids: HashSet<i32> = sqlx::query_scalar!("SELECT id FROM categories")
+        .fetch_all(pool.get_ref())
+        .await?
+        .into_iter()
+        .collect();
+
+    let rows = parse_rows(&data, &category_ids)?;
+    if rows.is_empty() {
+        return Err(AppError::BadRequest("The CSV has no product rows".to_string()));
+    }
+
+    let product_ids: Vec<Uuid> = rows.iter().map(|_| Uuid::new_v4()).collect();
+    let names: Vec<String> = rows.iter().map(|row| row.name.clone()).collect();
+    let descriptions: Vec<Option<String>> = rows.iter().map(|row| row.description.clone()).collect();
+    let prices: Vec<BigDecimal> = rows.iter().map(|row| row.price_per_unit.clone()).collect();
+    let stock: Vec<i32> = rows.iter().map(|row| row.stock_qty).collect();
+    let categories: Vec<i32> = rows.iter().map(|row| row.category_id).collect();
+    let image_urls: Vec<Option<String>> = rows.iter().map(|row| row.image_url.clone()).collect();
+    let review_status = initial_review_status(&config);
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO products (id, name, description, price_per_unit, stock_qty, seller_id, category_id, image_url,
+                              review_status)
+        SELECT row.id, row.name, row.description, row.price, row.stock, $1, row.category_id, row.image_url, $9
+        FROM UNNEST($2::uuid[], $3::text[], $4::text[], $5::numeric[], $6::int[], $7::int[], $8::text[])
+            AS row(id, name, description, price, stock, category_id, image_url)
+        "#,
+        seller_id,
+        &product_ids,
+        &names,
+        &descriptions as &[Option<String>],
+        &prices,
+        &stock,
+        &categories,
+        &image_urls as &[Option<String>],
+        review_status as ProductReviewStatus
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    // Opening stock goes into the inventory history in one statement, like the products themselves
+    let movement_ids: Vec<Uuid> = product_ids.iter().map(|_| Uuid::new_v4()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO inventory_movements (id, product_id, quantity_change, stock_after, reason, actor_id)
+        SELECT row.id, row.product_id, row.stock, row.stock, 'import', $1
+        FROM UNNEST($2::uuid[], $3::uuid[], $4::int[]) AS row(id, product_id, stock)
+        WHERE row.stock > 0
+        "#,
+        seller_id,
+        &movement_ids,
+        &product_ids,
+        &stock
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    // An image_url becomes a one-image gallery, as with create_product
+    let (imaged_products, urls): (Vec<Uuid>, Vec<String>) = product_ids
+        .iter()
+        .zip(&image_urls)
+        .filter_map(|(product_id, url)| url.clone().map(|url| (*product_id, url)))
+        .unzip();
+    let image_ids: Vec<Uuid> = urls.iter().map(|_| Uuid::new_v4()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO product_images (id, product_id, url, position)
+        SELECT image.id, image.product_id, image.url, 0
+        FROM UNNEST($1::uuid[], $2::uuid[], $3::text[]) AS image(id, product_id, url)
+        "#,
+        &image_ids,
+        &imaged_products,
+        &urls
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": format!("Imported {} products", product_ids.len()),
+        "imported": product_ids.len(),
+        "product_ids": product_ids,
+        "review_status": review_status
+    })))
+}
+
+/// Stream the supplier's whole catalog as CSV, a page of products at a time
+pub async fn export_products(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    require_supplier(pool.get_ref(), seller_id).await?;
+
+    let column_names = csv_line(EXPORT_COLUMNS.iter().map(|column| column.to_string()))?;
+    let pool = pool.get_ref().clone();
+
+    // Keyset pagination on id; the state is the last id sent, None once finished
+    let pages = stream::try_unfold(Some(Uuid::nil()), move |after| {
+        let pool = pool.clone();
+        async move {
+            let Some(after) = after else {
+                return Ok(None);
+            };
+
+            let products = sqlx::query!(
+                r#"
+                SELECT id, name, description, price_per_unit, stock_qty, category_id, image_url, created_at
+                FROM products
+                WHERE seller_id = $1 AND id > $2 AND deleted_at IS NULL
+                ORDER BY id
+                LIMIT $3
+                "#,
+                seller_id,
+                after,
+                EXPORT_PAGE_SIZE
+            )
+                .fetch_all(&pool)
+                .await?;
+
+            if products.is_empty() {
+                return Ok(None);
+            }
+
+            let mut chunk = BytesMut::new();
+            for product in &products {
+                chunk.extend_from_slice(&csv_line([
+                    product.id.to_string(),
+                    product.name.clone(),
+                    product.description.clone().unwrap_or_default(),
+                    product.price_per_unit.to_string(),
+                    product.stock_qty.to_string(),
+                    product.category_id.to_string(),
+                    product.image_url.clone().unwrap_or_default(),
+                    product.created_at.to_rfc3339(),
+                ])?);
+            }
+
+            let next = (products.len() as i64 == EXPORT_PAGE_SIZE).then(|| products[products.len() - 1].id);
+            Ok::<_, AppError>(Some((chunk.freeze(), next)))
+        }
+    });
+
+    let body = stream::once(async move { Ok::<_, AppError>(column_names) }).chain(pages);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"products.csv\""))
+        .streaming(body))
+}
+
+async fn require_supplier(pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+    let is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1",
+        user_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+/// Read the `file` field of the upload, capped at MAX_IMPORT_SIZE
+async fn read_csv_upload(payload: &mut Multipart) -> AppResult<Bytes> {
+    let mut data = BytesMut::new();
+
+    while let Some(mut field) = payload.try_next().await.map_err(malformed_upload)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        while let Some(chunk) = field.try_next().await.map_err(malformed_upload)? {
+            if data.len() + chunk.len() > MAX_IMPORT_SIZE {
+                return Err(AppError::PayloadTooLarge("CSV file exceeds 2MB limit".to_string()));
+            }
+            data.extend_from_slice(&chunk);
+        }
+    }
+
+    if data.is_empty() {
+        return Err(AppError::BadRequest("No file uploaded".to_string()));
+    }
+    Ok(data.freeze())
+}
+
+/// Validate every row, collecting all problems rather than stopping at the first.
+/// Rows are numbered as in a spreadsheet, with the header on row 1.
+fn parse_rows(data: &[u8], category_ids: &HashSet<i32>) -> AppResult<Vec<ImportRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::BadRequest(format!("Unreadable CSV header: {}", e)))?
+        .clone();
+    let column = |name: &str| headers.iter().position(|header| header.eq_ignore_ascii_case(name));
+
+    let (Some(name_col), Some(price_col), Some(stock_col), Some(category_col)) =
+        (column("name"), column("price_per_unit"), column("stock_qty"), column("category_id"))
+    else {
+        return Err(AppError::BadRequest(
+            "CSV must have name, price_per_unit, stock_qty and category_id columns".to_string(),
+        ));
+    };
+    let description_col = column("description");
+    let image_col = column("image_url");
+
+    let mut rows = vec![];
+    let mut invalid_rows = vec![];
+
+    for (index, record) in reader.records().enumerate() {
+        if index >= MAX_IMPORT_ROWS {
+            return Err(AppError::BadRequest(format!("At most {} products per import", MAX_IMPORT_ROWS)));
+        }
+        let row_number = index + 2;
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                invalid_rows.push(json!({ "row": row_number, "errors": [format!("Unreadable row: {}", e)] }));
+                continue;
+            }
+        };
+        let field = |col: usize| record.get(col).unwrap_or("");
+        let optional = |col: Option<usize>| col.map(field).filter(|value| !value.is_empty()).map(str::to_string);
+
+        let mut errors = vec![];
+
+        let name = field(name_col);
+        if name.is_empty() || name.chars().count() > 255 {
+            errors.push("name is required and must be at most 255 characters".to_string());
+        }
+
+        let price_per_unit = BigDecimal::from_str(field(price_col))
+            .ok()
+            .filter(|price| *price > BigDecimal::zero());
+        if price_per_unit.is_none() {
+            errors.push("price_per_unit must be a positive number".to_string());
+        }
+
+        let stock_qty = field(stock_col).parse::<i32>().ok().filter(|qty| *qty >= 0);
+        if stock_qty.is_none() {
+            errors.push("stock_qty must be a whole number of at least 0".to_string());
+        }
+
+        let category_id = field(category_col).parse::<i32>().ok().filter(|id| category_ids.contains(id));
+        if category_id.is_none() {
+            errors.push(format!("category_id '{}' is not a known category", field(category_col)));
+        }
+
+        match (price_per_unit, stock_qty, category_id) {
+            (Some(price_per_unit), Some(stock_qty), Some(category_id)) if errors.is_empty() => {
+                rows.push(ImportRow {
+                    name: name.to_string(),
+                    description: optional(description_col),
+                    price_per_unit: price_per_unit.round(2),
+                    stock_qty,
+                    category_id,
+                    image_url: optional(image_col),
+                });
+            }
+            _ => invalid_rows.push(json!({ "row": row_number, "errors": errors })),
+        }
+    }
+
+    if !invalid_rows.is_empty() {
+        return Err(AppError::InvalidRows(invalid_rows));
+    }
+    Ok(rows)
+}
+
+/// One CSV-encoded line, quoting fields as needed
+pub fn csv_line<I>(fields: I) -> AppResult<Bytes>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .write_record(fields)
+        .map_err(|_| AppError::InternalError)?;
+    let line = writer.into_inner().map_err(|_| AppError::InternalError)?;
+    Ok(Bytes::from(line))
+}
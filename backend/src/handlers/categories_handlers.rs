@@ -1,59 +1,362 @@
 // handlers/categories_handlers.rs
+use actix_identity::Identity;
 use actix_web::{web, HttpResponse};
 use serde_json::json;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 
-use crate::errors::AppResult;
-use crate::models::Category;
+use crate::cache::{Cache, Namespace};
+use crate::errors::{AppError, AppResult};
+use crate::models::{Category, CreateCategoryRequest, MergeCategoryRequest, RenameCategoryRequest, SetCategoryTaxRequest};
+use crate::utils::get_user_id;
+use crate::validation::Validate;
 
+/// Every category, flat; `parent_id` links subcategories to their parent
 pub async fn get_categories(
     pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
 ) -> AppResult<HttpResponse> {
-    // Fetch all unique categories from products
-    let categories = sqlx::query_as!(
+    let categories = cache.fetch(Namespace::Categories, "all", async {
+        let categories = sqlx::query_as!(
+            Category,
+            "SELECT id, name, parent_id FROM categories ORDER BY name"
+        )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+        Ok(json!({
+            "categories": categories
+        }))
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(categories))
+}
+
+pub async fn get_category_by_id(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+) -> AppResult<HttpResponse> {
+    let category_id = path.into_inner();
+
+    let category = sqlx::query_as!(
+        Category,
+        "SELECT id, name, parent_id FROM categories WHERE id = $1",
+        category_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Category not found".to_string()))?;
+
+    let subcategories = sqlx::query_as!(
         Category,
+        "SELECT id, name, parent_id FROM categories WHERE parent_id = $1 ORDER BY name",
+        category_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // Available products in the category or any of its descendants
+    let product_count = sqlx::query_scalar!(
         r#"
-        SELECT DISTINCT category_id as id, category_name as name
+        SELECT COUNT(*) as "count!"
         FROM products
-        WHERE stock_qty > 0
-        ORDER BY category_name
-        "#
+        WHERE category_id IN (SELECT category_subtree($1))
+          AND stock_qty > 0 AND taken_down_at IS NULL AND deleted_at IS NULL
+          AND review_status = 'approved'
+        "#,
+        category_id
     )
-    .fetch_all(pool.get_ref())
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    // The category's own rate, if set, and the one its products are taxed at
+    let tax = sqlx::query!(
+        r#"SELECT tax_rate, category_tax_rate(id) as "effective_tax_rate!" FROM categories WHERE id = $1"#,
+        category_id
+    )
+    .fetch_one(pool.get_ref())
     .await?;
 
     Ok(HttpResponse::Ok().json(json!({
-        "categories": categories
+        "id": category.id,
+        "name": category.name,
+        "parent_id": category.parent_id,
+        "tax_rate": tax.tax_rate,
+        "effective_tax_rate": tax.effective_tax_rate,
+        "subcategories": subcategories,
+        "product_count": product_count
     })))
 }
 
-pub async fn get_category_by_id(
+// The handlers below are admin routes, behind the RequireAdmin middleware
+
+pub async fn create_category(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    req: web::Json<CreateCategoryRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let name = category_name(&req.name)?;
+
+    let mut conn = pool.acquire().await?;
+
+    if let Some(parent_id) = req.parent_id {
+        lock_category(&mut conn, parent_id)
+            .await
+            .map_err(|_| AppError::BadRequest("Parent category not found".to_string()))?;
+    }
+    ensure_name_free(&mut conn, &name, None).await?;
+
+    let category = sqlx::query_as!(
+        Category,
+        "INSERT INTO categories (name, parent_id) VALUES ($1, $2) RETURNING id, name, parent_id",
+        name,
+        req.parent_id
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+
+    categories_changed(&cache).await;
+    tracing::info!(%admin_id, category_id = category.id, "Admin created category");
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Category created successfully",
+        "category": category
+    })))
+}
+
+/// Rename a category; products pick up the new name through the sync trigger
+pub async fn rename_category(
+    identity: Identity,
     pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
     path: web::Path<i32>,
+    req: web::Json<RenameCategoryRequest>,
 ) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
     let category_id = path.into_inner();
-    
-    // Fetch specific category with product count
+    let name = category_name(&req.name)?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_category(&mut tx, category_id).await?;
+    ensure_name_free(&mut tx, &name, Some(category_id)).await?;
+
+    let category = sqlx::query_as!(
+        Category,
+        "UPDATE categories SET name = $2 WHERE id = $1 RETURNING id, name, parent_id",
+        category_id,
+        name
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    categories_changed(&cache).await;
+    tracing::info!(%admin_id, category_id, "Admin renamed category");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Category renamed successfully",
+        "category": category
+    })))
+}
+
+/// Fold a category into another: its products and subcategories move to the
+/// target, then the emptied category is deleted
+pub async fn merge_category(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    path: web::Path<i32>,
+    req: web::Json<MergeCategoryRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let source_id = path.into_inner();
+    let target_id = req.into_id;
+
+    if source_id == target_id {
+        return Err(AppError::BadRequest("Can't merge a category into itself".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    // Lock in id order so two opposite merges can't deadlock
+    for id in [source_id.min(target_id), source_id.max(target_id)] {
+        lock_category(&mut tx, id).await?;
+    }
+
+    let into_descendant = sqlx::query_scalar!(
+        r#"SELECT $2 IN (SELECT category_subtree($1)) as "into_descendant!""#,
+        source_id,
+        target_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if into_descendant {
+        return Err(AppError::BadRequest(
+            "Can't merge a category into one of its own subcategories".to_string(),
+        ));
+    }
+
+    let moved_products = sqlx::query!(
+        "UPDATE products SET category_id = $2 WHERE category_id = $1",
+        source_id,
+        target_id
+    )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    sqlx::query!(
+        "UPDATE categories SET parent_id = $2 WHERE parent_id = $1",
+        source_id,
+        target_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!("DELETE FROM categories WHERE id = $1", source_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    categories_changed(&cache).await;
+    tracing::info!(%admin_id, source_id, target_id, moved_products, "Admin merged categories");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Categories merged successfully",
+        "category_id": target_id,
+        "moved_products": moved_products
+    })))
+}
+
+/// Set the category's tax rate, or clear it to use its parent's. Existing orders keep
+/// the rate they were charged.
+pub async fn set_category_tax_rate(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: web::Json<SetCategoryTaxRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let category_id = path.into_inner();
+    req.validate()?;
+
+    let tax_rate = req.tax_rate.as_ref().map(|tax_rate| tax_rate.round(2));
+
     let category = sqlx::query!(
         r#"
-        SELECT DISTINCT category_id, category_name, COUNT(*) as product_count
-        FROM products
-        WHERE category_id = $1 AND stock_qty > 0
-        GROUP BY category_id, category_name
+        UPDATE categories SET tax_rate = $2 WHERE id = $1
+        RETURNING tax_rate, category_tax_rate(id) as "effective_tax_rate!"
+        "#,
+        category_id,
+        tax_rate
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Category not found".to_string()))?;
+
+    tracing::info!(%admin_id, category_id, tax_rate = ?category.tax_rate, "Admin set category tax rate");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Tax rate updated",
+        "category_id": category_id,
+        "tax_rate": category.tax_rate,
+        "effective_tax_rate": category.effective_tax_rate
+    })))
+}
+
+/// Delete an empty category; one that still has products or subcategories is a conflict
+pub async fn delete_category(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    path: web::Path<i32>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let category_id = path.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    lock_category(&mut tx, category_id).await?;
+
+    let in_use = sqlx::query!(
+        r#"
+        SELECT
+            EXISTS(SELECT 1 FROM products WHERE category_id = $1) as "has_products!",
+            EXISTS(SELECT 1 FROM categories WHERE parent_id = $1) as "has_subcategories!"
         "#,
         category_id
     )
-    .fetch_optional(pool.get_ref())
-    .await?;
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if in_use.has_products {
+        return Err(AppError::Conflict(
+            "Category still has products; merge it into another category instead".to_string(),
+        ));
+    }
+    if in_use.has_subcategories {
+        return Err(AppError::Conflict("Category still has subcategories".to_string()));
+    }
+
+    sqlx::query!("DELETE FROM categories WHERE id = $1", category_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
 
-    match category {
-        Some(cat) => Ok(HttpResponse::Ok().json(json!({
-            "id": cat.category_id,
-            "name": cat.category_name,
-            "product_count": cat.product_count
-        }))),
-        None => Ok(HttpResponse::NotFound().json(json!({
-            "error": "Category not found or no products available"
-        })))
-    }
-}
\ No newline at end of file
+    categories_changed(&cache).await;
+    tracing::info!(%admin_id, category_id, "Admin deleted category");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Category deleted successfully"
+    })))
+}
+
+/// The category list is cached, and so are product listings, which show category names
+async fn categories_changed(cache: &Cache) {
+    cache.invalidate(Namespace::Categories).await;
+    cache.invalidate(Namespace::Products).await;
+}
+
+async fn lock_category(conn: &mut PgConnection, category_id: i32) -> AppResult<()> {
+    sqlx::query_scalar!("SELECT id FROM categories WHERE id = $1 FOR UPDATE", category_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Category not found".to_string()))?;
+
+    Ok(())
+}
+
+/// Names are unique regardless of case
+async fn ensure_name_free(conn: &mut PgConnection, name: &str, except_id: Option<i32>) -> AppResult<()> {
+    let taken = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM categories
+            WHERE LOWER(name) = LOWER($1) AND ($2::int IS NULL OR id <> $2)
+        ) as "exists!"
+        "#,
+        name,
+        except_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    if taken {
+        return Err(AppError::Conflict(format!("Category {} already exists", name)));
+    }
+    Ok(())
+}
+
+fn category_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::BadRequest("Category name must be 1-100 characters".to_string()));
+    }
+    Ok(name.to_string())
+}
@@ -0,0 +1,306 @@
+// handlers/coupon_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use bigdecimal::{BigDecimal, Zero};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{ApplyCouponRequest, Coupon, CreateCouponRequest, DiscountType, OrderStatus};
+use crate::utils::get_user_id;
+
+pub async fn create_coupon(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<CreateCouponRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+
+    let is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1",
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+
+    let code = normalize_code(&req.code)?;
+
+    if req.discount_value <= BigDecimal::zero() {
+        return Err(AppError::BadRequest("discount_value must be greater than zero".to_string()));
+    }
+    if req.discount_type == DiscountType::Percentage && req.discount_value > 100 {
+        return Err(AppError::BadRequest("A percentage discount can't exceed 100".to_string()));
+    }
+    if req.min_order_value.as_ref().is_some_and(|value| *value < BigDecimal::zero()) {
+        return Err(AppError::BadRequest("min_order_value can't be negative".to_string()));
+    }
+    if req.max_uses_per_user.is_some_and(|uses| uses <= 0) {
+        return Err(AppError::BadRequest("max_uses_per_user must be greater than zero".to_string()));
+    }
+    if req.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now()) {
+        return Err(AppError::BadRequest("expires_at must be in the future".to_string()));
+    }
+
+    let code_taken = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM coupons WHERE code = $1) as "exists!""#,
+        code
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if code_taken {
+        return Err(AppError::Conflict(format!("Coupon code {} is already in use", code)));
+    }
+
+    let coupon = sqlx::query_as!(
+        Coupon,
+        r#"
+        INSERT INTO coupons (id, seller_id, code, discount_type, discount_value,
+                             min_order_value, max_uses_per_user, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id, seller_id, code, discount_type as "discount_type: DiscountType",
+                  discount_value, min_order_value, max_uses_per_user, expires_at,
+                  is_active, created_at
+        "#,
+        Uuid::new_v4(),
+        seller_id,
+        code,
+        req.discount_type.clone() as DiscountType,
+        req.discount_value.round(2),
+        req.min_order_value.as_ref().map(|value| value.round(2)),
+        req.max_uses_per_user,
+        req.expires_at
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Coupon created successfully",
+        "coupon": coupon
+    })))
+}
+
+/// The supplier's own coupons, newest first, with how many orders used each
+pub async fn get_coupons(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+
+    let coupons = sqlx::query!(
+        r#"
+        SELECT c.id, c.code, c.discount_type as "discount_type: DiscountType", c.discount_value,
+               c.min_order_value, c.max_uses_per_user, c.expires_at, c.is_active, c.created_at,
+               (SELECT COUNT(*) FROM orders o
+                WHERE o.coupon_id = c.id AND o.status <> $2) as "times_used!"
+        FROM coupons c
+        WHERE c.seller_id = $1
+        ORDER BY c.created_at DESC
+        "#,
+        seller_id,
+        OrderStatus::Cancelled as OrderStatus
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let coupons = coupons.iter().map(|coupon| {
+        json!({
+            "id": coupon.id,
+            "code": coupon.code,
+            "discount_type": coupon.discount_type,
+            "discount_value": coupon.discount_value,
+            "min_order_value": coupon.min_order_value,
+            "max_uses_per_user": coupon.max_uses_per_user,
+            "expires_at": coupon.expires_at,
+            "is_active": coupon.is_active,
+            "times_used": coupon.times_used,
+            "created_at": coupon.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "coupons": coupons
+    })))
+}
+
+/// Attach a coupon to the buyer's cart; the discount is taken at checkout
+pub async fn apply_coupon(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<ApplyCouponRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let code = req.code.trim().to_uppercase();
+
+    let mut conn = pool.acquire().await?;
+
+    let coupon = sqlx::query_as!(
+        Coupon,
+        r#"
+        SELECT id, seller_id, code, discount_type as "discount_type: DiscountType",
+               discount_value, min_order_value, max_uses_per_user, expires_at,
+               is_active, created_at
+        FROM coupons
+        WHERE code = $1
+        "#,
+        code
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Coupon not found".to_string()))?;
+
+    let subtotal = seller_cart_subtotal(&mut conn, user_id, coupon.seller_id).await?;
+    check_coupon(&mut conn, &coupon, user_id, &subtotal).await?;
+
+    sqlx::query!(
+        "UPDATE carts SET coupon_id = $2, updated_at = NOW() WHERE user_id = $1",
+        user_id,
+        coupon.id
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Coupon applied",
+        "code": coupon.code,
+        "discount": discount_for(&coupon, &subtotal)
+    })))
+}
+
+pub async fn remove_coupon(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    sqlx::query!(
+        "UPDATE carts SET coupon_id = NULL, updated_at = NOW() WHERE user_id = $1",
+        user_id
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Coupon removed"
+    })))
+}
+
+/// The coupon applied to the user's cart, if any. Locks the coupon row so per-user
+/// limits hold against concurrent checkouts that use the same coupon.
+pub async fn cart_coupon(conn: &mut PgConnection, user_id: Uuid) -> AppResult<Option<Coupon>> {
+    let coupon = sqlx::query_as!(
+        Coupon,
+        r#"
+        SELECT c.id, c.seller_id, c.code, c.discount_type as "discount_type: DiscountType",
+               c.discount_value, c.min_order_value, c.max_uses_per_user, c.expires_at,
+               c.is_active, c.created_at
+        FROM carts ca
+        JOIN coupons c ON ca.coupon_id = c.id
+        WHERE ca.user_id = $1
+        FOR UPDATE OF c
+        "#,
+        user_id
+    )
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(coupon)
+}
+
+/// Reject a coupon that can't be used by this buyer on `subtotal` worth of the seller's items
+pub async fn check_coupon(
+    conn: &mut PgConnection,
+    coupon: &Coupon,
+    buyer_id: Uuid,
+    subtotal: &BigDecimal,
+) -> AppResult<()> {
+    if !coupon.is_active || coupon.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now()) {
+        return Err(AppError::BadRequest(format!("Coupon {} has expired", coupon.code)));
+    }
+
+    if *subtotal <= BigDecimal::zero() {
+        return Err(AppError::BadRequest(format!(
+            "Coupon {} doesn't apply to any item in your cart",
+            coupon.code
+        )));
+    }
+
+    if let Some(min_order_value) = &coupon.min_order_value
+        && subtotal < min_order_value
+    {
+        return Err(AppError::BadRequest(format!(
+            "Coupon {} needs at least {} of this seller's items",
+            coupon.code, min_order_value
+        )));
+    }
+
+    if let Some(max_uses) = coupon.max_uses_per_user {
+        let uses = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM orders
+            WHERE coupon_id = $1 AND buyer_id = $2 AND status <> $3
+            "#,
+            coupon.id,
+            buyer_id,
+            OrderStatus::Cancelled as OrderStatus
+        )
+            .fetch_one(conn)
+            .await?;
+
+        if uses >= i64::from(max_uses) {
+            return Err(AppError::BadRequest(format!(
+                "You have already used coupon {} the maximum number of times",
+                coupon.code
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Discount the coupon takes off `subtotal`, never more than the subtotal itself
+pub fn discount_for(coupon: &Coupon, subtotal: &BigDecimal) -> BigDecimal {
+    let discount = match coupon.discount_type {
+        DiscountType::Percentage => (subtotal * &coupon.discount_value / BigDecimal::from(100)).round(2),
+        DiscountType::Fixed => coupon.discount_value.clone(),
+    };
+
+    discount.min(subtotal.clone())
+}
+
+/// Value of the seller's items in the user's cart at current prices, price tiers applied
+pub async fn seller_cart_subtotal(conn: &mut PgConnection, user_id: Uuid, seller_id: Uuid) -> AppResult<BigDecimal> {
+    let subtotal = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(SUM(unit_price(ci.product_id, ci.variant_id, ci.quantity) * ci.quantity), 0) as "subtotal!"
+        FROM cart_items ci
+        JOIN products p ON ci.product_id = p.id
+        WHERE ci.user_id = $1 AND p.seller_id = $2
+        "#,
+        user_id,
+        seller_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    Ok(subtotal)
+}
+
+/// Codes are stored upper-case: 3-32 letters, digits, '-' or '_'
+fn normalize_code(code: &str) -> AppResult<String> {
+    let code = code.trim().to_uppercase();
+    if !(3..=32).contains(&code.len())
+        || !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(AppError::BadRequest(
+            "Coupon code must be 3-32 letters, digits, '-' or '_'".to_string(),
+        ));
+    }
+    Ok(code)
+}
@@ -0,0 +1,260 @@
+// handlers/deal_handlers.rs
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::cache::{Cache, Namespace};
+use crate::errors::{AppError, AppResult};
+use crate::models::{CreateDealRequest, Deal, PaginationQuery, ProductReviewStatus, ProductUnit, ProductWithSeller};
+use crate::utils::Pagination;
+use crate::validation::Validate;
+
+/// Listed products with a deal on now and units left, ending soonest first
+pub async fn get_active_deals(
+    user: Option<AuthUser>,
+    pool: web::Data<PgPool>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let viewer_id = user.map(|user| user.id);
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let products = sqlx::query_as!(
+        ProductWithSeller,
+        r#"
+        SELECT
+            p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+            p.unit as "unit: ProductUnit", p.min_increment, p.sku, p.barcode,
+            p.image_url, p.seller_id, p.category_id, p.created_at,
+            c.name as category_name,
+            u.name as seller_name, u.name as seller_company,
+            u.rating as seller_rating, u.total_deliveries as seller_deliveries,
+            u.min_order_value as seller_min_order_value, u.delivery_fee as seller_delivery_fee,
+            COALESCE(
+                (SELECT json_agg(json_build_object('id', pi.id, 'url', pi.url) ORDER BY pi.position)
+                 FROM product_images pi WHERE pi.product_id = p.id),
+                '[]'::json
+            ) as "images!",
+            COALESCE(
+                (SELECT json_agg(json_build_object('min_qty', t.min_qty, 'unit_price', t.unit_price::text) ORDER BY t.min_qty)
+                 FROM price_tiers t WHERE t.product_id = p.id),
+                '[]'::json
+            ) as "price_tiers!",
+            EXISTS(SELECT 1 FROM favorites f WHERE f.product_id = p.id AND f.user_id = $1) as "is_favorited!",
+            NULL::float8 as distance_km,
+            (SELECT COUNT(*) FROM stock_by_location s WHERE s.product_id = p.id AND s.quantity > 0) as "stock_locations!",
+            current_deal(p.id) as deal,
+            NULL::numeric as display_price, NULL::text as display_currency,
+            p.review_status as "review_status: ProductReviewStatus"
+        FROM deals d
+        JOIN products p ON d.product_id = p.id
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE d.starts_at <= NOW() AND d.ends_at > NOW() AND d.sold_quantity < d.max_quantity
+          AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved' AND NOT u.store_paused
+        ORDER BY d.ends_at, d.id
+        LIMIT $2 OFFSET $3
+        "#,
+        viewer_id,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM deals d
+        JOIN products p ON d.product_id = p.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE d.starts_at <= NOW() AND d.ends_at > NOW() AND d.sold_quantity < d.max_quantity
+          AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved' AND NOT u.store_paused
+        "#
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "products": products,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// The product's deals, past ones included, latest first (owner only)
+pub async fn get_product_deals(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let product_id = product_id.into_inner();
+
+    let mut conn = pool.acquire().await?;
+    lock_own_product(&mut conn, product_id, user.id, false).await?;
+
+    let deals = sqlx::query_as!(
+        Deal,
+        r#"
+        SELECT id, product_id, deal_price, starts_at, ends_at, max_quantity, sold_quantity, created_at
+        FROM deals
+        WHERE product_id = $1
+        ORDER BY starts_at DESC
+        "#,
+        product_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "product_id": product_id,
+        "deals": deals
+    })))
+}
+
+/// Put the product on a deal (owner only). Deals are below the list price, don't overlap
+/// the product's other deals, and aren't for products priced per variant.
+pub async fn create_deal(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<CreateDealRequest>,
+) -> AppResult<HttpResponse> {
+    let product_id = product_id.into_inner();
+    req.validate()?;
+
+    let deal_price = req.deal_price.round(2);
+    let starts_at = req.starts_at.unwrap_or_else(Utc::now).max(Utc::now());
+
+    // Locked like a checkout, so a deal can't change under an order claiming it
+    let mut tx = pool.begin().await?;
+    lock_own_product(&mut tx, product_id, user.id, true).await?;
+
+    let product = sqlx::query!(
+        r#"
+        SELECT price_per_unit,
+               EXISTS(SELECT 1 FROM product_variants v WHERE v.product_id = products.id) as "has_variants!",
+               EXISTS(SELECT 1 FROM deals d
+                      WHERE d.product_id = products.id AND d.starts_at < $3 AND d.ends_at > $2) as "overlaps!"
+        FROM products
+        WHERE id = $1
+        "#,
+        product_id,
+        starts_at,
+        req.ends_at
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if product.has_variants {
+        return Err(AppError::BadRequest(
+            "Products with variants are priced per variant and can't have deals".to_string(),
+        ));
+    }
+    if deal_price >= product.price_per_unit {
+        return Err(AppError::BadRequest(format!(
+            "The deal price must be below the list price of {}",
+            product.price_per_unit
+        )));
+    }
+    if product.overlaps {
+        return Err(AppError::Conflict("The product already has a deal during that time".to_string()));
+    }
+
+    let deal = sqlx::query_as!(
+        Deal,
+        r#"
+        INSERT INTO deals (id, product_id, deal_price, starts_at, ends_at, max_quantity)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, product_id, deal_price, starts_at, ends_at, max_quantity, sold_quantity, created_at
+        "#,
+        Uuid::new_v4(),
+        product_id,
+        deal_price,
+        starts_at,
+        req.ends_at,
+        req.max_quantity
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Deal created",
+        "deal": deal
+    })))
+}
+
+/// End a deal now (owner only); one that hasn't started is removed
+pub async fn end_deal(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> AppResult<HttpResponse> {
+    let (product_id, deal_id) = path.into_inner();
+
+    let mut tx = pool.begin().await?;
+    lock_own_product(&mut tx, product_id, user.id, true).await?;
+
+    let deal = sqlx::query!(
+        "SELECT starts_at, ends_at FROM deals WHERE id = $1 AND product_id = $2",
+        deal_id,
+        product_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Deal not found".to_string()))?;
+
+    let now = Utc::now();
+    if deal.ends_at <= now {
+        return Err(AppError::BadRequest("This deal has already ended".to_string()));
+    }
+    if deal.starts_at > now {
+        sqlx::query!("DELETE FROM deals WHERE id = $1", deal_id)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        sqlx::query!("UPDATE deals SET ends_at = NOW() WHERE id = $1", deal_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Deal ended"
+    })))
+}
+
+/// Check the product is the user's, locking its row when changing its deals
+async fn lock_own_product(conn: &mut PgConnection, product_id: Uuid, user_id: Uuid, lock: bool) -> AppResult<()> {
+    let seller_id = if lock {
+        sqlx::query_scalar!(
+            "SELECT seller_id FROM products WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+            product_id
+        )
+            .fetch_optional(conn)
+            .await?
+    } else {
+        sqlx::query_scalar!(
+            "SELECT seller_id FROM products WHERE id = $1 AND deleted_at IS NULL",
+            product_id
+        )
+            .fetch_optional(conn)
+            .await?
+    };
+
+    match seller_id {
+        None => Err(AppError::NotFound("Product not found".to_string())),
+        Some(seller_id) if seller_id != user_id => Err(AppError::Forbidden),
+        Some(_) => Ok(()),
+    }
+}
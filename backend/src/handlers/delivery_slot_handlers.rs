@@ -0,0 +1,151 @@
+// handlers/delivery_slot_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::CreateDeliverySlotRequest;
+use crate::repositories::delivery_slot_repository::{self, DeliverySlot};
+use crate::utils::get_user_id;
+use crate::validation::Validate;
+
+/// Most upcoming slots a seller can have at once
+const MAX_UPCOMING_SLOTS: usize = 500;
+
+pub async fn create_delivery_slot(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<CreateDeliverySlotRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    req.validate()?;
+
+    let is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1",
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+
+    let upcoming = delivery_slot_repository::upcoming(pool.get_ref(), seller_id).await?;
+    if upcoming.len() >= MAX_UPCOMING_SLOTS {
+        return Err(AppError::BadRequest(format!(
+            "You can have at most {} upcoming delivery slots",
+            MAX_UPCOMING_SLOTS
+        )));
+    }
+
+    let slot = sqlx::query_as!(
+        DeliverySlot,
+        r#"
+        INSERT INTO delivery_slots (id, seller_id, starts_at, ends_at, capacity)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, seller_id, starts_at, ends_at, capacity, 0::bigint as "booked!"
+        "#,
+        Uuid::new_v4(),
+        seller_id,
+        req.starts_at,
+        req.ends_at,
+        req.capacity
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Delivery slot created",
+        "delivery_slot": slot_json(&slot)
+    })))
+}
+
+/// The seller's slots that haven't ended, with how many orders each has
+pub async fn get_my_delivery_slots(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+
+    let slots = delivery_slot_repository::upcoming(pool.get_ref(), seller_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "delivery_slots": slots.iter().map(slot_json).collect::<Vec<_>>()
+    })))
+}
+
+/// Remove a slot no live order is booked in; move those orders elsewhere first
+pub async fn delete_delivery_slot(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    slot_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    let slot_id = slot_id.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let slot = delivery_slot_repository::lock(&mut tx, &[slot_id])
+        .await?
+        .pop()
+        .ok_or_else(|| AppError::NotFound("Delivery slot not found".to_string()))?;
+
+    if slot.seller_id != seller_id {
+        return Err(AppError::Forbidden);
+    }
+    if slot.booked > 0 {
+        return Err(AppError::Conflict(format!(
+            "{} order(s) are booked in this slot; reschedule them first",
+            slot.booked
+        )));
+    }
+
+    sqlx::query!("DELETE FROM delivery_slots WHERE id = $1", slot_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Delivery slot deleted"
+    })))
+}
+
+/// A seller's slots a buyer can still book: not yet started and with room left
+pub async fn get_seller_delivery_slots(
+    pool: web::Data<PgPool>,
+    seller_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let now = Utc::now();
+
+    let slots = delivery_slot_repository::upcoming(pool.get_ref(), seller_id.into_inner()).await?;
+    let open_slots = slots
+        .iter()
+        .filter(|slot| slot.starts_at > now && slot.remaining() > 0)
+        .map(|slot| json!({
+            "id": slot.id,
+            "starts_at": slot.starts_at,
+            "ends_at": slot.ends_at,
+            "remaining": slot.remaining()
+        }))
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "delivery_slots": open_slots
+    })))
+}
+
+fn slot_json(slot: &DeliverySlot) -> serde_json::Value {
+    json!({
+        "id": slot.id,
+        "starts_at": slot.starts_at,
+        "ends_at": slot.ends_at,
+        "capacity": slot.capacity,
+        "booked": slot.booked,
+        "remaining": slot.remaining()
+    })
+}
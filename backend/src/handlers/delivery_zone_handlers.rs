@@ -0,0 +1,78 @@
+// handlers/delivery_zone_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::SetDeliveryZoneRequest;
+use crate::repositories::delivery_zone_repository::{self, DeliveryZone};
+use crate::utils::{get_user_id, normalize_pincode};
+use crate::validation::Validate;
+
+/// Where the seller delivers
+pub async fn get_delivery_zone(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+
+    let zone = delivery_zone_repository::find(pool.get_ref(), seller_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "delivery_zone": zone_json(&zone)
+    })))
+}
+
+/// Set where the seller delivers: a list of pincodes, a radius around their location,
+/// or both. An empty list and no radius deliver anywhere. Checkouts to an address
+/// outside the zone are refused.
+pub async fn set_delivery_zone(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<SetDeliveryZoneRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    req.validate()?;
+
+    let seller = sqlx::query!(
+        "SELECT is_supplier, latitude FROM users WHERE id = $1",
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !seller.is_supplier {
+        return Err(AppError::Forbidden);
+    }
+    if req.radius_km.is_some() && seller.latitude.is_none() {
+        return Err(AppError::BadRequest(
+            "Set your location on your profile before delivering within a radius".to_string(),
+        ));
+    }
+
+    let pincodes: Vec<String> = req.pincodes.iter().map(|pincode| normalize_pincode(pincode)).collect();
+
+    let mut tx = pool.begin().await?;
+    delivery_zone_repository::replace(&mut tx, seller_id, &pincodes, req.radius_km).await?;
+    tx.commit().await?;
+
+    let zone = delivery_zone_repository::find(pool.get_ref(), seller_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Delivery zone updated",
+        "delivery_zone": zone_json(&zone)
+    })))
+}
+
+fn zone_json(zone: &DeliveryZone) -> serde_json::Value {
+    json!({
+        "pincodes": zone.pincodes,
+        "radius_km": zone.radius_km,
+        "center": zone.latitude.zip(zone.longitude).map(|(latitude, longitude)| json!({
+            "latitude": latitude,
+            "longitude": longitude
+        })),
+        "delivers_anywhere": zone.pincodes.is_empty() && zone.radius_km.is_none()
+    })
+}
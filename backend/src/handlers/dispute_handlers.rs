@@ -0,0 +1,423 @@
+// handlers/dispute_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use bigdecimal::{BigDecimal, Zero};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+use crate::handlers::notification_handlers::notify;
+use crate::handlers::order_handlers::{announce_order_update, notify_buyer_of_status};
+use crate::mailer::EmailSender;
+use crate::models::{
+    AdminDisputeQuery, Dispute, DisputeOutcome, DisputeResponseRequest, DisputeStatus, NotificationKind,
+    OpenDisputeRequest, OrderStatus, ResolveDisputeRequest,
+};
+use crate::payments::StripeClient;
+use crate::repositories::{ledger_repository, order_repository};
+use crate::utils::{get_user_id, Pagination};
+
+const MAX_EVIDENCE_URLS: usize = 5;
+const MAX_DISPUTE_TEXT: usize = 2000;
+
+/// Buyer opens a dispute on one of their paid, shipped or delivered orders
+pub async fn open_dispute(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<OpenDisputeRequest>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let order_id = order_id.into_inner();
+
+    let reason = dispute_text(&req.reason, "reason")?;
+    let evidence_urls: Vec<String> = req
+        .evidence_urls
+        .iter()
+        .flatten()
+        .map(|url| url.trim().to_string())
+        .collect();
+    if evidence_urls.len() > MAX_EVIDENCE_URLS {
+        return Err(AppError::BadRequest(format!("At most {} evidence images", MAX_EVIDENCE_URLS)));
+    }
+    if evidence_urls.iter().any(|url| url.is_empty()) {
+        return Err(AppError::BadRequest("Evidence URLs cannot be empty".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let order = sqlx::query!(
+        r#"SELECT buyer_id, seller_id, status as "status: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#,
+        order_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != buyer_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let disputed_before = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM disputes WHERE order_id = $1) as "exists!""#,
+        order_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if disputed_before {
+        return Err(AppError::Conflict("This order has already been disputed".to_string()));
+    }
+    if !matches!(
+        order.status,
+        OrderStatus::Paid | OrderStatus::PartiallyShipped | OrderStatus::Shipped | OrderStatus::Delivered
+    ) {
+        return Err(AppError::Conflict(
+            "Only paid, shipped or delivered orders can be disputed".to_string(),
+        ));
+    }
+
+    let status_before =
+        order_repository::transition_status(&mut tx, order_id, OrderStatus::Disputed, Some(buyer_id)).await?;
+
+    let dispute = sqlx::query_as!(
+        Dispute,
+        r#"
+        INSERT INTO disputes (id, order_id, buyer_id, seller_id, order_status_before, reason, evidence_urls)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, order_id, buyer_id, seller_id, reason, evidence_urls,
+                  status as "status: DisputeStatus", seller_response, seller_responded_at,
+                  resolution_note, resolved_by, resolved_at, created_at
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        buyer_id,
+        order.seller_id,
+        status_before as OrderStatus,
+        reason,
+        &evidence_urls
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    announce_order_update(pool.get_ref(), order_id).await?;
+    notify(
+        pool.get_ref(),
+        order.seller_id,
+        NotificationKind::Order,
+        "A buyer disputed an order",
+        json!({ "order_id": order_id, "dispute_id": dispute.id }),
+    ).await?;
+    tracing::info!(%buyer_id, %order_id, dispute_id = %dispute.id, "Dispute opened");
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Dispute opened",
+        "dispute": dispute
+    })))
+}
+
+/// The order's dispute, for its buyer or seller, with the refund if one was made
+pub async fn get_dispute(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let order_id = order_id.into_inner();
+
+    let dispute = sqlx::query_as!(
+        Dispute,
+        r#"
+        SELECT id, order_id, buyer_id, seller_id, reason, evidence_urls,
+               status as "status: DisputeStatus", seller_response, seller_responded_at,
+               resolution_note, resolved_by, resolved_at, created_at
+        FROM disputes
+        WHERE order_id = $1
+        "#,
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Dispute not found".to_string()))?;
+
+    if dispute.buyer_id != user_id && dispute.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let refund = sqlx::query!(
+        "SELECT amount, provider, restocked, created_at FROM refunds WHERE dispute_id = $1",
+        dispute.id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "dispute": dispute,
+        "refund": refund.map(|refund| json!({
+            "amount": refund.amount,
+            "provider": refund.provider,
+            "restocked": refund.restocked,
+            "created_at": refund.created_at
+        }))
+    })))
+}
+
+/// Seller's side of the story, once, while the dispute is still open
+pub async fn respond_to_dispute(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<DisputeResponseRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    let order_id = order_id.into_inner();
+    let response = dispute_text(&req.response, "response")?;
+
+    let dispute = sqlx::query!(
+        r#"SELECT id, seller_id, status as "status: DisputeStatus" FROM disputes WHERE order_id = $1"#,
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Dispute not found".to_string()))?;
+
+    if dispute.seller_id != seller_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE disputes
+        SET seller_response = $2, seller_responded_at = NOW(), status = $3
+        WHERE id = $1 AND status = $4
+        "#,
+        dispute.id,
+        response,
+        DisputeStatus::Responded as DisputeStatus,
+        DisputeStatus::Open as DisputeStatus
+    )
+        .execute(pool.get_ref())
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(AppError::Conflict("This dispute is no longer open for a response".to_string()));
+    }
+
+    tracing::info!(%seller_id, %order_id, dispute_id = %dispute.id, "Seller responded to dispute");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Response recorded"
+    })))
+}
+
+// The handlers below are admin routes, behind the RequireAdmin middleware
+
+pub async fn list_disputes(
+    pool: web::Data<PgPool>,
+    query: web::Query<AdminDisputeQuery>,
+) -> AppResult<HttpResponse> {
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let disputes = sqlx::query!(
+        r#"
+        SELECT d.id, d.order_id, d.status as "status: DisputeStatus", d.reason, d.created_at,
+               d.seller_responded_at, d.resolved_at, o.total_price,
+               b.name as buyer_name, s.name as seller_name
+        FROM disputes d
+        JOIN orders o ON d.order_id = o.id
+        JOIN users b ON d.buyer_id = b.id
+        JOIN users s ON d.seller_id = s.id
+        WHERE ($1::dispute_status IS NULL OR d.status = $1)
+        ORDER BY d.created_at ASC
+        LIMIT $2 OFFSET $3
+        "#,
+        query.status.clone() as Option<DisputeStatus>,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM disputes
+        WHERE ($1::dispute_status IS NULL OR status = $1)
+        "#,
+        query.status.clone() as Option<DisputeStatus>
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let dispute_list = disputes.iter().map(|dispute| {
+        json!({
+            "id": dispute.id,
+            "order_id": dispute.order_id,
+            "status": dispute.status,
+            "reason": dispute.reason,
+            "total_price": dispute.total_price,
+            "buyer_name": dispute.buyer_name,
+            "seller_name": dispute.seller_name,
+            "seller_responded_at": dispute.seller_responded_at,
+            "resolved_at": dispute.resolved_at,
+            "created_at": dispute.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "disputes": dispute_list,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// Settle a dispute. A refund returns the order total (through Stripe when the order was
+/// paid that way), records it in the refunds ledger and can put the items back in stock;
+/// a rejection returns the order to its status before the dispute.
+pub async fn resolve_dispute(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
+    dispute_id: web::Path<Uuid>,
+    req: web::Json<ResolveDisputeRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let dispute_id = dispute_id.into_inner();
+    let note = req.note.as_deref().map(str::trim).filter(|note| !note.is_empty());
+    let restock = req.restock.unwrap_or(false);
+
+    if note.is_some_and(|note| note.chars().count() > MAX_DISPUTE_TEXT) {
+        return Err(AppError::BadRequest(format!("note must be at most {} characters", MAX_DISPUTE_TEXT)));
+    }
+    if restock && req.outcome != DisputeOutcome::Refund {
+        return Err(AppError::BadRequest("Only a refund can restock the order".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let dispute = sqlx::query!(
+        r#"
+        SELECT order_id, status as "status: DisputeStatus",
+               order_status_before as "order_status_before: OrderStatus"
+        FROM disputes
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        dispute_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Dispute not found".to_string()))?;
+
+    if !matches!(dispute.status, DisputeStatus::Open | DisputeStatus::Responded) {
+        return Err(AppError::Conflict("Dispute is already resolved".to_string()));
+    }
+
+    let order_id = dispute.order_id;
+    let (order_status, dispute_status) = match req.outcome {
+        DisputeOutcome::Refund => (OrderStatus::Refunded, DisputeStatus::Refunded),
+        DisputeOutcome::Reject => (dispute.order_status_before, DisputeStatus::Rejected),
+    };
+
+    order_repository::transition_status(&mut tx, order_id, order_status.clone(), Some(admin_id)).await?;
+
+    let mut refunded = None;
+    if req.outcome == DisputeOutcome::Refund {
+        let total_price = sqlx::query_scalar!("SELECT total_price FROM orders WHERE id = $1", order_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        // Money taken through Stripe goes back the same way; other orders were settled
+        // outside the platform and the refund is only recorded
+        let payment_intent = sqlx::query_scalar!(
+            r#"
+            SELECT provider_payment_id FROM payments
+            WHERE order_id = $1 AND provider = 'stripe' AND status = 'succeeded'
+            ORDER BY updated_at DESC
+            LIMIT 1
+            "#,
+            order_id
+        )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let provider_refund = match payment_intent {
+            Some(payment_intent) => {
+                let stripe = StripeClient::from_config(&config)?;
+                Some(stripe.refund_payment_intent(&payment_intent, dispute_id).await?)
+            }
+            None => None,
+        };
+
+        if restock {
+            order_repository::restock_items(&mut tx, order_id, Some(admin_id)).await?;
+        }
+
+        ledger_repository::record_refund(&mut tx, order_id).await?;
+
+        if total_price > BigDecimal::zero() {
+            sqlx::query!(
+                r#"
+                INSERT INTO refunds (id, order_id, dispute_id, amount, provider, provider_refund_id, restocked, created_by)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+                Uuid::new_v4(),
+                order_id,
+                dispute_id,
+                total_price,
+                provider_refund.as_ref().map(|_| "stripe"),
+                provider_refund.as_ref().map(|refund| refund.id.clone()),
+                restock,
+                admin_id
+            )
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        refunded = Some(total_price);
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE disputes
+        SET status = $2, resolution_note = $3, resolved_by = $4, resolved_at = NOW()
+        WHERE id = $1
+        "#,
+        dispute_id,
+        dispute_status.clone() as DisputeStatus,
+        note,
+        admin_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    notify_buyer_of_status(pool.get_ref(), mailer.into_inner(), order_id, &order_status).await?;
+    announce_order_update(pool.get_ref(), order_id).await?;
+
+    tracing::info!(%admin_id, %dispute_id, %order_id, outcome = ?req.outcome, restock, "Admin resolved dispute");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Dispute resolved",
+        "status": dispute_status,
+        "order_status": order_status,
+        "refunded_amount": refunded
+    })))
+}
+
+fn dispute_text(text: &str, field: &str) -> AppResult<String> {
+    let text = text.trim();
+    if text.is_empty() || text.chars().count() > MAX_DISPUTE_TEXT {
+        return Err(AppError::BadRequest(format!(
+            "{} must be 1-{} characters",
+            field, MAX_DISPUTE_TEXT
+        )));
+    }
+    Ok(text.to_string())
+}
@@ -0,0 +1,228 @@
+// handlers/earnings_handlers.rs
+use actix_identity::Identity;
+use actix_web::{http::header, web, HttpResponse};
+use bigdecimal::{BigDecimal, Zero};
+use bytes::BytesMut;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+use crate::handlers::catalog_handlers::csv_line;
+use crate::handlers::notification_handlers::notify;
+use crate::models::{EarningsQuery, NotificationKind, PaginationQuery, RecordPayoutRequest};
+use crate::repositories::ledger_repository;
+use crate::services::currency_service;
+use crate::utils::{get_user_id, Pagination};
+use crate::validation::Validate;
+
+const STATEMENT_COLUMNS: [&str; 9] = [
+    "created_at", "kind", "order_id", "currency", "gross_amount", "commission_amount", "amount", "reference", "id",
+];
+
+/// The seller's balance in each currency and their ledger entries, newest first
+pub async fn get_earnings(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    query: web::Query<EarningsQuery>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    require_supplier(pool.get_ref(), seller_id).await?;
+    check_window(&query)?;
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let mut conn = pool.acquire().await?;
+    let balances = ledger_repository::balances(&mut conn, seller_id).await?;
+    let entries = ledger_repository::entries(
+        pool.get_ref(),
+        seller_id,
+        query.from,
+        query.to,
+        Some(pagination.limit),
+        pagination.offset,
+    ).await?;
+    let total = ledger_repository::count_entries(pool.get_ref(), seller_id, query.from, query.to).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "commission_percent": config.platform_commission_percent,
+        "balances": balances,
+        "entries": entries,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// The seller's ledger entries in the window as CSV, oldest first
+pub async fn export_statement(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    query: web::Query<EarningsQuery>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    require_supplier(pool.get_ref(), seller_id).await?;
+    check_window(&query)?;
+
+    let entries = ledger_repository::entries(pool.get_ref(), seller_id, query.from, query.to, None, 0).await?;
+
+    let mut body = BytesMut::new();
+    body.extend_from_slice(&csv_line(STATEMENT_COLUMNS.iter().map(|column| column.to_string()))?);
+    for entry in entries.iter().rev() {
+        body.extend_from_slice(&csv_line([
+            entry.created_at.to_rfc3339(),
+            entry.kind.as_str().to_string(),
+            entry.order_id.map(|id| id.to_string()).unwrap_or_default(),
+            entry.currency.clone(),
+            entry.gross_amount.to_string(),
+            entry.commission_amount.to_string(),
+            entry.amount.to_string(),
+            entry.reference.clone().unwrap_or_default(),
+            entry.id.to_string(),
+        ])?);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"statement.csv\""))
+        .body(body.freeze()))
+}
+
+/// What the platform owes, per seller and currency, largest first; sellers who are
+/// paid up are left out
+pub async fn list_payouts_due(
+    pool: web::Data<PgPool>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let balances = sqlx::query!(
+        r#"
+        SELECT l.seller_id, u.name as seller_name, u.email as seller_email, l.currency,
+               SUM(l.amount) as "balance!",
+               MAX(l.created_at) FILTER (WHERE l.kind = 'payout') as last_payout_at
+        FROM ledger_entries l
+        JOIN users u ON l.seller_id = u.id
+        GROUP BY l.seller_id, u.name, u.email, l.currency
+        HAVING SUM(l.amount) > 0
+        ORDER BY SUM(l.amount) DESC, l.seller_id
+        LIMIT $1 OFFSET $2
+        "#,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM (
+            SELECT 1 FROM ledger_entries
+            GROUP BY seller_id, currency
+            HAVING SUM(amount) > 0
+        ) due
+        "#
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let balance_list = balances.iter().map(|balance| {
+        json!({
+            "seller_id": balance.seller_id,
+            "seller_name": balance.seller_name,
+            "seller_email": balance.seller_email,
+            "currency": balance.currency,
+            "balance": balance.balance,
+            "last_payout_at": balance.last_payout_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "balances": balance_list,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// Record a payout already sent to the seller. It can't exceed what they're owed in
+/// that currency.
+pub async fn record_payout(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    seller_id: web::Path<Uuid>,
+    req: web::Json<RecordPayoutRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let seller_id = seller_id.into_inner();
+    req.validate()?;
+    let currency = match &req.currency {
+        Some(code) => currency_service::parse(&config, code)?,
+        None => config.base_currency.clone(),
+    };
+    let amount = req.amount.round(2);
+    let reference = req.reference.as_deref().map(str::trim).filter(|reference| !reference.is_empty());
+
+    let mut tx = pool.begin().await?;
+
+    // Payouts to the same seller go one at a time, so two can't both fit the balance
+    sqlx::query_scalar!("SELECT id FROM users WHERE id = $1 AND is_supplier FOR UPDATE", seller_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Seller not found".to_string()))?;
+
+    let balance = ledger_repository::balances(&mut tx, seller_id)
+        .await?
+        .into_iter()
+        .find(|balance| balance.currency == currency)
+        .map(|balance| balance.balance)
+        .unwrap_or_else(BigDecimal::zero);
+
+    if amount > balance {
+        return Err(AppError::Conflict(format!(
+            "The seller is owed {} {}; a payout can't exceed that",
+            balance, currency
+        )));
+    }
+
+    let entry = ledger_repository::record_payout(&mut tx, seller_id, &amount, &currency, reference, admin_id).await?;
+
+    tx.commit().await?;
+
+    notify(
+        pool.get_ref(),
+        seller_id,
+        NotificationKind::Payout,
+        &format!("You were paid {}", currency_service::format(&amount, &currency)),
+        json!({ "ledger_entry_id": entry.id, "amount": amount, "currency": currency }),
+    ).await?;
+    tracing::info!(%admin_id, %seller_id, %amount, %currency, "Admin recorded payout");
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Payout recorded",
+        "entry": entry,
+        "balance": balance - amount
+    })))
+}
+
+fn check_window(query: &EarningsQuery) -> AppResult<()> {
+    if let (Some(from), Some(to)) = (query.from, query.to)
+        && from >= to
+    {
+        return Err(AppError::BadRequest("from must be before to".to_string()));
+    }
+    Ok(())
+}
+
+async fn require_supplier(pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+    let is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1",
+        user_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
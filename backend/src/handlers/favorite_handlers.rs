@@ -0,0 +1,139 @@
+// handlers/favorite_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{PaginationQuery, ProductReviewStatus, ProductUnit, ProductWithSeller};
+use crate::utils::{get_user_id, Pagination};
+
+pub async fn add_favorite(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let product_id = product_id.into_inner();
+
+    let product_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM products WHERE id = $1 AND taken_down_at IS NULL AND deleted_at IS NULL AND review_status = 'approved') as "exists!""#,
+        product_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !product_exists {
+        return Err(AppError::NotFound("Product not found".to_string()));
+    }
+
+    // Favoriting twice is a no-op
+    sqlx::query!(
+        r#"
+        INSERT INTO favorites (user_id, product_id)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id, product_id) DO NOTHING
+        "#,
+        user_id,
+        product_id
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product added to favorites",
+        "is_favorited": true
+    })))
+}
+
+pub async fn remove_favorite(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    sqlx::query!(
+        "DELETE FROM favorites WHERE user_id = $1 AND product_id = $2",
+        user_id,
+        product_id.into_inner()
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product removed from favorites",
+        "is_favorited": false
+    })))
+}
+
+pub async fn get_favorites(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let products = sqlx::query_as!(
+        ProductWithSeller,
+        r#"
+        SELECT
+            p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+            p.unit as "unit: ProductUnit", p.min_increment, p.sku, p.barcode,
+            p.image_url, p.seller_id, p.category_id, p.created_at,
+            c.name as category_name,
+            u.name as seller_name, u.name as seller_company,
+            u.rating as seller_rating, u.total_deliveries as seller_deliveries,
+            u.min_order_value as seller_min_order_value, u.delivery_fee as seller_delivery_fee,
+            COALESCE(
+                (SELECT json_agg(json_build_object('id', pi.id, 'url', pi.url) ORDER BY pi.position)
+                 FROM product_images pi WHERE pi.product_id = p.id),
+                '[]'::json
+            ) as "images!",
+            COALESCE(
+                (SELECT json_agg(json_build_object('min_qty', t.min_qty, 'unit_price', t.unit_price::text) ORDER BY t.min_qty)
+                 FROM price_tiers t WHERE t.product_id = p.id),
+                '[]'::json
+            ) as "price_tiers!",
+            TRUE as "is_favorited!",
+            NULL::float8 as distance_km,
+            (SELECT COUNT(*) FROM stock_by_location s WHERE s.product_id = p.id AND s.quantity > 0) as "stock_locations!",
+            current_deal(p.id) as deal,
+            NULL::numeric as display_price, NULL::text as display_currency,
+            p.review_status as "review_status: ProductReviewStatus"
+        FROM favorites f
+        JOIN products p ON f.product_id = p.id
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE f.user_id = $1 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+        ORDER BY f.created_at DESC, f.product_id
+        LIMIT $2 OFFSET $3
+        "#,
+        user_id,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total_count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM favorites f
+        JOIN products p ON f.product_id = p.id
+        WHERE f.user_id = $1 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+        "#,
+        user_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "products": products,
+        "pagination": pagination.to_json(total_count)
+    })))
+}
@@ -0,0 +1,23 @@
+// handlers/graphql_handler.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::errors::AppResult;
+use crate::graphql::loaders;
+use crate::graphql::schema::{AppSchema, Viewer};
+use crate::utils::get_viewer_id;
+
+/// Run a GraphQL query as the session's user, or anonymously without one. Errors
+/// come back in the GraphQL `errors` list with a 200, as GraphQL clients expect.
+pub async fn graphql(
+    identity: Option<Identity>,
+    pool: web::Data<PgPool>,
+    schema: web::Data<AppSchema>,
+    request: web::Json<async_graphql::Request>,
+) -> AppResult<HttpResponse> {
+    let viewer_id = get_viewer_id(identity.as_ref())?;
+    let request = loaders::register(request.into_inner(), pool.get_ref()).data(Viewer(viewer_id));
+
+    Ok(HttpResponse::Ok().json(schema.execute(request).await))
+}
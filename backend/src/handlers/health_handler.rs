@@ -1,12 +1,36 @@
 // handlers/health_handler.rs
+//
+// `/health` is the detailed status for people; orchestrators probe the other two.
+// `/health/live` only says the process is serving requests, so a restart is the
+// answer when it fails. `/health/ready` checks every dependency a request may need
+// and answers 503 while any of them is down, so traffic is held back rather than
+// the process being restarted.
 use actix_web::{web, HttpResponse};
 use chrono::Utc;
 use serde_json::json;
 use sqlx::PgPool;
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-use crate::errors::AppResult;
+use crate::broker;
+use crate::cache::Cache;
+use crate::errors::{pool_timeouts, AppResult};
+use crate::storage::Storage;
+use crate::ws;
+use crate::MIGRATOR;
+
+/// How long one dependency may take before it counts as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub async fn health_check(pool: web::Data<PgPool>, cache: web::Data<Cache>) -> AppResult<HttpResponse> {
+    // Read before the check below borrows a connection. sqlx doesn't count queued
+    // requests; a saturated pool is one where new queries have to wait.
+    let max_connections = pool.options().get_max_connections();
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+    let saturated = size >= max_connections && idle == 0;
 
-pub async fn health_check(pool: web::Data<PgPool>) -> AppResult<HttpResponse> {
     // Check database connectivity
     let db_status = match sqlx::query!("SELECT 1 as health")
         .fetch_one(pool.get_ref())
@@ -16,12 +40,140 @@ pub async fn health_check(pool: web::Data<PgPool>) -> AppResult<HttpResponse> {
         Err(_) => "unhealthy",
     };
 
+    let (connections, heartbeat_timeouts) = ws::connection_stats();
+
     Ok(HttpResponse::Ok().json(json!({
         "status": if db_status == "healthy" { "healthy" } else { "degraded" },
         "timestamp": Utc::now(),
         "services": {
             "database": db_status,
             "api": "healthy"
-        }
+        },
+        // This instance only
+        "database_pool": {
+            "size": size,
+            "idle": idle,
+            "in_use": size.saturating_sub(idle),
+            "max_connections": max_connections,
+            "min_connections": pool.options().get_min_connections(),
+            "saturated": saturated,
+            "acquire_timeouts": pool_timeouts()
+        },
+        "websocket": {
+            "connections": connections,
+            "heartbeat_timeouts": heartbeat_timeouts
+        },
+        "cache": cache.stats()
     })))
-}
\ No newline at end of file
+}
+
+/// The process is up and serving requests; nothing else is checked
+pub async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "status": "alive",
+        "timestamp": Utc::now()
+    }))
+}
+
+/// Whether this instance can serve traffic: the database answers with every migration
+/// applied, file storage is usable (the S3 bucket reachable, or the local directory
+/// writable) and so are the WebSocket broker and the Redis cache, when there are.
+/// The checks run concurrently and each reports its own latency.
+pub async fn readiness(
+    pool: web::Data<PgPool>,
+    storage: web::Data<dyn Storage>,
+    cache: web::Data<Cache>,
+) -> HttpResponse {
+    let (database, migrations, storage, broker, cache) = tokio::join!(
+        check(check_database(&pool)),
+        check(check_migrations(&pool)),
+        check(async { storage.check().await.map_err(|e| e.to_string()) }),
+        check_broker(),
+        check_cache(&cache),
+    );
+
+    let checks = [&database, &migrations, &storage, &broker, &cache];
+    let ready = checks.iter().all(|check| check["status"] != "down");
+
+    let body = json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "timestamp": Utc::now(),
+        "checks": {
+            "database": database,
+            "migrations": migrations,
+            "storage": storage,
+            "broker": broker,
+            "cache": cache
+        }
+    });
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// Run one check under the timeout, timing it
+async fn check(probe: impl Future<Output = Result<(), String>>) -> serde_json::Value {
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, probe)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {}ms", CHECK_TIMEOUT.as_millis())));
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match outcome {
+        Ok(()) => json!({ "status": "up", "latency_ms": latency_ms }),
+        Err(error) => json!({ "status": "down", "latency_ms": latency_ms, "error": error }),
+    }
+}
+
+async fn check_database(pool: &PgPool) -> Result<(), String> {
+    sqlx::query!("SELECT 1 as health")
+        .fetch_one(pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Every migration built into this binary has been applied successfully. A rolling
+/// deploy whose migrations another instance is still running isn't ready yet.
+async fn check_migrations(pool: &PgPool) -> Result<(), String> {
+    // sqlx's own bookkeeping table, created by the migrator rather than a migration, so
+    // the query can't be checked at compile time
+    let applied: HashSet<i64> = sqlx::query_scalar::<_, i64>("SELECT version FROM _sqlx_migrations WHERE success")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+
+    let pending: Vec<i64> = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("pending migrations: {:?}", pending))
+    }
+}
+
+/// Redis, when WebSocket payloads fan out through it; in-process delivery has nothing to check
+async fn check_broker() -> serde_json::Value {
+    if !broker::is_distributed() {
+        return json!({ "status": "not_configured" });
+    }
+    check(async { broker::ping().await.map_err(|e| e.to_string()) }).await
+}
+
+/// Redis, when the cache is kept there; an in-process cache has nothing to check
+async fn check_cache(cache: &Cache) -> serde_json::Value {
+    if !cache.is_distributed() {
+        return json!({ "status": "not_configured" });
+    }
+    check(async { cache.ping().await.map_err(|e| e.to_string()) }).await
+}
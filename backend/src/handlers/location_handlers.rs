@@ -0,0 +1,464 @@
+// handlers/location_handlers.rs
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::cache::{Cache, Namespace};
+use crate::errors::{AppError, AppResult};
+use crate::handlers::product_handlers::audit_stock_change;
+use crate::models::{CreateLocationRequest, InventoryReason, Location, SetLocationStockRequest, TransferStockRequest, UpdateLocationRequest};
+use crate::repositories::inventory_repository::{self, StockMovement};
+use crate::repositories::location_repository;
+use crate::update_builder::UpdateBuilder;
+use crate::validation::Validate;
+
+/// Most locations a seller can have
+const MAX_LOCATIONS: i64 = 50;
+
+/// The seller's locations, default first, with what each holds
+pub async fn get_locations(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+
+    let locations = sqlx::query_as!(
+        Location,
+        r#"
+        SELECT l.id, l.name, l.address, l.is_default,
+               COUNT(s.product_id) FILTER (WHERE s.quantity > 0) as "product_count!",
+               COALESCE(SUM(s.quantity), 0)::int8 as "units_on_hand!",
+               l.created_at
+        FROM locations l
+        LEFT JOIN stock_by_location s ON s.location_id = l.id
+        WHERE l.seller_id = $1
+        GROUP BY l.id
+        ORDER BY l.is_default DESC, l.name
+        "#,
+        seller_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "locations": locations
+    })))
+}
+
+/// Add a stall or warehouse. The seller's first location becomes their default, which
+/// orders ship from unless another is picked.
+pub async fn create_location(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    req: web::Json<CreateLocationRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    req.validate()?;
+
+    let seller = sqlx::query!(
+        r#"
+        SELECT is_supplier,
+               (SELECT COUNT(*) FROM locations WHERE seller_id = users.id) as "location_count!"
+        FROM users WHERE id = $1
+        "#,
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !seller.is_supplier {
+        return Err(AppError::Forbidden);
+    }
+    if seller.location_count >= MAX_LOCATIONS {
+        return Err(AppError::BadRequest(format!("You can have at most {} locations", MAX_LOCATIONS)));
+    }
+
+    let name = req.name.trim();
+    let is_default = req.is_default || seller.location_count == 0;
+
+    let mut tx = pool.begin().await?;
+    ensure_name_free(&mut tx, seller_id, name, None).await?;
+    if is_default {
+        clear_default(&mut tx, seller_id).await?;
+    }
+
+    let location_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO locations (id, seller_id, name, address, is_default) VALUES ($1, $2, $3, $4, $5)",
+        location_id,
+        seller_id,
+        name,
+        req.address.as_deref().map(str::trim),
+        is_default
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let location = find_own(pool.get_ref(), seller_id, location_id).await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Location created",
+        "location": location
+    })))
+}
+
+/// Rename a location, change its address, or make it the default
+pub async fn update_location(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    location_id: web::Path<Uuid>,
+    req: web::Json<UpdateLocationRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    let location_id = location_id.into_inner();
+    req.validate()?;
+
+    find_own(pool.get_ref(), seller_id, location_id).await?;
+
+    let name = req.name.as_deref().map(str::trim);
+    let update = UpdateBuilder::new("locations")
+        .set("name", name)
+        .set("address", req.address.as_deref().map(str::trim))
+        .set("is_default", req.is_default);
+    if update.is_empty() {
+        return Err(AppError::BadRequest("No fields to update".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    if let Some(name) = name {
+        ensure_name_free(&mut tx, seller_id, name, Some(location_id)).await?;
+    }
+    if req.is_default == Some(true) {
+        clear_default(&mut tx, seller_id).await?;
+    }
+    update.execute(&mut *tx, location_id).await?;
+    tx.commit().await?;
+
+    let location = find_own(pool.get_ref(), seller_id, location_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Location updated",
+        "location": location
+    })))
+}
+
+/// Remove a location that holds no stock. If it was the default, the oldest remaining
+/// location takes over.
+pub async fn delete_location(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    location_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    let location_id = location_id.into_inner();
+
+    let location = find_own(pool.get_ref(), seller_id, location_id).await?;
+    if location.units_on_hand > 0 {
+        return Err(AppError::Conflict(format!(
+            "{} still holds {} units; move or clear its stock first",
+            location.name, location.units_on_hand
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!("DELETE FROM locations WHERE id = $1 AND seller_id = $2", location_id, seller_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if location.is_default {
+        sqlx::query!(
+            r#"
+            UPDATE locations SET is_default = TRUE
+            WHERE id = (SELECT id FROM locations WHERE seller_id = $1 ORDER BY created_at, id LIMIT 1)
+            "#,
+            seller_id
+        )
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Location deleted"
+    })))
+}
+
+/// How much of the owner's product is on hand at each of their locations
+pub async fn get_product_locations(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    let product_id = product_id.into_inner();
+
+    let mut conn = pool.acquire().await?;
+    let stock_qty = own_product_stock(&mut conn, product_id, seller_id).await?;
+    let stocked_by_location = location_repository::is_stocked_by_location(&mut conn, product_id).await?;
+
+    let locations = sqlx::query!(
+        r#"
+        SELECT l.id, l.name, l.is_default, COALESCE(s.quantity, 0) as "quantity!", s.updated_at as "updated_at?"
+        FROM locations l
+        LEFT JOIN stock_by_location s ON s.location_id = l.id AND s.product_id = $2
+        WHERE l.seller_id = $1
+        ORDER BY l.is_default DESC, l.name
+        "#,
+        seller_id,
+        product_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let on_hand: i32 = locations.iter().map(|location| location.quantity).sum();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "product_id": product_id,
+        "stocked_by_location": stocked_by_location,
+        "stock_qty": stock_qty,
+        "on_hand": on_hand,
+        "locations": locations.iter().map(|location| json!({
+            "location_id": location.id,
+            "name": location.name,
+            "is_default": location.is_default,
+            "quantity": location.quantity,
+            "updated_at": location.updated_at
+        })).collect::<Vec<_>>()
+    })))
+}
+
+/// Set how much of the owner's product is on hand at one of their locations, e.g. after
+/// a count. The product's stock becomes what's on hand across its locations less what's
+/// sold and awaiting shipment; the change is kept in the inventory history.
+pub async fn set_location_stock(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<SetLocationStockRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    let (product_id, location_id) = path.into_inner();
+    req.validate()?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_own_product(&mut tx, product_id, seller_id).await?;
+    let location_name = own_location_name(&mut tx, seller_id, location_id).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO stock_by_location (location_id, product_id, quantity)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (location_id, product_id) DO UPDATE SET quantity = EXCLUDED.quantity, updated_at = NOW()
+        "#,
+        location_id,
+        product_id,
+        req.quantity
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    let (stock_before, stock_after) = location_repository::refresh_stock(&mut tx, product_id).await?;
+
+    if stock_after != stock_before {
+        let note = match req.note.as_deref().map(str::trim).filter(|note| !note.is_empty()) {
+            Some(note) => note.to_string(),
+            None => format!("Stock at {}", location_name),
+        };
+        inventory_repository::record(&mut tx, StockMovement {
+            product_id,
+            variant_id: None,
+            quantity_change: stock_after - stock_before,
+            stock_after,
+            reason: InventoryReason::Adjustment,
+            note: Some(&note),
+            order_id: None,
+            actor_id: Some(seller_id),
+        }).await?;
+        audit_stock_change(&mut tx, product_id, None, stock_before, stock_after, seller_id).await?;
+    }
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Location stock updated",
+        "product_id": product_id,
+        "location_id": location_id,
+        "quantity": req.quantity,
+        "stock_qty": stock_after
+    })))
+}
+
+/// Move units of the owner's product from one of their locations to another. What's on
+/// hand overall, and so the product's stock, doesn't change.
+pub async fn transfer_stock(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<TransferStockRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    let product_id = product_id.into_inner();
+    req.validate()?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_own_product(&mut tx, product_id, seller_id).await?;
+    let from_name = own_location_name(&mut tx, seller_id, req.from_location_id).await?;
+    own_location_name(&mut tx, seller_id, req.to_location_id).await?;
+
+    let remaining = sqlx::query_scalar!(
+        r#"
+        UPDATE stock_by_location
+        SET quantity = quantity - $3, updated_at = NOW()
+        WHERE location_id = $1 AND product_id = $2 AND quantity >= $3
+        RETURNING quantity
+        "#,
+        req.from_location_id,
+        product_id,
+        req.quantity
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::Conflict(format!("{} doesn't hold {} units", from_name, req.quantity)))?;
+
+    let received = sqlx::query_scalar!(
+        r#"
+        INSERT INTO stock_by_location (location_id, product_id, quantity)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (location_id, product_id) DO UPDATE
+        SET quantity = stock_by_location.quantity + EXCLUDED.quantity, updated_at = NOW()
+        RETURNING quantity
+        "#,
+        req.to_location_id,
+        product_id,
+        req.quantity
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Stock transferred",
+        "product_id": product_id,
+        "from": { "location_id": req.from_location_id, "quantity": remaining },
+        "to": { "location_id": req.to_location_id, "quantity": received }
+    })))
+}
+
+async fn find_own(pool: &PgPool, seller_id: Uuid, location_id: Uuid) -> AppResult<Location> {
+    sqlx::query_as!(
+        Location,
+        r#"
+        SELECT l.id, l.name, l.address, l.is_default,
+               COUNT(s.product_id) FILTER (WHERE s.quantity > 0) as "product_count!",
+               COALESCE(SUM(s.quantity), 0)::int8 as "units_on_hand!",
+               l.created_at
+        FROM locations l
+        LEFT JOIN stock_by_location s ON s.location_id = l.id
+        WHERE l.id = $1 AND l.seller_id = $2
+        GROUP BY l.id
+        "#,
+        location_id,
+        seller_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Location not found".to_string()))
+}
+
+async fn own_location_name(conn: &mut PgConnection, seller_id: Uuid, location_id: Uuid) -> AppResult<String> {
+    sqlx::query_scalar!(
+        "SELECT name FROM locations WHERE id = $1 AND seller_id = $2",
+        location_id,
+        seller_id
+    )
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Location not found".to_string()))
+}
+
+/// The owner's product's stock_qty
+async fn own_product_stock(conn: &mut PgConnection, product_id: Uuid, seller_id: Uuid) -> AppResult<i32> {
+    let product = sqlx::query!(
+        "SELECT seller_id, stock_qty FROM products WHERE id = $1 AND deleted_at IS NULL",
+        product_id
+    )
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    if product.seller_id != seller_id {
+        return Err(AppError::Forbidden);
+    }
+    Ok(product.stock_qty)
+}
+
+/// Lock the owner's product for a change to its location stock, which only products
+/// without variants have
+async fn lock_own_product(conn: &mut PgConnection, product_id: Uuid, seller_id: Uuid) -> AppResult<()> {
+    let product = sqlx::query!(
+        r#"
+        SELECT seller_id,
+               EXISTS(SELECT 1 FROM product_variants WHERE product_id = products.id) as "has_variants!"
+        FROM products
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+        product_id
+    )
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    if product.seller_id != seller_id {
+        return Err(AppError::Forbidden);
+    }
+    if product.has_variants {
+        return Err(AppError::BadRequest(
+            "This product's stock is set per variant and can't be kept by location".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Location names are unique per seller, regardless of case
+async fn ensure_name_free(conn: &mut PgConnection, seller_id: Uuid, name: &str, except_id: Option<Uuid>) -> AppResult<()> {
+    let taken = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM locations
+            WHERE seller_id = $1 AND LOWER(name) = LOWER($2) AND ($3::uuid IS NULL OR id <> $3)
+        ) as "exists!"
+        "#,
+        seller_id,
+        name,
+        except_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    if taken {
+        return Err(AppError::Conflict(format!("You already have a location named {}", name)));
+    }
+    Ok(())
+}
+
+async fn clear_default(conn: &mut PgConnection, seller_id: Uuid) -> AppResult<()> {
+    sqlx::query!("UPDATE locations SET is_default = FALSE WHERE seller_id = $1 AND is_default", seller_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
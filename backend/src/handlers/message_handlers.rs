@@ -1,13 +1,25 @@
 // handlers/message_handlers.rs
 use actix_identity::Identity;
 use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
 use serde_json::json;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::errors::{AppError, AppResult};
-use crate::models::Message;
-use crate::utils::get_user_id;
+use crate::handlers::notification_handlers::notify;
+use crate::models::{
+    BroadcastRequest, EditMessageRequest, Message, MessageHistoryQuery, NotificationKind, OfferContent, SendMessageRequest,
+    ServerEvent, StartConversationRequest,
+};
+use crate::repositories::message_repository::{self, get_or_create_conversation, save_message};
+use crate::utils::{get_user_id, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::ws::send_to_user;
+
+const MAX_MESSAGE_LENGTH: usize = 5000;
+
+/// How long after sending a message its sender can still edit or delete it
+pub const MESSAGE_EDIT_MINUTES: i64 = 15;
 
 pub async fn get_conversations(
     identity: Identity,
@@ -15,31 +27,45 @@ pub async fn get_conversations(
 ) -> AppResult<HttpResponse> {
     let user_id = get_user_id(&identity)?;
 
+    // The other user is the partner in a one-to-one conversation, and the seller in
+    // their broadcast; a seller looking at their own broadcast has none
     let conversations = sqlx::query!(
         r#"
         SELECT DISTINCT ON (c.id)
-            c.id, c.user1_id, c.user2_id, c.last_updated,
-            CASE
-                WHEN c.user1_id = $1 THEN u2.name
-                ELSE u1.name
-            END as other_user_name,
-            CASE
-                WHEN c.user1_id = $1 THEN u2.id
-                ELSE u1.id
-            END as other_user_id,
-            m.content as last_message,
-            m.sent_at as last_message_time
-        FROM conversations c
-        JOIN users u1 ON c.user1_id = u1.id
-        JOIN users u2 ON c.user2_id = u2.id
+            c.id, c.is_group, c.title, c.last_updated,
+            other.name as "other_user_name?",
+            other.id as "other_user_id?",
+            (SELECT COUNT(*) FROM conversation_participants pc WHERE pc.conv_id = c.id) as "participant_count!",
+            CASE WHEN m.deleted_at IS NULL THEN m.content END as last_message,
+            m.attachment_type as last_message_attachment_type,
+            -- Conversations opened from a product page may have no messages yet
+            m.sent_at as "last_message_time?",
+            p.id as "product_id?", p.name as "product_name?", p.image_url as product_image_url,
+            p.price_per_unit as "product_price?", p.currency as "product_currency?",
+            (
+                SELECT COUNT(*) FROM messages um
+                WHERE um.conv_id = c.id AND um.sender_id <> $1
+                  AND CASE
+                      WHEN c.is_group THEN um.sent_at > COALESCE(cp.last_read_at, '-infinity')
+                      ELSE um.read_at IS NULL
+                  END
+            ) as "unread_count!"
+        FROM conversation_participants cp
+        JOIN conversations c ON c.id = cp.conv_id
+        LEFT JOIN users other ON other.id = CASE
+            WHEN c.is_group THEN NULLIF(c.owner_id, $1)
+            WHEN c.user1_id = $1 THEN c.user2_id
+            ELSE c.user1_id
+        END
+        LEFT JOIN products p ON p.id = c.product_id AND p.deleted_at IS NULL AND p.taken_down_at IS NULL
         LEFT JOIN LATERAL (
-            SELECT content, sent_at
+            SELECT content, attachment_type, sent_at, deleted_at
             FROM messages
             WHERE conv_id = c.id
             ORDER BY sent_at DESC
             LIMIT 1
         ) m ON true
-        WHERE c.user1_id = $1 OR c.user2_id = $1
+        WHERE cp.user_id = $1
         ORDER BY c.id, m.sent_at DESC NULLS LAST
         "#,
         user_id
@@ -50,11 +76,23 @@ pub async fn get_conversations(
     let conv_list = conversations.iter().map(|conv| {
         json!({
             "id": conv.id,
+            "is_group": conv.is_group,
+            "title": conv.title,
+            "participant_count": conv.participant_count,
             "other_user_id": conv.other_user_id,
             "other_user_name": conv.other_user_name,
             "last_message": conv.last_message,
+            "last_message_attachment_type": conv.last_message_attachment_type,
             "last_message_time": conv.last_message_time,
-            "last_updated": conv.last_updated
+            "last_updated": conv.last_updated,
+            "unread_count": conv.unread_count,
+            "product": conv.product_id.map(|product_id| json!({
+                "id": product_id,
+                "name": conv.product_name,
+                "image_url": conv.product_image_url,
+                "price_per_unit": conv.product_price,
+                "currency": conv.product_currency
+            }))
         })
     }).collect::<Vec<_>>();
 
@@ -63,132 +101,633 @@ pub async fn get_conversations(
     })))
 }
 
+/// Open the user's conversation with a seller, creating it if they've never talked, so
+/// the client can send over the WebSocket straight away. Started from a product page,
+/// the conversation is marked as being about that listing until another one is asked
+/// about.
+pub async fn start_conversation(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<StartConversationRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    if req.seller_id == user_id {
+        return Err(AppError::BadRequest("You can't message yourself".to_string()));
+    }
+
+    let seller_name = sqlx::query_scalar!(
+        "SELECT name FROM users WHERE id = $1 AND is_supplier AND deleted_at IS NULL",
+        req.seller_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Seller not found".to_string()))?;
+    ensure_not_blocked(pool.get_ref(), user_id, req.seller_id).await?;
+
+    let product = match req.product_id {
+        Some(product_id) => Some(
+            sqlx::query!(
+                r#"
+                SELECT id, name, image_url, price_per_unit, currency FROM products
+                WHERE id = $1 AND seller_id = $2 AND taken_down_at IS NULL AND deleted_at IS NULL
+                  AND review_status = 'approved'
+                "#,
+                product_id,
+                req.seller_id
+            )
+                .fetch_optional(pool.get_ref())
+                .await?
+                .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let conv_id = get_or_create_conversation(pool.get_ref(), user_id, req.seller_id).await?;
+
+    if let Some(product) = &product {
+        sqlx::query!(
+            "UPDATE conversations SET product_id = $2 WHERE id = $1",
+            conv_id,
+            product.id
+        )
+            .execute(pool.get_ref())
+            .await?;
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "conv_id": conv_id,
+        "other_user_id": req.seller_id,
+        "other_user_name": seller_name,
+        "product": product.map(|product| json!({
+            "id": product.id,
+            "name": product.name,
+            "image_url": product.image_url,
+            "price_per_unit": product.price_per_unit,
+            "currency": product.currency
+        }))
+    })))
+}
+
+/// A page of a conversation's history, newest first. Pass the `next_before` of one page
+/// as `before` to get the next, older page; `has_more` says whether there is one.
 pub async fn get_messages(
     identity: Identity,
     pool: web::Data<PgPool>,
     conv_id: web::Path<Uuid>,
+    query: web::Query<MessageHistoryQuery>,
 ) -> AppResult<HttpResponse> {
     let user_id = get_user_id(&identity)?;
     let conv_id = conv_id.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
 
     // Verify user is part of this conversation
-    let is_participant = sqlx::query_scalar!(
-        r#"
-        SELECT EXISTS(
-            SELECT 1 FROM conversations
-            WHERE id = $1 AND (user1_id = $2 OR user2_id = $2)
-        )
-        "#,
-        conv_id,
-        user_id
-    )
-        .fetch_one(pool.get_ref())
-        .await?.unwrap();
-
-    if !is_participant {
+    if !message_repository::is_participant(pool.get_ref(), conv_id, user_id).await? {
         return Err(AppError::Forbidden);
     }
 
-    // Get messages
-    let messages = sqlx::query!(
+    // Keyset position to page back from: a message id continues from that message,
+    // a timestamp takes everything sent strictly before it
+    let cursor: Option<(DateTime<Utc>, Uuid)> = match query.before.as_deref() {
+        None => None,
+        Some(before) => match Uuid::parse_str(before) {
+            Ok(message_id) => {
+                let sent_at = sqlx::query_scalar!(
+                    "SELECT sent_at FROM messages WHERE id = $1 AND conv_id = $2",
+                    message_id,
+                    conv_id
+                )
+                    .fetch_optional(pool.get_ref())
+                    .await?
+                    .ok_or_else(|| AppError::NotFound("Message not found".to_string()))?;
+                Some((sent_at, message_id))
+            }
+            Err(_) => {
+                let sent_before = DateTime::parse_from_rfc3339(before)
+                    .map_err(|_| AppError::BadRequest("before must be a message id or an RFC 3339 timestamp".to_string()))?;
+                Some((sent_before.with_timezone(&Utc), Uuid::nil()))
+            }
+        },
+    };
+
+    // One extra row tells whether an older page exists
+    let mut messages = sqlx::query!(
         r#"
-        SELECT m.id, m.sender_id, m.content, m.sent_at,
-               u.name as sender_name
+        SELECT m.id, m.conv_id, m.sender_id, m.content, m.attachment_url, m.attachment_type,
+               m.sent_at, m.read_at, m.edited_at, m.deleted_at, u.name as sender_name
         FROM messages m
         JOIN users u ON m.sender_id = u.id
         WHERE m.conv_id = $1
-        ORDER BY m.sent_at ASC
+          AND ($2::timestamptz IS NULL OR (m.sent_at, m.id) < ($2, $3))
+        ORDER BY m.sent_at DESC, m.id DESC
+        LIMIT $4
         "#,
-        conv_id
+        conv_id,
+        cursor.map(|(sent_at, _)| sent_at),
+        cursor.map(|(_, id)| id),
+        limit + 1
     )
         .fetch_all(pool.get_ref())
         .await?;
 
+    let has_more = messages.len() as i64 > limit;
+    messages.truncate(limit as usize);
+
+    // Same shape as the "message" WebSocket event, so live messages can be merged in.
+    // Deleted messages keep their place as tombstones with no content.
     let message_list = messages.iter().map(|msg| {
+        let deleted = msg.deleted_at.is_some();
         json!({
+            "type": "message",
             "id": msg.id,
+            "conv_id": msg.conv_id,
             "sender_id": msg.sender_id,
             "sender_name": msg.sender_name,
-            "content": msg.content,
-            "sent_at": msg.sent_at
+            "content": if deleted { None } else { Some(&msg.content) },
+            "attachment_url": msg.attachment_url,
+            "attachment_type": msg.attachment_type,
+            "sent_at": msg.sent_at,
+            "read_at": msg.read_at,
+            "edited_at": msg.edited_at,
+            "deleted_at": msg.deleted_at
         })
     }).collect::<Vec<_>>();
 
     Ok(HttpResponse::Ok().json(json!({
-        "messages": message_list
+        "messages": message_list,
+        "has_more": has_more,
+        "next_before": if has_more { messages.last().map(|msg| msg.id) } else { None }
     })))
 }
 
-/// Create or get existing conversation between two users
-pub async fn get_or_create_conversation(
-    pool: &PgPool,
-    user1_id: Uuid,
-    user2_id: Uuid,
-) -> AppResult<Uuid> {
-    // Check if conversation already exists
-    let existing_conv = sqlx::query_scalar!(
+/// Send a message without a WebSocket, for clients whose socket keeps dropping.
+/// Delivered exactly as if it had been sent over the socket.
+pub async fn send_message(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<SendMessageRequest>,
+) -> AppResult<HttpResponse> {
+    let sender_id = get_user_id(&identity)?;
+
+    let event = match (req.receiver_id, req.conv_id) {
+        (Some(receiver_id), _) => {
+            deliver_message(pool.get_ref(), sender_id, receiver_id, &req.content, req.attachment_url.as_deref()).await?
+        }
+        (None, Some(conv_id)) => {
+            deliver_to_conversation(pool.get_ref(), sender_id, conv_id, &req.content, req.attachment_url.as_deref()).await?
+        }
+        (None, None) => return Err(AppError::BadRequest("Missing receiver_id or conv_id".to_string())),
+    };
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Message sent",
+        "chat_message": event
+    })))
+}
+
+/// Change the text of one of the user's own messages within MESSAGE_EDIT_MINUTES of
+/// sending it. Every participant gets a "message_edited" event.
+pub async fn edit_message(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    message_id: web::Path<Uuid>,
+    req: web::Json<EditMessageRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let message_id = message_id.into_inner();
+
+    let message = own_recent_message(pool.get_ref(), user_id, message_id).await?;
+    check_content(&req.content, message.attachment_url.as_deref())?;
+
+    // A concurrent delete wins over the edit
+    let edited = sqlx::query!(
         r#"
-        SELECT id FROM conversations
-        WHERE (user1_id = $1 AND user2_id = $2) OR (user1_id = $2 AND user2_id = $1)
+        UPDATE messages SET content = $2, edited_at = NOW()
+        WHERE id = $1 AND deleted_at IS NULL
+        RETURNING edited_at as "edited_at!"
         "#,
-        user1_id,
-        user2_id
+        message_id,
+        req.content
     )
-        .fetch_optional(pool)
-        .await?;
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found".to_string()))?;
+
+    let event = ServerEvent::MessageEdited {
+        id: message_id,
+        conv_id: message.conv_id,
+        content: req.content.clone(),
+        edited_at: edited.edited_at,
+    };
+    send_to_participants(pool.get_ref(), message.conv_id, &event).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Message edited",
+        "chat_message": event
+    })))
+}
+
+/// Delete one of the user's own messages within MESSAGE_EDIT_MINUTES of sending it.
+/// Its text and attachment are cleared, leaving a tombstone in the history, and every
+/// participant gets a "message_deleted" event.
+pub async fn delete_message(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    message_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let message_id = message_id.into_inner();
+
+    let message = own_recent_message(pool.get_ref(), user_id, message_id).await?;
+
+    let deleted_at = sqlx::query_scalar!(
+        r#"
+        UPDATE messages
+        SET content = '', attachment_url = NULL, attachment_type = NULL, deleted_at = NOW()
+        WHERE id = $1 AND deleted_at IS NULL
+        RETURNING deleted_at as "deleted_at!"
+        "#,
+        message_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found".to_string()))?;
+
+    send_to_participants(pool.get_ref(), message.conv_id, &ServerEvent::MessageDeleted {
+        id: message_id,
+        conv_id: message.conv_id,
+        deleted_at,
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Message deleted"
+    })))
+}
+
+/// A message the user sent that they may still change: not deleted, not an offer
+/// event and sent within the last MESSAGE_EDIT_MINUTES
+async fn own_recent_message(pool: &PgPool, user_id: Uuid, message_id: Uuid) -> AppResult<Message> {
+    let message = message_repository::find_undeleted(pool, message_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found".to_string()))?;
+
+    if message.sender_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    // Offer events record the negotiation; they change only through the offer endpoints
+    if serde_json::from_str::<OfferContent>(&message.content).is_ok() {
+        return Err(AppError::BadRequest("Offer messages can't be edited or deleted".to_string()));
+    }
+    if message.sent_at < Utc::now() - Duration::minutes(MESSAGE_EDIT_MINUTES) {
+        return Err(AppError::BadRequest(format!(
+            "Messages can only be changed within {} minutes of sending",
+            MESSAGE_EDIT_MINUTES
+        )));
+    }
+
+    Ok(message)
+}
+
+/// Push an event to every participant of a conversation
+async fn send_to_participants(pool: &PgPool, conv_id: Uuid, event: &ServerEvent) -> AppResult<()> {
+    for user_id in message_repository::participant_ids(pool, conv_id).await? {
+        send_to_user(user_id, event);
+    }
+    Ok(())
+}
+
+/// Text is 1-MAX_MESSAGE_LENGTH characters, or may be empty on a message with an attachment
+fn check_content(content: &str, attachment_url: Option<&str>) -> AppResult<()> {
+    if content.chars().count() > MAX_MESSAGE_LENGTH
+        || (content.trim().is_empty() && attachment_url.is_none())
+    {
+        return Err(AppError::BadRequest(format!(
+            "Message must be 1-{} characters, or have an attachment",
+            MAX_MESSAGE_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+pub async fn mark_conversation_read(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    conv_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let marked = mark_read(pool.get_ref(), user_id, conv_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Conversation marked as read",
+        "marked_count": marked
+    })))
+}
+
+/// Mark the messages sent to the user in a conversation read, telling the other
+/// participant if there were any. Returns how many were marked. In a group only the
+/// user's own place is moved on; nobody is told who has read a broadcast.
+pub async fn mark_read(pool: &PgPool, user_id: Uuid, conv_id: Uuid) -> AppResult<u64> {
+    let conversation = message_repository::find_conversation(pool, conv_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+
+    if !message_repository::is_participant(pool, conv_id, user_id).await? {
+        return Err(AppError::Forbidden);
+    }
 
-    if let Some(conv_id) = existing_conv {
-        return Ok(conv_id);
+    let read_at = Utc::now();
+    if conversation.is_group {
+        let marked = sqlx::query_scalar!(
+            r#"
+            WITH previous AS (
+                SELECT last_read_at FROM conversation_participants
+                WHERE conv_id = $1 AND user_id = $2
+                FOR UPDATE
+            ), moved AS (
+                UPDATE conversation_participants SET last_read_at = $3
+                WHERE conv_id = $1 AND user_id = $2
+            )
+            SELECT COUNT(*) as "marked!"
+            FROM messages m, previous
+            WHERE m.conv_id = $1 AND m.sender_id <> $2 AND m.sent_at <= $3
+              AND m.sent_at > COALESCE(previous.last_read_at, '-infinity')
+            "#,
+            conv_id,
+            user_id,
+            read_at
+        )
+            .fetch_one(pool)
+            .await?;
+        return Ok(marked as u64);
     }
 
-    // Create new conversation
-    let conv_id = Uuid::new_v4();
-    sqlx::query!(
+    let other_user_id = if conversation.user1_id == Some(user_id) {
+        conversation.user2_id
+    } else {
+        conversation.user1_id
+    };
+
+    // Only messages sent to this user can be marked read by them
+
+    let marked = sqlx::query!(
         r#"
-        INSERT INTO conversations (id, user1_id, user2_id, last_updated)
-        VALUES ($1, $2, $3, NOW())
+        UPDATE messages SET read_at = $3
+        WHERE conv_id = $1 AND sender_id <> $2 AND read_at IS NULL
         "#,
         conv_id,
-        user1_id,
-        user2_id
+        user_id,
+        read_at
     )
         .execute(pool)
+        .await?
+        .rows_affected();
+
+    // Tell the sender their messages were seen
+    if let Some(other_user_id) = other_user_id.filter(|_| marked > 0) {
+        send_to_user(other_user_id, &ServerEvent::Read {
+            conv_id,
+            reader_id: user_id,
+            read_at,
+        });
+    }
+
+    Ok(marked)
+}
+
+/// Nobody can reach a user who blocked them, and a user who blocked someone has to
+/// unblock them before writing to them again
+pub async fn ensure_not_blocked(pool: &PgPool, sender_id: Uuid, recipient_id: Uuid) -> AppResult<()> {
+    let blockers = message_repository::blockers_between(pool, sender_id, recipient_id).await?;
+    if blockers.contains(&recipient_id) {
+        return Err(AppError::Forbidden);
+    }
+    if blockers.contains(&sender_id) {
+        return Err(AppError::BadRequest("You blocked this user; unblock them to contact them".to_string()));
+    }
+    Ok(())
+}
+
+/// Who hears from a user in a conversation they take part in
+pub enum Audience {
+    /// The other participant of a one-to-one conversation
+    Partner(Uuid),
+    /// Every buyer in the user's own broadcast
+    Broadcast,
+}
+
+/// Who the user would reach in a conversation. Only its seller posts in a broadcast;
+/// buyers reply to them one-to-one.
+pub async fn audience(pool: &PgPool, user_id: Uuid, conv_id: Uuid) -> AppResult<Audience> {
+    let conversation = message_repository::find_for_participant(pool, conv_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+
+    if conversation.is_group {
+        if conversation.owner_id != Some(user_id) {
+            return Err(AppError::Forbidden);
+        }
+        return Ok(Audience::Broadcast);
+    }
+
+    let partner_id = if conversation.user1_id == Some(user_id) {
+        conversation.user2_id
+    } else {
+        conversation.user1_id
+    };
+    partner_id
+        .map(Audience::Partner)
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))
+}
+
+/// Store a chat message and push it to the receiver, if online, and back to the sender.
+/// An attachment must have been uploaded by the sender to this conversation, through
+/// POST /upload/message; a message with one may have no text. Returns the "message"
+/// event both of them were sent.
+pub async fn deliver_message(
+    pool: &PgPool,
+    sender_id: Uuid,
+    receiver_id: Uuid,
+    content: &str,
+    attachment_url: Option<&str>,
+) -> AppResult<ServerEvent> {
+    check_content(content, attachment_url)?;
+    if receiver_id == sender_id {
+        return Err(AppError::BadRequest("You can't message yourself".to_string()));
+    }
+
+    let receiver_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1 AND deleted_at IS NULL) as "exists!""#,
+        receiver_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    if !receiver_exists {
+        return Err(AppError::NotFound("Receiver not found".to_string()));
+    }
+    ensure_not_blocked(pool, sender_id, receiver_id).await?;
+
+    // Get or create conversation
+    let conv_id = get_or_create_conversation(pool, sender_id, receiver_id).await?;
+
+    post(pool, conv_id, sender_id, &[receiver_id], content, attachment_url).await
+}
+
+/// Send a message into a conversation the user takes part in: to the partner of a
+/// one-to-one conversation, or to every buyer in the user's broadcast
+pub async fn deliver_to_conversation(
+    pool: &PgPool,
+    sender_id: Uuid,
+    conv_id: Uuid,
+    content: &str,
+    attachment_url: Option<&str>,
+) -> AppResult<ServerEvent> {
+    match audience(pool, sender_id, conv_id).await? {
+        Audience::Partner(receiver_id) => deliver_message(pool, sender_id, receiver_id, content, attachment_url).await,
+        Audience::Broadcast => {
+            let (event, _) = deliver_broadcast(pool, sender_id, conv_id, content, attachment_url).await?;
+            Ok(event)
+        }
+    }
+}
+
+/// Announce something, such as new stock, to every buyer who has ordered from the
+/// seller. The seller's broadcast conversation is created on first use; buyers who
+/// have ordered since join it, and buyers who blocked the seller, or were blocked by
+/// them, leave it.
+pub async fn broadcast(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<BroadcastRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+
+    let seller = sqlx::query!(
+        "SELECT name, is_supplier FROM users WHERE id = $1",
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
         .await?;
 
-    Ok(conv_id)
+    if !seller.is_supplier {
+        return Err(AppError::Forbidden);
+    }
+    check_content(&req.content, req.attachment_url.as_deref())?;
+
+    let title = format!("Announcements from {}", seller.name.as_deref().unwrap_or("your seller"));
+    let conv_id = message_repository::get_or_create_broadcast(pool.get_ref(), seller_id, &title).await?;
+
+    let (event, recipient_count) = deliver_broadcast(
+        pool.get_ref(),
+        seller_id,
+        conv_id,
+        &req.content,
+        req.attachment_url.as_deref(),
+    ).await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Broadcast sent",
+        "conv_id": conv_id,
+        "recipient_count": recipient_count,
+        "chat_message": event
+    })))
 }
 
-/// Save a message to the database
-pub async fn save_message(
+/// Post to the seller's broadcast, once its buyers are brought up to date. Returns the
+/// "message" event and how many buyers it went to.
+async fn deliver_broadcast(
+    pool: &PgPool,
+    seller_id: Uuid,
+    conv_id: Uuid,
+    content: &str,
+    attachment_url: Option<&str>,
+) -> AppResult<(ServerEvent, usize)> {
+    check_content(content, attachment_url)?;
+
+    let buyer_ids = message_repository::sync_broadcast(pool, conv_id, seller_id).await?;
+    if buyer_ids.is_empty() {
+        return Err(AppError::BadRequest("No buyers have ordered from you yet".to_string()));
+    }
+
+    let event = post(pool, conv_id, seller_id, &buyer_ids, content, attachment_url).await?;
+    Ok((event, buyer_ids.len()))
+}
+
+/// Save a message to a conversation and push it to its recipients, if online, and back
+/// to the sender. Recipients are notified of it.
+async fn post(
     pool: &PgPool,
     conv_id: Uuid,
     sender_id: Uuid,
+    recipient_ids: &[Uuid],
     content: &str,
-) -> AppResult<Message> {
-    let message_id = Uuid::new_v4();
+    attachment_url: Option<&str>,
+) -> AppResult<ServerEvent> {
+    let attachment = match attachment_url {
+        Some(url) => {
+            let attachment_type = sqlx::query_scalar!(
+                r#"
+                SELECT attachment_type FROM message_attachments
+                WHERE url = $1 AND conv_id = $2 AND uploader_id = $3
+                "#,
+                url,
+                conv_id,
+                sender_id
+            )
+                .fetch_optional(pool)
+                .await?
+                .ok_or_else(|| AppError::BadRequest("Attachment was not uploaded to this conversation".to_string()))?;
+            Some((url, attachment_type))
+        }
+        None => None,
+    };
 
-    let message = sqlx::query_as!(
-        Message,
-        r#"
-        INSERT INTO messages (id, conv_id, sender_id, content, sent_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        RETURNING id, conv_id, sender_id, content, sent_at
-        "#,
-        message_id,
+    // Save message to database
+    let saved_message = save_message(
+        pool,
         conv_id,
         sender_id,
-        content
+        content,
+        attachment.as_ref().map(|(url, attachment_type)| (*url, attachment_type.as_str())),
+    ).await?;
+
+    // Get sender name
+    let sender_name = sqlx::query_scalar!(
+        "SELECT name FROM users WHERE id = $1",
+        sender_id
     )
         .fetch_one(pool)
         .await?;
 
-    // Update conversation last_updated timestamp
-    sqlx::query!(
-        "UPDATE conversations SET last_updated = NOW() WHERE id = $1",
-        conv_id
-    )
-        .execute(pool)
-        .await?;
+    // Send to recipients if online, and echo back to sender
+    let event = ServerEvent::Message {
+        id: saved_message.id,
+        conv_id,
+        sender_id,
+        sender_name: sender_name.clone(),
+        content: content.to_string(),
+        attachment_url: saved_message.attachment_url,
+        attachment_type: saved_message.attachment_type,
+        sent_at: saved_message.sent_at,
+        read_at: saved_message.read_at,
+        edited_at: saved_message.edited_at,
+        deleted_at: saved_message.deleted_at,
+    };
+    for &recipient_id in recipient_ids {
+        send_to_user(recipient_id, &event);
+    }
+    send_to_user(sender_id, &event);
 
-    Ok(message)
+    let title = format!("New message from {}", sender_name.as_deref().unwrap_or("a StreetSource user"));
+    for &recipient_id in recipient_ids {
+        notify(
+            pool,
+            recipient_id,
+            NotificationKind::Message,
+            &title,
+            json!({ "conv_id": conv_id, "message_id": saved_message.id }),
+        ).await?;
+    }
+
+    Ok(event)
 }
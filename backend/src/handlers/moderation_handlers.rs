@@ -0,0 +1,355 @@
+// handlers/moderation_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{AdminReportQuery, ReportStatus, ReportUserRequest, ResolveReportRequest};
+use crate::repositories::message_repository;
+use crate::utils::{get_user_id, Pagination};
+
+/// How many of the latest messages between the two users a report keeps
+const REPORT_CONTEXT_MESSAGES: i64 = 20;
+const MAX_REPORT_TEXT: usize = 2000;
+
+pub async fn block_user(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    blocked_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let blocked_id = blocked_id.into_inner();
+
+    if blocked_id == user_id {
+        return Err(AppError::BadRequest("You can't block yourself".to_string()));
+    }
+    ensure_user_exists(pool.get_ref(), blocked_id).await?;
+
+    block(pool.get_ref(), user_id, blocked_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "User blocked"
+    })))
+}
+
+pub async fn unblock_user(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    blocked_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let removed = sqlx::query!(
+        "DELETE FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2",
+        user_id,
+        blocked_id.into_inner()
+    )
+        .execute(pool.get_ref())
+        .await?
+        .rows_affected();
+
+    if removed == 0 {
+        return Err(AppError::NotFound("User is not blocked".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "User unblocked"
+    })))
+}
+
+/// The users the caller has blocked, most recent first
+pub async fn get_blocked_users(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let blocked = sqlx::query!(
+        r#"
+        SELECT u.id, u.name, u.profile_image_url, b.created_at as blocked_at
+        FROM user_blocks b
+        JOIN users u ON b.blocked_id = u.id
+        WHERE b.blocker_id = $1
+        ORDER BY b.created_at DESC
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let blocked_list = blocked.iter().map(|user| {
+        json!({
+            "id": user.id,
+            "name": user.name,
+            "profile_image_url": user.profile_image_url,
+            "blocked_at": user.blocked_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "blocked_users": blocked_list
+    })))
+}
+
+/// Report a user to the admins, optionally blocking them too. One open report per pair
+/// of users at a time.
+pub async fn report_user(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    reported_id: web::Path<Uuid>,
+    req: web::Json<ReportUserRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let reported_id = reported_id.into_inner();
+
+    let reason = req.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REPORT_TEXT {
+        return Err(AppError::BadRequest(format!("reason must be 1-{} characters", MAX_REPORT_TEXT)));
+    }
+    if reported_id == user_id {
+        return Err(AppError::BadRequest("You can't report yourself".to_string()));
+    }
+    ensure_user_exists(pool.get_ref(), reported_id).await?;
+
+    let already_open = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM user_reports WHERE reporter_id = $1 AND reported_id = $2 AND status = 'open'
+        ) as "exists!"
+        "#,
+        user_id,
+        reported_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if already_open {
+        return Err(AppError::Conflict("You already have an open report about this user".to_string()));
+    }
+
+    // Kept as they are now, so editing or deleting messages later can't hide them
+    let (conv_id, messages) =
+        message_repository::recent_between(pool.get_ref(), user_id, reported_id, REPORT_CONTEXT_MESSAGES).await?;
+    let message_context = messages.iter().map(|message| {
+        json!({
+            "id": message.id,
+            "sender_id": message.sender_id,
+            "content": message.deleted_at.is_none().then_some(&message.content),
+            "attachment_url": message.attachment_url,
+            "sent_at": message.sent_at,
+            "edited_at": message.edited_at,
+            "deleted_at": message.deleted_at
+        })
+    }).collect::<Vec<_>>();
+
+    let report_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_reports (id, reporter_id, reported_id, reason, conv_id, message_context)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        report_id,
+        user_id,
+        reported_id,
+        reason,
+        conv_id,
+        json!(message_context)
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    let blocked = req.block.unwrap_or(false);
+    if blocked {
+        block(pool.get_ref(), user_id, reported_id).await?;
+    }
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Report submitted",
+        "report_id": report_id,
+        "blocked": blocked
+    })))
+}
+
+/// The moderation queue, oldest first
+pub async fn list_reports(
+    pool: web::Data<PgPool>,
+    query: web::Query<AdminReportQuery>,
+) -> AppResult<HttpResponse> {
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let reports = sqlx::query!(
+        r#"
+        SELECT r.id, r.reporter_id, r.reported_id, r.reason, r.status as "status: ReportStatus",
+               r.created_at, r.resolved_at, jsonb_array_length(r.message_context) as "message_count!",
+               reporter.name as reporter_name, reported.name as reported_name,
+               (SELECT COUNT(*) FROM user_reports other WHERE other.reported_id = r.reported_id) as "reports_against!"
+        FROM user_reports r
+        JOIN users reporter ON r.reporter_id = reporter.id
+        JOIN users reported ON r.reported_id = reported.id
+        WHERE ($1::report_status IS NULL OR r.status = $1)
+        ORDER BY r.created_at ASC
+        LIMIT $2 OFFSET $3
+        "#,
+        query.status.clone() as Option<ReportStatus>,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM user_reports
+        WHERE ($1::report_status IS NULL OR status = $1)
+        "#,
+        query.status.clone() as Option<ReportStatus>
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let report_list = reports.iter().map(|report| {
+        json!({
+            "id": report.id,
+            "reporter_id": report.reporter_id,
+            "reporter_name": report.reporter_name,
+            "reported_id": report.reported_id,
+            "reported_name": report.reported_name,
+            "reports_against": report.reports_against,
+            "reason": report.reason,
+            "status": report.status,
+            "message_count": report.message_count,
+            "resolved_at": report.resolved_at,
+            "created_at": report.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "reports": report_list,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// One report with the messages it captured
+pub async fn get_report(
+    pool: web::Data<PgPool>,
+    report_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let report = sqlx::query!(
+        r#"
+        SELECT r.id, r.reporter_id, r.reported_id, r.reason, r.conv_id, r.message_context,
+               r.status as "status: ReportStatus", r.resolution_note, r.resolved_by, r.resolved_at,
+               r.created_at, reporter.name as reporter_name, reported.name as reported_name,
+               reported.suspended_at as reported_suspended_at
+        FROM user_reports r
+        JOIN users reporter ON r.reporter_id = reporter.id
+        JOIN users reported ON r.reported_id = reported.id
+        WHERE r.id = $1
+        "#,
+        report_id.into_inner()
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": report.id,
+        "reporter_id": report.reporter_id,
+        "reporter_name": report.reporter_name,
+        "reported_id": report.reported_id,
+        "reported_name": report.reported_name,
+        "reported_suspended": report.reported_suspended_at.is_some(),
+        "reason": report.reason,
+        "conv_id": report.conv_id,
+        "messages": report.message_context,
+        "status": report.status,
+        "resolution_note": report.resolution_note,
+        "resolved_by": report.resolved_by,
+        "resolved_at": report.resolved_at,
+        "created_at": report.created_at
+    })))
+}
+
+/// Close a report as dismissed or actioned; what action to take (such as suspending the
+/// user) is up to the admin
+pub async fn resolve_report(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    report_id: web::Path<Uuid>,
+    req: web::Json<ResolveReportRequest>,
+) -> AppResult<HttpResponse> {
+    let admin_id = get_user_id(&identity)?;
+    let report_id = report_id.into_inner();
+    let note = req.note.as_deref().map(str::trim).filter(|note| !note.is_empty());
+
+    if req.status == ReportStatus::Open {
+        return Err(AppError::BadRequest("status must be dismissed or actioned".to_string()));
+    }
+    if note.is_some_and(|note| note.chars().count() > MAX_REPORT_TEXT) {
+        return Err(AppError::BadRequest(format!("note must be at most {} characters", MAX_REPORT_TEXT)));
+    }
+
+    let resolved = sqlx::query!(
+        r#"
+        UPDATE user_reports
+        SET status = $2, resolution_note = $3, resolved_by = $4, resolved_at = NOW()
+        WHERE id = $1 AND status = 'open'
+        "#,
+        report_id,
+        req.status.clone() as ReportStatus,
+        note,
+        admin_id
+    )
+        .execute(pool.get_ref())
+        .await?
+        .rows_affected();
+
+    if resolved == 0 {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM user_reports WHERE id = $1) as "exists!""#,
+            report_id
+        )
+            .fetch_one(pool.get_ref())
+            .await?;
+
+        return Err(if exists {
+            AppError::Conflict("Report is already resolved".to_string())
+        } else {
+            AppError::NotFound("Report not found".to_string())
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Report resolved",
+        "status": req.status
+    })))
+}
+
+async fn block(pool: &PgPool, user_id: Uuid, blocked_id: Uuid) -> AppResult<()> {
+    sqlx::query!(
+        "INSERT INTO user_blocks (blocker_id, blocked_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        user_id,
+        blocked_id
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn ensure_user_exists(pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1 AND deleted_at IS NULL) as "exists!""#,
+        user_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    if !exists {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    Ok(())
+}
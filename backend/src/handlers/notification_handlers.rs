@@ -0,0 +1,298 @@
+// handlers/notification_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{Notification, NotificationKind, NotificationQuery, PushDevice, PushPlatform, RegisterPushDeviceRequest, ServerEvent, WebhookEvent};
+use crate::push;
+use crate::utils::{get_user_id, Pagination};
+use crate::validation::Validate;
+use crate::webhooks;
+use crate::ws::send_to_user;
+
+/// Stock at or below which a sale alerts the seller
+pub const LOW_STOCK_THRESHOLD: i32 = 5;
+
+/// The user's notifications, newest first
+pub async fn get_notifications(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    query: web::Query<NotificationQuery>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let pagination = Pagination::new(query.page, query.limit);
+    let unread_only = query.unread.unwrap_or(false);
+
+    let notifications = sqlx::query_as!(
+        Notification,
+        r#"
+        SELECT id, kind as "kind: NotificationKind", title, data, read_at, created_at
+        FROM notifications
+        WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+        ORDER BY created_at DESC, id
+        LIMIT $3 OFFSET $4
+        "#,
+        user_id,
+        unread_only,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM notifications
+        WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+        "#,
+        user_id,
+        unread_only
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "notifications": notifications,
+        "unread_count": unread_count(pool.get_ref(), user_id).await?,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+pub async fn get_unread_count(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "unread_count": unread_count(pool.get_ref(), user_id).await?
+    })))
+}
+
+pub async fn mark_read(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    notification_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    // Reading it again keeps the first read time
+    let updated = sqlx::query!(
+        r#"
+        UPDATE notifications SET read_at = COALESCE(read_at, NOW())
+        WHERE id = $1 AND user_id = $2
+        "#,
+        notification_id.into_inner(),
+        user_id
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(AppError::NotFound("Notification not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Notification marked as read",
+        "unread_count": unread_count(pool.get_ref(), user_id).await?
+    })))
+}
+
+pub async fn mark_all_read(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let updated = sqlx::query!(
+        "UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL",
+        user_id
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "All notifications marked as read",
+        "marked": updated.rows_affected(),
+        "unread_count": 0
+    })))
+}
+
+/// The devices the user gets pushes on
+pub async fn list_push_devices(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let devices = sqlx::query_as!(
+        PushDevice,
+        r#"
+        SELECT id, platform as "platform: PushPlatform", created_at, last_used_at
+        FROM push_devices
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "devices": devices })))
+}
+
+/// Register this device's FCM token. A token already registered, by this user or one
+/// who logged in on the device before, now belongs to this user.
+pub async fn register_push_device(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<RegisterPushDeviceRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    req.validate()?;
+
+    let device = sqlx::query_as!(
+        PushDevice,
+        r#"
+        INSERT INTO push_devices (id, user_id, platform, token)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (token) DO UPDATE
+        SET user_id = EXCLUDED.user_id, platform = EXCLUDED.platform, created_at = NOW(), last_used_at = NULL
+        RETURNING id, platform as "platform: PushPlatform", created_at, last_used_at
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        req.platform as PushPlatform,
+        req.token.trim()
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Device registered for push notifications",
+        "device": device
+    })))
+}
+
+/// Stop pushing to a device, e.g. when the user logs out on it
+pub async fn remove_push_device(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    device_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let deleted = sqlx::query!(
+        "DELETE FROM push_devices WHERE id = $1 AND user_id = $2",
+        device_id.into_inner(),
+        user_id
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Device not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({ "message": "Device removed" })))
+}
+
+async fn unread_count(pool: &PgPool, user_id: Uuid) -> AppResult<i64> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM notifications WHERE user_id = $1 AND read_at IS NULL"#,
+        user_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count)
+}
+
+/// Store a notification for the user and push it with their new unread count
+pub async fn notify(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: NotificationKind,
+    title: &str,
+    data: serde_json::Value,
+) -> AppResult<()> {
+    let notification = sqlx::query_as!(
+        Notification,
+        r#"
+        INSERT INTO notifications (id, user_id, kind, title, data)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, kind as "kind: NotificationKind", title, data, read_at, created_at
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        kind as NotificationKind,
+        title,
+        data
+    )
+        .fetch_one(pool)
+        .await?;
+
+    send_to_user(user_id, &ServerEvent::Notification {
+        notification: notification.clone(),
+        unread_count: unread_count(pool, user_id).await?,
+    });
+    push::dispatch(pool, user_id, &notification);
+
+    Ok(())
+}
+
+/// Alert the seller, and their product.low_stock webhooks, when a sale takes a product's
+/// or variant's stock from above LOW_STOCK_THRESHOLD to at or below it, so each run-down
+/// is reported once
+pub async fn notify_low_stock(
+    pool: &PgPool,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    stock_before: i32,
+    stock_after: i32,
+) -> AppResult<()> {
+    if stock_before <= LOW_STOCK_THRESHOLD || stock_after > LOW_STOCK_THRESHOLD {
+        return Ok(());
+    }
+
+    let product = sqlx::query!(
+        r#"
+        SELECT p.name, p.seller_id, v.name as "variant_name?"
+        FROM products p
+        LEFT JOIN product_variants v ON v.id = $2 AND v.product_id = p.id
+        WHERE p.id = $1
+        "#,
+        product_id,
+        variant_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    let name = match &product.variant_name {
+        Some(variant_name) => format!("{} ({})", product.name, variant_name),
+        None => product.name.clone(),
+    };
+
+    webhooks::enqueue(pool, product.seller_id, WebhookEvent::ProductLowStock, json!({
+        "product_id": product_id,
+        "variant_id": variant_id,
+        "name": name,
+        "stock_qty": stock_after
+    })).await?;
+
+    notify(
+        pool,
+        product.seller_id,
+        NotificationKind::LowStock,
+        &format!("{} is running low: {} left", name, stock_after),
+        json!({
+            "product_id": product_id,
+            "variant_id": variant_id,
+            "stock_qty": stock_after
+        }),
+    ).await
+}
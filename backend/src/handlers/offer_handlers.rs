@@ -0,0 +1,568 @@
+// handlers/offer_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use bigdecimal::{BigDecimal, Zero};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::handlers::address_handlers::shipping_snapshot;
+use crate::handlers::message_handlers::ensure_not_blocked;
+use crate::handlers::notification_handlers::{notify, notify_low_stock};
+use crate::handlers::order_handlers::announce_order_update;
+use crate::models::{
+    CounterOfferRequest, CreateOfferRequest, CreateOrderRequest, InventoryReason, NotificationKind, Offer, OfferContent,
+    OfferQuery, OfferStatus, OrderStatus, ServerEvent, WebhookEvent,
+};
+use crate::repositories::delivery_zone_repository;
+use crate::repositories::inventory_repository::{self, StockMovement};
+use crate::recommendations;
+use crate::repositories::message_repository::{get_or_create_conversation, save_message};
+use crate::repositories::order_repository;
+use crate::services::order_service::{self, line_tax};
+use crate::utils::get_user_id;
+use crate::webhooks;
+use crate::ws::send_to_user;
+
+pub async fn create_offer(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    offer_data: web::Json<CreateOfferRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let offer = open_offer(pool.get_ref(), user_id, &offer_data).await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Offer sent successfully",
+        "offer": offer
+    })))
+}
+
+pub async fn get_offers(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    query: web::Query<OfferQuery>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let offers = sqlx::query!(
+        r#"
+        SELECT o.id, o.product_id, o.buyer_id, o.seller_id, o.proposed_by,
+               o.price_per_unit, o.quantity, o.status as "status: OfferStatus",
+               o.parent_offer_id, o.order_id, o.created_at, o.updated_at,
+               p.name as product_name, b.name as buyer_name, s.name as seller_name
+        FROM offers o
+        JOIN products p ON o.product_id = p.id
+        JOIN users b ON o.buyer_id = b.id
+        JOIN users s ON o.seller_id = s.id
+        WHERE (o.buyer_id = $1 OR o.seller_id = $1)
+          AND ($2::offer_status IS NULL OR o.status = $2)
+        ORDER BY o.updated_at DESC
+        "#,
+        user_id,
+        query.status.clone() as Option<OfferStatus>
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let offers: Vec<serde_json::Value> = offers
+        .into_iter()
+        .map(|o| {
+            json!({
+                "id": o.id,
+                "product_id": o.product_id,
+                "product_name": o.product_name,
+                "buyer_id": o.buyer_id,
+                "buyer_name": o.buyer_name,
+                "seller_id": o.seller_id,
+                "seller_name": o.seller_name,
+                "proposed_by": o.proposed_by,
+                "price_per_unit": o.price_per_unit,
+                "quantity": o.quantity,
+                "status": o.status,
+                "parent_offer_id": o.parent_offer_id,
+                "order_id": o.order_id,
+                "created_at": o.created_at,
+                "updated_at": o.updated_at
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(offers))
+}
+
+pub async fn accept_offer(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let offer_id = path.into_inner();
+
+    let offer = fetch_pending_offer_for_recipient(pool.get_ref(), offer_id, user_id).await?;
+    let offer = set_offer_status(pool.get_ref(), offer.id, OfferStatus::Accepted).await?;
+    announce_offer(pool.get_ref(), user_id, &offer).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Offer accepted",
+        "offer": offer
+    })))
+}
+
+pub async fn reject_offer(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let offer_id = path.into_inner();
+
+    let offer = fetch_pending_offer_for_recipient(pool.get_ref(), offer_id, user_id).await?;
+    let offer = set_offer_status(pool.get_ref(), offer.id, OfferStatus::Rejected).await?;
+    announce_offer(pool.get_ref(), user_id, &offer).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Offer rejected",
+        "offer": offer
+    })))
+}
+
+pub async fn counter_offer(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    counter_data: web::Json<CounterOfferRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let offer_id = path.into_inner();
+
+    validate_terms(&counter_data.price_per_unit, counter_data.quantity)?;
+
+    let previous = fetch_pending_offer_for_recipient(pool.get_ref(), offer_id, user_id).await?;
+    ensure_not_blocked(pool.get_ref(), user_id, previous.proposed_by).await?;
+
+    let mut tx = pool.begin().await?;
+
+    let closed = sqlx::query!(
+        "UPDATE offers SET status = $2, updated_at = NOW() WHERE id = $1 AND status = 'pending'",
+        previous.id,
+        OfferStatus::Countered as OfferStatus
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    if closed.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Offer is no longer pending".to_string()));
+    }
+
+    let offer = sqlx::query_as!(
+        Offer,
+        r#"
+        INSERT INTO offers (id, product_id, buyer_id, seller_id, proposed_by,
+                            price_per_unit, quantity, status, parent_offer_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id, product_id, buyer_id, seller_id, proposed_by, price_per_unit,
+                  quantity, status as "status: OfferStatus", parent_offer_id, order_id,
+                  created_at, updated_at
+        "#,
+        Uuid::new_v4(),
+        previous.product_id,
+        previous.buyer_id,
+        previous.seller_id,
+        user_id,
+        counter_data.price_per_unit,
+        counter_data.quantity,
+        OfferStatus::Pending as OfferStatus,
+        previous.id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    announce_offer(pool.get_ref(), user_id, &offer).await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Counter-offer sent",
+        "offer": offer
+    })))
+}
+
+/// Turn an accepted offer into a pending order at the negotiated price
+pub async fn convert_offer_to_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    req: web::Json<CreateOrderRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let offer_id = path.into_inner();
+
+    let shipping_address = shipping_snapshot(pool.get_ref(), user_id, req.address_id).await?;
+
+    let mut tx = pool.begin().await?;
+    let ordered = order_offer(&mut tx, user_id, offer_id, &shipping_address).await?;
+    tx.commit().await?;
+
+    announce_ordered_offer(pool.get_ref(), user_id, &ordered).await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Order created from offer",
+        "order_id": ordered.order_id,
+        "offer": ordered.offer
+    })))
+}
+
+/// An accepted offer once it has been ordered
+pub struct OrderedOffer {
+    pub order_id: Uuid,
+    pub offer: Offer,
+    // The product's stock after the order took its quantity
+    pub remaining_stock: i32,
+}
+
+/// Place a pending order for the buyer's accepted offer at the negotiated price, in the
+/// caller's transaction. Announce it with `announce_ordered_offer` once that commits.
+pub async fn order_offer(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    offer_id: Uuid,
+    shipping_address: &serde_json::Value,
+) -> AppResult<OrderedOffer> {
+    let offer = sqlx::query_as!(
+        Offer,
+        r#"
+        SELECT id, product_id, buyer_id, seller_id, proposed_by, price_per_unit,
+               quantity, status as "status: OfferStatus", parent_offer_id, order_id,
+               created_at, updated_at
+        FROM offers
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        offer_id
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Offer not found".to_string()))?;
+
+    if offer.buyer_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    if offer.status != OfferStatus::Accepted {
+        return Err(AppError::BadRequest("Only accepted offers can be ordered".to_string()));
+    }
+
+    let (pincode, location) = order_service::destination(shipping_address);
+    let undeliverable = delivery_zone_repository::undeliverable(conn, &[offer.seller_id], &pincode, location).await?;
+    if let Some(seller) = undeliverable.first() {
+        return Err(AppError::BadRequest(order_service::undeliverable_message(seller, &pincode)));
+    }
+
+    // Reserve stock; fails if it ran out since the offer was made
+    let reserved = sqlx::query!(
+        r#"
+        UPDATE products SET stock_qty = stock_qty - $2
+        WHERE id = $1 AND stock_qty >= $2 AND taken_down_at IS NULL AND deleted_at IS NULL
+          AND review_status = 'approved'
+          AND NOT EXISTS(SELECT 1 FROM product_variants v WHERE v.product_id = products.id)
+        RETURNING stock_qty, currency, category_tax_rate(category_id) as "tax_rate!"
+        "#,
+        offer.product_id,
+        offer.quantity
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::BadRequest(format!(
+            "Insufficient stock for product {}",
+            offer.product_id
+        )))?;
+    let remaining = reserved.stock_qty;
+
+    // Taxed like a checkout: only when the seller is registered, on the agreed price
+    let seller = sqlx::query!("SELECT tax_id, tax_name FROM users WHERE id = $1", offer.seller_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    let tax_rate = match seller.tax_id {
+        Some(_) => reserved.tax_rate,
+        None => BigDecimal::zero(),
+    };
+
+    let order_id = Uuid::new_v4();
+    let subtotal_price: BigDecimal = offer.quantity * &offer.price_per_unit;
+    let tax_amount = line_tax(&subtotal_price, &subtotal_price, &BigDecimal::zero(), &tax_rate);
+    let total_price = &subtotal_price + &tax_amount;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO orders (id, buyer_id, seller_id, status, subtotal_price, tax_amount, total_price, currency,
+                            shipping_address, seller_tax_id, seller_tax_name, expected_delivery_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, order_eta($3, NULL))
+        "#,
+        order_id,
+        offer.buyer_id,
+        offer.seller_id,
+        OrderStatus::Pending as OrderStatus,
+        subtotal_price,
+        tax_amount,
+        total_price,
+        reserved.currency,
+        shipping_address,
+        seller.tax_id,
+        seller.tax_name
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    order_repository::record_status(conn, order_id, None, OrderStatus::Pending, Some(user_id)).await?;
+    webhooks::enqueue(&mut *conn, offer.seller_id, WebhookEvent::OrderCreated, json!({
+        "order_id": order_id,
+        "buyer_id": offer.buyer_id,
+        "status": OrderStatus::Pending,
+        "total_price": total_price,
+        "currency": reserved.currency,
+        "delivery_slot_id": null
+    })).await?;
+    inventory_repository::record(conn, StockMovement {
+        product_id: offer.product_id,
+        variant_id: None,
+        quantity_change: -offer.quantity,
+        stock_after: remaining,
+        reason: InventoryReason::Order,
+        note: None,
+        order_id: Some(order_id),
+        actor_id: Some(user_id),
+    }).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO order_items (id, order_id, product_id, quantity, unit_price, tax_rate, tax_amount)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        offer.product_id,
+        offer.quantity,
+        offer.price_per_unit,
+        tax_rate,
+        tax_amount
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    let offer = sqlx::query_as!(
+        Offer,
+        r#"
+        UPDATE offers SET status = $2, order_id = $3, updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, product_id, buyer_id, seller_id, proposed_by, price_per_unit,
+                  quantity, status as "status: OfferStatus", parent_offer_id, order_id,
+                  created_at, updated_at
+        "#,
+        offer.id,
+        OfferStatus::Ordered as OfferStatus,
+        order_id
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(OrderedOffer { order_id, offer, remaining_stock: remaining })
+}
+
+/// Tell both sides about an ordered offer, after the order has committed
+pub async fn announce_ordered_offer(pool: &PgPool, user_id: Uuid, ordered: &OrderedOffer) -> AppResult<()> {
+    let offer = &ordered.offer;
+    recommendations::invalidate(user_id);
+
+    announce_offer(pool, user_id, offer).await?;
+    announce_order_update(pool, ordered.order_id).await?;
+    notify_low_stock(pool, offer.product_id, None, ordered.remaining_stock + offer.quantity, ordered.remaining_stock).await
+}
+
+/// Open a new negotiation; shared by the REST endpoint and the WebSocket handler
+pub async fn open_offer(pool: &PgPool, sender_id: Uuid, request: &CreateOfferRequest) -> AppResult<Offer> {
+    validate_terms(&request.price_per_unit, request.quantity)?;
+
+    let product = sqlx::query!(
+        r#"
+        SELECT seller_id, stock_qty,
+               EXISTS(SELECT 1 FROM product_variants v WHERE v.product_id = products.id) as "has_variants!"
+        FROM products
+        WHERE id = $1 AND taken_down_at IS NULL AND deleted_at IS NULL
+          AND review_status = 'approved'
+        "#,
+        request.product_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    // Offers are per product; variant pricing goes through the cart
+    if product.has_variants {
+        return Err(AppError::BadRequest("Offers can't be made on products with variants".to_string()));
+    }
+
+    // The seller may open a negotiation with a specific buyer; anyone else is the buyer
+    let buyer_id = if sender_id == product.seller_id {
+        match request.recipient_id {
+            Some(recipient_id) if recipient_id != sender_id => recipient_id,
+            _ => return Err(AppError::BadRequest("recipient_id must name the buyer".to_string())),
+        }
+    } else {
+        if request.recipient_id.is_some_and(|id| id != product.seller_id) {
+            return Err(AppError::BadRequest("Offers must be sent to the product's seller".to_string()));
+        }
+        sender_id
+    };
+
+    if request.quantity > product.stock_qty {
+        return Err(AppError::BadRequest("Offer quantity exceeds available stock".to_string()));
+    }
+
+    let recipient_id = if sender_id == buyer_id { product.seller_id } else { buyer_id };
+    ensure_not_blocked(pool, sender_id, recipient_id).await?;
+
+    let offer = sqlx::query_as!(
+        Offer,
+        r#"
+        INSERT INTO offers (id, product_id, buyer_id, seller_id, proposed_by,
+                            price_per_unit, quantity, status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id, product_id, buyer_id, seller_id, proposed_by, price_per_unit,
+                  quantity, status as "status: OfferStatus", parent_offer_id, order_id,
+                  created_at, updated_at
+        "#,
+        Uuid::new_v4(),
+        request.product_id,
+        buyer_id,
+        product.seller_id,
+        sender_id,
+        request.price_per_unit,
+        request.quantity,
+        OfferStatus::Pending as OfferStatus
+    )
+        .fetch_one(pool)
+        .await?;
+
+    announce_offer(pool, sender_id, &offer).await?;
+
+    Ok(offer)
+}
+
+fn validate_terms(price_per_unit: &BigDecimal, quantity: i32) -> AppResult<()> {
+    if *price_per_unit <= BigDecimal::zero() {
+        return Err(AppError::BadRequest("Price must be greater than zero".to_string()));
+    }
+    if quantity <= 0 {
+        return Err(AppError::BadRequest("Quantity must be greater than zero".to_string()));
+    }
+    Ok(())
+}
+
+/// Load a pending offer that `user_id` is allowed to respond to (the party that did not propose it)
+async fn fetch_pending_offer_for_recipient(pool: &PgPool, offer_id: Uuid, user_id: Uuid) -> AppResult<Offer> {
+    let offer = sqlx::query_as!(
+        Offer,
+        r#"
+        SELECT id, product_id, buyer_id, seller_id, proposed_by, price_per_unit,
+               quantity, status as "status: OfferStatus", parent_offer_id, order_id,
+               created_at, updated_at
+        FROM offers
+        WHERE id = $1
+        "#,
+        offer_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Offer not found".to_string()))?;
+
+    if user_id != offer.buyer_id && user_id != offer.seller_id {
+        return Err(AppError::NotFound("Offer not found".to_string()));
+    }
+
+    if user_id == offer.proposed_by {
+        return Err(AppError::Forbidden);
+    }
+
+    if offer.status != OfferStatus::Pending {
+        return Err(AppError::BadRequest("Offer is no longer pending".to_string()));
+    }
+
+    Ok(offer)
+}
+
+async fn set_offer_status(pool: &PgPool, offer_id: Uuid, status: OfferStatus) -> AppResult<Offer> {
+    let offer = sqlx::query_as!(
+        Offer,
+        r#"
+        UPDATE offers SET status = $2, updated_at = NOW()
+        WHERE id = $1 AND status = 'pending'
+        RETURNING id, product_id, buyer_id, seller_id, proposed_by, price_per_unit,
+                  quantity, status as "status: OfferStatus", parent_offer_id, order_id,
+                  created_at, updated_at
+        "#,
+        offer_id,
+        status as OfferStatus
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Offer is no longer pending".to_string()))?;
+
+    Ok(offer)
+}
+
+/// Record an offer event in the buyer/seller conversation and push it to both sides
+pub async fn announce_offer(pool: &PgPool, actor_id: Uuid, offer: &Offer) -> AppResult<()> {
+    let conv_id = get_or_create_conversation(pool, offer.buyer_id, offer.seller_id).await?;
+
+    let content = serde_json::to_string(&OfferContent {
+        msg_type: "offer".to_string(),
+        offer_id: offer.id,
+        product_id: offer.product_id,
+        price: offer.price_per_unit.clone(),
+        qty: offer.quantity,
+        status: offer.status.clone(),
+    })
+        .map_err(|_| AppError::InternalError)?;
+
+    let saved_message = save_message(pool, conv_id, actor_id, &content, None).await?;
+
+    let sender_name = sqlx::query_scalar!(
+        "SELECT name FROM users WHERE id = $1",
+        actor_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    let event = ServerEvent::Offer {
+        id: saved_message.id,
+        conv_id,
+        sender_id: actor_id,
+        sender_name,
+        content,
+        sent_at: saved_message.sent_at,
+        offer: offer.clone(),
+    };
+
+    send_to_user(offer.buyer_id, &event);
+    send_to_user(offer.seller_id, &event);
+
+    // The other side of the negotiation hears about it in their notifications too
+    let recipient_id = if actor_id == offer.buyer_id { offer.seller_id } else { offer.buyer_id };
+    let title = match offer.status {
+        OfferStatus::Pending => "You received a new offer",
+        OfferStatus::Accepted => "Your offer was accepted",
+        OfferStatus::Rejected => "Your offer was rejected",
+        OfferStatus::Countered => "Your offer was countered",
+        OfferStatus::Ordered => "An accepted offer was ordered",
+    };
+    notify(
+        pool,
+        recipient_id,
+        NotificationKind::Offer,
+        title,
+        json!({ "offer_id": offer.id, "conv_id": conv_id, "status": offer.status }),
+    ).await
+}
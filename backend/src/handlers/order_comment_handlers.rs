@@ -0,0 +1,112 @@
+// handlers/order_comment_handlers.rs
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::errors::{AppError, AppResult};
+use crate::handlers::notification_handlers::notify;
+use crate::models::{CreateOrderCommentRequest, NotificationKind, OrderComment, ServerEvent};
+use crate::validation::Validate;
+use crate::ws::send_to_user;
+
+/// The order's comments, oldest first (buyer or seller)
+pub async fn get_order_comments(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let order_id = order_id.into_inner();
+
+    order_parties(pool.get_ref(), order_id, user.id).await?;
+
+    let comments = sqlx::query_as!(
+        OrderComment,
+        r#"
+        SELECT c.id, c.order_id, c.author_id, u.name as author_name, c.body, c.created_at
+        FROM order_comments c
+        JOIN users u ON c.author_id = u.id
+        WHERE c.order_id = $1
+        ORDER BY c.created_at, c.id
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "order_id": order_id,
+        "comments": comments
+    })))
+}
+
+/// Add a comment to the order (buyer or seller)
+pub async fn create_order_comment(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<CreateOrderCommentRequest>,
+) -> AppResult<HttpResponse> {
+    let author_id = user.id;
+    let order_id = order_id.into_inner();
+    req.validate()?;
+
+    let (buyer_id, seller_id) = order_parties(pool.get_ref(), order_id, author_id).await?;
+
+    let comment = sqlx::query_as!(
+        OrderComment,
+        r#"
+        WITH comment AS (
+            INSERT INTO order_comments (id, order_id, author_id, body)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, order_id, author_id, body, created_at
+        )
+        SELECT c.id, c.order_id, c.author_id, u.name as author_name, c.body, c.created_at
+        FROM comment c
+        JOIN users u ON c.author_id = u.id
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        author_id,
+        req.body.trim()
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    // The author too, for their other open tabs and devices
+    let event = ServerEvent::OrderComment { comment: comment.clone() };
+    send_to_user(buyer_id, &event);
+    send_to_user(seller_id, &event);
+
+    let (recipient_id, title) = if author_id == buyer_id {
+        (seller_id, "The buyer commented on an order")
+    } else {
+        (buyer_id, "The seller commented on your order")
+    };
+    notify(
+        pool.get_ref(),
+        recipient_id,
+        NotificationKind::Order,
+        title,
+        json!({ "order_id": order_id, "comment_id": comment.id }),
+    ).await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Comment added",
+        "comment": comment
+    })))
+}
+
+/// The order's buyer and seller; the user must be one of them
+async fn order_parties(pool: &PgPool, order_id: Uuid, user_id: Uuid) -> AppResult<(Uuid, Uuid)> {
+    let order = sqlx::query!("SELECT buyer_id, seller_id FROM orders WHERE id = $1", order_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != user_id && order.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    Ok((order.buyer_id, order.seller_id))
+}
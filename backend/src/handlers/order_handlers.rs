@@ -1,133 +1,164 @@
 // handlers/order_handlers.rs
-use actix_identity::Identity;
-use actix_session::Session;
 use actix_web::{web, HttpResponse};
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{NaiveDate, Utc};
 use serde_json::json;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::auth::AuthUser;
+use crate::config::Config;
 use crate::errors::{AppError, AppResult};
-use crate::models::{CartItem, OrderStatus, UpdateOrderStatusRequest};
-use crate::utils::get_user_id;
+use crate::handlers::address_handlers::shipping_snapshot;
+use crate::handlers::coupon_handlers::{cart_coupon, check_coupon, discount_for};
+use crate::handlers::notification_handlers::{notify, notify_low_stock};
+use crate::mailer::{self, templates, EmailSender};
+use crate::models::{BulkOrderStatusRequest, CartItem, CreateOrderRequest, FulfillmentStatus, NotificationKind, OrderQuery, OrderStatus, PicklistLine, PicklistQuery, ProductUnit, ReorderRequest, RescheduleDeliveryRequest, ServerEvent, ShipmentDetails, UpdateItemFulfillmentRequest, UpdateOrderStatusRequest};
+use crate::recommendations;
+use crate::repositories::order_repository::{self, NewOrder};
+use crate::repositories::{cart_repository, delivery_slot_repository, delivery_zone_repository, ledger_repository, location_repository, product_repository, reservation_repository};
+use crate::services::order_service;
+use crate::utils::Pagination;
+use crate::validation::Validate;
+use crate::ws::send_to_user;
 
-const CART_SESSION_KEY: &str = "cart";
+/// Most orders a seller can move in one bulk status change
+const MAX_BULK_ORDERS: usize = 100;
 
 pub async fn create_order(
-    identity: Identity,
-    session: Session,
+    user: AuthUser,
     pool: web::Data<PgPool>,
+    req: web::Json<CreateOrderRequest>,
 ) -> AppResult<HttpResponse> {
-    let buyer_id = get_user_id(&identity).expect("Failed to get user ID from identity");
+    let buyer_id = user.id;
 
-    // Get cart from session
-    let cart_items: Vec<CartItem> = session
-        .get::<Vec<CartItem>>(CART_SESSION_KEY)
-        .expect("Failed to get cart from session")
-        .unwrap_or_default();
+    // Every order produced by this checkout ships to the same address
+    let shipping_address = shipping_snapshot(pool.get_ref(), buyer_id, req.address_id).await?;
+
+    // Get cart
+    let cart_items = cart_repository::get_items(pool.get_ref(), buyer_id).await?;
 
     if cart_items.is_empty() {
         return Err(AppError::BadRequest("Cart is empty".to_string()));
     }
 
-    // Group items by seller
     let product_ids: Vec<Uuid> = cart_items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<Uuid> = cart_items.iter().filter_map(|item| item.variant_id).collect();
 
-    let products = sqlx::query!(
-        r#"
-        SELECT id, seller_id, price_per_unit, stock_qty
-        FROM products
-        WHERE id = ANY($1)
-        "#,
-        &product_ids
-    )
-        .fetch_all(pool.get_ref())
-        .await.expect("Failed to fetch products from database");
-
-    // Group by seller
-    let mut orders_by_seller: std::collections::HashMap<Uuid, Vec<(Uuid, i32, BigDecimal)>> =
-        std::collections::HashMap::new();
-
-    for item in &cart_items {
-        if let Some(product) = products.iter().find(|p| p.id == item.product_id) {
-            if product.stock_qty < item.quantity {
-                return Err(AppError::BadRequest(format!(
-                    "Insufficient stock for product {}",
-                    product.id
-                )));
-            }
+    // Begin transaction
+    let mut tx = pool.begin().await?;
 
-            orders_by_seller
-                .entry(product.seller_id)
-                .or_insert_with(Vec::new)
-                .push((product.id, item.quantity, product.price_per_unit.clone()));
-        }
+    let products = product_repository::lock_for_checkout(&mut tx, &product_ids).await?;
+    let variants = product_repository::checkout_variants(&mut tx, &variant_ids).await?;
+
+    // Every line must still be buyable at the quantity and price the buyer last saw; other
+    // buyers' cart holds are off limits. POST /cart/validate reconciles the cart.
+    let issues = cart_repository::check_lines(&mut tx, buyer_id).await?;
+    if !issues.is_empty() {
+        return Err(AppError::CartChanged(issues.iter().map(|issue| json!(issue)).collect()));
     }
 
-    // Begin transaction
-    let mut tx = pool.begin().await.expect("Failed to begin database transaction");
+    let prices = product_repository::line_prices(&mut tx, &cart_items).await?;
+    let orders_by_seller = order_service::group_by_seller(&cart_items, &products, &variants, &prices)?;
+
+    // The cart's coupon discounts the issuing seller's order; an unusable one fails the checkout
+    let coupon = cart_coupon(&mut tx, buyer_id).await?;
+
+    let seller_ids: Vec<Uuid> = orders_by_seller.keys().copied().collect();
+    let seller_terms = order_repository::seller_terms(&mut tx, &seller_ids).await?;
+
+    let (pincode, location) = order_service::destination(&shipping_address);
+    let undeliverable = delivery_zone_repository::undeliverable(&mut tx, &seller_ids, &pincode, location).await?;
+    order_service::check_delivery_zones(&undeliverable, &pincode)?;
+
+    // Locked until commit, so the orders stored below are what fills the slots
+    let mut slot_ids = req.delivery_slot_ids.clone();
+    slot_ids.sort();
+    slot_ids.dedup();
+    let slots = delivery_slot_repository::lock(&mut tx, &slot_ids).await?;
+    let mut slots_by_seller = order_service::assign_delivery_slots(&slot_ids, slots, &seller_ids)?;
 
     let mut created_orders = vec![];
+    let mut order_sellers = vec![];
+    // (product, variant, stock before, stock after) for low-stock alerts
+    let mut stock_changes = vec![];
 
     // Create orders for each seller
-    for (seller_id, items) in orders_by_seller {
+    for (seller_id, lines) in orders_by_seller {
         let order_id = Uuid::new_v4();
-        let total_price: BigDecimal = items.iter().map(|(_, qty, price)| *qty * price).sum();
+        let terms = &seller_terms[&seller_id];
+        let subtotal_price = order_service::subtotal(&lines);
+        order_service::check_seller_terms(terms, &subtotal_price)?;
+        let currency = order_service::order_currency(&lines)?;
 
-        // Create order
-        sqlx::query!(
-            r#"
-            INSERT INTO orders (id, buyer_id, seller_id, status, total_price)
-            VALUES ($1, $2, $3, $4, $5)
-            "#,
-            order_id,
+        let delivery_slot = slots_by_seller.remove(&seller_id);
+        if let Some(slot) = &delivery_slot {
+            order_service::check_delivery_slot(slot, Utc::now())?;
+        }
+
+        let seller_coupon = coupon.as_ref().filter(|coupon| coupon.seller_id == seller_id);
+        let discount_amount = match seller_coupon {
+            Some(coupon) => {
+                check_coupon(&mut tx, coupon, buyer_id, &subtotal_price).await?;
+                discount_for(coupon, &subtotal_price)
+            }
+            None => BigDecimal::from(0),
+        };
+
+        let pricing = order_service::price_order(&lines, terms, discount_amount);
+
+        order_repository::insert(&mut tx, &NewOrder {
+            id: order_id,
             buyer_id,
             seller_id,
-            OrderStatus::Pending as OrderStatus,
-            total_price
-        )
-            .execute(&mut *tx)
-            .await
-            .expect("Failed to insert order into database");
-
-        // Create order items
-        for (product_id, quantity, unit_price) in &items {
-            let item_id = Uuid::new_v4();
-
-            sqlx::query!(
-                r#"
-                INSERT INTO order_items (id, order_id, product_id, quantity, unit_price)
-                VALUES ($1, $2, $3, $4, $5)
-                "#,
-                item_id,
-                order_id,
-                product_id,
-                quantity,
-                unit_price
-            )
-                .execute(&mut *tx)
-                .await
-                .expect("Failed to insert order item into database");
-
-            // Update product stock
-            sqlx::query!(
-                "UPDATE products SET stock_qty = stock_qty - $2 WHERE id = $1",
-                product_id,
-                quantity
-            )
-                .execute(&mut *tx)
-                .await
-                .expect("Failed to update product stock");
-        }
+            subtotal_price: &pricing.subtotal_price,
+            discount_amount: &pricing.discount_amount,
+            coupon_id: seller_coupon.map(|coupon| coupon.id),
+            delivery_fee: &pricing.delivery_fee,
+            tax_amount: &pricing.tax_amount,
+            total_price: &pricing.total_price,
+            currency,
+            shipping_address: &shipping_address,
+            delivery_slot_id: delivery_slot.map(|slot| slot.id),
+            seller_tax_id: terms.tax_id.as_deref(),
+            seller_tax_name: terms.tax_name.as_deref(),
+        }).await?;
+
+        stock_changes.extend(order_service::insert_lines(&mut *tx, order_id, buyer_id, &lines, &pricing).await?);
 
         created_orders.push(order_id);
+        order_sellers.push((order_id, seller_id));
     }
 
+    // Clear cart and its stock holds together with the orders it produced
+    cart_repository::clear(&mut tx, buyer_id).await?;
+
     // Commit transaction
-    tx.commit().await.expect("Failed to commit database transaction");
+    tx.commit().await?;
+    recommendations::invalidate(buyer_id);
 
-    // Clear cart
-    session.remove(CART_SESSION_KEY);
+    // The orders are placed; failing the request now would only get them placed again
+    for (order_id, seller_id) in order_sellers {
+        if let Err(e) = announce_order_update(pool.get_ref(), order_id).await {
+            tracing::warn!(%order_id, error = %e, "Failed to announce new order");
+        }
+        if let Err(e) = notify(
+            pool.get_ref(),
+            seller_id,
+            NotificationKind::Order,
+            "You have a new order",
+            json!({ "order_id": order_id }),
+        ).await {
+            tracing::warn!(%order_id, error = %e, "Failed to notify seller of new order");
+        }
+    }
+    for (product_id, variant_id, stock_before, stock_after) in stock_changes {
+        if let Err(e) = notify_low_stock(pool.get_ref(), product_id, variant_id, stock_before, stock_after).await {
+            tracing::warn!(%product_id, error = %e, "Failed to send low stock alert");
+        }
+    }
 
     Ok(HttpResponse::Created().json(json!({
         "message": "Orders created successfully",
@@ -136,70 +167,172 @@ pub async fn create_order(
 }
 
 pub async fn get_orders(
-    identity: Identity,
+    user: AuthUser,
     pool: web::Data<PgPool>,
+    query: web::Query<OrderQuery>,
 ) -> AppResult<HttpResponse> {
-    let user_id = get_user_id(&identity).expect("Failed to get user ID from identity");
+    let user_id = user.id;
+    let pagination = Pagination::new(query.page, query.limit);
+    let search = order_search(&query)?;
 
     let orders = sqlx::query!(
         r#"
         SELECT o.id, o.seller_id, o.status as "status: OrderStatus",
-               o.total_price, o.created_at,
-               u.name as seller_name
+               o.subtotal_price, o.discount_amount, o.delivery_fee, o.tax_amount, o.total_price, o.currency, o.shipping_address, o.created_at,
+               o.courier_name, o.tracking_number, o.expected_delivery_date, o.expected_delivery_at,
+               ds.id as "delivery_slot_id?", ds.starts_at as "delivery_starts_at?", ds.ends_at as "delivery_ends_at?",
+               u.name as seller_name, c.code as "coupon_code?"
         FROM orders o
         JOIN users u ON o.seller_id = u.id
+        LEFT JOIN coupons c ON o.coupon_id = c.id
+        LEFT JOIN delivery_slots ds ON o.delivery_slot_id = ds.id
         WHERE o.buyer_id = $1
-        ORDER BY o.created_at DESC
+          AND ($2::order_status IS NULL OR o.status = $2)
+          AND ($3::uuid IS NULL OR o.seller_id = $3)
+          AND ($4::timestamptz IS NULL OR o.created_at >= $4)
+          AND ($5::timestamptz IS NULL OR o.created_at < $5)
+          AND ($6::text IS NULL OR EXISTS (
+                SELECT 1 FROM order_items oi JOIN products p ON oi.product_id = p.id
+                WHERE oi.order_id = o.id AND (p.name ILIKE '%' || $6 || '%' OR oi.variant_name ILIKE '%' || $6 || '%')
+              ))
+        ORDER BY o.created_at DESC, o.id DESC
+        LIMIT $7 OFFSET $8
         "#,
-        user_id
+        user_id,
+        query.status.clone() as Option<OrderStatus>,
+        query.seller_id,
+        query.from,
+        query.to,
+        search,
+        pagination.limit,
+        pagination.offset
     )
         .fetch_all(pool.get_ref())
-        .await.expect("Failed to fetch orders from database");
+        .await?;
 
-    let mut order_details = vec![];
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM orders o
+        WHERE o.buyer_id = $1
+          AND ($2::order_status IS NULL OR o.status = $2)
+          AND ($3::uuid IS NULL OR o.seller_id = $3)
+          AND ($4::timestamptz IS NULL OR o.created_at >= $4)
+          AND ($5::timestamptz IS NULL OR o.created_at < $5)
+          AND ($6::text IS NULL OR EXISTS (
+                SELECT 1 FROM order_items oi JOIN products p ON oi.product_id = p.id
+                WHERE oi.order_id = o.id AND (p.name ILIKE '%' || $6 || '%' OR oi.variant_name ILIKE '%' || $6 || '%')
+              ))
+        "#,
+        user_id,
+        query.status.clone() as Option<OrderStatus>,
+        query.seller_id,
+        query.from,
+        query.to,
+        search
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
 
-    for order in orders {
-        // Get order items
-        let items = sqlx::query!(
-            r#"
-            SELECT oi.product_id, oi.quantity, oi.unit_price,
-                   p.name as product_name, p.image_url
-            FROM order_items oi
-            JOIN products p ON oi.product_id = p.id
-            WHERE oi.order_id = $1
-            "#,
-            order.id
-        )
-            .fetch_all(pool.get_ref())
-            .await.expect("Failed to fetch order items from database");
+    let order_ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+    let mut items_by_order = order_items_by_order(pool.get_ref(), &order_ids).await?;
 
-        order_details.push(json!({
+    let order_details = orders.iter().map(|order| {
+        json!({
             "id": order.id,
             "seller_id": order.seller_id,
             "seller_name": order.seller_name,
             "status": order.status,
+            "subtotal_price": order.subtotal_price,
+            "discount_amount": order.discount_amount,
+            "delivery_fee": order.delivery_fee,
+            "tax_amount": order.tax_amount,
+            "coupon_code": order.coupon_code,
             "total_price": order.total_price,
+            "currency": order.currency,
+            "shipping_address": order.shipping_address,
+            "delivery_slot": order.delivery_slot_id.map(|id| json!({
+                "id": id,
+                "starts_at": order.delivery_starts_at,
+                "ends_at": order.delivery_ends_at
+            })),
+            "shipment": shipment_json(
+                order.courier_name.as_deref(),
+                order.tracking_number.as_deref(),
+                order.expected_delivery_date
+            ),
+            "expected_delivery_at": order.expected_delivery_at,
             "created_at": order.created_at,
-            "items": items.iter().map(|item| json!({
+            "items": items_by_order.remove(&order.id).unwrap_or_default()
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "orders": order_details,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// Validate an order history filter, returning its trimmed product search
+fn order_search(query: &OrderQuery) -> AppResult<Option<&str>> {
+    if let (Some(from), Some(to)) = (query.from, query.to)
+        && from >= to
+    {
+        return Err(AppError::BadRequest("from must be before to".to_string()));
+    }
+
+    Ok(query.search.as_deref().map(str::trim).filter(|s| !s.is_empty()))
+}
+
+/// Items of the given orders, fetched in one query and grouped by order
+async fn order_items_by_order(pool: &PgPool, order_ids: &[Uuid]) -> AppResult<HashMap<Uuid, Vec<serde_json::Value>>> {
+    let items = sqlx::query!(
+        r#"
+        SELECT oi.id, oi.order_id, oi.product_id, oi.variant_id, oi.variant_name, oi.quantity, oi.unit_price,
+               oi.tax_rate, oi.tax_amount,
+               oi.fulfillment_status as "fulfillment_status: FulfillmentStatus", oi.shipped_at, oi.delivered_at,
+               p.name as product_name, p.unit as "unit: ProductUnit", p.image_url
+        FROM order_items oi
+        JOIN products p ON oi.product_id = p.id
+        WHERE oi.order_id = ANY($1)
+        "#,
+        order_ids
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let mut items_by_order: HashMap<Uuid, Vec<serde_json::Value>> = HashMap::new();
+    for item in &items {
+        items_by_order
+            .entry(item.order_id)
+            .or_default()
+            .push(json!({
+                "id": item.id,
                 "product_id": item.product_id,
                 "product_name": item.product_name,
+                "variant_id": item.variant_id,
+                "variant_name": item.variant_name,
                 "quantity": item.quantity,
+                "unit": item.unit,
                 "unit_price": item.unit_price,
-                "image_url": item.image_url
-            })).collect::<Vec<_>>()
-        }));
+                "tax_rate": item.tax_rate,
+                "tax_amount": item.tax_amount,
+                "image_url": item.image_url,
+                "fulfillment_status": item.fulfillment_status,
+                "shipped_at": item.shipped_at,
+                "delivered_at": item.delivered_at
+            }));
     }
 
-    Ok(HttpResponse::Ok().json(json!({
-        "orders": order_details
-    })))
+    Ok(items_by_order)
 }
 
+/// The seller's pending orders, newest first, flagging those past their expected delivery
 pub async fn get_seller_pending_orders(
-    identity: Identity,
+    user: AuthUser,
     pool: web::Data<PgPool>,
 ) -> AppResult<HttpResponse> {
-    let seller_id = get_user_id(&identity).expect("Failed to get user ID from identity");
+    let seller_id = user.id;
 
     // Check if user is a supplier
     let is_supplier = sqlx::query_scalar!(
@@ -207,7 +340,7 @@ pub async fn get_seller_pending_orders(
         seller_id
     )
         .fetch_one(pool.get_ref())
-        .await.expect("Failed to fetch supplier status from database");
+        .await?;
 
     if !is_supplier {
         return Err(AppError::Forbidden);
@@ -216,27 +349,33 @@ pub async fn get_seller_pending_orders(
     let orders = sqlx::query!(
         r#"
         SELECT o.id, o.buyer_id, o.status as "status: OrderStatus",
-               o.total_price, o.created_at,
-               u.name as buyer_name, u.phone as buyer_phone
+               o.subtotal_price, o.discount_amount, o.delivery_fee, o.tax_amount, o.total_price, o.currency, o.shipping_address, o.created_at,
+               o.expected_delivery_at, COALESCE(o.expected_delivery_at < NOW(), FALSE) as "overdue!",
+               ds.id as "delivery_slot_id?", ds.starts_at as "delivery_starts_at?", ds.ends_at as "delivery_ends_at?",
+               u.name as buyer_name, u.phone as buyer_phone, c.code as "coupon_code?"
         FROM orders o
         JOIN users u ON o.buyer_id = u.id
+        LEFT JOIN coupons c ON o.coupon_id = c.id
+        LEFT JOIN delivery_slots ds ON o.delivery_slot_id = ds.id
         WHERE o.seller_id = $1 AND o.status = $2
-        ORDER BY o.created_at DESC
+        ORDER BY o.created_at DESC, o.id DESC
         "#,
         seller_id,
         OrderStatus::Pending as OrderStatus
     )
         .fetch_all(pool.get_ref())
-        .await.expect("Failed to fetch seller pending orders from database");
+        .await?;
 
+    let overdue_count = orders.iter().filter(|order| order.overdue).count();
     let mut order_details = vec![];
 
     for order in orders {
         // Get order items
         let items = sqlx::query!(
             r#"
-            SELECT oi.product_id, oi.quantity, oi.unit_price,
-                   p.name as product_name
+            SELECT oi.id, oi.product_id, oi.variant_name, oi.quantity, oi.unit_price,
+                   oi.fulfillment_status as "fulfillment_status: FulfillmentStatus",
+                   p.name as product_name, p.unit as "unit: ProductUnit"
             FROM order_items oi
             JOIN products p ON oi.product_id = p.id
             WHERE oi.order_id = $1
@@ -244,7 +383,7 @@ pub async fn get_seller_pending_orders(
             order.id
         )
             .fetch_all(pool.get_ref())
-            .await.expect("Failed to fetch seller order items from database");
+            .await?;
 
         order_details.push(json!({
             "id": order.id,
@@ -252,66 +391,1223 @@ pub async fn get_seller_pending_orders(
             "buyer_name": order.buyer_name,
             "buyer_phone": order.buyer_phone,
             "status": order.status,
+            "subtotal_price": order.subtotal_price,
+            "discount_amount": order.discount_amount,
+            "delivery_fee": order.delivery_fee,
+            "tax_amount": order.tax_amount,
+            "coupon_code": order.coupon_code,
             "total_price": order.total_price,
+            "currency": order.currency,
+            "shipping_address": order.shipping_address,
+            "delivery_slot": order.delivery_slot_id.map(|id| json!({
+                "id": id,
+                "starts_at": order.delivery_starts_at,
+                "ends_at": order.delivery_ends_at
+            })),
+            "expected_delivery_at": order.expected_delivery_at,
+            "overdue": order.overdue,
             "created_at": order.created_at,
             "items": items.iter().map(|item| json!({
+                "id": item.id,
                 "product_id": item.product_id,
                 "product_name": item.product_name,
+                "variant_name": item.variant_name,
                 "quantity": item.quantity,
-                "unit_price": item.unit_price
+                "unit": item.unit,
+                "unit_price": item.unit_price,
+                "fulfillment_status": item.fulfillment_status
             })).collect::<Vec<_>>()
         }));
     }
 
     Ok(HttpResponse::Ok().json(json!({
-        "orders": order_details
+        "orders": order_details,
+        "overdue_count": overdue_count
+    })))
+}
+
+/// A seller's orders in any status, with the same filters as the buyer's history
+/// (`buyer_id` in place of `seller_id`). Open orders past their expected delivery are
+/// flagged overdue.
+pub async fn get_seller_orders(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    query: web::Query<OrderQuery>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    let pagination = Pagination::new(query.page, query.limit);
+    let search = order_search(&query)?;
+
+    let is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1",
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+
+    let orders = sqlx::query!(
+        r#"
+        SELECT o.id, o.buyer_id, o.status as "status: OrderStatus",
+               o.subtotal_price, o.discount_amount, o.delivery_fee, o.tax_amount, o.total_price, o.currency, o.shipping_address, o.created_at,
+               o.courier_name, o.tracking_number, o.expected_delivery_date, o.expected_delivery_at,
+               COALESCE(
+                   o.status IN ('pending', 'paid', 'partially_shipped', 'shipped') AND o.expected_delivery_at < NOW(),
+                   FALSE
+               ) as "overdue!",
+               ds.id as "delivery_slot_id?", ds.starts_at as "delivery_starts_at?", ds.ends_at as "delivery_ends_at?",
+               u.name as buyer_name, u.phone as buyer_phone, c.code as "coupon_code?"
+        FROM orders o
+        JOIN users u ON o.buyer_id = u.id
+        LEFT JOIN coupons c ON o.coupon_id = c.id
+        LEFT JOIN delivery_slots ds ON o.delivery_slot_id = ds.id
+        WHERE o.seller_id = $1
+          AND ($2::order_status IS NULL OR o.status = $2)
+          AND ($3::uuid IS NULL OR o.buyer_id = $3)
+          AND ($4::timestamptz IS NULL OR o.created_at >= $4)
+          AND ($5::timestamptz IS NULL OR o.created_at < $5)
+          AND ($6::text IS NULL OR EXISTS (
+                SELECT 1 FROM order_items oi JOIN products p ON oi.product_id = p.id
+                WHERE oi.order_id = o.id AND (p.name ILIKE '%' || $6 || '%' OR oi.variant_name ILIKE '%' || $6 || '%')
+              ))
+        ORDER BY o.created_at DESC, o.id DESC
+        LIMIT $7 OFFSET $8
+        "#,
+        seller_id,
+        query.status.clone() as Option<OrderStatus>,
+        query.buyer_id,
+        query.from,
+        query.to,
+        search,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM orders o
+        WHERE o.seller_id = $1
+          AND ($2::order_status IS NULL OR o.status = $2)
+          AND ($3::uuid IS NULL OR o.buyer_id = $3)
+          AND ($4::timestamptz IS NULL OR o.created_at >= $4)
+          AND ($5::timestamptz IS NULL OR o.created_at < $5)
+          AND ($6::text IS NULL OR EXISTS (
+                SELECT 1 FROM order_items oi JOIN products p ON oi.product_id = p.id
+                WHERE oi.order_id = o.id AND (p.name ILIKE '%' || $6 || '%' OR oi.variant_name ILIKE '%' || $6 || '%')
+              ))
+        "#,
+        seller_id,
+        query.status.clone() as Option<OrderStatus>,
+        query.buyer_id,
+        query.from,
+        query.to,
+        search
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let order_ids: Vec<Uuid> = orders.iter().map(|order| order.id).collect();
+    let mut items_by_order = order_items_by_order(pool.get_ref(), &order_ids).await?;
+
+    let order_details = orders.iter().map(|order| {
+        json!({
+            "id": order.id,
+            "buyer_id": order.buyer_id,
+            "buyer_name": order.buyer_name,
+            "buyer_phone": order.buyer_phone,
+            "status": order.status,
+            "subtotal_price": order.subtotal_price,
+            "discount_amount": order.discount_amount,
+            "delivery_fee": order.delivery_fee,
+            "tax_amount": order.tax_amount,
+            "coupon_code": order.coupon_code,
+            "total_price": order.total_price,
+            "currency": order.currency,
+            "shipping_address": order.shipping_address,
+            "delivery_slot": order.delivery_slot_id.map(|id| json!({
+                "id": id,
+                "starts_at": order.delivery_starts_at,
+                "ends_at": order.delivery_ends_at
+            })),
+            "shipment": shipment_json(
+                order.courier_name.as_deref(),
+                order.tracking_number.as_deref(),
+                order.expected_delivery_date
+            ),
+            "expected_delivery_at": order.expected_delivery_at,
+            "overdue": order.overdue,
+            "created_at": order.created_at,
+            "items": items_by_order.remove(&order.id).unwrap_or_default()
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "orders": order_details,
+        "pagination": pagination.to_json(total)
     })))
 }
 
 pub async fn update_order_status(
-    identity: Identity,
+    user: AuthUser,
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
     order_id: web::Path<Uuid>,
     req: web::Json<UpdateOrderStatusRequest>,
 ) -> AppResult<HttpResponse> {
-    let user_id = get_user_id(&identity).expect("Failed to get user ID from identity");
+    let user_id = user.id;
     let order_id = order_id.into_inner();
+    req.validate()?;
+
+    let mut tx = pool.begin().await?;
+    apply_seller_status(&mut tx, &config, order_id, user_id, &req.status, req.location_id).await?;
+    if !req.shipment.is_empty() {
+        order_repository::update_shipment(&mut tx, order_id, &req.shipment, Some(user_id)).await?;
+    }
+    tx.commit().await?;
+
+    notify_buyer_of_status(pool.get_ref(), mailer.into_inner(), order_id, &req.status).await?;
+    announce_order_update(pool.get_ref(), order_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Order status updated successfully"
+    })))
+}
+
+/// Move several of the seller's orders to the same status, e.g. shipping a morning's
+/// deliveries at once. Every order gets the checks of a single update; if any fails,
+/// none change and the error names the order.
+pub async fn bulk_update_order_status(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
+    req: web::Json<BulkOrderStatusRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+
+    // Sorted so concurrent bulk updates lock the orders in the same order
+    let mut order_ids = req.order_ids.clone();
+    order_ids.sort();
+    order_ids.dedup();
+    if order_ids.is_empty() {
+        return Err(AppError::BadRequest("order_ids can't be empty".to_string()));
+    }
+    if order_ids.len() > MAX_BULK_ORDERS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} orders can be updated at once",
+            MAX_BULK_ORDERS
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    for &order_id in &order_ids {
+        apply_seller_status(&mut tx, &config, order_id, user_id, &req.status, req.location_id)
+            .await
+            .map_err(|e| match e {
+                AppError::NotFound(message) => AppError::NotFound(format!("Order {}: {}", order_id, message)),
+                AppError::Conflict(message) => AppError::Conflict(format!("Order {}: {}", order_id, message)),
+                e => e,
+            })?;
+    }
 
+    tx.commit().await?;
+
+    let mailer = mailer.into_inner();
+    for &order_id in &order_ids {
+        notify_buyer_of_status(pool.get_ref(), mailer.clone(), order_id, &req.status).await?;
+        announce_order_update(pool.get_ref(), order_id).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": format!("{} orders updated", order_ids.len()),
+        "updated": order_ids.len(),
+        "order_ids": order_ids,
+        "status": req.status
+    })))
+}
+
+/// Move one of the seller's orders to a status they set by hand, with that status's
+/// effect on items, deliveries and stock. Items shipping with it leave from `location_id`,
+/// or the seller's default location. Locks the order; call it inside a transaction.
+async fn apply_seller_status(
+    conn: &mut PgConnection,
+    config: &Config,
+    order_id: Uuid,
+    seller_id: Uuid,
+    status: &OrderStatus,
+    location_id: Option<Uuid>,
+) -> AppResult<()> {
     // Check if user is the seller of this order
     let order = sqlx::query!(
-        "SELECT seller_id, status as \"status: OrderStatus\" FROM orders WHERE id = $1",
+        r#"SELECT seller_id, status as "status: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#,
         order_id
     )
-        .fetch_optional(pool.get_ref())
-        .await.expect("Failed to fetch order from database")
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.seller_id != seller_id {
+        return Err(AppError::Forbidden);
+    }
+
+    // Payment states are driven by the payment provider, not the seller
+    if matches!(status, OrderStatus::Paid | OrderStatus::Failed) {
+        return Err(AppError::BadRequest("Payment status cannot be set manually".to_string()));
+    }
+
+    // Disputes are opened by the buyer and settled by an admin
+    if matches!(status, OrderStatus::Disputed | OrderStatus::Refunded) {
+        return Err(AppError::BadRequest("Dispute status cannot be set manually".to_string()));
+    }
+    if order.status == OrderStatus::Disputed {
+        return Err(AppError::Conflict("Order is under dispute".to_string()));
+    }
+
+    // Partial shipment follows from shipping individual items
+    if *status == OrderStatus::PartiallyShipped {
+        return Err(AppError::BadRequest(
+            "Ship individual items to partially ship an order".to_string(),
+        ));
+    }
+
+    // Rejects invalid transitions (e.g. Delivered back to Pending) with 409
+    order_repository::transition_status(&mut *conn, order_id, status.clone(), Some(seller_id)).await?;
+
+    match status {
+        OrderStatus::Shipped => {
+            location_repository::take_for_shipment(&mut *conn, seller_id, order_id, None, location_id).await?;
+            order_repository::fulfill_all_items(&mut *conn, order_id, FulfillmentStatus::Shipped).await?;
+        }
+        // If order is completed, update seller's total deliveries
+        OrderStatus::Delivered => {
+            location_repository::take_for_shipment(&mut *conn, seller_id, order_id, None, location_id).await?;
+            order_repository::fulfill_all_items(&mut *conn, order_id, FulfillmentStatus::Delivered).await?;
+            record_delivery(&mut *conn, config, order_id, seller_id).await?;
+        }
+        OrderStatus::Cancelled => order_repository::restock_items(&mut *conn, order_id, Some(seller_id)).await?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// What to pick for a day's deliveries: the unshipped items of the seller's open orders
+/// placed that day (UTC), summed per product and variant
+pub async fn get_picklist(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    query: web::Query<PicklistQuery>,
+) -> AppResult<HttpResponse> {
+    let seller_id = user.id;
+    let date = query.date.unwrap_or_else(|| Utc::now().date_naive());
+
+    let is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1",
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+
+    let lines = sqlx::query_as!(
+        PicklistLine,
+        r#"
+        SELECT oi.product_id, p.name as product_name, oi.variant_name, p.unit as "unit: ProductUnit",
+               SUM(oi.quantity) as "quantity!", COUNT(DISTINCT o.id) as "order_count!"
+        FROM order_items oi
+        JOIN orders o ON oi.order_id = o.id
+        JOIN products p ON oi.product_id = p.id
+        WHERE o.seller_id = $1 AND (o.created_at AT TIME ZONE 'UTC')::date = $2
+          AND o.status IN ('pending', 'paid', 'partially_shipped') AND oi.fulfillment_status = 'unfulfilled'
+        GROUP BY oi.product_id, p.name, oi.variant_name, p.unit
+        ORDER BY p.name, oi.variant_name NULLS FIRST
+        "#,
+        seller_id,
+        date
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let order_ids = sqlx::query_scalar!(
+        r#"
+        SELECT o.id
+        FROM orders o
+        WHERE o.seller_id = $1 AND (o.created_at AT TIME ZONE 'UTC')::date = $2
+          AND o.status IN ('pending', 'paid', 'partially_shipped')
+          AND EXISTS(SELECT 1 FROM order_items oi WHERE oi.order_id = o.id AND oi.fulfillment_status = 'unfulfilled')
+        ORDER BY o.created_at
+        "#,
+        seller_id,
+        date
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "date": date,
+        "order_count": order_ids.len(),
+        "order_ids": order_ids,
+        "items": lines
+    })))
+}
+
+/// Ship or deliver some of an order's items. Each item moves one step, unfulfilled to
+/// shipped to delivered, and the order's status is derived from all of its items.
+pub async fn update_item_fulfillment(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<UpdateItemFulfillmentRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let order_id = order_id.into_inner();
+
+    let mut item_ids = req.item_ids.clone();
+    item_ids.sort();
+    item_ids.dedup();
+    if item_ids.is_empty() {
+        return Err(AppError::BadRequest("item_ids can't be empty".to_string()));
+    }
+
+    // The status an item must have to move to the requested one
+    let (required, out_of_step) = match req.status {
+        FulfillmentStatus::Shipped => (FulfillmentStatus::Unfulfilled, "has already shipped"),
+        FulfillmentStatus::Delivered => (FulfillmentStatus::Shipped, "must be shipped before it is delivered"),
+        FulfillmentStatus::Unfulfilled => {
+            return Err(AppError::BadRequest("Items can only be marked shipped or delivered".to_string()));
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let order = sqlx::query!(
+        r#"SELECT seller_id, status as "status: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#,
+        order_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
         .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
 
     if order.seller_id != user_id {
         return Err(AppError::Forbidden);
     }
+    if order.status == OrderStatus::Disputed {
+        return Err(AppError::Conflict("Order is under dispute".to_string()));
+    }
+    if !matches!(
+        order.status,
+        OrderStatus::Pending | OrderStatus::Paid | OrderStatus::PartiallyShipped | OrderStatus::Shipped
+    ) {
+        return Err(AppError::Conflict(format!(
+            "Items of a {} order can't be updated",
+            order.status.as_str()
+        )));
+    }
+
+    let items = sqlx::query!(
+        r#"
+        SELECT id, fulfillment_status as "fulfillment_status: FulfillmentStatus"
+        FROM order_items
+        WHERE order_id = $1 AND id = ANY($2)
+        "#,
+        order_id,
+        &item_ids
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+
+    if items.len() != item_ids.len() {
+        return Err(AppError::NotFound("Order item not found".to_string()));
+    }
+    if let Some(item) = items.iter().find(|item| item.fulfillment_status != required) {
+        return Err(AppError::Conflict(format!("Item {} {}", item.id, out_of_step)));
+    }
+    if req.status == FulfillmentStatus::Shipped {
+        location_repository::take_for_shipment(&mut tx, user_id, order_id, Some(&item_ids), req.location_id).await?;
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE order_items
+        SET fulfillment_status = $2,
+            shipped_at = COALESCE(shipped_at, NOW()),
+            delivered_at = CASE WHEN $2::fulfillment_status = 'delivered' THEN NOW() END
+        WHERE id = ANY($1)
+        "#,
+        &item_ids,
+        req.status.clone() as FulfillmentStatus
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    let new_status = order_repository::sync_status_with_items(&mut tx, order_id, Some(user_id)).await?;
+    if new_status == Some(OrderStatus::Delivered) {
+        record_delivery(&mut tx, &config, order_id, user_id).await?;
+    }
+
+    tx.commit().await?;
+
+    if let Some(status) = &new_status {
+        notify_buyer_of_status(pool.get_ref(), mailer.into_inner(), order_id, status).await?;
+    }
+    announce_order_update(pool.get_ref(), order_id).await?;
 
-    // Update order status
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Order items updated successfully",
+        "updated_items": item_ids.len(),
+        "status": new_status.unwrap_or(order.status)
+    })))
+}
+
+/// Count a completed order towards the seller's deliveries and credit it to their ledger
+async fn record_delivery(conn: &mut PgConnection, config: &Config, order_id: Uuid, seller_id: Uuid) -> AppResult<()> {
+    // When it arrived, for the seller's on-time rate
     sqlx::query!(
-        "UPDATE orders SET status = $2 WHERE id = $1",
+        "UPDATE orders SET delivered_at = COALESCE(delivered_at, NOW()) WHERE id = $1",
+        order_id
+    )
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        "UPDATE users SET total_deliveries = total_deliveries + 1 WHERE id = $1",
+        seller_id
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    ledger_repository::record_sale(conn, order_id, &config.platform_commission_percent).await?;
+
+    Ok(())
+}
+
+/// Email and notify the buyer that their order moved to a new status
+pub async fn notify_buyer_of_status(
+    pool: &PgPool,
+    mailer: Arc<dyn EmailSender>,
+    order_id: Uuid,
+    status: &OrderStatus,
+) -> AppResult<()> {
+    let buyer = sqlx::query!(
+        r#"
+        SELECT u.id, u.email, u.name, o.courier_name, o.tracking_number, o.expected_delivery_date
+        FROM orders o
+        JOIN users u ON o.buyer_id = u.id
+        WHERE o.id = $1
+        "#,
+        order_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    // How to follow the parcel, once it's on its way
+    let on_its_way = matches!(status, OrderStatus::PartiallyShipped | OrderStatus::Shipped);
+    let tracking = on_its_way
+        .then(|| shipment_summary(buyer.courier_name.as_deref(), buyer.tracking_number.as_deref(), buyer.expected_delivery_date))
+        .flatten();
+
+    mailer::send_in_background(
+        mailer,
+        templates::order_status_changed(&buyer.email, buyer.name.as_deref(), order_id, status, tracking.as_deref()),
+    );
+
+    let mut data = json!({ "order_id": order_id, "status": status });
+    if on_its_way {
+        data["shipment"] = shipment_json(
+            buyer.courier_name.as_deref(),
+            buyer.tracking_number.as_deref(),
+            buyer.expected_delivery_date,
+        );
+    }
+
+    notify(
+        pool,
+        buyer.id,
+        NotificationKind::Order,
+        &format!("Your order is {}", status.as_str().replace('_', " ")),
+        data,
+    ).await
+}
+
+/// The order's shipment details, or null before the seller gave any
+pub fn shipment_json(
+    courier_name: Option<&str>,
+    tracking_number: Option<&str>,
+    expected_delivery_date: Option<NaiveDate>,
+) -> serde_json::Value {
+    if courier_name.is_none() && tracking_number.is_none() && expected_delivery_date.is_none() {
+        return serde_json::Value::Null;
+    }
+
+    json!({
+        "courier_name": courier_name,
+        "tracking_number": tracking_number,
+        "expected_delivery_date": expected_delivery_date
+    })
+}
+
+/// The shipment details as lines for an email
+fn shipment_summary(
+    courier_name: Option<&str>,
+    tracking_number: Option<&str>,
+    expected_delivery_date: Option<NaiveDate>,
+) -> Option<String> {
+    let lines: Vec<String> = [
+        courier_name.map(|courier| format!("Courier: {}", courier)),
+        tracking_number.map(|number| format!("Tracking number: {}", number)),
+        expected_delivery_date.map(|date| format!("Expected delivery: {}", date.format("%-d %B %Y"))),
+    ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Push the order's current status to the buyer's and seller's WebSocket sessions
+pub async fn announce_order_update(pool: &PgPool, order_id: Uuid) -> AppResult<()> {
+    let order = sqlx::query!(
+        r#"
+        SELECT buyer_id, seller_id, status as "status: OrderStatus", total_price, currency
+        FROM orders
+        WHERE id = $1
+        "#,
+        order_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    let event = ServerEvent::OrderUpdate {
         order_id,
-        req.status.clone() as OrderStatus
+        status: order.status,
+        total_price: order.total_price,
+        currency: order.currency,
+    };
+
+    send_to_user(order.buyer_id, &event);
+    send_to_user(order.seller_id, &event);
+
+    Ok(())
+}
+
+/// Buyer cancellation, only while the order is still awaiting payment
+pub async fn cancel_order(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let order_id = order_id.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let order = sqlx::query!(
+        r#"SELECT buyer_id, seller_id, status as "status: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#,
+        order_id
     )
-        .execute(pool.get_ref())
-        .await
-        .expect("Failed to update order status");
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    // Once paid, cancellation goes through the seller
+    if !matches!(order.status, OrderStatus::Pending | OrderStatus::Failed) {
+        return Err(AppError::Conflict("Only unpaid orders can be cancelled by the buyer".to_string()));
+    }
+
+    order_repository::transition_status(&mut tx, order_id, OrderStatus::Cancelled, Some(user_id)).await?;
+    order_repository::restock_items(&mut tx, order_id, Some(user_id)).await?;
+
+    tx.commit().await?;
+
+    announce_order_update(pool.get_ref(), order_id).await?;
+    notify(
+        pool.get_ref(),
+        order.seller_id,
+        NotificationKind::Order,
+        "An order was cancelled by the buyer",
+        json!({ "order_id": order_id, "status": OrderStatus::Cancelled }),
+    ).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Order cancelled successfully"
+    })))
+}
+
+/// Order again what an earlier order had, at today's prices. The items go into the cart,
+/// merged with what is there, or with an address straight into a new order to the same
+/// seller. Items no longer for sale or out of stock are left out and quantities are cut
+/// to the stock left; the response lists those and every item whose price has changed.
+pub async fn reorder(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<ReorderRequest>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = user.id;
+    let order_id = order_id.into_inner();
 
-    // If order is completed, update seller's total deliveries
-    if matches!(req.status, OrderStatus::Delivered) {
+    let order = sqlx::query!("SELECT buyer_id, seller_id FROM orders WHERE id = $1", order_id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != buyer_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let order_items = sqlx::query!(
+        r#"
+        SELECT oi.product_id, oi.variant_id, oi.quantity, oi.unit_price, p.name,
+               COALESCE(v.name, oi.variant_name) as "variant_name?", p.min_increment,
+               (p.taken_down_at IS NULL AND p.deleted_at IS NULL AND p.review_status = 'approved') as "on_sale!"
+        FROM order_items oi
+        JOIN products p ON oi.product_id = p.id
+        LEFT JOIN product_variants v ON oi.variant_id = v.id
+        WHERE oi.order_id = $1
+        ORDER BY p.name, oi.variant_name
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    // Into the cart, what is already there counts towards the stock left
+    let cart_items = match req.address_id {
+        Some(_) => vec![],
+        None => cart_repository::get_items(pool.get_ref(), buyer_id).await?,
+    };
+
+    let keys: Vec<_> = order_items.iter().map(|item| (item.product_id, item.variant_id)).collect();
+    let mut conn = pool.acquire().await?;
+    let available = reservation_repository::available_stock(&mut conn, &keys, Some(buyer_id)).await?;
+
+    let mut lines = vec![];
+    let mut unavailable = vec![];
+    let mut adjusted = vec![];
+
+    for item in &order_items {
+        let stock = available.get(&(item.product_id, item.variant_id)).copied().unwrap_or(0);
+        let stock = reservation_repository::whole_increments(stock, item.min_increment);
+        if !item.on_sale || stock <= 0 {
+            unavailable.push(json!({
+                "product_id": item.product_id,
+                "variant_id": item.variant_id,
+                "name": item.name,
+                "variant_name": item.variant_name,
+                "requested_quantity": item.quantity
+            }));
+            continue;
+        }
+
+        let in_cart = cart_items
+            .iter()
+            .find(|cart_item| cart_item.product_id == item.product_id && cart_item.variant_id == item.variant_id)
+            .map(|cart_item| cart_item.quantity)
+            .unwrap_or(0);
+        let wanted = in_cart + item.quantity;
+        // In whole multiples of the product's increment, should it have changed since
+        let quantity = reservation_repository::whole_increments(wanted, item.min_increment)
+            .max(item.min_increment)
+            .min(stock);
+
+        if quantity != wanted {
+            adjusted.push(json!({
+                "product_id": item.product_id,
+                "variant_id": item.variant_id,
+                "name": item.name,
+                "variant_name": item.variant_name,
+                "requested_quantity": wanted,
+                "quantity": quantity
+            }));
+        }
+
+        lines.push(CartItem {
+            product_id: item.product_id,
+            variant_id: item.variant_id,
+            quantity,
+        });
+    }
+
+    // Priced at the quantity going into the cart or order, so price tiers count
+    let prices = product_repository::line_prices(&mut conn, &lines).await?;
+    drop(conn);
+    let price_changes: Vec<_> = order_items
+        .iter()
+        .filter_map(|item| {
+            let price = &prices.get(&(item.product_id, item.variant_id))?.unit_price;
+            (*price != item.unit_price).then(|| json!({
+                "product_id": item.product_id,
+                "variant_id": item.variant_id,
+                "name": item.name,
+                "variant_name": item.variant_name,
+                "previous_unit_price": item.unit_price,
+                "unit_price": price
+            }))
+        })
+        .collect();
+
+    let Some(address_id) = req.address_id else {
+        cart_repository::set_quantities(pool.get_ref(), buyer_id, &lines, config.cart_reservation_minutes).await?;
+        let cart_size = cart_repository::count_items(pool.get_ref(), buyer_id).await?;
+
+        return Ok(HttpResponse::Ok().json(json!({
+            "message": "Order items added to cart",
+            "cart_size": cart_size,
+            "adjusted_items": adjusted,
+            "unavailable_items": unavailable,
+            "price_changes": price_changes
+        })));
+    };
+
+    if lines.is_empty() {
+        return Err(AppError::BadRequest("None of the order's items are available any more".to_string()));
+    }
+
+    let shipping_address = shipping_snapshot(pool.get_ref(), buyer_id, address_id).await?;
+
+    let mut tx = pool.begin().await?;
+    let (new_order_id, stock_changes) =
+        order_service::place_seller_order(&mut *tx, buyer_id, order.seller_id, &shipping_address, &lines).await?;
+    tx.commit().await?;
+    recommendations::invalidate(buyer_id);
+
+    announce_order_update(pool.get_ref(), new_order_id).await?;
+    notify(
+        pool.get_ref(),
+        order.seller_id,
+        NotificationKind::Order,
+        "You have a new order",
+        json!({ "order_id": new_order_id }),
+    ).await?;
+    for (product_id, variant_id, stock_before, stock_after) in stock_changes {
+        notify_low_stock(pool.get_ref(), product_id, variant_id, stock_before, stock_after).await?;
+    }
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Order placed",
+        "order_id": new_order_id,
+        "adjusted_items": adjusted,
+        "unavailable_items": unavailable,
+        "price_changes": price_changes
+    })))
+}
+
+/// Move the order's delivery to another of the seller's slots. Either side may, until the
+/// order ships; the other side is notified.
+pub async fn reschedule_delivery(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<RescheduleDeliveryRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let order_id = order_id.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let order = sqlx::query!(
+        r#"
+        SELECT buyer_id, seller_id, status as "status: OrderStatus", delivery_slot_id
+        FROM orders
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        order_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != user_id && order.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    if !matches!(order.status, OrderStatus::Pending | OrderStatus::Paid | OrderStatus::Failed) {
+        return Err(AppError::Conflict("Only orders that haven't shipped can be rescheduled".to_string()));
+    }
+    if order.delivery_slot_id == Some(req.delivery_slot_id) {
+        return Err(AppError::BadRequest("The order is already booked in that slot".to_string()));
+    }
+
+    let slot = delivery_slot_repository::lock(&mut tx, &[req.delivery_slot_id])
+        .await?
+        .pop()
+        .filter(|slot| slot.seller_id == order.seller_id)
+        .ok_or_else(|| AppError::NotFound("Delivery slot not found".to_string()))?;
+    order_service::check_delivery_slot(&slot, Utc::now())?;
+
+    delivery_slot_repository::book(&mut tx, order_id, slot.id).await?;
+
+    // A buyer moving their delivery moves when it's due; a seller moving it doesn't, so
+    // a later slot still counts against them
+    if user_id == order.buyer_id {
         sqlx::query!(
-            "UPDATE users SET total_deliveries = total_deliveries + 1 WHERE id = $1",
-            user_id
+            "UPDATE orders SET expected_delivery_at = $2 WHERE id = $1",
+            order_id,
+            slot.ends_at
         )
-            .execute(pool.get_ref())
-            .await
-            .expect("Failed to update seller's total deliveries");
+            .execute(&mut *tx)
+            .await?;
     }
 
+    tx.commit().await?;
+
+    let delivery_slot = json!({
+        "id": slot.id,
+        "starts_at": slot.starts_at,
+        "ends_at": slot.ends_at
+    });
+
+    let other_party = if user_id == order.buyer_id { order.seller_id } else { order.buyer_id };
+    notify(
+        pool.get_ref(),
+        other_party,
+        NotificationKind::Order,
+        "An order's delivery was rescheduled",
+        json!({ "order_id": order_id, "delivery_slot": delivery_slot }),
+    ).await?;
+
     Ok(HttpResponse::Ok().json(json!({
-        "message": "Order status updated successfully"
+        "message": "Delivery rescheduled",
+        "delivery_slot": delivery_slot
     })))
-}
\ No newline at end of file
+}
+
+pub async fn get_order_history(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let order_id = order_id.into_inner();
+
+    let order = sqlx::query!(
+        "SELECT buyer_id, seller_id FROM orders WHERE id = $1",
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != user_id && order.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let history = sqlx::query!(
+        r#"
+        SELECT from_status as "from_status: OrderStatus", to_status as "to_status: OrderStatus",
+               changed_by, changed_at
+        FROM order_status_history
+        WHERE order_id = $1
+        ORDER BY changed_at ASC
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "order_id": order_id,
+        "history": history.iter().map(|entry| json!({
+            "from_status": entry.from_status,
+            "to_status": entry.to_status,
+            "changed_by": entry.changed_by,
+            "changed_at": entry.changed_at
+        })).collect::<Vec<_>>()
+    })))
+}
+/// Correct or complete the shipment details of an order on its way. The buyer is told
+/// each time.
+pub async fn update_shipment(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<ShipmentDetails>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let order_id = order_id.into_inner();
+    req.validate()?;
+
+    if req.is_empty() {
+        return Err(AppError::BadRequest(
+            "Give a courier_name, tracking_number or expected_delivery_date".to_string(),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let order = sqlx::query!(
+        r#"SELECT buyer_id, seller_id, status as "status: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#,
+        order_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    if !matches!(order.status, OrderStatus::PartiallyShipped | OrderStatus::Shipped) {
+        return Err(AppError::Conflict(
+            "Shipment details can only be changed while the order is on its way".to_string(),
+        ));
+    }
+
+    order_repository::update_shipment(&mut tx, order_id, &req, Some(user_id)).await?;
+
+    let shipment = sqlx::query!(
+        "SELECT courier_name, tracking_number, expected_delivery_date FROM orders WHERE id = $1",
+        order_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let shipment = shipment_json(
+        shipment.courier_name.as_deref(),
+        shipment.tracking_number.as_deref(),
+        shipment.expected_delivery_date,
+    );
+    notify(
+        pool.get_ref(),
+        order.buyer_id,
+        NotificationKind::Order,
+        "Tracking details for your order were updated",
+        json!({ "order_id": order_id, "shipment": shipment }),
+    ).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Shipment details updated",
+        "shipment": shipment
+    })))
+}
+
+/// Everything that happened to the order, oldest first: each status it moved to, each
+/// change to its shipment details and each comment, for the buyer or seller to follow it
+pub async fn get_order_timeline(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let order_id = order_id.into_inner();
+
+    let order = sqlx::query!(
+        r#"
+        SELECT buyer_id, seller_id, status as "status: OrderStatus",
+               courier_name, tracking_number, expected_delivery_date
+        FROM orders
+        WHERE id = $1
+        "#,
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != user_id && order.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let statuses = sqlx::query!(
+        r#"
+        SELECT from_status as "from_status: OrderStatus", to_status as "to_status: OrderStatus",
+               changed_by, changed_at
+        FROM order_status_history
+        WHERE order_id = $1
+        ORDER BY changed_at ASC
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let shipment_updates = sqlx::query!(
+        r#"
+        SELECT courier_name, tracking_number, expected_delivery_date, updated_by, created_at
+        FROM order_shipment_updates
+        WHERE order_id = $1
+        ORDER BY created_at ASC
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let comments = sqlx::query!(
+        r#"
+        SELECT c.id, c.author_id, u.name as author_name, c.body, c.created_at
+        FROM order_comments c
+        JOIN users u ON c.author_id = u.id
+        WHERE c.order_id = $1
+        ORDER BY c.created_at, c.id
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let mut events: Vec<_> = statuses.iter().map(|entry| {
+        (entry.changed_at, json!({
+            "type": "status",
+            "from_status": entry.from_status,
+            "status": entry.to_status,
+            "changed_by": entry.changed_by,
+            "at": entry.changed_at
+        }))
+    }).collect();
+    events.extend(shipment_updates.iter().map(|update| {
+        (update.created_at, json!({
+            "type": "shipment",
+            "courier_name": update.courier_name,
+            "tracking_number": update.tracking_number,
+            "expected_delivery_date": update.expected_delivery_date,
+            "changed_by": update.updated_by,
+            "at": update.created_at
+        }))
+    }));
+    events.extend(comments.iter().map(|comment| {
+        (comment.created_at, json!({
+            "type": "comment",
+            "id": comment.id,
+            "author_id": comment.author_id,
+            "author_name": comment.author_name,
+            "body": comment.body,
+            "at": comment.created_at
+        }))
+    }));
+    // Stable, so a status change keeps its place before the shipment details given with it
+    events.sort_by_key(|(at, _)| *at);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "order_id": order_id,
+        "status": order.status,
+        "shipment": shipment_json(
+            order.courier_name.as_deref(),
+            order.tracking_number.as_deref(),
+            order.expected_delivery_date
+        ),
+        "events": events.into_iter().map(|(_, event)| event).collect::<Vec<_>>()
+    })))
+}
+
+/// A tax invoice for the order, for its buyer or seller: each line with its tax, the
+/// tax per rate, and the seller's tax registration as it was at checkout
+pub async fn get_invoice(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let order_id = order_id.into_inner();
+
+    let order = sqlx::query!(
+        r#"
+        SELECT o.buyer_id, o.seller_id, o.status as "status: OrderStatus", o.subtotal_price, o.discount_amount,
+               o.delivery_fee, o.tax_amount, o.total_price, o.currency, o.shipping_address, o.seller_tax_id, o.seller_tax_name,
+               o.created_at, b.name as buyer_name, s.name as seller_name
+        FROM orders o
+        JOIN users b ON o.buyer_id = b.id
+        JOIN users s ON o.seller_id = s.id
+        WHERE o.id = $1
+        "#,
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != user_id && order.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let items = sqlx::query!(
+        r#"
+        SELECT oi.product_id, oi.variant_name, oi.quantity, oi.unit_price, oi.tax_rate, oi.tax_amount,
+               p.name as product_name, p.unit as "unit: ProductUnit"
+        FROM order_items oi
+        JOIN products p ON oi.product_id = p.id
+        WHERE oi.order_id = $1
+        ORDER BY p.name, oi.variant_name
+        "#,
+        order_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    // Tax is charged on each line's share of the discounted subtotal
+    let taxable_share = |amount: &BigDecimal| -> BigDecimal {
+        if order.subtotal_price.is_zero() {
+            return BigDecimal::zero();
+        }
+        (amount * (&order.subtotal_price - &order.discount_amount) / &order.subtotal_price).round(2)
+    };
+
+    let mut tax_by_rate: BTreeMap<BigDecimal, (BigDecimal, BigDecimal)> = BTreeMap::new();
+    let lines = items.iter().map(|item| {
+        let amount = item.quantity * &item.unit_price;
+        if order.seller_tax_id.is_some() {
+            let (taxable, tax) = tax_by_rate.entry(item.tax_rate.clone()).or_default();
+            *taxable += taxable_share(&amount);
+            *tax += &item.tax_amount;
+        }
+        json!({
+            "product_id": item.product_id,
+            "product_name": item.product_name,
+            "variant_name": item.variant_name,
+            "quantity": item.quantity,
+            "unit": item.unit,
+            "unit_price": item.unit_price,
+            "amount": amount,
+            "tax_rate": item.tax_rate,
+            "tax_amount": item.tax_amount
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "invoice_number": format!("INV-{}-{}", order.created_at.format("%Y%m%d"), &order_id.simple().to_string()[..8].to_uppercase()),
+        "order_id": order_id,
+        "issued_at": order.created_at,
+        "status": order.status,
+        "seller": {
+            "id": order.seller_id,
+            "name": order.seller_name,
+            "tax_id": order.seller_tax_id,
+            "tax_name": order.seller_tax_name
+        },
+        "buyer": {
+            "id": order.buyer_id,
+            "name": order.buyer_name
+        },
+        "shipping_address": order.shipping_address,
+        "lines": lines,
+        "tax_summary": tax_by_rate.into_iter().map(|(tax_rate, (taxable_amount, tax_amount))| json!({
+            "tax_rate": tax_rate,
+            "taxable_amount": taxable_amount,
+            "tax_amount": tax_amount
+        })).collect::<Vec<_>>(),
+        "subtotal_price": order.subtotal_price,
+        "discount_amount": order.discount_amount,
+        "tax_amount": order.tax_amount,
+        "delivery_fee": order.delivery_fee,
+        "total_price": order.total_price,
+        "currency": order.currency
+    })))
+}
@@ -0,0 +1,143 @@
+// handlers/payment_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+use crate::handlers::order_handlers::{announce_order_update, notify_buyer_of_status};
+use crate::mailer::EmailSender;
+use crate::models::OrderStatus;
+use crate::payments::{verify_webhook_signature, StripeClient, StripeEvent};
+use crate::repositories::order_repository;
+use crate::utils::get_user_id;
+
+pub async fn pay_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    order_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let order_id = order_id.into_inner();
+
+    let order = sqlx::query!(
+        r#"SELECT buyer_id, total_price, currency, status as "status: OrderStatus" FROM orders WHERE id = $1"#,
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    if order.buyer_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    // Failed payments can be retried; anything past payment cannot
+    if !matches!(order.status, OrderStatus::Pending | OrderStatus::Failed) {
+        return Err(AppError::BadRequest("Order is not awaiting payment".to_string()));
+    }
+
+    let stripe = StripeClient::from_config(&config)?;
+    let intent = stripe.create_payment_intent(order_id, &order.total_price, &order.currency).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO payments (id, order_id, provider, provider_payment_id, amount, currency, status)
+        VALUES ($1, $2, 'stripe', $3, $4, $5, $6)
+        ON CONFLICT (provider_payment_id) DO UPDATE SET status = EXCLUDED.status, updated_at = NOW()
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        intent.id,
+        order.total_price,
+        order.currency,
+        intent.status
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "payment_intent_id": intent.id,
+        "client_secret": intent.client_secret,
+        "amount": order.total_price,
+        "currency": order.currency
+    })))
+}
+
+pub async fn stripe_webhook(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
+    body: web::Bytes,
+) -> AppResult<HttpResponse> {
+    let secret = config
+        .stripe_webhook_secret
+        .as_deref()
+        .ok_or_else(|| AppError::PaymentError("Payments are not configured".to_string()))?;
+
+    let signature = request
+        .headers()
+        .get("Stripe-Signature")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| AppError::BadRequest("Missing Stripe-Signature header".to_string()))?;
+
+    if !verify_webhook_signature(&body, signature, secret, Utc::now().timestamp()) {
+        return Err(AppError::BadRequest("Invalid webhook signature".to_string()));
+    }
+
+    let event: StripeEvent = serde_json::from_slice(&body)
+        .map_err(|_| AppError::BadRequest("Invalid webhook payload".to_string()))?;
+
+    let new_status = match event.event_type.as_str() {
+        "payment_intent.succeeded" => OrderStatus::Paid,
+        "payment_intent.payment_failed" => OrderStatus::Failed,
+        // Acknowledge events we don't act on so Stripe stops retrying them
+        _ => return Ok(HttpResponse::Ok().json(json!({ "received": true }))),
+    };
+
+    let intent = event.data.object;
+    let mut tx = pool.begin().await?;
+
+    let order_id = sqlx::query_scalar!(
+        r#"
+        UPDATE payments SET status = $2, updated_at = NOW()
+        WHERE provider = 'stripe' AND provider_payment_id = $1
+        RETURNING order_id
+        "#,
+        intent.id,
+        intent.status
+    )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let Some(order_id) = order_id else {
+        tracing::warn!(payment_intent = %intent.id, "Stripe webhook for unknown PaymentIntent");
+        return Ok(HttpResponse::Ok().json(json!({ "received": true })));
+    };
+
+    // Only orders still awaiting payment move; redelivered events are no-ops
+    let moved = match order_repository::transition_status(&mut tx, order_id, new_status.clone(), None).await {
+        Ok(_) => true,
+        Err(AppError::Conflict(_)) => false,
+        Err(e) => return Err(e),
+    };
+
+    tx.commit().await?;
+
+    // The event is applied; an error now would only have Stripe deliver it again
+    if moved {
+        if let Err(e) = notify_buyer_of_status(pool.get_ref(), mailer.into_inner(), order_id, &new_status).await {
+            tracing::warn!(%order_id, error = %e, "Failed to notify buyer of payment update");
+        }
+        if let Err(e) = announce_order_update(pool.get_ref(), order_id).await {
+            tracing::warn!(%order_id, error = %e, "Failed to announce payment update");
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(json!({ "received": true })))
+}
@@ -1,132 +1,465 @@
 // handlers/product_handlers.rs
-use actix_identity::Identity;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use bigdecimal::BigDecimal;
-use serde_json::json;
-use sqlx::PgPool;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::auth::AuthUser;
+use crate::cache::{Cache, Namespace};
+use crate::config::Config;
 use crate::errors::{AppError, AppResult};
-use crate::models::{CreateProductRequest, ProductQuery, ProductWithSeller, UpdateProductRequest};
-use crate::utils::get_user_id;
+use crate::mailer::{self, templates, EmailSender};
+use crate::models::{AddProductImageRequest, AuditAction, CategoryFacet, CreateProductRequest, CreateVariantRequest, Category, CurrencyQuery, InventoryMovement, InventoryReason, PaginationQuery, PriceChange, ProductLookupQuery, ProductQuery, ProductReviewStatus, ProductUnit, ProductVariant, ProductWithSeller, ReorderProductImagesRequest, ServerEvent, SetPriceTiersRequest, StockAdjustRequest, SuggestQuery, TransferProductRequest, UpdateProductRequest, UpdateVariantRequest};
+use crate::repositories::audit_repository::{self, AuditEntry};
+use crate::repositories::inventory_repository::{self, StockMovement};
+use crate::repositories::{deal_repository, location_repository, product_repository};
+use crate::services::currency_service;
+use crate::update_builder::UpdateBuilder;
+use crate::utils::{validate_location, Pagination};
+use crate::validation::Validate;
+use crate::ws::send_to_user;
+
+/// How long a seller can restore a deleted product before it becomes eligible for purging
+pub const PRODUCT_RESTORE_DAYS: i64 = 30;
+
+/// Note on the movement recording a new product's or variant's opening stock
+const INITIAL_STOCK_NOTE: &str = "Initial stock";
+
+const SUGGEST_MIN_CHARS: usize = 3;
+const SUGGEST_DEFAULT_LIMIT: i64 = 8;
+const SUGGEST_MAX_LIMIT: i64 = 20;
+
+/// Sellers listed for one scanned barcode
+const LOOKUP_LIMIT: i64 = 20;
+
+/// FROM and WHERE shared by the product listing, its total count and its facets.
+/// Binds $1 search, $2 category, $3/$4 lat/lng, $5 radius_km, $6 the viewer, who
+/// also sees their own listings still waiting for review, and $7 the pincode only
+/// sellers delivering there are shown for; lat/lng is checked against their radius.
+const LISTING_FILTER: &str = r#"
+        FROM products p
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE p.stock_qty > 0
+          AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND (p.review_status = 'approved' OR p.seller_id = $6)
+          AND NOT u.store_paused
+          AND ($1::text IS NULL OR p.search_vector @@ websearch_to_tsquery('english', $1))
+          AND ($2::int IS NULL OR p.category_id IN (SELECT category_subtree($2)))
+          AND ($5::float8 IS NULL OR distance_km($3, $4, COALESCE(p.latitude, u.latitude),
+                                                 COALESCE(p.longitude, u.longitude)) <= $5)
+          AND ($7::text IS NULL OR delivers_to(u.id, $7, $3, $4))
+"#;
 
 pub async fn list_products(
+    request: HttpRequest,
+    user: Option<AuthUser>,
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    cache: web::Data<Cache>,
     query: web::Query<ProductQuery>,
 ) -> AppResult<HttpResponse> {
-    let page = query.page.unwrap_or(1).max(1);
-    let limit = query.limit.unwrap_or(20).min(100);
-    let offset = (page - 1) * limit;
+    // Signed-in viewers also see their favourites and their own listings under review,
+    // so only the anonymous listing is cached
+    let listing = match user {
+        None => {
+            let load = product_listing(pool.get_ref(), &config, &query, None);
+            cache.fetch(Namespace::Products, &format!("list?{}", request.query_string()), load).await?
+        }
+        Some(user) => product_listing(pool.get_ref(), &config, &query, Some(user.id)).await?,
+    };
+
+    Ok(HttpResponse::Ok().json(listing))
+}
+
+/// A page of the product listing with its total and category facets
+async fn product_listing(
+    pool: &PgPool,
+    config: &Config,
+    query: &ProductQuery,
+    viewer_id: Option<Uuid>,
+) -> AppResult<Value> {
+    let pagination = Pagination::new(query.page, query.limit);
+    let display_currency = query.currency.as_deref().map(|code| currency_service::parse(config, code)).transpose()?;
+
+    // Blank search strings behave like no search at all
+    let search = query.search.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let category_id = query.category.filter(|id| *id > 0);
+    let deliverable_to = query.deliverable_to.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+    let location = validate_location(query.lat, query.lng)?;
+    if query.radius_km.is_some_and(|radius| radius <= 0.0) {
+        return Err(AppError::BadRequest("radius_km must be greater than zero".to_string()));
+    }
+    if location.is_none() && (query.radius_km.is_some() || query.sort.as_deref() == Some("distance")) {
+        return Err(AppError::BadRequest("Searching by distance needs lat and lng".to_string()));
+    }
 
-    // Build dynamic query based on filters
-    let mut sql = r#"
+    // Filters are bound as parameters; only the whitelisted ORDER BY is formatted in
+    let mut sql = format!(r#"
         SELECT
-            p.id, p.name, p.description, p.price_per_unit, p.stock_qty,
+            p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+            p.unit, p.min_increment, p.sku, p.barcode,
             p.image_url, p.seller_id, p.category_id, p.created_at,
             c.name as category_name,
             u.name as seller_name, u.name as seller_company,
-            u.rating as seller_rating, u.total_deliveries as seller_deliveries
-        FROM products p
-        JOIN categories c ON p.category_id = c.id
-        JOIN users u ON p.seller_id = u.id
-        WHERE p.stock_qty > 0
-    "#.to_string();
-
-    // Add search filter
-    if let Some(search) = &query.search {
-        sql.push_str(&format!(" AND (p.name ILIKE '%{}%' OR p.description ILIKE '%{}%')", search, search));
-    }
+            u.rating as seller_rating, u.total_deliveries as seller_deliveries,
+            u.min_order_value as seller_min_order_value, u.delivery_fee as seller_delivery_fee,
+            COALESCE(
+                (SELECT json_agg(json_build_object('id', pi.id, 'url', pi.url) ORDER BY pi.position)
+                 FROM product_images pi WHERE pi.product_id = p.id),
+                '[]'::json
+            ) as images,
+            COALESCE(
+                (SELECT json_agg(json_build_object('min_qty', t.min_qty, 'unit_price', t.unit_price::text) ORDER BY t.min_qty)
+                 FROM price_tiers t WHERE t.product_id = p.id),
+                '[]'::json
+            ) as price_tiers,
+            EXISTS(SELECT 1 FROM favorites f WHERE f.product_id = p.id AND f.user_id = $6) as is_favorited,
+            distance_km($3, $4, COALESCE(p.latitude, u.latitude), COALESCE(p.longitude, u.longitude)) as distance_km,
+            (SELECT COUNT(*) FROM stock_by_location s WHERE s.product_id = p.id AND s.quantity > 0) as stock_locations,
+            current_deal(p.id) as deal,
+            NULL::numeric as display_price, NULL::text as display_currency,
+            p.review_status
+        {}
+    "#, LISTING_FILTER);
 
-    // Add category filter
-    if let Some(category_id) = query.category {
-        if category_id > 0 {
-            sql.push_str(&format!(" AND p.category_id = {}", category_id));
-        }
-    }
-
-    // Add sorting
+    // Add sorting; searches default to relevance, and listings from a location to distance.
+    // Products with no known location come last.
     let order_clause = match query.sort.as_deref() {
+        Some("distance") => " ORDER BY distance_km ASC NULLS LAST, p.created_at DESC",
         Some("price_asc") => " ORDER BY p.price_per_unit ASC",
         Some("price_desc") => " ORDER BY p.price_per_unit DESC",
         Some("rating") => " ORDER BY u.rating DESC NULLS LAST",
         Some("deliveries") => " ORDER BY u.total_deliveries DESC",
         Some("name") => " ORDER BY p.name ASC",
+        _ if search.is_some() => " ORDER BY ts_rank(p.search_vector, websearch_to_tsquery('english', $1)) DESC, p.created_at DESC",
+        _ if location.is_some() => " ORDER BY distance_km ASC NULLS LAST, p.created_at DESC",
         _ => " ORDER BY p.created_at DESC",
     };
     sql.push_str(order_clause);
 
     // Add pagination
-    sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, offset));
+    sql.push_str(" LIMIT $8 OFFSET $9");
 
-    let products: Vec<ProductWithSeller> = sqlx::query_as(&sql)
-        .fetch_all(pool.get_ref())
+    let latitude = location.map(|(latitude, _)| latitude);
+    let longitude = location.map(|(_, longitude)| longitude);
+
+    let mut products: Vec<ProductWithSeller> = sqlx::query_as(&sql)
+        .bind(search)
+        .bind(category_id)
+        .bind(latitude)
+        .bind(longitude)
+        .bind(query.radius_km)
+        .bind(viewer_id)
+        .bind(deliverable_to)
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .fetch_all(pool)
         .await?;
 
-    // Get total count for pagination
-    let count_sql = r#"
-        SELECT COUNT(*) as count
-        FROM products p
-        WHERE p.stock_qty > 0
-    "#;
+    if let Some(currency) = &display_currency {
+        currency_service::localize_products(config, &mut products, currency);
+    }
 
-    let total_count: i64 = sqlx::query_scalar(count_sql)
-        .fetch_one(pool.get_ref())
+    // Get total count for pagination, under the same filters as the page itself
+    let count_sql = format!("SELECT COUNT(*) {}", LISTING_FILTER);
+
+    let total_count: i64 = sqlx::query_scalar(&count_sql)
+        .bind(search)
+        .bind(category_id)
+        .bind(latitude)
+        .bind(longitude)
+        .bind(query.radius_km)
+        .bind(viewer_id)
+        .bind(deliverable_to)
+        .fetch_one(pool)
         .await?;
 
-    Ok(HttpResponse::Ok().json(json!({
+    // Matches per category for the same search and area, ignoring the category
+    // filter so the UI can offer switching to any of them
+    let facets_sql = format!(
+        "SELECT c.id, c.name, COUNT(*) as count {} GROUP BY c.id, c.name ORDER BY count DESC, c.name",
+        LISTING_FILTER
+    );
+
+    let facets: Vec<CategoryFacet> = sqlx::query_as(&facets_sql)
+        .bind(search)
+        .bind(None::<i32>)
+        .bind(latitude)
+        .bind(longitude)
+        .bind(query.radius_km)
+        .bind(viewer_id)
+        .bind(deliverable_to)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(json!({
         "products": products,
-        "pagination": {
-            "page": page,
-            "limit": limit,
-            "total": total_count,
-            "pages": (total_count as f64 / limit as f64).ceil() as i32
+        "pagination": pagination.to_json(total_count),
+        "facets": {
+            "categories": facets
         }
+    }))
+}
+
+/// Type-ahead suggestions: product names and categories whose names contain a word
+/// similar to `q`, prefix matches first
+pub async fn suggest_products(
+    pool: web::Data<PgPool>,
+    query: web::Query<SuggestQuery>,
+) -> AppResult<HttpResponse> {
+    let q = query.q.as_deref().map(str::trim).unwrap_or_default();
+    let limit = query.limit.unwrap_or(SUGGEST_DEFAULT_LIMIT).clamp(1, SUGGEST_MAX_LIMIT);
+
+    // One or two letters match nearly everything; wait for more typing
+    if q.chars().count() < SUGGEST_MIN_CHARS {
+        return Ok(HttpResponse::Ok().json(json!({
+            "query": q,
+            "products": [],
+            "categories": []
+        })));
+    }
+
+    // Several sellers often list the same name; suggest it once
+    let products = sqlx::query!(
+        r#"
+        SELECT id as "id!", name as "name!"
+        FROM (
+            SELECT DISTINCT ON (LOWER(p.name)) p.id, p.name,
+                   word_similarity($1, p.name) as score,
+                   starts_with(LOWER(p.name), LOWER($1)) as is_prefix
+            FROM products p
+            JOIN users u ON p.seller_id = u.id
+            WHERE $1 <% p.name
+              AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+              AND p.review_status = 'approved'
+              AND NOT u.store_paused
+            ORDER BY LOWER(p.name), p.created_at DESC
+        ) matches
+        ORDER BY is_prefix DESC, score DESC, name
+        LIMIT $2
+        "#,
+        q,
+        limit
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let categories = sqlx::query_as!(
+        Category,
+        r#"
+        SELECT id, name, parent_id
+        FROM categories
+        WHERE $1 <% name
+        ORDER BY starts_with(LOWER(name), LOWER($1)) DESC, word_similarity($1, name) DESC, name
+        LIMIT $2
+        "#,
+        q,
+        limit
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let products = products.iter().map(|product| {
+        json!({
+            "id": product.id,
+            "name": product.name
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "query": q,
+        "products": products,
+        "categories": categories
     })))
 }
 
 pub async fn get_product(
+    user: Option<AuthUser>,
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
     product_id: web::Path<Uuid>,
+    query: web::Query<CurrencyQuery>,
 ) -> AppResult<HttpResponse> {
-    let product = sqlx::query_as!(
+    let viewer_id = user.map(|user| user.id);
+    let display_currency = query.currency.as_deref().map(|code| currency_service::parse(&config, code)).transpose()?;
+
+    let mut product = sqlx::query_as!(
         ProductWithSeller,
         r#"
         SELECT
-            p.id, p.name, p.description, p.price_per_unit, p.stock_qty,
+            p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+            p.unit as "unit: ProductUnit", p.min_increment, p.sku, p.barcode,
             p.image_url, p.seller_id, p.category_id, p.created_at,
             c.name as category_name,
             u.name as seller_name, u.name as seller_company,
-            u.rating as seller_rating, u.total_deliveries as seller_deliveries
+            u.rating as seller_rating, u.total_deliveries as seller_deliveries,
+            u.min_order_value as seller_min_order_value, u.delivery_fee as seller_delivery_fee,
+            COALESCE(
+                (SELECT json_agg(json_build_object('id', pi.id, 'url', pi.url) ORDER BY pi.position)
+                 FROM product_images pi WHERE pi.product_id = p.id),
+                '[]'::json
+            ) as "images!",
+            COALESCE(
+                (SELECT json_agg(json_build_object('min_qty', t.min_qty, 'unit_price', t.unit_price::text) ORDER BY t.min_qty)
+                 FROM price_tiers t WHERE t.product_id = p.id),
+                '[]'::json
+            ) as "price_tiers!",
+            EXISTS(SELECT 1 FROM favorites f WHERE f.product_id = p.id AND f.user_id = $2) as "is_favorited!",
+            NULL::float8 as distance_km,
+            (SELECT COUNT(*) FROM stock_by_location s WHERE s.product_id = p.id AND s.quantity > 0) as "stock_locations!",
+            current_deal(p.id) as deal,
+            NULL::numeric as display_price, NULL::text as display_currency,
+            p.review_status as "review_status: ProductReviewStatus"
         FROM products p
         JOIN categories c ON p.category_id = c.id
         JOIN users u ON p.seller_id = u.id
-        WHERE p.id = $1
+        WHERE p.id = $1 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND (p.review_status = 'approved' OR p.seller_id = $2)
         "#,
-        product_id.into_inner()
+        product_id.into_inner(),
+        viewer_id
     )
         .fetch_optional(pool.get_ref())
         .await?
         .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
 
-    Ok(HttpResponse::Ok().json(product))
+    let variants = sqlx::query_as!(
+        ProductVariant,
+        r#"
+        SELECT id, product_id, name, price_per_unit, stock_qty, created_at
+        FROM product_variants
+        WHERE product_id = $1
+        ORDER BY price_per_unit, name
+        "#,
+        product.id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    // Variants are priced in their product's currency
+    let mut variants = json!(variants);
+    if let Some(currency) = &display_currency {
+        currency_service::localize_products(&config, std::slice::from_mut(&mut product), currency);
+        for variant in variants.as_array_mut().into_iter().flatten() {
+            let price = serde_json::from_value::<BigDecimal>(variant["price_per_unit"].clone()).ok();
+            variant["display_price"] = json!(price.and_then(|price| {
+                currency_service::convert(&config, &price, &product.currency, currency)
+            }));
+        }
+    }
+
+    // Only the seller sees their rejected listings, and why they were rejected
+    let review_reason = match product.review_status {
+        ProductReviewStatus::Rejected => sqlx::query_scalar!(
+            "SELECT review_reason FROM products WHERE id = $1",
+            product.id
+        )
+            .fetch_one(pool.get_ref())
+            .await?,
+        _ => None,
+    };
+
+    let mut body = json!(product);
+    body["variants"] = variants;
+    if review_reason.is_some() {
+        body["review_reason"] = json!(review_reason);
+    }
+
+    Ok(HttpResponse::Ok().json(body))
+}
+
+/// The products carrying a scanned barcode: a supplier's own first, to manage its stock,
+/// then those from sellers the viewer has ordered from, to reorder, then the cheapest
+pub async fn lookup_product(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    query: web::Query<ProductLookupQuery>,
+) -> AppResult<HttpResponse> {
+    let barcode = query.barcode.as_deref().map(str::trim).filter(|barcode| !barcode.is_empty())
+        .ok_or_else(|| AppError::BadRequest("barcode is required".to_string()))?;
+
+    let products = sqlx::query_as!(
+        ProductWithSeller,
+        r#"
+        SELECT
+            p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+            p.unit as "unit: ProductUnit", p.min_increment, p.sku, p.barcode,
+            p.image_url, p.seller_id, p.category_id, p.created_at,
+            c.name as category_name,
+            u.name as seller_name, u.name as seller_company,
+            u.rating as seller_rating, u.total_deliveries as seller_deliveries,
+            u.min_order_value as seller_min_order_value, u.delivery_fee as seller_delivery_fee,
+            COALESCE(
+                (SELECT json_agg(json_build_object('id', pi.id, 'url', pi.url) ORDER BY pi.position)
+                 FROM product_images pi WHERE pi.product_id = p.id),
+                '[]'::json
+            ) as "images!",
+            COALESCE(
+                (SELECT json_agg(json_build_object('min_qty', t.min_qty, 'unit_price', t.unit_price::text) ORDER BY t.min_qty)
+                 FROM price_tiers t WHERE t.product_id = p.id),
+                '[]'::json
+            ) as "price_tiers!",
+            EXISTS(SELECT 1 FROM favorites f WHERE f.product_id = p.id AND f.user_id = $2) as "is_favorited!",
+            NULL::float8 as distance_km,
+            (SELECT COUNT(*) FROM stock_by_location s WHERE s.product_id = p.id AND s.quantity > 0) as "stock_locations!",
+            current_deal(p.id) as deal,
+            NULL::numeric as display_price, NULL::text as display_currency,
+            p.review_status as "review_status: ProductReviewStatus"
+        FROM products p
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE p.barcode = $1 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND (p.seller_id = $2 OR (p.review_status = 'approved' AND NOT u.store_paused))
+        ORDER BY p.seller_id = $2 DESC,
+                 EXISTS(SELECT 1 FROM orders o WHERE o.buyer_id = $2 AND o.seller_id = p.seller_id) DESC,
+                 p.price_per_unit, p.created_at
+        LIMIT $3
+        "#,
+        barcode,
+        user.id,
+        LOOKUP_LIMIT
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    if products.is_empty() {
+        return Err(AppError::NotFound("No product has this barcode".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "barcode": barcode,
+        "products": products
+    })))
 }
 
 pub async fn create_product(
-    identity: Identity,
+    user: AuthUser,
     pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    config: web::Data<Config>,
     req: web::Json<CreateProductRequest>,
 ) -> AppResult<HttpResponse> {
-    if req.name.trim().is_empty() {
-        return Err(AppError::BadRequest("Invalid product name".to_string()));
-    }
-    if req.price_per_unit <= BigDecimal::from(0) {
-        return Err(AppError::BadRequest("Invalid product price".to_string()));
-    }
-    if req.stock_qty < 0 {
-        return Err(AppError::BadRequest("Invalid stock quantity".to_string()));
-    }
+    req.validate()?;
+    let currency = match &req.currency {
+        Some(code) => currency_service::parse(&config, code)?,
+        None => config.base_currency.clone(),
+    };
+
+    // A bare image_url is treated as a one-image gallery
+    let images = match (&req.images, &req.image_url) {
+        (Some(images), _) => images.clone(),
+        (None, Some(url)) => vec![url.clone()],
+        (None, None) => vec![],
+    };
+    validate_image_urls(&images)?;
+    let location = validate_location(req.latitude, req.longitude)?;
 
-    let user_id = get_user_id(&identity)?;
+    let user_id = user.id;
 
     // Check if user is a supplier
     let is_supplier = sqlx::query_scalar!(
@@ -141,43 +474,83 @@ pub async fn create_product(
     }
 
     let product_id = Uuid::new_v4();
+    let review_status = initial_review_status(&config);
+    let sku = req.sku.as_deref().map(str::trim).filter(|sku| !sku.is_empty());
+    let barcode = req.barcode.as_deref().map(str::trim).filter(|barcode| !barcode.is_empty());
+
+    let mut tx = pool.begin().await?;
+
+    if let Some(code) = taken_product_code(&mut tx, user_id, sku, barcode, None).await? {
+        return Err(AppError::Conflict(format!("You already have a product with {}", code)));
+    }
 
     let product = sqlx::query!(
         r#"
-        INSERT INTO products (id, name, description, price_per_unit, stock_qty, image_url, seller_id, category_id)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        INSERT INTO products (id, name, description, price_per_unit, currency, stock_qty, unit, min_increment,
+                              seller_id, category_id, latitude, longitude, review_status, sku, barcode)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
         RETURNING id
         "#,
         product_id,
         req.name,
         req.description,
         req.price_per_unit,
+        currency,
         req.stock_qty,
-        req.image_url,
+        req.unit.unwrap_or(ProductUnit::Piece) as ProductUnit,
+        req.min_increment.unwrap_or(1),
         user_id,
-        req.category_id
+        req.category_id,
+        location.map(|(latitude, _)| latitude),
+        location.map(|(_, longitude)| longitude),
+        review_status as ProductReviewStatus,
+        sku,
+        barcode
     )
-        .fetch_one(pool.get_ref())
+        .fetch_one(&mut *tx)
         .await?;
 
+    if req.stock_qty > 0 {
+        inventory_repository::record(&mut tx, StockMovement {
+            product_id,
+            variant_id: None,
+            quantity_change: req.stock_qty,
+            stock_after: req.stock_qty,
+            reason: InventoryReason::Adjustment,
+            note: Some(INITIAL_STOCK_NOTE),
+            order_id: None,
+            actor_id: Some(user_id),
+        }).await?;
+    }
+
+    replace_product_images(&mut tx, product_id, &images).await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
     Ok(HttpResponse::Created().json(json!({
         "message": "Product created successfully",
-        "product_id": product.id
+        "product_id": product.id,
+        "review_status": review_status
     })))
 }
 
 pub async fn update_product(
-    identity: Identity,
+    user: AuthUser,
     pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn EmailSender>,
     product_id: web::Path<Uuid>,
     req: web::Json<UpdateProductRequest>,
 ) -> AppResult<HttpResponse> {
-    let user_id = get_user_id(&identity)?;
+    let user_id = user.id;
     let product_id = product_id.into_inner();
+    req.validate()?;
 
     // Check if user owns the product
     let seller_id = sqlx::query_scalar!(
-        "SELECT seller_id FROM products WHERE id = $1",
+        "SELECT seller_id FROM products WHERE id = $1 AND deleted_at IS NULL",
         product_id
     )
         .fetch_optional(pool.get_ref())
@@ -188,86 +561,153 @@ pub async fn update_product(
         return Err(AppError::Forbidden);
     }
 
-    // Build dynamic update query
-    let mut query_parts = vec![];
-    let mut param_count = 2; // Starting from $2 since $1 is product_id
-
-    if req.name.is_some() {
-        query_parts.push(format!("name = ${}", param_count));
-        param_count += 1;
-    }
-    if req.description.is_some() {
-        query_parts.push(format!("description = ${}", param_count));
-        param_count += 1;
-    }
-    if req.price_per_unit.is_some() {
-        query_parts.push(format!("price_per_unit = ${}", param_count));
-        param_count += 1;
-    }
+    // Stock of a product with variants is the sum of its variants' stock
     if req.stock_qty.is_some() {
-        query_parts.push(format!("stock_qty = ${}", param_count));
-        param_count += 1;
-    }
-    if req.category_id.is_some() {
-        query_parts.push(format!("category_id = ${}", param_count));
-        param_count += 1;
-    }
-    if req.image_url.is_some() {
-        query_parts.push(format!("image_url = ${}", param_count));
+        let has_variants = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM product_variants WHERE product_id = $1) as "exists!""#,
+            product_id
+        )
+            .fetch_one(pool.get_ref())
+            .await?;
+
+        if has_variants {
+            return Err(AppError::BadRequest(
+                "This product's stock is set per variant".to_string(),
+            ));
+        }
+        let mut conn = pool.acquire().await?;
+        if location_repository::is_stocked_by_location(&mut conn, product_id).await? {
+            return Err(AppError::BadRequest(
+                "This product's stock is kept by location; set it per location".to_string(),
+            ));
+        }
     }
 
-    if query_parts.is_empty() {
-        return Err(AppError::BadRequest("No fields to update".to_string()));
+    // Blank codes clear them
+    let sku = req.sku.as_deref().map(|sku| Some(sku.trim()).filter(|sku| !sku.is_empty()));
+    let barcode = req.barcode.as_deref().map(|barcode| Some(barcode.trim()).filter(|barcode| !barcode.is_empty()));
+    if sku.flatten().is_some() || barcode.flatten().is_some() {
+        let mut conn = pool.acquire().await?;
+        if let Some(code) = taken_product_code(&mut conn, user_id, sku.flatten(), barcode.flatten(), Some(product_id)).await? {
+            return Err(AppError::Conflict(format!("You already have a product with {}", code)));
+        }
     }
 
-    let update_query = format!(
-        "UPDATE products SET {} WHERE id = $1",
-        query_parts.join(", ")
-    );
+    let price = req.price_per_unit.as_ref().map(|price| price.round(2));
+    let currency = req.currency.as_deref().map(|code| currency_service::parse(&config, code)).transpose()?;
+    let location = validate_location(req.latitude, req.longitude)?;
 
-    // Execute with dynamic parameters
-    let mut query = sqlx::query(&update_query).bind(product_id);
+    let update = UpdateBuilder::new("products")
+        .set("name", req.name.as_deref())
+        .set("description", req.description.as_deref())
+        .set("price_per_unit", price.clone())
+        .set("currency", currency.clone())
+        .set("stock_qty", req.stock_qty)
+        .set("unit", req.unit)
+        .set("min_increment", req.min_increment)
+        .set("category_id", req.category_id)
+        .set("latitude", location.map(|(latitude, _)| latitude))
+        .set("longitude", location.map(|(_, longitude)| longitude))
+        .set("sku", sku)
+        .set("barcode", barcode);
 
-    if let Some(ref name) = req.name {
-        query = query.bind(name);
+    let updates_images = req.images.is_some() || req.image_url.is_some();
+    if update.is_empty() && !updates_images {
+        return Err(AppError::BadRequest("No fields to update".to_string()));
     }
-    if let Some(ref desc) = req.description {
-        query = query.bind(desc);
+    if let Some(images) = &req.images {
+        validate_image_urls(images)?;
     }
-    if let Some(price) = &req.price_per_unit {
-        query = query.bind(price);
+    if let Some(url) = &req.image_url {
+        validate_image_urls(std::slice::from_ref(url))?;
     }
-    if let Some(qty) = req.stock_qty {
-        query = query.bind(qty);
+
+    let mut tx = pool.begin().await?;
+
+    // The price and stock being replaced, read under the row lock so concurrent changes are recorded in order
+    let (old_price, old_stock) = if price.is_some() || req.stock_qty.is_some() {
+        let old = sqlx::query!("SELECT price_per_unit, stock_qty FROM products WHERE id = $1 FOR UPDATE", product_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        (Some(old.price_per_unit), Some(old.stock_qty))
+    } else {
+        (None, None)
+    };
+
+    update.execute(&mut *tx, product_id).await?;
+
+    // Editing a rejected listing sends it back to the review queue
+    let resubmitted = sqlx::query!(
+        r#"
+        UPDATE products SET review_status = 'pending_review', review_reason = NULL, reviewed_by = NULL, reviewed_at = NULL
+        WHERE id = $1 AND review_status = 'rejected'
+        "#,
+        product_id
+    )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() > 0;
+
+    // A full list replaces the gallery; a bare image_url replaces just the primary image
+    if let Some(images) = &req.images {
+        replace_product_images(&mut tx, product_id, images).await?;
+    } else if let Some(url) = &req.image_url {
+        set_primary_product_image(&mut tx, product_id, url).await?;
     }
-    if let Some(cat_id) = req.category_id {
-        query = query.bind(cat_id);
+
+    let price_change = old_price.zip(price).filter(|(old_price, price)| old_price != price);
+    if let Some((old_price, price)) = &price_change {
+        record_price_change(&mut tx, product_id, None, old_price, price, user_id).await?;
     }
-    if let Some(ref img) = req.image_url {
-        query = query.bind(img);
+    if let Some((old_stock, stock_qty)) = old_stock.zip(req.stock_qty).filter(|(old_stock, stock_qty)| old_stock != stock_qty) {
+        inventory_repository::record(&mut tx, StockMovement {
+            product_id,
+            variant_id: None,
+            quantity_change: stock_qty - old_stock,
+            stock_after: stock_qty,
+            reason: InventoryReason::Adjustment,
+            note: None,
+            order_id: None,
+            actor_id: Some(user_id),
+        }).await?;
+        audit_stock_change(&mut tx, product_id, None, old_stock, stock_qty, user_id).await?;
     }
 
-    query.execute(pool.get_ref()).await?;
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    // A price in a new currency can't be compared with the old one
+    let price_drop = price_change.filter(|(old_price, price)| price < old_price && currency.is_none());
+    if let Some((old_price, price)) = price_drop {
+        notify_price_drop(pool.get_ref(), mailer.into_inner(), product_id, None, &old_price, &price).await?;
+    }
 
     Ok(HttpResponse::Ok().json(json!({
-        "message": "Product updated successfully"
+        "message": "Product updated successfully",
+        "resubmitted_for_review": resubmitted
     })))
 }
 
+/// Hide the product from the marketplace, keeping its stock and history. The seller can
+/// restore it for PRODUCT_RESTORE_DAYS; holds on it are released and cart lines for it
+/// fail validation.
 pub async fn delete_product(
-    identity: Identity,
+    user: AuthUser,
     pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
     product_id: web::Path<Uuid>,
 ) -> AppResult<HttpResponse> {
-    let user_id = get_user_id(&identity)?;
+    let user_id = user.id;
     let product_id = product_id.into_inner();
 
+    let mut tx = pool.begin().await?;
+
     // Check if user owns the product
     let seller_id = sqlx::query_scalar!(
-        "SELECT seller_id FROM products WHERE id = $1",
+        "SELECT seller_id FROM products WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
         product_id
     )
-        .fetch_optional(pool.get_ref())
+        .fetch_optional(&mut *tx)
         .await?
         .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
 
@@ -275,15 +715,1070 @@ pub async fn delete_product(
         return Err(AppError::Forbidden);
     }
 
-    // Soft delete by setting stock to 0
-    sqlx::query!(
-        "UPDATE products SET stock_qty = 0 WHERE id = $1",
+    let deleted_at = sqlx::query_scalar!(
+        "UPDATE products SET deleted_at = NOW() WHERE id = $1 RETURNING deleted_at as \"deleted_at!\"",
+        product_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    sqlx::query!("DELETE FROM stock_reservations WHERE product_id = $1", product_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product deleted successfully",
+        "restorable_until": deleted_at + Duration::days(PRODUCT_RESTORE_DAYS)
+    })))
+}
+
+/// Bring back a product the seller deleted within the last PRODUCT_RESTORE_DAYS
+pub async fn restore_deleted_product(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let product_id = product_id.into_inner();
+
+    let product = sqlx::query!(
+        "SELECT seller_id, deleted_at FROM products WHERE id = $1",
         product_id
     )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    if product.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let Some(deleted_at) = product.deleted_at else {
+        return Err(AppError::BadRequest("Product is not deleted".to_string()));
+    };
+    if deleted_at + Duration::days(PRODUCT_RESTORE_DAYS) <= Utc::now() {
+        return Err(AppError::Conflict(format!(
+            "Deleted products can only be restored within {} days",
+            PRODUCT_RESTORE_DAYS
+        )));
+    }
+
+    sqlx::query!("UPDATE products SET deleted_at = NULL WHERE id = $1", product_id)
         .execute(pool.get_ref())
         .await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product restored successfully"
+    })))
+}
+
+pub async fn transfer_product(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<TransferProductRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let product_id = product_id.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    // Check if user owns the product, locking it so concurrent transfers take turns
+    let product = sqlx::query!(
+        "SELECT seller_id, name, sku, barcode FROM products WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        product_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    if product.seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    if req.target_user_id == user_id {
+        return Err(AppError::BadRequest("Product already belongs to this seller".to_string()));
+    }
+
+    // Target must be an active supplier
+    let target_is_supplier = sqlx::query_scalar!(
+        "SELECT is_supplier FROM users WHERE id = $1 AND deleted_at IS NULL AND suspended_at IS NULL",
+        req.target_user_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Target user not found".to_string()))?;
+
+    if !target_is_supplier {
+        return Err(AppError::BadRequest("Target user is not a supplier".to_string()));
+    }
+
+    let taken = taken_product_code(
+        &mut tx,
+        req.target_user_id,
+        product.sku.as_deref(),
+        product.barcode.as_deref(),
+        Some(product_id),
+    ).await?;
+    if let Some(code) = taken {
+        return Err(AppError::Conflict(format!("The seller already has a product with {}", code)));
+    }
+
+    // Only the product moves; existing orders keep their original seller_id
+    sqlx::query!(
+        "UPDATE products SET seller_id = $2 WHERE id = $1",
+        product_id,
+        req.target_user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+    location_repository::move_stock(&mut tx, product_id, req.target_user_id).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO product_ownership_history (id, product_id, from_seller_id, to_seller_id)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        Uuid::new_v4(),
+        product_id,
+        user_id,
+        req.target_user_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    // Let the new owner know if they are online
+    send_to_user(req.target_user_id, &ServerEvent::ProductTransfer {
+        product_id,
+        product_name: product.name,
+        from_seller_id: user_id,
+    });
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Product transferred successfully",
+        "product_id": product_id,
+        "seller_id": req.target_user_id
+    })))
+}
+
+const MAX_PRODUCT_IMAGES: usize = 10;
+
+pub async fn add_product_image(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<AddProductImageRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let product_id = product_id.into_inner();
+
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+    validate_image_urls(std::slice::from_ref(&req.url))?;
+
+    let mut tx = pool.begin().await?;
+
+    let image_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM product_images WHERE product_id = $1"#,
+        product_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if image_count as usize >= MAX_PRODUCT_IMAGES {
+        return Err(AppError::BadRequest(format!("A product can have at most {} images", MAX_PRODUCT_IMAGES)));
+    }
+
+    let image_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO product_images (id, product_id, url, position)
+        SELECT $1, $2, $3, COALESCE(MAX(position) + 1, 0)
+        FROM product_images WHERE product_id = $2
+        "#,
+        image_id,
+        product_id,
+        req.url
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    sync_primary_image(&mut tx, product_id).await?;
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Image added successfully",
+        "image_id": image_id
+    })))
+}
+
+pub async fn remove_product_image(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let (product_id, image_id) = path.into_inner();
+
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    let deleted = sqlx::query!(
+        "DELETE FROM product_images WHERE id = $1 AND product_id = $2",
+        image_id,
+        product_id
+    )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(AppError::NotFound("Image not found".to_string()));
+    }
+
+    sync_primary_image(&mut tx, product_id).await?;
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
 
     Ok(HttpResponse::Ok().json(json!({
-        "message": "Product deleted successfully"
+        "message": "Image removed successfully"
     })))
-}
\ No newline at end of file
+}
+
+pub async fn reorder_product_images(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<ReorderProductImagesRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let product_id = product_id.into_inner();
+
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    // The new order must name every image of the product exactly once
+    let mut current_ids = sqlx::query_scalar!(
+        "SELECT id FROM product_images WHERE product_id = $1",
+        product_id
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+    let mut requested_ids = req.image_ids.clone();
+    current_ids.sort();
+    requested_ids.sort();
+
+    if current_ids != requested_ids {
+        return Err(AppError::BadRequest("image_ids must list every image of the product exactly once".to_string()));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE product_images pi
+        SET position = ordered.ord - 1
+        FROM UNNEST($2::uuid[]) WITH ORDINALITY AS ordered(id, ord)
+        WHERE pi.id = ordered.id AND pi.product_id = $1
+        "#,
+        product_id,
+        &req.image_ids
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    sync_primary_image(&mut tx, product_id).await?;
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Images reordered successfully"
+    })))
+}
+
+/// Add a variant. The product's own stock is replaced by the sum of its variants', and
+/// cart lines, holds and template lines for the product without a variant are dropped,
+/// since it can now only be bought as one of them. Its price tiers go too, as variants
+/// carry their own prices.
+pub async fn create_variant(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<CreateVariantRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let product_id = product_id.into_inner();
+
+    req.validate()?;
+    let name = req.name.trim().to_string();
+
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_product(&mut tx, product_id).await?;
+    if location_repository::is_stocked_by_location(&mut tx, product_id).await? {
+        return Err(AppError::BadRequest(
+            "This product's stock is kept by location and can't be split into variants".to_string(),
+        ));
+    }
+    // Variants are priced on their own, so the deal's price would no longer apply
+    if deal_repository::has_open_deal(&mut tx, product_id).await? {
+        return Err(AppError::BadRequest(
+            "This product has a deal on or coming up; end it before adding variants".to_string(),
+        ));
+    }
+    ensure_variant_name_free(&mut tx, product_id, &name, None).await?;
+
+    let variant = sqlx::query_as!(
+        ProductVariant,
+        r#"
+        INSERT INTO product_variants (id, product_id, name, price_per_unit, stock_qty)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, product_id, name, price_per_unit, stock_qty, created_at
+        "#,
+        Uuid::new_v4(),
+        product_id,
+        name,
+        req.price_per_unit.round(2),
+        req.stock_qty
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if variant.stock_qty > 0 {
+        inventory_repository::record(&mut tx, StockMovement {
+            product_id,
+            variant_id: Some(variant.id),
+            quantity_change: variant.stock_qty,
+            stock_after: variant.stock_qty,
+            reason: InventoryReason::Adjustment,
+            note: Some(INITIAL_STOCK_NOTE),
+            order_id: None,
+            actor_id: Some(user_id),
+        }).await?;
+    }
+
+    sqlx::query!(
+        "DELETE FROM cart_items WHERE product_id = $1 AND variant_id IS NULL",
+        product_id
+    )
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM stock_reservations WHERE product_id = $1 AND variant_id IS NULL",
+        product_id
+    )
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM cart_template_items WHERE product_id = $1 AND variant_id IS NULL",
+        product_id
+    )
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM price_tiers WHERE product_id = $1", product_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Variant created successfully",
+        "variant": variant
+    })))
+}
+
+pub async fn update_variant(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    mailer: web::Data<dyn EmailSender>,
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<UpdateVariantRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let (product_id, variant_id) = path.into_inner();
+
+    req.validate()?;
+    let name = req.name.as_deref().map(|name| name.trim().to_string());
+
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_product(&mut tx, product_id).await?;
+    if let Some(name) = &name {
+        ensure_variant_name_free(&mut tx, product_id, name, Some(variant_id)).await?;
+    }
+
+    let old = sqlx::query!(
+        "SELECT price_per_unit, stock_qty FROM product_variants WHERE id = $1 AND product_id = $2 FOR UPDATE",
+        variant_id,
+        product_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Variant not found".to_string()))?;
+    let old_price = old.price_per_unit;
+
+    let variant = sqlx::query_as!(
+        ProductVariant,
+        r#"
+        UPDATE product_variants
+        SET name = COALESCE($3, name),
+            price_per_unit = COALESCE($4, price_per_unit),
+            stock_qty = COALESCE($5, stock_qty)
+        WHERE id = $1 AND product_id = $2
+        RETURNING id, product_id, name, price_per_unit, stock_qty, created_at
+        "#,
+        variant_id,
+        product_id,
+        name,
+        req.price_per_unit.as_ref().map(|price| price.round(2)),
+        req.stock_qty
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Variant not found".to_string()))?;
+
+    if variant.price_per_unit != old_price {
+        record_price_change(&mut tx, product_id, Some(variant_id), &old_price, &variant.price_per_unit, user_id).await?;
+    }
+    if variant.stock_qty != old.stock_qty {
+        inventory_repository::record(&mut tx, StockMovement {
+            product_id,
+            variant_id: Some(variant_id),
+            quantity_change: variant.stock_qty - old.stock_qty,
+            stock_after: variant.stock_qty,
+            reason: InventoryReason::Adjustment,
+            note: None,
+            order_id: None,
+            actor_id: Some(user_id),
+        }).await?;
+        audit_stock_change(&mut tx, product_id, Some(variant_id), old.stock_qty, variant.stock_qty, user_id).await?;
+    }
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    if variant.price_per_unit < old_price {
+        notify_price_drop(
+            pool.get_ref(),
+            mailer.into_inner(),
+            product_id,
+            Some(variant_id),
+            &old_price,
+            &variant.price_per_unit,
+        ).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Variant updated successfully",
+        "variant": variant
+    })))
+}
+
+/// Remove a variant; cart lines for it go with it, past order items keep its name
+pub async fn delete_variant(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let (product_id, variant_id) = path.into_inner();
+
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_product(&mut tx, product_id).await?;
+
+    let deleted = sqlx::query!(
+        "DELETE FROM product_variants WHERE id = $1 AND product_id = $2",
+        variant_id,
+        product_id
+    )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(AppError::NotFound("Variant not found".to_string()));
+    }
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Variant deleted successfully"
+    })))
+}
+
+/// Replace the product's quantity discounts. Each tier must undercut the list price;
+/// products with variants are priced per variant and can't be tiered.
+pub async fn set_price_tiers(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<SetPriceTiersRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let product_id = product_id.into_inner();
+
+    req.validate()?;
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_product(&mut tx, product_id).await?;
+
+    let product = sqlx::query!(
+        r#"
+        SELECT price_per_unit,
+               EXISTS(SELECT 1 FROM product_variants v WHERE v.product_id = products.id) as "has_variants!"
+        FROM products
+        WHERE id = $1
+        "#,
+        product_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if product.has_variants && !req.tiers.is_empty() {
+        return Err(AppError::BadRequest(
+            "Products with variants are priced per variant and can't have price tiers".to_string(),
+        ));
+    }
+    if let Some(tier) = req.tiers.iter().find(|tier| tier.unit_price.round(2) >= product.price_per_unit) {
+        return Err(AppError::BadRequest(format!(
+            "The tier from {} units must cost less than the list price of {}",
+            tier.min_qty, product.price_per_unit
+        )));
+    }
+
+    product_repository::set_price_tiers(&mut tx, product_id, &req.tiers).await?;
+    let tiers = product_repository::price_tiers(&mut tx, product_id).await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Price tiers updated successfully",
+        "price_tiers": tiers
+    })))
+}
+
+/// Price changes to the product and its variants, newest first
+pub async fn get_price_history(
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let product_id = product_id.into_inner();
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let product = sqlx::query!(
+        r#"
+        SELECT price_per_unit
+        FROM products
+        WHERE id = $1 AND taken_down_at IS NULL AND deleted_at IS NULL
+          AND review_status = 'approved'
+        "#,
+        product_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    let history = sqlx::query_as!(
+        PriceChange,
+        r#"
+        SELECT h.id, h.variant_id, v.name as "variant_name?", h.old_price, h.new_price, h.changed_at
+        FROM price_history h
+        LEFT JOIN product_variants v ON h.variant_id = v.id
+        WHERE h.product_id = $1
+        ORDER BY h.changed_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        product_id,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM price_history WHERE product_id = $1"#,
+        product_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "product_id": product_id,
+        "current_price": product.price_per_unit,
+        "history": history,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// Correct a product's or variant's stock by hand, e.g. after a recount or spoilage.
+/// The note says why and is kept in the inventory history.
+pub async fn stock_adjust(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
+    product_id: web::Path<Uuid>,
+    req: web::Json<StockAdjustRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = user.id;
+    let product_id = product_id.into_inner();
+    req.validate()?;
+
+    ensure_product_owner(pool.get_ref(), product_id, user_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    lock_product(&mut tx, product_id).await?;
+
+    let has_variants = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM product_variants WHERE product_id = $1) as "exists!""#,
+        product_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let stock_qty = match (req.variant_id, has_variants) {
+        (Some(variant_id), true) => sqlx::query_scalar!(
+            "SELECT stock_qty FROM product_variants WHERE id = $1 AND product_id = $2 FOR UPDATE",
+            variant_id,
+            product_id
+        )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Variant not found".to_string()))?,
+        (None, true) => {
+            return Err(AppError::BadRequest(
+                "This product's stock is set per variant; choose a variant_id".to_string(),
+            ));
+        }
+        (Some(_), false) => return Err(AppError::NotFound("Variant not found".to_string())),
+        (None, false) if location_repository::is_stocked_by_location(&mut tx, product_id).await? => {
+            return Err(AppError::BadRequest(
+                "This product's stock is kept by location; set it per location".to_string(),
+            ));
+        }
+        (None, false) => sqlx::query_scalar!("SELECT stock_qty FROM products WHERE id = $1", product_id)
+            .fetch_one(&mut *tx)
+            .await?,
+    };
+
+    let stock_after = stock_qty + req.quantity_change;
+    if stock_after < 0 {
+        return Err(AppError::BadRequest(format!(
+            "Only {} in stock; the adjustment would leave it negative",
+            stock_qty
+        )));
+    }
+
+    match req.variant_id {
+        Some(variant_id) => {
+            sqlx::query!("UPDATE product_variants SET stock_qty = $2 WHERE id = $1", variant_id, stock_after)
+                .execute(&mut *tx)
+                .await?;
+        }
+        None => {
+            sqlx::query!("UPDATE products SET stock_qty = $2 WHERE id = $1", product_id, stock_after)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    inventory_repository::record(&mut tx, StockMovement {
+        product_id,
+        variant_id: req.variant_id,
+        quantity_change: req.quantity_change,
+        stock_after,
+        reason: InventoryReason::Adjustment,
+        note: Some(req.note.trim()),
+        order_id: None,
+        actor_id: Some(user_id),
+    }).await?;
+    audit_stock_change(&mut tx, product_id, req.variant_id, stock_qty, stock_after, user_id).await?;
+
+    tx.commit().await?;
+    cache.invalidate(Namespace::Products).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Stock adjusted successfully",
+        "product_id": product_id,
+        "variant_id": req.variant_id,
+        "stock_qty": stock_after
+    })))
+}
+
+/// Every stock change of the owner's product, newest first
+pub async fn get_inventory_history(
+    user: AuthUser,
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let product_id = product_id.into_inner();
+    let pagination = Pagination::new(query.page, query.limit);
+
+    ensure_product_owner(pool.get_ref(), product_id, user.id).await?;
+
+    let movements = sqlx::query_as!(
+        InventoryMovement,
+        r#"
+        SELECT m.id, m.product_id, m.variant_id, v.name as "variant_name?", m.quantity_change,
+               m.stock_after, m.reason as "reason: InventoryReason", m.note, m.order_id, m.actor_id,
+               u.name as "actor_name?", m.created_at
+        FROM inventory_movements m
+        LEFT JOIN product_variants v ON m.variant_id = v.id
+        LEFT JOIN users u ON m.actor_id = u.id
+        WHERE m.product_id = $1
+        ORDER BY m.created_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        product_id,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM inventory_movements WHERE product_id = $1"#,
+        product_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "product_id": product_id,
+        "movements": movements,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+async fn record_price_change(
+    conn: &mut PgConnection,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    old_price: &BigDecimal,
+    new_price: &BigDecimal,
+    changed_by: Uuid,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO price_history (id, product_id, variant_id, old_price, new_price, changed_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        product_id,
+        variant_id,
+        old_price,
+        new_price,
+        changed_by
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    audit_repository::record(conn, AuditEntry {
+        actor_id: Some(changed_by),
+        action: AuditAction::ProductPriceChanged,
+        subject_id: Some(product_id),
+        details: json!({ "variant_id": variant_id, "old_price": old_price, "new_price": new_price }),
+    }).await?;
+
+    Ok(())
+}
+
+/// Audit a stock level the seller set or adjusted by hand; sales and cancellations are
+/// in the inventory history only
+pub async fn audit_stock_change(
+    conn: &mut PgConnection,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    stock_before: i32,
+    stock_after: i32,
+    changed_by: Uuid,
+) -> AppResult<()> {
+    audit_repository::record(conn, AuditEntry {
+        actor_id: Some(changed_by),
+        action: AuditAction::ProductStockChanged,
+        subject_id: Some(product_id),
+        details: json!({ "variant_id": variant_id, "stock_before": stock_before, "stock_after": stock_after }),
+    }).await
+}
+
+/// Tell everyone with the product in their cart or favorites that its price dropped, over
+/// WebSocket and by email. A variant's drop reaches carts holding that variant; the
+/// product's own price reaches carts holding it without a variant.
+/// Where a new listing starts: in the review queue when PRODUCT_REVIEW_REQUIRED is on
+pub fn initial_review_status(config: &Config) -> ProductReviewStatus {
+    if config.product_review_required {
+        ProductReviewStatus::PendingReview
+    } else {
+        ProductReviewStatus::Approved
+    }
+}
+
+async fn notify_price_drop(
+    pool: &PgPool,
+    mailer: Arc<dyn EmailSender>,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    old_price: &BigDecimal,
+    new_price: &BigDecimal,
+) -> AppResult<()> {
+    let Some(product) = sqlx::query!(
+        r#"
+        SELECT p.name, p.seller_id, p.currency,
+               (SELECT v.name FROM product_variants v WHERE v.id = $2) as variant_name
+        FROM products p
+        WHERE p.id = $1 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+        "#,
+        product_id,
+        variant_id
+    )
+        .fetch_optional(pool)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let recipients = sqlx::query!(
+        r#"
+        SELECT u.id, u.email, u.name
+        FROM users u
+        WHERE u.deleted_at IS NULL AND u.id <> $3
+          AND (EXISTS(SELECT 1 FROM cart_items ci
+                      WHERE ci.user_id = u.id AND ci.product_id = $1 AND ci.variant_id IS NOT DISTINCT FROM $2)
+               OR EXISTS(SELECT 1 FROM favorites f WHERE f.user_id = u.id AND f.product_id = $1))
+        "#,
+        product_id,
+        variant_id,
+        product.seller_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let product_name = match &product.variant_name {
+        Some(variant_name) => format!("{} ({})", product.name, variant_name),
+        None => product.name.clone(),
+    };
+    let event = ServerEvent::PriceDrop {
+        product_id,
+        variant_id,
+        product_name: product_name.clone(),
+        old_price: old_price.clone(),
+        new_price: new_price.clone(),
+        currency: product.currency.clone(),
+    };
+    let old_price = currency_service::format(old_price, &product.currency);
+    let new_price = currency_service::format(new_price, &product.currency);
+
+    for recipient in &recipients {
+        send_to_user(recipient.id, &event);
+        mailer::send_in_background(
+            mailer.clone(),
+            templates::price_drop(&recipient.email, recipient.name.as_deref(), &product_name, &old_price, &new_price),
+        );
+    }
+
+    tracing::info!(%product_id, ?variant_id, notified = recipients.len(), "Price drop announced");
+
+    Ok(())
+}
+
+/// Lock the product row; variant changes take it first, like checkout, so they can't deadlock
+async fn lock_product(conn: &mut PgConnection, product_id: Uuid) -> AppResult<()> {
+    sqlx::query_scalar!("SELECT id FROM products WHERE id = $1 AND deleted_at IS NULL FOR UPDATE", product_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    Ok(())
+}
+
+/// Variant names are unique within a product, regardless of case
+async fn ensure_variant_name_free(
+    conn: &mut PgConnection,
+    product_id: Uuid,
+    name: &str,
+    except_id: Option<Uuid>,
+) -> AppResult<()> {
+    let taken = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM product_variants
+            WHERE product_id = $1 AND LOWER(name) = LOWER($2) AND ($3::uuid IS NULL OR id <> $3)
+        ) as "exists!"
+        "#,
+        product_id,
+        name,
+        except_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    if taken {
+        return Err(AppError::Conflict(format!("Variant {} already exists", name)));
+    }
+    Ok(())
+}
+
+/// Which of the SKU and barcode another of the seller's products, deleted ones included,
+/// already has, described for an error message. SKUs are compared regardless of case.
+async fn taken_product_code(
+    conn: &mut PgConnection,
+    seller_id: Uuid,
+    sku: Option<&str>,
+    barcode: Option<&str>,
+    except_id: Option<Uuid>,
+) -> AppResult<Option<String>> {
+    if sku.is_none() && barcode.is_none() {
+        return Ok(None);
+    }
+
+    let taken = sqlx::query!(
+        r#"
+        SELECT
+            EXISTS(SELECT 1 FROM products
+                   WHERE seller_id = $1 AND LOWER(sku) = LOWER($2) AND ($4::uuid IS NULL OR id <> $4)) as "sku!",
+            EXISTS(SELECT 1 FROM products
+                   WHERE seller_id = $1 AND barcode = $3 AND ($4::uuid IS NULL OR id <> $4)) as "barcode!"
+        "#,
+        seller_id,
+        sku,
+        barcode,
+        except_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    Ok(match (sku, barcode) {
+        (Some(sku), _) if taken.sku => Some(format!("SKU {}", sku)),
+        (_, Some(barcode)) if taken.barcode => Some(format!("barcode {}", barcode)),
+        _ => None,
+    })
+}
+
+async fn ensure_product_owner(pool: &PgPool, product_id: Uuid, user_id: Uuid) -> AppResult<()> {
+    let seller_id = sqlx::query_scalar!(
+        "SELECT seller_id FROM products WHERE id = $1 AND deleted_at IS NULL",
+        product_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    if seller_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(())
+}
+
+fn validate_image_urls(urls: &[String]) -> AppResult<()> {
+    if urls.len() > MAX_PRODUCT_IMAGES {
+        return Err(AppError::BadRequest(format!("A product can have at most {} images", MAX_PRODUCT_IMAGES)));
+    }
+    if urls.iter().any(|url| url.trim().is_empty()) {
+        return Err(AppError::BadRequest("Image URLs cannot be empty".to_string()));
+    }
+    Ok(())
+}
+
+/// Replace the whole gallery with the given URLs, in order
+async fn replace_product_images(conn: &mut PgConnection, product_id: Uuid, urls: &[String]) -> AppResult<()> {
+    sqlx::query!("DELETE FROM product_images WHERE product_id = $1", product_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let image_ids: Vec<Uuid> = urls.iter().map(|_| Uuid::new_v4()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO product_images (id, product_id, url, position)
+        SELECT image.id, $1, image.url, image.ord - 1
+        FROM UNNEST($2::uuid[], $3::text[]) WITH ORDINALITY AS image(id, url, ord)
+        "#,
+        product_id,
+        &image_ids,
+        urls
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    sync_primary_image(conn, product_id).await
+}
+
+/// Swap the URL of the first image, or add one if the gallery is empty
+async fn set_primary_product_image(conn: &mut PgConnection, product_id: Uuid, url: &str) -> AppResult<()> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE product_images SET url = $2
+        WHERE id = (
+            SELECT id FROM product_images WHERE product_id = $1
+            ORDER BY position LIMIT 1
+        )
+        "#,
+        product_id,
+        url
+    )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        sqlx::query!(
+            "INSERT INTO product_images (id, product_id, url, position) VALUES ($1, $2, $3, 0)",
+            Uuid::new_v4(),
+            product_id,
+            url
+        )
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sync_primary_image(conn, product_id).await
+}
+
+/// Keep products.image_url pointing at the first gallery image
+async fn sync_primary_image(conn: &mut PgConnection, product_id: Uuid) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        UPDATE products SET image_url = (
+            SELECT url FROM product_images WHERE product_id = $1
+            ORDER BY position LIMIT 1
+        )
+        WHERE id = $1
+        "#,
+        product_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// handlers/recommendation_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::errors::{AppError, AppResult};
+use crate::recommendations;
+use crate::utils::get_user_id;
+
+/// Products related to this one: bought by the same buyers, in the same category or
+/// from the same seller
+pub async fn get_related_products(
+    user: Option<AuthUser>,
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let viewer_id = user.map(|user| user.id);
+    let product_id = product_id.into_inner();
+
+    let product_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM products WHERE id = $1 AND taken_down_at IS NULL AND deleted_at IS NULL AND review_status = 'approved') as "exists!""#,
+        product_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !product_exists {
+        return Err(AppError::NotFound("Product not found".to_string()));
+    }
+
+    let products = recommendations::related(pool.get_ref(), product_id, viewer_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "product_id": product_id,
+        "products": products
+    })))
+}
+
+pub async fn get_recommendations(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let (basis, products) = recommendations::for_user(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "based_on": basis,
+        "products": products
+    })))
+}
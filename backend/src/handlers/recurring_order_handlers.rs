@@ -0,0 +1,358 @@
+// handlers/recurring_order_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::handlers::address_handlers::shipping_snapshot;
+use crate::models::{CreateRecurringOrderRequest, RecurringOrder, RecurringOrderStatus, UpdateRecurringOrderRequest};
+use crate::recurring_orders::next_run_after;
+use crate::repositories::recurring_order_repository;
+use crate::utils::get_user_id;
+use crate::validation::Validate;
+
+/// Runs shown with a standing order
+const RECENT_RUNS: i64 = 20;
+
+pub async fn get_recurring_orders(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+
+    let recurring_orders = sqlx::query_as!(
+        RecurringOrder,
+        r#"
+        SELECT id, buyer_id, seller_id, address_id, interval_days, status as "status: RecurringOrderStatus",
+               next_run_at, created_at, updated_at
+        FROM recurring_orders
+        WHERE buyer_id = $1
+        ORDER BY status = 'cancelled', next_run_at
+        "#,
+        buyer_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let mut list = vec![];
+    for recurring_order in &recurring_orders {
+        list.push(recurring_order_json(pool.get_ref(), recurring_order).await?);
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "recurring_orders": list
+    })))
+}
+
+/// Set up a standing order. The first order is placed at `starts_at`, or one interval
+/// from now.
+pub async fn create_recurring_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<CreateRecurringOrderRequest>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    req.validate()?;
+
+    shipping_snapshot(pool.get_ref(), buyer_id, req.address_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    let seller_id = recurring_order_repository::seller_of(&mut tx, &req.items).await?;
+    if seller_id == buyer_id {
+        return Err(AppError::BadRequest("You can't order your own products".to_string()));
+    }
+
+    let id = Uuid::new_v4();
+    let next_run_at = req.starts_at.unwrap_or_else(|| Utc::now() + Duration::days(req.interval_days.into()));
+    sqlx::query!(
+        r#"
+        INSERT INTO recurring_orders (id, buyer_id, seller_id, address_id, interval_days, next_run_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        id,
+        buyer_id,
+        seller_id,
+        req.address_id,
+        req.interval_days,
+        next_run_at
+    )
+        .execute(&mut *tx)
+        .await?;
+    recurring_order_repository::set_items(&mut tx, id, &req.items).await?;
+
+    tx.commit().await?;
+
+    let recurring_order = owned_recurring_order(pool.get_ref(), id, buyer_id).await?;
+    tracing::info!(%buyer_id, %seller_id, recurring_order_id = %id, "Standing order created");
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Standing order created",
+        "recurring_order": recurring_order_json(pool.get_ref(), &recurring_order).await?
+    })))
+}
+
+/// The standing order with its latest runs, newest first
+pub async fn get_recurring_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let recurring_order = owned_recurring_order(pool.get_ref(), id.into_inner(), buyer_id).await?;
+    let runs = recurring_order_repository::runs(pool.get_ref(), recurring_order.id, RECENT_RUNS).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "recurring_order": recurring_order_json(pool.get_ref(), &recurring_order).await?,
+        "runs": runs
+    })))
+}
+
+/// Change the items, interval or address. A new interval counts from the next order
+/// already scheduled.
+pub async fn update_recurring_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    id: web::Path<Uuid>,
+    req: web::Json<UpdateRecurringOrderRequest>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    req.validate()?;
+    let recurring_order = owned_recurring_order(pool.get_ref(), id.into_inner(), buyer_id).await?;
+
+    if recurring_order.status == RecurringOrderStatus::Cancelled {
+        return Err(AppError::Conflict("This standing order was cancelled".to_string()));
+    }
+    if let Some(address_id) = req.address_id {
+        shipping_snapshot(pool.get_ref(), buyer_id, address_id).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+
+    if let Some(items) = &req.items {
+        let seller_id = recurring_order_repository::seller_of(&mut tx, items).await?;
+        if seller_id != recurring_order.seller_id {
+            return Err(AppError::BadRequest(
+                "A standing order stays with its seller; set up a new one for another seller".to_string(),
+            ));
+        }
+        recurring_order_repository::set_items(&mut tx, recurring_order.id, items).await?;
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE recurring_orders
+        SET interval_days = COALESCE($2, interval_days),
+            address_id = COALESCE($3, address_id),
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+        recurring_order.id,
+        req.interval_days,
+        req.address_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let recurring_order = owned_recurring_order(pool.get_ref(), recurring_order.id, buyer_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Standing order updated",
+        "recurring_order": recurring_order_json(pool.get_ref(), &recurring_order).await?
+    })))
+}
+
+/// Stop placing orders until the buyer resumes
+pub async fn pause_recurring_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let recurring_order = owned_recurring_order(pool.get_ref(), id.into_inner(), buyer_id).await?;
+
+    if recurring_order.status != RecurringOrderStatus::Active {
+        return Err(AppError::Conflict("Only an active standing order can be paused".to_string()));
+    }
+
+    let recurring_order = update_schedule(
+        pool.get_ref(),
+        &recurring_order,
+        RecurringOrderStatus::Paused,
+        recurring_order.next_run_at,
+    ).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Standing order paused",
+        "recurring_order": recurring_order_json(pool.get_ref(), &recurring_order).await?
+    })))
+}
+
+/// Start placing orders again from the next cycle still to come; cycles that fell due
+/// while paused aren't made up
+pub async fn resume_recurring_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let recurring_order = owned_recurring_order(pool.get_ref(), id.into_inner(), buyer_id).await?;
+
+    if recurring_order.status != RecurringOrderStatus::Paused {
+        return Err(AppError::Conflict("Only a paused standing order can be resumed".to_string()));
+    }
+
+    let now = Utc::now();
+    let next_run_at = if recurring_order.next_run_at > now {
+        recurring_order.next_run_at
+    } else {
+        next_run_after(recurring_order.next_run_at, recurring_order.interval_days, now)
+    };
+    let recurring_order = update_schedule(pool.get_ref(), &recurring_order, RecurringOrderStatus::Active, next_run_at).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Standing order resumed",
+        "recurring_order": recurring_order_json(pool.get_ref(), &recurring_order).await?
+    })))
+}
+
+/// Leave out the next order; the one after is placed as usual
+pub async fn skip_recurring_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let recurring_order = owned_recurring_order(pool.get_ref(), id.into_inner(), buyer_id).await?;
+
+    if recurring_order.status == RecurringOrderStatus::Cancelled {
+        return Err(AppError::Conflict("This standing order was cancelled".to_string()));
+    }
+
+    let next_run_at = recurring_order.next_run_at + Duration::days(recurring_order.interval_days.into());
+    let recurring_order = update_schedule(pool.get_ref(), &recurring_order, recurring_order.status, next_run_at).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Next order skipped",
+        "recurring_order": recurring_order_json(pool.get_ref(), &recurring_order).await?
+    })))
+}
+
+/// Stop the standing order for good. Orders it already placed are unaffected.
+pub async fn cancel_recurring_order(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let recurring_order = owned_recurring_order(pool.get_ref(), id.into_inner(), buyer_id).await?;
+
+    if recurring_order.status == RecurringOrderStatus::Cancelled {
+        return Err(AppError::Conflict("This standing order was already cancelled".to_string()));
+    }
+
+    let recurring_order = update_schedule(
+        pool.get_ref(),
+        &recurring_order,
+        RecurringOrderStatus::Cancelled,
+        recurring_order.next_run_at,
+    ).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Standing order cancelled",
+        "recurring_order": recurring_order_json(pool.get_ref(), &recurring_order).await?
+    })))
+}
+
+async fn owned_recurring_order(pool: &PgPool, id: Uuid, buyer_id: Uuid) -> AppResult<RecurringOrder> {
+    let recurring_order = recurring_order_repository::find(pool, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Standing order not found".to_string()))?;
+
+    if recurring_order.buyer_id != buyer_id {
+        return Err(AppError::Forbidden);
+    }
+    Ok(recurring_order)
+}
+
+/// Move the standing order to `status` and `next_run_at`, unless the scheduler or
+/// another request changed it since it was read
+async fn update_schedule(
+    pool: &PgPool,
+    recurring_order: &RecurringOrder,
+    status: RecurringOrderStatus,
+    next_run_at: chrono::DateTime<Utc>,
+) -> AppResult<RecurringOrder> {
+    let updated = sqlx::query_as!(
+        RecurringOrder,
+        r#"
+        UPDATE recurring_orders
+        SET status = $4, next_run_at = $5, updated_at = NOW()
+        WHERE id = $1 AND status = $2 AND next_run_at = $3
+        RETURNING id, buyer_id, seller_id, address_id, interval_days, status as "status: RecurringOrderStatus",
+                  next_run_at, created_at, updated_at
+        "#,
+        recurring_order.id,
+        recurring_order.status as RecurringOrderStatus,
+        recurring_order.next_run_at,
+        status as RecurringOrderStatus,
+        next_run_at
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Conflict("The standing order just changed; try again".to_string()))?;
+
+    Ok(updated)
+}
+
+/// The standing order with its seller, and its items at today's prices
+async fn recurring_order_json(pool: &PgPool, recurring_order: &RecurringOrder) -> AppResult<serde_json::Value> {
+    let items = sqlx::query!(
+        r#"
+        SELECT ri.product_id, ri.variant_id, ri.quantity, p.name, v.name as "variant_name?",
+               p.currency, unit_price(ri.product_id, ri.variant_id, ri.quantity) as unit_price
+        FROM recurring_order_items ri
+        JOIN products p ON ri.product_id = p.id
+        LEFT JOIN product_variants v ON ri.variant_id = v.id
+        WHERE ri.recurring_order_id = $1
+        ORDER BY p.name, v.name
+        "#,
+        recurring_order.id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let seller_name = sqlx::query_scalar!("SELECT name FROM users WHERE id = $1", recurring_order.seller_id)
+        .fetch_one(pool)
+        .await?;
+
+    let item_list = items.iter().map(|item| {
+        json!({
+            "product_id": item.product_id,
+            "variant_id": item.variant_id,
+            "name": item.name,
+            "variant_name": item.variant_name,
+            "quantity": item.quantity,
+            "unit_price": item.unit_price,
+            "currency": item.currency
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(json!({
+        "id": recurring_order.id,
+        "seller_id": recurring_order.seller_id,
+        "seller_name": seller_name,
+        "address_id": recurring_order.address_id,
+        "interval_days": recurring_order.interval_days,
+        "status": recurring_order.status,
+        "next_run_at": recurring_order.next_run_at,
+        "items": item_list,
+        "created_at": recurring_order.created_at,
+        "updated_at": recurring_order.updated_at
+    }))
+}
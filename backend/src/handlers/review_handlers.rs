@@ -0,0 +1,162 @@
+// handlers/review_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{CreateReviewRequest, OrderStatus, PaginationQuery};
+use crate::utils::{get_user_id, Pagination};
+use crate::validation::Validate;
+
+pub async fn create_review(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    req: web::Json<CreateReviewRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let order_id = order_id.into_inner();
+
+    req.validate()?;
+
+    let order = sqlx::query!(
+        r#"SELECT buyer_id, seller_id, status as "status: OrderStatus" FROM orders WHERE id = $1"#,
+        order_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+
+    // Only the buyer can review, and only once the goods have arrived
+    if order.buyer_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    if !matches!(order.status, OrderStatus::Delivered) {
+        return Err(AppError::BadRequest("Only delivered orders can be reviewed".to_string()));
+    }
+
+    let already_reviewed = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM reviews WHERE order_id = $1) as "exists!""#,
+        order_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if already_reviewed {
+        return Err(AppError::Conflict("Order has already been reviewed".to_string()));
+    }
+
+    let review_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO reviews (id, buyer_id, seller_id, order_id, rating, comment)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        review_id,
+        user_id,
+        order.seller_id,
+        order_id,
+        req.rating,
+        req.comment
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    // Refresh the seller's aggregate rating without holding up the response
+    let pool_clone = pool.get_ref().clone();
+    let seller_id = order.seller_id;
+    actix_web::rt::spawn(async move {
+        if let Err(e) = recalculate_seller_rating(&pool_clone, seller_id).await {
+            tracing::error!(%seller_id, error = %e, "Failed to recalculate seller rating");
+        }
+    }.in_current_span());
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Review submitted successfully",
+        "review_id": review_id
+    })))
+}
+
+pub async fn get_product_reviews(
+    pool: web::Data<PgPool>,
+    product_id: web::Path<Uuid>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let product_id = product_id.into_inner();
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let product_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM products WHERE id = $1 AND deleted_at IS NULL) as "exists!""#,
+        product_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !product_exists {
+        return Err(AppError::NotFound("Product not found".to_string()));
+    }
+
+    // A review covers the whole order, so it applies to every product in it
+    let reviews = sqlx::query!(
+        r#"
+        SELECT r.id, r.rating, r.comment, r.created_at,
+               u.name as buyer_name
+        FROM reviews r
+        JOIN users u ON r.buyer_id = u.id
+        WHERE r.order_id IN (SELECT order_id FROM order_items WHERE product_id = $1)
+        ORDER BY r.created_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        product_id,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let summary = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!", AVG(r.rating)::float8 as average_rating
+        FROM reviews r
+        WHERE r.order_id IN (SELECT order_id FROM order_items WHERE product_id = $1)
+        "#,
+        product_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let review_list = reviews.iter().map(|review| {
+        json!({
+            "id": review.id,
+            "rating": review.rating,
+            "comment": review.comment,
+            "buyer_name": review.buyer_name,
+            "created_at": review.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "reviews": review_list,
+        "average_rating": summary.average_rating,
+        "pagination": pagination.to_json(summary.count)
+    })))
+}
+
+/// Recompute a seller's average rating from all of their reviews
+pub async fn recalculate_seller_rating(pool: &PgPool, seller_id: Uuid) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET rating = (SELECT AVG(rating)::float8 FROM reviews WHERE seller_id = $1)
+        WHERE id = $1
+        "#,
+        seller_id
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
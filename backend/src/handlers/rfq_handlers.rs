@@ -0,0 +1,566 @@
+// handlers/rfq_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::handlers::address_handlers::shipping_snapshot;
+use crate::handlers::notification_handlers::notify;
+use crate::handlers::offer_handlers::{announce_offer, announce_ordered_offer, open_offer, order_offer};
+use crate::models::{
+    AcceptQuoteRequest, CreateOfferRequest, CreateQuoteRequest, CreateRfqRequest, NotificationKind, Offer, OfferStatus,
+    Quote, Rfq, RfqQuery, RfqStatus,
+};
+use crate::utils::{get_user_id, Pagination};
+use crate::validation::Validate;
+
+/// How long an RFQ stays open unless the buyer says otherwise
+const DEFAULT_RFQ_DAYS: i64 = 7;
+/// Open RFQs a buyer can have at once
+const MAX_OPEN_RFQS: i64 = 20;
+
+/// Post a request for quote and alert the suppliers who can answer it
+pub async fn create_rfq(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<CreateRfqRequest>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    req.validate()?;
+
+    // A product's other sellers can quote too, through its category
+    let category_id = match req.product_id {
+        Some(product_id) => {
+            let product = sqlx::query!(
+                r#"
+                SELECT seller_id, category_id
+                FROM products
+                WHERE id = $1 AND taken_down_at IS NULL AND deleted_at IS NULL AND review_status = 'approved'
+                "#,
+                product_id
+            )
+                .fetch_optional(pool.get_ref())
+                .await?
+                .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+            if product.seller_id == buyer_id {
+                return Err(AppError::BadRequest("You can't request quotes for your own product".to_string()));
+            }
+            product.category_id
+        }
+        None => {
+            let category_id = req.category_id.unwrap_or_default();
+            sqlx::query_scalar!("SELECT id FROM categories WHERE id = $1", category_id)
+                .fetch_optional(pool.get_ref())
+                .await?
+                .ok_or_else(|| AppError::NotFound("Category not found".to_string()))?
+        }
+    };
+
+    let open = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM rfqs WHERE buyer_id = $1 AND status = 'open' AND expires_at > NOW()"#,
+        buyer_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if open >= MAX_OPEN_RFQS {
+        return Err(AppError::BadRequest(format!("You can have at most {} open requests for quote", MAX_OPEN_RFQS)));
+    }
+
+    let rfq_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO rfqs (id, buyer_id, product_id, category_id, quantity, target_price, note, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        rfq_id,
+        buyer_id,
+        req.product_id,
+        category_id,
+        req.quantity,
+        req.target_price.as_ref().map(|price| price.round(2)),
+        req.note.as_deref().map(str::trim),
+        req.expires_at.unwrap_or_else(|| Utc::now() + Duration::days(DEFAULT_RFQ_DAYS))
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    // Suppliers on vacation or blocked either way aren't told
+    let suppliers = sqlx::query_scalar!(
+        r#"
+        SELECT u.id
+        FROM users u
+        WHERE u.is_supplier AND NOT u.store_paused AND u.id <> $2
+          AND NOT EXISTS(
+              SELECT 1 FROM user_blocks b
+              WHERE (b.blocker_id = u.id AND b.blocked_id = $2) OR (b.blocker_id = $2 AND b.blocked_id = u.id)
+          )
+          AND rfq_reaches($1, u.id)
+        "#,
+        rfq_id,
+        buyer_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    for supplier_id in &suppliers {
+        notify(
+            pool.get_ref(),
+            *supplier_id,
+            NotificationKind::Rfq,
+            "New request for quote",
+            json!({ "rfq_id": rfq_id }),
+        ).await?;
+    }
+
+    let rfq = find_rfq(pool.get_ref(), rfq_id).await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Request for quote posted",
+        "rfq": rfq,
+        "supplier_count": suppliers.len()
+    })))
+}
+
+/// The buyer's own RFQs, newest first
+pub async fn get_rfqs(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    query: web::Query<RfqQuery>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let rfqs = sqlx::query_as!(
+        Rfq,
+        r#"
+        SELECT r.id, r.buyer_id, u.name as buyer_name, r.product_id, p.name as "product_name?",
+               r.category_id, c.name as "category_name?", r.quantity, r.target_price, r.note,
+               r.status as "status: RfqStatus", r.expires_at, r.awarded_quote_id,
+               (SELECT COUNT(*) FROM quotes q WHERE q.rfq_id = r.id) as "quote_count!", r.created_at
+        FROM rfqs r
+        JOIN users u ON u.id = r.buyer_id
+        LEFT JOIN products p ON p.id = r.product_id
+        LEFT JOIN categories c ON c.id = r.category_id
+        WHERE r.buyer_id = $1 AND ($2::rfq_status IS NULL OR r.status = $2)
+        ORDER BY r.created_at DESC, r.id
+        LIMIT $3 OFFSET $4
+        "#,
+        buyer_id,
+        query.status as Option<RfqStatus>,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM rfqs
+        WHERE buyer_id = $1 AND ($2::rfq_status IS NULL OR status = $2)
+        "#,
+        buyer_id,
+        query.status as Option<RfqStatus>
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "rfqs": rfqs,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// Open RFQs the supplier can still quote on: ones covering a live product of theirs that
+/// they haven't quoted on yet, newest first
+pub async fn get_open_rfqs(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    query: web::Query<RfqQuery>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let is_supplier = sqlx::query_scalar!("SELECT is_supplier FROM users WHERE id = $1", seller_id)
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !is_supplier {
+        return Err(AppError::Forbidden);
+    }
+
+    let rfqs = sqlx::query_as!(
+        Rfq,
+        r#"
+        SELECT r.id, r.buyer_id, u.name as buyer_name, r.product_id, p.name as "product_name?",
+               r.category_id, c.name as "category_name?", r.quantity, r.target_price, r.note,
+               r.status as "status: RfqStatus", r.expires_at, r.awarded_quote_id,
+               (SELECT COUNT(*) FROM quotes q WHERE q.rfq_id = r.id) as "quote_count!", r.created_at
+        FROM rfqs r
+        JOIN users u ON u.id = r.buyer_id
+        LEFT JOIN products p ON p.id = r.product_id
+        LEFT JOIN categories c ON c.id = r.category_id
+        WHERE r.status = 'open' AND r.expires_at > NOW() AND r.buyer_id <> $1
+          AND NOT EXISTS(SELECT 1 FROM quotes q WHERE q.rfq_id = r.id AND q.seller_id = $1)
+          AND rfq_reaches(r.id, $1)
+        ORDER BY r.created_at DESC, r.id
+        LIMIT $2 OFFSET $3
+        "#,
+        seller_id,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM rfqs r
+        WHERE r.status = 'open' AND r.expires_at > NOW() AND r.buyer_id <> $1
+          AND NOT EXISTS(SELECT 1 FROM quotes q WHERE q.rfq_id = r.id AND q.seller_id = $1)
+          AND rfq_reaches(r.id, $1)
+        "#,
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "rfqs": rfqs,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+/// An RFQ with its quotes: all of them for its buyer, and a supplier's own for a supplier
+/// who can quote on it
+pub async fn get_rfq(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    rfq_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let rfq_id = rfq_id.into_inner();
+
+    let rfq = find_rfq(pool.get_ref(), rfq_id).await?;
+
+    let quotes_from = if rfq.buyer_id == user_id {
+        None
+    } else {
+        let visible = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM quotes WHERE rfq_id = $1 AND seller_id = $2) OR rfq_reaches($1, $2)
+                as "visible!"
+            "#,
+            rfq_id,
+            user_id
+        )
+            .fetch_one(pool.get_ref())
+            .await?;
+
+        if !visible {
+            return Err(AppError::NotFound("Request for quote not found".to_string()));
+        }
+        Some(user_id)
+    };
+
+    let quotes = find_quotes(pool.get_ref(), rfq_id, quotes_from).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "rfq": rfq,
+        "quotes": quotes
+    })))
+}
+
+/// Withdraw an open RFQ; its quotes' offers still pending are rejected
+pub async fn cancel_rfq(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    rfq_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let rfq_id = rfq_id.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let rfq = lock_own_rfq(&mut tx, rfq_id, buyer_id).await?;
+    if rfq.status != RfqStatus::Open {
+        return Err(AppError::Conflict("This request for quote is no longer open".to_string()));
+    }
+
+    sqlx::query!(
+        "UPDATE rfqs SET status = 'cancelled', updated_at = NOW() WHERE id = $1",
+        rfq_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    let withdrawn = reject_pending_quotes(&mut tx, rfq_id).await?;
+
+    tx.commit().await?;
+
+    for offer in &withdrawn {
+        announce_offer(pool.get_ref(), buyer_id, offer).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Request for quote cancelled",
+        "rfq": find_rfq(pool.get_ref(), rfq_id).await?
+    })))
+}
+
+/// Quote on an RFQ with one of the supplier's products it covers. The quote is sent to the
+/// buyer as an offer in their chat.
+pub async fn create_quote(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    rfq_id: web::Path<Uuid>,
+    req: web::Json<CreateQuoteRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    let rfq_id = rfq_id.into_inner();
+    req.validate()?;
+
+    let rfq = sqlx::query!(
+        r#"
+        SELECT buyer_id, quantity, status as "status: RfqStatus", expires_at,
+               EXISTS(SELECT 1 FROM quotes q WHERE q.rfq_id = rfqs.id AND q.seller_id = $2) as "quoted!",
+               rfq_reaches(id, $2) as "reaches!"
+        FROM rfqs
+        WHERE id = $1
+        "#,
+        rfq_id,
+        seller_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .filter(|rfq| rfq.reaches || rfq.quoted)
+        .ok_or_else(|| AppError::NotFound("Request for quote not found".to_string()))?;
+
+    ensure_open(rfq.status, rfq.expires_at)?;
+    if rfq.buyer_id == seller_id {
+        return Err(AppError::BadRequest("You can't quote on your own request".to_string()));
+    }
+    if rfq.quoted {
+        return Err(AppError::Conflict("You have already quoted on this request".to_string()));
+    }
+
+    let product = sqlx::query!(
+        r#"SELECT seller_id, rfq_covers($2, id) as "covered!" FROM products WHERE id = $1"#,
+        req.product_id,
+        rfq_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .filter(|product| product.seller_id == seller_id)
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+
+    if !product.covered {
+        return Err(AppError::BadRequest("This product isn't one the request covers".to_string()));
+    }
+
+    // Checks the product is on sale with the stock, and that neither side blocked the other
+    let offer = open_offer(pool.get_ref(), seller_id, &CreateOfferRequest {
+        product_id: req.product_id,
+        price_per_unit: req.price_per_unit.round(2),
+        quantity: req.quantity.unwrap_or(rfq.quantity),
+        recipient_id: Some(rfq.buyer_id),
+    }).await?;
+
+    let quote_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO quotes (id, rfq_id, seller_id, product_id, offer_id, note)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        quote_id,
+        rfq_id,
+        seller_id,
+        req.product_id,
+        offer.id,
+        req.note.as_deref().map(str::trim)
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    let quote = find_quotes(pool.get_ref(), rfq_id, Some(seller_id))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::InternalError)?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Quote sent",
+        "quote": quote
+    })))
+}
+
+/// Accept a quote and order it, shipped to one of the buyer's addresses. The RFQ is awarded
+/// and the other quotes' pending offers are rejected.
+pub async fn accept_quote(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<AcceptQuoteRequest>,
+) -> AppResult<HttpResponse> {
+    let buyer_id = get_user_id(&identity)?;
+    let (rfq_id, quote_id) = path.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let rfq = lock_own_rfq(&mut tx, rfq_id, buyer_id).await?;
+    ensure_open(rfq.status, rfq.expires_at)?;
+
+    let shipping_address = shipping_snapshot(pool.get_ref(), buyer_id, req.address_id).await?;
+
+    let offer_id = sqlx::query_scalar!(
+        "SELECT offer_id FROM quotes WHERE id = $1 AND rfq_id = $2",
+        quote_id,
+        rfq_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Quote not found".to_string()))?;
+
+    // Accepting the quote accepts its offer; one the buyer already accepted in the chat
+    // is ordered as it is
+    let accepted = sqlx::query!(
+        "UPDATE offers SET status = 'accepted', updated_at = NOW() WHERE id = $1 AND status IN ('pending', 'accepted')",
+        offer_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    if accepted.rows_affected() == 0 {
+        return Err(AppError::BadRequest(
+            "This quote's offer is no longer open; continue with its counter-offer in the chat".to_string(),
+        ));
+    }
+
+    let ordered = order_offer(&mut tx, buyer_id, offer_id, &shipping_address).await?;
+
+    sqlx::query!(
+        "UPDATE rfqs SET status = 'awarded', awarded_quote_id = $2, updated_at = NOW() WHERE id = $1",
+        rfq_id,
+        quote_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    let withdrawn = reject_pending_quotes(&mut tx, rfq_id).await?;
+
+    tx.commit().await?;
+
+    announce_ordered_offer(pool.get_ref(), buyer_id, &ordered).await?;
+    for offer in &withdrawn {
+        announce_offer(pool.get_ref(), buyer_id, offer).await?;
+    }
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Quote accepted and ordered",
+        "order_id": ordered.order_id,
+        "offer": ordered.offer
+    })))
+}
+
+async fn find_rfq(pool: &PgPool, rfq_id: Uuid) -> AppResult<Rfq> {
+    sqlx::query_as!(
+        Rfq,
+        r#"
+        SELECT r.id, r.buyer_id, u.name as buyer_name, r.product_id, p.name as "product_name?",
+               r.category_id, c.name as "category_name?", r.quantity, r.target_price, r.note,
+               r.status as "status: RfqStatus", r.expires_at, r.awarded_quote_id,
+               (SELECT COUNT(*) FROM quotes q WHERE q.rfq_id = r.id) as "quote_count!", r.created_at
+        FROM rfqs r
+        JOIN users u ON u.id = r.buyer_id
+        LEFT JOIN products p ON p.id = r.product_id
+        LEFT JOIN categories c ON c.id = r.category_id
+        WHERE r.id = $1
+        "#,
+        rfq_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Request for quote not found".to_string()))
+}
+
+/// The RFQ's quotes, cheapest first, or only the one from `seller_id`
+async fn find_quotes(pool: &PgPool, rfq_id: Uuid, seller_id: Option<Uuid>) -> AppResult<Vec<Quote>> {
+    let quotes = sqlx::query_as!(
+        Quote,
+        r#"
+        SELECT q.id, q.rfq_id, q.seller_id, s.name as seller_name, q.product_id, p.name as product_name,
+               q.offer_id, o.price_per_unit, o.quantity, p.currency, o.status as "offer_status: OfferStatus",
+               q.note, q.created_at
+        FROM quotes q
+        JOIN offers o ON o.id = q.offer_id
+        JOIN products p ON p.id = q.product_id
+        JOIN users s ON s.id = q.seller_id
+        WHERE q.rfq_id = $1 AND ($2::uuid IS NULL OR q.seller_id = $2)
+        ORDER BY o.price_per_unit, q.created_at
+        "#,
+        rfq_id,
+        seller_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(quotes)
+}
+
+struct LockedRfq {
+    status: RfqStatus,
+    expires_at: DateTime<Utc>,
+}
+
+/// Lock the buyer's RFQ for the rest of the transaction
+async fn lock_own_rfq(conn: &mut PgConnection, rfq_id: Uuid, buyer_id: Uuid) -> AppResult<LockedRfq> {
+    sqlx::query_as!(
+        LockedRfq,
+        r#"
+        SELECT status as "status: RfqStatus", expires_at
+        FROM rfqs
+        WHERE id = $1 AND buyer_id = $2
+        FOR UPDATE
+        "#,
+        rfq_id,
+        buyer_id
+    )
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Request for quote not found".to_string()))
+}
+
+fn ensure_open(status: RfqStatus, expires_at: DateTime<Utc>) -> AppResult<()> {
+    if status != RfqStatus::Open {
+        return Err(AppError::Conflict("This request for quote is no longer open".to_string()));
+    }
+    if expires_at <= Utc::now() {
+        return Err(AppError::Conflict("This request for quote has expired".to_string()));
+    }
+    Ok(())
+}
+
+/// Reject the offers of the RFQ's quotes that are still pending, returning them to announce
+async fn reject_pending_quotes(conn: &mut PgConnection, rfq_id: Uuid) -> AppResult<Vec<Offer>> {
+    let offers = sqlx::query_as!(
+        Offer,
+        r#"
+        UPDATE offers SET status = 'rejected', updated_at = NOW()
+        WHERE status = 'pending' AND id IN (SELECT offer_id FROM quotes WHERE rfq_id = $1)
+        RETURNING id, product_id, buyer_id, seller_id, proposed_by, price_per_unit,
+                  quantity, status as "status: OfferStatus", parent_offer_id, order_id,
+                  created_at, updated_at
+        "#,
+        rfq_id
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(offers)
+}
@@ -0,0 +1,169 @@
+// handlers/search_handlers.rs
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::errors::AppResult;
+use crate::models::SearchQuery;
+
+const SEARCH_DEFAULT_LIMIT: i64 = 5;
+const SEARCH_MAX_LIMIT: i64 = 20;
+
+pub async fn search(
+    pool: web::Data<PgPool>,
+    query: web::Query<SearchQuery>,
+) -> AppResult<HttpResponse> {
+    let q = query.q.as_deref().map(str::trim).unwrap_or_default();
+    let limit = query.limit.unwrap_or(SEARCH_DEFAULT_LIMIT).clamp(1, SEARCH_MAX_LIMIT);
+
+    if q.is_empty() {
+        return Ok(HttpResponse::Ok().json(json!({
+            "query": q,
+            "products": { "total": 0, "results": [] },
+            "sellers": { "total": 0, "results": [] },
+            "categories": { "total": 0, "results": [] }
+        })));
+    }
+
+    // Only what the public product listing would show
+    let products = sqlx::query!(
+        r#"
+        SELECT p.id, p.name, p.price_per_unit, p.currency, p.image_url, p.seller_id,
+               u.name as seller_name, p.category_id, c.name as category_name,
+               COUNT(*) OVER () as "total!"
+        FROM products p
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        CROSS JOIN LATERAL (
+            SELECT p.search_vector @@ websearch_to_tsquery('english', $1) as is_match
+        ) m
+        WHERE (m.is_match OR $1 <% p.name)
+          AND p.stock_qty > 0
+          AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+          AND NOT u.store_paused
+        ORDER BY m.is_match DESC,
+                 ts_rank(p.search_vector, websearch_to_tsquery('english', $1)) DESC,
+                 word_similarity($1, p.name) DESC,
+                 p.created_at DESC
+        LIMIT $2
+        "#,
+        q,
+        limit
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    // Sellers with a storefront, whether or not anything of theirs matched
+    let sellers = sqlx::query!(
+        r#"
+        SELECT u.id, u.name, u.profile_image_url, u.rating, u.total_deliveries, u.store_paused,
+               (
+                   SELECT COUNT(*) FROM products p
+                   WHERE p.seller_id = u.id AND p.stock_qty > 0
+                     AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+                     AND p.review_status = 'approved'
+               ) as "product_count!",
+               COUNT(*) OVER () as "total!"
+        FROM users u
+        CROSS JOIN LATERAL (
+            SELECT u.search_vector @@ websearch_to_tsquery('simple', $1) as is_match
+        ) m
+        WHERE (m.is_match OR $1 <% u.name)
+          AND u.is_supplier AND u.suspended_at IS NULL AND u.deleted_at IS NULL
+        ORDER BY m.is_match DESC,
+                 starts_with(LOWER(u.name), LOWER($1)) DESC,
+                 word_similarity($1, u.name) DESC,
+                 u.total_deliveries DESC
+        LIMIT $2
+        "#,
+        q,
+        limit
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let categories = sqlx::query!(
+        r#"
+        SELECT c.id, c.name, c.parent_id,
+               (
+                   SELECT COUNT(*) FROM products p
+                   JOIN users u ON p.seller_id = u.id
+                   WHERE p.category_id IN (SELECT category_subtree(c.id))
+                     AND p.stock_qty > 0
+                     AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+                     AND p.review_status = 'approved'
+                     AND NOT u.store_paused
+               ) as "product_count!",
+               COUNT(*) OVER () as "total!"
+        FROM categories c
+        CROSS JOIN LATERAL (
+            SELECT c.search_vector @@ websearch_to_tsquery('english', $1) as is_match
+        ) m
+        WHERE m.is_match OR $1 <% c.name
+        ORDER BY m.is_match DESC,
+                 starts_with(LOWER(c.name), LOWER($1)) DESC,
+                 word_similarity($1, c.name) DESC,
+                 c.name
+        LIMIT $2
+        "#,
+        q,
+        limit
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let product_list = products.iter().map(|product| {
+        json!({
+            "type": "product",
+            "id": product.id,
+            "name": product.name,
+            "price_per_unit": product.price_per_unit,
+            "currency": product.currency,
+            "image_url": product.image_url,
+            "seller_id": product.seller_id,
+            "seller_name": product.seller_name,
+            "category_id": product.category_id,
+            "category_name": product.category_name
+        })
+    }).collect::<Vec<_>>();
+
+    let seller_list = sellers.iter().map(|seller| {
+        json!({
+            "type": "seller",
+            "id": seller.id,
+            "name": seller.name,
+            "profile_image_url": seller.profile_image_url,
+            "rating": seller.rating,
+            "total_deliveries": seller.total_deliveries,
+            "store_paused": seller.store_paused,
+            "product_count": seller.product_count
+        })
+    }).collect::<Vec<_>>();
+
+    let category_list = categories.iter().map(|category| {
+        json!({
+            "type": "category",
+            "id": category.id,
+            "name": category.name,
+            "parent_id": category.parent_id,
+            "product_count": category.product_count
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "query": q,
+        "products": {
+            "total": products.first().map_or(0, |product| product.total),
+            "results": product_list
+        },
+        "sellers": {
+            "total": sellers.first().map_or(0, |seller| seller.total),
+            "results": seller_list
+        },
+        "categories": {
+            "total": categories.first().map_or(0, |category| category.total),
+            "results": category_list
+        }
+    })))
+}
@@ -0,0 +1,160 @@
+// handlers/seller_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{PaginationQuery, ProductReviewStatus, ProductUnit, ProductWithSeller};
+use crate::utils::{get_viewer_id, Pagination};
+
+/// Public storefront: seller profile plus the first page of their active products
+pub async fn get_seller(
+    identity: Option<Identity>,
+    pool: web::Data<PgPool>,
+    seller_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let viewer_id = get_viewer_id(identity.as_ref())?;
+    let seller_id = seller_id.into_inner();
+
+    // Suspended or non-supplier accounts have no storefront
+    let seller = sqlx::query!(
+        r#"
+        SELECT id, name, rating, total_deliveries, profile_image_url, min_order_value, delivery_fee, store_paused,
+               lead_time_hours, created_at,
+               (
+                   SELECT ROUND(100.0 * COUNT(*) FILTER (WHERE o.delivered_at <= o.expected_delivery_at)
+                                / NULLIF(COUNT(*), 0), 1)::float8
+                   FROM orders o
+                   WHERE o.seller_id = users.id AND o.delivered_at IS NOT NULL AND o.expected_delivery_at IS NOT NULL
+               ) as on_time_delivery_percent
+        FROM users
+        WHERE id = $1 AND is_supplier = TRUE AND suspended_at IS NULL AND deleted_at IS NULL
+        "#,
+        seller_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Seller not found".to_string()))?;
+
+    let pagination = Pagination::new(None, None);
+    let (products, total_count) = fetch_active_products(pool.get_ref(), seller_id, viewer_id, pagination).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "seller": {
+            "id": seller.id,
+            "name": seller.name,
+            "rating": seller.rating,
+            "total_deliveries": seller.total_deliveries,
+            "on_time_delivery_percent": seller.on_time_delivery_percent,
+            "lead_time_hours": seller.lead_time_hours,
+            "profile_image_url": seller.profile_image_url,
+            "min_order_value": seller.min_order_value,
+            "delivery_fee": seller.delivery_fee,
+            "store_paused": seller.store_paused,
+            "member_since": seller.created_at,
+            "product_count": total_count
+        },
+        "products": products,
+        "pagination": pagination.to_json(total_count)
+    })))
+}
+
+pub async fn get_seller_products(
+    identity: Option<Identity>,
+    pool: web::Data<PgPool>,
+    seller_id: web::Path<Uuid>,
+    query: web::Query<PaginationQuery>,
+) -> AppResult<HttpResponse> {
+    let viewer_id = get_viewer_id(identity.as_ref())?;
+    let seller_id = seller_id.into_inner();
+    let pagination = Pagination::new(query.page, query.limit);
+
+    let seller_exists = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM users WHERE id = $1 AND is_supplier = TRUE AND suspended_at IS NULL AND deleted_at IS NULL
+        ) as "exists!"
+        "#,
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !seller_exists {
+        return Err(AppError::NotFound("Seller not found".to_string()));
+    }
+
+    let (products, total_count) = fetch_active_products(pool.get_ref(), seller_id, viewer_id, pagination).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "products": products,
+        "pagination": pagination.to_json(total_count)
+    })))
+}
+
+/// In-stock, visible products of one seller, newest first, with the total for pagination
+async fn fetch_active_products(
+    pool: &PgPool,
+    seller_id: Uuid,
+    viewer_id: Option<Uuid>,
+    pagination: Pagination,
+) -> AppResult<(Vec<ProductWithSeller>, i64)> {
+    let products = sqlx::query_as!(
+        ProductWithSeller,
+        r#"
+        SELECT
+            p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+            p.unit as "unit: ProductUnit", p.min_increment, p.sku, p.barcode,
+            p.image_url, p.seller_id, p.category_id, p.created_at,
+            c.name as category_name,
+            u.name as seller_name, u.name as seller_company,
+            u.rating as seller_rating, u.total_deliveries as seller_deliveries,
+            u.min_order_value as seller_min_order_value, u.delivery_fee as seller_delivery_fee,
+            COALESCE(
+                (SELECT json_agg(json_build_object('id', pi.id, 'url', pi.url) ORDER BY pi.position)
+                 FROM product_images pi WHERE pi.product_id = p.id),
+                '[]'::json
+            ) as "images!",
+            COALESCE(
+                (SELECT json_agg(json_build_object('min_qty', t.min_qty, 'unit_price', t.unit_price::text) ORDER BY t.min_qty)
+                 FROM price_tiers t WHERE t.product_id = p.id),
+                '[]'::json
+            ) as "price_tiers!",
+            EXISTS(SELECT 1 FROM favorites f WHERE f.product_id = p.id AND f.user_id = $4) as "is_favorited!",
+            NULL::float8 as distance_km,
+            (SELECT COUNT(*) FROM stock_by_location s WHERE s.product_id = p.id AND s.quantity > 0) as "stock_locations!",
+            current_deal(p.id) as deal,
+            NULL::numeric as display_price, NULL::text as display_currency,
+            p.review_status as "review_status: ProductReviewStatus"
+        FROM products p
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE p.seller_id = $1 AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+        ORDER BY p.created_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        seller_id,
+        pagination.limit,
+        pagination.offset,
+        viewer_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let total_count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM products
+        WHERE seller_id = $1 AND stock_qty > 0 AND taken_down_at IS NULL AND deleted_at IS NULL
+          AND review_status = 'approved'
+        "#,
+        seller_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok((products, total_count))
+}
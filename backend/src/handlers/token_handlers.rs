@@ -0,0 +1,170 @@
+// handlers/token_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::{hash_api_token, API_TOKEN_SCOPES};
+use crate::errors::{AppError, AppResult};
+use crate::models::CreateApiTokenRequest;
+use crate::utils::{generate_random_string, get_user_id};
+
+const TOKEN_PREFIX: &str = "ss_";
+const TOKEN_RANDOM_LENGTH: usize = 40;
+// Enough of the token to recognise it in the list, far too little to use it
+const TOKEN_DISPLAY_LENGTH: usize = 10;
+const MAX_TOKENS_PER_USER: i64 = 20;
+const MAX_TOKEN_DAYS: i64 = 365;
+
+/// The user's tokens, newest first; the tokens themselves are never shown again
+pub async fn get_tokens(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let tokens = sqlx::query!(
+        r#"
+        SELECT id, name, token_prefix, scopes, expires_at, last_used_at, created_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let tokens = tokens.iter().map(|token| {
+        json!({
+            "id": token.id,
+            "name": token.name,
+            "token_prefix": token.token_prefix,
+            "scopes": token.scopes,
+            "expires_at": token.expires_at,
+            "last_used_at": token.last_used_at,
+            "created_at": token.created_at
+        })
+    }).collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "tokens": tokens
+    })))
+}
+
+/// Issue a token with the given scopes. The token is only returned in this response.
+pub async fn create_token(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<CreateApiTokenRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::BadRequest("Token name must be 1-100 characters".to_string()));
+    }
+
+    let mut scopes = req.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(AppError::BadRequest("A token needs at least one scope".to_string()));
+    }
+    if let Some(unknown) = scopes.iter().find(|scope| !API_TOKEN_SCOPES.contains(&scope.as_str())) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown scope {}; scopes are {}",
+            unknown,
+            API_TOKEN_SCOPES.join(", ")
+        )));
+    }
+
+    if req.expires_in_days.is_some_and(|days| !(1..=MAX_TOKEN_DAYS).contains(&days)) {
+        return Err(AppError::BadRequest(format!(
+            "expires_in_days must be 1-{}",
+            MAX_TOKEN_DAYS
+        )));
+    }
+    let expires_at = req.expires_in_days.map(|days| Utc::now() + Duration::days(days));
+
+    let token_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM api_tokens WHERE user_id = $1"#,
+        user_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if token_count >= MAX_TOKENS_PER_USER {
+        return Err(AppError::Conflict(format!(
+            "You can have at most {} tokens; revoke one first",
+            MAX_TOKENS_PER_USER
+        )));
+    }
+
+    let token = format!("{}{}", TOKEN_PREFIX, generate_random_string(TOKEN_RANDOM_LENGTH).to_lowercase());
+    let token_prefix = &token[..TOKEN_DISPLAY_LENGTH];
+
+    let api_token = sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (id, user_id, name, token_hash, token_prefix, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, created_at
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash_api_token(&token),
+        token_prefix,
+        &scopes,
+        expires_at
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    tracing::info!(%user_id, token_id = %api_token.id, ?scopes, "API token created");
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Token created; copy it now, it won't be shown again",
+        "token": token,
+        "api_token": {
+            "id": api_token.id,
+            "name": name,
+            "token_prefix": token_prefix,
+            "scopes": scopes,
+            "expires_at": expires_at,
+            "last_used_at": null,
+            "created_at": api_token.created_at
+        }
+    })))
+}
+
+/// Revoke a token; requests using it fail from now on
+pub async fn delete_token(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    token_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    let token_id = token_id.into_inner();
+
+    let deleted = sqlx::query!(
+        "DELETE FROM api_tokens WHERE id = $1 AND user_id = $2",
+        token_id,
+        user_id
+    )
+        .execute(pool.get_ref())
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(AppError::NotFound("Token not found".to_string()));
+    }
+
+    tracing::info!(%user_id, %token_id, "API token revoked");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Token revoked"
+    })))
+}
@@ -0,0 +1,243 @@
+// handlers/two_factor_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::handlers::user_handlers::check_password;
+use crate::models::{AuditAction, DisableTwoFactorRequest, TwoFactorCodeRequest};
+use crate::repositories::audit_repository::{self, AuditEntry};
+use crate::totp;
+use crate::utils::{generate_random_string, get_user_id};
+
+const TOTP_ISSUER: &str = "StreetSource";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Start (or restart) setup: a fresh secret and the URI to show as a QR code
+pub async fn setup_two_factor(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    if two_factor_enabled(pool.get_ref(), user_id).await? {
+        return Err(AppError::Conflict("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let email = sqlx::query_scalar!("SELECT email FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let secret = totp::generate_secret();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW()
+        WHERE user_totp.enabled_at IS NULL
+        "#,
+        user_id,
+        secret
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Scan the code with your authenticator app, then confirm with a code from it",
+        "secret": secret,
+        "provisioning_uri": totp::provisioning_uri(TOTP_ISSUER, &email, &secret)
+    })))
+}
+
+/// Confirm setup with a current code. The recovery codes are only ever shown here.
+pub async fn enable_two_factor(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<TwoFactorCodeRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let mut tx = pool.begin().await?;
+
+    let pending = sqlx::query!(
+        "SELECT secret, enabled_at FROM user_totp WHERE user_id = $1 FOR UPDATE",
+        user_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Start two-factor setup first".to_string()))?;
+
+    if pending.enabled_at.is_some() {
+        return Err(AppError::Conflict("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let step = totp::verify(&pending.secret, &req.code, chrono::Utc::now().timestamp(), None)
+        .ok_or(AppError::InvalidOtp)?;
+
+    sqlx::query!(
+        "UPDATE user_totp SET enabled_at = NOW(), last_used_step = $2 WHERE user_id = $1",
+        user_id,
+        step
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    let recovery_codes = replace_recovery_codes(&mut tx, user_id).await?;
+
+    audit_repository::record(&mut *tx, AuditEntry {
+        actor_id: Some(user_id),
+        action: AuditAction::TwoFactorEnabled,
+        subject_id: Some(user_id),
+        details: json!({}),
+    }).await?;
+
+    tx.commit().await?;
+
+    tracing::info!(%user_id, "Two-factor authentication enabled");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Two-factor authentication enabled. Store the recovery codes somewhere safe",
+        "recovery_codes": recovery_codes
+    })))
+}
+
+/// Turn two-factor authentication off; needs the account password
+pub async fn disable_two_factor(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    req: web::Json<DisableTwoFactorRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    check_password(pool.get_ref(), user_id, &req.password).await?;
+
+    let mut tx = pool.begin().await?;
+
+    let removed = sqlx::query!("DELETE FROM user_totp WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    sqlx::query!("DELETE FROM totp_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if removed > 0 {
+        audit_repository::record(&mut *tx, AuditEntry {
+            actor_id: Some(user_id),
+            action: AuditAction::TwoFactorDisabled,
+            subject_id: Some(user_id),
+            details: json!({}),
+        }).await?;
+    }
+
+    tx.commit().await?;
+
+    if removed > 0 {
+        tracing::info!(%user_id, "Two-factor authentication disabled");
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Two-factor authentication disabled"
+    })))
+}
+
+/// Whether the user has confirmed a two-factor setup
+pub async fn two_factor_enabled(pool: &PgPool, user_id: Uuid) -> AppResult<bool> {
+    let enabled = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL) as "exists!""#,
+        user_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok(enabled)
+}
+
+/// Accept a current authenticator code or an unused recovery code, using it up.
+/// Locks the user's TOTP row, so call it inside the caller's transaction.
+pub async fn verify_second_factor(conn: &mut PgConnection, user_id: Uuid, code: &str) -> AppResult<bool> {
+    let Some(totp_row) = sqlx::query!(
+        "SELECT secret, last_used_step FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL FOR UPDATE",
+        user_id
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if let Some(step) = totp::verify(&totp_row.secret, code, now, totp_row.last_used_step) {
+        sqlx::query!(
+            "UPDATE user_totp SET last_used_step = $2 WHERE user_id = $1",
+            user_id,
+            step
+        )
+            .execute(&mut *conn)
+            .await?;
+        return Ok(true);
+    }
+
+    let used = sqlx::query!(
+        r#"
+        UPDATE totp_recovery_codes SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        recovery_code_hash(code)
+    )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    if used > 0 {
+        tracing::info!(%user_id, "Recovery code used");
+    }
+    Ok(used > 0)
+}
+
+/// Issue a new set of recovery codes, invalidating the old ones; returns them in clear
+async fn replace_recovery_codes(conn: &mut PgConnection, user_id: Uuid) -> AppResult<Vec<String>> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = generate_random_string(10);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect();
+    let ids: Vec<Uuid> = codes.iter().map(|_| Uuid::new_v4()).collect();
+    let hashes: Vec<String> = codes.iter().map(|code| recovery_code_hash(code)).collect();
+
+    sqlx::query!("DELETE FROM totp_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO totp_recovery_codes (id, user_id, code_hash)
+        SELECT code.id, $1, code.hash
+        FROM UNNEST($2::uuid[], $3::text[]) AS code(id, hash)
+        "#,
+        user_id,
+        &ids,
+        &hashes
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(codes)
+}
+
+/// Recovery codes are random enough that a plain SHA-256 is a safe way to store them.
+/// Case, spaces and dashes are ignored.
+fn recovery_code_hash(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
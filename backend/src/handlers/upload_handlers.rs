@@ -1,122 +1,255 @@
 // handlers/upload_handlers.rs
 use actix_identity::Identity;
 use actix_multipart::Multipart;
-use actix_web::HttpResponse;
-use aws_config::BehaviorVersion;
-use aws_sdk_s3::Client as S3Client;
+use actix_web::{web, HttpRequest, HttpResponse};
 use bytes::BytesMut;
 use futures_util::TryStreamExt;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, ImageResult, Limits};
 use nanoid::nanoid;
 use serde_json::json;
-use std::env;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
+use crate::config::Config;
 use crate::errors::{AppError, AppResult};
+use crate::models::ImageUrls;
+use crate::repositories::message_repository;
+use crate::storage::Storage;
 use crate::utils::get_user_id;
 
-const MAX_FILE_SIZE: usize = 5 * 1024 * 1024; // 5 MB
 const ALLOWED_IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+/// Enough of the file to tell its format from
+const SNIFF_LENGTH: usize = 16;
+/// Room in the request for the form's boundaries, headers and text fields
+const FORM_OVERHEAD: usize = 64 * 1024;
+/// Text fields (`conv_id`) are ids, never more than this
+const MAX_TEXT_FIELD_LENGTH: usize = 1024;
+/// Images are decoded whole, so their pixels are capped as well as their bytes: a small
+/// file can decompress to gigabytes
+const MAX_IMAGE_EDGE: u32 = 8192;
 
 pub async fn upload_profile_image(
     identity: Identity,
+    config: web::Data<Config>,
+    storage: web::Data<dyn Storage>,
+    request: HttpRequest,
     mut payload: Multipart,
 ) -> AppResult<HttpResponse> {
     let user_id = get_user_id(&identity)?;
 
-    // Process multipart upload
-    let mut file_data = BytesMut::new();
-    let mut filename = String::new();
+    let (image, _) = receive_image(&request, &mut payload, config.max_file_size_mb).await?;
+    let image = image.ok_or_else(|| AppError::BadRequest("No file uploaded".to_string()))?;
 
-    while let Some(mut field) = payload.try_next().await.expect("Failed to read multipart field") {
-        let content_disposition = field.content_disposition().expect("Missing content disposition in multipart field");
+    // Resize and upload all variants under one unique prefix
+    let key_prefix = format!("profile-images/{}-{}", user_id, nanoid!(10));
+    let urls = upload_image_variants(storage.get_ref(), &key_prefix, image).await?;
 
-        if let Some(name) = content_disposition.get_name() {
-            if name == "file" {
-                if let Some(fname) = content_disposition.get_filename() {
-                    filename = fname.to_string();
-                }
-
-                // Read file data
-                while let Some(chunk) = field.try_next().await.expect("Failed to read file chunk from multipart field") {
-                    if file_data.len() + chunk.len() > MAX_FILE_SIZE {
-                        return Err(AppError::BadRequest("File size exceeds 5MB limit".to_string()));
-                    }
-                    file_data.extend_from_slice(&chunk);
-                }
-            }
-        }
-    }
-
-    if file_data.is_empty() {
-        return Err(AppError::BadRequest("No file uploaded".to_string()));
-    }
-
-    // Validate file extension
-    let extension = filename
-        .split('.')
-        .last()
-        .unwrap_or("")
-        .to_lowercase();
-
-    if !ALLOWED_IMAGE_EXTENSIONS.contains(&extension.as_str()) {
-        return Err(AppError::BadRequest("Invalid file type. Only JPG, PNG, and WebP are allowed".to_string()));
-    }
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Profile image uploaded successfully",
+        "image_url": urls.full,
+        "images": urls
+    })))
+}
 
-    // Validate it's actually an image
-    if image::guess_format(&file_data).is_err() {
-        return Err(AppError::BadRequest("Invalid image file".to_string()));
-    }
+pub async fn upload_product_image(
+    identity: Identity,
+    config: web::Data<Config>,
+    storage: web::Data<dyn Storage>,
+    request: HttpRequest,
+    mut payload: Multipart,
+) -> AppResult<HttpResponse> {
+    get_user_id(&identity)?;
 
-    // Generate unique filename
-    let unique_filename = format!("profile-images/{}-{}.{}", user_id, nanoid!(10), extension);
+    let (image, _) = receive_image(&request, &mut payload, config.max_file_size_mb).await?;
+    let image = image.ok_or_else(|| AppError::BadRequest("No file uploaded".to_string()))?;
 
-    // Upload to S3
-    let s3_url = upload_to_s3(&unique_filename, file_data.freeze().to_vec(), &format!("image/{}", extension)).await.expect("Failed to upload profile image to S3");
+    // Resize and upload all variants under one unique prefix
+    let key_prefix = format!("product-images/{}-{}", Uuid::new_v4(), nanoid!(10));
+    let urls = upload_image_variants(storage.get_ref(), &key_prefix, image).await?;
 
     Ok(HttpResponse::Ok().json(json!({
-        "message": "Profile image uploaded successfully",
-        "image_url": s3_url
+        "message": "Product image uploaded successfully",
+        "image_url": urls.full,
+        "images": urls
     })))
 }
 
-pub async fn upload_product_image(
+/// Upload an image to attach to a chat message. The multipart form carries the image as
+/// `file` and the conversation as `conv_id`; only its participants can upload to it.
+pub async fn upload_message_attachment(
     identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    storage: web::Data<dyn Storage>,
+    request: HttpRequest,
     mut payload: Multipart,
 ) -> AppResult<HttpResponse> {
     let user_id = get_user_id(&identity)?;
 
-    // Process multipart upload
-    let mut file_data = BytesMut::new();
-    let mut filename = String::new();
+    let (image, fields) = receive_image(&request, &mut payload, config.max_file_size_mb).await?;
+
+    let conv_id = fields.get("conv_id").map_or("", |conv_id| conv_id.trim());
+    let conv_id = Uuid::parse_str(conv_id)
+        .map_err(|_| AppError::BadRequest("conv_id must be a conversation id".to_string()))?;
 
-    while let Some(mut field) = payload.try_next().await.expect("Failed to read multipart field") {
-        let content_disposition = field.content_disposition().expect("Missing content disposition in multipart field");
+    if !message_repository::is_participant(pool.get_ref(), conv_id, user_id).await? {
+        return Err(AppError::Forbidden);
+    }
+
+    let image = image.ok_or_else(|| AppError::BadRequest("No file uploaded".to_string()))?;
+
+    let key_prefix = format!("message-attachments/{}/{}", conv_id, nanoid!(10));
+    let urls = upload_image_variants(storage.get_ref(), &key_prefix, image).await?;
+
+    // Recorded so a message can only attach what was uploaded to its conversation
+    sqlx::query!(
+        r#"
+        INSERT INTO message_attachments (id, conv_id, uploader_id, url, attachment_type)
+        VALUES ($1, $2, $3, $4, 'image')
+        "#,
+        Uuid::new_v4(),
+        conv_id,
+        user_id,
+        urls.full
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Attachment uploaded successfully",
+        "attachment_url": urls.full,
+        "attachment_type": "image",
+        "images": urls
+    })))
+}
+
+pub fn malformed_upload(e: actix_multipart::MultipartError) -> AppError {
+    AppError::BadRequest(format!("Malformed multipart upload: {}", e))
+}
 
-        if let Some(name) = content_disposition.get_name() {
-            if name == "file" {
-                if let Some(fname) = content_disposition.get_filename() {
-                    filename = fname.to_string();
+/// A file in the temp directory, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new() -> Self {
+        TempFile(std::env::temp_dir().join(format!("streetsource-upload-{}", nanoid!())))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// An uploaded image, on disk rather than in memory
+struct ReceivedImage {
+    file: TempFile,
+    format: ImageFormat,
+}
+
+/// The form's `file`, if it has one, and its other fields, which are short text. The
+/// file is written to disk as it arrives, so memory use doesn't grow with its size; its
+/// type is checked from its first bytes and its size as it comes in, so a wrong or
+/// oversized file is turned away without reading the rest.
+async fn receive_image(
+    request: &HttpRequest,
+    payload: &mut Multipart,
+    max_file_size_mb: usize,
+) -> AppResult<(Option<ReceivedImage>, HashMap<String, String>)> {
+    let max_bytes = max_file_size_mb * 1024 * 1024;
+    let too_large = || AppError::PayloadTooLarge(format!("File size exceeds {}MB limit", max_file_size_mb));
+
+    // A request that says up front it's too big isn't read at all
+    let content_length = request
+        .headers()
+        .get("Content-Length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > max_bytes + FORM_OVERHEAD) {
+        return Err(too_large());
+    }
+
+    let mut image = None;
+    let mut fields = HashMap::new();
+
+    while let Some(mut field) = payload.try_next().await.map_err(malformed_upload)? {
+        let content_disposition = field
+            .content_disposition()
+            .ok_or_else(|| AppError::BadRequest("Missing content disposition in multipart field".to_string()))?;
+        let name = content_disposition.get_name().unwrap_or_default().to_string();
+        let filename = content_disposition.get_filename().unwrap_or_default().to_string();
+
+        if name != "file" {
+            let mut value = BytesMut::new();
+            while let Some(chunk) = field.try_next().await.map_err(malformed_upload)? {
+                if value.len() + chunk.len() > MAX_TEXT_FIELD_LENGTH {
+                    return Err(AppError::BadRequest(format!("The {} field is too long", name)));
                 }
+                value.extend_from_slice(&chunk);
+            }
+            fields.insert(name, String::from_utf8_lossy(&value).into_owned());
+            continue;
+        }
+
+        let file = TempFile::new();
+        let mut output = tokio::fs::File::create(file.path()).await.map_err(spool_error)?;
+        // The first bytes are held back until the format is known
+        let mut head = BytesMut::new();
+        let mut format = None;
+        let mut size = 0;
+
+        while let Some(chunk) = field.try_next().await.map_err(malformed_upload)? {
+            size += chunk.len();
+            if size > max_bytes {
+                return Err(too_large());
+            }
 
-                // Read file data
-                while let Some(chunk) = field.try_next().await.expect("Failed to read file chunk from multipart field") {
-                    if file_data.len() + chunk.len() > MAX_FILE_SIZE {
-                        return Err(AppError::BadRequest("File size exceeds 10MB limit".to_string()));
-                    }
-                    file_data.extend_from_slice(&chunk);
+            if format.is_some() {
+                output.write_all(&chunk).await.map_err(spool_error)?;
+            } else {
+                head.extend_from_slice(&chunk);
+                if head.len() >= SNIFF_LENGTH {
+                    format = Some(sniff_image_format(&filename, &head)?);
+                    output.write_all(&head).await.map_err(spool_error)?;
                 }
             }
         }
-    }
 
-    if file_data.is_empty() {
-        return Err(AppError::BadRequest("No file uploaded".to_string()));
+        // A file shorter than the sniffing window
+        if format.is_none() && !head.is_empty() {
+            format = Some(sniff_image_format(&filename, &head)?);
+            output.write_all(&head).await.map_err(spool_error)?;
+        }
+        output.flush().await.map_err(spool_error)?;
+
+        image = format.map(|format| ReceivedImage { file, format });
     }
 
-    // Validate file extension
+    Ok((image, fields))
+}
+
+fn spool_error(e: std::io::Error) -> AppError {
+    tracing::error!(error = %e, "Failed to write upload to a temporary file");
+    AppError::InternalError
+}
+
+/// The image's real format, sniffed from its bytes. The extension must be one we accept
+/// and agree with the content, so a PNG can't be stored as photo.jpg.
+fn sniff_image_format(filename: &str, data: &[u8]) -> AppResult<ImageFormat> {
     let extension = filename
         .split('.')
-        .last()
+        .next_back()
         .unwrap_or("")
         .to_lowercase();
 
@@ -124,49 +257,118 @@ pub async fn upload_product_image(
         return Err(AppError::BadRequest("Invalid file type. Only JPG, PNG, and WebP are allowed".to_string()));
     }
 
-    // Validate it's actually an image
-    if image::guess_format(&file_data).is_err() {
-        return Err(AppError::BadRequest("Invalid image file".to_string()));
+    let format = image::guess_format(data)
+        .map_err(|_| AppError::BadRequest("Invalid image file".to_string()))?;
+
+    if !format.extensions_str().contains(&extension.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "File content is {} but its extension is .{}",
+            format.to_mime_type(),
+            extension
+        )));
     }
 
-    // Generate unique filename
-    let unique_filename = format!("product-images/{}-{}.{}", Uuid::new_v4(), nanoid!(10), extension);
+    Ok(format)
+}
+
+/// Longest edge in pixels for each generated variant; `full` keeps the original size
+const THUMBNAIL_MAX_EDGE: u32 = 200;
+const MEDIUM_MAX_EDGE: u32 = 800;
+
+/// Re-encode the image and upload it with thumbnail/medium variants. Every stored file is
+/// re-encoded from the decoded pixels, so EXIF (GPS position, camera serial) and other
+/// metadata never reach the bucket; the EXIF orientation is applied first so photos
+/// don't turn sideways once it's gone. The variants are written to disk and sent to the
+/// store from there.
+async fn upload_image_variants(storage: &dyn Storage, key_prefix: &str, image: ReceivedImage) -> AppResult<ImageUrls> {
+    let ReceivedImage { file, format } = image;
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+    let content_type = format.to_mime_type();
+
+    // Decoding and resizing is CPU-bound, keep it off the async workers
+    let (thumbnail, medium, full) = web::block(move || -> AppResult<(TempFile, TempFile, TempFile)> {
+        let img = decode_oriented(file.path(), format).map_err(|e| match e {
+            ImageError::Limits(_) => AppError::BadRequest(format!(
+                "Image is too large; at most {} pixels on a side",
+                MAX_IMAGE_EDGE
+            )),
+            _ => AppError::BadRequest("Invalid image file".to_string()),
+        })?;
+        Ok((
+            encode_resized(&img, THUMBNAIL_MAX_EDGE, format)?,
+            encode_resized(&img, MEDIUM_MAX_EDGE, format)?,
+            encode(&img, format)?,
+        ))
+    })
+        .await
+        .map_err(|_| AppError::InternalError)??;
 
-    // Upload to S3
-    let s3_url = upload_to_s3(&unique_filename, file_data.freeze().to_vec(), &format!("image/{}", extension)).await.expect("Failed to upload product image to S3");
+    let thumbnail_url = storage.put_file(&format!("{}/thumbnail.{}", key_prefix, extension), thumbnail.path(), content_type).await?;
+    let medium_url = storage.put_file(&format!("{}/medium.{}", key_prefix, extension), medium.path(), content_type).await?;
+    let full_url = storage.put_file(&format!("{}/full.{}", key_prefix, extension), full.path(), content_type).await?;
 
-    Ok(HttpResponse::Ok().json(json!({
-        "message": "Product image uploaded successfully",
-        "image_url": s3_url
-    })))
+    Ok(ImageUrls {
+        thumbnail: thumbnail_url,
+        medium: medium_url,
+        full: full_url,
+    })
 }
 
-async fn upload_to_s3(key: &str, data: Vec<u8>, content_type: &str) -> AppResult<String> {
-    // Load AWS configuration
-    let config = aws_config::defaults(BehaviorVersion::latest())
-        .load()
-        .await;
-
-    let s3_client = S3Client::new(&config);
+/// Decode and rotate/flip upright according to the EXIF orientation, if any
+fn decode_oriented(path: &Path, format: ImageFormat) -> ImageResult<DynamicImage> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_EDGE);
+    limits.max_image_height = Some(MAX_IMAGE_EDGE);
+
+    let mut reader = ImageReader::with_format(BufReader::new(File::open(path)?), format);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
 
-    // Get bucket name from environment
-    let bucket_name = env::var("S3_BUCKET_NAME")
-        .unwrap_or_else(|_| "streetsource-assets".to_string());
+/// Shrink to fit within `max_edge` (never upscaling) and re-encode in the same format
+fn encode_resized(img: &DynamicImage, max_edge: u32, format: ImageFormat) -> AppResult<TempFile> {
+    if img.width() > max_edge || img.height() > max_edge {
+        encode(&img.resize(max_edge, max_edge, FilterType::Lanczos3), format)
+    } else {
+        encode(img, format)
+    }
+}
 
-    // Upload to S3
-    s3_client
-        .put_object()
-        .bucket(&bucket_name)
-        .key(key)
-        .body(data.into())
-        .content_type(content_type)
-        .send()
-        .await
-        .map_err(|e| AppError::AwsError(e.to_string()))?;
+/// The encoders write pixels only, never the source's metadata
+fn encode(img: &DynamicImage, format: ImageFormat) -> AppResult<TempFile> {
+    let file = TempFile::new();
+    let mut writer = BufWriter::new(File::create(file.path()).map_err(spool_error)?);
+    img.write_to(&mut writer, format)
+        .map_err(|_| AppError::InternalError)?;
+    writer.flush().map_err(spool_error)?;
 
-    // Return the public URL
-    let region = env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
-    let s3_url = format!("https://{}.s3.{}.amazonaws.com/{}", bucket_name, region, key);
+    Ok(file)
+}
 
-    Ok(s3_url)
-}
\ No newline at end of file
+/// An uploaded file, for stores the app serves itself (local disk). Keys embed a random
+/// id and are never overwritten, so the file can be cached for good.
+pub async fn serve_upload(
+    storage: web::Data<dyn Storage>,
+    key: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let key = key.into_inner();
+    let data = storage
+        .get(&key)
+        .await?
+        .ok_or_else(|| AppError::NotFound("File not found".to_string()))?;
+
+    let content_type = key
+        .rsplit_once('.')
+        .and_then(|(_, extension)| ImageFormat::from_extension(extension))
+        .map_or("application/octet-stream", |format| format.to_mime_type());
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+        .insert_header(("X-Content-Type-Options", "nosniff"))
+        .body(data))
+}
@@ -1,15 +1,31 @@
 // handlers/user_handlers.rs
 use actix_identity::Identity;
+use actix_session::Session;
 use actix_web::{web, HttpResponse};
 use serde_json::json;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
 
+use crate::cache::{Cache, Namespace};
+use crate::config::Config;
 use crate::errors::{AppError, AppResult};
-use crate::models::{PublicUser, UpdateProfileRequest, UpdateSettingsRequest};
-use crate::utils::get_user_id;
+use crate::handlers::auth_handlers::{hash_password, password_matches};
+use crate::mailer::{self, templates, EmailSender};
+use crate::passwords;
+use crate::models::{AuditAction, ChangeEmailRequest, ChangePasswordRequest, ConfirmEmailChangeRequest, PublicUser, UpdateProfileRequest, UpdateSettingsRequest};
+use crate::repositories::audit_repository::{self, AuditEntry};
+use crate::sessions;
+use crate::update_builder::UpdateBuilder;
+use crate::utils::{generate_random_string, get_user_id, validate_location};
+use crate::validation::Validate;
+use crate::ws;
+
+/// How long the code sent to a new email address stays valid
+const EMAIL_CHANGE_TOKEN_MINUTES: i64 = 60;
 
 pub async fn get_profile(
     identity: Identity,
+    session: Session,
     pool: web::Data<PgPool>,
 ) -> AppResult<HttpResponse> {
     let user_id = get_user_id(&identity)?;
@@ -17,7 +33,8 @@ pub async fn get_profile(
     let user = sqlx::query_as!(
         PublicUser,
         r#"
-        SELECT id, email, name, phone, is_supplier, rating, total_deliveries, profile_image_url
+        SELECT id, email, name, phone, is_supplier, rating, total_deliveries, profile_image_url,
+               latitude, longitude
         FROM users
         WHERE id = $1
         "#,
@@ -27,7 +44,11 @@ pub async fn get_profile(
         .await?
         .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
 
-    Ok(HttpResponse::Ok().json(user))
+    // So the app can show that an admin is signed in as this user
+    let mut profile = serde_json::to_value(user).map_err(|_| AppError::InternalError)?;
+    profile["impersonated_by"] = json!(sessions::impersonator(&session));
+
+    Ok(HttpResponse::Ok().json(profile))
 }
 
 pub async fn update_profile(
@@ -36,21 +57,16 @@ pub async fn update_profile(
     req: web::Json<UpdateProfileRequest>,
 ) -> AppResult<HttpResponse> {
     let user_id = get_user_id(&identity)?;
+    req.validate()?;
+    let location = validate_location(req.latitude, req.longitude)?;
 
-    sqlx::query!(
-        r#"
-        UPDATE users
-        SET name = COALESCE($2, name),
-            phone = COALESCE($3, phone),
-            profile_image_url = COALESCE($4, profile_image_url)
-        WHERE id = $1
-        "#,
-        user_id,
-        req.name,
-        req.phone,
-        req.profile_image_url
-    )
-        .execute(pool.get_ref())
+    UpdateBuilder::new("users")
+        .set("name", req.name.as_deref())
+        .set("phone", req.phone.as_deref())
+        .set("profile_image_url", req.profile_image_url.as_deref())
+        .set("latitude", location.map(|(latitude, _)| latitude))
+        .set("longitude", location.map(|(_, longitude)| longitude))
+        .execute(pool.get_ref(), user_id)
         .await?;
 
     Ok(HttpResponse::Ok().json(json!({
@@ -65,7 +81,11 @@ pub async fn get_settings(
     let user_id = get_user_id(&identity)?;
 
     let settings = sqlx::query!(
-        "SELECT is_supplier FROM users WHERE id = $1",
+        r#"
+        SELECT is_supplier, min_order_value, delivery_fee, tax_id, tax_name, store_paused, lead_time_hours,
+               push_messages, push_order_updates
+        FROM users WHERE id = $1
+        "#,
         user_id
     )
         .fetch_optional(pool.get_ref())
@@ -73,28 +93,346 @@ pub async fn get_settings(
         .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
 
     Ok(HttpResponse::Ok().json(json!({
-        "is_supplier": settings.is_supplier
+        "is_supplier": settings.is_supplier,
+        "min_order_value": settings.min_order_value,
+        "delivery_fee": settings.delivery_fee,
+        "tax_id": settings.tax_id,
+        "tax_name": settings.tax_name,
+        "store_paused": settings.store_paused,
+        "lead_time_hours": settings.lead_time_hours,
+        "push_messages": settings.push_messages,
+        "push_order_updates": settings.push_order_updates
     })))
 }
 
 pub async fn update_settings(
     identity: Identity,
     pool: web::Data<PgPool>,
+    cache: web::Data<Cache>,
     req: web::Json<UpdateSettingsRequest>,
 ) -> AppResult<HttpResponse> {
     let user_id = get_user_id(&identity)?;
+    req.validate()?;
+
+    let min_order_value = req.min_order_value.as_ref().map(|value| value.round(2));
+    let delivery_fee = req.delivery_fee.as_ref().map(|fee| fee.round(2));
+    let tax_id = req.tax_id.as_ref().map(|tax_id| tax_id.trim().to_uppercase());
+    let tax_name = req.tax_name.as_ref().map(|tax_name| tax_name.trim().to_string());
+
+    let mut tx = pool.begin().await?;
 
     if let Some(become_supplier) = req.become_supplier {
-        sqlx::query!(
-            "UPDATE users SET is_supplier = $2 WHERE id = $1",
+        let changed = sqlx::query!(
+            "UPDATE users SET is_supplier = $2 WHERE id = $1 AND is_supplier <> $2",
             user_id,
             become_supplier
         )
-            .execute(pool.get_ref())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() > 0;
+
+        if changed {
+            audit_repository::record(&mut *tx, AuditEntry {
+                actor_id: Some(user_id),
+                action: AuditAction::RoleChanged,
+                subject_id: Some(user_id),
+                details: json!({ "is_supplier": become_supplier }),
+            }).await?;
+        }
+    }
+
+    if req.push_messages.is_some() || req.push_order_updates.is_some() {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET push_messages = COALESCE($2, push_messages),
+                push_order_updates = COALESCE($3, push_order_updates)
+            WHERE id = $1
+            "#,
+            user_id,
+            req.push_messages,
+            req.push_order_updates
+        )
+            .execute(&mut *tx)
             .await?;
     }
 
+    // Order terms, tax registration, vacation mode and lead time only mean something for suppliers
+    if min_order_value.is_some()
+        || delivery_fee.is_some()
+        || tax_id.is_some()
+        || tax_name.is_some()
+        || req.store_paused.is_some()
+        || req.lead_time_hours.is_some()
+    {
+        let updated = sqlx::query!(
+            r#"
+            UPDATE users
+            SET min_order_value = COALESCE($2, min_order_value),
+                delivery_fee = COALESCE($3, delivery_fee),
+                tax_id = CASE WHEN $4::text IS NULL THEN tax_id ELSE NULLIF($4, '') END,
+                tax_name = CASE WHEN $5::text IS NULL THEN tax_name ELSE NULLIF($5, '') END,
+                store_paused = COALESCE($6, store_paused),
+                lead_time_hours = COALESCE($7, lead_time_hours)
+            WHERE id = $1 AND is_supplier = TRUE
+            "#,
+            user_id,
+            min_order_value,
+            delivery_fee,
+            tax_id,
+            tax_name,
+            req.store_paused,
+            req.lead_time_hours
+        )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        if updated == 0 {
+            return Err(AppError::Forbidden);
+        }
+    }
+
+    // The settings given, as stored
+    let settings: serde_json::Map<String, serde_json::Value> = [
+        ("min_order_value", json!(min_order_value)),
+        ("delivery_fee", json!(delivery_fee)),
+        ("tax_id", json!(tax_id)),
+        ("tax_name", json!(tax_name)),
+        ("store_paused", json!(req.store_paused)),
+        ("lead_time_hours", json!(req.lead_time_hours)),
+        ("push_messages", json!(req.push_messages)),
+        ("push_order_updates", json!(req.push_order_updates)),
+    ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+
+    if !settings.is_empty() {
+        audit_repository::record(&mut *tx, AuditEntry {
+            actor_id: Some(user_id),
+            action: AuditAction::SettingsUpdated,
+            subject_id: Some(user_id),
+            details: serde_json::Value::Object(settings),
+        }).await?;
+    }
+
+    tx.commit().await?;
+
+    // Listings show the seller's order minimum and fee, and hide paused stores
+    if min_order_value.is_some() || delivery_fee.is_some() || req.store_paused.is_some() {
+        cache.invalidate(Namespace::Products).await;
+    }
+
     Ok(HttpResponse::Ok().json(json!({
         "message": "Settings updated successfully"
     })))
-}
\ No newline at end of file
+}
+
+pub async fn change_password(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: web::Json<ChangePasswordRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    passwords::validate(&config, &*req, "new_password", &req.new_password).await?;
+
+    check_password(pool.get_ref(), user_id, &req.current_password).await?;
+
+    let password_hash = hash_password(&req.new_password)?;
+
+    // Every session signed in before now ends, this one included
+    sqlx::query!(
+        "UPDATE users SET password_hash = $2, password_changed_at = NOW() WHERE id = $1",
+        user_id,
+        password_hash
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    // An outstanding reset code would otherwise still undo the change
+    sqlx::query!("DELETE FROM password_resets WHERE user_id = $1", user_id)
+        .execute(pool.get_ref())
+        .await?;
+
+    audit_repository::record(pool.get_ref(), AuditEntry {
+        actor_id: Some(user_id),
+        action: AuditAction::PasswordChanged,
+        subject_id: Some(user_id),
+        details: json!({}),
+    }).await?;
+
+    identity.logout();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Password changed successfully; sign in again with the new password"
+    })))
+}
+
+/// Start an email change: the new address only takes effect once the code
+/// mailed to it is confirmed
+pub async fn change_email(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    mailer: web::Data<dyn EmailSender>,
+    req: web::Json<ChangeEmailRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+    req.validate()?;
+    let new_email = req.new_email.trim();
+
+    check_password(pool.get_ref(), user_id, &req.password).await?;
+
+    let mut conn = pool.acquire().await?;
+    ensure_email_free(&mut conn, new_email).await?;
+
+    let token = generate_random_string(8);
+
+    // A new request replaces any pending one
+    sqlx::query!(
+        r#"
+        INSERT INTO email_changes (user_id, new_email, token, expires_at)
+        VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))
+        ON CONFLICT (user_id)
+        DO UPDATE SET new_email = EXCLUDED.new_email, token = EXCLUDED.token,
+                      expires_at = EXCLUDED.expires_at, created_at = NOW()
+        "#,
+        user_id,
+        new_email,
+        token,
+        EMAIL_CHANGE_TOKEN_MINUTES as i32
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    mailer::send_in_background(
+        mailer.into_inner(),
+        templates::email_change_confirmation(new_email, &token, EMAIL_CHANGE_TOKEN_MINUTES),
+    );
+
+    Ok(HttpResponse::Accepted().json(json!({
+        "message": "A confirmation code has been sent to the new email address"
+    })))
+}
+
+/// Apply the pending email change; the old address is told about it
+pub async fn confirm_email_change(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    mailer: web::Data<dyn EmailSender>,
+    req: web::Json<ConfirmEmailChangeRequest>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    let mut tx = pool.begin().await?;
+
+    let change = sqlx::query!(
+        "SELECT new_email, token, expires_at FROM email_changes WHERE user_id = $1 FOR UPDATE",
+        user_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No pending email change".to_string()))?;
+
+    if change.token != req.token.trim().to_uppercase() {
+        return Err(AppError::InvalidOtp);
+    }
+    if change.expires_at < chrono::Utc::now() {
+        return Err(AppError::OtpExpired);
+    }
+
+    // The address may have been registered since the change was requested
+    ensure_email_free(&mut tx, &change.new_email).await?;
+
+    let old_email = sqlx::query_scalar!("SELECT email FROM users WHERE id = $1", user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        "UPDATE users SET email = $2 WHERE id = $1",
+        user_id,
+        change.new_email
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!("DELETE FROM email_changes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_repository::record(&mut *tx, AuditEntry {
+        actor_id: Some(user_id),
+        action: AuditAction::EmailChanged,
+        subject_id: Some(user_id),
+        details: json!({ "old_email": old_email, "new_email": change.new_email }),
+    }).await?;
+
+    tx.commit().await?;
+
+    mailer::send_in_background(
+        mailer.into_inner(),
+        templates::email_changed(&old_email, &change.new_email),
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Email changed successfully",
+        "email": change.new_email
+    })))
+}
+
+/// Reject the request unless `password` is the user's current password
+pub async fn check_password(pool: &PgPool, user_id: Uuid, password: &str) -> AppResult<()> {
+    let password_hash = sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE id = $1",
+        user_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if !password_matches(password, &password_hash)? {
+        return Err(AppError::BadRequest("Current password is incorrect".to_string()));
+    }
+    Ok(())
+}
+
+async fn ensure_email_free(conn: &mut PgConnection, email: &str) -> AppResult<()> {
+    let taken = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE email = $1) as "exists!""#,
+        email
+    )
+        .fetch_one(conn)
+        .await?;
+
+    if taken {
+        return Err(AppError::Conflict("Email already registered".to_string()));
+    }
+    Ok(())
+}
+
+/// Whether a user has a live WebSocket, and when their last connection closed
+pub async fn get_presence(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    // Presence is only visible to signed-in users
+    get_user_id(&identity)?;
+    let user_id = user_id.into_inner();
+
+    let last_seen_at = sqlx::query_scalar!(
+        "SELECT last_seen_at FROM users WHERE id = $1",
+        user_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "user_id": user_id,
+        "online": ws::is_online(user_id),
+        "last_seen_at": last_seen_at
+    })))
+}
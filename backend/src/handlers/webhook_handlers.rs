@@ -0,0 +1,243 @@
+// handlers/webhook_handlers.rs
+use actix_identity::Identity;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+use crate::models::{CreateWebhookRequest, UpdateWebhookRequest, Webhook, WebhookDelivery, WebhookDeliveryQuery, WebhookDeliveryStatus, WebhookEvent};
+use crate::update_builder::UpdateBuilder;
+use crate::utils::{generate_random_string, get_user_id, Pagination};
+use crate::validation::{FieldErrors, Validate};
+use crate::webhooks;
+
+/// Most webhooks a seller can register
+const MAX_WEBHOOKS: i64 = 10;
+
+pub async fn get_webhooks(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+
+    let webhooks = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT id, url, events as "events: Vec<WebhookEvent>", is_active, created_at
+        FROM webhooks
+        WHERE seller_id = $1
+        ORDER BY created_at
+        "#,
+        seller_id
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "webhooks": webhooks
+    })))
+}
+
+/// Register a URL for some of the seller's events. The signing secret is only ever
+/// shown here.
+pub async fn create_webhook(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: web::Json<CreateWebhookRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    req.validate()?;
+    check_url(&config, &req.url)?;
+
+    let seller = sqlx::query!(
+        r#"
+        SELECT is_supplier,
+               (SELECT COUNT(*) FROM webhooks WHERE seller_id = users.id) as "webhook_count!"
+        FROM users WHERE id = $1
+        "#,
+        seller_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !seller.is_supplier {
+        return Err(AppError::Forbidden);
+    }
+    if seller.webhook_count >= MAX_WEBHOOKS {
+        return Err(AppError::BadRequest(format!("You can have at most {} webhooks", MAX_WEBHOOKS)));
+    }
+
+    let secret = format!("whsec_{}", generate_random_string(32));
+    let webhook = sqlx::query_as!(
+        Webhook,
+        r#"
+        INSERT INTO webhooks (id, seller_id, url, secret, events)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, url, events as "events: Vec<WebhookEvent>", is_active, created_at
+        "#,
+        Uuid::new_v4(),
+        seller_id,
+        req.url.trim(),
+        secret,
+        distinct(&req.events) as Vec<WebhookEvent>
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "message": "Webhook created",
+        "webhook": webhook,
+        "secret": secret
+    })))
+}
+
+/// Change a webhook's URL or events, or pause and resume it
+pub async fn update_webhook(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    webhook_id: web::Path<Uuid>,
+    req: web::Json<UpdateWebhookRequest>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    let webhook_id = webhook_id.into_inner();
+    req.validate()?;
+    if let Some(url) = &req.url {
+        check_url(&config, url)?;
+    }
+
+    find_own(pool.get_ref(), seller_id, webhook_id).await?;
+
+    let update = UpdateBuilder::new("webhooks")
+        .set("url", req.url.as_deref().map(str::trim))
+        .set("events", req.events.as_deref().map(distinct))
+        .set("is_active", req.is_active);
+    if update.is_empty() {
+        return Err(AppError::BadRequest("No fields to update".to_string()));
+    }
+    update.execute(pool.get_ref(), webhook_id).await?;
+
+    let webhook = find_own(pool.get_ref(), seller_id, webhook_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Webhook updated",
+        "webhook": webhook
+    })))
+}
+
+/// Remove a webhook, with its delivery log and anything still queued for it
+pub async fn delete_webhook(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    webhook_id: web::Path<Uuid>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    let webhook_id = webhook_id.into_inner();
+
+    let deleted = sqlx::query!(
+        "DELETE FROM webhooks WHERE id = $1 AND seller_id = $2",
+        webhook_id,
+        seller_id
+    )
+        .execute(pool.get_ref())
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Webhook deleted"
+    })))
+}
+
+/// What was sent to a webhook, newest first: each event's payload, how many attempts it
+/// took and what the last one got back
+pub async fn get_webhook_deliveries(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+    webhook_id: web::Path<Uuid>,
+    query: web::Query<WebhookDeliveryQuery>,
+) -> AppResult<HttpResponse> {
+    let seller_id = get_user_id(&identity)?;
+    let webhook_id = webhook_id.into_inner();
+    let pagination = Pagination::new(query.page, query.limit);
+
+    find_own(pool.get_ref(), seller_id, webhook_id).await?;
+
+    let deliveries = sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        SELECT id, event_id, event as "event: WebhookEvent", payload, status as "status: WebhookDeliveryStatus",
+               attempts, next_attempt_at, last_response_status, last_error, created_at, delivered_at
+        FROM webhook_deliveries
+        WHERE webhook_id = $1 AND ($2::webhook_delivery_status IS NULL OR status = $2)
+        ORDER BY created_at DESC, id
+        LIMIT $3 OFFSET $4
+        "#,
+        webhook_id,
+        query.status as Option<WebhookDeliveryStatus>,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM webhook_deliveries
+        WHERE webhook_id = $1 AND ($2::webhook_delivery_status IS NULL OR status = $2)
+        "#,
+        webhook_id,
+        query.status as Option<WebhookDeliveryStatus>
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "deliveries": deliveries,
+        "max_attempts": webhooks::MAX_ATTEMPTS,
+        "pagination": pagination.to_json(total)
+    })))
+}
+
+async fn find_own(pool: &PgPool, seller_id: Uuid, webhook_id: Uuid) -> AppResult<Webhook> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT id, url, events as "events: Vec<WebhookEvent>", is_active, created_at
+        FROM webhooks
+        WHERE id = $1 AND seller_id = $2
+        "#,
+        webhook_id,
+        seller_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))
+}
+
+/// A 422 on `url` if webhooks can't be sent there
+fn check_url(config: &Config, url: &str) -> AppResult<()> {
+    let mut errors = FieldErrors::default();
+    if let Some(problem) = webhooks::check_url(url.trim(), config.webhook_allow_private_urls) {
+        errors.add("url", problem);
+    }
+    errors.into_result()
+}
+
+/// The events, each once, in the order given
+fn distinct(events: &[WebhookEvent]) -> Vec<WebhookEvent> {
+    let mut distinct = Vec::with_capacity(events.len());
+    for event in events {
+        if !distinct.contains(event) {
+            distinct.push(*event);
+        }
+    }
+    distinct
+}
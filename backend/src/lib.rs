@@ -0,0 +1,377 @@
+// lib.rs
+use actix_identity::IdentityMiddleware;
+use actix_session::{SessionMiddleware, storage::CookieSessionStore};
+use actix_web::body::MessageBody;
+use actix_web::cookie::Key;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{web, App, middleware::from_fn};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing_actix_web::TracingLogger;
+
+pub mod auth;
+pub mod broker;
+pub mod cache;
+pub mod config;
+pub mod mailer;
+pub mod passwords;
+pub mod payments;
+pub mod push;
+pub mod recommendations;
+pub mod recurring_orders;
+pub mod sessions;
+pub mod storage;
+pub mod telemetry;
+pub mod totp;
+pub mod update_builder;
+pub mod webhooks;
+pub mod models;
+pub mod handlers {
+    pub mod auth_handlers;
+    pub mod account_handlers;
+    pub mod two_factor_handlers;
+    pub mod token_handlers;
+    pub mod notification_handlers;
+    pub mod user_handlers;
+    pub mod address_handlers;
+    pub mod product_handlers;
+    pub mod cart_handlers;
+    pub mod catalog_handlers;
+    pub mod coupon_handlers;
+    pub mod deal_handlers;
+    pub mod delivery_slot_handlers;
+    pub mod delivery_zone_handlers;
+    pub mod location_handlers;
+    pub mod earnings_handlers;
+    pub mod order_handlers;
+    pub mod order_comment_handlers;
+    pub mod message_handlers;
+    pub mod offer_handlers;
+    pub mod upload_handlers;
+    pub mod health_handler;
+    pub mod categories_handlers;
+    pub mod review_handlers;
+    pub mod favorite_handlers;
+    pub mod seller_handlers;
+    pub mod analytics_handlers;
+    pub mod dispute_handlers;
+    pub mod moderation_handlers;
+    pub mod payment_handlers;
+    pub mod admin_handlers;
+    pub mod graphql_handler;
+    pub mod recommendation_handlers;
+    pub mod recurring_order_handlers;
+    pub mod rfq_handlers;
+    pub mod search_handlers;
+    pub mod webhook_handlers;
+}
+pub mod graphql {
+    pub mod loaders;
+    pub mod schema;
+}
+pub mod repositories {
+    pub mod audit_repository;
+    pub mod cart_repository;
+    pub mod deal_repository;
+    pub mod delivery_slot_repository;
+    pub mod delivery_zone_repository;
+    pub mod ledger_repository;
+    pub mod inventory_repository;
+    pub mod location_repository;
+    pub mod message_repository;
+    pub mod order_repository;
+    pub mod product_repository;
+    pub mod recurring_order_repository;
+    pub mod reservation_repository;
+}
+pub mod services {
+    pub mod currency_service;
+    pub mod order_service;
+}
+pub mod errors;
+pub mod ws;
+pub mod utils;
+pub mod validation;
+
+use cache::Cache;
+use config::Config;
+use graphql::schema::AppSchema;
+use handlers::{auth_handlers, account_handlers, two_factor_handlers, token_handlers, notification_handlers, user_handlers, address_handlers, product_handlers, cart_handlers, catalog_handlers, coupon_handlers, deal_handlers, delivery_slot_handlers, delivery_zone_handlers, location_handlers, earnings_handlers, order_handlers, order_comment_handlers, message_handlers, offer_handlers, categories_handlers, review_handlers, favorite_handlers, seller_handlers, analytics_handlers, dispute_handlers, moderation_handlers, payment_handlers, admin_handlers, graphql_handler, recommendation_handlers, recurring_order_handlers, rfq_handlers, search_handlers, webhook_handlers};
+use mailer::EmailSender;
+use sqlx::migrate::Migrator;
+use storage::Storage;
+
+/// The schema migrations built into the binary, run at startup and checked for readiness
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Every route with the middleware and shared state they rely on. Built once per worker.
+pub fn app(
+    pool: PgPool,
+    config: Config,
+    schema: AppSchema,
+    mailer: Arc<dyn EmailSender>,
+    storage: Arc<dyn Storage>,
+    cache: Arc<Cache>,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let secret_key = config.secret_key.clone();
+    let json_body_limit = config.json_body_limit_kb * 1024;
+
+    App::new()
+        .app_data(web::Data::new(pool))
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(schema))
+        .app_data(web::Data::from(mailer))
+        .app_data(web::Data::from(storage))
+        .app_data(web::Data::from(cache))
+        .app_data(
+            web::JsonConfig::default()
+                .limit(json_body_limit)
+                .error_handler(validation::json_error_handler)
+        )
+        .wrap(from_fn(telemetry::request_id_header))
+        .wrap(TracingLogger::default())
+        .wrap(from_fn(sessions::enforce))
+        .wrap(IdentityMiddleware::default())
+        .wrap(
+            SessionMiddleware::builder(
+                CookieSessionStore::default(),
+                Key::from(secret_key.as_bytes())
+            )
+                .cookie_name(sessions::SESSION_COOKIE.to_string())
+                .cookie_secure(false) // Set to true in production with HTTPS
+                .build()
+        )
+        .wrap(from_fn(sessions::cookie_lifetime))
+        // Auth routes
+        .service(
+            web::scope("/api")
+                .route("/register", web::post().to(auth_handlers::register))
+                .route("/login", web::post().to(auth_handlers::login))
+                .route("/login/2fa", web::post().to(auth_handlers::login_two_factor))
+                .route("/logout", web::post().to(auth_handlers::logout))
+                .route("/password_reset/request", web::post().to(auth_handlers::request_password_reset))
+                .route("/password_reset/verify", web::post().to(auth_handlers::verify_password_reset))
+                // User routes
+                .route("/user/profile", web::get().to(user_handlers::get_profile))
+                .route("/user/profile", web::put().to(user_handlers::update_profile))
+                .route("/user/settings", web::get().to(user_handlers::get_settings))
+                .route("/user/settings", web::put().to(user_handlers::update_settings))
+                .route("/user/password", web::put().to(user_handlers::change_password))
+                .route("/user/email", web::put().to(user_handlers::change_email))
+                .route("/user/email/confirm", web::post().to(user_handlers::confirm_email_change))
+                .route("/user/account", web::delete().to(account_handlers::delete_account))
+                .route("/user/export", web::get().to(account_handlers::export_account))
+                .route("/user/2fa/setup", web::post().to(two_factor_handlers::setup_two_factor))
+                .route("/user/2fa/enable", web::post().to(two_factor_handlers::enable_two_factor))
+                .route("/user/2fa", web::delete().to(two_factor_handlers::disable_two_factor))
+                .route("/user/tokens", web::get().to(token_handlers::get_tokens))
+                .route("/user/tokens", web::post().to(token_handlers::create_token))
+                .route("/user/tokens/{id}", web::delete().to(token_handlers::delete_token))
+                .route("/notifications", web::get().to(notification_handlers::get_notifications))
+                .route("/notifications/unread_count", web::get().to(notification_handlers::get_unread_count))
+                .route("/notifications/read_all", web::post().to(notification_handlers::mark_all_read))
+                .route("/notifications/{id}/read", web::post().to(notification_handlers::mark_read))
+                .route("/notifications/devices", web::get().to(notification_handlers::list_push_devices))
+                .route("/notifications/devices", web::post().to(notification_handlers::register_push_device))
+                .route("/notifications/devices/{id}", web::delete().to(notification_handlers::remove_push_device))
+                .route("/users/{id}/presence", web::get().to(user_handlers::get_presence))
+                .route("/users/{id}/block", web::post().to(moderation_handlers::block_user))
+                .route("/users/{id}/block", web::delete().to(moderation_handlers::unblock_user))
+                .route("/users/{id}/report", web::post().to(moderation_handlers::report_user))
+                .route("/user/blocks", web::get().to(moderation_handlers::get_blocked_users))
+                .route("/user/addresses", web::get().to(address_handlers::get_addresses))
+                .route("/user/addresses", web::post().to(address_handlers::create_address))
+                .route("/user/addresses/{id}", web::put().to(address_handlers::update_address))
+                .route("/user/addresses/{id}", web::delete().to(address_handlers::delete_address))
+                .route("/user/favorites", web::get().to(favorite_handlers::get_favorites))
+                .route("/user/recommendations", web::get().to(recommendation_handlers::get_recommendations))
+                .route("/search", web::get().to(search_handlers::search))
+                // Category routes
+                .route("/categories", web::get().to(categories_handlers::get_categories))
+                .route("/categories/{id}", web::get().to(categories_handlers::get_category_by_id))
+                // Product routes
+                .route("/products", web::get().to(product_handlers::list_products))
+                .route("/products", web::post().to(product_handlers::create_product))
+                .route("/products/import", web::post().to(catalog_handlers::import_products))
+                .route("/products/export", web::get().to(catalog_handlers::export_products))
+                .route("/products/suggest", web::get().to(product_handlers::suggest_products))
+                .route("/products/lookup", web::get().to(product_handlers::lookup_product))
+                .route("/products/{id}", web::get().to(product_handlers::get_product))
+                .route("/products/{id}", web::put().to(product_handlers::update_product))
+                .route("/products/{id}", web::delete().to(product_handlers::delete_product))
+                .route("/products/{id}/transfer", web::post().to(product_handlers::transfer_product))
+                .route("/products/{id}/restore", web::post().to(product_handlers::restore_deleted_product))
+                .route("/products/{id}/related", web::get().to(recommendation_handlers::get_related_products))
+                .route("/products/{id}/reviews", web::get().to(review_handlers::get_product_reviews))
+                .route("/products/{id}/price_history", web::get().to(product_handlers::get_price_history))
+                .route("/products/{id}/stock_adjust", web::post().to(product_handlers::stock_adjust))
+                .route("/products/{id}/inventory_history", web::get().to(product_handlers::get_inventory_history))
+                .route("/products/{id}/locations", web::get().to(location_handlers::get_product_locations))
+                .route("/products/{id}/locations/transfer", web::post().to(location_handlers::transfer_stock))
+                .route("/products/{id}/locations/{location_id}", web::put().to(location_handlers::set_location_stock))
+                .route("/products/{id}/deals", web::get().to(deal_handlers::get_product_deals))
+                .route("/products/{id}/deals", web::post().to(deal_handlers::create_deal))
+                .route("/products/{id}/deals/{deal_id}", web::delete().to(deal_handlers::end_deal))
+                .route("/deals/active", web::get().to(deal_handlers::get_active_deals))
+                .route("/products/{id}/favorite", web::post().to(favorite_handlers::add_favorite))
+                .route("/products/{id}/favorite", web::delete().to(favorite_handlers::remove_favorite))
+                // Seller storefront routes
+                .route("/sellers/{id}", web::get().to(seller_handlers::get_seller))
+                .route("/sellers/{id}/products", web::get().to(seller_handlers::get_seller_products))
+                .route("/sellers/{id}/delivery_slots", web::get().to(delivery_slot_handlers::get_seller_delivery_slots))
+                .route("/seller/analytics", web::get().to(analytics_handlers::get_seller_analytics))
+                .route("/seller/earnings", web::get().to(earnings_handlers::get_earnings))
+                .route("/seller/earnings/statement", web::get().to(earnings_handlers::export_statement))
+                .route("/seller/delivery_slots", web::get().to(delivery_slot_handlers::get_my_delivery_slots))
+                .route("/seller/delivery_slots", web::post().to(delivery_slot_handlers::create_delivery_slot))
+                .route("/seller/delivery_slots/{id}", web::delete().to(delivery_slot_handlers::delete_delivery_slot))
+                .route("/seller/delivery_zone", web::get().to(delivery_zone_handlers::get_delivery_zone))
+                .route("/seller/delivery_zone", web::put().to(delivery_zone_handlers::set_delivery_zone))
+                .route("/seller/locations", web::get().to(location_handlers::get_locations))
+                .route("/seller/locations", web::post().to(location_handlers::create_location))
+                .route("/seller/locations/{id}", web::put().to(location_handlers::update_location))
+                .route("/seller/locations/{id}", web::delete().to(location_handlers::delete_location))
+                .route("/seller/webhooks", web::get().to(webhook_handlers::get_webhooks))
+                .route("/seller/webhooks", web::post().to(webhook_handlers::create_webhook))
+                .route("/seller/webhooks/{id}", web::put().to(webhook_handlers::update_webhook))
+                .route("/seller/webhooks/{id}", web::delete().to(webhook_handlers::delete_webhook))
+                .route("/seller/webhooks/{id}/deliveries", web::get().to(webhook_handlers::get_webhook_deliveries))
+                .route("/seller/broadcast", web::post().to(message_handlers::broadcast))
+                .route("/products/{id}/images", web::post().to(product_handlers::add_product_image))
+                .route("/products/{id}/images/order", web::put().to(product_handlers::reorder_product_images))
+                .route("/products/{id}/images/{image_id}", web::delete().to(product_handlers::remove_product_image))
+                .route("/products/{id}/variants", web::post().to(product_handlers::create_variant))
+                .route("/products/{id}/price_tiers", web::put().to(product_handlers::set_price_tiers))
+                .route("/products/{id}/variants/{variant_id}", web::put().to(product_handlers::update_variant))
+                .route("/products/{id}/variants/{variant_id}", web::delete().to(product_handlers::delete_variant))
+                // Cart routes
+                .route("/cart", web::get().to(cart_handlers::get_cart))
+                .route("/cart", web::put().to(cart_handlers::replace_cart))
+                .route("/cart/items/{product_id}", web::put().to(cart_handlers::set_cart_quantity))
+                .route("/cart/add", web::post().to(cart_handlers::add_to_cart))
+                .route("/cart/remove", web::post().to(cart_handlers::remove_from_cart))
+                .route("/cart/validate", web::post().to(cart_handlers::validate_cart))
+                .route("/cart/save-as-template", web::post().to(cart_handlers::save_cart_as_template))
+                .route("/cart/templates", web::get().to(cart_handlers::get_cart_templates))
+                .route("/cart/templates/{id}/apply", web::post().to(cart_handlers::apply_cart_template))
+                .route("/cart/apply_coupon", web::post().to(coupon_handlers::apply_coupon))
+                .route("/cart/coupon", web::delete().to(coupon_handlers::remove_coupon))
+                .route("/coupons", web::get().to(coupon_handlers::get_coupons))
+                .route("/coupons", web::post().to(coupon_handlers::create_coupon))
+                // Order routes
+                .route("/orders", web::get().to(order_handlers::get_orders))
+                .route("/orders", web::post().to(order_handlers::create_order))
+                .route("/orders/seller", web::get().to(order_handlers::get_seller_orders))
+                .route("/orders/seller/pending", web::get().to(order_handlers::get_seller_pending_orders))
+                .route("/orders/seller/picklist", web::get().to(order_handlers::get_picklist))
+                .route("/orders/seller/bulk_status", web::post().to(order_handlers::bulk_update_order_status))
+                .route("/orders/{id}/status", web::put().to(order_handlers::update_order_status))
+                .route("/orders/{id}/items/fulfillment", web::post().to(order_handlers::update_item_fulfillment))
+                .route("/orders/{id}/cancel", web::post().to(order_handlers::cancel_order))
+                .route("/orders/{id}/reorder", web::post().to(order_handlers::reorder))
+                .route("/orders/{id}/delivery_slot", web::put().to(order_handlers::reschedule_delivery))
+                .route("/orders/{id}/shipment", web::put().to(order_handlers::update_shipment))
+                .route("/orders/{id}/history", web::get().to(order_handlers::get_order_history))
+                .route("/orders/{id}/timeline", web::get().to(order_handlers::get_order_timeline))
+                .route("/orders/{id}/comments", web::get().to(order_comment_handlers::get_order_comments))
+                .route("/orders/{id}/comments", web::post().to(order_comment_handlers::create_order_comment))
+                .route("/orders/{id}/invoice", web::get().to(order_handlers::get_invoice))
+                .route("/orders/{id}/review", web::post().to(review_handlers::create_review))
+                .route("/orders/{id}/pay", web::post().to(payment_handlers::pay_order))
+                .route("/orders/{id}/dispute", web::post().to(dispute_handlers::open_dispute))
+                .route("/orders/{id}/dispute", web::get().to(dispute_handlers::get_dispute))
+                .route("/orders/{id}/dispute/response", web::post().to(dispute_handlers::respond_to_dispute))
+                // Standing order routes
+                .route("/recurring_orders", web::get().to(recurring_order_handlers::get_recurring_orders))
+                .route("/recurring_orders", web::post().to(recurring_order_handlers::create_recurring_order))
+                .route("/recurring_orders/{id}", web::get().to(recurring_order_handlers::get_recurring_order))
+                .route("/recurring_orders/{id}", web::put().to(recurring_order_handlers::update_recurring_order))
+                .route("/recurring_orders/{id}/pause", web::post().to(recurring_order_handlers::pause_recurring_order))
+                .route("/recurring_orders/{id}/resume", web::post().to(recurring_order_handlers::resume_recurring_order))
+                .route("/recurring_orders/{id}/skip", web::post().to(recurring_order_handlers::skip_recurring_order))
+                .route("/recurring_orders/{id}/cancel", web::post().to(recurring_order_handlers::cancel_recurring_order))
+                // Payment provider webhooks
+                .service(
+                    web::resource("/webhooks/stripe")
+                        .app_data(web::PayloadConfig::new(json_body_limit))
+                        .route(web::post().to(payment_handlers::stripe_webhook))
+                )
+                // Message routes
+                .route("/conversations", web::get().to(message_handlers::get_conversations))
+                .route("/conversations", web::post().to(message_handlers::start_conversation))
+                .route("/messages", web::post().to(message_handlers::send_message))
+                .route("/messages/{conv_id}", web::get().to(message_handlers::get_messages))
+                .route("/messages/{id}", web::put().to(message_handlers::edit_message))
+                .route("/messages/{id}", web::delete().to(message_handlers::delete_message))
+                .route("/messages/{conv_id}/read", web::post().to(message_handlers::mark_conversation_read))
+                .route("/ws/ticket", web::post().to(ws::create_ticket))
+                // Offer routes
+                .route("/offers", web::post().to(offer_handlers::create_offer))
+                .route("/offers", web::get().to(offer_handlers::get_offers))
+                .route("/offers/{id}/accept", web::post().to(offer_handlers::accept_offer))
+                .route("/offers/{id}/reject", web::post().to(offer_handlers::reject_offer))
+                .route("/offers/{id}/counter", web::post().to(offer_handlers::counter_offer))
+                .route("/offers/{id}/order", web::post().to(offer_handlers::convert_offer_to_order))
+                .route("/rfq", web::post().to(rfq_handlers::create_rfq))
+                .route("/rfq", web::get().to(rfq_handlers::get_rfqs))
+                .route("/rfq/open", web::get().to(rfq_handlers::get_open_rfqs))
+                .route("/rfq/{id}", web::get().to(rfq_handlers::get_rfq))
+                .route("/rfq/{id}/cancel", web::post().to(rfq_handlers::cancel_rfq))
+                .route("/rfq/{id}/quotes", web::post().to(rfq_handlers::create_quote))
+                .route("/rfq/{id}/quotes/{quote_id}/accept", web::post().to(rfq_handlers::accept_quote))
+                // Upload routes
+                .route("/upload/profile", web::post().to(handlers::upload_handlers::upload_profile_image))
+                .route("/upload/product", web::post().to(handlers::upload_handlers::upload_product_image))
+                .route("/upload/message", web::post().to(handlers::upload_handlers::upload_message_attachment))
+                // Admin routes; stopping an impersonation is made as the impersonated user
+                .route("/admin/impersonate/stop", web::post().to(admin_handlers::stop_impersonation))
+                .service(
+                    web::scope("/admin")
+                        .wrap(auth::RequireAdmin)
+                        .route("/users", web::get().to(admin_handlers::list_users))
+                        .route("/impersonate/{user_id}", web::post().to(admin_handlers::impersonate_user))
+                        .route("/users/{id}/suspend", web::post().to(admin_handlers::suspend_user))
+                        .route("/users/{id}/unsuspend", web::post().to(admin_handlers::unsuspend_user))
+                        .route("/users/{id}/unlock", web::post().to(admin_handlers::unlock_user))
+                        .route("/audit_log", web::get().to(admin_handlers::list_audit_log))
+                        .route("/products/{id}/takedown", web::post().to(admin_handlers::take_down_product))
+                        .route("/products/{id}/restore", web::post().to(admin_handlers::restore_product))
+                        .route("/products/purge", web::post().to(admin_handlers::purge_deleted_products))
+                        .route("/products/review", web::get().to(admin_handlers::list_products_for_review))
+                        .route("/products/review/{id}", web::post().to(admin_handlers::review_product))
+                        .route("/categories", web::post().to(categories_handlers::create_category))
+                        .route("/categories/{id}", web::put().to(categories_handlers::rename_category))
+                        .route("/categories/{id}", web::delete().to(categories_handlers::delete_category))
+                        .route("/categories/{id}/merge", web::post().to(categories_handlers::merge_category))
+                        .route("/categories/{id}/tax_rate", web::put().to(categories_handlers::set_category_tax_rate))
+                        .route("/orders", web::get().to(admin_handlers::list_orders))
+                        .route("/orders/{id}", web::get().to(admin_handlers::get_order))
+                        .route("/disputes", web::get().to(dispute_handlers::list_disputes))
+                        .route("/disputes/{id}/resolve", web::post().to(dispute_handlers::resolve_dispute))
+                        .route("/reports", web::get().to(moderation_handlers::list_reports))
+                        .route("/reports/platform", web::get().to(analytics_handlers::get_platform_report))
+                        .route("/reports/{id}", web::get().to(moderation_handlers::get_report))
+                        .route("/reports/{id}/resolve", web::post().to(moderation_handlers::resolve_report))
+                        .route("/payouts", web::get().to(earnings_handlers::list_payouts_due))
+                        .route("/sellers/{id}/payouts", web::post().to(earnings_handlers::record_payout))
+                )
+        )
+        // Uploaded files, when they're stored on local disk
+        .route("/uploads/{key:.*}", web::get().to(handlers::upload_handlers::serve_upload))
+        // GraphQL endpoint
+        .route("/graphql", web::post().to(graphql_handler::graphql))
+        // WebSocket endpoint
+        .route("/ws/messages", web::get().to(ws::websocket_handler))
+        //Health Check endpoints
+        .route("/health", web::get().to(handlers::health_handler::health_check))
+        .route("/health/live", web::get().to(handlers::health_handler::liveness))
+        .route("/health/ready", web::get().to(handlers::health_handler::readiness))
+}
@@ -0,0 +1,284 @@
+// mailer.rs
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_sesv2::types::{Body, Content, Destination, EmailContent, Message};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::Instrument;
+
+use crate::config::{Config, EmailProvider};
+
+/// A rendered email, ready for any transport
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+#[derive(Error, Debug)]
+#[error("Email delivery failed: {0}")]
+pub struct MailError(pub String);
+
+#[async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), MailError>;
+}
+
+/// Build the sender selected by the configuration
+pub async fn from_config(config: &Config) -> Result<Arc<dyn EmailSender>, MailError> {
+    let sender: Arc<dyn EmailSender> = match config.email_provider {
+        EmailProvider::Log => Arc::new(LogSender),
+        EmailProvider::Ses => Arc::new(SesSender::new(&config.ses_region, &config.email_from).await),
+        EmailProvider::Smtp => Arc::new(SmtpSender::new(config)?),
+    };
+
+    tracing::info!("Email delivery via {:?}", config.email_provider);
+    Ok(sender)
+}
+
+/// Send without holding up the request; failures are logged under the caller's span
+pub fn send_in_background(sender: Arc<dyn EmailSender>, email: Email) {
+    actix_web::rt::spawn(async move {
+        if let Err(e) = sender.send(&email).await {
+            tracing::error!(to = %email.to, subject = %email.subject, error = %e, "Failed to send email");
+        }
+    }.in_current_span());
+}
+
+pub struct LogSender;
+
+#[async_trait]
+impl EmailSender for LogSender {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        tracing::info!(to = %email.to, subject = %email.subject, "Email (not sent)\n{}", email.text);
+        Ok(())
+    }
+}
+
+pub struct SesSender {
+    client: aws_sdk_sesv2::Client,
+    from: String,
+}
+
+impl SesSender {
+    pub async fn new(region: &str, from: &str) -> Self {
+        let aws_config = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(region.to_string()))
+            .load()
+            .await;
+
+        SesSender {
+            client: aws_sdk_sesv2::Client::new(&aws_config),
+            from: from.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSender for SesSender {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        let content = |data: &str| {
+            Content::builder()
+                .data(data)
+                .charset("UTF-8")
+                .build()
+                .map_err(|e| MailError(e.to_string()))
+        };
+
+        let message = Message::builder()
+            .subject(content(&email.subject)?)
+            .body(
+                Body::builder()
+                    .text(content(&email.text)?)
+                    .html(content(&email.html)?)
+                    .build(),
+            )
+            .build();
+
+        self.client
+            .send_email()
+            .from_email_address(&self.from)
+            .destination(Destination::builder().to_addresses(&email.to).build())
+            .content(EmailContent::builder().simple(message).build())
+            .send()
+            .await
+            .map_err(|e| MailError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+pub struct SmtpSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpSender {
+    pub fn new(config: &Config) -> Result<Self, MailError> {
+        let host = config
+            .smtp_host
+            .as_deref()
+            .ok_or_else(|| MailError("SMTP_HOST is not set".to_string()))?;
+
+        // STARTTLS on the configured port
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| MailError(e.to_string()))?
+            .port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let from = config
+            .email_from
+            .parse()
+            .map_err(|_| MailError(format!("Invalid EMAIL_FROM address '{}'", config.email_from)))?;
+
+        Ok(SmtpSender {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailSender for SmtpSender {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        let to: Mailbox = email
+            .to
+            .parse()
+            .map_err(|_| MailError(format!("Invalid recipient address '{}'", email.to)))?;
+
+        let message = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&email.subject)
+            .multipart(MultiPart::alternative_plain_html(email.text.clone(), email.html.clone()))
+            .map_err(|e| MailError(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| MailError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// Email bodies, each rendered as plain text plus HTML in a shared layout
+pub mod templates {
+    use uuid::Uuid;
+
+    use super::Email;
+    use crate::models::OrderStatus;
+
+    pub fn password_reset(to: &str, otp: &str, expires_in_minutes: i64) -> Email {
+        render(
+            to,
+            "Your StreetSource password reset code",
+            &format!(
+                "Your password reset code is {}. It expires in {} minutes.\n\nIf you didn't ask to reset your password, you can ignore this email.",
+                otp, expires_in_minutes
+            ),
+        )
+    }
+
+    pub fn welcome(to: &str, name: Option<&str>) -> Email {
+        render(
+            to,
+            "Welcome to StreetSource",
+            &format!(
+                "Hi {},\n\nYour StreetSource account is ready. Browse fresh supplies from local sellers and order in a few taps.",
+                name.unwrap_or("there")
+            ),
+        )
+    }
+
+    pub fn email_change_confirmation(to: &str, token: &str, expires_in_minutes: i64) -> Email {
+        render(
+            to,
+            "Confirm your new StreetSource email address",
+            &format!(
+                "Your email confirmation code is {}. Enter it in your account settings within {} minutes to start using this address.\n\nIf you didn't ask to change your email, you can ignore this email.",
+                token, expires_in_minutes
+            ),
+        )
+    }
+
+    pub fn email_changed(to: &str, new_email: &str) -> Email {
+        render(
+            to,
+            "Your StreetSource email address was changed",
+            &format!(
+                "The email address on your StreetSource account was changed to {}. If you didn't make this change, contact support right away.",
+                new_email
+            ),
+        )
+    }
+
+    /// `tracking` is the shipment details, a line each, once the order is on its way
+    pub fn order_status_changed(
+        to: &str,
+        name: Option<&str>,
+        order_id: Uuid,
+        status: &OrderStatus,
+        tracking: Option<&str>,
+    ) -> Email {
+        let status = status.as_str().replace('_', " ");
+        let mut text = format!(
+            "Hi {},\n\nYour order {} is now {}. You can follow it from the Orders page.",
+            name.unwrap_or("there"),
+            order_id,
+            status
+        );
+        if let Some(tracking) = tracking {
+            text.push_str(&format!("\n\n{}", tracking));
+        }
+
+        render(to, &format!("Your StreetSource order is {}", status), &text)
+    }
+
+    /// Prices come already formatted with their currency
+    pub fn price_drop(to: &str, name: Option<&str>, product_name: &str, old_price: &str, new_price: &str) -> Email {
+        render(
+            to,
+            &format!("{} is now cheaper on StreetSource", product_name),
+            &format!(
+                "Hi {},\n\nThe price of {}, which is in your cart or favorites, dropped from {} to {}.",
+                name.unwrap_or("there"),
+                product_name,
+                old_price,
+                new_price
+            ),
+        )
+    }
+
+    fn render(to: &str, subject: &str, text: &str) -> Email {
+        let paragraphs: String = text
+            .split("\n\n")
+            .map(|paragraph| format!("<p>{}</p>", escape_html(paragraph).replace('\n', "<br>")))
+            .collect();
+
+        Email {
+            to: to.to_string(),
+            subject: subject.to_string(),
+            text: format!("{}\n\n— The StreetSource team", text),
+            html: format!(
+                r#"<!DOCTYPE html><html><body style="font-family:sans-serif;color:#1f2937;max-width:560px;margin:auto"><h2 style="color:#f97316">StreetSource</h2>{}<p style="color:#6b7280">— The StreetSource team</p></body></html>"#,
+                paragraphs
+            ),
+        }
+    }
+
+    fn escape_html(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+}
@@ -1,112 +1,128 @@
-use actix_identity::IdentityMiddleware;
-use actix_session::{SessionMiddleware, storage::CookieSessionStore};
-use actix_web::{web, App, HttpServer, middleware::Logger};
-use actix_web::cookie::Key;
+use actix_web::HttpServer;
 use dotenv::dotenv;
-use sqlx::postgres::PgPoolOptions;
-use std::env;
-use log::Level;
-
-mod auth;
-mod models;
-mod handlers {
-    pub mod auth_handlers;
-    pub mod user_handlers;
-    pub mod product_handlers;
-    pub mod cart_handlers;
-    pub mod order_handlers;
-    pub mod message_handlers;
-    pub mod upload_handlers;
-    pub mod health_handler;
-    pub mod categories_handlers;
-}
-mod errors;
-mod ws;
-mod utils;
 
-use handlers::{auth_handlers, user_handlers, product_handlers, cart_handlers, order_handlers, message_handlers, categories_handlers};
+use backend::config::Config;
+use backend::{broker, cache, graphql, mailer, push, recurring_orders, storage, telemetry, webhooks, ws, MIGRATOR};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    telemetry::init();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let server_address = env::var("SERVER_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
-    let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+    // Validate configuration before touching the database
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let server_address = config.server_address.clone();
+    let shutdown_timeout = config.shutdown_timeout_seconds;
 
     // Create database pool
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
+    let connect_options = config
+        .database_connect_options()
+        .expect("Invalid DATABASE_URL");
+    let pool = config
+        .database_pool_options()
+        .connect_with(connect_options)
         .await
         .expect("Failed to create pool");
 
     // Run migrations
-    sqlx::migrate!("./migrations")
+    MIGRATOR
         .run(&pool)
         .await
         .expect("Failed to run migrations");
 
-    println!("Starting server at http://{}", server_address);
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(pool.clone()))
-            .wrap(Logger::default())
-            .wrap(IdentityMiddleware::default())
-            .wrap(
-                SessionMiddleware::builder(
-                    CookieSessionStore::default(),
-                    Key::from(secret_key.as_bytes())
-                )
-                    .cookie_secure(false) // Set to true in production with HTTPS
-                    .build()
-            )
-            // Auth routes
-            .service(
-                web::scope("/api")
-                    .route("/register", web::post().to(auth_handlers::register))
-                    .route("/login", web::post().to(auth_handlers::login))
-                    .route("/logout", web::post().to(auth_handlers::logout))
-                    .route("/password_reset/request", web::post().to(auth_handlers::request_password_reset))
-                    .route("/password_reset/verify", web::post().to(auth_handlers::verify_password_reset))
-                    // User routes
-                    .route("/user/profile", web::get().to(user_handlers::get_profile))
-                    .route("/user/profile", web::put().to(user_handlers::update_profile))
-                    .route("/user/settings", web::get().to(user_handlers::get_settings))
-                    .route("/user/settings", web::put().to(user_handlers::update_settings))
-                    // Category routes
-                    .route("/categories", web::get().to(categories_handlers::get_categories))
-                    .route("/categories/{id}", web::get().to(categories_handlers::get_category_by_id))
-                    // Product routes
-                    .route("/products", web::get().to(product_handlers::list_products))
-                    .route("/products", web::post().to(product_handlers::create_product))
-                    .route("/products/{id}", web::get().to(product_handlers::get_product))
-                    .route("/products/{id}", web::put().to(product_handlers::update_product))
-                    .route("/products/{id}", web::delete().to(product_handlers::delete_product))
-                    // Cart routes
-                    .route("/cart", web::get().to(cart_handlers::get_cart))
-                    .route("/cart/add", web::post().to(cart_handlers::add_to_cart))
-                    .route("/cart/remove", web::post().to(cart_handlers::remove_from_cart))
-                    // Order routes
-                    .route("/orders", web::get().to(order_handlers::get_orders))
-                    .route("/orders", web::post().to(order_handlers::create_order))
-                    .route("/orders/seller/pending", web::get().to(order_handlers::get_seller_pending_orders))
-                    .route("/orders/{id}/status", web::put().to(order_handlers::update_order_status))
-                    // Message routes
-                    .route("/conversations", web::get().to(message_handlers::get_conversations))
-                    .route("/messages/{conv_id}", web::get().to(message_handlers::get_messages))
-                    // Upload routes
-                    .route("/upload/profile", web::post().to(handlers::upload_handlers::upload_profile_image))
-                    .route("/upload/product", web::post().to(handlers::upload_handlers::upload_product_image))
-            )
-            // WebSocket endpoint
-            .route("/ws/messages", web::get().to(ws::websocket_handler))
-            //Health Check endpoint
-            .route("/health", web::get().to(handlers::health_handler::health_check))
+    // Cross-instance WebSocket delivery (falls back to in-memory without REDIS_URL)
+    broker::init(config.redis_url.as_deref())
+        .await
+        .expect("Failed to connect to Redis");
+
+    // Outgoing email (logged instead of sent unless EMAIL_PROVIDER is set)
+    let mailer = match mailer::from_config(&config).await {
+        Ok(mailer) => mailer,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Uploaded files (S3 unless STORAGE_BACKEND=local)
+    let storage = match storage::from_config(&config).await {
+        Ok(storage) => storage,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Push notifications for offline users (logged instead of sent unless PUSH_PROVIDER is set)
+    if let Err(e) = push::init(&config) {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    }
+
+    // Cached catalog reads (in memory unless CACHE_BACKEND says otherwise)
+    let cache = cache::from_config(&config)
+        .await
+        .expect("Failed to connect to the Redis cache");
+
+    // Standing orders are placed in the background as they come due
+    actix_web::rt::spawn(recurring_orders::run(pool.clone()));
+    // And sellers' webhooks sent, and retried, by another worker
+    actix_web::rt::spawn(webhooks::run(pool.clone(), config.webhook_allow_private_urls));
+
+    tracing::info!("Starting server at http://{}", server_address);
+
+    let app_pool = pool.clone();
+    let schema = graphql::schema::build(pool.clone());
+    let server = HttpServer::new(move || {
+        backend::app(app_pool.clone(), config.clone(), schema.clone(), mailer.clone(), storage.clone(), cache.clone())
     })
+        .shutdown_timeout(shutdown_timeout)
+        .disable_signals()
         .bind(&server_address)?
-        .run()
-        .await
+        .run();
+
+    // On SIGINT/SIGTERM stop accepting connections and let in-flight requests finish.
+    // WebSocket clients are told to go first, or their connections would hold up the
+    // drain until the timeout.
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!(timeout_seconds = shutdown_timeout, "Shutting down, draining connections");
+        ws::close_all();
+        handle.stop(true).await;
+    });
+
+    server.await?;
+
+    pool.close().await;
+    tracing::info!("Server stopped");
+    Ok(())
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM (what container orchestrators send) on Unix
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Failed to listen for Ctrl-C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
@@ -1,6 +1,6 @@
 use bigdecimal::BigDecimal;
 // models.rs
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
@@ -18,6 +18,8 @@ pub struct User {
     pub total_deliveries: i32,
     pub profile_image_url: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub is_admin: bool,
+    pub suspended_at: Option<DateTime<Utc>>,
 }
 
 // Public user info (without sensitive data)
@@ -31,6 +33,8 @@ pub struct PublicUser {
     pub rating: Option<f64>,
     pub total_deliveries: i32,
     pub profile_image_url: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
 }
 
 // Category model
@@ -38,6 +42,15 @@ pub struct PublicUser {
 pub struct Category {
     pub id: i32,
     pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+/// A category with how many listed products match the current search
+#[derive(Debug, Serialize, FromRow)]
+pub struct CategoryFacet {
+    pub id: i32,
+    pub name: String,
+    pub count: i64,
 }
 
 // Product model
@@ -61,7 +74,18 @@ pub struct ProductWithSeller {
     pub name: String,
     pub description: Option<String>,
     pub price_per_unit: BigDecimal,
+    pub currency: String,
     pub stock_qty: i32,
+    // Locations holding some of the stock; 0 for products not stocked by location
+    pub stock_locations: i64,
+    // The flash deal on now with units left: id, deal_price, ends_at and remaining
+    pub deal: Option<serde_json::Value>,
+    pub unit: ProductUnit,
+    // Carts and orders take whole multiples of this many units
+    pub min_increment: i32,
+    // The seller's stock-keeping code and the product's barcode, when set
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
     pub image_url: Option<String>,
     pub seller_id: Uuid,
     pub category_id: i32,
@@ -70,9 +94,70 @@ pub struct ProductWithSeller {
     pub seller_company: Option<String>,
     pub seller_rating: Option<f64>,
     pub seller_deliveries: i32,
+    pub seller_min_order_value: BigDecimal,
+    pub seller_delivery_fee: BigDecimal,
+    pub images: serde_json::Value,
+    // Quantity discounts, by ascending min_qty
+    pub price_tiers: serde_json::Value,
+    // Always false for anonymous viewers
+    pub is_favorited: bool,
+    // Only set when the listing was searched from a location
+    pub distance_km: Option<f64>,
+    // Only set when prices were asked for in a currency (?currency=)
+    pub display_price: Option<BigDecimal>,
+    pub display_currency: Option<String>,
+    // Anything but approved is only ever shown to the product's seller
+    pub review_status: ProductReviewStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+// Where a product is in the review-before-publish queue
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "product_review_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ProductReviewStatus {
+    PendingReview,
+    Approved,
+    Rejected,
+}
+
+// What one unit of a product's price and stock is
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "product_unit", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ProductUnit {
+    Piece,
+    Kg,
+    Gram,
+    Litre,
+    Dozen,
+    Bunch,
+    Crate,
+    Sack,
+}
+
+// Product variant model (pack size, unit or grade), with its own price and stock
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ProductVariant {
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub name: String,
+    pub price_per_unit: BigDecimal,
+    pub stock_qty: i32,
     pub created_at: DateTime<Utc>,
 }
 
+// A recorded change to a product's price, or to one of its variants' prices
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PriceChange {
+    pub id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub variant_name: Option<String>,
+    pub old_price: BigDecimal,
+    pub new_price: BigDecimal,
+    pub changed_at: DateTime<Utc>,
+}
+
 // Order model
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Order {
@@ -80,16 +165,108 @@ pub struct Order {
     pub buyer_id: Uuid,
     pub seller_id: Uuid,
     pub status: OrderStatus,
+    pub subtotal_price: f64,
+    pub discount_amount: f64,
+    pub coupon_id: Option<Uuid>,
+    pub delivery_fee: f64,
     pub total_price: f64,
+    pub shipping_address: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Delivery address model
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Address {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub label: Option<String>,
+    pub recipient_name: String,
+    pub phone: String,
+    pub line1: String,
+    pub line2: Option<String>,
+    pub city: String,
+    pub state: String,
+    pub postal_code: String,
+    pub country: String,
+    // Where the address is, for sellers who deliver within a radius
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub is_default: bool,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 // Order status enum
-#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone)]
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
 #[sqlx(type_name = "order_status", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum OrderStatus {
     Pending,
+    Paid,
+    Failed,
+    // Some but not all items shipped
+    #[sqlx(rename = "partially_shipped")]
+    #[serde(rename = "partially_shipped")]
+    PartiallyShipped,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Disputed,
+    Refunded,
+}
+
+impl OrderStatus {
+    /// Statuses an order may move to from this one. Pending orders can ship
+    /// unpaid (cash on delivery), in full or a few items at a time. Paid, (partially)
+    /// shipped and delivered orders can be disputed; a dispute ends refunded or back
+    /// where it was. Cancelled and Refunded are final.
+    pub fn allowed_transitions(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderStatus::Pending => &[
+                OrderStatus::Paid, OrderStatus::Failed, OrderStatus::PartiallyShipped,
+                OrderStatus::Shipped, OrderStatus::Cancelled,
+            ],
+            OrderStatus::Failed => &[OrderStatus::Paid, OrderStatus::Cancelled],
+            OrderStatus::Paid => &[
+                OrderStatus::PartiallyShipped, OrderStatus::Shipped, OrderStatus::Cancelled, OrderStatus::Disputed,
+            ],
+            OrderStatus::PartiallyShipped => &[OrderStatus::Shipped, OrderStatus::Disputed],
+            OrderStatus::Shipped => &[OrderStatus::Delivered, OrderStatus::Disputed],
+            OrderStatus::Delivered => &[OrderStatus::Disputed],
+            OrderStatus::Disputed => &[
+                OrderStatus::Paid, OrderStatus::PartiallyShipped, OrderStatus::Shipped,
+                OrderStatus::Delivered, OrderStatus::Refunded,
+            ],
+            OrderStatus::Cancelled | OrderStatus::Refunded => &[],
+        }
+    }
+
+    /// The status as it appears in the API and database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Failed => "failed",
+            OrderStatus::PartiallyShipped => "partially_shipped",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Disputed => "disputed",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: &OrderStatus) -> bool {
+        self.allowed_transitions().contains(next)
+    }
+}
+
+// Where a single order line is in its delivery
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "fulfillment_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FulfillmentStatus {
+    Unfulfilled,
     Shipped,
     Delivered,
 }
@@ -108,15 +285,43 @@ pub struct OrderItem {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CartItem {
     pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
     pub quantity: i32,
 }
 
-// Conversation model
+// Why a cart line can't be checked out as it stands
+#[derive(Debug, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum CartIssue {
+    // Deleted, taken down, or a product with variants held without one
+    Unavailable,
+    OutOfStock,
+    QuantityReduced { requested: i32, available: i32 },
+    // Now sold in multiples the quantity isn't one of
+    QuantityRounded { requested: i32, quantity: i32 },
+    PriceChanged { old_price: BigDecimal, new_price: BigDecimal },
+}
+
+#[derive(Debug, Serialize)]
+pub struct CartLineIssue {
+    pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub name: String,
+    pub variant_name: Option<String>,
+    #[serde(flatten)]
+    pub issue: CartIssue,
+}
+
+// Conversation model: a pair of users (user1_id < user2_id), or a group such as a
+// seller's broadcast, with its owner and title and no pair
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Conversation {
     pub id: Uuid,
-    pub user1_id: Uuid,
-    pub user2_id: Uuid,
+    pub user1_id: Option<Uuid>,
+    pub user2_id: Option<Uuid>,
+    pub is_group: bool,
+    pub owner_id: Option<Uuid>,
+    pub title: Option<String>,
     pub last_updated: DateTime<Utc>,
 }
 
@@ -127,16 +332,156 @@ pub struct Message {
     pub conv_id: Uuid,
     pub sender_id: Uuid,
     pub content: String,
+    pub attachment_url: Option<String>,
+    pub attachment_type: Option<String>,
     pub sent_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// Why a product's stock changed
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "inventory_reason", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum InventoryReason {
+    Order,
+    Cancellation,
+    Adjustment,
+    Import,
+}
+
+// Inventory movement model
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct InventoryMovement {
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub variant_name: Option<String>,
+    pub quantity_change: i32,
+    pub stock_after: i32,
+    pub reason: InventoryReason,
+    pub note: Option<String>,
+    pub order_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub actor_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// What a notification is about
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "notification_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Order,
+    Message,
+    Offer,
+    LowStock,
+    ProductReview,
+    Payout,
+    Rfq,
+}
+
+// Notification model
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Notification {
+    pub id: Uuid,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub data: serde_json::Value,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// What kind of device a push token belongs to
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "push_platform", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PushPlatform {
+    Android,
+    Ios,
+    Web,
+}
+
+// A device registered for push notifications; the token itself isn't shown again
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PushDevice {
+    pub id: Uuid,
+    pub platform: PushPlatform,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterPushDeviceRequest {
+    pub platform: PushPlatform,
+    // The FCM registration token from the device's Firebase SDK
+    pub token: String,
+}
+
+// Offer status enum
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "offer_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum OfferStatus {
+    Pending,
+    Accepted,
+    Rejected,
+    Countered,
+    Ordered,
+}
+
+// Offer model
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Offer {
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub buyer_id: Uuid,
+    pub seller_id: Uuid,
+    pub proposed_by: Uuid,
+    pub price_per_unit: BigDecimal,
+    pub quantity: i32,
+    pub status: OfferStatus,
+    pub parent_offer_id: Option<Uuid>,
+    pub order_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
-// Special offer message content
+// Special offer message content, stored as the chat message body
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OfferContent {
     #[serde(rename = "type")]
     pub msg_type: String,
-    pub price: f64,
+    pub offer_id: Uuid,
+    pub product_id: Uuid,
+    pub price: BigDecimal,
     pub qty: i32,
+    pub status: OfferStatus,
+}
+
+// Coupon discount kind
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "discount_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountType {
+    Percentage,
+    Fixed,
+}
+
+// Coupon model
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Coupon {
+    pub id: Uuid,
+    pub seller_id: Uuid,
+    pub code: String,
+    pub discount_type: DiscountType,
+    pub discount_value: BigDecimal,
+    pub min_order_value: Option<BigDecimal>,
+    pub max_uses_per_user: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
 }
 
 // Password reset model
@@ -162,6 +507,9 @@ pub struct RegisterRequest {
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    /// Stay signed in for REMEMBER_ME_DAYS, across browser restarts
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -184,6 +532,18 @@ pub struct CreateProductRequest {
     pub stock_qty: i32,
     pub category_id: i32,
     pub image_url: Option<String>,
+    pub images: Option<Vec<String>>,
+    // Where this stock is, if not at the seller's location
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    // ISO code with an exchange rate; the base currency when omitted
+    pub currency: Option<String>,
+    // By the piece, one at a time, when omitted
+    pub unit: Option<ProductUnit>,
+    pub min_increment: Option<i32>,
+    // Each unique among the seller's products
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -191,9 +551,68 @@ pub struct UpdateProductRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub price_per_unit: Option<BigDecimal>,
+    pub currency: Option<String>,
     pub stock_qty: Option<i32>,
+    pub unit: Option<ProductUnit>,
+    pub min_increment: Option<i32>,
     pub category_id: Option<i32>,
     pub image_url: Option<String>,
+    pub images: Option<Vec<String>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    // An empty string clears them
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateVariantRequest {
+    pub name: String,
+    pub price_per_unit: BigDecimal,
+    pub stock_qty: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateVariantRequest {
+    pub name: Option<String>,
+    pub price_per_unit: Option<BigDecimal>,
+    pub stock_qty: Option<i32>,
+}
+
+// A quantity discount: each unit costs unit_price once a cart line reaches min_qty
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PriceTier {
+    pub min_qty: i32,
+    pub unit_price: BigDecimal,
+}
+
+// Replaces all of a product's tiers; an empty list removes them
+#[derive(Debug, Deserialize)]
+pub struct SetPriceTiersRequest {
+    pub tiers: Vec<PriceTier>,
+}
+
+// A manual stock correction; `variant_id` is required for products with variants
+#[derive(Debug, Deserialize)]
+pub struct StockAdjustRequest {
+    pub variant_id: Option<Uuid>,
+    pub quantity_change: i32,
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddProductImageRequest {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderProductImagesRequest {
+    pub image_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferProductRequest {
+    pub target_user_id: Uuid,
 }
 
 #[derive(Debug, Deserialize)]
@@ -203,48 +622,1117 @@ pub struct ProductQuery {
     pub sort: Option<String>,
     pub page: Option<i32>,
     pub limit: Option<i32>,
+    // Search from a location; radius_km drops products farther away
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
+    pub radius_km: Option<f64>,
+    // Also show prices converted into this currency
+    pub currency: Option<String>,
+    // Only sellers who deliver to this pincode, or within their radius of lat/lng
+    pub deliverable_to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductLookupQuery {
+    pub barcode: Option<String>,
+}
+
+// Show prices converted into this currency as well
+#[derive(Debug, Deserialize)]
+pub struct CurrencyQuery {
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestQuery {
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// limit applies to each kind of result separately
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    pub limit: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AddToCartRequest {
     pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetCartQuantityRequest {
+    pub variant_id: Option<Uuid>,
+    // 0 removes the line
     pub quantity: i32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ReplaceCartRequest {
+    // The whole cart; lines left out are removed
+    pub items: Vec<CartItem>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RemoveFromCartRequest {
     pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
     pub quantity: Option<i32>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct SaveCartTemplateRequest {
+    pub name: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateOrderStatusRequest {
     pub status: OrderStatus,
+    // Only when marking the order shipped
+    #[serde(flatten)]
+    pub shipment: ShipmentDetails,
+    // Where products stocked by location ship from; the default location unless given
+    pub location_id: Option<Uuid>,
+}
+
+// How a shipped order travels; fields left out keep their current value
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ShipmentDetails {
+    pub courier_name: Option<String>,
+    pub tracking_number: Option<String>,
+    pub expected_delivery_date: Option<NaiveDate>,
+}
+
+impl ShipmentDetails {
+    pub fn is_empty(&self) -> bool {
+        self.courier_name.is_none() && self.tracking_number.is_none() && self.expected_delivery_date.is_none()
+    }
 }
 
+// The same status for several of the seller's orders, applied all or nothing
 #[derive(Debug, Deserialize)]
-pub struct UpdateProfileRequest {
-    pub name: Option<String>,
-    pub phone: Option<String>,
-    pub profile_image_url: Option<String>,
+pub struct BulkOrderStatusRequest {
+    pub order_ids: Vec<Uuid>,
+    pub status: OrderStatus,
+    // As for a single order
+    pub location_id: Option<Uuid>,
 }
 
+// A seller's pick list for one day (UTC), today when `date` is left out
 #[derive(Debug, Deserialize)]
-pub struct UpdateSettingsRequest {
-    pub become_supplier: Option<bool>,
+pub struct PicklistQuery {
+    pub date: Option<NaiveDate>,
 }
 
-// WebSocket message types
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WsMessage {
-    pub conv_id: Uuid,
-    pub content: String,
-    pub sender_id: Uuid,
-    pub sent_at: DateTime<Utc>,
+#[derive(Debug, Serialize)]
+pub struct PicklistLine {
+    pub product_id: Uuid,
+    pub product_name: String,
+    pub variant_name: Option<String>,
+    pub unit: ProductUnit,
+    pub quantity: i64,
+    pub order_count: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CategoryWithCount {
-    pub id: i32,
-    pub name: String,
-    pub product_count: i64,
-}
\ No newline at end of file
+// A note on an order between its buyer and seller
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct OrderComment {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub author_id: Uuid,
+    pub author_name: Option<String>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrderCommentRequest {
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateItemFulfillmentRequest {
+    pub item_ids: Vec<Uuid>,
+    pub status: FulfillmentStatus,
+    // As for the whole order, when shipping
+    pub location_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateReviewRequest {
+    pub rating: i32,
+    pub comment: Option<String>,
+}
+
+// Seller analytics window: a preset `range`, or explicit `from`/`to` bounds
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQuery {
+    pub range: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+// Platform report window, chosen as for seller analytics, and whether to get the
+// daily figures as CSV instead of the whole report as JSON
+#[derive(Debug, Deserialize)]
+pub struct PlatformReportQuery {
+    pub range: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+// Order history filters; `search` matches product and variant names. Buyers can
+// narrow by `seller_id` and sellers by `buyer_id`, and `to` is exclusive
+#[derive(Debug, Deserialize)]
+pub struct OrderQuery {
+    pub status: Option<OrderStatus>,
+    pub seller_id: Option<Uuid>,
+    pub buyer_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub search: Option<String>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationQuery {
+    // Only unread notifications
+    pub unread: Option<bool>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaginationQuery {
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+// Keyset page of a conversation: messages older than `before` (a message id or an
+// RFC 3339 timestamp), newest first
+#[derive(Debug, Deserialize)]
+pub struct MessageHistoryQuery {
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewProductRequest {
+    // approved or rejected
+    pub status: ProductReviewStatus,
+    // Required when rejecting; shown to the seller
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminUserQuery {
+    pub search: Option<String>,
+    pub suspended: Option<bool>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminOrderQuery {
+    pub user_id: Option<Uuid>,
+    pub status: Option<OrderStatus>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+// A sensitive action recorded in the audit log
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    PasswordChanged,
+    PasswordResetRequested,
+    PasswordReset,
+    EmailChanged,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    SettingsUpdated,
+    RoleChanged,
+    UserSuspended,
+    UserUnsuspended,
+    UserUnlocked,
+    ProductPriceChanged,
+    ProductStockChanged,
+    OrderStatusChanged,
+    ImpersonationStarted,
+    ImpersonationStopped,
+    ImpersonatedRequest,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    pub action: AuditAction,
+    pub subject_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+// Audit log filters; `user_id` matches entries by or about the user, and `to` is exclusive
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub user_id: Option<Uuid>,
+    pub action: Option<AuditAction>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+// Dispute status enum
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "dispute_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DisputeStatus {
+    Open,
+    Responded,
+    Refunded,
+    Rejected,
+}
+
+// Dispute model
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Dispute {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub buyer_id: Uuid,
+    pub seller_id: Uuid,
+    pub reason: String,
+    pub evidence_urls: Vec<String>,
+    pub status: DisputeStatus,
+    pub seller_response: Option<String>,
+    pub seller_responded_at: Option<DateTime<Utc>>,
+    pub resolution_note: Option<String>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenDisputeRequest {
+    pub reason: String,
+    pub evidence_urls: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisputeResponseRequest {
+    pub response: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisputeOutcome {
+    Refund,
+    Reject,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveDisputeRequest {
+    pub outcome: DisputeOutcome,
+    pub note: Option<String>,
+    // Put the order's items back into stock along with the refund
+    pub restock: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminDisputeQuery {
+    pub status: Option<DisputeStatus>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCategoryRequest {
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameCategoryRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeCategoryRequest {
+    pub into_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetCategoryTaxRequest {
+    // Percent; null goes back to the parent category's rate
+    pub tax_rate: Option<BigDecimal>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOfferRequest {
+    pub product_id: Uuid,
+    pub price_per_unit: BigDecimal,
+    pub quantity: i32,
+    // Required when the seller opens the negotiation
+    pub recipient_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CounterOfferRequest {
+    pub price_per_unit: BigDecimal,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OfferQuery {
+    pub status: Option<OfferStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAddressRequest {
+    pub label: Option<String>,
+    pub recipient_name: String,
+    pub phone: String,
+    pub line1: String,
+    pub line2: Option<String>,
+    pub city: String,
+    pub state: String,
+    pub postal_code: String,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub is_default: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAddressRequest {
+    pub label: Option<String>,
+    pub recipient_name: Option<String>,
+    pub phone: Option<String>,
+    pub line1: Option<String>,
+    pub line2: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub is_default: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCouponRequest {
+    pub code: String,
+    pub discount_type: DiscountType,
+    pub discount_value: BigDecimal,
+    pub min_order_value: Option<BigDecimal>,
+    pub max_uses_per_user: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyCouponRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrderRequest {
+    pub address_id: Uuid,
+    // At most one per seller in the cart; orders from other sellers go unscheduled
+    #[serde(default)]
+    pub delivery_slot_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderRequest {
+    // Place the order straight away, shipped here, rather than fill the cart
+    pub address_id: Option<Uuid>,
+}
+
+// Recurring (standing) order status enum
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "recurring_order_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RecurringOrderStatus {
+    Active,
+    Paused,
+    Cancelled,
+}
+
+// A standing order: the same items from one seller every `interval_days` days
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RecurringOrder {
+    pub id: Uuid,
+    pub buyer_id: Uuid,
+    pub seller_id: Uuid,
+    pub address_id: Option<Uuid>,
+    pub interval_days: i32,
+    pub status: RecurringOrderStatus,
+    pub next_run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// One cycle of a standing order: the order placed, or why none was
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RecurringOrderRun {
+    pub id: Uuid,
+    pub scheduled_for: DateTime<Utc>,
+    pub order_id: Option<Uuid>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRecurringOrderRequest {
+    // All from one seller
+    pub items: Vec<CartItem>,
+    pub interval_days: i32,
+    pub address_id: Uuid,
+    // The first order; one interval from now when left out
+    pub starts_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRecurringOrderRequest {
+    // Replaces every item; still all from the same seller
+    pub items: Option<Vec<CartItem>>,
+    pub interval_days: Option<i32>,
+    pub address_id: Option<Uuid>,
+}
+
+// A delivery window a seller offers, taking up to `capacity` orders
+#[derive(Debug, Deserialize)]
+pub struct CreateDeliverySlotRequest {
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub capacity: i32,
+}
+
+// Where a seller delivers, replacing what was set before; no pincodes and no radius
+// means anywhere
+#[derive(Debug, Deserialize)]
+pub struct SetDeliveryZoneRequest {
+    #[serde(default)]
+    pub pincodes: Vec<String>,
+    // Around the seller's location
+    pub radius_km: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RescheduleDeliveryRequest {
+    pub delivery_slot_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfileRequest {
+    pub name: Option<String>,
+    pub phone: Option<String>,
+    pub profile_image_url: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    // None for a token that doesn't expire
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeEmailRequest {
+    pub new_email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge_id: Uuid,
+    // An authenticator code or a recovery code
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTwoFactorRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsRequest {
+    pub become_supplier: Option<bool>,
+    // Supplier order terms; a min_order_value of 0 means no minimum
+    pub min_order_value: Option<BigDecimal>,
+    pub delivery_fee: Option<BigDecimal>,
+    // Tax registration (e.g. a GSTIN) and the registered business name; "" clears them
+    pub tax_id: Option<String>,
+    pub tax_name: Option<String>,
+    // Vacation mode: hides the seller's products and stops new orders
+    pub store_paused: Option<bool>,
+    // How long after an order is placed the seller promises to deliver it
+    pub lead_time_hours: Option<i32>,
+    // Which notifications are pushed to the user's devices while they're offline
+    pub push_messages: Option<bool>,
+    pub push_order_updates: Option<bool>,
+}
+
+// Starting a chat with a seller, from a product page when product_id is given
+#[derive(Debug, Deserialize)]
+pub struct StartConversationRequest {
+    pub seller_id: Uuid,
+    pub product_id: Option<Uuid>,
+}
+
+// To receiver_id, or into the conversation conv_id, which may be a group
+#[derive(Debug, Deserialize)]
+pub struct SendMessageRequest {
+    pub receiver_id: Option<Uuid>,
+    pub conv_id: Option<Uuid>,
+    // May be empty when the message has an attachment
+    #[serde(default)]
+    pub content: String,
+    // URL returned by POST /upload/message for this conversation
+    pub attachment_url: Option<String>,
+}
+
+// A seller's announcement to every buyer who has ordered from them
+#[derive(Debug, Deserialize)]
+pub struct BroadcastRequest {
+    // May be empty when the message has an attachment
+    #[serde(default)]
+    pub content: String,
+    // URL returned by POST /upload/message for the broadcast conversation
+    pub attachment_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditMessageRequest {
+    // May be empty when the message has an attachment
+    #[serde(default)]
+    pub content: String,
+}
+
+// Report status enum
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
+#[sqlx(type_name = "report_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    Open,
+    Dismissed,
+    Actioned,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportUserRequest {
+    pub reason: String,
+    // Also block the user in the same step
+    pub block: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveReportRequest {
+    // dismissed or actioned
+    pub status: ReportStatus,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminReportQuery {
+    pub status: Option<ReportStatus>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+// Opening a WebSocket with a ticket from POST /api/ws/ticket instead of the session cookie
+#[derive(Debug, Deserialize)]
+pub struct WsConnectQuery {
+    pub ticket: Option<String>,
+}
+
+// The WebSocket protocol version. Every frame the server sends carries it as `version`;
+// a client frame may leave it out, and is refused if it names another version.
+pub const WS_PROTOCOL_VERSION: u32 = 1;
+
+// A frame sent by the client, tagged by `type`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    // To `receiver_id`, or the other participant of `conv_id`; `content` may be left
+    // out when there's an attachment
+    Message {
+        receiver_id: Option<Uuid>,
+        conv_id: Option<Uuid>,
+        content: Option<String>,
+        attachment_url: Option<String>,
+    },
+    TypingStart {
+        conv_id: Uuid,
+    },
+    TypingStop {
+        conv_id: Uuid,
+    },
+    // Mark the conversation's messages to this user read
+    Read {
+        conv_id: Uuid,
+    },
+    // To `receiver_id`, or the other participant of `conv_id`
+    Offer {
+        product_id: Uuid,
+        price: BigDecimal,
+        qty: i32,
+        receiver_id: Option<Uuid>,
+        conv_id: Option<Uuid>,
+    },
+    // Any type this version of the protocol doesn't have
+    #[serde(other)]
+    Unknown,
+}
+
+// A frame pushed to the client, tagged by `type`
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Message {
+        id: Uuid,
+        conv_id: Uuid,
+        sender_id: Uuid,
+        sender_name: Option<String>,
+        content: String,
+        attachment_url: Option<String>,
+        attachment_type: Option<String>,
+        sent_at: DateTime<Utc>,
+        read_at: Option<DateTime<Utc>>,
+        edited_at: Option<DateTime<Utc>>,
+        deleted_at: Option<DateTime<Utc>>,
+    },
+    MessageEdited {
+        id: Uuid,
+        conv_id: Uuid,
+        content: String,
+        edited_at: DateTime<Utc>,
+    },
+    MessageDeleted {
+        id: Uuid,
+        conv_id: Uuid,
+        deleted_at: DateTime<Utc>,
+    },
+    Read {
+        conv_id: Uuid,
+        reader_id: Uuid,
+        read_at: DateTime<Utc>,
+    },
+    TypingStart {
+        conv_id: Uuid,
+        user_id: Uuid,
+    },
+    TypingStop {
+        conv_id: Uuid,
+        user_id: Uuid,
+    },
+    Offer {
+        id: Uuid,
+        conv_id: Uuid,
+        sender_id: Uuid,
+        sender_name: Option<String>,
+        content: String,
+        sent_at: DateTime<Utc>,
+        offer: Offer,
+    },
+    OrderUpdate {
+        order_id: Uuid,
+        status: OrderStatus,
+        total_price: BigDecimal,
+        currency: String,
+    },
+    OrderComment {
+        comment: OrderComment,
+    },
+    Notification {
+        notification: Notification,
+        unread_count: i64,
+    },
+    ProductTransfer {
+        product_id: Uuid,
+        product_name: String,
+        from_seller_id: Uuid,
+    },
+    PriceDrop {
+        product_id: Uuid,
+        variant_id: Option<Uuid>,
+        product_name: String,
+        old_price: BigDecimal,
+        new_price: BigDecimal,
+        currency: String,
+    },
+    // A client frame that was refused
+    Error {
+        code: WsErrorCode,
+        message: String,
+    },
+}
+
+// Why a client frame was refused
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WsErrorCode {
+    // Not JSON, or an event missing a field or with one of the wrong type
+    InvalidFrame,
+    FrameTooLarge,
+    UnsupportedVersion,
+    UnknownEvent,
+    // The event was well formed but couldn't be carried out
+    BadRequest,
+    Forbidden,
+    NotFound,
+    Conflict,
+    InternalError,
+}
+
+// URLs of the resized variants generated for an uploaded image
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageUrls {
+    pub thumbnail: String,
+    pub medium: String,
+    pub full: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryWithCount {
+    pub id: i32,
+    pub name: String,
+    pub product_count: i64,
+}
+
+// What a seller ledger entry records
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "ledger_entry_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerEntryKind {
+    Sale,
+    Refund,
+    Payout,
+}
+
+impl LedgerEntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerEntryKind::Sale => "sale",
+            LedgerEntryKind::Refund => "refund",
+            LedgerEntryKind::Payout => "payout",
+        }
+    }
+}
+
+// An entry in a seller's ledger; amounts are signed from the seller's side, so credits
+// are positive and refunds and payouts negative
+#[derive(Debug, Serialize, FromRow)]
+pub struct LedgerEntry {
+    pub id: Uuid,
+    pub order_id: Option<Uuid>,
+    pub kind: LedgerEntryKind,
+    pub gross_amount: BigDecimal,
+    pub commission_amount: BigDecimal,
+    pub amount: BigDecimal,
+    pub currency: String,
+    pub reference: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// What the platform owes a seller in one currency
+#[derive(Debug, Serialize, FromRow)]
+pub struct SellerBalance {
+    pub currency: String,
+    // Sales less refunds, before commission
+    pub gross: BigDecimal,
+    pub commission: BigDecimal,
+    pub paid_out: BigDecimal,
+    pub balance: BigDecimal,
+}
+
+// The ledger window; `to` is exclusive
+#[derive(Debug, Deserialize)]
+pub struct EarningsQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+// A payout already sent to the seller outside the platform
+#[derive(Debug, Deserialize)]
+pub struct RecordPayoutRequest {
+    pub amount: BigDecimal,
+    // Defaults to the base currency
+    pub currency: Option<String>,
+    pub reference: Option<String>,
+}
+
+// What a webhook can be told about
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "webhook_event")]
+pub enum WebhookEvent {
+    #[sqlx(rename = "order.created")]
+    #[serde(rename = "order.created")]
+    OrderCreated,
+    #[sqlx(rename = "order.status_changed")]
+    #[serde(rename = "order.status_changed")]
+    OrderStatusChanged,
+    #[sqlx(rename = "product.low_stock")]
+    #[serde(rename = "product.low_stock")]
+    ProductLowStock,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "webhook_delivery_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    // Not yet answered with a 2xx; retried at next_attempt_at
+    Pending,
+    Delivered,
+    // Out of attempts
+    Failed,
+}
+
+// A URL the seller's events are POSTed to. The secret signs each delivery and is only
+// shown when the webhook is created.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// One event sent, or still to be sent, to a webhook
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    // Replaces the subscribed events
+    pub events: Option<Vec<WebhookEvent>>,
+    // A paused webhook queues nothing; what's already queued is still sent
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookDeliveryQuery {
+    pub status: Option<WebhookDeliveryStatus>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+// An RFQ is open until it's awarded, cancelled or past expires_at
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "rfq_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RfqStatus {
+    Open,
+    Awarded,
+    Cancelled,
+}
+
+// A buyer's request for quote, for a product or for anything in a category
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Rfq {
+    pub id: Uuid,
+    pub buyer_id: Uuid,
+    pub buyer_name: Option<String>,
+    pub product_id: Option<Uuid>,
+    pub product_name: Option<String>,
+    pub category_id: Option<i32>,
+    pub category_name: Option<String>,
+    pub quantity: i32,
+    pub target_price: Option<BigDecimal>,
+    pub note: Option<String>,
+    pub status: RfqStatus,
+    pub expires_at: DateTime<Utc>,
+    pub awarded_quote_id: Option<Uuid>,
+    pub quote_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+// A supplier's answer to an RFQ; its terms are those of the offer it opened
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Quote {
+    pub id: Uuid,
+    pub rfq_id: Uuid,
+    pub seller_id: Uuid,
+    pub seller_name: Option<String>,
+    pub product_id: Uuid,
+    pub product_name: String,
+    pub offer_id: Uuid,
+    pub price_per_unit: BigDecimal,
+    pub quantity: i32,
+    pub currency: String,
+    pub offer_status: OfferStatus,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRfqRequest {
+    // Exactly one of product_id and category_id
+    pub product_id: Option<Uuid>,
+    pub category_id: Option<i32>,
+    pub quantity: i32,
+    // Per unit
+    pub target_price: Option<BigDecimal>,
+    pub note: Option<String>,
+    // Seven days from now unless given
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RfqQuery {
+    pub status: Option<RfqStatus>,
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateQuoteRequest {
+    // One of the supplier's products the RFQ covers
+    pub product_id: Uuid,
+    pub price_per_unit: BigDecimal,
+    // The RFQ's quantity unless given
+    pub quantity: Option<i32>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptQuoteRequest {
+    pub address_id: Uuid,
+}
+
+// A stall or warehouse a supplier keeps stock at
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Location {
+    pub id: Uuid,
+    pub name: String,
+    pub address: Option<String>,
+    pub is_default: bool,
+    // Distinct products and units on hand here
+    pub product_count: i64,
+    pub units_on_hand: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLocationRequest {
+    pub name: String,
+    pub address: Option<String>,
+    // A seller's first location is the default regardless
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateLocationRequest {
+    pub name: Option<String>,
+    pub address: Option<String>,
+    // Only true: making another location the default is how this one stops being it
+    pub is_default: Option<bool>,
+}
+
+// How much of a product is on hand at one location
+#[derive(Debug, Deserialize)]
+pub struct SetLocationStockRequest {
+    pub quantity: i32,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferStockRequest {
+    pub from_location_id: Uuid,
+    pub to_location_id: Uuid,
+    pub quantity: i32,
+}
+
+// A flash deal: the product sells at deal_price from starts_at to ends_at, for up to
+// max_quantity units in all
+#[derive(Debug, Serialize, FromRow)]
+pub struct Deal {
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub deal_price: BigDecimal,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub max_quantity: i32,
+    pub sold_quantity: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDealRequest {
+    pub deal_price: BigDecimal,
+    // Now when omitted or past
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: DateTime<Utc>,
+    pub max_quantity: i32,
+}
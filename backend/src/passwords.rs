@@ -0,0 +1,137 @@
+// passwords.rs
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::{BreachCheck, CharacterClass, Config, MAX_PASSWORD_LENGTH};
+use crate::errors::AppResult;
+use crate::validation::{FieldErrors, Validate};
+
+const HIBP_RANGE_URL: &str = "https://api.pwnedpasswords.com/range";
+// A slow lookup shouldn't hold up registration for long
+const HIBP_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Read on first use and kept for the life of the process, lowercased
+static BREACH_LIST: OnceLock<HashSet<String>> = OnceLock::new();
+
+static HTTP: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Check a request that sets a password: its own fields and the password policy,
+/// reported together in one 422
+pub async fn validate(config: &Config, req: &impl Validate, field: &str, password: &str) -> AppResult<()> {
+    let mut errors = FieldErrors::default();
+    req.check(&mut errors);
+    check(config, field, password, &mut errors).await;
+    errors.into_result()
+}
+
+/// Record each rule of the policy the password breaks. It's only looked up among
+/// breached passwords once it meets the others, and a lookup that fails lets it through
+/// rather than blocking sign-ups.
+pub async fn check(config: &Config, field: &str, password: &str, errors: &mut FieldErrors) {
+    let mut meets_policy = true;
+    let mut broken = |errors: &mut FieldErrors, message: String| {
+        errors.add(field, message);
+        meets_policy = false;
+    };
+
+    let length = password.chars().count();
+    if length < config.password_min_length {
+        broken(errors, format!("must be at least {} characters", config.password_min_length));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        broken(errors, format!("must be at most {} characters", MAX_PASSWORD_LENGTH));
+    }
+    for class in &config.password_required_classes {
+        if !password.chars().any(|c| is_of_class(*class, c)) {
+            broken(errors, format!("must contain {}", describe(*class)));
+        }
+    }
+
+    if meets_policy && is_breached(config, password).await {
+        errors.add(field, "appears in a list of breached passwords; choose another");
+    }
+}
+
+fn is_of_class(class: CharacterClass, c: char) -> bool {
+    match class {
+        CharacterClass::Letter => c.is_alphabetic(),
+        CharacterClass::Lowercase => c.is_lowercase(),
+        CharacterClass::Uppercase => c.is_uppercase(),
+        CharacterClass::Digit => c.is_ascii_digit(),
+        CharacterClass::Symbol => !c.is_alphanumeric(),
+    }
+}
+
+fn describe(class: CharacterClass) -> &'static str {
+    match class {
+        CharacterClass::Letter => "a letter",
+        CharacterClass::Lowercase => "a lowercase letter",
+        CharacterClass::Uppercase => "an uppercase letter",
+        CharacterClass::Digit => "a digit",
+        CharacterClass::Symbol => "a symbol",
+    }
+}
+
+async fn is_breached(config: &Config, password: &str) -> bool {
+    let breached = match config.password_breach_check {
+        BreachCheck::Off => return false,
+        BreachCheck::List => in_breach_list(config, password).await,
+        BreachCheck::Hibp => in_hibp(password).await,
+    };
+
+    breached.unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "Breached password lookup failed; accepting the password");
+        false
+    })
+}
+
+/// Whether the password is on the configured list, ignoring case
+async fn in_breach_list(config: &Config, password: &str) -> Result<bool, String> {
+    let list = match BREACH_LIST.get() {
+        Some(list) => list,
+        None => {
+            let path = config
+                .password_breach_list
+                .as_deref()
+                .ok_or_else(|| "PASSWORD_BREACH_LIST is not set".to_string())?;
+            // Lists taken from breaches aren't always valid UTF-8
+            let contents = tokio::fs::read(path).await.map_err(|e| format!("{}: {}", path, e))?;
+            let list = String::from_utf8_lossy(&contents)
+                .lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty())
+                .collect();
+            BREACH_LIST.get_or_init(|| list)
+        }
+    };
+
+    Ok(list.contains(&password.to_lowercase()))
+}
+
+/// Whether Have I Been Pwned has seen the password. Only the first five characters of
+/// its SHA-1 are sent; the response lists the suffixes of every breached password
+/// sharing them, padded with decoys whose count is 0.
+async fn in_hibp(password: &str) -> Result<bool, String> {
+    let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(5);
+
+    let body = HTTP
+        .get_or_init(reqwest::Client::new)
+        .get(format!("{}/{}", HIBP_RANGE_URL, prefix))
+        .header("Add-Padding", "true")
+        .timeout(HIBP_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(body.lines().any(|line| {
+        line.split_once(':')
+            .is_some_and(|(candidate, count)| candidate.eq_ignore_ascii_case(suffix) && count.trim() != "0")
+    }))
+}
@@ -0,0 +1,157 @@
+// payments.rs
+use bigdecimal::{BigDecimal, ToPrimitive};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+
+const STRIPE_API_BASE: &str = "https://api.stripe.com/v1";
+// Reject webhook deliveries signed more than this many seconds ago (replay protection)
+const WEBHOOK_TOLERANCE_SECONDS: i64 = 300;
+
+/// Minimal Stripe client covering the PaymentIntent flow
+pub struct StripeClient {
+    secret_key: String,
+    http: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentIntent {
+    pub id: String,
+    pub client_secret: Option<String>,
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Refund {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StripeEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub data: StripeEventData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StripeEventData {
+    pub object: PaymentIntent,
+}
+
+impl StripeClient {
+    pub fn from_config(config: &Config) -> AppResult<Self> {
+        let secret_key = config
+            .stripe_secret_key
+            .clone()
+            .ok_or_else(|| AppError::PaymentError("Payments are not configured".to_string()))?;
+
+        Ok(StripeClient {
+            secret_key,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// Create a PaymentIntent for an order, in the order's currency; the order id travels in metadata
+    pub async fn create_payment_intent(&self, order_id: Uuid, amount: &BigDecimal, currency: &str) -> AppResult<PaymentIntent> {
+        let amount_minor = to_minor_units(amount)?;
+
+        let response = self
+            .http
+            .post(format!("{}/payment_intents", STRIPE_API_BASE))
+            .basic_auth(&self.secret_key, None::<&str>)
+            // Retrying the same order returns the same intent instead of charging twice
+            .header("Idempotency-Key", format!("order-{}-{}", order_id, amount_minor))
+            .form(&[
+                ("amount", amount_minor.to_string()),
+                ("currency", currency.to_lowercase()),
+                ("metadata[order_id]", order_id.to_string()),
+                ("automatic_payment_methods[enabled]", "true".to_string()),
+            ])
+            .send()
+            .await
+            .map_err(|e| AppError::PaymentError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            tracing::error!(%order_id, response = %body, "Stripe rejected PaymentIntent");
+            return Err(AppError::PaymentError("Payment provider rejected the request".to_string()));
+        }
+
+        response
+            .json::<PaymentIntent>()
+            .await
+            .map_err(|e| AppError::PaymentError(e.to_string()))
+    }
+
+    /// Refund a PaymentIntent in full; keyed on the dispute so a retry can't refund twice
+    pub async fn refund_payment_intent(&self, payment_intent_id: &str, dispute_id: Uuid) -> AppResult<Refund> {
+        let response = self
+            .http
+            .post(format!("{}/refunds", STRIPE_API_BASE))
+            .basic_auth(&self.secret_key, None::<&str>)
+            .header("Idempotency-Key", format!("dispute-{}", dispute_id))
+            .form(&[
+                ("payment_intent", payment_intent_id.to_string()),
+                ("metadata[dispute_id]", dispute_id.to_string()),
+            ])
+            .send()
+            .await
+            .map_err(|e| AppError::PaymentError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            tracing::error!(%dispute_id, response = %body, "Stripe rejected Refund");
+            return Err(AppError::PaymentError("Payment provider rejected the refund".to_string()));
+        }
+
+        response
+            .json::<Refund>()
+            .await
+            .map_err(|e| AppError::PaymentError(e.to_string()))
+    }
+}
+
+/// Convert a decimal amount into the smallest currency unit (e.g. paise)
+fn to_minor_units(amount: &BigDecimal) -> AppResult<i64> {
+    (amount * BigDecimal::from(100))
+        .round(0)
+        .to_i64()
+        .filter(|minor| *minor > 0)
+        .ok_or_else(|| AppError::BadRequest("Invalid payment amount".to_string()))
+}
+
+/// Verify a `Stripe-Signature` header against the raw request body
+pub fn verify_webhook_signature(payload: &[u8], header: &str, secret: &str, now: i64) -> bool {
+    let mut timestamp = None;
+    let mut signatures = vec![];
+
+    for part in header.split(',') {
+        match part.split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+
+    let timestamp = match timestamp {
+        Some(t) if (now - t).abs() <= WEBHOOK_TOLERANCE_SECONDS => t,
+        _ => return false,
+    };
+
+    signatures.iter().any(|signature| {
+        let Ok(expected) = hex::decode(signature) else {
+            return false;
+        };
+        let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+            return false;
+        };
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(payload);
+        mac.verify_slice(&expected).is_ok()
+    })
+}
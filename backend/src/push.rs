@@ -0,0 +1,289 @@
+// push.rs
+use async_trait::async_trait;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::broker;
+use crate::config::{Config, PushProvider};
+use crate::models::{Notification, NotificationKind, PushPlatform};
+
+/// A notification as the device shows it, with the data the app gets alongside
+#[derive(Debug, Clone)]
+pub struct Push {
+    pub title: String,
+    /// FCM only carries string values
+    pub data: HashMap<String, String>,
+}
+
+#[derive(Error, Debug)]
+pub enum PushError {
+    /// The token no longer reaches a device (the app was removed or notifications
+    /// were turned off), so the device should be forgotten
+    #[error("Push token is no longer registered")]
+    Unregistered,
+    #[error("Push delivery failed: {0}")]
+    Failed(String),
+}
+
+#[async_trait]
+pub trait PushSender: Send + Sync {
+    async fn send(&self, platform: PushPlatform, token: &str, push: &Push) -> Result<(), PushError>;
+}
+
+static SENDER: OnceLock<Arc<dyn PushSender>> = OnceLock::new();
+
+/// Pick the push transport for this process (FCM, or the log by default); call once at
+/// startup. Until then (as in the tests) pushes are only logged.
+pub fn init(config: &Config) -> Result<(), PushError> {
+    let sender: Arc<dyn PushSender> = match config.push_provider {
+        PushProvider::Log => Arc::new(LogSender),
+        PushProvider::Fcm => {
+            let path = config
+                .fcm_credentials_file
+                .as_deref()
+                .ok_or_else(|| PushError::Failed("FCM_CREDENTIALS_FILE is not set".to_string()))?;
+            Arc::new(FcmSender::from_file(path)?)
+        }
+    };
+
+    tracing::info!("Push notifications via {:?}", config.push_provider);
+    let _ = SENDER.set(sender);
+    Ok(())
+}
+
+/// Push a stored notification to the user's devices without holding up the request.
+/// Only messages and order updates are pushed, and only while the user is offline.
+pub fn dispatch(pool: &PgPool, user_id: Uuid, notification: &Notification) {
+    if !matches!(notification.kind, NotificationKind::Message | NotificationKind::Order) {
+        return;
+    }
+
+    let mut data = HashMap::from([
+        ("notification_id".to_string(), notification.id.to_string()),
+        ("kind".to_string(), json!(notification.kind).as_str().unwrap_or_default().to_string()),
+    ]);
+    if let Some(fields) = notification.data.as_object() {
+        for (key, value) in fields {
+            let value = match value {
+                serde_json::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            data.insert(key.clone(), value);
+        }
+    }
+
+    let pool = pool.clone();
+    let kind = notification.kind;
+    let push = Push {
+        title: notification.title.clone(),
+        data,
+    };
+    actix_web::rt::spawn(async move {
+        if let Err(e) = deliver(&pool, user_id, kind, &push).await {
+            tracing::error!(%user_id, error = %e, "Failed to push notification");
+        }
+    }.in_current_span());
+}
+
+async fn deliver(pool: &PgPool, user_id: Uuid, kind: NotificationKind, push: &Push) -> Result<(), sqlx::Error> {
+    if broker::is_online(user_id).await {
+        return Ok(());
+    }
+
+    let preferences = sqlx::query!(
+        "SELECT push_messages, push_order_updates FROM users WHERE id = $1 AND deleted_at IS NULL",
+        user_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    let wanted = match (preferences, kind) {
+        (Some(preferences), NotificationKind::Message) => preferences.push_messages,
+        (Some(preferences), _) => preferences.push_order_updates,
+        (None, _) => false,
+    };
+    if !wanted {
+        return Ok(());
+    }
+
+    let devices = sqlx::query!(
+        r#"SELECT id, platform as "platform: PushPlatform", token FROM push_devices WHERE user_id = $1"#,
+        user_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let sender = SENDER.get().cloned().unwrap_or_else(|| Arc::new(LogSender));
+    for device in devices {
+        match sender.send(device.platform, &device.token, push).await {
+            Ok(()) => {
+                sqlx::query!("UPDATE push_devices SET last_used_at = NOW() WHERE id = $1", device.id)
+                    .execute(pool)
+                    .await?;
+            }
+            Err(PushError::Unregistered) => {
+                tracing::info!(%user_id, device_id = %device.id, "Forgetting unregistered push device");
+                sqlx::query!("DELETE FROM push_devices WHERE id = $1", device.id)
+                    .execute(pool)
+                    .await?;
+            }
+            Err(e) => {
+                tracing::warn!(%user_id, device_id = %device.id, error = %e, "Push not delivered");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub struct LogSender;
+
+#[async_trait]
+impl PushSender for LogSender {
+    async fn send(&self, platform: PushPlatform, _token: &str, push: &Push) -> Result<(), PushError> {
+        tracing::info!(?platform, title = %push.title, data = ?push.data, "Push (not sent)");
+        Ok(())
+    }
+}
+
+const FCM_API_BASE: &str = "https://fcm.googleapis.com/v1/projects";
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+// How long the signed grant asks the access token to last, and how long before it
+// expires a new one is fetched
+const ACCESS_TOKEN_SECONDS: i64 = 3600;
+const ACCESS_TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+/// The fields of a Firebase service account key that sending needs
+#[derive(Deserialize)]
+struct ServiceAccount {
+    project_id: String,
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// FCM HTTP v1, authorized as a service account with a short-lived OAuth access token
+pub struct FcmSender {
+    account: ServiceAccount,
+    key: EncodingKey,
+    http: reqwest::Client,
+    access_token: Mutex<Option<(String, Instant)>>,
+}
+
+impl FcmSender {
+    pub fn from_file(path: &str) -> Result<Self, PushError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| PushError::Failed(format!("Can't read FCM credentials from {}: {}", path, e)))?;
+        let account: ServiceAccount = serde_json::from_str(&contents)
+            .map_err(|e| PushError::Failed(format!("{} is not a service account key: {}", path, e)))?;
+        let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+            .map_err(|e| PushError::Failed(format!("Invalid private key in {}: {}", path, e)))?;
+
+        Ok(FcmSender {
+            account,
+            key,
+            http: reqwest::Client::new(),
+            access_token: Mutex::new(None),
+        })
+    }
+
+    /// The cached access token, or a new one exchanged for a signed grant once it's
+    /// about to expire
+    async fn access_token(&self) -> Result<String, PushError> {
+        let mut cached = self.access_token.lock().await;
+        if let Some((token, refresh_at)) = cached.as_ref()
+            && Instant::now() < *refresh_at
+        {
+            return Ok(token.clone());
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = json!({
+            "iss": self.account.client_email,
+            "scope": FCM_SCOPE,
+            "aud": self.account.token_uri,
+            "iat": now,
+            "exp": now + ACCESS_TOKEN_SECONDS
+        });
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .map_err(|e| PushError::Failed(e.to_string()))?;
+
+        let response = self
+            .http
+            .post(&self.account.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| PushError::Failed(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(PushError::Failed(format!("FCM authorization was refused: {}", body)));
+        }
+
+        let token = response
+            .json::<AccessToken>()
+            .await
+            .map_err(|e| PushError::Failed(e.to_string()))?;
+        let refresh_at = Instant::now() + Duration::from_secs(token.expires_in).saturating_sub(ACCESS_TOKEN_MARGIN);
+        *cached = Some((token.access_token.clone(), refresh_at));
+
+        Ok(token.access_token)
+    }
+}
+
+#[async_trait]
+impl PushSender for FcmSender {
+    async fn send(&self, platform: PushPlatform, token: &str, push: &Push) -> Result<(), PushError> {
+        let access_token = self.access_token().await?;
+
+        let mut message = json!({
+            "token": token,
+            "notification": { "title": push.title },
+            "data": push.data
+        });
+        // Shown straight away rather than batched to save battery
+        match platform {
+            PushPlatform::Android => message["android"] = json!({ "priority": "high" }),
+            PushPlatform::Ios => message["apns"] = json!({ "headers": { "apns-priority": "10" } }),
+            PushPlatform::Web => message["webpush"] = json!({ "headers": { "Urgency": "high" } }),
+        }
+
+        let response = self
+            .http
+            .post(format!("{}/{}/messages:send", FCM_API_BASE, self.account.project_id))
+            .bearer_auth(access_token)
+            .json(&json!({ "message": message }))
+            .send()
+            .await
+            .map_err(|e| PushError::Failed(e.to_string()))?;
+
+        // FCM answers 404 (UNREGISTERED) for tokens whose app instance is gone
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::NOT_FOUND => Err(PushError::Unregistered),
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                Err(PushError::Failed(format!("FCM answered {}: {}", status, body)))
+            }
+        }
+    }
+}
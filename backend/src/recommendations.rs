@@ -0,0 +1,241 @@
+// recommendations.rs
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+use crate::models::{ProductReviewStatus, ProductUnit, ProductWithSeller};
+
+pub const RELATED_LIMIT: i64 = 12;
+pub const RECOMMENDATION_LIMIT: i64 = 20;
+const CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// What a buyer's recommendations were drawn from
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecommendationBasis {
+    OrderHistory,
+    /// Nothing ordered yet: the most bought products of the last 30 days
+    Popular,
+}
+
+struct CachedRecommendations {
+    computed_at: Instant,
+    basis: RecommendationBasis,
+    product_ids: Vec<Uuid>,
+}
+
+static CACHE: OnceLock<Mutex<HashMap<Uuid, CachedRecommendations>>> = OnceLock::new();
+
+fn cache() -> &'static Mutex<HashMap<Uuid, CachedRecommendations>> {
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Forget the user's recommendations, e.g. once a new order changes their history
+pub fn invalidate(user_id: Uuid) {
+    cache().lock().unwrap().remove(&user_id);
+}
+
+/// Listed products like this one, best match first
+pub async fn related(pool: &PgPool, product_id: Uuid, viewer_id: Option<Uuid>) -> AppResult<Vec<ProductWithSeller>> {
+    let product_ids = sqlx::query_scalar!(
+        r#"
+        WITH source AS (
+            SELECT id, category_id, seller_id FROM products WHERE id = $1
+        ),
+        buyers AS (
+            SELECT DISTINCT o.buyer_id
+            FROM orders o
+            JOIN order_items oi ON oi.order_id = o.id
+            WHERE oi.product_id = $1 AND o.status NOT IN ('cancelled', 'failed', 'refunded')
+        ),
+        co_purchased AS (
+            SELECT oi.product_id, COUNT(DISTINCT o.buyer_id) as buyers
+            FROM orders o
+            JOIN order_items oi ON oi.order_id = o.id
+            WHERE o.buyer_id IN (SELECT buyer_id FROM buyers)
+              AND oi.product_id <> $1
+              AND o.status NOT IN ('cancelled', 'failed', 'refunded')
+            GROUP BY oi.product_id
+        )
+        SELECT p.id
+        FROM products p
+        CROSS JOIN source s
+        LEFT JOIN co_purchased cp ON cp.product_id = p.id
+        WHERE p.id <> s.id AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+          AND (cp.product_id IS NOT NULL OR p.category_id = s.category_id OR p.seller_id = s.seller_id)
+        ORDER BY COALESCE(cp.buyers, 0) * 3
+                 + (p.category_id = s.category_id)::int * 2
+                 + (p.seller_id = s.seller_id)::int DESC,
+                 p.created_at DESC
+        LIMIT $2
+        "#,
+        product_id,
+        RELATED_LIMIT
+    )
+        .fetch_all(pool)
+        .await?;
+
+    load_products(pool, &product_ids, viewer_id).await
+}
+
+/// Products the user hasn't bought yet, ranked from their order history
+pub async fn for_user(pool: &PgPool, user_id: Uuid) -> AppResult<(RecommendationBasis, Vec<ProductWithSeller>)> {
+    let cached = cache()
+        .lock()
+        .unwrap()
+        .get(&user_id)
+        .filter(|cached| cached.computed_at.elapsed() < CACHE_TTL)
+        .map(|cached| (cached.basis, cached.product_ids.clone()));
+
+    let (basis, product_ids) = match cached {
+        Some(cached) => cached,
+        None => {
+            let ranked = rank_for_user(pool, user_id).await?;
+            let mut cache = cache().lock().unwrap();
+            cache.retain(|_, cached| cached.computed_at.elapsed() < CACHE_TTL);
+            cache.insert(user_id, CachedRecommendations {
+                computed_at: Instant::now(),
+                basis: ranked.0,
+                product_ids: ranked.1.clone(),
+            });
+            ranked
+        }
+    };
+
+    Ok((basis, load_products(pool, &product_ids, Some(user_id)).await?))
+}
+
+async fn rank_for_user(pool: &PgPool, user_id: Uuid) -> AppResult<(RecommendationBasis, Vec<Uuid>)> {
+    let product_ids = sqlx::query_scalar!(
+        r#"
+        WITH history AS (
+            SELECT oi.product_id, p.category_id, p.seller_id
+            FROM orders o
+            JOIN order_items oi ON oi.order_id = o.id
+            JOIN products p ON p.id = oi.product_id
+            WHERE o.buyer_id = $1 AND o.status NOT IN ('cancelled', 'failed', 'refunded')
+        ),
+        bought_categories AS (
+            SELECT category_id, COUNT(*) as lines FROM history GROUP BY category_id
+        ),
+        bought_sellers AS (
+            SELECT seller_id, COUNT(*) as lines FROM history GROUP BY seller_id
+        ),
+        similar_buyers AS (
+            SELECT DISTINCT o.buyer_id
+            FROM orders o
+            JOIN order_items oi ON oi.order_id = o.id
+            WHERE oi.product_id IN (SELECT product_id FROM history)
+              AND o.buyer_id <> $1
+              AND o.status NOT IN ('cancelled', 'failed', 'refunded')
+        ),
+        co_purchased AS (
+            SELECT oi.product_id, COUNT(DISTINCT o.buyer_id) as buyers
+            FROM orders o
+            JOIN order_items oi ON oi.order_id = o.id
+            WHERE o.buyer_id IN (SELECT buyer_id FROM similar_buyers)
+              AND o.status NOT IN ('cancelled', 'failed', 'refunded')
+            GROUP BY oi.product_id
+        )
+        SELECT p.id
+        FROM products p
+        LEFT JOIN co_purchased cp ON cp.product_id = p.id
+        LEFT JOIN bought_categories bc ON bc.category_id = p.category_id
+        LEFT JOIN bought_sellers bs ON bs.seller_id = p.seller_id
+        WHERE p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+          AND p.seller_id <> $1
+          AND p.id NOT IN (SELECT product_id FROM history)
+          AND (cp.product_id IS NOT NULL OR bc.category_id IS NOT NULL OR bs.seller_id IS NOT NULL)
+        ORDER BY COALESCE(cp.buyers, 0) * 3 + COALESCE(bc.lines, 0) * 2 + COALESCE(bs.lines, 0) DESC,
+                 p.created_at DESC
+        LIMIT $2
+        "#,
+        user_id,
+        RECOMMENDATION_LIMIT
+    )
+        .fetch_all(pool)
+        .await?;
+
+    if !product_ids.is_empty() {
+        return Ok((RecommendationBasis::OrderHistory, product_ids));
+    }
+
+    let product_ids = sqlx::query_scalar!(
+        r#"
+        SELECT p.id
+        FROM products p
+        LEFT JOIN (
+            SELECT oi.product_id, COUNT(DISTINCT o.buyer_id) as buyers
+            FROM orders o
+            JOIN order_items oi ON oi.order_id = o.id
+            WHERE o.created_at > NOW() - INTERVAL '30 days'
+              AND o.status NOT IN ('cancelled', 'failed', 'refunded')
+            GROUP BY oi.product_id
+        ) popular ON popular.product_id = p.id
+        WHERE p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+          AND p.seller_id <> $1
+        ORDER BY COALESCE(popular.buyers, 0) DESC, p.created_at DESC
+        LIMIT $2
+        "#,
+        user_id,
+        RECOMMENDATION_LIMIT
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok((RecommendationBasis::Popular, product_ids))
+}
+
+/// The products in ranking order, skipping any that sold out, were delisted or whose
+/// seller paused their store since
+async fn load_products(pool: &PgPool, product_ids: &[Uuid], viewer_id: Option<Uuid>) -> AppResult<Vec<ProductWithSeller>> {
+    let products = sqlx::query_as!(
+        ProductWithSeller,
+        r#"
+        SELECT
+            p.id, p.name, p.description, p.price_per_unit, p.currency, p.stock_qty,
+            p.unit as "unit: ProductUnit", p.min_increment, p.sku, p.barcode,
+            p.image_url, p.seller_id, p.category_id, p.created_at,
+            c.name as category_name,
+            u.name as seller_name, u.name as seller_company,
+            u.rating as seller_rating, u.total_deliveries as seller_deliveries,
+            u.min_order_value as seller_min_order_value, u.delivery_fee as seller_delivery_fee,
+            COALESCE(
+                (SELECT json_agg(json_build_object('id', pi.id, 'url', pi.url) ORDER BY pi.position)
+                 FROM product_images pi WHERE pi.product_id = p.id),
+                '[]'::json
+            ) as "images!",
+            COALESCE(
+                (SELECT json_agg(json_build_object('min_qty', t.min_qty, 'unit_price', t.unit_price::text) ORDER BY t.min_qty)
+                 FROM price_tiers t WHERE t.product_id = p.id),
+                '[]'::json
+            ) as "price_tiers!",
+            EXISTS(SELECT 1 FROM favorites f WHERE f.product_id = p.id AND f.user_id = $2) as "is_favorited!",
+            NULL::float8 as distance_km,
+            (SELECT COUNT(*) FROM stock_by_location s WHERE s.product_id = p.id AND s.quantity > 0) as "stock_locations!",
+            current_deal(p.id) as deal,
+            NULL::numeric as display_price, NULL::text as display_currency,
+            p.review_status as "review_status: ProductReviewStatus"
+        FROM products p
+        JOIN categories c ON p.category_id = c.id
+        JOIN users u ON p.seller_id = u.id
+        WHERE p.id = ANY($1) AND p.stock_qty > 0 AND p.taken_down_at IS NULL AND p.deleted_at IS NULL
+          AND p.review_status = 'approved'
+          AND NOT u.store_paused
+        ORDER BY array_position($1, p.id)
+        "#,
+        product_ids,
+        viewer_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(products)
+}
@@ -0,0 +1,176 @@
+// recurring_orders.rs
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::{Connection, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::handlers::address_handlers::shipping_snapshot;
+use crate::handlers::notification_handlers::{notify, notify_low_stock};
+use crate::handlers::order_handlers::announce_order_update;
+use crate::models::{NotificationKind, RecurringOrder};
+use crate::recommendations;
+use crate::repositories::recurring_order_repository;
+use crate::services::order_service::{self, StockChanges};
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Place standing orders as they come due, until the server stops. Runs lock their
+/// standing order and skip locked ones, so several instances can run this side by side.
+pub async fn run(pool: PgPool) {
+    let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        ticker.tick().await;
+        match place_due(&pool).await {
+            Ok(0) => {}
+            Ok(runs) => tracing::info!(runs, "Ran standing orders"),
+            Err(e) => tracing::error!(error = %e, "Standing order run failed"),
+        }
+    }
+}
+
+/// Run every standing order that is due, one at a time. Returns how many ran, placed
+/// or not.
+pub async fn place_due(pool: &PgPool) -> AppResult<usize> {
+    let mut runs = 0;
+
+    loop {
+        let mut tx = pool.begin().await?;
+        let Some(recurring_order) = recurring_order_repository::lock_next_due(&mut tx).await? else {
+            break;
+        };
+        let next_run_at = next_run_after(recurring_order.next_run_at, recurring_order.interval_days, Utc::now());
+
+        // A savepoint, so a cycle that can't be placed still records its run
+        let mut attempt = tx.begin().await?;
+        let outcome = match place(pool, &mut attempt, &recurring_order).await {
+            Ok(placed) => {
+                attempt.commit().await?;
+                Ok(placed)
+            }
+            Err(e) => {
+                attempt.rollback().await?;
+                Err(failure_reason(e)?)
+            }
+        };
+
+        recurring_order_repository::record_run(
+            &mut tx,
+            recurring_order.id,
+            recurring_order.next_run_at,
+            outcome.as_ref().ok().map(|(order_id, _)| *order_id),
+            outcome.as_ref().err().map(String::as_str),
+            next_run_at,
+        ).await?;
+        tx.commit().await?;
+
+        announce(pool, &recurring_order, outcome).await?;
+        runs += 1;
+    }
+
+    Ok(runs)
+}
+
+/// When the cycle after `run_at` falls, skipping any that are already past by `now`
+pub fn next_run_after(run_at: DateTime<Utc>, interval_days: i32, now: DateTime<Utc>) -> DateTime<Utc> {
+    let interval = Duration::days(interval_days.into());
+    let missed = (now - run_at).num_seconds().max(0) / interval.num_seconds();
+    run_at + interval * (missed as i32 + 1)
+}
+
+/// Place this cycle's order, returning it and the stock it took
+async fn place(
+    pool: &PgPool,
+    conn: &mut PgConnection,
+    recurring_order: &RecurringOrder,
+) -> AppResult<(Uuid, StockChanges)> {
+    let buyer_id = recurring_order.buyer_id;
+    let seller_id = recurring_order.seller_id;
+
+    let Some(address_id) = recurring_order.address_id else {
+        return Err(AppError::BadRequest(
+            "The delivery address was deleted; choose another for this standing order".to_string(),
+        ));
+    };
+    let shipping_address = shipping_snapshot(pool, buyer_id, address_id).await?;
+
+    // Items whose product was purged are gone from the standing order
+    let items = recurring_order_repository::items(&mut *conn, recurring_order.id).await?;
+    if items.is_empty() {
+        return Err(AppError::BadRequest("None of the standing order's items are for sale any more".to_string()));
+    }
+
+    order_service::place_seller_order(conn, buyer_id, seller_id, &shipping_address, &items).await
+}
+
+/// What to tell the buyer when a cycle couldn't be placed. Only database and internal
+/// errors aren't the standing order's fault; they abort the run, which is retried on
+/// the next check. Anything else is a failed run, so one bad standing order can't hold
+/// up the ones due after it.
+fn failure_reason(error: AppError) -> AppResult<String> {
+    match error {
+        AppError::DatabaseError(_) | AppError::InternalError => Err(error),
+        AppError::BadRequest(reason) | AppError::NotFound(reason) | AppError::Conflict(reason) => Ok(reason),
+        error => Ok(error.to_string()),
+    }
+}
+
+async fn announce(
+    pool: &PgPool,
+    recurring_order: &RecurringOrder,
+    outcome: Result<(Uuid, StockChanges), String>,
+) -> AppResult<()> {
+    match outcome {
+        Ok((order_id, stock_changes)) => {
+            recommendations::invalidate(recurring_order.buyer_id);
+            announce_order_update(pool, order_id).await?;
+            notify(
+                pool,
+                recurring_order.seller_id,
+                NotificationKind::Order,
+                "You have a new order",
+                json!({ "order_id": order_id, "recurring_order_id": recurring_order.id }),
+            ).await?;
+            notify(
+                pool,
+                recurring_order.buyer_id,
+                NotificationKind::Order,
+                "Your standing order was placed",
+                json!({ "order_id": order_id, "recurring_order_id": recurring_order.id }),
+            ).await?;
+            for (product_id, variant_id, stock_before, stock_after) in stock_changes {
+                notify_low_stock(pool, product_id, variant_id, stock_before, stock_after).await?;
+            }
+        }
+        Err(reason) => {
+            tracing::info!(recurring_order_id = %recurring_order.id, %reason, "Standing order couldn't be placed");
+            notify(
+                pool,
+                recurring_order.buyer_id,
+                NotificationKind::Order,
+                "Your standing order couldn't be placed",
+                json!({ "recurring_order_id": recurring_order.id, "reason": reason }),
+            ).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_server_errors_abort_a_run() {
+        let reason = failure_reason(AppError::BadRequest("Only 2 of Rice left; 5 were ordered".to_string()));
+        assert_eq!(reason.unwrap(), "Only 2 of Rice left; 5 were ordered");
+        assert_eq!(failure_reason(AppError::Forbidden).unwrap(), "Forbidden");
+        let reason = failure_reason(AppError::Undeliverable(vec![json!({ "seller_id": Uuid::nil() })]));
+        assert_eq!(reason.unwrap(), "1 seller(s) don't deliver to this address");
+
+        assert!(matches!(failure_reason(AppError::InternalError), Err(AppError::InternalError)));
+        let error = failure_reason(AppError::DatabaseError(sqlx::Error::PoolTimedOut));
+        assert!(matches!(error, Err(AppError::DatabaseError(_))));
+    }
+}
@@ -0,0 +1,84 @@
+// repositories/audit_repository.rs
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+use crate::models::{AuditAction, AuditLogEntry, AuditLogQuery};
+use crate::utils::Pagination;
+
+/// One entry for the audit log
+pub struct AuditEntry {
+    // None when the system did it, or for a request made without a login
+    pub actor_id: Option<Uuid>,
+    pub action: AuditAction,
+    // The user, product or order the action was done to
+    pub subject_id: Option<Uuid>,
+    pub details: Value,
+}
+
+/// Add an entry to the audit log. Where the action changes data, call it in the same
+/// transaction as the change, so the log holds exactly what happened.
+pub async fn record<'e>(executor: impl PgExecutor<'e>, entry: AuditEntry) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (id, actor_id, action, subject_id, details)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        entry.actor_id,
+        entry.action as AuditAction,
+        entry.subject_id,
+        entry.details
+    )
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// A page of the entries matching the filters, newest first, and how many match in all
+pub async fn list(pool: &PgPool, query: &AuditLogQuery, pagination: &Pagination) -> AppResult<(Vec<AuditLogEntry>, i64)> {
+    let entries = sqlx::query_as!(
+        AuditLogEntry,
+        r#"
+        SELECT a.id, a.actor_id, u.email as "actor_email?", a.action as "action: AuditAction",
+               a.subject_id, a.details, a.created_at
+        FROM audit_log a
+        LEFT JOIN users u ON a.actor_id = u.id
+        WHERE ($1::uuid IS NULL OR a.actor_id = $1 OR a.subject_id = $1)
+          AND ($2::audit_action IS NULL OR a.action = $2)
+          AND ($3::timestamptz IS NULL OR a.created_at >= $3)
+          AND ($4::timestamptz IS NULL OR a.created_at < $4)
+        ORDER BY a.created_at DESC, a.id
+        LIMIT $5 OFFSET $6
+        "#,
+        query.user_id,
+        query.action as Option<AuditAction>,
+        query.from,
+        query.to,
+        pagination.limit,
+        pagination.offset
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM audit_log a
+        WHERE ($1::uuid IS NULL OR a.actor_id = $1 OR a.subject_id = $1)
+          AND ($2::audit_action IS NULL OR a.action = $2)
+          AND ($3::timestamptz IS NULL OR a.created_at >= $3)
+          AND ($4::timestamptz IS NULL OR a.created_at < $4)
+        "#,
+        query.user_id,
+        query.action as Option<AuditAction>,
+        query.from,
+        query.to
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok((entries, total))
+}
@@ -0,0 +1,433 @@
+// repositories/cart_repository.rs
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{CartIssue, CartItem, CartLineIssue};
+use crate::repositories::reservation_repository;
+
+/// Session key of a guest's cookie cart, merged into the DB cart on login
+pub const CART_SESSION_KEY: &str = "cart";
+
+/// Get all items in a user's cart, oldest first
+pub async fn get_items(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<CartItem>> {
+    let items = sqlx::query_as!(
+        CartItem,
+        r#"
+        SELECT product_id, variant_id, quantity
+        FROM cart_items
+        WHERE user_id = $1
+        ORDER BY added_at ASC
+        "#,
+        user_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(items)
+}
+
+/// Get the quantity of a single product (or variant) in a user's cart
+pub async fn get_quantity(
+    pool: &PgPool,
+    user_id: Uuid,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+) -> AppResult<Option<i32>> {
+    let quantity = sqlx::query_scalar!(
+        r#"
+        SELECT quantity FROM cart_items
+        WHERE user_id = $1 AND product_id = $2 AND variant_id IS NOT DISTINCT FROM $3
+        "#,
+        user_id,
+        product_id,
+        variant_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(quantity)
+}
+
+/// Count distinct lines (products or variants) in a user's cart
+pub async fn count_items(pool: &PgPool, user_id: Uuid) -> AppResult<i64> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM cart_items WHERE user_id = $1"#,
+        user_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count)
+}
+
+/// Make sure the user has a cart row and bump its timestamp
+async fn touch_cart(conn: &mut PgConnection, user_id: Uuid) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO carts (user_id, updated_at) VALUES ($1, NOW())
+        ON CONFLICT (user_id) DO UPDATE SET updated_at = NOW()
+        "#,
+        user_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Add a quantity of a product (or one of its variants), increasing it if already in the cart.
+/// Holds stock for the new cart quantity, failing if other buyers' holds leave too little.
+pub async fn add_item(
+    pool: &PgPool,
+    user_id: Uuid,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    quantity: i32,
+    reserve_minutes: i32,
+) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+
+    let in_cart = sqlx::query_scalar!(
+        r#"
+        SELECT quantity FROM cart_items
+        WHERE user_id = $1 AND product_id = $2 AND variant_id IS NOT DISTINCT FROM $3
+        "#,
+        user_id,
+        product_id,
+        variant_id
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .unwrap_or(0);
+
+    reservation_repository::reserve(&mut tx, user_id, product_id, variant_id, in_cart + quantity, reserve_minutes)
+        .await?;
+    touch_cart(&mut tx, user_id).await?;
+
+    // A line keeps the price it was first added at, so a later change shows up at validation
+    sqlx::query!(
+        r#"
+        INSERT INTO cart_items (user_id, product_id, variant_id, quantity, price_when_added)
+        SELECT $1, p.id, $3, $4, COALESCE(v.price_per_unit, p.price_per_unit)
+        FROM products p
+        LEFT JOIN product_variants v ON v.id = $3
+        WHERE p.id = $2
+        ON CONFLICT (user_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'::uuid))
+        DO UPDATE SET quantity = cart_items.quantity + EXCLUDED.quantity
+        "#,
+        user_id,
+        product_id,
+        variant_id,
+        quantity
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Set the exact quantity of a product (or variant), holding stock for it; zero or less removes it
+pub async fn set_quantity(
+    pool: &PgPool,
+    user_id: Uuid,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    quantity: i32,
+    reserve_minutes: i32,
+) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+    set_quantity_in(&mut tx, user_id, product_id, variant_id, quantity, reserve_minutes).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn set_quantity_in(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    quantity: i32,
+    reserve_minutes: i32,
+) -> AppResult<()> {
+    if quantity <= 0 {
+        delete_line(&mut *conn, user_id, product_id, variant_id).await?;
+        return Ok(());
+    }
+
+    reservation_repository::reserve(&mut *conn, user_id, product_id, variant_id, quantity, reserve_minutes).await?;
+    touch_cart(&mut *conn, user_id).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO cart_items (user_id, product_id, variant_id, quantity, price_when_added)
+        SELECT $1, p.id, $3, $4, COALESCE(v.price_per_unit, p.price_per_unit)
+        FROM products p
+        LEFT JOIN product_variants v ON v.id = $3
+        WHERE p.id = $2
+        ON CONFLICT (user_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'::uuid))
+        DO UPDATE SET quantity = EXCLUDED.quantity
+        "#,
+        user_id,
+        product_id,
+        variant_id,
+        quantity
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Set several quantities at once in a single transaction
+pub async fn set_quantities(pool: &PgPool, user_id: Uuid, items: &[CartItem], reserve_minutes: i32) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+    for item in items {
+        set_quantity_in(&mut tx, user_id, item.product_id, item.variant_id, item.quantity, reserve_minutes).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Make the cart exactly `items`: lines not among them are removed with their holds and
+/// the rest set to their quantity, holding stock for it. All or nothing; an error names
+/// the item it's about by its index.
+pub async fn replace_items(pool: &PgPool, user_id: Uuid, items: &[CartItem], reserve_minutes: i32) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+
+    let current = sqlx::query!(
+        "SELECT product_id, variant_id FROM cart_items WHERE user_id = $1",
+        user_id
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for line in current {
+        let kept = items
+            .iter()
+            .any(|item| item.product_id == line.product_id && item.variant_id == line.variant_id);
+        if !kept {
+            delete_line(&mut tx, user_id, line.product_id, line.variant_id).await?;
+        }
+    }
+
+    for (index, item) in items.iter().enumerate() {
+        set_quantity_in(&mut tx, user_id, item.product_id, item.variant_id, item.quantity, reserve_minutes)
+            .await
+            .map_err(|e| about_item(index, e))?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Say which of a request's items a stock or lookup error is about
+pub fn about_item(index: usize, error: AppError) -> AppError {
+    match error {
+        AppError::BadRequest(message) => AppError::BadRequest(format!("items[{}]: {}", index, message)),
+        AppError::NotFound(message) => AppError::NotFound(format!("items[{}]: {}", index, message)),
+        e => e,
+    }
+}
+
+/// Remove a product (or variant) from the cart entirely, releasing its hold
+pub async fn remove_item(pool: &PgPool, user_id: Uuid, product_id: Uuid, variant_id: Option<Uuid>) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+    delete_line(&mut tx, user_id, product_id, variant_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn delete_line(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM cart_items
+        WHERE user_id = $1 AND product_id = $2 AND variant_id IS NOT DISTINCT FROM $3
+        "#,
+        user_id,
+        product_id,
+        variant_id
+    )
+        .execute(&mut *conn)
+        .await?;
+    reservation_repository::release(conn, user_id, product_id, variant_id).await?;
+
+    Ok(())
+}
+
+/// Empty the cart, release its holds and drop its coupon, e.g. inside the order-creation transaction
+pub async fn clear(conn: &mut PgConnection, user_id: Uuid) -> AppResult<()> {
+    sqlx::query!("DELETE FROM cart_items WHERE user_id = $1", user_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!("UPDATE carts SET coupon_id = NULL WHERE user_id = $1", user_id)
+        .execute(&mut *conn)
+        .await?;
+    reservation_repository::release_all(conn, user_id).await?;
+
+    Ok(())
+}
+
+/// Check every line against current stock and prices, oldest first. A line that can still be
+/// bought may have two issues: less stock than its quantity, and a new price.
+pub async fn check_lines(conn: &mut PgConnection, user_id: Uuid) -> AppResult<Vec<CartLineIssue>> {
+    let lines = sqlx::query!(
+        r#"
+        SELECT ci.product_id, ci.variant_id, ci.quantity, ci.price_when_added,
+               p.name, v.name as "variant_name?", p.min_increment,
+               COALESCE(v.price_per_unit, p.price_per_unit) as "current_price!",
+               (p.taken_down_at IS NOT NULL OR p.deleted_at IS NOT NULL OR p.review_status <> 'approved'
+                OR (ci.variant_id IS NULL
+                    AND EXISTS(SELECT 1 FROM product_variants pv WHERE pv.product_id = p.id))) as "unavailable!"
+        FROM cart_items ci
+        JOIN products p ON ci.product_id = p.id
+        LEFT JOIN product_variants v ON ci.variant_id = v.id
+        WHERE ci.user_id = $1
+        ORDER BY ci.added_at ASC
+        "#,
+        user_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let keys: Vec<_> = lines.iter().map(|line| (line.product_id, line.variant_id)).collect();
+    let available = reservation_repository::available_stock(&mut *conn, &keys, Some(user_id)).await?;
+
+    let mut issues = vec![];
+    for line in lines {
+        let stock = available.get(&(line.product_id, line.variant_id)).copied().unwrap_or(0);
+        // Only whole multiples of the increment can be bought
+        let stock = reservation_repository::whole_increments(stock, line.min_increment);
+
+        let mut line_issues = vec![];
+        if line.unavailable {
+            line_issues.push(CartIssue::Unavailable);
+        } else if stock <= 0 {
+            line_issues.push(CartIssue::OutOfStock);
+        } else {
+            if line.quantity % line.min_increment != 0 {
+                let quantity = reservation_repository::whole_increments(line.quantity, line.min_increment)
+                    .max(line.min_increment)
+                    .min(stock);
+                line_issues.push(CartIssue::QuantityRounded { requested: line.quantity, quantity });
+            } else if stock < line.quantity {
+                line_issues.push(CartIssue::QuantityReduced { requested: line.quantity, available: stock });
+            }
+            if line.current_price != line.price_when_added {
+                line_issues.push(CartIssue::PriceChanged {
+                    old_price: line.price_when_added.clone(),
+                    new_price: line.current_price.clone(),
+                });
+            }
+        }
+
+        issues.extend(line_issues.into_iter().map(|issue| CartLineIssue {
+            product_id: line.product_id,
+            variant_id: line.variant_id,
+            name: line.name.clone(),
+            variant_name: line.variant_name.clone(),
+            issue,
+        }));
+    }
+
+    Ok(issues)
+}
+
+/// Bring the cart in line with the catalog: drop lines that can't be bought, cut quantities
+/// down to the stock left and take on current prices. Returns what was changed.
+pub async fn reconcile(pool: &PgPool, user_id: Uuid, reserve_minutes: i32) -> AppResult<Vec<CartLineIssue>> {
+    let mut tx = pool.begin().await?;
+
+    let issues = check_lines(&mut tx, user_id).await?;
+
+    for line in &issues {
+        match &line.issue {
+            CartIssue::Unavailable | CartIssue::OutOfStock => {
+                delete_line(&mut tx, user_id, line.product_id, line.variant_id).await?;
+            }
+            CartIssue::QuantityReduced { available: quantity, .. } | CartIssue::QuantityRounded { quantity, .. } => {
+                set_quantity_in(&mut tx, user_id, line.product_id, line.variant_id, *quantity, reserve_minutes)
+                    .await?;
+            }
+            CartIssue::PriceChanged { new_price, .. } => {
+                sqlx::query!(
+                    r#"
+                    UPDATE cart_items SET price_when_added = $4
+                    WHERE user_id = $1 AND product_id = $2 AND variant_id IS NOT DISTINCT FROM $3
+                    "#,
+                    user_id,
+                    line.product_id,
+                    line.variant_id,
+                    new_price
+                )
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(issues)
+}
+
+/// Fold items (e.g. a pre-login session cart) into the user's DB cart. A line already in the
+/// cart gets the quantities summed, capped at the stock on hand (but never cut below what the
+/// cart already had); lines with no stock are skipped. Merged items hold no stock until their
+/// quantity next changes; checkout re-checks availability.
+pub async fn merge_items(pool: &PgPool, user_id: Uuid, items: &[CartItem]) -> AppResult<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    let product_ids: Vec<Uuid> = items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<Option<Uuid>> = items.iter().map(|item| item.variant_id).collect();
+    let quantities: Vec<i32> = items.iter().map(|item| item.quantity).collect();
+
+    let mut tx = pool.begin().await?;
+    touch_cart(&mut tx, user_id).await?;
+
+    // Skip products and variants that no longer exist, were deleted or are sold out rather
+    // than failing the whole login
+    sqlx::query!(
+        r#"
+        INSERT INTO cart_items (user_id, product_id, variant_id, quantity, price_when_added)
+        SELECT $1, item.product_id, item.variant_id,
+               LEAST(SUM(item.quantity), COALESCE(v.stock_qty, p.stock_qty))::int,
+               COALESCE(v.price_per_unit, p.price_per_unit)
+        FROM UNNEST($2::uuid[], $3::uuid[], $4::int[]) AS item(product_id, variant_id, quantity)
+        JOIN products p ON p.id = item.product_id
+        LEFT JOIN product_variants v ON v.id = item.variant_id AND v.product_id = p.id
+        WHERE item.quantity > 0 AND p.deleted_at IS NULL AND COALESCE(v.stock_qty, p.stock_qty) > 0
+          AND (item.variant_id IS NULL OR v.id IS NOT NULL)
+          AND (item.variant_id IS NOT NULL
+               OR NOT EXISTS(SELECT 1 FROM product_variants pv WHERE pv.product_id = p.id))
+        GROUP BY item.product_id, item.variant_id, p.price_per_unit, p.stock_qty, v.price_per_unit, v.stock_qty
+        ON CONFLICT (user_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'::uuid))
+        DO UPDATE SET quantity = GREATEST(
+            cart_items.quantity,
+            LEAST(
+                cart_items.quantity + EXCLUDED.quantity,
+                (SELECT COALESCE(v.stock_qty, p.stock_qty)
+                 FROM products p
+                 LEFT JOIN product_variants v ON v.id = EXCLUDED.variant_id
+                 WHERE p.id = EXCLUDED.product_id)
+            )
+        )
+        "#,
+        user_id,
+        &product_ids,
+        &variant_ids as &[Option<Uuid>],
+        &quantities
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+// repositories/deal_repository.rs
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+
+/// Whether the product has a deal on now or still to come
+pub async fn has_open_deal(conn: &mut PgConnection, product_id: Uuid) -> AppResult<bool> {
+    let open = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM deals WHERE product_id = $1 AND ends_at > NOW()) as "exists!""#,
+        product_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    Ok(open)
+}
+
+/// Count a line priced at the deal, as `unit_deal` found it, against the deal. Call with
+/// the product locked, as checkout does, in the transaction that priced and places the
+/// order, so the units `unit_deal` saw left are still there.
+pub async fn claim(conn: &mut PgConnection, deal_id: Uuid, quantity: i32) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE deals SET sold_quantity = sold_quantity + $2 WHERE id = $1",
+        deal_id,
+        quantity
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
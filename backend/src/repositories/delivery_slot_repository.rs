@@ -0,0 +1,78 @@
+// repositories/delivery_slot_repository.rs
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+
+/// A delivery slot and how many orders hold a place in it; cancelled and refunded
+/// orders don't
+pub struct DeliverySlot {
+    pub id: Uuid,
+    pub seller_id: Uuid,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub capacity: i32,
+    pub booked: i64,
+}
+
+impl DeliverySlot {
+    pub fn remaining(&self) -> i64 {
+        (self.capacity as i64 - self.booked).max(0)
+    }
+}
+
+/// Lock the slots in id order, so bookings of the same slot run one at a time and
+/// can't overfill it. Call it inside the caller's transaction.
+pub async fn lock(conn: &mut PgConnection, slot_ids: &[Uuid]) -> AppResult<Vec<DeliverySlot>> {
+    let slots = sqlx::query_as!(
+        DeliverySlot,
+        r#"
+        SELECT s.id, s.seller_id, s.starts_at, s.ends_at, s.capacity,
+               (SELECT COUNT(*) FROM orders o
+                WHERE o.delivery_slot_id = s.id AND o.status NOT IN ('cancelled', 'refunded')) as "booked!"
+        FROM delivery_slots s
+        WHERE s.id = ANY($1)
+        ORDER BY s.id
+        FOR UPDATE
+        "#,
+        slot_ids
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(slots)
+}
+
+/// A seller's slots that haven't ended yet, soonest first
+pub async fn upcoming(pool: &PgPool, seller_id: Uuid) -> AppResult<Vec<DeliverySlot>> {
+    let slots = sqlx::query_as!(
+        DeliverySlot,
+        r#"
+        SELECT s.id, s.seller_id, s.starts_at, s.ends_at, s.capacity,
+               (SELECT COUNT(*) FROM orders o
+                WHERE o.delivery_slot_id = s.id AND o.status NOT IN ('cancelled', 'refunded')) as "booked!"
+        FROM delivery_slots s
+        WHERE s.seller_id = $1 AND s.ends_at > NOW()
+        ORDER BY s.starts_at, s.id
+        "#,
+        seller_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(slots)
+}
+
+/// Point the order at a slot; the caller has locked and checked it
+pub async fn book(conn: &mut PgConnection, order_id: Uuid, slot_id: Uuid) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE orders SET delivery_slot_id = $2 WHERE id = $1",
+        order_id,
+        slot_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
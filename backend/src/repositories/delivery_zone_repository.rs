@@ -0,0 +1,94 @@
+// repositories/delivery_zone_repository.rs
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+
+/// Where a seller delivers: any of the pincodes, or within radius_km of their location.
+/// Neither means anywhere.
+pub struct DeliveryZone {
+    pub pincodes: Vec<String>,
+    pub radius_km: Option<f64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// A seller who can't deliver to an address
+pub struct Undeliverable {
+    pub seller_id: Uuid,
+    pub seller_name: Option<String>,
+}
+
+pub async fn find(pool: &PgPool, seller_id: Uuid) -> AppResult<DeliveryZone> {
+    let zone = sqlx::query_as!(
+        DeliveryZone,
+        r#"
+        SELECT ARRAY(
+                   SELECT pincode FROM seller_delivery_pincodes
+                   WHERE seller_id = u.id
+                   ORDER BY pincode
+               ) as "pincodes!",
+               u.delivery_radius_km as radius_km, u.latitude, u.longitude
+        FROM users u
+        WHERE u.id = $1
+        "#,
+        seller_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok(zone)
+}
+
+/// Replace the seller's zone. Pincodes must already be normalized.
+pub async fn replace(conn: &mut PgConnection, seller_id: Uuid, pincodes: &[String], radius_km: Option<f64>) -> AppResult<()> {
+    sqlx::query!("DELETE FROM seller_delivery_pincodes WHERE seller_id = $1", seller_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO seller_delivery_pincodes (seller_id, pincode)
+        SELECT $1, pincode FROM UNNEST($2::text[]) AS pincode
+        ON CONFLICT DO NOTHING
+        "#,
+        seller_id,
+        pincodes
+    )
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        "UPDATE users SET delivery_radius_km = $2 WHERE id = $1",
+        seller_id,
+        radius_km
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Which of the sellers don't deliver to the pincode and coordinates
+pub async fn undeliverable(
+    conn: &mut PgConnection,
+    seller_ids: &[Uuid],
+    pincode: &str,
+    location: Option<(f64, f64)>,
+) -> AppResult<Vec<Undeliverable>> {
+    let sellers = sqlx::query_as!(
+        Undeliverable,
+        r#"
+        SELECT id as seller_id, name as seller_name
+        FROM users
+        WHERE id = ANY($1) AND NOT delivers_to(id, $2, $3, $4)
+        ORDER BY name, id
+        "#,
+        seller_ids,
+        pincode,
+        location.map(|(latitude, _)| latitude),
+        location.map(|(_, longitude)| longitude)
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(sellers)
+}
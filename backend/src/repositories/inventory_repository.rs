@@ -0,0 +1,44 @@
+// repositories/inventory_repository.rs
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+use crate::models::InventoryReason;
+
+/// One change to a product's stock, or to a variant's when `variant_id` is set
+pub struct StockMovement<'a> {
+    pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub quantity_change: i32,
+    pub stock_after: i32,
+    pub reason: InventoryReason,
+    pub note: Option<&'a str>,
+    pub order_id: Option<Uuid>,
+    // None when the system made the change, e.g. a cancellation by the payment provider
+    pub actor_id: Option<Uuid>,
+}
+
+/// Append a stock change to the product's inventory history; call it in the same
+/// transaction as the change itself
+pub async fn record(conn: &mut PgConnection, movement: StockMovement<'_>) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO inventory_movements (id, product_id, variant_id, quantity_change, stock_after,
+                                         reason, note, order_id, actor_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        Uuid::new_v4(),
+        movement.product_id,
+        movement.variant_id,
+        movement.quantity_change,
+        movement.stock_after,
+        movement.reason as InventoryReason,
+        movement.note,
+        movement.order_id,
+        movement.actor_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
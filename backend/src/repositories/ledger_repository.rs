@@ -0,0 +1,162 @@
+// repositories/ledger_repository.rs
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+use crate::models::{LedgerEntry, LedgerEntryKind, SellerBalance};
+
+/// Credit a delivered order to its seller: the order total less `commission_percent` of
+/// its goods after discounts. An order is credited once, however often it is delivered.
+pub async fn record_sale(conn: &mut PgConnection, order_id: Uuid, commission_percent: &BigDecimal) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO ledger_entries (id, seller_id, order_id, kind, gross_amount, commission_amount, amount, currency)
+        SELECT $1, o.seller_id, o.id, 'sale', o.total_price, c.commission, o.total_price - c.commission, o.currency
+        FROM orders o
+        CROSS JOIN LATERAL (
+            SELECT ROUND((o.subtotal_price - o.discount_amount) * $3 / 100, 2) as commission
+        ) c
+        WHERE o.id = $2
+        ON CONFLICT DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        commission_percent
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Take back a refunded order's credit, commission included. Orders refunded before
+/// delivery were never credited and record nothing.
+pub async fn record_refund(conn: &mut PgConnection, order_id: Uuid) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO ledger_entries (id, seller_id, order_id, kind, gross_amount, commission_amount, amount, currency)
+        SELECT $1, seller_id, order_id, 'refund', -gross_amount, -commission_amount, -amount, currency
+        FROM ledger_entries
+        WHERE order_id = $2 AND kind = 'sale'
+        ON CONFLICT DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        order_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Record money sent to the seller; `amount` is positive and is entered as a debit
+pub async fn record_payout(
+    conn: &mut PgConnection,
+    seller_id: Uuid,
+    amount: &BigDecimal,
+    currency: &str,
+    reference: Option<&str>,
+    admin_id: Uuid,
+) -> AppResult<LedgerEntry> {
+    let entry = sqlx::query_as!(
+        LedgerEntry,
+        r#"
+        INSERT INTO ledger_entries (id, seller_id, kind, gross_amount, amount, currency, reference, created_by)
+        VALUES ($1, $2, 'payout', -$3::numeric, -$3::numeric, $4, $5, $6)
+        RETURNING id, order_id, kind as "kind: LedgerEntryKind", gross_amount, commission_amount, amount,
+                  currency, reference, created_at
+        "#,
+        Uuid::new_v4(),
+        seller_id,
+        amount,
+        currency,
+        reference,
+        admin_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    Ok(entry)
+}
+
+/// The seller's balance in each currency they have entries in
+pub async fn balances(conn: &mut PgConnection, seller_id: Uuid) -> AppResult<Vec<SellerBalance>> {
+    let balances = sqlx::query_as!(
+        SellerBalance,
+        r#"
+        SELECT currency,
+               COALESCE(SUM(gross_amount) FILTER (WHERE kind <> 'payout'), 0) as "gross!",
+               COALESCE(SUM(commission_amount), 0) as "commission!",
+               COALESCE(-SUM(amount) FILTER (WHERE kind = 'payout'), 0) as "paid_out!",
+               SUM(amount) as "balance!"
+        FROM ledger_entries
+        WHERE seller_id = $1
+        GROUP BY currency
+        ORDER BY currency
+        "#,
+        seller_id
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(balances)
+}
+
+/// The seller's entries in [from, to), newest first; `limit` None returns them all
+pub async fn entries(
+    pool: &PgPool,
+    seller_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+    offset: i64,
+) -> AppResult<Vec<LedgerEntry>> {
+    let entries = sqlx::query_as!(
+        LedgerEntry,
+        r#"
+        SELECT id, order_id, kind as "kind: LedgerEntryKind", gross_amount, commission_amount, amount,
+               currency, reference, created_at
+        FROM ledger_entries
+        WHERE seller_id = $1
+          AND ($2::timestamptz IS NULL OR created_at >= $2)
+          AND ($3::timestamptz IS NULL OR created_at < $3)
+        ORDER BY created_at DESC, id
+        LIMIT $4 OFFSET $5
+        "#,
+        seller_id,
+        from,
+        to,
+        limit,
+        offset
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(entries)
+}
+
+pub async fn count_entries(
+    pool: &PgPool,
+    seller_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> AppResult<i64> {
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM ledger_entries
+        WHERE seller_id = $1
+          AND ($2::timestamptz IS NULL OR created_at >= $2)
+          AND ($3::timestamptz IS NULL OR created_at < $3)
+        "#,
+        seller_id,
+        from,
+        to
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok(total)
+}
@@ -0,0 +1,191 @@
+// repositories/location_repository.rs
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+
+struct ShipFrom {
+    id: Uuid,
+    name: String,
+}
+
+/// Whether the product's stock is kept by location: once it has stock recorded at any of
+/// its seller's locations, its stock_qty follows what's on hand there
+pub async fn is_stocked_by_location(conn: &mut PgConnection, product_id: Uuid) -> AppResult<bool> {
+    let stocked = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM stock_by_location WHERE product_id = $1) as "exists!""#,
+        product_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    Ok(stocked)
+}
+
+/// Set a product's stock_qty from its locations: the units on hand less those sold and
+/// awaiting shipment. Call with the product row locked, after changing its quantities;
+/// returns the stock before and after. Refused if the locations would hold fewer units
+/// than are awaiting shipment.
+pub async fn refresh_stock(conn: &mut PgConnection, product_id: Uuid) -> AppResult<(i32, i32)> {
+    // Unshipped items of orders that still hold the stock taken at checkout
+    let stock = sqlx::query!(
+        r#"
+        SELECT p.stock_qty,
+               (SELECT COALESCE(SUM(s.quantity), 0) FROM stock_by_location s WHERE s.product_id = p.id)::int4 as "on_hand!",
+               (SELECT COALESCE(SUM(oi.quantity), 0)
+                FROM order_items oi
+                JOIN orders o ON o.id = oi.order_id
+                WHERE oi.product_id = p.id AND oi.variant_name IS NULL AND oi.fulfillment_status = 'unfulfilled'
+                  AND o.status IN ('pending', 'paid', 'failed', 'partially_shipped', 'disputed'))::int4 as "awaiting_shipment!"
+        FROM products p
+        WHERE p.id = $1
+        "#,
+        product_id
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+
+    if stock.on_hand < stock.awaiting_shipment {
+        return Err(AppError::Conflict(format!(
+            "{} sold units are awaiting shipment; at least that many must stay on hand",
+            stock.awaiting_shipment
+        )));
+    }
+
+    let stock_after = stock.on_hand - stock.awaiting_shipment;
+    sqlx::query!("UPDATE products SET stock_qty = $2 WHERE id = $1", product_id, stock_after)
+        .execute(conn)
+        .await?;
+
+    Ok((stock.stock_qty, stock_after))
+}
+
+/// Move a product's stock at its seller's locations into `new_seller_id`'s default
+/// location, when the product changes hands. Call in the same transaction as the
+/// transfer; refused if the product is stocked by location and they have no default.
+pub async fn move_stock(conn: &mut PgConnection, product_id: Uuid, new_seller_id: Uuid) -> AppResult<()> {
+    if !is_stocked_by_location(&mut *conn, product_id).await? {
+        return Ok(());
+    }
+    let Some(location) = ship_from(&mut *conn, new_seller_id, None).await? else {
+        return Err(AppError::BadRequest(
+            "The new owner needs a stock location to take this product's stock".to_string(),
+        ));
+    };
+
+    sqlx::query!(
+        r#"
+        WITH moved AS (
+            DELETE FROM stock_by_location WHERE product_id = $2 RETURNING quantity
+        )
+        INSERT INTO stock_by_location (location_id, product_id, quantity)
+        SELECT $1, $2, SUM(quantity)::int4 FROM moved
+        "#,
+        location.id,
+        product_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// The seller's location to ship from: the one given, or their default
+async fn ship_from(conn: &mut PgConnection, seller_id: Uuid, location_id: Option<Uuid>) -> AppResult<Option<ShipFrom>> {
+    let location = sqlx::query_as!(
+        ShipFrom,
+        r#"
+        SELECT id, name FROM locations
+        WHERE seller_id = $1 AND (id = $2 OR ($2::uuid IS NULL AND is_default))
+        "#,
+        seller_id,
+        location_id
+    )
+        .fetch_optional(conn)
+        .await?;
+
+    if location.is_none() && location_id.is_some() {
+        return Err(AppError::NotFound("Location not found".to_string()));
+    }
+    Ok(location)
+}
+
+/// Take the order's unshipped items, or just `item_ids` of them, from the location they
+/// ship from, for products stocked by location; other items don't need one. Call before
+/// marking the items shipped, in the same transaction.
+pub async fn take_for_shipment(
+    conn: &mut PgConnection,
+    seller_id: Uuid,
+    order_id: Uuid,
+    item_ids: Option<&[Uuid]>,
+    location_id: Option<Uuid>,
+) -> AppResult<()> {
+    let location = ship_from(&mut *conn, seller_id, location_id).await?;
+
+    // Per product, in id order so concurrent shipments lock the rows in the same order
+    let products = sqlx::query!(
+        r#"
+        SELECT oi.product_id, p.name, SUM(oi.quantity)::int4 as "quantity!", array_agg(oi.id) as "item_ids!"
+        FROM order_items oi
+        JOIN products p ON p.id = oi.product_id
+        WHERE oi.order_id = $1 AND oi.fulfillment_status = 'unfulfilled' AND oi.variant_name IS NULL
+          AND ($2::uuid[] IS NULL OR oi.id = ANY($2))
+          AND EXISTS(SELECT 1 FROM stock_by_location s WHERE s.product_id = oi.product_id)
+        GROUP BY oi.product_id, p.name
+        ORDER BY oi.product_id
+        "#,
+        order_id,
+        item_ids
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    if products.is_empty() {
+        return Ok(());
+    }
+    // The first location a seller adds is their default, so this is only a seller without any
+    let Some(location) = location else {
+        return Err(AppError::BadRequest("Choose a location_id to ship from".to_string()));
+    };
+
+    for product in &products {
+        let taken = sqlx::query_scalar!(
+            r#"
+            UPDATE stock_by_location
+            SET quantity = quantity - $3, updated_at = NOW()
+            WHERE location_id = $1 AND product_id = $2 AND quantity >= $3
+            RETURNING quantity
+            "#,
+            location.id,
+            product.product_id,
+            product.quantity
+        )
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        if taken.is_none() {
+            let on_hand = sqlx::query_scalar!(
+                "SELECT quantity FROM stock_by_location WHERE location_id = $1 AND product_id = $2",
+                location.id,
+                product.product_id
+            )
+                .fetch_optional(&mut *conn)
+                .await?
+                .unwrap_or(0);
+            return Err(AppError::Conflict(format!(
+                "Only {} of {} on hand at {}; {} needed",
+                on_hand, product.name, location.name, product.quantity
+            )));
+        }
+
+        sqlx::query!(
+            "UPDATE order_items SET fulfilled_from_location_id = $2 WHERE id = ANY($1)",
+            &product.item_ids,
+            location.id
+        )
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,322 @@
+// repositories/message_repository.rs
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+use crate::models::{Conversation, Message};
+
+/// Create or get existing conversation between two users
+pub async fn get_or_create_conversation(
+    pool: &PgPool,
+    user1_id: Uuid,
+    user2_id: Uuid,
+) -> AppResult<Uuid> {
+    // The table requires user1_id < user2_id
+    let (user1_id, user2_id) = if user1_id < user2_id {
+        (user1_id, user2_id)
+    } else {
+        (user2_id, user1_id)
+    };
+
+    // Check if conversation already exists
+    let existing_conv = sqlx::query_scalar!(
+        r#"
+        SELECT id FROM conversations
+        WHERE (user1_id = $1 AND user2_id = $2) OR (user1_id = $2 AND user2_id = $1)
+        "#,
+        user1_id,
+        user2_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    if let Some(conv_id) = existing_conv {
+        return Ok(conv_id);
+    }
+
+    // Create new conversation
+    let conv_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO conversations (id, user1_id, user2_id, last_updated)
+        VALUES ($1, $2, $3, NOW())
+        "#,
+        conv_id,
+        user1_id,
+        user2_id
+    )
+        .execute(pool)
+        .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO conversation_participants (conv_id, user_id)
+        VALUES ($1, $2), ($1, $3)
+        "#,
+        conv_id,
+        user1_id,
+        user2_id
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(conv_id)
+}
+
+/// Create or get the seller's broadcast conversation, under the given title
+pub async fn get_or_create_broadcast(pool: &PgPool, seller_id: Uuid, title: &str) -> AppResult<Uuid> {
+    let conv_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO conversations (id, is_group, owner_id, title, last_updated)
+        VALUES ($1, TRUE, $2, $3, NOW())
+        ON CONFLICT (owner_id) WHERE is_group DO UPDATE SET title = EXCLUDED.title
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        seller_id,
+        title
+    )
+        .fetch_one(pool)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO conversation_participants (conv_id, user_id)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        conv_id,
+        seller_id
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(conv_id)
+}
+
+/// Bring a seller's broadcast up to date: every buyer who has placed an order with
+/// them joins, and buyers who are gone or blocked, either way, leave. Returns the
+/// buyers taking part.
+pub async fn sync_broadcast(pool: &PgPool, conv_id: Uuid, seller_id: Uuid) -> AppResult<Vec<Uuid>> {
+    sqlx::query!(
+        r#"
+        DELETE FROM conversation_participants cp
+        USING users u
+        WHERE cp.conv_id = $1 AND cp.user_id <> $2 AND u.id = cp.user_id
+          AND (
+              u.deleted_at IS NOT NULL
+              OR EXISTS(
+                  SELECT 1 FROM user_blocks b
+                  WHERE (b.blocker_id = cp.user_id AND b.blocked_id = $2)
+                     OR (b.blocker_id = $2 AND b.blocked_id = cp.user_id)
+              )
+          )
+        "#,
+        conv_id,
+        seller_id
+    )
+        .execute(pool)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO conversation_participants (conv_id, user_id)
+        SELECT DISTINCT $1::uuid, o.buyer_id
+        FROM orders o
+        JOIN users u ON u.id = o.buyer_id AND u.deleted_at IS NULL
+        WHERE o.seller_id = $2 AND o.buyer_id <> $2 AND o.status <> 'failed'
+          AND NOT EXISTS(
+              SELECT 1 FROM user_blocks b
+              WHERE (b.blocker_id = o.buyer_id AND b.blocked_id = $2)
+                 OR (b.blocker_id = $2 AND b.blocked_id = o.buyer_id)
+          )
+        ON CONFLICT DO NOTHING
+        "#,
+        conv_id,
+        seller_id
+    )
+        .execute(pool)
+        .await?;
+
+    let buyer_ids = sqlx::query_scalar!(
+        "SELECT user_id FROM conversation_participants WHERE conv_id = $1 AND user_id <> $2",
+        conv_id,
+        seller_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(buyer_ids)
+}
+
+pub async fn find_conversation(pool: &PgPool, conv_id: Uuid) -> AppResult<Option<Conversation>> {
+    let conversation = sqlx::query_as!(
+        Conversation,
+        r#"
+        SELECT id, user1_id, user2_id, is_group, owner_id, title, last_updated
+        FROM conversations
+        WHERE id = $1
+        "#,
+        conv_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(conversation)
+}
+
+/// The conversation, if the user takes part in it
+pub async fn find_for_participant(pool: &PgPool, conv_id: Uuid, user_id: Uuid) -> AppResult<Option<Conversation>> {
+    let conversation = sqlx::query_as!(
+        Conversation,
+        r#"
+        SELECT c.id, c.user1_id, c.user2_id, c.is_group, c.owner_id, c.title, c.last_updated
+        FROM conversations c
+        JOIN conversation_participants cp ON cp.conv_id = c.id
+        WHERE c.id = $1 AND cp.user_id = $2
+        "#,
+        conv_id,
+        user_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(conversation)
+}
+
+pub async fn is_participant(pool: &PgPool, conv_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+    let is_participant = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM conversation_participants
+            WHERE conv_id = $1 AND user_id = $2
+        ) as "exists!"
+        "#,
+        conv_id,
+        user_id
+    )
+        .fetch_one(pool)
+        .await?;
+
+    Ok(is_participant)
+}
+
+pub async fn participant_ids(pool: &PgPool, conv_id: Uuid) -> AppResult<Vec<Uuid>> {
+    let participant_ids = sqlx::query_scalar!(
+        "SELECT user_id FROM conversation_participants WHERE conv_id = $1",
+        conv_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(participant_ids)
+}
+
+/// Save a message to the database, with its attachment's URL and type if it has one
+pub async fn save_message(
+    pool: &PgPool,
+    conv_id: Uuid,
+    sender_id: Uuid,
+    content: &str,
+    attachment: Option<(&str, &str)>,
+) -> AppResult<Message> {
+    let message_id = Uuid::new_v4();
+
+    let message = sqlx::query_as!(
+        Message,
+        r#"
+        INSERT INTO messages (id, conv_id, sender_id, content, attachment_url, attachment_type, sent_at)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW())
+        RETURNING id, conv_id, sender_id, content, attachment_url, attachment_type, sent_at, read_at,
+                  edited_at, deleted_at
+        "#,
+        message_id,
+        conv_id,
+        sender_id,
+        content,
+        attachment.map(|(url, _)| url),
+        attachment.map(|(_, attachment_type)| attachment_type)
+    )
+        .fetch_one(pool)
+        .await?;
+
+    // Update conversation last_updated timestamp
+    sqlx::query!(
+        "UPDATE conversations SET last_updated = NOW() WHERE id = $1",
+        conv_id
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(message)
+}
+
+/// A message by id, unless it was deleted
+pub async fn find_undeleted(pool: &PgPool, message_id: Uuid) -> AppResult<Option<Message>> {
+    let message = sqlx::query_as!(
+        Message,
+        r#"
+        SELECT id, conv_id, sender_id, content, attachment_url, attachment_type, sent_at, read_at,
+               edited_at, deleted_at
+        FROM messages
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        message_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(message)
+}
+
+/// Which of the two users have blocked the other
+pub async fn blockers_between(pool: &PgPool, user_id: Uuid, other_id: Uuid) -> AppResult<Vec<Uuid>> {
+    let blockers = sqlx::query_scalar!(
+        r#"
+        SELECT blocker_id FROM user_blocks
+        WHERE (blocker_id = $1 AND blocked_id = $2) OR (blocker_id = $2 AND blocked_id = $1)
+        "#,
+        user_id,
+        other_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(blockers)
+}
+
+/// The latest `limit` messages between two users, oldest first, as a report keeps them
+pub async fn recent_between(pool: &PgPool, user_id: Uuid, other_id: Uuid, limit: i64) -> AppResult<(Option<Uuid>, Vec<Message>)> {
+    let conv_id = sqlx::query_scalar!(
+        r#"
+        SELECT id FROM conversations
+        WHERE (user1_id = $1 AND user2_id = $2) OR (user1_id = $2 AND user2_id = $1)
+        "#,
+        user_id,
+        other_id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    let Some(conv_id) = conv_id else {
+        return Ok((None, vec![]));
+    };
+
+    let mut messages = sqlx::query_as!(
+        Message,
+        r#"
+        SELECT id, conv_id, sender_id, content, attachment_url, attachment_type, sent_at, read_at,
+               edited_at, deleted_at
+        FROM messages
+        WHERE conv_id = $1
+        ORDER BY sent_at DESC
+        LIMIT $2
+        "#,
+        conv_id,
+        limit
+    )
+        .fetch_all(pool)
+        .await?;
+    messages.reverse();
+
+    Ok((Some(conv_id), messages))
+}
@@ -0,0 +1,431 @@
+// repositories/order_repository.rs
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde_json::json;
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{AuditAction, FulfillmentStatus, InventoryReason, OrderStatus, ShipmentDetails, WebhookEvent};
+use crate::repositories::audit_repository::{self, AuditEntry};
+use crate::repositories::delivery_zone_repository::{self, Undeliverable};
+use crate::repositories::inventory_repository::{self, StockMovement};
+use crate::webhooks;
+
+/// A seller's minimum order value, delivery fee, tax registration and vacation mode, as set in their settings
+pub struct SellerTerms {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub min_order_value: BigDecimal,
+    pub delivery_fee: BigDecimal,
+    pub tax_id: Option<String>,
+    pub tax_name: Option<String>,
+    pub store_paused: bool,
+}
+
+/// A new pending order, with its prices already worked out
+pub struct NewOrder<'a> {
+    pub id: Uuid,
+    pub buyer_id: Uuid,
+    pub seller_id: Uuid,
+    pub subtotal_price: &'a BigDecimal,
+    pub discount_amount: &'a BigDecimal,
+    pub coupon_id: Option<Uuid>,
+    pub delivery_fee: &'a BigDecimal,
+    pub tax_amount: &'a BigDecimal,
+    pub total_price: &'a BigDecimal,
+    pub currency: &'a str,
+    pub shipping_address: &'a serde_json::Value,
+    pub delivery_slot_id: Option<Uuid>,
+    pub seller_tax_id: Option<&'a str>,
+    pub seller_tax_name: Option<&'a str>,
+}
+
+pub struct NewOrderItem<'a> {
+    pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub variant_name: Option<&'a str>,
+    pub quantity: i32,
+    pub unit_price: &'a BigDecimal,
+    /// The deal the line is priced at
+    pub deal_id: Option<Uuid>,
+    /// In percent; 0 when the seller doesn't charge tax
+    pub tax_rate: &'a BigDecimal,
+    pub tax_amount: &'a BigDecimal,
+}
+
+pub async fn seller_terms(conn: &mut PgConnection, seller_ids: &[Uuid]) -> AppResult<HashMap<Uuid, SellerTerms>> {
+    let terms = sqlx::query_as!(
+        SellerTerms,
+        "SELECT id, name, min_order_value, delivery_fee, tax_id, tax_name, store_paused FROM users WHERE id = ANY($1)",
+        seller_ids
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(terms.into_iter().map(|terms| (terms.id, terms)).collect())
+}
+
+/// Insert the order and the first entry of its status history, and queue the seller's
+/// order.created webhooks. It's expected by the end of its delivery slot, or within the
+/// seller's lead time without one.
+pub async fn insert(conn: &mut PgConnection, order: &NewOrder<'_>) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO orders (id, buyer_id, seller_id, status, subtotal_price, discount_amount,
+                            coupon_id, delivery_fee, tax_amount, total_price, currency, shipping_address,
+                            delivery_slot_id, seller_tax_id, seller_tax_name, expected_delivery_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, order_eta($3, $13))
+        "#,
+        order.id,
+        order.buyer_id,
+        order.seller_id,
+        OrderStatus::Pending as OrderStatus,
+        order.subtotal_price,
+        order.discount_amount,
+        order.coupon_id,
+        order.delivery_fee,
+        order.tax_amount,
+        order.total_price,
+        order.currency,
+        order.shipping_address,
+        order.delivery_slot_id,
+        order.seller_tax_id,
+        order.seller_tax_name
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    webhooks::enqueue(&mut *conn, order.seller_id, WebhookEvent::OrderCreated, json!({
+        "order_id": order.id,
+        "buyer_id": order.buyer_id,
+        "status": OrderStatus::Pending,
+        "total_price": order.total_price,
+        "currency": order.currency,
+        "delivery_slot_id": order.delivery_slot_id
+    })).await?;
+    record_status(conn, order.id, None, OrderStatus::Pending, Some(order.buyer_id)).await
+}
+
+pub async fn insert_item(conn: &mut PgConnection, order_id: Uuid, item: &NewOrderItem<'_>) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO order_items (id, order_id, product_id, variant_id, variant_name, quantity, unit_price,
+                                 deal_id, tax_rate, tax_amount)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        item.product_id,
+        item.variant_id,
+        item.variant_name,
+        item.quantity,
+        item.unit_price,
+        item.deal_id,
+        item.tax_rate,
+        item.tax_amount
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Append a row to the order's status history
+pub async fn record_status(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    from_status: Option<OrderStatus>,
+    to_status: OrderStatus,
+    changed_by: Option<Uuid>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO order_status_history (id, order_id, from_status, to_status, changed_by)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        from_status as Option<OrderStatus>,
+        to_status as OrderStatus,
+        changed_by
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Move an order to `next` if the transition table allows it, returning the previous status,
+/// and queue the seller's order.status_changed webhooks. Locks the order row, so call it
+/// inside the caller's transaction.
+pub async fn transition_status(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    next: OrderStatus,
+    changed_by: Option<Uuid>,
+) -> AppResult<OrderStatus> {
+    let order = sqlx::query!(
+        r#"SELECT seller_id, status as "status: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#,
+        order_id
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".to_string()))?;
+    let current = order.status;
+
+    if !current.can_transition_to(&next) {
+        return Err(AppError::Conflict(format!(
+            "Cannot change order status from {} to {}",
+            current.as_str(),
+            next.as_str()
+        )));
+    }
+
+    sqlx::query!(
+        "UPDATE orders SET status = $2 WHERE id = $1",
+        order_id,
+        next.clone() as OrderStatus
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    audit_repository::record(&mut *conn, AuditEntry {
+        actor_id: changed_by,
+        action: AuditAction::OrderStatusChanged,
+        subject_id: Some(order_id),
+        details: json!({ "from": current, "to": next }),
+    }).await?;
+    webhooks::enqueue(&mut *conn, order.seller_id, WebhookEvent::OrderStatusChanged, json!({
+        "order_id": order_id,
+        "from": current,
+        "to": next
+    })).await?;
+    record_status(conn, order_id, Some(current.clone()), next, changed_by).await?;
+
+    Ok(current)
+}
+
+/// Bring every item of the order up to `status`, for when the whole order ships or is
+/// delivered. Items already further along are left alone (the enum is ordered).
+pub async fn fulfill_all_items(conn: &mut PgConnection, order_id: Uuid, status: FulfillmentStatus) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        UPDATE order_items
+        SET fulfillment_status = $2,
+            shipped_at = COALESCE(shipped_at, NOW()),
+            delivered_at = CASE WHEN $2 = 'delivered' THEN NOW() END
+        WHERE order_id = $1 AND fulfillment_status < $2
+        "#,
+        order_id,
+        status as FulfillmentStatus
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Move the order to the status its items add up to: delivered once every item is,
+/// shipped once every item has shipped, partially shipped once any has. Returns the
+/// new status, or None if it didn't change.
+pub async fn sync_status_with_items(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    changed_by: Option<Uuid>,
+) -> AppResult<Option<OrderStatus>> {
+    let items = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "total!",
+               COUNT(*) FILTER (WHERE fulfillment_status <> 'unfulfilled') as "shipped!",
+               COUNT(*) FILTER (WHERE fulfillment_status = 'delivered') as "delivered!",
+               (SELECT status FROM orders WHERE id = $1) as "current!: OrderStatus"
+        FROM order_items
+        WHERE order_id = $1
+        "#,
+        order_id
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let derived = if items.delivered == items.total {
+        OrderStatus::Delivered
+    } else if items.shipped == items.total {
+        OrderStatus::Shipped
+    } else if items.shipped > 0 {
+        OrderStatus::PartiallyShipped
+    } else {
+        return Ok(None);
+    };
+
+    if derived == items.current {
+        return Ok(None);
+    }
+
+    transition_status(conn, order_id, derived.clone(), changed_by).await?;
+
+    Ok(Some(derived))
+}
+
+/// Change the order's shipment details, keeping the current value of any left out, and
+/// record the result for the order's timeline
+pub async fn update_shipment(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    shipment: &ShipmentDetails,
+    updated_by: Option<Uuid>,
+) -> AppResult<()> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE orders
+        SET courier_name = COALESCE($2, courier_name),
+            tracking_number = COALESCE($3, tracking_number),
+            expected_delivery_date = COALESCE($4, expected_delivery_date)
+        WHERE id = $1
+        RETURNING courier_name, tracking_number, expected_delivery_date
+        "#,
+        order_id,
+        shipment.courier_name.as_deref().map(str::trim),
+        shipment.tracking_number.as_deref().map(str::trim),
+        shipment.expected_delivery_date
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO order_shipment_updates (id, order_id, courier_name, tracking_number, expected_delivery_date, updated_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        order_id,
+        updated.courier_name,
+        updated.tracking_number,
+        updated.expected_delivery_date,
+        updated_by
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Put a cancelled order's quantities back into stock, recording each return in the
+/// inventory history. Variant items go back to their variant; items whose variant has
+/// since been deleted have nowhere to go.
+pub async fn restock_items(conn: &mut PgConnection, order_id: Uuid, actor_id: Option<Uuid>) -> AppResult<()> {
+    // Products before variants, in id order, as checkout locks them
+    sqlx::query!(
+        r#"
+        SELECT id FROM products
+        WHERE id IN (SELECT product_id FROM order_items WHERE order_id = $1)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+        order_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let products = sqlx::query!(
+        r#"
+        UPDATE products p
+        SET stock_qty = p.stock_qty + oi.quantity
+        FROM order_items oi
+        WHERE oi.order_id = $1 AND oi.product_id = p.id AND oi.variant_name IS NULL
+        RETURNING p.id, p.stock_qty, oi.quantity
+        "#,
+        order_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let variants = sqlx::query!(
+        r#"
+        UPDATE product_variants v
+        SET stock_qty = v.stock_qty + oi.quantity
+        FROM order_items oi
+        WHERE oi.order_id = $1 AND oi.variant_id = v.id
+        RETURNING v.product_id, v.id, v.stock_qty, oi.quantity
+        "#,
+        order_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let returns = products.iter().map(|row| (row.id, None, row.quantity, row.stock_qty))
+        .chain(variants.iter().map(|row| (row.product_id, Some(row.id), row.quantity, row.stock_qty)));
+
+    // Units bought at a deal go back to it, even once it has ended
+    sqlx::query!(
+        r#"
+        UPDATE deals d
+        SET sold_quantity = GREATEST(d.sold_quantity - returned.quantity, 0)
+        FROM (
+            SELECT deal_id, SUM(quantity)::int4 as quantity FROM order_items
+            WHERE order_id = $1 AND deal_id IS NOT NULL
+            GROUP BY deal_id
+        ) returned
+        WHERE d.id = returned.deal_id
+        "#,
+        order_id
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    for (product_id, variant_id, quantity, stock_after) in returns {
+        inventory_repository::record(&mut *conn, StockMovement {
+            product_id,
+            variant_id,
+            quantity_change: quantity,
+            stock_after,
+            reason: InventoryReason::Cancellation,
+            note: None,
+            order_id: Some(order_id),
+            actor_id,
+        }).await?;
+    }
+
+    Ok(())
+}
+
+/// The order side of placing an order, as the order service sees it. Postgres
+/// implements it on a connection, inside the checkout's transaction.
+#[async_trait]
+pub trait OrderRepo: Send {
+    async fn seller_terms(&mut self, seller_ids: &[Uuid]) -> AppResult<HashMap<Uuid, SellerTerms>>;
+    /// Which of the sellers don't deliver to the pincode and coordinates
+    async fn undeliverable(
+        &mut self,
+        seller_ids: &[Uuid],
+        pincode: &str,
+        location: Option<(f64, f64)>,
+    ) -> AppResult<Vec<Undeliverable>>;
+    async fn insert(&mut self, order: &NewOrder<'_>) -> AppResult<()>;
+    async fn insert_item(&mut self, order_id: Uuid, item: &NewOrderItem<'_>) -> AppResult<()>;
+}
+
+#[async_trait]
+impl OrderRepo for PgConnection {
+    async fn seller_terms(&mut self, seller_ids: &[Uuid]) -> AppResult<HashMap<Uuid, SellerTerms>> {
+        seller_terms(self, seller_ids).await
+    }
+
+    async fn undeliverable(
+        &mut self,
+        seller_ids: &[Uuid],
+        pincode: &str,
+        location: Option<(f64, f64)>,
+    ) -> AppResult<Vec<Undeliverable>> {
+        delivery_zone_repository::undeliverable(self, seller_ids, pincode, location).await
+    }
+
+    async fn insert(&mut self, order: &NewOrder<'_>) -> AppResult<()> {
+        insert(self, order).await
+    }
+
+    async fn insert_item(&mut self, order_id: Uuid, item: &NewOrderItem<'_>) -> AppResult<()> {
+        insert_item(self, order_id, item).await
+    }
+}
@@ -0,0 +1,239 @@
+// repositories/product_repository.rs
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+use crate::models::{CartItem, InventoryReason, PriceTier};
+use crate::repositories::inventory_repository::{self, StockMovement};
+use crate::repositories::{deal_repository, reservation_repository};
+
+/// What checkout needs to know about a product in the cart
+pub struct CheckoutProduct {
+    pub id: Uuid,
+    pub seller_id: Uuid,
+    pub currency: String,
+    /// The category's effective rate, in percent
+    pub tax_rate: BigDecimal,
+    pub has_variants: bool,
+    pub min_increment: i32,
+}
+
+pub struct CheckoutVariant {
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub name: String,
+}
+
+/// What each unit of a line costs at its quantity
+#[derive(Clone)]
+pub struct LinePrice {
+    pub unit_price: BigDecimal,
+    /// The deal that sets the price, if any
+    pub deal_id: Option<Uuid>,
+}
+
+/// Lock the products in a fixed order so concurrent checkouts can't oversell or deadlock;
+/// variant stock changes also lock their product first. Call it inside the checkout's
+/// transaction.
+pub async fn lock_for_checkout(conn: &mut PgConnection, product_ids: &[Uuid]) -> AppResult<Vec<CheckoutProduct>> {
+    let products = sqlx::query_as!(
+        CheckoutProduct,
+        r#"
+        SELECT id, seller_id, currency, category_tax_rate(category_id) as "tax_rate!",
+               EXISTS(SELECT 1 FROM product_variants v WHERE v.product_id = products.id) as "has_variants!",
+               min_increment
+        FROM products
+        WHERE id = ANY($1)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+        product_ids
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(products)
+}
+
+pub async fn checkout_variants(conn: &mut PgConnection, variant_ids: &[Uuid]) -> AppResult<Vec<CheckoutVariant>> {
+    let variants = sqlx::query_as!(
+        CheckoutVariant,
+        "SELECT id, product_id, name FROM product_variants WHERE id = ANY($1)",
+        variant_ids
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(variants)
+}
+
+/// What each unit of every line costs at its quantity, price tiers and deals applied,
+/// keyed by (product, variant). Lines whose product is gone are left out. The `unit_price`
+/// SQL function holds the rule, so the cart, its coupon preview and checkout all agree.
+pub async fn line_prices(conn: &mut PgConnection, items: &[CartItem]) -> AppResult<HashMap<(Uuid, Option<Uuid>), LinePrice>> {
+    let product_ids: Vec<Uuid> = items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<Option<Uuid>> = items.iter().map(|item| item.variant_id).collect();
+    let quantities: Vec<i32> = items.iter().map(|item| item.quantity).collect();
+
+    let prices = sqlx::query!(
+        r#"
+        SELECT item.product_id as "product_id!", item.variant_id,
+               unit_price(item.product_id, item.variant_id, item.quantity) as unit_price,
+               unit_deal(item.product_id, item.variant_id, item.quantity) as deal_id
+        FROM UNNEST($1::uuid[], $2::uuid[], $3::int[]) AS item(product_id, variant_id, quantity)
+        "#,
+        &product_ids,
+        &variant_ids as &[Option<Uuid>],
+        &quantities
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(prices
+        .into_iter()
+        .filter_map(|line| {
+            let price = LinePrice { unit_price: line.unit_price?, deal_id: line.deal_id };
+            Some(((line.product_id, line.variant_id), price))
+        })
+        .collect())
+}
+
+pub async fn price_tiers(conn: &mut PgConnection, product_id: Uuid) -> AppResult<Vec<PriceTier>> {
+    let tiers = sqlx::query_as!(
+        PriceTier,
+        "SELECT min_qty, unit_price FROM price_tiers WHERE product_id = $1 ORDER BY min_qty",
+        product_id
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(tiers)
+}
+
+/// Replace all of the product's tiers
+pub async fn set_price_tiers(conn: &mut PgConnection, product_id: Uuid, tiers: &[PriceTier]) -> AppResult<()> {
+    let min_qtys: Vec<i32> = tiers.iter().map(|tier| tier.min_qty).collect();
+    let unit_prices: Vec<BigDecimal> = tiers.iter().map(|tier| tier.unit_price.round(2)).collect();
+
+    sqlx::query!("DELETE FROM price_tiers WHERE product_id = $1", product_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO price_tiers (product_id, min_qty, unit_price)
+        SELECT $1, tier.min_qty, tier.unit_price
+        FROM UNNEST($2::int[], $3::numeric[]) AS tier(min_qty, unit_price)
+        "#,
+        product_id,
+        &min_qtys,
+        &unit_prices
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Take `quantity` off the variant's stock, or the product's when there is no variant,
+/// never going below zero. Returns the stock left, or None when there wasn't enough.
+/// A variant's product stock follows through the sync trigger.
+pub async fn take_stock(
+    conn: &mut PgConnection,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    quantity: i32,
+) -> AppResult<Option<i32>> {
+    let remaining = match variant_id {
+        Some(variant_id) => sqlx::query_scalar!(
+            "UPDATE product_variants SET stock_qty = stock_qty - $2 WHERE id = $1 AND stock_qty >= $2 RETURNING stock_qty",
+            variant_id,
+            quantity
+        )
+            .fetch_optional(conn)
+            .await?,
+        None => sqlx::query_scalar!(
+            "UPDATE products SET stock_qty = stock_qty - $2 WHERE id = $1 AND stock_qty >= $2 RETURNING stock_qty",
+            product_id,
+            quantity
+        )
+            .fetch_optional(conn)
+            .await?,
+    };
+
+    Ok(remaining)
+}
+
+/// The product side of placing an order, as the order service sees it. Postgres
+/// implements it on a connection, inside the checkout's transaction.
+#[async_trait]
+pub trait ProductRepo: Send {
+    async fn lock_for_checkout(&mut self, product_ids: &[Uuid]) -> AppResult<Vec<CheckoutProduct>>;
+    async fn checkout_variants(&mut self, variant_ids: &[Uuid]) -> AppResult<Vec<CheckoutVariant>>;
+    async fn line_prices(&mut self, items: &[CartItem]) -> AppResult<HashMap<(Uuid, Option<Uuid>), LinePrice>>;
+    /// Fail on the first item the buyer can't have right now
+    async fn check_stock(&mut self, buyer_id: Uuid, items: &[CartItem]) -> AppResult<()>;
+    /// Take a line of a placed order out of stock, counting it against the deal it was
+    /// priced at and recording the movement. Returns the stock left, or None when there
+    /// wasn't enough.
+    async fn sell(
+        &mut self,
+        order_id: Uuid,
+        buyer_id: Uuid,
+        product_id: Uuid,
+        variant_id: Option<Uuid>,
+        deal_id: Option<Uuid>,
+        quantity: i32,
+    ) -> AppResult<Option<i32>>;
+}
+
+#[async_trait]
+impl ProductRepo for PgConnection {
+    async fn lock_for_checkout(&mut self, product_ids: &[Uuid]) -> AppResult<Vec<CheckoutProduct>> {
+        lock_for_checkout(self, product_ids).await
+    }
+
+    async fn checkout_variants(&mut self, variant_ids: &[Uuid]) -> AppResult<Vec<CheckoutVariant>> {
+        checkout_variants(self, variant_ids).await
+    }
+
+    async fn line_prices(&mut self, items: &[CartItem]) -> AppResult<HashMap<(Uuid, Option<Uuid>), LinePrice>> {
+        line_prices(self, items).await
+    }
+
+    async fn check_stock(&mut self, buyer_id: Uuid, items: &[CartItem]) -> AppResult<()> {
+        reservation_repository::check_stock(self, buyer_id, items).await
+    }
+
+    async fn sell(
+        &mut self,
+        order_id: Uuid,
+        buyer_id: Uuid,
+        product_id: Uuid,
+        variant_id: Option<Uuid>,
+        deal_id: Option<Uuid>,
+        quantity: i32,
+    ) -> AppResult<Option<i32>> {
+        if let Some(deal_id) = deal_id {
+            deal_repository::claim(&mut *self, deal_id, quantity).await?;
+        }
+
+        let Some(remaining) = take_stock(&mut *self, product_id, variant_id, quantity).await? else {
+            return Ok(None);
+        };
+        inventory_repository::record(self, StockMovement {
+            product_id,
+            variant_id,
+            quantity_change: -quantity,
+            stock_after: remaining,
+            reason: InventoryReason::Order,
+            note: None,
+            order_id: Some(order_id),
+            actor_id: Some(buyer_id),
+        }).await?;
+
+        Ok(Some(remaining))
+    }
+}
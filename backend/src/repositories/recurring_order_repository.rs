@@ -0,0 +1,199 @@
+// repositories/recurring_order_repository.rs
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{CartItem, RecurringOrder, RecurringOrderRun, RecurringOrderStatus};
+use crate::repositories::cart_repository::about_item;
+
+pub async fn find(pool: &PgPool, id: Uuid) -> AppResult<Option<RecurringOrder>> {
+    let recurring_order = sqlx::query_as!(
+        RecurringOrder,
+        r#"
+        SELECT id, buyer_id, seller_id, address_id, interval_days, status as "status: RecurringOrderStatus",
+               next_run_at, created_at, updated_at
+        FROM recurring_orders
+        WHERE id = $1
+        "#,
+        id
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(recurring_order)
+}
+
+/// Lock the active standing order that has been due the longest, passing over any
+/// another instance is already placing. Call it inside the run's transaction.
+pub async fn lock_next_due(conn: &mut PgConnection) -> AppResult<Option<RecurringOrder>> {
+    let recurring_order = sqlx::query_as!(
+        RecurringOrder,
+        r#"
+        SELECT id, buyer_id, seller_id, address_id, interval_days, status as "status: RecurringOrderStatus",
+               next_run_at, created_at, updated_at
+        FROM recurring_orders
+        WHERE status = 'active' AND next_run_at <= NOW()
+        ORDER BY next_run_at
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+        "#
+    )
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(recurring_order)
+}
+
+pub async fn items(conn: &mut PgConnection, id: Uuid) -> AppResult<Vec<CartItem>> {
+    let items = sqlx::query_as!(
+        CartItem,
+        r#"
+        SELECT product_id, variant_id, quantity
+        FROM recurring_order_items
+        WHERE recurring_order_id = $1
+        ORDER BY product_id, variant_id
+        "#,
+        id
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(items)
+}
+
+/// Replace every item of the standing order
+pub async fn set_items(conn: &mut PgConnection, id: Uuid, items: &[CartItem]) -> AppResult<()> {
+    let product_ids: Vec<Uuid> = items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<Option<Uuid>> = items.iter().map(|item| item.variant_id).collect();
+    let quantities: Vec<i32> = items.iter().map(|item| item.quantity).collect();
+
+    sqlx::query!("DELETE FROM recurring_order_items WHERE recurring_order_id = $1", id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO recurring_order_items (recurring_order_id, product_id, variant_id, quantity)
+        SELECT $1, item.product_id, item.variant_id, item.quantity
+        FROM UNNEST($2::uuid[], $3::uuid[], $4::int[]) AS item(product_id, variant_id, quantity)
+        "#,
+        id,
+        &product_ids,
+        &variant_ids as &[Option<Uuid>],
+        &quantities
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// The one seller whose listed products these items all are. A product with variants
+/// has to be ordered as one of them, and every product in multiples of its increment.
+pub async fn seller_of(conn: &mut PgConnection, items: &[CartItem]) -> AppResult<Uuid> {
+    let product_ids: Vec<Uuid> = items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<Option<Uuid>> = items.iter().map(|item| item.variant_id).collect();
+
+    let lines = sqlx::query!(
+        r#"
+        SELECT line.product_id as "product_id!", p.seller_id as "seller_id?", p.name as "name?",
+               p.min_increment as "min_increment?", v.id as "variant_id?",
+               EXISTS(SELECT 1 FROM product_variants pv WHERE pv.product_id = p.id) as "has_variants!"
+        FROM UNNEST($1::uuid[], $2::uuid[]) WITH ORDINALITY AS line(product_id, variant_id, position)
+        LEFT JOIN products p ON p.id = line.product_id
+            AND p.taken_down_at IS NULL AND p.deleted_at IS NULL AND p.review_status = 'approved'
+        LEFT JOIN product_variants v ON v.id = line.variant_id AND v.product_id = p.id
+        ORDER BY line.position
+        "#,
+        &product_ids,
+        &variant_ids as &[Option<Uuid>]
+    )
+        .fetch_all(conn)
+        .await?;
+
+    let mut seller_id = None;
+    for (index, (line, item)) in lines.iter().zip(items).enumerate() {
+        let Some(line_seller_id) = line.seller_id else {
+            return Err(about_item(index, AppError::NotFound("Product not found".to_string())));
+        };
+        if item.variant_id.is_some() && line.variant_id.is_none() {
+            return Err(about_item(index, AppError::NotFound("Variant not found".to_string())));
+        }
+        if item.variant_id.is_none() && line.has_variants {
+            return Err(about_item(index, AppError::BadRequest(format!(
+                "Choose a variant of {}",
+                line.name.as_deref().unwrap_or("this product")
+            ))));
+        }
+        if let Some(min_increment) = line.min_increment.filter(|increment| item.quantity % increment != 0) {
+            return Err(about_item(index, AppError::BadRequest(format!(
+                "{} is sold in multiples of {}",
+                line.name.as_deref().unwrap_or("This product"),
+                min_increment
+            ))));
+        }
+        if seller_id.is_some_and(|seller_id| seller_id != line_seller_id) {
+            return Err(AppError::BadRequest(
+                "A standing order is from one seller; set up one per seller".to_string(),
+            ));
+        }
+        seller_id = Some(line_seller_id);
+    }
+
+    seller_id.ok_or_else(|| AppError::BadRequest("A standing order needs at least one item".to_string()))
+}
+
+/// Record how this cycle went and move the schedule on to `next_run_at`
+pub async fn record_run(
+    conn: &mut PgConnection,
+    id: Uuid,
+    scheduled_for: DateTime<Utc>,
+    order_id: Option<Uuid>,
+    error: Option<&str>,
+    next_run_at: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO recurring_order_runs (id, recurring_order_id, scheduled_for, order_id, error)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (recurring_order_id, scheduled_for) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        id,
+        scheduled_for,
+        order_id,
+        error
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query!(
+        "UPDATE recurring_orders SET next_run_at = $2, updated_at = NOW() WHERE id = $1",
+        id,
+        next_run_at
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// The standing order's latest runs, newest first
+pub async fn runs(pool: &PgPool, id: Uuid, limit: i64) -> AppResult<Vec<RecurringOrderRun>> {
+    let runs = sqlx::query_as!(
+        RecurringOrderRun,
+        r#"
+        SELECT id, scheduled_for, order_id, error, created_at
+        FROM recurring_order_runs
+        WHERE recurring_order_id = $1
+        ORDER BY scheduled_for DESC
+        LIMIT $2
+        "#,
+        id,
+        limit
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(runs)
+}
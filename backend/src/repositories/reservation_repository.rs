@@ -0,0 +1,269 @@
+// repositories/reservation_repository.rs
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::CartItem;
+
+/// A cart line's stock key: the product, and the variant for products that have them
+pub type StockKey = (Uuid, Option<Uuid>);
+
+/// Hold `quantity` of a product (or one of its variants) for the user for the next `minutes`,
+/// replacing any earlier hold. Locks the product row so concurrent holds and checkouts of the
+/// same product run one at a time; call it inside the caller's transaction.
+pub async fn reserve(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    quantity: i32,
+    minutes: i32,
+) -> AppResult<()> {
+    let min_increment = sqlx::query_scalar!(
+        "SELECT min_increment FROM products WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        product_id
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+    check_increment(quantity, min_increment)?;
+
+    sqlx::query!(
+        "DELETE FROM stock_reservations WHERE product_id = $1 AND expires_at <= NOW()",
+        product_id
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    ensure_available(&mut *conn, Some(user_id), product_id, variant_id, quantity).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO stock_reservations (user_id, product_id, variant_id, quantity, expires_at)
+        VALUES ($1, $2, $3, $4, NOW() + make_interval(mins => $5))
+        ON CONFLICT (user_id, product_id, COALESCE(variant_id, '00000000-0000-0000-0000-000000000000'::uuid))
+        DO UPDATE SET quantity = EXCLUDED.quantity, expires_at = EXCLUDED.expires_at
+        "#,
+        user_id,
+        product_id,
+        variant_id,
+        quantity,
+        minutes
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Check a guest's session cart quantity against stock without holding any; guests' carts
+/// hold nothing until they log in and the cart is merged
+pub async fn check_guest_quantity(
+    conn: &mut PgConnection,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    quantity: i32,
+) -> AppResult<()> {
+    let min_increment = sqlx::query_scalar!(
+        "SELECT min_increment FROM products WHERE id = $1 AND deleted_at IS NULL",
+        product_id
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Product not found".to_string()))?;
+    check_increment(quantity, min_increment)?;
+
+    ensure_available(conn, None, product_id, variant_id, quantity).await
+}
+
+/// A product is bought in whole multiples of its increment, e.g. 5, 10 or 15 kg of rice
+/// sold by the 5 kg
+fn check_increment(quantity: i32, min_increment: i32) -> AppResult<()> {
+    if quantity % min_increment != 0 {
+        return Err(AppError::BadRequest(format!(
+            "This product is sold in multiples of {}",
+            min_increment
+        )));
+    }
+
+    Ok(())
+}
+
+/// The most of `quantity` that is a whole multiple of the increment, for capping a line
+/// at the stock left
+pub fn whole_increments(quantity: i32, min_increment: i32) -> i32 {
+    quantity / min_increment * min_increment
+}
+
+async fn ensure_available(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+    quantity: i32,
+) -> AppResult<()> {
+    check_variant(&mut *conn, product_id, variant_id).await?;
+
+    let available = available_stock(&mut *conn, &[(product_id, variant_id)], user_id)
+        .await?
+        .get(&(product_id, variant_id))
+        .copied()
+        .unwrap_or(0);
+
+    if quantity > available {
+        return Err(AppError::BadRequest("Insufficient stock".to_string()));
+    }
+
+    Ok(())
+}
+
+/// A product with variants is only sold as one of them, and a variant must belong to its product
+async fn check_variant(conn: &mut PgConnection, product_id: Uuid, variant_id: Option<Uuid>) -> AppResult<()> {
+    let variants = sqlx::query!(
+        r#"
+        SELECT
+            EXISTS(SELECT 1 FROM product_variants WHERE product_id = $1) as "has_variants!",
+            EXISTS(SELECT 1 FROM product_variants WHERE product_id = $1 AND id = $2) as "found!"
+        "#,
+        product_id,
+        variant_id
+    )
+        .fetch_one(conn)
+        .await?;
+
+    match variant_id {
+        None if variants.has_variants => {
+            Err(AppError::BadRequest("Choose a variant of this product".to_string()))
+        }
+        Some(_) if !variants.found => Err(AppError::NotFound("Variant not found".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Drop the user's hold on one product or variant
+pub async fn release(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    product_id: Uuid,
+    variant_id: Option<Uuid>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM stock_reservations
+        WHERE user_id = $1 AND product_id = $2 AND variant_id IS NOT DISTINCT FROM $3
+        "#,
+        user_id,
+        product_id,
+        variant_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Drop every hold the user has, e.g. once their cart becomes orders
+pub async fn release_all(conn: &mut PgConnection, user_id: Uuid) -> AppResult<()> {
+    sqlx::query!("DELETE FROM stock_reservations WHERE user_id = $1", user_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Stock the user can still take for each key: the variant's stock_qty, or the product's for
+/// products without variants, minus other users' active holds (every hold, for a guest). A
+/// deleted product, or one that has variants but is asked for without one, has nothing available.
+pub async fn available_stock(
+    conn: &mut PgConnection,
+    keys: &[StockKey],
+    user_id: Option<Uuid>,
+) -> AppResult<HashMap<StockKey, i32>> {
+    let product_ids: Vec<Uuid> = keys.iter().map(|(product_id, _)| *product_id).collect();
+    let variant_ids: Vec<Option<Uuid>> = keys.iter().map(|(_, variant_id)| *variant_id).collect();
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT line.product_id as "product_id!", line.variant_id,
+               CASE
+                   WHEN p.deleted_at IS NOT NULL THEN 0
+                   WHEN line.variant_id IS NOT NULL THEN COALESCE(v.stock_qty, 0)
+                   WHEN EXISTS(SELECT 1 FROM product_variants pv WHERE pv.product_id = p.id) THEN 0
+                   ELSE p.stock_qty
+               END - COALESCE((
+                   SELECT SUM(r.quantity)
+                   FROM stock_reservations r
+                   WHERE r.product_id = p.id AND r.variant_id IS NOT DISTINCT FROM line.variant_id
+                     AND r.user_id IS DISTINCT FROM $3 AND r.expires_at > NOW()
+               ), 0)::int as "available!"
+        FROM UNNEST($1::uuid[], $2::uuid[]) AS line(product_id, variant_id)
+        JOIN products p ON p.id = line.product_id
+        LEFT JOIN product_variants v ON v.id = line.variant_id AND v.product_id = p.id
+        "#,
+        &product_ids,
+        &variant_ids as &[Option<Uuid>],
+        user_id
+    )
+        .fetch_all(conn)
+        .await?;
+
+    Ok(rows.into_iter().map(|row| ((row.product_id, row.variant_id), row.available)).collect())
+}
+
+/// Fail on the first item the buyer can't have right now: taken off sale, or with less
+/// stock left than they order, not counting what other buyers hold in their carts
+pub async fn check_stock(conn: &mut PgConnection, buyer_id: Uuid, items: &[CartItem]) -> AppResult<()> {
+    let keys: Vec<_> = items.iter().map(|item| (item.product_id, item.variant_id)).collect();
+    let available = available_stock(&mut *conn, &keys, Some(buyer_id)).await?;
+
+    let product_ids: Vec<Uuid> = items.iter().map(|item| item.product_id).collect();
+    let names = sqlx::query!(
+        r#"
+        SELECT id, name,
+               (taken_down_at IS NULL AND deleted_at IS NULL AND review_status = 'approved') as "on_sale!"
+        FROM products
+        WHERE id = ANY($1)
+        "#,
+        &product_ids
+    )
+        .fetch_all(conn)
+        .await?;
+
+    for item in items {
+        let product = names.iter().find(|product| product.id == item.product_id);
+        let name = product.map_or("A product", |product| product.name.as_str());
+        if !product.is_some_and(|product| product.on_sale) {
+            return Err(AppError::BadRequest(format!("{} is no longer available", name)));
+        }
+
+        let stock = available.get(&(item.product_id, item.variant_id)).copied().unwrap_or(0);
+        if stock < item.quantity {
+            return Err(AppError::BadRequest(format!(
+                "Only {} of {} left; {} were ordered",
+                stock.max(0),
+                name,
+                item.quantity
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// When each of the user's active holds runs out
+pub async fn expiries(pool: &PgPool, user_id: Uuid) -> AppResult<HashMap<StockKey, DateTime<Utc>>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT product_id, variant_id, expires_at
+        FROM stock_reservations
+        WHERE user_id = $1 AND expires_at > NOW()
+        "#,
+        user_id
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.into_iter().map(|row| ((row.product_id, row.variant_id), row.expires_at)).collect())
+}
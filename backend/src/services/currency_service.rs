@@ -0,0 +1,63 @@
+// services/currency_service.rs
+use bigdecimal::{BigDecimal, RoundingMode};
+
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+use crate::models::ProductWithSeller;
+
+/// An ISO code the platform has a rate for, normalized to upper case
+pub fn parse(config: &Config, code: &str) -> AppResult<String> {
+    let code = code.trim().to_uppercase();
+    if config.exchange_rates.contains_key(&code) {
+        return Ok(code);
+    }
+
+    let mut supported: Vec<&str> = config.exchange_rates.keys().map(String::as_str).collect();
+    supported.sort_unstable();
+    Err(AppError::BadRequest(format!(
+        "Unsupported currency '{}'; use one of {}",
+        code,
+        supported.join(", ")
+    )))
+}
+
+/// `amount` in `from` expressed in `to`, rounded to two decimals; None if either
+/// currency has no rate. Rates are to BASE_CURRENCY, so other pairs go through it.
+/// For display only: orders are charged in their items' currency.
+pub fn convert(config: &Config, amount: &BigDecimal, from: &str, to: &str) -> Option<BigDecimal> {
+    if from == to {
+        return Some(amount.round(2));
+    }
+    let from_rate = config.exchange_rates.get(from)?;
+    let to_rate = config.exchange_rates.get(to)?;
+    Some((amount * from_rate / to_rate).round(2))
+}
+
+/// The amount with its currency's symbol, or its code when it has no common symbol
+pub fn format(amount: &BigDecimal, currency: &str) -> String {
+    let amount = amount.with_scale_round(2, RoundingMode::HalfEven);
+    match symbol(currency) {
+        Some(symbol) => format!("{}{}", symbol, amount),
+        None => format!("{} {}", currency, amount),
+    }
+}
+
+fn symbol(currency: &str) -> Option<&'static str> {
+    match currency {
+        "INR" => Some("₹"),
+        "USD" => Some("$"),
+        "EUR" => Some("€"),
+        "GBP" => Some("£"),
+        "JPY" => Some("¥"),
+        _ => None,
+    }
+}
+
+/// Fill in each product's price in `currency` for display; products whose own currency
+/// has no rate any more are left without one
+pub fn localize_products(config: &Config, products: &mut [ProductWithSeller], currency: &str) {
+    for product in products {
+        product.display_price = convert(config, &product.price_per_unit, &product.currency, currency);
+        product.display_currency = product.display_price.as_ref().map(|_| currency.to_string());
+    }
+}
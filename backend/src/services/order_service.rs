@@ -0,0 +1,723 @@
+// services/order_service.rs
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::CartItem;
+use crate::repositories::delivery_slot_repository::DeliverySlot;
+use crate::repositories::delivery_zone_repository::Undeliverable;
+use crate::repositories::order_repository::{NewOrder, NewOrderItem, OrderRepo, SellerTerms};
+use crate::repositories::product_repository::{CheckoutProduct, CheckoutVariant, LinePrice, ProductRepo};
+
+/// One cart line priced for checkout
+pub struct CheckoutLine {
+    pub product_id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub variant_name: Option<String>,
+    pub quantity: i32,
+    pub unit_price: BigDecimal,
+    /// The deal the line is priced at
+    pub deal_id: Option<Uuid>,
+    pub currency: String,
+    /// The product category's rate, in percent
+    pub tax_rate: BigDecimal,
+}
+
+impl CheckoutLine {
+    pub fn total(&self) -> BigDecimal {
+        self.quantity * &self.unit_price
+    }
+}
+
+/// What one seller's order comes to
+pub struct OrderPricing {
+    pub subtotal_price: BigDecimal,
+    pub discount_amount: BigDecimal,
+    pub tax_amount: BigDecimal,
+    pub delivery_fee: BigDecimal,
+    pub total_price: BigDecimal,
+    /// (rate charged, tax) per line, in line order
+    pub line_taxes: Vec<(BigDecimal, BigDecimal)>,
+}
+
+/// Split the cart into one list of priced lines per seller, in seller id order. A product
+/// with variants is bought as one of them, and in multiples of its increment; `prices`
+/// holds each line's unit price at its quantity. Lines whose product is gone are dropped,
+/// as the cart check has already reported them.
+pub fn group_by_seller(
+    cart_items: &[CartItem],
+    products: &[CheckoutProduct],
+    variants: &[CheckoutVariant],
+    prices: &HashMap<(Uuid, Option<Uuid>), LinePrice>,
+) -> AppResult<BTreeMap<Uuid, Vec<CheckoutLine>>> {
+    let mut orders_by_seller: BTreeMap<Uuid, Vec<CheckoutLine>> = BTreeMap::new();
+
+    for item in cart_items {
+        let Some(product) = products.iter().find(|p| p.id == item.product_id) else {
+            continue;
+        };
+        let Some(price) = prices.get(&(item.product_id, item.variant_id)) else {
+            continue;
+        };
+
+        let variant = match item.variant_id {
+            Some(variant_id) => Some(
+                variants
+                    .iter()
+                    .find(|v| v.id == variant_id && v.product_id == product.id)
+                    .ok_or_else(|| AppError::NotFound("Variant not found".to_string()))?,
+            ),
+            None if product.has_variants => {
+                return Err(AppError::BadRequest(format!(
+                    "Choose a variant of product {}",
+                    product.id
+                )));
+            }
+            None => None,
+        };
+
+        if item.quantity % product.min_increment != 0 {
+            return Err(AppError::BadRequest(format!(
+                "Product {} is sold in multiples of {}",
+                product.id, product.min_increment
+            )));
+        }
+
+        orders_by_seller
+            .entry(product.seller_id)
+            .or_default()
+            .push(CheckoutLine {
+                product_id: product.id,
+                variant_id: item.variant_id,
+                variant_name: variant.map(|v| v.name.clone()),
+                quantity: item.quantity,
+                unit_price: price.unit_price.clone(),
+                deal_id: price.deal_id,
+                currency: product.currency.clone(),
+                tax_rate: product.tax_rate.clone(),
+            });
+    }
+
+    Ok(orders_by_seller)
+}
+
+pub fn subtotal(lines: &[CheckoutLine]) -> BigDecimal {
+    lines.iter().map(CheckoutLine::total).sum()
+}
+
+/// The one currency a seller's lines are priced in; an order can't mix currencies, and
+/// its minimum, delivery fee and coupon are taken to be in the same one
+pub fn order_currency(lines: &[CheckoutLine]) -> AppResult<&str> {
+    let currency = lines.first().map_or("", |line| line.currency.as_str());
+    if lines.iter().any(|line| line.currency != currency) {
+        return Err(AppError::BadRequest(
+            "One seller's items in an order must share a currency; check them out separately".to_string(),
+        ));
+    }
+    Ok(currency)
+}
+
+/// A seller on vacation takes no orders, and the seller's minimum applies to their
+/// items before any discount
+pub fn check_seller_terms(terms: &SellerTerms, subtotal_price: &BigDecimal) -> AppResult<()> {
+    if terms.store_paused {
+        return Err(AppError::BadRequest(format!(
+            "{} is on vacation and not taking orders right now; remove their items to check out",
+            terms.name.as_deref().unwrap_or("This seller")
+        )));
+    }
+
+    if *subtotal_price < terms.min_order_value {
+        return Err(AppError::BadRequest(format!(
+            "Orders from {} must be at least {}; your cart has {} of their items",
+            terms.name.as_deref().unwrap_or("this seller"),
+            terms.min_order_value,
+            subtotal_price
+        )));
+    }
+
+    Ok(())
+}
+
+/// Where a shipping address snapshot is, for checking delivery zones: its pincode and
+/// coordinates, if it has them
+pub fn destination(shipping_address: &serde_json::Value) -> (String, Option<(f64, f64)>) {
+    let pincode = shipping_address["postal_code"].as_str().unwrap_or_default().to_string();
+    let location = shipping_address["latitude"].as_f64().zip(shipping_address["longitude"].as_f64());
+    (pincode, location)
+}
+
+/// Refuse a checkout if any of its sellers don't deliver to the address, naming each
+pub fn check_delivery_zones(undeliverable: &[Undeliverable], pincode: &str) -> AppResult<()> {
+    if undeliverable.is_empty() {
+        return Ok(());
+    }
+
+    Err(AppError::Undeliverable(undeliverable.iter().map(|seller| json!({
+        "seller_id": seller.seller_id,
+        "seller_name": seller.seller_name,
+        "message": undeliverable_message(seller, pincode)
+    })).collect()))
+}
+
+pub fn undeliverable_message(seller: &Undeliverable, pincode: &str) -> String {
+    format!(
+        "{} doesn't deliver to {}; remove their items or choose another address",
+        seller.seller_name.as_deref().unwrap_or("This seller"),
+        pincode
+    )
+}
+
+/// Match the delivery slots the buyer picked to the sellers in the cart, at most one
+/// slot per seller
+pub fn assign_delivery_slots(
+    requested: &[Uuid],
+    slots: Vec<DeliverySlot>,
+    seller_ids: &[Uuid],
+) -> AppResult<HashMap<Uuid, DeliverySlot>> {
+    if let Some(missing) = requested.iter().find(|id| !slots.iter().any(|slot| slot.id == **id)) {
+        return Err(AppError::NotFound(format!("Delivery slot {} not found", missing)));
+    }
+
+    let mut slots_by_seller = HashMap::new();
+    for slot in slots {
+        if !seller_ids.contains(&slot.seller_id) {
+            return Err(AppError::BadRequest(format!(
+                "Delivery slot {} is from a seller with nothing in your cart",
+                slot.id
+            )));
+        }
+        if slots_by_seller.insert(slot.seller_id, slot).is_some() {
+            return Err(AppError::BadRequest("Pick at most one delivery slot per seller".to_string()));
+        }
+    }
+
+    Ok(slots_by_seller)
+}
+
+/// A slot takes bookings until it starts, while it has room
+pub fn check_delivery_slot(slot: &DeliverySlot, now: DateTime<Utc>) -> AppResult<()> {
+    if slot.starts_at <= now {
+        return Err(AppError::BadRequest(
+            "That delivery slot has already started; pick a later one".to_string(),
+        ));
+    }
+    if slot.remaining() == 0 {
+        return Err(AppError::Conflict("That delivery slot is full; pick another".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Price one seller's order. Only sellers registered for tax charge it, and the
+/// delivery fee isn't taxed.
+pub fn price_order(lines: &[CheckoutLine], terms: &SellerTerms, discount_amount: BigDecimal) -> OrderPricing {
+    let subtotal_price = subtotal(lines);
+
+    let line_taxes: Vec<(BigDecimal, BigDecimal)> = lines
+        .iter()
+        .map(|line| match terms.tax_id {
+            Some(_) => {
+                let tax = line_tax(&line.total(), &subtotal_price, &discount_amount, &line.tax_rate);
+                (line.tax_rate.clone(), tax)
+            }
+            None => (BigDecimal::zero(), BigDecimal::zero()),
+        })
+        .collect();
+
+    let tax_amount: BigDecimal = line_taxes.iter().map(|(_, tax)| tax).sum();
+    let total_price = &subtotal_price - &discount_amount + &tax_amount + &terms.delivery_fee;
+
+    OrderPricing {
+        subtotal_price,
+        discount_amount,
+        tax_amount,
+        delivery_fee: terms.delivery_fee.clone(),
+        total_price,
+        line_taxes,
+    }
+}
+
+/// Tax on a line worth `line_total` in an order whose `subtotal` was discounted by
+/// `discount`: the line bears its share of the discount, and the rate is in percent
+pub fn line_tax(line_total: &BigDecimal, subtotal: &BigDecimal, discount: &BigDecimal, tax_rate: &BigDecimal) -> BigDecimal {
+    if subtotal.is_zero() {
+        return BigDecimal::zero();
+    }
+    (line_total * (subtotal - discount) / subtotal * tax_rate / BigDecimal::from(100)).round(2)
+}
+
+/// (product, variant, stock before, stock after) per line of a placed order
+pub type StockChanges = Vec<(Uuid, Option<Uuid>, i32, i32)>;
+
+/// Place one order from the buyer to the seller for these items without a cart: priced,
+/// stock-checked and taken out of stock like a checkout without coupon or delivery slot.
+/// Standing orders and reorders are placed this way, inside the caller's transaction.
+/// Returns the order and the stock it took.
+pub async fn place_seller_order<R: ProductRepo + OrderRepo>(
+    repo: &mut R,
+    buyer_id: Uuid,
+    seller_id: Uuid,
+    shipping_address: &serde_json::Value,
+    items: &[CartItem],
+) -> AppResult<(Uuid, StockChanges)> {
+    let product_ids: Vec<Uuid> = items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<Uuid> = items.iter().filter_map(|item| item.variant_id).collect();
+    let products = repo.lock_for_checkout(&product_ids).await?;
+    let variants = repo.checkout_variants(&variant_ids).await?;
+    repo.check_stock(buyer_id, items).await?;
+
+    let prices = repo.line_prices(items).await?;
+    let mut orders_by_seller = group_by_seller(items, &products, &variants, &prices)?;
+    let lines = orders_by_seller.remove(&seller_id).unwrap_or_default();
+    if lines.is_empty() || !orders_by_seller.is_empty() {
+        return Err(AppError::BadRequest("Some of the items are now sold by another seller".to_string()));
+    }
+
+    let terms = repo
+        .seller_terms(&[seller_id])
+        .await?
+        .remove(&seller_id)
+        .ok_or_else(|| AppError::NotFound("Seller not found".to_string()))?;
+    let subtotal_price = subtotal(&lines);
+    check_seller_terms(&terms, &subtotal_price)?;
+    let currency = order_currency(&lines)?;
+    let pricing = price_order(&lines, &terms, BigDecimal::zero());
+
+    let (pincode, location) = destination(shipping_address);
+    let undeliverable = repo.undeliverable(&[seller_id], &pincode, location).await?;
+    if let Some(seller) = undeliverable.first() {
+        return Err(AppError::BadRequest(undeliverable_message(seller, &pincode)));
+    }
+
+    let order_id = Uuid::new_v4();
+    repo.insert(&NewOrder {
+        id: order_id,
+        buyer_id,
+        seller_id,
+        subtotal_price: &pricing.subtotal_price,
+        discount_amount: &pricing.discount_amount,
+        coupon_id: None,
+        delivery_fee: &pricing.delivery_fee,
+        tax_amount: &pricing.tax_amount,
+        total_price: &pricing.total_price,
+        currency,
+        shipping_address,
+        delivery_slot_id: None,
+        seller_tax_id: terms.tax_id.as_deref(),
+        seller_tax_name: terms.tax_name.as_deref(),
+    }).await?;
+    let stock_changes = insert_lines(repo, order_id, buyer_id, &lines, &pricing).await?;
+
+    Ok((order_id, stock_changes))
+}
+
+/// Store a seller's priced lines on their new order and take them out of stock, recording
+/// each movement. Returns (product, variant, stock before, stock after) per line, for
+/// low-stock alerts once the order is committed.
+pub async fn insert_lines<R: ProductRepo + OrderRepo>(
+    repo: &mut R,
+    order_id: Uuid,
+    buyer_id: Uuid,
+    lines: &[CheckoutLine],
+    pricing: &OrderPricing,
+) -> AppResult<StockChanges> {
+    let mut stock_changes = vec![];
+
+    for (line, (tax_rate, tax_amount)) in lines.iter().zip(&pricing.line_taxes) {
+        repo.insert_item(order_id, &NewOrderItem {
+            product_id: line.product_id,
+            variant_id: line.variant_id,
+            variant_name: line.variant_name.as_deref(),
+            quantity: line.quantity,
+            unit_price: &line.unit_price,
+            deal_id: line.deal_id,
+            tax_rate,
+            tax_amount,
+        }).await?;
+        let Some(remaining) = repo.sell(order_id, buyer_id, line.product_id, line.variant_id, line.deal_id, line.quantity).await? else {
+            return Err(AppError::BadRequest(format!(
+                "Insufficient stock for product {}",
+                line.product_id
+            )));
+        };
+        stock_changes.push((line.product_id, line.variant_id, remaining + line.quantity, remaining));
+    }
+
+    Ok(stock_changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chrono::Duration;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn product(seller_id: Uuid) -> CheckoutProduct {
+        CheckoutProduct {
+            id: Uuid::new_v4(),
+            seller_id,
+            currency: "INR".to_string(),
+            tax_rate: dec("5"),
+            has_variants: false,
+            min_increment: 1,
+        }
+    }
+
+    fn item(product_id: Uuid, quantity: i32) -> CartItem {
+        CartItem { product_id, variant_id: None, quantity }
+    }
+
+    fn line(unit_price: &str, quantity: i32, tax_rate: &str) -> CheckoutLine {
+        CheckoutLine {
+            product_id: Uuid::new_v4(),
+            variant_id: None,
+            variant_name: None,
+            quantity,
+            unit_price: dec(unit_price),
+            deal_id: None,
+            currency: "INR".to_string(),
+            tax_rate: dec(tax_rate),
+        }
+    }
+
+    fn terms(seller_id: Uuid, tax_id: Option<&str>) -> SellerTerms {
+        SellerTerms {
+            id: seller_id,
+            name: Some("Green Farms".to_string()),
+            min_order_value: BigDecimal::zero(),
+            delivery_fee: dec("40"),
+            tax_id: tax_id.map(str::to_string),
+            tax_name: None,
+            store_paused: false,
+        }
+    }
+
+    fn slot(seller_id: Uuid) -> DeliverySlot {
+        let starts_at = Utc::now() + Duration::days(1);
+        DeliverySlot { id: Uuid::new_v4(), seller_id, starts_at, ends_at: starts_at + Duration::hours(2), capacity: 5, booked: 0 }
+    }
+
+    fn prices(items: &[CartItem], unit_price: &str) -> HashMap<(Uuid, Option<Uuid>), LinePrice> {
+        items
+            .iter()
+            .map(|item| ((item.product_id, item.variant_id), LinePrice { unit_price: dec(unit_price), deal_id: None }))
+            .collect()
+    }
+
+    #[test]
+    fn the_cart_is_split_by_seller_in_seller_order() {
+        let mut sellers = [Uuid::new_v4(), Uuid::new_v4()];
+        sellers.sort();
+        let apples = product(sellers[1]);
+        let rice = product(sellers[0]);
+        let okra = product(sellers[1]);
+        let items = [item(apples.id, 2), item(rice.id, 5), item(okra.id, 1), item(Uuid::new_v4(), 3)];
+
+        let orders = group_by_seller(&items, &[apples, rice, okra], &[], &prices(&items, "20")).unwrap();
+
+        assert_eq!(orders.keys().copied().collect::<Vec<_>>(), sellers);
+        assert_eq!(orders[&sellers[0]].len(), 1);
+        let lines = &orders[&sellers[1]];
+        assert_eq!(lines.iter().map(|line| line.quantity).collect::<Vec<_>>(), [2, 1]);
+        assert_eq!(subtotal(lines), dec("60"));
+    }
+
+    #[test]
+    fn lines_need_a_variant_of_their_own_product_in_whole_increments() {
+        let seller_id = Uuid::new_v4();
+        let mut tomatoes = product(seller_id);
+        tomatoes.has_variants = true;
+        tomatoes.min_increment = 2;
+        let variant = CheckoutVariant { id: Uuid::new_v4(), product_id: tomatoes.id, name: "Cherry".to_string() };
+        let other = CheckoutVariant { id: Uuid::new_v4(), product_id: Uuid::new_v4(), name: "Roma".to_string() };
+        let products = [tomatoes];
+        let variants = [variant, other];
+        let with = |variant_id: Option<Uuid>, quantity: i32| CartItem { product_id: products[0].id, variant_id, quantity };
+
+        let items = [with(None, 2)];
+        let err = group_by_seller(&items, &products, &variants, &prices(&items, "30")).err();
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "{:?}", err);
+
+        let items = [with(Some(variants[1].id), 2)];
+        let err = group_by_seller(&items, &products, &variants, &prices(&items, "30")).err();
+        assert!(matches!(err, Some(AppError::NotFound(_))), "{:?}", err);
+
+        let items = [with(Some(variants[0].id), 3)];
+        let err = group_by_seller(&items, &products, &variants, &prices(&items, "30")).err();
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "{:?}", err);
+
+        let items = [with(Some(variants[0].id), 4)];
+        let orders = group_by_seller(&items, &products, &variants, &prices(&items, "30")).unwrap();
+        assert_eq!(orders[&seller_id][0].variant_name.as_deref(), Some("Cherry"));
+    }
+
+    #[test]
+    fn each_line_is_taxed_on_its_share_of_the_discount() {
+        assert_eq!(line_tax(&dec("60"), &dec("100"), &dec("10"), &dec("5")), dec("2.70"));
+        assert_eq!(line_tax(&dec("33.33"), &dec("100"), &BigDecimal::zero(), &dec("18")), dec("6.00"));
+        assert_eq!(line_tax(&BigDecimal::zero(), &BigDecimal::zero(), &BigDecimal::zero(), &dec("5")), BigDecimal::zero());
+    }
+
+    #[test]
+    fn only_registered_sellers_charge_tax_and_never_on_delivery() {
+        let seller_id = Uuid::new_v4();
+        let lines = [line("25", 4, "5"), line("10", 5, "12")];
+
+        let pricing = price_order(&lines, &terms(seller_id, Some("27AAPFU0939F1ZV")), dec("15"));
+        assert_eq!(pricing.subtotal_price, dec("150"));
+        assert_eq!(pricing.line_taxes, [(dec("5"), dec("4.50")), (dec("12"), dec("5.40"))]);
+        assert_eq!(pricing.tax_amount, dec("9.90"));
+        assert_eq!(pricing.total_price, dec("184.90"));
+
+        let pricing = price_order(&lines, &terms(seller_id, None), dec("15"));
+        assert!(pricing.line_taxes.iter().all(|(rate, tax)| rate.is_zero() && tax.is_zero()));
+        assert_eq!(pricing.delivery_fee, dec("40"));
+        assert_eq!(pricing.total_price, dec("175"));
+    }
+
+    #[test]
+    fn every_seller_that_cant_deliver_is_named() {
+        assert!(check_delivery_zones(&[], "411001").is_ok());
+
+        let undeliverable = [
+            Undeliverable { seller_id: Uuid::new_v4(), seller_name: Some("Green Farms".to_string()) },
+            Undeliverable { seller_id: Uuid::new_v4(), seller_name: None },
+        ];
+        let Err(AppError::Undeliverable(sellers)) = check_delivery_zones(&undeliverable, "411001") else {
+            panic!("expected the sellers that can't deliver");
+        };
+        assert_eq!(sellers.len(), 2);
+        assert_eq!(sellers[0]["seller_id"], json!(undeliverable[0].seller_id));
+        assert!(sellers[0]["message"].as_str().unwrap().starts_with("Green Farms doesn't deliver to 411001"));
+        assert!(sellers[1]["message"].as_str().unwrap().starts_with("This seller"));
+    }
+
+    #[test]
+    fn slots_go_to_sellers_in_the_cart_one_each() {
+        let sellers = [Uuid::new_v4(), Uuid::new_v4()];
+        let first = slot(sellers[0]);
+        let second = slot(sellers[1]);
+        let ids = [first.id, second.id];
+
+        let slots = assign_delivery_slots(&ids, vec![first, second], &sellers).unwrap();
+        assert_eq!(slots[&sellers[0]].id, ids[0]);
+        assert_eq!(slots[&sellers[1]].id, ids[1]);
+
+        let missing = Uuid::new_v4();
+        let err = assign_delivery_slots(&[missing], vec![], &sellers).err();
+        assert!(matches!(err, Some(AppError::NotFound(_))), "{:?}", err);
+
+        let stranger = slot(Uuid::new_v4());
+        let err = assign_delivery_slots(&[stranger.id], vec![stranger], &sellers).err();
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "{:?}", err);
+
+        let (early, late) = (slot(sellers[0]), slot(sellers[0]));
+        let err = assign_delivery_slots(&[early.id, late.id], vec![early, late], &sellers).err();
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "{:?}", err);
+    }
+
+    struct StockedProduct {
+        id: Uuid,
+        seller_id: Uuid,
+        price: BigDecimal,
+        stock: i32,
+    }
+
+    struct Seller {
+        id: Uuid,
+        tax_id: Option<&'static str>,
+        min_order_value: BigDecimal,
+        store_paused: bool,
+        delivers: bool,
+    }
+
+    /// Products, sellers and the orders placed with them, held in memory
+    #[derive(Default)]
+    struct MemoryRepo {
+        products: Vec<StockedProduct>,
+        sellers: Vec<Seller>,
+        orders: Vec<(Uuid, Uuid, BigDecimal)>,
+        items: Vec<(Uuid, Uuid, i32, BigDecimal)>,
+    }
+
+    impl MemoryRepo {
+        fn add_seller(&mut self, tax_id: Option<&'static str>) -> Uuid {
+            let id = Uuid::new_v4();
+            self.sellers.push(Seller { id, tax_id, min_order_value: BigDecimal::zero(), store_paused: false, delivers: true });
+            id
+        }
+
+        fn add_product(&mut self, seller_id: Uuid, price: &str, stock: i32) -> Uuid {
+            let id = Uuid::new_v4();
+            self.products.push(StockedProduct { id, seller_id, price: dec(price), stock });
+            id
+        }
+
+        fn seller(&mut self, seller_id: Uuid) -> &mut Seller {
+            self.sellers.iter_mut().find(|seller| seller.id == seller_id).unwrap()
+        }
+
+        fn stock(&self, product_id: Uuid) -> i32 {
+            self.products.iter().find(|stocked| stocked.id == product_id).unwrap().stock
+        }
+    }
+
+    #[async_trait]
+    impl ProductRepo for MemoryRepo {
+        async fn lock_for_checkout(&mut self, product_ids: &[Uuid]) -> AppResult<Vec<CheckoutProduct>> {
+            Ok(self
+                .products
+                .iter()
+                .filter(|stocked| product_ids.contains(&stocked.id))
+                .map(|stocked| CheckoutProduct { id: stocked.id, ..product(stocked.seller_id) })
+                .collect())
+        }
+
+        async fn checkout_variants(&mut self, _variant_ids: &[Uuid]) -> AppResult<Vec<CheckoutVariant>> {
+            Ok(vec![])
+        }
+
+        async fn line_prices(&mut self, items: &[CartItem]) -> AppResult<HashMap<(Uuid, Option<Uuid>), LinePrice>> {
+            Ok(items
+                .iter()
+                .filter_map(|item| {
+                    let stocked = self.products.iter().find(|stocked| stocked.id == item.product_id)?;
+                    Some(((item.product_id, item.variant_id), LinePrice { unit_price: stocked.price.clone(), deal_id: None }))
+                })
+                .collect())
+        }
+
+        // Holds aren't kept here, so a shortfall only shows when the line is sold
+        async fn check_stock(&mut self, _buyer_id: Uuid, _items: &[CartItem]) -> AppResult<()> {
+            Ok(())
+        }
+
+        async fn sell(
+            &mut self,
+            _order_id: Uuid,
+            _buyer_id: Uuid,
+            product_id: Uuid,
+            _variant_id: Option<Uuid>,
+            _deal_id: Option<Uuid>,
+            quantity: i32,
+        ) -> AppResult<Option<i32>> {
+            let stocked = self.products.iter_mut().find(|stocked| stocked.id == product_id).unwrap();
+            if stocked.stock < quantity {
+                return Ok(None);
+            }
+            stocked.stock -= quantity;
+            Ok(Some(stocked.stock))
+        }
+    }
+
+    #[async_trait]
+    impl OrderRepo for MemoryRepo {
+        async fn seller_terms(&mut self, seller_ids: &[Uuid]) -> AppResult<HashMap<Uuid, SellerTerms>> {
+            Ok(self
+                .sellers
+                .iter()
+                .filter(|seller| seller_ids.contains(&seller.id))
+                .map(|seller| (seller.id, SellerTerms {
+                    min_order_value: seller.min_order_value.clone(),
+                    store_paused: seller.store_paused,
+                    ..terms(seller.id, seller.tax_id)
+                }))
+                .collect())
+        }
+
+        async fn undeliverable(
+            &mut self,
+            seller_ids: &[Uuid],
+            _pincode: &str,
+            _location: Option<(f64, f64)>,
+        ) -> AppResult<Vec<Undeliverable>> {
+            Ok(self
+                .sellers
+                .iter()
+                .filter(|seller| seller_ids.contains(&seller.id) && !seller.delivers)
+                .map(|seller| Undeliverable { seller_id: seller.id, seller_name: Some("Green Farms".to_string()) })
+                .collect())
+        }
+
+        async fn insert(&mut self, order: &NewOrder<'_>) -> AppResult<()> {
+            self.orders.push((order.id, order.seller_id, order.total_price.clone()));
+            Ok(())
+        }
+
+        async fn insert_item(&mut self, order_id: Uuid, item: &NewOrderItem<'_>) -> AppResult<()> {
+            self.items.push((order_id, item.product_id, item.quantity, item.tax_amount.clone()));
+            Ok(())
+        }
+    }
+
+    fn address() -> serde_json::Value {
+        json!({ "recipient_name": "Asha", "city": "Pune", "postal_code": "411001" })
+    }
+
+    #[tokio::test]
+    async fn a_placed_order_is_priced_and_takes_its_stock() {
+        let mut repo = MemoryRepo::default();
+        let seller_id = repo.add_seller(Some("27AAPFU0939F1ZV"));
+        let rice = repo.add_product(seller_id, "50", 10);
+        let buyer_id = Uuid::new_v4();
+
+        let (order_id, stock_changes) = place_seller_order(&mut repo, buyer_id, seller_id, &address(), &[item(rice, 4)])
+            .await
+            .unwrap();
+
+        assert_eq!(repo.orders, [(order_id, seller_id, dec("250"))]);
+        assert_eq!(repo.items, [(order_id, rice, 4, dec("10"))]);
+        assert_eq!(stock_changes, [(rice, None, 10, 6)]);
+        assert_eq!(repo.stock(rice), 6);
+    }
+
+    #[tokio::test]
+    async fn orders_that_cant_be_placed_are_refused() {
+        let mut repo = MemoryRepo::default();
+        let seller_id = repo.add_seller(None);
+        let rival_id = repo.add_seller(None);
+        let rice = repo.add_product(seller_id, "50", 10);
+        let dal = repo.add_product(rival_id, "90", 10);
+        let buyer_id = Uuid::new_v4();
+
+        let place = async |repo: &mut MemoryRepo, items: &[CartItem]| {
+            place_seller_order(repo, buyer_id, seller_id, &address(), items).await.err()
+        };
+
+        let err = place(&mut repo, &[item(rice, 1), item(dal, 1)]).await;
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "another seller's items: {:?}", err);
+
+        let err = place(&mut repo, &[item(rice, 11)]).await;
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "more than is in stock: {:?}", err);
+        assert_eq!(repo.stock(rice), 10);
+        // The caller's transaction would roll back what was inserted before the shortfall
+        repo.orders.clear();
+        repo.items.clear();
+
+        repo.seller(seller_id).delivers = false;
+        let err = place(&mut repo, &[item(rice, 1)]).await;
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "outside the delivery zone: {:?}", err);
+        assert!(repo.orders.is_empty());
+
+        repo.seller(seller_id).delivers = true;
+        repo.seller(seller_id).store_paused = true;
+        let err = place(&mut repo, &[item(rice, 1)]).await;
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "on vacation: {:?}", err);
+
+        repo.seller(seller_id).store_paused = false;
+        repo.seller(seller_id).min_order_value = dec("100");
+        let err = place(&mut repo, &[item(rice, 1)]).await;
+        assert!(matches!(err, Some(AppError::BadRequest(_))), "under the minimum: {:?}", err);
+        assert!(repo.orders.is_empty());
+        assert!(place(&mut repo, &[item(rice, 2)]).await.is_none());
+    }
+}
@@ -0,0 +1,228 @@
+// sessions.rs
+use actix_identity::IdentityExt;
+use actix_session::{Session, SessionExt};
+use actix_web::{
+    body::MessageBody,
+    cookie::{time, Cookie},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderName, HeaderValue, SET_COOKIE},
+        Method,
+    },
+    middleware::Next,
+    web, Error,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::{AppError, AppResult};
+use crate::models::AuditAction;
+use crate::repositories::audit_repository::{self, AuditEntry};
+use crate::utils::get_user_id;
+
+pub const SESSION_COOKIE: &str = "id";
+
+const LOGIN_AT_KEY: &str = "login_at";
+const SEEN_AT_KEY: &str = "seen_at";
+const REMEMBER_ME_KEY: &str = "remember_me";
+const IMPERSONATOR_KEY: &str = "impersonator_id";
+
+/// Names the impersonating admin on every response to an impersonated session
+pub const IMPERSONATION_HEADER: &str = "x-impersonated-by";
+
+/// Account security an impersonating admin can't change for the user
+const IMPERSONATION_BLOCKED: &[&str] = &["/api/user/password", "/api/user/email", "/api/user/account", "/api/user/2fa", "/api/user/tokens"];
+
+/// Activity renews the session at most this often, so not every request rewrites the cookie
+const RENEW_AFTER_SECONDS: i64 = 60;
+
+/// Start the clock on a sign-in. Call after `Identity::login`.
+pub fn start(session: &Session, remember_me: bool) -> AppResult<()> {
+    let now = Utc::now().timestamp_micros();
+    for (key, value) in [(LOGIN_AT_KEY, now), (SEEN_AT_KEY, now)] {
+        session.insert(key, value).map_err(|e| AppError::SessionError(e.to_string()))?;
+    }
+    session.insert(REMEMBER_ME_KEY, remember_me).map_err(|e| AppError::SessionError(e.to_string()))?;
+    session.remove(IMPERSONATOR_KEY);
+    Ok(())
+}
+
+/// Mark the session as the admin's impersonation of whoever it is now signed in as. Call
+/// after `Identity::login` as that user; the session keeps its times.
+pub fn start_impersonation(session: &Session, admin_id: Uuid) -> AppResult<()> {
+    session.insert(IMPERSONATOR_KEY, admin_id).map_err(|e| AppError::SessionError(e.to_string()))
+}
+
+/// End an impersonation, returning the admin to sign back in as
+pub fn stop_impersonation(session: &Session) -> Option<Uuid> {
+    session.remove_as::<Uuid>(IMPERSONATOR_KEY).and_then(Result::ok)
+}
+
+/// The admin impersonating the signed-in user, if any
+pub fn impersonator(session: &Session) -> Option<Uuid> {
+    session.get::<Uuid>(IMPERSONATOR_KEY).ok().flatten()
+}
+
+/// Marks a response whose session cookie should outlive the browser, until the given time
+struct KeepCookieUntil(DateTime<Utc>);
+
+/// Sign out sessions that have run out or predate a password change, and renew the rest;
+/// flag and audit impersonated requests. A session runs out SESSION_IDLE_TIMEOUT_MINUTES
+/// after its last request or SESSION_TIMEOUT_HOURS after signing in, or after
+/// REMEMBER_ME_DAYS with "remember me"; an impersonation stays on the admin's clock.
+/// Must be wrapped inside `IdentityMiddleware`.
+pub async fn enforce(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let config = req.app_data::<web::Data<Config>>().cloned().ok_or(AppError::InternalError)?;
+    let pool = req.app_data::<web::Data<PgPool>>().cloned().ok_or(AppError::InternalError)?;
+    let mut impersonation = None;
+
+    if let Ok(identity) = req.get_identity() {
+        let session = req.get_session();
+        match get_user_id(&identity) {
+            Ok(user_id) if is_current(&session, &config, &pool, user_id).await? => {
+                renew(&session)?;
+                impersonation = impersonator(&session).map(|admin_id| (admin_id, user_id));
+            }
+            _ => identity.logout(),
+        }
+    }
+
+    let changes = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if impersonation.is_some() && changes && IMPERSONATION_BLOCKED.iter().any(|path| req.path().starts_with(path)) {
+        return Err(AppError::Forbidden.into());
+    }
+
+    let mut res = next.call(req).await?;
+
+    if let Some((admin_id, user_id)) = impersonation {
+        if changes {
+            audit_repository::record(pool.get_ref(), AuditEntry {
+                actor_id: Some(admin_id),
+                action: AuditAction::ImpersonatedRequest,
+                subject_id: Some(user_id),
+                details: json!({
+                    "method": res.request().method().as_str(),
+                    "path": res.request().path(),
+                    "status": res.status().as_u16()
+                }),
+            }).await?;
+        }
+        if let Ok(value) = HeaderValue::from_str(&admin_id.to_string()) {
+            res.headers_mut().insert(HeaderName::from_static(IMPERSONATION_HEADER), value);
+        }
+    }
+
+    // Read after the handler, which may have signed in or out
+    if let Some(until) = remembered_until(&res.request().get_session(), &config) {
+        res.response_mut().extensions_mut().insert(KeepCookieUntil(until));
+    }
+    Ok(res)
+}
+
+/// Give a remembered session's cookie an expiry, at the end of the sign-in, so the browser
+/// keeps it; other session cookies go when the browser closes. Must be wrapped outside
+/// `SessionMiddleware`, which writes the cookie.
+pub async fn cookie_lifetime(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let mut res = next.call(req).await?;
+
+    let Some(until) = res.response().extensions().get::<KeepCookieUntil>().map(|keep| keep.0) else {
+        return Ok(res);
+    };
+
+    let cookies: Vec<HeaderValue> = res.headers().get_all(SET_COOKIE).cloned().collect();
+    res.headers_mut().remove(SET_COOKIE);
+    for value in cookies {
+        let cookie = value.to_str().ok().and_then(|value| Cookie::parse(value.to_string()).ok());
+        let value = match cookie {
+            Some(mut cookie) if cookie.name() == SESSION_COOKIE => {
+                let seconds = (until - Utc::now()).num_seconds().max(0);
+                cookie.set_max_age(time::Duration::seconds(seconds));
+                HeaderValue::from_str(&cookie.to_string()).unwrap_or(value)
+            }
+            _ => value,
+        };
+        res.headers_mut().append(SET_COOKIE, value);
+    }
+    Ok(res)
+}
+
+/// Whether the signed-in session is still within its lifetime and its user still has an
+/// account in good standing. Sessions from before lifetimes were kept, or whose times
+/// can't be read, have run out. An impersonation is the admin's session: it's their
+/// password that counts, and they must still be an admin.
+async fn is_current(session: &Session, config: &Config, pool: &PgPool, user_id: Uuid) -> AppResult<bool> {
+    let (Some(login_at), Some(seen_at)) = (timestamp(session, LOGIN_AT_KEY), timestamp(session, SEEN_AT_KEY)) else {
+        return Ok(false);
+    };
+    let now = Utc::now();
+
+    if remembers(session) {
+        if now >= login_at + Duration::days(config.remember_me_days) {
+            return Ok(false);
+        }
+    } else if now >= login_at + Duration::hours(config.session_timeout_hours)
+        || now >= seen_at + Duration::minutes(config.session_idle_timeout_minutes)
+    {
+        return Ok(false);
+    }
+
+    let impersonator_id = impersonator(session);
+    let accounts = sqlx::query!(
+        r#"
+        SELECT u.suspended_at IS NULL AND u.deleted_at IS NULL as "active!",
+               o.password_changed_at,
+               o.is_admin AND o.suspended_at IS NULL AND o.deleted_at IS NULL as "active_admin!"
+        FROM users u
+        JOIN users o ON o.id = $2
+        WHERE u.id = $1
+        "#,
+        user_id,
+        impersonator_id.unwrap_or(user_id)
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    let Some(accounts) = accounts else {
+        return Ok(false);
+    };
+    if !accounts.active || (impersonator_id.is_some() && !accounts.active_admin) {
+        return Ok(false);
+    }
+    Ok(accounts.password_changed_at.is_none_or(|changed_at| changed_at <= login_at))
+}
+
+/// Count the request as activity
+fn renew(session: &Session) -> AppResult<()> {
+    let now = Utc::now();
+    let due = timestamp(session, SEEN_AT_KEY).is_none_or(|seen_at| now - seen_at >= Duration::seconds(RENEW_AFTER_SECONDS));
+    if due {
+        session
+            .insert(SEEN_AT_KEY, now.timestamp_micros())
+            .map_err(|e| AppError::SessionError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// When a signed-in "remember me" session runs out
+fn remembered_until(session: &Session, config: &Config) -> Option<DateTime<Utc>> {
+    let login_at = timestamp(session, LOGIN_AT_KEY)?;
+    remembers(session).then(|| login_at + Duration::days(config.remember_me_days))
+}
+
+fn remembers(session: &Session) -> bool {
+    session.get::<bool>(REMEMBER_ME_KEY).ok().flatten().unwrap_or(false)
+}
+
+fn timestamp(session: &Session, key: &str) -> Option<DateTime<Utc>> {
+    let micros = session.get::<i64>(key).ok().flatten()?;
+    DateTime::from_timestamp_micros(micros)
+}
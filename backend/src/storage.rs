@@ -0,0 +1,271 @@
+// storage.rs
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::primitives::{ByteStream, Length};
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client as S3Client;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::config::{Config, StorageBackend};
+
+/// Files above this go to S3 a part at a time; S3 parts are at least 5 MiB, bar the last
+const S3_PART_SIZE: u64 = 8 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct StorageError(pub String);
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Store the file at `path` under `key`, replacing any file already there, and return
+    /// the URL it can be fetched from. The file is read as it's sent, never whole.
+    async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<String, StorageError>;
+
+    /// A file this app serves itself; None when it doesn't exist, or when the store
+    /// serves its files from its own URLs
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Whether files can be stored right now, for the readiness probe
+    async fn check(&self) -> Result<(), StorageError>;
+}
+
+/// Build the store selected by the configuration: S3, or a local directory served under
+/// /uploads for development without AWS credentials
+pub async fn from_config(config: &Config) -> Result<Arc<dyn Storage>, StorageError> {
+    let storage: Arc<dyn Storage> = match config.storage_backend {
+        StorageBackend::S3 => Arc::new(S3Storage::new(&config.aws_region, &config.s3_bucket_name).await),
+        StorageBackend::Local => Arc::new(
+            LocalStorage::new(&config.local_storage_dir, &config.local_storage_url).await?,
+        ),
+    };
+
+    tracing::info!("File storage in {:?}", config.storage_backend);
+    Ok(storage)
+}
+
+/// One client for the life of the process, rather than loading the AWS configuration
+/// on every upload
+pub struct S3Storage {
+    client: S3Client,
+    bucket: String,
+    region: String,
+}
+
+impl S3Storage {
+    pub async fn new(region: &str, bucket: &str) -> Self {
+        let aws_config = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(region.to_string()))
+            .load()
+            .await;
+
+        S3Storage {
+            client: S3Client::new(&aws_config),
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+        }
+    }
+
+    /// Send the file in parts, so a large one never has to be held in memory; an upload
+    /// that fails part way is aborted rather than left for S3 to bill
+    async fn put_multipart(&self, key: &str, path: &Path, size: u64, content_type: &str) -> Result<(), StorageError> {
+        let upload = self.client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .send()
+            .await
+            .map_err(|e| StorageError(e.to_string()))?;
+        let upload_id = upload
+            .upload_id()
+            .ok_or_else(|| StorageError("S3 returned no upload id".to_string()))?;
+
+        let parts = match self.upload_parts(key, upload_id, path, size).await {
+            Ok(parts) => parts,
+            Err(e) => {
+                let aborted = self.client
+                    .abort_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .send()
+                    .await;
+                if let Err(abort_error) = aborted {
+                    tracing::warn!(key, error = %abort_error, "Failed to abort S3 multipart upload");
+                }
+                return Err(e);
+            }
+        };
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+            .send()
+            .await
+            .map_err(|e| StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn upload_parts(&self, key: &str, upload_id: &str, path: &Path, size: u64) -> Result<Vec<CompletedPart>, StorageError> {
+        let mut parts = Vec::new();
+
+        for (index, offset) in (0..size).step_by(S3_PART_SIZE as usize).enumerate() {
+            let part_number = index as i32 + 1;
+            let body = ByteStream::read_from()
+                .path(path)
+                .offset(offset)
+                .length(Length::Exact(S3_PART_SIZE.min(size - offset)))
+                .build()
+                .await
+                .map_err(|e| StorageError(e.to_string()))?;
+
+            let part = self.client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| StorageError(e.to_string()))?;
+
+            parts.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(part.e_tag().map(str::to_string))
+                    .build(),
+            );
+        }
+
+        Ok(parts)
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<String, StorageError> {
+        let size = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| StorageError(e.to_string()))?
+            .len();
+
+        if size > S3_PART_SIZE {
+            self.put_multipart(key, path, size, content_type).await?;
+        } else {
+            let body = ByteStream::from_path(path)
+                .await
+                .map_err(|e| StorageError(e.to_string()))?;
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .body(body)
+                .content_type(content_type)
+                .send()
+                .await
+                .map_err(|e| StorageError(e.to_string()))?;
+        }
+
+        Ok(format!("https://{}.s3.{}.amazonaws.com/{}", self.bucket, self.region, key))
+    }
+
+    /// Objects are fetched from the bucket directly
+    async fn get(&self, _key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(None)
+    }
+
+    async fn check(&self) -> Result<(), StorageError> {
+        self.client
+            .head_bucket()
+            .bucket(&self.bucket)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| StorageError(e.to_string()))
+    }
+}
+
+/// Files under a directory on this machine. Only one instance can use it, so it's for
+/// development.
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    /// Files are stored under `root` and served at `base_url`/key; the directory is
+    /// created if it's missing
+    pub async fn new(root: impl AsRef<Path>, base_url: &str) -> Result<Self, StorageError> {
+        let root = root.as_ref().to_path_buf();
+        tokio::fs::create_dir_all(&root)
+            .await
+            .map_err(|e| StorageError(format!("Can't create {}: {}", root.display(), e)))?;
+
+        Ok(LocalStorage {
+            root,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// The file's path under the root. Keys are relative paths of plain names; anything
+    /// that could reach outside the root (`..`, an absolute path) has no file.
+    fn path_of(&self, key: &str) -> Option<PathBuf> {
+        let relative = Path::new(key);
+        let is_plain = !key.is_empty()
+            && relative.components().all(|component| matches!(component, Component::Normal(_)));
+
+        is_plain.then(|| self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put_file(&self, key: &str, source: &Path, _content_type: &str) -> Result<String, StorageError> {
+        let path = self
+            .path_of(key)
+            .ok_or_else(|| StorageError(format!("Invalid storage key '{}'", key)))?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| StorageError(e.to_string()))?;
+        }
+        tokio::fs::copy(source, &path)
+            .await
+            .map_err(|e| StorageError(e.to_string()))?;
+
+        Ok(format!("{}/{}", self.base_url, key))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let Some(path) = self.path_of(key) else {
+            return Ok(None);
+        };
+
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            // A directory, e.g. the prefix of an image's variants
+            Err(_) if path.is_dir() => Ok(None),
+            Err(e) => Err(StorageError(e.to_string())),
+        }
+    }
+
+    async fn check(&self) -> Result<(), StorageError> {
+        let metadata = tokio::fs::metadata(&self.root)
+            .await
+            .map_err(|e| StorageError(format!("{}: {}", self.root.display(), e)))?;
+
+        if metadata.permissions().readonly() {
+            return Err(StorageError(format!("{} is read-only", self.root.display())));
+        }
+        Ok(())
+    }
+}
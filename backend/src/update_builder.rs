@@ -0,0 +1,62 @@
+// update_builder.rs
+use sqlx::{Encode, Executor, Postgres, QueryBuilder, Type};
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+
+/// An UPDATE setting only the fields a request gave. Each column is pushed with its bound
+/// value, so the placeholders can't drift out of step with the binds.
+pub struct UpdateBuilder<'args> {
+    query: QueryBuilder<'args, Postgres>,
+    columns: usize,
+}
+
+impl<'args> UpdateBuilder<'args> {
+    pub fn new(table: &'static str) -> Self {
+        UpdateBuilder {
+            query: QueryBuilder::new(format!("UPDATE {} SET ", table)),
+            columns: 0,
+        }
+    }
+
+    /// Set `column` to `value`, if there is one
+    pub fn set<T>(mut self, column: &'static str, value: Option<T>) -> Self
+    where
+        T: 'args + Encode<'args, Postgres> + Type<Postgres>,
+    {
+        if let Some(value) = value {
+            if self.columns > 0 {
+                self.query.push(", ");
+            }
+            self.query.push(column).push(" = ").push_bind(value);
+            self.columns += 1;
+        }
+        self
+    }
+
+    /// Whether no column has been set, leaving nothing to update
+    pub fn is_empty(&self) -> bool {
+        self.columns == 0
+    }
+
+    /// The statement so far, without its WHERE clause
+    pub fn sql(&self) -> &str {
+        self.query.sql()
+    }
+
+    /// Update the row with this id, returning how many rows changed. Does nothing when
+    /// no column was set.
+    pub async fn execute<'c, E>(mut self, executor: E, id: Uuid) -> AppResult<u64>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        if self.is_empty() {
+            return Ok(0);
+        }
+
+        self.query.push(" WHERE id = ").push_bind(id);
+        let result = self.query.build().execute(executor).await?;
+
+        Ok(result.rows_affected())
+    }
+}
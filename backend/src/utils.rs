@@ -27,6 +27,14 @@ pub fn get_user_id_opt(identity: &Identity) -> AppResult<Option<Uuid>> {
     }
 }
 
+/// Extract user ID for endpoints that also serve anonymous visitors
+pub fn get_viewer_id(identity: Option<&Identity>) -> AppResult<Option<Uuid>> {
+    match identity {
+        Some(identity) => get_user_id_opt(identity),
+        None => Ok(None),
+    }
+}
+
 /// Validate email format
 pub fn validate_email(email: &str) -> bool {
     // Simple email validation
@@ -34,6 +42,27 @@ pub fn validate_email(email: &str) -> bool {
     email_regex.is_match(email)
 }
 
+/// Coordinates come as a pair, within the usual degree ranges
+pub fn validate_location(latitude: Option<f64>, longitude: Option<f64>) -> AppResult<Option<(f64, f64)>> {
+    match (latitude, longitude) {
+        (None, None) => Ok(None),
+        (Some(latitude), Some(longitude))
+            if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) =>
+        {
+            Ok(Some((latitude, longitude)))
+        }
+        (Some(_), Some(_)) => Err(AppError::BadRequest(
+            "Latitude must be within ±90 and longitude within ±180".to_string(),
+        )),
+        _ => Err(AppError::BadRequest("Latitude and longitude must be given together".to_string())),
+    }
+}
+
+/// Pincodes are compared upper case without spaces, like the database's normalize_pincode()
+pub fn normalize_pincode(pincode: &str) -> String {
+    pincode.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase()
+}
+
 /// Sanitize phone number
 pub fn sanitize_phone(phone: &str) -> String {
     // Remove all non-digit characters
@@ -44,12 +73,50 @@ pub fn sanitize_phone(phone: &str) -> String {
 pub fn generate_random_string(length: usize) -> String {
     use rand::Rng;
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rng();
 
     (0..length)
         .map(|_| {
-            let idx = rng.gen_range(0..CHARSET.len());
+            let idx = rng.random_range(0..CHARSET.len());
             CHARSET[idx] as char
         })
         .collect()
-}
\ No newline at end of file
+}
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Page/limit/offset resolved from optional query parameters
+#[derive(Debug, Clone, Copy)]
+pub struct Pagination {
+    pub page: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Pagination {
+    /// Clamp client-supplied values: page starts at 1, limit is capped at MAX_PAGE_SIZE
+    pub fn new(page: Option<i32>, limit: Option<i32>) -> Self {
+        let page = page.map(i64::from).unwrap_or(1).max(1);
+        let limit = limit
+            .map(i64::from)
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        Pagination {
+            page,
+            limit,
+            offset: (page - 1) * limit,
+        }
+    }
+
+    /// Pagination block included in list responses
+    pub fn to_json(&self, total: i64) -> serde_json::Value {
+        serde_json::json!({
+            "page": self.page,
+            "limit": self.limit,
+            "total": total,
+            "pages": (total + self.limit - 1) / self.limit
+        })
+    }
+}
@@ -0,0 +1,650 @@
+// validation.rs
+use actix_web::{error::JsonPayloadError, HttpRequest};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{
+    AddToCartRequest, CartItem, ChangeEmailRequest, ChangePasswordRequest, CreateAddressRequest, CreateDeliverySlotRequest, SetDeliveryZoneRequest,
+    CreateProductRequest, CreateRecurringOrderRequest, CreateReviewRequest, CreateVariantRequest, PasswordResetVerify, RecordPayoutRequest, RegisterPushDeviceRequest, RegisterRequest,
+    OrderStatus, ReplaceCartRequest, SetCartQuantityRequest, ShipmentDetails, SetCategoryTaxRequest, SetPriceTiersRequest, StockAdjustRequest, UpdateAddressRequest,
+    UpdateOrderStatusRequest, UpdateProductRequest, UpdateProfileRequest, UpdateRecurringOrderRequest, UpdateSettingsRequest, UpdateVariantRequest,
+    CreateWebhookRequest, UpdateWebhookRequest, CreateRfqRequest, CreateQuoteRequest, CreateLocationRequest, UpdateLocationRequest,
+    SetLocationStockRequest, TransferStockRequest, CreateOrderCommentRequest, CreateDealRequest,
+};
+use crate::utils::{normalize_pincode, sanitize_phone, validate_email};
+
+const MAX_SLOT_CAPACITY: i32 = 1000;
+const MAX_SLOT_HOURS: i64 = 24;
+const MAX_CART_LINES: usize = 100;
+const MAX_MIN_INCREMENT: i32 = 1000;
+const MAX_PRODUCT_CODE_LENGTH: usize = 64;
+const MAX_RECURRING_ORDER_LINES: usize = 50;
+const MAX_RECURRING_INTERVAL_DAYS: i32 = 90;
+const MAX_DELIVERY_PINCODES: usize = 1000;
+const MAX_DELIVERY_RADIUS_KM: f64 = 500.0;
+const MAX_LEAD_TIME_HOURS: i32 = 720;
+const MAX_ORDER_COMMENT_LENGTH: usize = 1000;
+const MAX_WEBHOOK_URL_LENGTH: usize = 2048;
+const MAX_RFQ_DAYS: i64 = 30;
+const MAX_RFQ_NOTE_LENGTH: usize = 1000;
+const MAX_DEAL_DAYS: i64 = 30;
+// FCM registration tokens run to a few hundred characters
+const MAX_PUSH_TOKEN_LENGTH: usize = 4096;
+
+/// Field-level checks on a request body, run before touching the database. Every problem
+/// is reported at once, in a 422 with one {"field", "message"} entry each.
+pub trait Validate {
+    /// Record every problem with this request in `errors`
+    fn check(&self, errors: &mut FieldErrors);
+
+    fn validate(&self) -> AppResult<()> {
+        let mut errors = FieldErrors::default();
+        self.check(&mut errors);
+        errors.into_result()
+    }
+}
+
+/// The problems found so far, in field order
+#[derive(Default)]
+pub struct FieldErrors(Vec<serde_json::Value>);
+
+impl FieldErrors {
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.push(json!({ "field": field, "message": message.into() }));
+    }
+
+    pub fn into_result(self) -> AppResult<()> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ValidationFailed(self.0))
+        }
+    }
+
+    fn email(&mut self, field: &str, email: &str) {
+        if !validate_email(email.trim()) {
+            self.add(field, "must be a valid email address");
+        }
+    }
+
+    /// Length of the trimmed text, in characters
+    fn length(&mut self, field: &str, text: &str, min: usize, max: usize) {
+        let length = text.trim().chars().count();
+        if length < min || length > max {
+            if min == 1 {
+                self.add(field, format!("is required and must be at most {} characters", max));
+            } else {
+                self.add(field, format!("must be {}-{} characters", min, max));
+            }
+        }
+    }
+
+    /// Digits with the usual separators; 7 to 15 digits once `sanitize_phone` strips the rest
+    fn phone(&mut self, field: &str, phone: &str) {
+        let allowed = phone.chars().all(|c| c.is_ascii_digit() || " +-().".contains(c));
+        let digits = sanitize_phone(phone).len();
+        if !allowed || !(7..=15).contains(&digits) || phone.len() > 20 {
+            self.add(field, "must be a phone number of 7 to 15 digits");
+        }
+    }
+
+    /// Prices are stored with two decimals, so they must still be positive once rounded
+    fn price(&mut self, field: &str, price: &BigDecimal) {
+        if price.round(2) <= BigDecimal::zero() {
+            self.add(field, "must be greater than 0");
+        }
+    }
+
+    fn non_negative_amount(&mut self, field: &str, amount: &BigDecimal) {
+        if amount.round(2) < BigDecimal::zero() {
+            self.add(field, "can't be negative");
+        }
+    }
+
+    fn stock(&mut self, field: &str, quantity: i32) {
+        if quantity < 0 {
+            self.add(field, "can't be negative");
+        }
+    }
+
+    /// Blank SKUs are left out or cleared, so they pass
+    fn sku(&mut self, field: &str, sku: &str) {
+        let sku = sku.trim();
+        if sku.len() > MAX_PRODUCT_CODE_LENGTH || !sku.chars().all(|c| c.is_ascii_alphanumeric() || "-./_".contains(c)) {
+            self.add(field, format!("must be at most {} letters, digits, hyphens, dots, slashes and underscores", MAX_PRODUCT_CODE_LENGTH));
+        }
+    }
+
+    /// As printed under the bars; blank ones pass, as for SKUs
+    fn barcode(&mut self, field: &str, barcode: &str) {
+        let barcode = barcode.trim();
+        if barcode.len() > MAX_PRODUCT_CODE_LENGTH || !barcode.chars().all(|c| c.is_ascii_alphanumeric()) {
+            self.add(field, format!("must be at most {} letters and digits", MAX_PRODUCT_CODE_LENGTH));
+        }
+    }
+
+    fn min_increment(&mut self, field: &str, increment: i32) {
+        if !(1..=MAX_MIN_INCREMENT).contains(&increment) {
+            self.add(field, format!("must be 1-{}", MAX_MIN_INCREMENT));
+        }
+    }
+
+    /// Cart-style lines: positive quantities, each product and variant once
+    fn lines(&mut self, items: &[CartItem]) {
+        for (index, item) in items.iter().enumerate() {
+            if item.quantity <= 0 {
+                self.add(&format!("items[{}].quantity", index), "must be greater than 0");
+            }
+            let repeated = items[..index]
+                .iter()
+                .any(|earlier| earlier.product_id == item.product_id && earlier.variant_id == item.variant_id);
+            if repeated {
+                self.add(&format!("items[{}]", index), "repeats an earlier line");
+            }
+        }
+    }
+
+    fn recurring_items(&mut self, items: &[CartItem]) {
+        if items.is_empty() || items.len() > MAX_RECURRING_ORDER_LINES {
+            self.add("items", format!("must have 1-{} lines", MAX_RECURRING_ORDER_LINES));
+        }
+        self.lines(items);
+    }
+
+    fn interval_days(&mut self, field: &str, days: i32) {
+        if !(1..=MAX_RECURRING_INTERVAL_DAYS).contains(&days) {
+            self.add(field, format!("must be 1-{} days", MAX_RECURRING_INTERVAL_DAYS));
+        }
+    }
+}
+
+/// Turn malformed JSON bodies into the same 422 shape, naming the field when serde does
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::Deserialize(e) => {
+            let message = e.to_string();
+            // serde reports "missing field `x`" and "unknown field `x`" with the name quoted
+            let field = message
+                .split('`')
+                .nth(1)
+                .filter(|_| message.starts_with("missing field") || message.starts_with("unknown field"))
+                .unwrap_or("body");
+
+            let mut errors = FieldErrors::default();
+            errors.add(field, message.split(" at line ").next().unwrap_or(&message));
+            errors.into_result().unwrap_err().into()
+        }
+        JsonPayloadError::Overflow { limit } | JsonPayloadError::OverflowKnownLength { limit, .. } => {
+            AppError::PayloadTooLarge(format!("JSON body exceeds {}KB limit", limit / 1024)).into()
+        }
+        err => AppError::BadRequest(err.to_string()).into(),
+    }
+}
+
+impl Validate for RegisterRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        // The password is held to the configured policy by `passwords::validate`
+        errors.email("email", &self.email);
+        if let Some(name) = &self.name {
+            errors.length("name", name, 1, 255);
+        }
+        if let Some(phone) = &self.phone {
+            errors.phone("phone", phone);
+        }
+    }
+}
+
+// Only the new password, which `passwords::validate` holds to the policy
+impl Validate for PasswordResetVerify {
+    fn check(&self, _errors: &mut FieldErrors) {}
+}
+
+impl Validate for ChangePasswordRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.new_password == self.current_password {
+            errors.add("new_password", "must differ from the current password");
+        }
+    }
+}
+
+impl Validate for ChangeEmailRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.email("new_email", &self.new_email);
+    }
+}
+
+impl Validate for UpdateProfileRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(name) = &self.name {
+            errors.length("name", name, 1, 255);
+        }
+        if let Some(phone) = &self.phone {
+            errors.phone("phone", phone);
+        }
+    }
+}
+
+impl Validate for UpdateSettingsRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(min_order_value) = &self.min_order_value {
+            errors.non_negative_amount("min_order_value", min_order_value);
+        }
+        if let Some(delivery_fee) = &self.delivery_fee {
+            errors.non_negative_amount("delivery_fee", delivery_fee);
+        }
+        if let Some(tax_id) = self.tax_id.as_deref().map(str::trim).filter(|tax_id| !tax_id.is_empty())
+            && (tax_id.len() > 32 || !tax_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        {
+            errors.add("tax_id", "must be at most 32 letters, digits and hyphens");
+        }
+        if self.tax_name.as_ref().is_some_and(|tax_name| tax_name.trim().chars().count() > 255) {
+            errors.add("tax_name", "must be at most 255 characters");
+        }
+        if self.lead_time_hours.is_some_and(|hours| !(1..=MAX_LEAD_TIME_HOURS).contains(&hours)) {
+            errors.add("lead_time_hours", format!("must be between 1 and {}", MAX_LEAD_TIME_HOURS));
+        }
+    }
+}
+
+impl Validate for CreateWebhookRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.length("url", &self.url, 1, MAX_WEBHOOK_URL_LENGTH);
+        if self.events.is_empty() {
+            errors.add("events", "must list at least one event");
+        }
+    }
+}
+
+impl Validate for UpdateWebhookRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(url) = &self.url {
+            errors.length("url", url, 1, MAX_WEBHOOK_URL_LENGTH);
+        }
+        if self.events.as_ref().is_some_and(Vec::is_empty) {
+            errors.add("events", "must list at least one event");
+        }
+    }
+}
+
+impl Validate for CreateLocationRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.length("name", &self.name, 1, 100);
+        if let Some(address) = &self.address {
+            errors.length("address", address, 0, 500);
+        }
+    }
+}
+
+impl Validate for UpdateLocationRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(name) = &self.name {
+            errors.length("name", name, 1, 100);
+        }
+        if let Some(address) = &self.address {
+            errors.length("address", address, 0, 500);
+        }
+        if self.is_default == Some(false) {
+            errors.add("is_default", "make another location the default instead");
+        }
+    }
+}
+
+impl Validate for SetLocationStockRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.quantity < 0 {
+            errors.add("quantity", "must be at least 0");
+        }
+        if let Some(note) = &self.note {
+            errors.length("note", note, 0, 500);
+        }
+    }
+}
+
+impl Validate for TransferStockRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.quantity <= 0 {
+            errors.add("quantity", "must be greater than 0");
+        }
+        if self.from_location_id == self.to_location_id {
+            errors.add("to_location_id", "must differ from from_location_id");
+        }
+    }
+}
+
+impl Validate for CreateRfqRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.product_id.is_some() == self.category_id.is_some() {
+            errors.add("product_id", "give either a product_id or a category_id");
+        }
+        if self.quantity <= 0 {
+            errors.add("quantity", "must be greater than 0");
+        }
+        if let Some(target_price) = &self.target_price {
+            errors.price("target_price", target_price);
+        }
+        if let Some(note) = &self.note {
+            errors.length("note", note, 1, MAX_RFQ_NOTE_LENGTH);
+        }
+        if let Some(expires_at) = self.expires_at
+            && (expires_at <= Utc::now() || expires_at > Utc::now() + Duration::days(MAX_RFQ_DAYS))
+        {
+            errors.add("expires_at", format!("must be within the next {} days", MAX_RFQ_DAYS));
+        }
+    }
+}
+
+impl Validate for CreateDealRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.price("deal_price", &self.deal_price);
+        if self.max_quantity <= 0 {
+            errors.add("max_quantity", "must be greater than 0");
+        }
+        let starts_at = self.starts_at.unwrap_or_else(Utc::now).max(Utc::now());
+        if self.ends_at <= starts_at {
+            errors.add("ends_at", "must be after the deal starts");
+        } else if self.ends_at - starts_at > Duration::days(MAX_DEAL_DAYS) {
+            errors.add("ends_at", format!("a deal can last at most {} days", MAX_DEAL_DAYS));
+        }
+    }
+}
+
+impl Validate for CreateQuoteRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.price("price_per_unit", &self.price_per_unit);
+        if self.quantity.is_some_and(|quantity| quantity <= 0) {
+            errors.add("quantity", "must be greater than 0");
+        }
+        if let Some(note) = &self.note {
+            errors.length("note", note, 1, MAX_RFQ_NOTE_LENGTH);
+        }
+    }
+}
+
+impl Validate for SetCategoryTaxRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(tax_rate) = &self.tax_rate
+            && (*tax_rate < BigDecimal::zero() || *tax_rate > 100)
+        {
+            errors.add("tax_rate", "must be between 0 and 100");
+        }
+    }
+}
+
+impl Validate for CreateProductRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.length("name", &self.name, 1, 255);
+        errors.price("price_per_unit", &self.price_per_unit);
+        errors.stock("stock_qty", self.stock_qty);
+        if let Some(increment) = self.min_increment {
+            errors.min_increment("min_increment", increment);
+        }
+        if let Some(sku) = &self.sku {
+            errors.sku("sku", sku);
+        }
+        if let Some(barcode) = &self.barcode {
+            errors.barcode("barcode", barcode);
+        }
+    }
+}
+
+impl Validate for UpdateProductRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(name) = &self.name {
+            errors.length("name", name, 1, 255);
+        }
+        if let Some(price) = &self.price_per_unit {
+            errors.price("price_per_unit", price);
+        }
+        if let Some(stock_qty) = self.stock_qty {
+            errors.stock("stock_qty", stock_qty);
+        }
+        if let Some(increment) = self.min_increment {
+            errors.min_increment("min_increment", increment);
+        }
+        if let Some(sku) = &self.sku {
+            errors.sku("sku", sku);
+        }
+        if let Some(barcode) = &self.barcode {
+            errors.barcode("barcode", barcode);
+        }
+    }
+}
+
+impl Validate for CreateVariantRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.length("name", &self.name, 1, 100);
+        errors.price("price_per_unit", &self.price_per_unit);
+        errors.stock("stock_qty", self.stock_qty);
+    }
+}
+
+impl Validate for UpdateVariantRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(name) = &self.name {
+            errors.length("name", name, 1, 100);
+        }
+        if let Some(price) = &self.price_per_unit {
+            errors.price("price_per_unit", price);
+        }
+        if let Some(stock_qty) = self.stock_qty {
+            errors.stock("stock_qty", stock_qty);
+        }
+    }
+}
+
+/// Most quantity tiers one product can have
+const MAX_PRICE_TIERS: usize = 10;
+
+impl Validate for SetPriceTiersRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.tiers.len() > MAX_PRICE_TIERS {
+            errors.add("tiers", format!("can have at most {} tiers", MAX_PRICE_TIERS));
+        }
+        for (index, tier) in self.tiers.iter().enumerate() {
+            if tier.min_qty < 2 {
+                errors.add(&format!("tiers[{}].min_qty", index), "must be at least 2");
+            }
+            errors.price(&format!("tiers[{}].unit_price", index), &tier.unit_price);
+        }
+
+        // Buying more should never cost more per unit
+        let mut tiers: Vec<_> = self.tiers.iter().collect();
+        tiers.sort_by_key(|tier| tier.min_qty);
+        for pair in tiers.windows(2) {
+            if pair[0].min_qty == pair[1].min_qty {
+                errors.add("tiers", format!("has more than one tier from {} units", pair[0].min_qty));
+            } else if pair[1].unit_price >= pair[0].unit_price {
+                errors.add("tiers", "unit prices must fall as min_qty rises");
+            }
+        }
+    }
+}
+
+impl Validate for StockAdjustRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.quantity_change == 0 {
+            errors.add("quantity_change", "can't be 0");
+        }
+        errors.length("note", &self.note, 1, 255);
+    }
+}
+
+impl Validate for AddToCartRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.quantity <= 0 {
+            errors.add("quantity", "must be greater than 0");
+        }
+    }
+}
+
+impl Validate for SetCartQuantityRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.stock("quantity", self.quantity);
+    }
+}
+
+impl Validate for ReplaceCartRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.items.len() > MAX_CART_LINES {
+            errors.add("items", format!("can have at most {} lines", MAX_CART_LINES));
+        }
+        errors.lines(&self.items);
+    }
+}
+
+impl Validate for CreateRecurringOrderRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.recurring_items(&self.items);
+        errors.interval_days("interval_days", self.interval_days);
+        if self.starts_at.is_some_and(|starts_at| starts_at <= Utc::now()) {
+            errors.add("starts_at", "must be in the future");
+        }
+    }
+}
+
+impl Validate for UpdateRecurringOrderRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(items) = &self.items {
+            errors.recurring_items(items);
+        }
+        if let Some(interval_days) = self.interval_days {
+            errors.interval_days("interval_days", interval_days);
+        }
+    }
+}
+
+impl Validate for ShipmentDetails {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(courier_name) = &self.courier_name {
+            errors.length("courier_name", courier_name, 1, 100);
+        }
+        if let Some(tracking_number) = &self.tracking_number {
+            errors.length("tracking_number", tracking_number, 1, 100);
+        }
+        if self.expected_delivery_date.is_some_and(|date| date < Utc::now().date_naive()) {
+            errors.add("expected_delivery_date", "can't be in the past");
+        }
+    }
+}
+
+impl Validate for UpdateOrderStatusRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.status != OrderStatus::Shipped && !self.shipment.is_empty() {
+            errors.add("status", "shipment details can only be given when marking the order shipped");
+        }
+        self.shipment.check(errors);
+    }
+}
+
+impl Validate for CreateOrderCommentRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.length("body", &self.body, 1, MAX_ORDER_COMMENT_LENGTH);
+    }
+}
+
+impl Validate for CreateDeliverySlotRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.starts_at <= Utc::now() {
+            errors.add("starts_at", "must be in the future");
+        }
+        if self.ends_at <= self.starts_at {
+            errors.add("ends_at", "must be after starts_at");
+        } else if self.ends_at - self.starts_at > Duration::hours(MAX_SLOT_HOURS) {
+            errors.add("ends_at", format!("must be at most {} hours after starts_at", MAX_SLOT_HOURS));
+        }
+        if !(1..=MAX_SLOT_CAPACITY).contains(&self.capacity) {
+            errors.add("capacity", format!("must be 1-{}", MAX_SLOT_CAPACITY));
+        }
+    }
+}
+
+impl Validate for SetDeliveryZoneRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if self.pincodes.len() > MAX_DELIVERY_PINCODES {
+            errors.add("pincodes", format!("must list at most {} pincodes", MAX_DELIVERY_PINCODES));
+        }
+        for (index, pincode) in self.pincodes.iter().enumerate() {
+            let pincode = normalize_pincode(pincode);
+            let valid = pincode.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+            if !valid || !(3..=10).contains(&pincode.len()) {
+                errors.add(&format!("pincodes[{}]", index), "must be 3-10 letters, digits or hyphens");
+            }
+        }
+        if self.radius_km.is_some_and(|radius| !(radius > 0.0 && radius <= MAX_DELIVERY_RADIUS_KM)) {
+            errors.add("radius_km", format!("must be greater than 0 and at most {}", MAX_DELIVERY_RADIUS_KM));
+        }
+    }
+}
+
+impl Validate for RegisterPushDeviceRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.length("token", &self.token, 1, MAX_PUSH_TOKEN_LENGTH);
+    }
+}
+
+impl Validate for RecordPayoutRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.price("amount", &self.amount);
+        if let Some(reference) = &self.reference {
+            errors.length("reference", reference, 0, 255);
+        }
+    }
+}
+
+impl Validate for CreateReviewRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if !(1..=5).contains(&self.rating) {
+            errors.add("rating", "must be between 1 and 5");
+        }
+    }
+}
+
+impl Validate for CreateAddressRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        errors.length("recipient_name", &self.recipient_name, 1, 255);
+        errors.phone("phone", &self.phone);
+        errors.length("line1", &self.line1, 1, 255);
+        errors.length("city", &self.city, 1, 100);
+        errors.length("state", &self.state, 1, 100);
+        errors.length("postal_code", &self.postal_code, 1, 20);
+        if let Some(country) = &self.country {
+            country_code(errors, country);
+        }
+    }
+}
+
+impl Validate for UpdateAddressRequest {
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(recipient_name) = &self.recipient_name {
+            errors.length("recipient_name", recipient_name, 1, 255);
+        }
+        if let Some(phone) = &self.phone {
+            errors.phone("phone", phone);
+        }
+        if let Some(line1) = &self.line1 {
+            errors.length("line1", line1, 1, 255);
+        }
+        if let Some(city) = &self.city {
+            errors.length("city", city, 1, 100);
+        }
+        if let Some(state) = &self.state {
+            errors.length("state", state, 1, 100);
+        }
+        if let Some(postal_code) = &self.postal_code {
+            errors.length("postal_code", postal_code, 1, 20);
+        }
+        if let Some(country) = &self.country {
+            country_code(errors, country);
+        }
+    }
+}
+
+/// Countries are stored as ISO 3166-1 alpha-2 codes
+fn country_code(errors: &mut FieldErrors, country: &str) {
+    let country = country.trim();
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        errors.add("country", "must be a 2-letter ISO code");
+    }
+}
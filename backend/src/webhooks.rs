@@ -0,0 +1,354 @@
+// webhooks.rs
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{PgExecutor, PgPool};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+use crate::errors::AppResult;
+use crate::models::{WebhookDeliveryStatus, WebhookEvent};
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Attempts before a delivery is given up as failed
+pub const MAX_ATTEMPTS: i32 = 8;
+/// The wait after the first failed attempt, doubling with each one after
+const FIRST_RETRY_SECONDS: i64 = 30;
+/// `t=<unix time>,v1=<hex HMAC-SHA256 of "<t>.<body>">`, as Stripe signs its webhooks
+pub const SIGNATURE_HEADER: &str = "StreetSource-Signature";
+
+static HTTP: OnceLock<reqwest::Client> = OnceLock::new();
+static HTTP_PRIVATE: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Why an attempt failed, as the delivery log shows it. Only these are kept: what the
+/// receiver answered, or why it couldn't be reached, would let a webhook read back
+/// whatever its URL leads to.
+pub const ERROR_BLOCKED_ADDRESS: &str = "blocked_address";
+pub const ERROR_TIMEOUT: &str = "timeout";
+pub const ERROR_CONNECTION_FAILED: &str = "connection_failed";
+pub const ERROR_UNEXPECTED_STATUS: &str = "unexpected_status";
+pub const ERROR_REQUEST_FAILED: &str = "request_failed";
+
+/// A delivery claimed for an attempt, with where it goes
+struct Claimed {
+    id: Uuid,
+    event_id: Uuid,
+    event: WebhookEvent,
+    payload: serde_json::Value,
+    attempts: i32,
+    created_at: DateTime<Utc>,
+    url: String,
+    secret: String,
+}
+
+/// Send webhook deliveries as they come due, until the server stops. Unless
+/// `allow_private` they are only ever sent to public addresses. Each delivery is claimed
+/// before it's sent, so several instances can run this side by side.
+pub async fn run(pool: PgPool, allow_private: bool) {
+    let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        ticker.tick().await;
+        match deliver_due(&pool, allow_private).await {
+            Ok(0) => {}
+            Ok(attempts) => tracing::info!(attempts, "Sent webhook deliveries"),
+            Err(e) => tracing::error!(error = %e, "Webhook delivery run failed"),
+        }
+    }
+}
+
+/// Attempt every delivery that is due, one at a time. Returns how many were attempted,
+/// delivered or not.
+pub async fn deliver_due(pool: &PgPool, allow_private: bool) -> AppResult<usize> {
+    let mut attempts = 0;
+
+    while let Some(delivery) = claim_next_due(pool).await? {
+        let outcome = send(&delivery, allow_private).await;
+        record_attempt(pool, &delivery, outcome).await?;
+        attempts += 1;
+    }
+
+    Ok(attempts)
+}
+
+/// Queue `event` for each of the seller's active webhooks that subscribe to it. Call it
+/// in the transaction that made the change, so the event is sent only if it commits.
+pub async fn enqueue<'e>(
+    executor: impl PgExecutor<'e>,
+    seller_id: Uuid,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_deliveries (id, webhook_id, event_id, event, payload)
+        SELECT gen_random_uuid(), w.id, $2, $3, $4
+        FROM webhooks w
+        WHERE w.seller_id = $1 AND w.is_active AND $3 = ANY(w.events)
+        "#,
+        seller_id,
+        Uuid::new_v4(),
+        event as WebhookEvent,
+        data
+    )
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// How long to wait before the next attempt, after `attempts` have failed
+pub fn retry_delay(attempts: i32) -> Duration {
+    Duration::seconds(FIRST_RETRY_SECONDS << (attempts - 1).clamp(0, 20))
+}
+
+/// The signature header for `body` sent at `timestamp`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Why a webhook can't be sent to `url`, if it can't. Unless private URLs are allowed
+/// it must be https:// and not name this machine or a private network by address or
+/// by a local-only host name. Names are checked again once resolved, when sent.
+pub fn check_url(url: &str, allow_private: bool) -> Option<&'static str> {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return Some("must be a valid URL");
+    };
+    let Some(host) = parsed.host_str() else {
+        return Some("must be a valid URL");
+    };
+    if !matches!(parsed.scheme(), "https" | "http") {
+        return Some("must be an https:// URL");
+    }
+    if allow_private {
+        return None;
+    }
+    if parsed.scheme() != "https" {
+        return Some("must be an https:// URL");
+    }
+
+    let host = host.to_lowercase();
+    let public = match host_address(&parsed) {
+        Some(ip) => is_public(ip),
+        None => host != "localhost" && ![".localhost", ".local", ".internal"].iter().any(|suffix| host.ends_with(suffix)),
+    };
+
+    (!public).then_some("must be a public address")
+}
+
+/// The URL's host, if it's an address rather than a name. The URL parser writes
+/// addresses in their usual form, whatever form they came in.
+fn host_address(url: &reqwest::Url) -> Option<IpAddr> {
+    url.host_str()?.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Whether `ip` is reachable on the public internet, rather than this machine, a private
+/// or carrier-grade NAT network, a link-local address (cloud metadata services among
+/// them), or an IPv4 address in IPv6 form
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // 0.0.0.0/8, "this network"
+                || first == 0
+                // 100.64.0.0/10, carrier-grade NAT
+                || (first == 100 && (second & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public(IpAddr::V4(mapped)),
+            // Unique local (fc00::/7) and link-local (fe80::/10) addresses
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (ip.segments()[0] & 0xfe00) == 0xfc00
+                    || (ip.segments()[0] & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves host names for webhook requests to their public addresses only, so a name
+/// pointing into the private network (or changed to, after the URL was checked) is
+/// never connected to
+struct PublicResolver;
+
+/// The error a webhook request fails with when its host has no public address
+#[derive(Debug)]
+struct BlockedAddress;
+
+impl std::fmt::Display for BlockedAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the host has no public address")
+    }
+}
+
+impl std::error::Error for BlockedAddress {}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(Box::new(BlockedAddress) as Box<dyn std::error::Error + Send + Sync>);
+            }
+            Ok(Box::new(addresses.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// The client webhooks are sent with; unless `allow_private` it only connects to
+/// public addresses
+fn http_client(allow_private: bool) -> &'static reqwest::Client {
+    let build = |allow_private: bool| {
+        let builder = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            // A redirect could lead anywhere, including places the URL check kept out
+            .redirect(reqwest::redirect::Policy::none())
+            // Nor may a proxy resolve the name for us
+            .no_proxy();
+        let builder = if allow_private { builder } else { builder.dns_resolver(Arc::new(PublicResolver)) };
+        builder.build().unwrap_or_default()
+    };
+
+    if allow_private {
+        HTTP_PRIVATE.get_or_init(|| build(true))
+    } else {
+        HTTP.get_or_init(|| build(false))
+    }
+}
+
+/// The delivery log's category for a request that got no response
+fn failure_category(e: &reqwest::Error) -> &'static str {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(error) = source {
+        if error.is::<BlockedAddress>() {
+            return ERROR_BLOCKED_ADDRESS;
+        }
+        source = error.source();
+    }
+
+    if e.is_timeout() {
+        ERROR_TIMEOUT
+    } else if e.is_connect() {
+        ERROR_CONNECTION_FAILED
+    } else {
+        ERROR_REQUEST_FAILED
+    }
+}
+
+/// Claim the delivery due soonest, if any, counting the attempt about to be made
+async fn claim_next_due(pool: &PgPool) -> AppResult<Option<Claimed>> {
+    let delivery = sqlx::query_as!(
+        Claimed,
+        r#"
+        UPDATE webhook_deliveries d
+        SET attempts = d.attempts + 1, next_attempt_at = NOW() + make_interval(secs => $1)
+        FROM webhooks w
+        WHERE w.id = d.webhook_id
+          AND d.id = (
+              SELECT id FROM webhook_deliveries
+              WHERE status = 'pending' AND next_attempt_at <= NOW()
+              ORDER BY next_attempt_at
+              LIMIT 1
+              FOR UPDATE SKIP LOCKED
+          )
+        RETURNING d.id, d.event_id, d.event as "event: WebhookEvent", d.payload, d.attempts, d.created_at,
+                  w.url, w.secret
+        "#,
+        // Long enough for the request to finish or time out
+        2.0 * REQUEST_TIMEOUT.as_secs_f64()
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(delivery)
+}
+
+/// POST the delivery, returning the response status, and the category of failure unless
+/// it was a 2xx
+async fn send(delivery: &Claimed, allow_private: bool) -> (Option<i32>, Option<&'static str>) {
+    // An address in the URL isn't resolved, so it's checked here; names are resolved to
+    // public addresses only
+    let address = reqwest::Url::parse(&delivery.url).ok().and_then(|url| host_address(&url));
+    if !allow_private && address.is_some_and(|ip| !is_public(ip)) {
+        tracing::warn!(delivery_id = %delivery.id, "Webhook URL is not a public address");
+        return (None, Some(ERROR_BLOCKED_ADDRESS));
+    }
+
+    let body = json!({
+        "id": delivery.event_id,
+        "type": delivery.event,
+        "created_at": delivery.created_at,
+        "data": delivery.payload
+    })
+        .to_string();
+    let signature = sign(&delivery.secret, Utc::now().timestamp(), body.as_bytes());
+
+    let response = http_client(allow_private)
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header("StreetSource-Event", json!(delivery.event).as_str().unwrap_or_default())
+        .header("StreetSource-Delivery", delivery.id.to_string())
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16().into()), None),
+        // The body isn't read, let alone kept
+        Ok(response) => (Some(response.status().as_u16().into()), Some(ERROR_UNEXPECTED_STATUS)),
+        Err(e) => {
+            tracing::warn!(delivery_id = %delivery.id, error = %e, "Webhook request failed");
+            (None, Some(failure_category(&e)))
+        }
+    }
+}
+
+/// Mark the delivery sent, or schedule its retry, or give up on it
+async fn record_attempt(pool: &PgPool, delivery: &Claimed, (response_status, error): (Option<i32>, Option<&'static str>)) -> AppResult<()> {
+    let (status, next_attempt_at) = match &error {
+        None => (WebhookDeliveryStatus::Delivered, Utc::now()),
+        Some(_) if delivery.attempts >= MAX_ATTEMPTS => (WebhookDeliveryStatus::Failed, Utc::now()),
+        Some(_) => (WebhookDeliveryStatus::Pending, Utc::now() + retry_delay(delivery.attempts)),
+    };
+
+    if let Some(error) = error {
+        tracing::warn!(delivery_id = %delivery.id, attempts = delivery.attempts, response_status, error, "Webhook delivery failed");
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2, next_attempt_at = $3, last_response_status = $4, last_error = $5,
+            delivered_at = CASE WHEN $2 = 'delivered'::webhook_delivery_status THEN NOW() END
+        WHERE id = $1
+        "#,
+        delivery.id,
+        status as WebhookDeliveryStatus,
+        next_attempt_at,
+        response_status,
+        error
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -1,22 +1,49 @@
 // ws.rs
 use actix_identity::Identity;
-use actix_web::{web, HttpRequest, HttpResponse};
-use actix_ws::{Message, MessageStream, Session};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
 use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{interval_at, Instant};
 use uuid::Uuid;
 
+use crate::auth::hash_api_token;
+use crate::broker;
 use crate::errors::{AppError, AppResult};
-use crate::handlers::message_handlers::{get_or_create_conversation, save_message};
-use crate::models::{OfferContent, WsMessage};
-use crate::utils::get_user_id_opt;
+use crate::handlers::message_handlers::{
+    audience, deliver_message, deliver_to_conversation, ensure_not_blocked, mark_read, Audience,
+};
+use crate::handlers::offer_handlers::open_offer;
+use crate::models::{
+    ClientEvent, CreateOfferRequest, ServerEvent, WsConnectQuery, WsErrorCode, WS_PROTOCOL_VERSION,
+};
+use crate::repositories::message_repository::participant_ids;
+use crate::utils::{generate_random_string, get_user_id, get_user_id_opt};
 
 type UserSessions = Arc<Mutex<HashMap<Uuid, mpsc::UnboundedSender<String>>>>;
 
+// The server pings every HEARTBEAT_INTERVAL; a client that sends nothing back, not even
+// a pong, for MAX_MISSED_PINGS pings in a row is treated as gone and disconnected
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const MAX_MISSED_PINGS: u32 = 3;
+
+// How long a WebSocket ticket can be redeemed, and its length
+const WS_TICKET_SECONDS: i32 = 30;
+const WS_TICKET_LENGTH: usize = 32;
+
+// The largest client frame handled; room for a message of the longest allowed text
+const MAX_CLIENT_FRAME_BYTES: usize = 32 * 1024;
+
+// Sockets dropped for missing heartbeats since startup
+static HEARTBEAT_TIMEOUTS: AtomicU64 = AtomicU64::new(0);
+
 // Global sessions storage - in production, use a proper state management solution
 static SESSIONS: std::sync::OnceLock<UserSessions> = std::sync::OnceLock::new();
 
@@ -24,16 +51,83 @@ fn get_sessions() -> &'static UserSessions {
     SESSIONS.get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
 }
 
+// Flips to true once the server starts shutting down
+static SHUTDOWN: std::sync::OnceLock<watch::Sender<bool>> = std::sync::OnceLock::new();
+
+fn shutdown_sender() -> &'static watch::Sender<bool> {
+    SHUTDOWN.get_or_init(|| watch::channel(false).0)
+}
+
+/// Close every open socket on this instance with a Close frame ("going away"),
+/// so clients reconnect elsewhere instead of holding up the shutdown
+pub fn close_all() {
+    shutdown_sender().send_replace(true);
+}
+
+// A panic while holding the lock leaves the map itself intact, so keep using it
+fn lock_sessions() -> std::sync::MutexGuard<'static, HashMap<Uuid, mpsc::UnboundedSender<String>>> {
+    get_sessions().lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Issue a one-time ticket for opening a WebSocket as this user without the session
+/// cookie: `GET /ws/messages?ticket=...` within WS_TICKET_SECONDS
+pub async fn create_ticket(
+    identity: Identity,
+    pool: web::Data<PgPool>,
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&identity)?;
+
+    sqlx::query!("DELETE FROM ws_tickets WHERE user_id = $1 AND expires_at <= NOW()", user_id)
+        .execute(pool.get_ref())
+        .await?;
+
+    let ticket = generate_random_string(WS_TICKET_LENGTH);
+    sqlx::query!(
+        r#"
+        INSERT INTO ws_tickets (token_hash, user_id, expires_at)
+        VALUES ($1, $2, NOW() + make_interval(secs => $3))
+        "#,
+        hash_api_token(&ticket),
+        user_id,
+        WS_TICKET_SECONDS as f64
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "ticket": ticket,
+        "expires_in": WS_TICKET_SECONDS
+    })))
+}
+
+/// The ticket's user; redeeming deletes it in the same statement, so of two
+/// connections racing with one ticket only the first gets in
+async fn redeem_ticket(pool: &PgPool, ticket: &str) -> AppResult<Uuid> {
+    let redeemed = sqlx::query!(
+        r#"DELETE FROM ws_tickets WHERE token_hash = $1 RETURNING user_id, expires_at > NOW() as "valid!""#,
+        hash_api_token(ticket)
+    )
+        .fetch_optional(pool)
+        .await?;
+
+    match redeemed {
+        Some(redeemed) if redeemed.valid => Ok(redeemed.user_id),
+        _ => Err(AppError::Unauthorized),
+    }
+}
+
 pub async fn websocket_handler(
     req: HttpRequest,
     stream: web::Payload,
     pool: web::Data<PgPool>,
     identity: Option<Identity>,
+    query: web::Query<WsConnectQuery>,
 ) -> AppResult<HttpResponse> {
-    // Get user ID from identity
-    let user_id = match identity {
-        Some(id) => get_user_id_opt(&id).expect("Failed to get user ID from identity"),
-        None => return Err(AppError::Unauthorized),
+    // A ticket stands in for the session cookie when the client can't send it
+    let user_id = match (&query.ticket, identity) {
+        (Some(ticket), _) => Some(redeem_ticket(pool.get_ref(), ticket).await?),
+        (None, Some(id)) => get_user_id_opt(&id)?,
+        (None, None) => return Err(AppError::Unauthorized),
     };
 
     let user_id = match user_id {
@@ -42,16 +136,19 @@ pub async fn websocket_handler(
     };
 
     // Upgrade to WebSocket
-    let (response, mut session, msg_stream) = actix_ws::handle(&req, stream).expect("WebSocket upgrade failed");
+    let (response, mut session, msg_stream) = actix_ws::handle(&req, stream)
+        .map_err(|e| AppError::BadRequest(format!("WebSocket upgrade failed: {}", e)))?;
 
     // Create channel for this user
     let (tx, mut rx) = mpsc::unbounded_channel::<String>();
 
-    // Register session
+    // Register session, replacing any older socket of the same user
+    let registered = tx.clone();
     {
-        let mut sessions = get_sessions().lock().expect("Failed to lock sessions mutex");
+        let mut sessions = lock_sessions();
         sessions.insert(user_id, tx);
     }
+    broker::mark_online(user_id);
 
     // Spawn handler for this connection
     let pool_clone = pool.get_ref().clone();
@@ -61,12 +158,25 @@ pub async fn websocket_handler(
             &mut session,
             msg_stream,
             &mut rx,
-            pool_clone,
+            pool_clone.clone(),
         ).await;
 
-        // Remove session on disconnect
-        let mut sessions = get_sessions().lock().expect("Failed to lock sessions mutex");
-        sessions.remove(&user_id);
+        // Remove session on disconnect, unless a newer socket of the same user replaced it
+        let current = {
+            let mut sessions = lock_sessions();
+            let current = sessions.get(&user_id).is_some_and(|tx| tx.same_channel(&registered));
+            if current {
+                sessions.remove(&user_id);
+            }
+            current
+        };
+        if current {
+            broker::mark_offline(user_id);
+        }
+
+        if let Err(e) = record_last_seen(&pool_clone, user_id).await {
+            tracing::warn!(%user_id, error = %e, "Failed to record last seen");
+        }
     });
 
     Ok(response)
@@ -79,23 +189,52 @@ async fn handle_websocket(
     rx: &mut mpsc::UnboundedReceiver<String>,
     pool: PgPool,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let mut shutdown = shutdown_sender().subscribe();
+    let mut heartbeat = interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    let mut missed_pings = 0;
+
     loop {
         tokio::select! {
-            // Handle incoming messages from client
+            // Server is shutting down
+            _ = shutdown.wait_for(|closing| *closing) => {
+                session.clone().close(Some(CloseReason {
+                    code: CloseCode::Away,
+                    description: Some("Server shutting down".to_string()),
+                })).await?;
+                break;
+            }
+
+            _ = heartbeat.tick() => {
+                if missed_pings >= MAX_MISSED_PINGS {
+                    HEARTBEAT_TIMEOUTS.fetch_add(1, Ordering::Relaxed);
+                    tracing::info!(%user_id, "WebSocket heartbeat timed out");
+                    session.clone().close(Some(CloseReason {
+                        code: CloseCode::Normal,
+                        description: Some("Heartbeat timed out".to_string()),
+                    })).await?;
+                    break;
+                }
+                missed_pings += 1;
+                session.ping(b"").await?;
+                broker::mark_online(user_id);
+            }
+
+            // Handle incoming messages from client; anything it sends shows it is alive
             Some(msg) = msg_stream.next() => {
+                missed_pings = 0;
                 match msg? {
                     Message::Text(text) => {
-                        if let Err(e) = handle_client_message(user_id, &text, &pool).await {
-                            let error_msg = json!({
-                                "type": "error",
-                                "message": e.to_string()
-                            });
-                            session.text(error_msg.to_string()).await?;
+                        if let Err(refusal) = handle_client_message(user_id, &text, &pool).await {
+                            session.text(frame(&refusal)?).await?;
                         }
                     }
+                    Message::Ping(bytes) => {
+                        session.pong(&bytes).await?;
+                    }
                     Message::Close(_) => {
                         break;
                     }
+                    // Pong and anything else only needed to reset the heartbeat
                     _ => {}
                 }
             }
@@ -112,72 +251,209 @@ async fn handle_websocket(
     Ok(())
 }
 
-async fn handle_client_message(
+/// Carry out a frame from the client. A frame that is refused, before or while being
+/// carried out, comes back as the error event to send the client.
+pub async fn handle_client_message(
     sender_id: Uuid,
     message: &str,
     pool: &PgPool,
-) -> AppResult<()> {
-    // Parse message
-    let msg_data: serde_json::Value = serde_json::from_str(message)
-        .map_err(|_| AppError::BadRequest("Invalid message format".to_string()))?;
+) -> Result<(), ServerEvent> {
+    if message.len() > MAX_CLIENT_FRAME_BYTES {
+        return Err(refusal(
+            WsErrorCode::FrameTooLarge,
+            format!("Frames are limited to {} bytes", MAX_CLIENT_FRAME_BYTES),
+        ));
+    }
 
-    let receiver_id = msg_data["receiver_id"]
-        .as_str()
-        .and_then(|s| Uuid::parse_str(s).ok())
-        .ok_or_else(|| AppError::BadRequest("Missing receiver_id".to_string()))?;
+    let frame: serde_json::Value = serde_json::from_str(message)
+        .map_err(|e| refusal(WsErrorCode::InvalidFrame, format!("Invalid JSON: {}", e)))?;
 
-    let content = msg_data["content"]
-        .as_str()
-        .ok_or_else(|| AppError::BadRequest("Missing content".to_string()))?;
+    // Clients that predate versioning send none
+    let version = match frame.get("version") {
+        None => WS_PROTOCOL_VERSION,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| refusal(WsErrorCode::InvalidFrame, "version must be a whole number".to_string()))?,
+    };
+    if version != WS_PROTOCOL_VERSION {
+        return Err(refusal(
+            WsErrorCode::UnsupportedVersion,
+            format!("Protocol version {} isn't supported; this server speaks version {}", version, WS_PROTOCOL_VERSION),
+        ));
+    }
 
-    // Get or create conversation
-    let conv_id = get_or_create_conversation(pool, sender_id, receiver_id).await?;
+    let event = ClientEvent::deserialize(&frame)
+        .map_err(|e| refusal(WsErrorCode::InvalidFrame, e.to_string()))?;
+    if let ClientEvent::Unknown = event {
+        return Err(refusal(WsErrorCode::UnknownEvent, format!("Unknown event type {}", frame["type"])));
+    }
 
-    // Save message to database
-    let saved_message = save_message(pool, conv_id, sender_id, content).await?;
+    handle_event(sender_id, event, pool).await.map_err(refusal_for)
+}
 
-    // Get sender name
-    let sender_name = sqlx::query_scalar!(
-        "SELECT name FROM users WHERE id = $1",
-        sender_id
-    )
-        .fetch_one(pool)
-        .await?;
+async fn handle_event(sender_id: Uuid, event: ClientEvent, pool: &PgPool) -> AppResult<()> {
+    match event {
+        ClientEvent::Message { receiver_id, conv_id, content, attachment_url } => {
+            // Text is optional alongside an attachment
+            let content = match content {
+                Some(content) => content,
+                None if attachment_url.is_some() => String::new(),
+                None => return Err(AppError::BadRequest("Missing content".to_string())),
+            };
 
-    // Prepare message to send
-    let ws_message = json!({
-        "type": "message",
-        "id": saved_message.id,
-        "conv_id": conv_id,
-        "sender_id": sender_id,
-        "sender_name": sender_name,
-        "content": content,
-        "sent_at": saved_message.sent_at
-    });
+            // A conversation's message goes to all of its participants, which in a
+            // broadcast is every buyer
+            match (receiver_id, conv_id) {
+                (Some(receiver_id), _) => {
+                    deliver_message(pool, sender_id, receiver_id, &content, attachment_url.as_deref()).await?;
+                }
+                (None, Some(conv_id)) => {
+                    deliver_to_conversation(pool, sender_id, conv_id, &content, attachment_url.as_deref()).await?;
+                }
+                (None, None) => return Err(AppError::BadRequest("Missing receiver_id or conv_id".to_string())),
+            }
+        }
+        ClientEvent::TypingStart { conv_id } => handle_typing(sender_id, conv_id, true, pool).await?,
+        ClientEvent::TypingStop { conv_id } => handle_typing(sender_id, conv_id, false, pool).await?,
+        ClientEvent::Read { conv_id } => {
+            mark_read(pool, sender_id, conv_id).await?;
+        }
+        ClientEvent::Offer { product_id, price, qty, receiver_id, conv_id } => {
+            let request = CreateOfferRequest {
+                product_id,
+                price_per_unit: price.round(2),
+                quantity: qty,
+                recipient_id: recipient(pool, sender_id, receiver_id, conv_id).await?,
+            };
 
-    // Send to receiver if online
-    {
-        let sessions = get_sessions().lock().unwrap();
-        if let Some(tx) = sessions.get(&receiver_id) {
-            let _ = tx.send(ws_message.to_string());
+            open_offer(pool, sender_id, &request).await?;
         }
+        ClientEvent::Unknown => {}
     }
 
-    // Echo back to sender
-    {
-        let sessions = get_sessions().lock().unwrap();
-        if let Some(tx) = sessions.get(&sender_id) {
-            let _ = tx.send(ws_message.to_string());
+    Ok(())
+}
+
+/// The error event for a frame that was refused
+fn refusal(code: WsErrorCode, message: String) -> ServerEvent {
+    ServerEvent::Error { code, message }
+}
+
+/// The error event for an event that couldn't be carried out; server-side failures
+/// are logged rather than described to the client
+fn refusal_for(error: AppError) -> ServerEvent {
+    let code = match error.status_code().as_u16() {
+        401 | 403 => WsErrorCode::Forbidden,
+        404 => WsErrorCode::NotFound,
+        409 => WsErrorCode::Conflict,
+        500.. => WsErrorCode::InternalError,
+        _ => WsErrorCode::BadRequest,
+    };
+
+    if code == WsErrorCode::InternalError {
+        tracing::error!(error = %error, "WebSocket event failed");
+        return refusal(code, "Internal server error".to_string());
+    }
+    refusal(code, error.to_string())
+}
+
+/// Who an offer is for: `receiver_id` if given, else the other participant of
+/// `conv_id`. Offers are only made one-to-one.
+async fn recipient(
+    pool: &PgPool,
+    sender_id: Uuid,
+    receiver_id: Option<Uuid>,
+    conv_id: Option<Uuid>,
+) -> AppResult<Option<Uuid>> {
+    match (receiver_id, conv_id) {
+        (Some(receiver_id), _) => Ok(Some(receiver_id)),
+        (None, Some(conv_id)) => match audience(pool, sender_id, conv_id).await? {
+            Audience::Partner(partner_id) => Ok(Some(partner_id)),
+            Audience::Broadcast => Err(AppError::BadRequest(
+                "Offers are made in a one-to-one conversation".to_string(),
+            )),
+        },
+        (None, None) => Ok(None),
+    }
+}
+
+/// Relay a typing indicator to the other participants of a conversation; nothing is stored
+async fn handle_typing(
+    sender_id: Uuid,
+    conv_id: Uuid,
+    typing: bool,
+    pool: &PgPool,
+) -> AppResult<()> {
+    let recipient_ids = match audience(pool, sender_id, conv_id).await? {
+        Audience::Partner(partner_id) => {
+            ensure_not_blocked(pool, sender_id, partner_id).await?;
+            vec![partner_id]
         }
+        Audience::Broadcast => participant_ids(pool, conv_id)
+            .await?
+            .into_iter()
+            .filter(|user_id| *user_id != sender_id)
+            .collect(),
+    };
+
+    let event = if typing {
+        ServerEvent::TypingStart { conv_id, user_id: sender_id }
+    } else {
+        ServerEvent::TypingStop { conv_id, user_id: sender_id }
+    };
+    for recipient_id in recipient_ids {
+        send_to_user(recipient_id, &event);
     }
 
     Ok(())
 }
 
-// Helper function to send a message to a specific user
-pub fn send_to_user(user_id: Uuid, message: String) {
-    let sessions = get_sessions().lock().unwrap();
+async fn record_last_seen(pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+    sqlx::query!("UPDATE users SET last_seen_at = NOW() WHERE id = $1", user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether the user has a socket open on this instance
+pub fn is_online(user_id: Uuid) -> bool {
+    lock_sessions().contains_key(&user_id)
+}
+
+/// Sockets open on this instance (one per user) and how many were dropped for missed heartbeats
+pub fn connection_stats() -> (usize, u64) {
+    (lock_sessions().len(), HEARTBEAT_TIMEOUTS.load(Ordering::Relaxed))
+}
+
+/// An event as sent over the socket, stamped with the protocol version
+#[derive(Serialize)]
+struct Frame<'a> {
+    version: u32,
+    #[serde(flatten)]
+    event: &'a ServerEvent,
+}
+
+fn frame(event: &ServerEvent) -> serde_json::Result<String> {
+    serde_json::to_string(&Frame {
+        version: WS_PROTOCOL_VERSION,
+        event,
+    })
+}
+
+// Helper function to send an event to a specific user, on whichever instance they are connected
+pub fn send_to_user(user_id: Uuid, event: &ServerEvent) {
+    match frame(event) {
+        Ok(message) => broker::publish(user_id, message),
+        Err(e) => tracing::error!(%user_id, error = %e, "Failed to encode WebSocket event"),
+    }
+}
+
+// Deliver a message to a user connected to this instance
+pub fn deliver_local(user_id: Uuid, message: &str) {
+    let sessions = lock_sessions();
     if let Some(tx) = sessions.get(&user_id) {
-        let _ = tx.send(message);
+        let _ = tx.send(message.to_string());
     }
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+// tests/audit_log.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn sensitive_actions_are_audited(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).price("100.00").stock(10).create(&pool).await;
+
+        let (status, _) = send(&app, TestRequest::post()
+            .uri("/api/login")
+            .set_json(json!({ "email": seller.email, "password": "wrong password" })), None).await;
+        assert_eq!(status, 401);
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/products/{}", product_id))
+            .set_json(json!({ "price_per_unit": "80.00", "stock_qty": 25 })), Some(&session)).await;
+        assert_eq!(status, 200, "update product: {}", body);
+
+        let admin_session = login(&app, &admin.email).await;
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&format!("/api/admin/users/{}/suspend", seller.id))
+            .set_json(json!({ "reason": "Chargebacks" })), Some(&admin_session)).await;
+        assert_eq!(status, 200, "suspend: {}", body);
+
+        // By or about the seller, newest first
+        let (status, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/admin/audit_log?user_id={}", seller.id)), Some(&admin_session)).await;
+        assert_eq!(status, 200, "audit log: {}", body);
+        let actions: Vec<&str> = body["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["action"].as_str().unwrap())
+            .collect();
+        for action in ["login_failed", "login", "product_price_changed", "product_stock_changed", "user_suspended"] {
+            assert!(actions.contains(&action), "{} missing from {:?}", action, actions);
+        }
+        assert_eq!(actions[0], "user_suspended");
+        assert_eq!(body["entries"][0]["actor_id"], json!(admin.id));
+        assert_eq!(body["entries"][0]["actor_email"], json!(admin.email));
+        assert_eq!(body["entries"][0]["details"]["reason"], json!("Chargebacks"));
+
+        let (status, body) = send(&app, TestRequest::get()
+            .uri("/api/admin/audit_log?action=product_price_changed"), Some(&admin_session)).await;
+        assert_eq!(status, 200, "by action: {}", body);
+        assert_eq!(body["pagination"]["total"], json!(1));
+        let entry = &body["entries"][0];
+        assert_eq!(entry["subject_id"], json!(product_id));
+        assert_eq!(entry["details"]["new_price"], json!("80.00"));
+
+        let (status, body) = send(&app, TestRequest::get()
+            .uri("/api/admin/audit_log?action=product_stock_changed"), Some(&admin_session)).await;
+        assert_eq!(status, 200, "stock: {}", body);
+        assert_eq!(body["entries"][0]["details"]["stock_before"], json!(10));
+        assert_eq!(body["entries"][0]["details"]["stock_after"], json!(25));
+
+        let tomorrow = (Utc::now() + Duration::days(1)).format("%Y-%m-%dT%H:%M:%SZ");
+        let (status, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/admin/audit_log?from={}", tomorrow)), Some(&admin_session)).await;
+        assert_eq!(status, 200, "by date: {}", body);
+        assert_eq!(body["pagination"]["total"], json!(0));
+
+        // Only admins read the log
+        let buyer = UserBuilder::new().create(&pool).await;
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, _) = send(&app, TestRequest::get().uri("/api/admin/audit_log"), Some(&buyer_session)).await;
+        assert_eq!(status, 403);
+    }).await;
+}
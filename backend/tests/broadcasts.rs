@@ -0,0 +1,84 @@
+// tests/broadcasts.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use backend::ws::handle_client_message;
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder};
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn sellers_broadcast_to_the_buyers_who_ordered_from_them(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().name("Asha").create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let regular = UserBuilder::new().create(&pool).await;
+        let blocker = UserBuilder::new().create(&pool).await;
+        let stranger = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).create(&pool).await;
+        for buyer_id in [buyer.id, regular.id, regular.id, blocker.id] {
+            OrderBuilder::new(buyer_id, seller.id).item(rice, 1, "50.00").create(&pool).await;
+        }
+        sqlx::query!("INSERT INTO user_blocks (blocker_id, blocked_id) VALUES ($1, $2)", blocker.id, seller.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, _) = send(&app, TestRequest::post().uri("/api/seller/broadcast").set_json(json!({
+            "content": "Fresh stock"
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 403, "buyers have no broadcast");
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/seller/broadcast").set_json(json!({
+            "content": "New basmati arrived today"
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "broadcast: {}", body);
+        assert_eq!(body["recipient_count"], 2, "everyone who ordered, less whoever blocked the seller");
+        let conv_id = body["conv_id"].as_str().unwrap().to_string();
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/conversations"), Some(&buyer_session)).await;
+        let conversation = &body["conversations"][0];
+        assert_eq!(conversation["id"], conv_id.as_str());
+        assert_eq!(conversation["is_group"], true);
+        assert_eq!(conversation["title"], "Announcements from Asha");
+        assert_eq!(conversation["other_user_id"], json!(seller.id));
+        assert_eq!(conversation["participant_count"], 3);
+        assert_eq!(conversation["unread_count"], 1);
+
+        for outsider in [&blocker, &stranger] {
+            let session = login(&app, &outsider.email).await;
+            let (status, _) = send(&app, TestRequest::get().uri(&format!("/api/messages/{}", conv_id)), Some(&session)).await;
+            assert_eq!(status, 403);
+        }
+
+        // Over the socket, the seller's post reaches every buyer; buyers can't post
+        let post = |content: &str| json!({ "type": "message", "conv_id": conv_id, "content": content }).to_string();
+        handle_client_message(seller.id, &post("Mangoes on Friday"), &pool).await.expect("seller posts");
+        let error = handle_client_message(buyer.id, &post("Me too"), &pool).await.expect_err("buyer posts");
+        assert_eq!(serde_json::to_value(error).unwrap()["code"], "forbidden");
+
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/messages/{}", conv_id)), Some(&buyer_session)).await;
+        assert_eq!(body["messages"][0]["content"], "Mangoes on Friday", "{}", body);
+
+        // Each buyer keeps their own place in the broadcast
+        let (status, body) = send(&app, TestRequest::post().uri(&format!("/api/messages/{}/read", conv_id)), Some(&buyer_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["marked_count"], 2);
+        let (_, body) = send(&app, TestRequest::get().uri("/api/conversations"), Some(&buyer_session)).await;
+        assert_eq!(body["conversations"][0]["unread_count"], 0);
+
+        let regular_session = login(&app, &regular.email).await;
+        let (_, body) = send(&app, TestRequest::get().uri("/api/conversations"), Some(&regular_session)).await;
+        assert_eq!(body["conversations"][0]["unread_count"], 2);
+
+        // A later broadcast reuses the conversation
+        let (_, body) = send(&app, TestRequest::post().uri("/api/seller/broadcast").set_json(json!({
+            "content": "Closed on Sunday"
+        })), Some(&session)).await;
+        assert_eq!(body["conv_id"], conv_id.as_str());
+    }).await;
+}
@@ -0,0 +1,156 @@
+// tests/cart.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn set_quantity_holds_the_whole_amount(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).stock(10).create(&pool).await;
+        let session = login(&app, &buyer.email).await;
+        let uri = format!("/api/cart/items/{}", product_id);
+
+        let (status, body) = send(&app, TestRequest::put().uri(&uri).set_json(json!({ "quantity": 4 })), Some(&session)).await;
+        assert_eq!(status, 200, "set quantity: {}", body);
+        let (status, _) = send(&app, TestRequest::put().uri(&uri).set_json(json!({ "quantity": 7 })), Some(&session)).await;
+        assert_eq!(status, 200);
+
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&session)).await;
+        assert_eq!(cart["items"][0]["quantity"], json!(7), "the quantity is replaced, not added to");
+
+        let (status, _) = send(&app, TestRequest::put().uri(&uri).set_json(json!({ "quantity": 11 })), Some(&session)).await;
+        assert_eq!(status, 400, "more than the stock");
+
+        let (status, _) = send(&app, TestRequest::put().uri(&uri).set_json(json!({ "quantity": 0 })), Some(&session)).await;
+        assert_eq!(status, 200);
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&session)).await;
+        assert_eq!(cart["items"], json!([]));
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn replacing_the_cart_is_all_or_nothing(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).name("Rice").stock(10).create(&pool).await;
+        let oil = ProductBuilder::new(seller.id).name("Oil").stock(3).create(&pool).await;
+        let salt = ProductBuilder::new(seller.id).name("Salt").stock(5).create(&pool).await;
+        let session = login(&app, &buyer.email).await;
+
+        let (status, _) = send(&app, TestRequest::post().uri("/api/cart/add").set_json(json!({
+            "product_id": rice,
+            "quantity": 2
+        })), Some(&session)).await;
+        assert_eq!(status, 200);
+
+        // Too much oil: nothing changes
+        let (status, body) = send(&app, TestRequest::put().uri("/api/cart").set_json(json!({
+            "items": [
+                { "product_id": salt, "quantity": 1 },
+                { "product_id": oil, "quantity": 4 }
+            ]
+        })), Some(&session)).await;
+        assert_eq!(status, 400);
+        assert!(body["error"].to_string().contains("items[1]"), "names the line: {}", body);
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&session)).await;
+        assert_eq!(cart["items"].as_array().map(Vec::len), Some(1));
+        assert_eq!(cart["items"][0]["product_id"], json!(rice));
+
+        let (status, _) = send(&app, TestRequest::put().uri("/api/cart").set_json(json!({
+            "items": [
+                { "product_id": salt, "quantity": 1 },
+                { "product_id": salt, "quantity": 2 }
+            ]
+        })), Some(&session)).await;
+        assert_eq!(status, 422, "a line given twice");
+
+        let (status, body) = send(&app, TestRequest::put().uri("/api/cart").set_json(json!({
+            "items": [
+                { "product_id": salt, "quantity": 1 },
+                { "product_id": oil, "quantity": 3 }
+            ]
+        })), Some(&session)).await;
+        assert_eq!(status, 200, "replace cart: {}", body);
+
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&session)).await;
+        let mut lines: Vec<_> = cart["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| (item["name"].as_str().unwrap().to_string(), item["quantity"].as_i64().unwrap()))
+            .collect();
+        lines.sort();
+        assert_eq!(lines, vec![("Oil".to_string(), 3), ("Salt".to_string(), 1)]);
+
+        // Rice's hold went with its line
+        let held = sqlx::query_scalar!("SELECT COUNT(*) FROM stock_reservations WHERE product_id = $1", rice)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(held, Some(0));
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn quantities_come_in_whole_increments(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let category_id = sqlx::query_scalar!("SELECT MIN(id) FROM categories")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let seller_session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/products").set_json(json!({
+            "name": "Basmati Rice",
+            "price_per_unit": "90.00",
+            "stock_qty": 40,
+            "category_id": category_id,
+            "unit": "kg",
+            "min_increment": 5
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 201, "create product: {}", body);
+        let product_id = body["product_id"].as_str().unwrap().to_string();
+
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", product_id)), None).await;
+        assert_eq!((&product["unit"], &product["min_increment"]), (&json!("kg"), &json!(5)));
+
+        let session = login(&app, &buyer.email).await;
+        let add = |quantity: i32| TestRequest::post()
+            .uri("/api/cart/add")
+            .set_json(json!({ "product_id": product_id, "quantity": quantity }));
+        let (status, body) = send(&app, add(3), Some(&session)).await;
+        assert_eq!(status, 400, "{}", body);
+        assert_eq!(body["error"], "Bad request: This product is sold in multiples of 5");
+        let (status, body) = send(&app, add(10), Some(&session)).await;
+        assert_eq!(status, 200, "add to cart: {}", body);
+
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&session)).await;
+        assert_eq!(cart["items"][0]["quantity"], json!(10));
+        assert_eq!(cart["items"][0]["unit"], json!("kg"));
+
+        // Sold by the 25 kg from now on: the line is rounded to the nearest amount in stock
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/products/{}", product_id))
+            .set_json(json!({ "min_increment": 25 })), Some(&seller_session)).await;
+        assert_eq!(status, 200, "update product: {}", body);
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/cart/validate"), Some(&session)).await;
+        assert_eq!(status, 200, "validate cart: {}", body);
+        assert_eq!(body["issues"][0]["issue"], json!("quantity_rounded"), "{}", body);
+        assert_eq!(body["issues"][0]["quantity"], json!(25));
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&session)).await;
+        assert_eq!(cart["items"][0]["quantity"], json!(25));
+    }).await;
+}
@@ -0,0 +1,80 @@
+// tests/cart_templates.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+fn add_to_cart(product_id: Uuid, quantity: i32) -> TestRequest {
+    TestRequest::post().uri("/api/cart/add").set_json(json!({ "product_id": product_id, "quantity": quantity }))
+}
+
+/// (product, quantity) of each cart line, in product id order
+fn cart_lines(cart: &Value) -> Vec<(String, i64)> {
+    let mut lines: Vec<_> = cart["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| (item["product_id"].as_str().unwrap().to_string(), item["quantity"].as_i64().unwrap()))
+        .collect();
+    lines.sort();
+    lines
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn a_template_refills_the_cart_without_what_sold_out(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let vendor = UserBuilder::new().create(&pool).await;
+        let onions = ProductBuilder::new(seller.id).name("Onions").stock(50).create(&pool).await;
+        let chillies = ProductBuilder::new(seller.id).name("Green Chillies").stock(10).create(&pool).await;
+        let session = login(&app, &vendor.email).await;
+
+        send(&app, add_to_cart(onions, 20), Some(&session)).await;
+        send(&app, add_to_cart(chillies, 4), Some(&session)).await;
+        let (status, body) = send(&app, TestRequest::post()
+            .uri("/api/cart/save-as-template")
+            .set_json(json!({ "name": "Weekly stock" })), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let template_id = body["template_id"].as_str().unwrap().to_string();
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/cart/templates"), Some(&session)).await;
+        assert_eq!(body["templates"][0]["name"], "Weekly stock", "{}", body);
+        assert_eq!(body["templates"][0]["item_count"], 2);
+
+        // A week on: the cart was emptied and the chillies have sold out
+        let (status, _) = send(&app, TestRequest::put().uri("/api/cart").set_json(json!({ "items": [] })), Some(&session)).await;
+        assert_eq!(status, 200);
+        sqlx::query!("UPDATE products SET stock_qty = 0 WHERE id = $1", chillies)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&format!("/api/cart/templates/{}/apply", template_id)), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["unavailable_items"], json!([{
+            "product_id": chillies,
+            "variant_id": null,
+            "name": "Green Chillies",
+            "variant_name": null,
+            "requested_quantity": 4
+        }]));
+        assert_eq!(body["adjusted_items"], json!([]));
+        assert_eq!(body["cart_size"], 1);
+
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&session)).await;
+        assert_eq!(cart_lines(&cart), [(onions.to_string(), 20)], "{}", cart);
+
+        // Someone else's template isn't theirs to apply
+        let other = UserBuilder::new().create(&pool).await;
+        let other_session = login(&app, &other.email).await;
+        let (status, _) = send(&app, TestRequest::post()
+            .uri(&format!("/api/cart/templates/{}/apply", template_id)), Some(&other_session)).await;
+        assert_eq!(status, 403);
+    }).await;
+}
@@ -0,0 +1,96 @@
+// tests/catalog_cache.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+fn listing() -> TestRequest {
+    TestRequest::get().uri("/api/products?search=Basmati")
+}
+
+fn names(body: &Value) -> Vec<String> {
+    body["products"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|product| product["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn listings_are_cached_until_a_product_changes(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).name("Basmati Rice").create(&pool).await;
+
+        let (status, body) = send(&app, listing(), None).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(names(&body), ["Basmati Rice"]);
+
+        // Changed behind the API's back, so the cached page is still served
+        sqlx::query!("UPDATE products SET name = 'Basmati Rice (5kg)' WHERE id = $1", rice)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (_, body) = send(&app, listing(), None).await;
+        assert_eq!(names(&body), ["Basmati Rice"]);
+
+        // Signed-in viewers aren't served the shared page
+        let session = login(&app, &seller.email).await;
+        let (_, body) = send(&app, listing(), Some(&session)).await;
+        assert_eq!(names(&body), ["Basmati Rice (5kg)"]);
+
+        let (_, health) = send(&app, TestRequest::get().uri("/health"), None).await;
+        let products = &health["cache"]["namespaces"]["products"];
+        assert_eq!(health["cache"]["backend"], "memory", "{}", health);
+        assert_eq!(products["hits"], 1, "{}", health);
+        assert_eq!(products["misses"], 1);
+
+        // An update through the API empties the cache
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/products/{}", rice))
+            .set_json(json!({ "name": "Basmati Rice (10kg)" })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        let (_, body) = send(&app, listing(), None).await;
+        assert_eq!(names(&body), ["Basmati Rice (10kg)"]);
+
+        let (status, _) = send(&app, TestRequest::delete().uri(&format!("/api/products/{}", rice)), Some(&session)).await;
+        assert_eq!(status, 200);
+        let (_, body) = send(&app, listing(), None).await;
+        assert_eq!(body["products"], json!([]));
+
+        let (_, health) = send(&app, TestRequest::get().uri("/health"), None).await;
+        assert_eq!(health["cache"]["namespaces"]["products"]["invalidations"], 2, "{}", health);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn category_changes_empty_the_category_cache(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+
+        let (_, before) = send(&app, TestRequest::get().uri("/api/categories"), None).await;
+        let count = before["categories"].as_array().unwrap().len();
+
+        sqlx::query!("INSERT INTO categories (name) VALUES ('Cached Millets')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (_, body) = send(&app, TestRequest::get().uri("/api/categories"), None).await;
+        assert_eq!(body, before);
+
+        let session = login(&app, &admin.email).await;
+        let (status, body) = send(&app, TestRequest::post()
+            .uri("/api/admin/categories")
+            .set_json(json!({ "name": "Cached Pulses" })), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/categories"), None).await;
+        assert_eq!(body["categories"].as_array().unwrap().len(), count + 2);
+    }).await;
+}
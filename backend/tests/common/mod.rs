@@ -0,0 +1,354 @@
+// tests/common/mod.rs
+#![allow(dead_code)]
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::test;
+use bigdecimal::BigDecimal;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use backend::config::{BreachCheck, CacheBackend, CharacterClass, Config, EmailProvider, PushProvider, StorageBackend};
+use backend::handlers::auth_handlers::hash_password;
+use backend::mailer::LogSender;
+use backend::models::OrderStatus;
+use backend::repositories::order_repository::{self, NewOrder, NewOrderItem};
+use backend::storage::LocalStorage;
+
+pub const PASSWORD: &str = "password123";
+
+/// The configuration `Config::from_env` would give with nothing set beyond the
+/// required variables, except that uploads go to a local directory. Emails are logged
+/// and there's no Redis or Stripe.
+pub fn test_config() -> Config {
+    Config {
+        // The pool comes from #[sqlx::test]
+        database_url: String::new(),
+        database_max_connections: 5,
+        database_min_connections: 0,
+        database_acquire_timeout_seconds: 30,
+        database_idle_timeout_seconds: 600,
+        database_max_lifetime_seconds: 1800,
+        database_statement_timeout_ms: 30000,
+        server_address: "127.0.0.1:8080".to_string(),
+        shutdown_timeout_seconds: 30,
+        json_body_limit_kb: 256,
+        max_file_size_mb: 5,
+        secret_key: "integration-test-secret-key-".repeat(3),
+        s3_bucket_name: "streetsource-test".to_string(),
+        aws_region: "us-east-1".to_string(),
+        redis_url: None,
+        cache_backend: CacheBackend::Memory,
+        cache_ttl_seconds: 30,
+        stripe_secret_key: None,
+        stripe_webhook_secret: None,
+        base_currency: "INR".to_string(),
+        exchange_rates: HashMap::from([("INR".to_string(), BigDecimal::from(1))]),
+        login_max_attempts: 5,
+        login_lockout_minutes: 15,
+        session_timeout_hours: 24,
+        session_idle_timeout_minutes: 120,
+        remember_me_days: 30,
+        cart_reservation_minutes: 15,
+        product_review_required: false,
+        platform_commission_percent: BigDecimal::from(0),
+        email_provider: EmailProvider::Log,
+        email_from: "noreply@streetsource.com".to_string(),
+        ses_region: "us-east-1".to_string(),
+        smtp_host: None,
+        smtp_port: 587,
+        smtp_username: None,
+        smtp_password: None,
+        push_provider: PushProvider::Log,
+        fcm_credentials_file: None,
+        storage_backend: StorageBackend::Local,
+        local_storage_dir: test_storage_dir(),
+        local_storage_url: "http://localhost:8080/uploads".to_string(),
+        password_min_length: 8,
+        password_required_classes: vec![CharacterClass::Letter, CharacterClass::Digit],
+        password_breach_check: BreachCheck::Off,
+        password_breach_list: None,
+        // The tests' webhook receivers listen on localhost
+        webhook_allow_private_urls: true,
+    }
+}
+
+/// Uploads go to a directory of their own per test process
+pub fn test_storage_dir() -> String {
+    std::env::temp_dir()
+        .join(format!("streetsource-test-uploads-{}", std::process::id()))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Run a test body where `actix_web::rt::spawn` works, as it does under #[actix_web::main]
+pub async fn local<F: Future>(body: F) -> F::Output {
+    tokio::task::LocalSet::new().run_until(body).await
+}
+
+/// The application as main.rs serves it, on the test's database
+pub async fn init_app(
+    pool: PgPool,
+) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error> {
+    init_app_with(pool, test_config()).await
+}
+
+/// The application with settings changed from `test_config`
+pub async fn init_app_with(
+    pool: PgPool,
+    config: Config,
+) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error> {
+    let schema = backend::graphql::schema::build(pool.clone());
+    let storage = LocalStorage::new(&config.local_storage_dir, &config.local_storage_url)
+        .await
+        .expect("Failed to create the upload directory");
+    let cache = backend::cache::from_config(&config).await.expect("Failed to create the cache");
+    test::init_service(backend::app(pool, config, schema, Arc::new(LogSender), Arc::new(storage), cache)).await
+}
+
+/// Send a request, with the session cookie when there is one, returning the status
+/// and the JSON body (null when the body isn't JSON)
+pub async fn send<S, B>(app: &S, request: test::TestRequest, session: Option<&Cookie<'static>>) -> (u16, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = match session {
+        Some(cookie) => request.cookie(cookie.clone()),
+        None => request,
+    };
+    // Middleware such as RequireAdmin rejects with an error rather than a response
+    let (status, body) = match test::try_call_service(app, request.to_request()).await {
+        Ok(response) => (response.status().as_u16(), test::read_body(response).await),
+        Err(error) => {
+            let response = error.error_response();
+            let status = response.status().as_u16();
+            (status, actix_web::body::to_bytes(response.into_body()).await.unwrap_or_default())
+        }
+    };
+
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Log in through the API, returning the session cookie for later requests
+pub async fn login<S, B>(app: &S, email: &str) -> Cookie<'static>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({ "email": email, "password": PASSWORD }))
+        .to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status().as_u16(), 200, "login as {} failed", email);
+
+    response
+        .response()
+        .cookies()
+        .next()
+        .expect("login set no session cookie")
+        .into_owned()
+}
+
+pub struct TestUser {
+    pub id: Uuid,
+    pub email: String,
+}
+
+/// A user who can log in with `PASSWORD`
+pub struct UserBuilder {
+    name: String,
+    is_supplier: bool,
+    is_admin: bool,
+}
+
+impl UserBuilder {
+    pub fn new() -> Self {
+        UserBuilder {
+            name: "Test User".to_string(),
+            is_supplier: false,
+            is_admin: false,
+        }
+    }
+
+    pub fn supplier(mut self) -> Self {
+        self.is_supplier = true;
+        self
+    }
+
+    pub fn admin(mut self) -> Self {
+        self.is_admin = true;
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub async fn create(self, pool: &PgPool) -> TestUser {
+        let id = Uuid::new_v4();
+        let email = format!("user-{}@example.com", id.simple());
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, password_hash, name, is_supplier, is_admin)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            id,
+            email,
+            hash_password(PASSWORD).expect("hash password"),
+            self.name,
+            self.is_supplier,
+            self.is_admin
+        )
+            .execute(pool)
+            .await
+            .expect("insert user");
+
+        TestUser { id, email }
+    }
+}
+
+/// A listed product in the first category
+pub struct ProductBuilder {
+    seller_id: Uuid,
+    name: String,
+    price_per_unit: BigDecimal,
+    stock_qty: i32,
+}
+
+impl ProductBuilder {
+    pub fn new(seller_id: Uuid) -> Self {
+        ProductBuilder {
+            seller_id,
+            name: "Test Rice".to_string(),
+            price_per_unit: BigDecimal::from(50),
+            stock_qty: 100,
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn price(mut self, price: &str) -> Self {
+        self.price_per_unit = BigDecimal::from_str(price).expect("price");
+        self
+    }
+
+    pub fn stock(mut self, stock_qty: i32) -> Self {
+        self.stock_qty = stock_qty;
+        self
+    }
+
+    pub async fn create(self, pool: &PgPool) -> Uuid {
+        let id = Uuid::new_v4();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO products (id, name, price_per_unit, stock_qty, seller_id, category_id)
+            VALUES ($1, $2, $3, $4, $5, (SELECT MIN(id) FROM categories))
+            "#,
+            id,
+            self.name,
+            self.price_per_unit,
+            self.stock_qty,
+            self.seller_id
+        )
+            .execute(pool)
+            .await
+            .expect("insert product");
+
+        id
+    }
+}
+
+/// An order placed the way checkout stores one, without touching stock; moved straight
+/// to `status` afterwards when that isn't pending
+pub struct OrderBuilder {
+    buyer_id: Uuid,
+    seller_id: Uuid,
+    items: Vec<(Uuid, i32, BigDecimal)>,
+    status: OrderStatus,
+}
+
+impl OrderBuilder {
+    pub fn new(buyer_id: Uuid, seller_id: Uuid) -> Self {
+        OrderBuilder {
+            buyer_id,
+            seller_id,
+            items: vec![],
+            status: OrderStatus::Pending,
+        }
+    }
+
+    pub fn item(mut self, product_id: Uuid, quantity: i32, unit_price: &str) -> Self {
+        self.items.push((product_id, quantity, BigDecimal::from_str(unit_price).expect("unit price")));
+        self
+    }
+
+    pub fn status(mut self, status: OrderStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub async fn create(self, pool: &PgPool) -> Uuid {
+        let id = Uuid::new_v4();
+        let total: BigDecimal = self.items.iter().map(|(_, quantity, price)| *quantity * price).sum();
+        let zero = BigDecimal::from(0);
+        let mut conn = pool.acquire().await.expect("acquire connection");
+
+        order_repository::insert(&mut conn, &NewOrder {
+            id,
+            buyer_id: self.buyer_id,
+            seller_id: self.seller_id,
+            subtotal_price: &total,
+            discount_amount: &zero,
+            coupon_id: None,
+            delivery_fee: &zero,
+            tax_amount: &zero,
+            total_price: &total,
+            currency: "INR",
+            shipping_address: &json!({ "recipient_name": "Test Buyer", "line1": "1 Market Road", "city": "Pune" }),
+            delivery_slot_id: None,
+            seller_tax_id: None,
+            seller_tax_name: None,
+        }).await.expect("insert order");
+
+        for (product_id, quantity, unit_price) in &self.items {
+            order_repository::insert_item(&mut conn, id, &NewOrderItem {
+                product_id: *product_id,
+                variant_id: None,
+                variant_name: None,
+                quantity: *quantity,
+                unit_price,
+                deal_id: None,
+                tax_rate: &zero,
+                tax_amount: &zero,
+            }).await.expect("insert order item");
+        }
+
+        if self.status != OrderStatus::Pending {
+            sqlx::query!(
+                "UPDATE orders SET status = $2 WHERE id = $1",
+                id,
+                self.status as OrderStatus
+            )
+                .execute(&mut *conn)
+                .await
+                .expect("set order status");
+        }
+
+        id
+    }
+}
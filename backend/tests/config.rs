@@ -0,0 +1,120 @@
+// tests/config.rs
+use bigdecimal::BigDecimal;
+use std::env;
+use std::sync::{Mutex, MutexGuard};
+
+use backend::config::{CacheBackend, CharacterClass, Config};
+
+/// Every variable Config::from_env reads
+const VARS: &[&str] = &[
+    "AWS_REGION", "AWS_SES_REGION", "BASE_CURRENCY", "CACHE_BACKEND", "CACHE_TTL_SECONDS",
+    "CART_RESERVATION_MINUTES", "DATABASE_ACQUIRE_TIMEOUT_SECONDS", "DATABASE_IDLE_TIMEOUT_SECONDS",
+    "DATABASE_MAX_CONNECTIONS", "DATABASE_MAX_LIFETIME_SECONDS", "DATABASE_MIN_CONNECTIONS",
+    "DATABASE_STATEMENT_TIMEOUT_MS", "DATABASE_URL", "EMAIL_FROM", "EMAIL_PROVIDER", "EXCHANGE_RATES",
+    "FCM_CREDENTIALS_FILE", "JSON_BODY_LIMIT_KB", "LOCAL_STORAGE_DIR", "LOCAL_STORAGE_URL",
+    "LOGIN_LOCKOUT_MINUTES", "LOGIN_MAX_ATTEMPTS", "MAX_FILE_SIZE_MB", "PASSWORD_BREACH_CHECK",
+    "PASSWORD_BREACH_LIST", "PASSWORD_MIN_LENGTH", "PASSWORD_REQUIRED_CLASSES",
+    "PLATFORM_COMMISSION_PERCENT", "PRODUCT_REVIEW_REQUIRED", "PUSH_PROVIDER", "REDIS_URL",
+    "REMEMBER_ME_DAYS", "S3_BUCKET_NAME", "SECRET_KEY", "SERVER_ADDRESS", "SESSION_IDLE_TIMEOUT_MINUTES",
+    "SESSION_TIMEOUT_HOURS", "SHUTDOWN_TIMEOUT_SECONDS", "SMTP_HOST", "SMTP_PASSWORD", "SMTP_PORT",
+    "SMTP_USERNAME", "STORAGE_BACKEND", "STRIPE_SECRET_KEY", "STRIPE_WEBHOOK_SECRET",
+    "WEBHOOK_ALLOW_PRIVATE_URLS",
+];
+
+// The environment belongs to the whole process, so the tests take turns with it
+static ENV: Mutex<()> = Mutex::new(());
+
+/// Leave only these of the configuration's variables set, holding the environment until
+/// the guard is dropped
+fn with_env(vars: &[(&str, &str)]) -> MutexGuard<'static, ()> {
+    let guard = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // SAFETY: every test in this binary changes and reads the environment only while
+    // holding ENV, and none of them start other threads that read it
+    unsafe {
+        for name in VARS {
+            env::remove_var(name);
+        }
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+    }
+    guard
+}
+
+fn secret_key() -> String {
+    "k".repeat(64)
+}
+
+#[test]
+fn a_valid_environment_parses() {
+    let secret_key = secret_key();
+    let _env = with_env(&[
+        ("DATABASE_URL", "postgres://postgres@localhost/ss"),
+        ("SECRET_KEY", &secret_key),
+        ("DATABASE_MAX_CONNECTIONS", "10"),
+        ("CACHE_BACKEND", "off"),
+        ("EXCHANGE_RATES", "usd=83.2, EUR=90.5"),
+        ("PASSWORD_REQUIRED_CLASSES", "lowercase,digit,lowercase"),
+        ("PRODUCT_REVIEW_REQUIRED", "yes"),
+    ]);
+
+    let config = Config::from_env().unwrap();
+
+    assert_eq!(config.database_url, "postgres://postgres@localhost/ss");
+    assert_eq!(config.database_max_connections, 10);
+    assert_eq!(config.cache_backend, CacheBackend::Off);
+    assert_eq!(config.exchange_rates.len(), 3);
+    assert_eq!(config.exchange_rates["USD"], "83.2".parse::<BigDecimal>().unwrap());
+    assert_eq!(config.password_required_classes, [CharacterClass::Lowercase, CharacterClass::Digit]);
+    assert!(config.product_review_required);
+
+    // Left out, so defaulted
+    assert_eq!(config.server_address, "127.0.0.1:8080");
+    assert_eq!(config.local_storage_url, "http://127.0.0.1:8080/uploads");
+    assert_eq!(config.base_currency, "INR");
+    assert_eq!(config.session_timeout_hours, 24);
+    assert!(!config.webhook_allow_private_urls);
+}
+
+#[test]
+fn the_required_variables_are_each_reported_missing() {
+    let _env = with_env(&[]);
+
+    let err = Config::from_env().unwrap_err();
+
+    assert_eq!(err.problems, ["DATABASE_URL must be set", "SECRET_KEY must be set"]);
+}
+
+#[test]
+fn every_invalid_variable_is_reported_together() {
+    let _env = with_env(&[
+        ("DATABASE_URL", "  "),
+        ("DATABASE_MAX_CONNECTIONS", "zero"),
+        ("DATABASE_MIN_CONNECTIONS", "8"),
+        ("SECRET_KEY", "short"),
+        ("SERVER_ADDRESS", "localhost"),
+        ("CACHE_BACKEND", "redis"),
+        ("STRIPE_SECRET_KEY", "sk_test_123"),
+        ("EXCHANGE_RATES", "USD=83.2,EUR"),
+        ("PLATFORM_COMMISSION_PERCENT", "100"),
+        ("EMAIL_FROM", "nobody"),
+        ("WEBHOOK_ALLOW_PRIVATE_URLS", "maybe"),
+    ]);
+
+    let err = Config::from_env().unwrap_err();
+
+    assert_eq!(err.problems, [
+        "DATABASE_URL is set but empty",
+        "DATABASE_MAX_CONNECTIONS must be a positive integer (got 'zero')",
+        "DATABASE_MIN_CONNECTIONS (8) can't exceed DATABASE_MAX_CONNECTIONS (5)",
+        "SECRET_KEY must be at least 64 characters (got 5)",
+        "SERVER_ADDRESS must be in host:port form (got 'localhost')",
+        "REDIS_URL must be set when CACHE_BACKEND=redis",
+        "STRIPE_SECRET_KEY and STRIPE_WEBHOOK_SECRET must be set together",
+        "EXCHANGE_RATES entries must look like USD=83.2, for currencies other than INR (got 'EUR')",
+        "PLATFORM_COMMISSION_PERCENT must be a number from 0 up to 100 (got '100')",
+        "EMAIL_FROM must be an email address (got 'nobody')",
+        "WEBHOOK_ALLOW_PRIVATE_URLS must be true or false (got 'maybe')",
+    ]);
+    assert!(err.to_string().starts_with("Invalid configuration (11 problem(s)):"), "{}", err);
+}
@@ -0,0 +1,117 @@
+// tests/conversations.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use backend::ws::handle_client_message;
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn conversation_started_from_a_product_shows_the_listing(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().name("Asha").create(&pool).await;
+        let other_seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).name("Rice").create(&pool).await;
+        let oil = ProductBuilder::new(seller.id).name("Oil").create(&pool).await;
+        let elsewhere = ProductBuilder::new(other_seller.id).create(&pool).await;
+        let session = login(&app, &buyer.email).await;
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/conversations").set_json(json!({
+            "seller_id": seller.id,
+            "product_id": rice
+        })), Some(&session)).await;
+        assert_eq!(status, 200, "start conversation: {}", body);
+        assert_eq!(body["other_user_name"], json!("Asha"));
+        assert_eq!(body["product"]["name"], json!("Rice"));
+        let conv_id = body["conv_id"].clone();
+
+        // The same pair keeps one conversation, now about the latest listing
+        let (_, body) = send(&app, TestRequest::post().uri("/api/conversations").set_json(json!({
+            "seller_id": seller.id,
+            "product_id": oil
+        })), Some(&session)).await;
+        assert_eq!(body["conv_id"], conv_id);
+
+        let (status, _) = send(&app, TestRequest::post().uri("/api/conversations").set_json(json!({
+            "seller_id": seller.id,
+            "product_id": elsewhere
+        })), Some(&session)).await;
+        assert_eq!(status, 404, "a product from another seller");
+
+        let (status, _) = send(&app, TestRequest::post().uri("/api/conversations").set_json(json!({
+            "seller_id": buyer.id
+        })), Some(&session)).await;
+        assert_eq!(status, 400, "yourself");
+
+        // The seller sees which listing the buyer is asking about
+        let seller_session = login(&app, &seller.email).await;
+        let (_, body) = send(&app, TestRequest::get().uri("/api/conversations"), Some(&seller_session)).await;
+        let conversation = &body["conversations"][0];
+        assert_eq!(conversation["id"], conv_id);
+        assert_eq!(conversation["other_user_id"], json!(buyer.id));
+        assert_eq!(conversation["product"]["name"], json!("Oil"));
+        assert_eq!(conversation["last_message"], json!(null));
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn websocket_frames_are_checked_before_being_carried_out(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let session = login(&app, &buyer.email).await;
+
+        let (_, body) = send(&app, TestRequest::post().uri("/api/conversations").set_json(json!({
+            "seller_id": seller.id
+        })), Some(&session)).await;
+        let conv_id = body["conv_id"].as_str().unwrap().to_string();
+
+        let refusal = |frame: String| {
+            let pool = pool.clone();
+            async move {
+                let error = handle_client_message(buyer.id, &frame, &pool).await.expect_err(&frame);
+                serde_json::to_value(error).unwrap()
+            }
+        };
+
+        let error = refusal(json!({ "type": "wave", "conv_id": conv_id }).to_string()).await;
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["code"], "unknown_event");
+
+        let error = refusal(json!({ "version": 2, "type": "typing_start", "conv_id": conv_id }).to_string()).await;
+        assert_eq!(error["code"], "unsupported_version");
+
+        for frame in ["not json".to_string(), json!({ "type": "typing_start" }).to_string(), json!({ "conv_id": conv_id }).to_string()] {
+            assert_eq!(refusal(frame.clone()).await["code"], "invalid_frame", "{}", frame);
+        }
+
+        let long = json!({ "type": "message", "conv_id": conv_id, "content": "a".repeat(40 * 1024) }).to_string();
+        assert_eq!(refusal(long).await["code"], "frame_too_large");
+
+        let stranger = json!({ "type": "message", "conv_id": uuid::Uuid::new_v4(), "content": "Hi" }).to_string();
+        assert_eq!(refusal(stranger).await["code"], "not_found");
+
+        // A message may be addressed by conversation, and the version may be left out
+        for frame in [
+            json!({ "version": 1, "type": "message", "conv_id": conv_id, "content": "Is the rice fresh?" }),
+            json!({ "type": "typing_stop", "conv_id": conv_id }),
+        ] {
+            handle_client_message(buyer.id, &frame.to_string(), &pool).await.expect("accepted");
+        }
+
+        let seller_session = login(&app, &seller.email).await;
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/messages/{}", conv_id)), Some(&seller_session)).await;
+        assert_eq!(body["messages"][0]["content"], "Is the rice fresh?", "{}", body);
+        assert_eq!(body["messages"][0]["read_at"], json!(null));
+
+        let read = json!({ "type": "read", "conv_id": conv_id }).to_string();
+        handle_client_message(seller.id, &read, &pool).await.expect("read");
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/messages/{}", conv_id)), Some(&seller_session)).await;
+        assert!(body["messages"][0]["read_at"].is_string(), "{}", body);
+    }).await;
+}
@@ -0,0 +1,283 @@
+// tests/deals.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+fn create_deal(product_id: Uuid, deal: Value) -> TestRequest {
+    TestRequest::post().uri(&format!("/api/products/{}/deals", product_id)).set_json(deal)
+}
+
+fn add_to_cart(product_id: Uuid, quantity: i32) -> TestRequest {
+    TestRequest::post().uri("/api/cart/add").set_json(json!({ "product_id": product_id, "quantity": quantity }))
+}
+
+fn add_address() -> TestRequest {
+    TestRequest::post().uri("/api/user/addresses").set_json(json!({
+        "recipient_name": "Deal Hunter",
+        "phone": "9876543210",
+        "line1": "1 Market Road",
+        "city": "Pune",
+        "state": "Maharashtra",
+        "postal_code": "411001"
+    }))
+}
+
+fn place_order(address: &Value) -> TestRequest {
+    TestRequest::post().uri("/api/orders").set_json(json!({ "address_id": address["address"]["id"] }))
+}
+
+/// The unit price of the order's only line
+async fn line_price(pool: &PgPool, order: &Value) -> String {
+    let order_id: Uuid = order["order_ids"][0].as_str().unwrap().parse().unwrap();
+    sqlx::query_scalar!(r#"SELECT unit_price::text as "price!" FROM order_items WHERE order_id = $1"#, order_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn deals_price_carts_and_orders_until_they_sell_out(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let first = UserBuilder::new().create(&pool).await;
+        let second = UserBuilder::new().create(&pool).await;
+        let mangoes = ProductBuilder::new(seller.id).name("Alphonso Mangoes").price("50.00").stock(20).create(&pool).await;
+        let seller_session = login(&app, &seller.email).await;
+
+        let (status, body) = send(&app, create_deal(mangoes, json!({
+            "deal_price": "35.00",
+            "ends_at": Utc::now() + Duration::hours(2),
+            "max_quantity": 4
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let deal_id = body["deal"]["id"].clone();
+
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", mangoes)), None).await;
+        assert_eq!(product["deal"]["id"], deal_id, "{}", product);
+        assert_eq!(product["deal"]["deal_price"], "35.00");
+        assert_eq!(product["deal"]["remaining"], 4);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/deals/active"), None).await;
+        assert_eq!(body["products"][0]["id"], json!(mangoes), "{}", body);
+        assert_eq!(body["pagination"]["total"], 1);
+
+        // Overlapping deals for the same product aren't allowed
+        let (status, _) = send(&app, create_deal(mangoes, json!({
+            "deal_price": "30.00",
+            "ends_at": Utc::now() + Duration::hours(1),
+            "max_quantity": 2
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 409);
+
+        let first_session = login(&app, &first.email).await;
+        let (_, address) = send(&app, add_address(), Some(&first_session)).await;
+        let (status, _) = send(&app, add_to_cart(mangoes, 3), Some(&first_session)).await;
+        assert_eq!(status, 200);
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&first_session)).await;
+        assert_eq!(cart["items"][0]["deal"]["id"], deal_id, "{}", cart);
+        let price: f64 = cart["items"][0]["price_per_unit"].as_str().unwrap().parse().unwrap();
+        assert_eq!(price, 35.0);
+
+        let (status, order) = send(&app, place_order(&address), Some(&first_session)).await;
+        assert_eq!(status, 201, "{}", order);
+        assert_eq!(line_price(&pool, &order).await, "35.00");
+
+        // One unit is left at the deal price, so a line of two pays the list price
+        let second_session = login(&app, &second.email).await;
+        let (_, address) = send(&app, add_address(), Some(&second_session)).await;
+        send(&app, add_to_cart(mangoes, 2), Some(&second_session)).await;
+        let (status, order) = send(&app, place_order(&address), Some(&second_session)).await;
+        assert_eq!(status, 201, "{}", order);
+        assert_eq!(line_price(&pool, &order).await, "50.00");
+
+        send(&app, add_to_cart(mangoes, 1), Some(&second_session)).await;
+        let (status, order) = send(&app, place_order(&address), Some(&second_session)).await;
+        assert_eq!(status, 201, "{}", order);
+        assert_eq!(line_price(&pool, &order).await, "35.00");
+
+        let sold = sqlx::query_scalar!("SELECT sold_quantity FROM deals WHERE product_id = $1", mangoes)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(sold, 4);
+
+        // Sold out: gone from the product and the active deals
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", mangoes)), None).await;
+        assert_eq!(product["deal"], Value::Null, "{}", product);
+        let (_, body) = send(&app, TestRequest::get().uri("/api/deals/active"), None).await;
+        assert_eq!(body["products"], json!([]), "{}", body);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn only_units_bought_at_the_deal_count_against_it(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let mangoes = ProductBuilder::new(seller.id).name("Alphonso Mangoes").price("50.00").stock(20).create(&pool).await;
+        let seller_session = login(&app, &seller.email).await;
+
+        // Six or more cost the same as the deal price anyway
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/products/{}/price_tiers", mangoes))
+            .set_json(json!({ "tiers": [{ "min_qty": 6, "unit_price": "35.00" }] })), Some(&seller_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        let (status, body) = send(&app, create_deal(mangoes, json!({
+            "deal_price": "35.00",
+            "ends_at": Utc::now() + Duration::hours(2),
+            "max_quantity": 10
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 201, "{}", body);
+
+        let sold = || async {
+            sqlx::query_scalar!("SELECT sold_quantity FROM deals WHERE product_id = $1", mangoes)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+
+        let session = login(&app, &buyer.email).await;
+        let (_, address) = send(&app, add_address(), Some(&session)).await;
+        send(&app, add_to_cart(mangoes, 6), Some(&session)).await;
+        let (status, order) = send(&app, place_order(&address), Some(&session)).await;
+        assert_eq!(status, 201, "{}", order);
+        assert_eq!(line_price(&pool, &order).await, "35.00");
+        assert_eq!(sold().await, 0, "the tier priced that line, not the deal");
+
+        send(&app, add_to_cart(mangoes, 3), Some(&session)).await;
+        let (status, order) = send(&app, place_order(&address), Some(&session)).await;
+        assert_eq!(status, 201, "{}", order);
+        assert_eq!(sold().await, 3);
+
+        // Cancelling gives the deal its units back
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&format!("/api/orders/{}/cancel", order["order_ids"][0].as_str().unwrap())), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(sold().await, 0);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn only_the_seller_manages_a_products_deals(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let rival = UserBuilder::new().supplier().create(&pool).await;
+        let okra = ProductBuilder::new(seller.id).name("Okra").price("40.00").create(&pool).await;
+        let seller_session = login(&app, &seller.email).await;
+        let rival_session = login(&app, &rival.email).await;
+
+        let deal = json!({
+            "deal_price": "30.00",
+            "ends_at": Utc::now() + Duration::hours(3),
+            "max_quantity": 10
+        });
+        let (status, _) = send(&app, create_deal(okra, deal.clone()), Some(&rival_session)).await;
+        assert_eq!(status, 403);
+
+        let (status, body) = send(&app, create_deal(okra, json!({
+            "deal_price": "40.00",
+            "ends_at": Utc::now() + Duration::hours(3),
+            "max_quantity": 10
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 400, "not below the list price: {}", body);
+
+        let (status, body) = send(&app, create_deal(okra, json!({
+            "deal_price": "30.00",
+            "ends_at": Utc::now() + Duration::days(31),
+            "max_quantity": 10
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 422, "{}", body);
+
+        // Starting later, then ended before it starts: removed outright
+        let (status, body) = send(&app, create_deal(okra, json!({
+            "deal_price": "30.00",
+            "starts_at": Utc::now() + Duration::hours(5),
+            "ends_at": Utc::now() + Duration::hours(6),
+            "max_quantity": 10
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let later = body["deal"]["id"].as_str().unwrap().to_string();
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", okra)), None).await;
+        assert_eq!(product["deal"], Value::Null, "not on yet");
+
+        let end = |deal_id: &str| TestRequest::delete().uri(&format!("/api/products/{}/deals/{}", okra, deal_id));
+        let (status, _) = send(&app, end(&later), Some(&rival_session)).await;
+        assert_eq!(status, 403);
+        let (status, _) = send(&app, end(&later), Some(&seller_session)).await;
+        assert_eq!(status, 200);
+
+        // On now, then ended early: kept, but over
+        let (status, body) = send(&app, create_deal(okra, deal), Some(&seller_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let now = body["deal"]["id"].as_str().unwrap().to_string();
+        let (status, _) = send(&app, end(&now), Some(&seller_session)).await;
+        assert_eq!(status, 200);
+        let (status, _) = send(&app, end(&now), Some(&seller_session)).await;
+        assert_eq!(status, 400);
+
+        let (status, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/products/{}/deals", okra)), Some(&seller_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        let deals = body["deals"].as_array().unwrap();
+        assert_eq!(deals.len(), 1, "{}", body);
+        assert_eq!(deals[0]["id"], json!(now));
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", okra)), None).await;
+        assert_eq!(product["deal"], Value::Null);
+
+        // Products priced per variant don't take deals
+        sqlx::query!(
+            "INSERT INTO product_variants (id, product_id, name, price_per_unit, stock_qty) VALUES ($1, $2, 'Tender', 45, 5)",
+            Uuid::new_v4(),
+            okra
+        )
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (status, body) = send(&app, create_deal(okra, json!({
+            "deal_price": "30.00",
+            "ends_at": Utc::now() + Duration::hours(3),
+            "max_quantity": 10
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 400, "{}", body);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn variants_wait_for_the_deal_to_end(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let paneer = ProductBuilder::new(seller.id).name("Paneer").price("300.00").create(&pool).await;
+        let session = login(&app, &seller.email).await;
+
+        let (status, body) = send(&app, create_deal(paneer, json!({
+            "deal_price": "250.00",
+            "starts_at": Utc::now() + Duration::hours(1),
+            "ends_at": Utc::now() + Duration::hours(4),
+            "max_quantity": 20
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let deal_id = body["deal"]["id"].as_str().unwrap().to_string();
+
+        let add_variant = || TestRequest::post()
+            .uri(&format!("/api/products/{}/variants", paneer))
+            .set_json(json!({ "name": "500 g", "price_per_unit": "150.00", "stock_qty": 10 }));
+        let (status, body) = send(&app, add_variant(), Some(&session)).await;
+        assert_eq!(status, 400, "a deal still to come counts too: {}", body);
+
+        let (status, _) = send(&app, TestRequest::delete()
+            .uri(&format!("/api/products/{}/deals/{}", paneer, deal_id)), Some(&session)).await;
+        assert_eq!(status, 200);
+        let (status, body) = send(&app, add_variant(), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+    }).await;
+}
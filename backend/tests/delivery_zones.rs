@@ -0,0 +1,107 @@
+// tests/delivery_zones.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+fn product_names(body: &Value) -> Vec<String> {
+    let mut names: Vec<String> = body["products"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|product| product["name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn sellers_only_take_orders_they_can_deliver(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let by_pincode = UserBuilder::new().supplier().name("Pincode Traders").create(&pool).await;
+        let by_radius = UserBuilder::new().supplier().name("Radius Foods").create(&pool).await;
+        let anywhere = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(by_pincode.id).name("Rice").create(&pool).await;
+        ProductBuilder::new(by_radius.id).name("Dal").create(&pool).await;
+        let oil = ProductBuilder::new(anywhere.id).name("Oil").create(&pool).await;
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, _) = send(&app, TestRequest::put().uri("/api/seller/delivery_zone").set_json(json!({
+            "pincodes": ["411001"]
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 403);
+
+        let session = login(&app, &by_pincode.email).await;
+        let (status, body) = send(&app, TestRequest::put().uri("/api/seller/delivery_zone").set_json(json!({
+            "pincodes": ["4110 01", "x"]
+        })), Some(&session)).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(body["fields"][0]["field"], "pincodes[1]");
+
+        let (status, body) = send(&app, TestRequest::put().uri("/api/seller/delivery_zone").set_json(json!({
+            "pincodes": ["4110 01", "411002"]
+        })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["delivery_zone"]["pincodes"], json!(["411001", "411002"]));
+
+        // A radius is measured from the seller's location, so needs one
+        let session = login(&app, &by_radius.email).await;
+        let radius = TestRequest::put().uri("/api/seller/delivery_zone").set_json(json!({ "radius_km": 10 }));
+        let (status, _) = send(&app, radius, Some(&session)).await;
+        assert_eq!(status, 400);
+        sqlx::query!("UPDATE users SET latitude = 18.52, longitude = 73.85 WHERE id = $1", by_radius.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let radius = TestRequest::put().uri("/api/seller/delivery_zone").set_json(json!({ "radius_km": 10 }));
+        let (status, body) = send(&app, radius, Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/products?deliverable_to=411001"), None).await;
+        assert_eq!(product_names(&body), ["Oil", "Rice"]);
+        let (_, body) = send(&app, TestRequest::get().uri("/api/products?deliverable_to=560001&lat=18.53&lng=73.86"), None).await;
+        assert_eq!(product_names(&body), ["Dal", "Oil"]);
+
+        for (product_id, quantity) in [(rice, 1), (oil, 1)] {
+            let (status, body) = send(&app, TestRequest::post().uri("/api/cart/add").set_json(json!({
+                "product_id": product_id,
+                "quantity": quantity
+            })), Some(&buyer_session)).await;
+            assert_eq!(status, 200, "add to cart: {}", body);
+        }
+
+        let address = |postal_code: &str| TestRequest::post().uri("/api/user/addresses").set_json(json!({
+            "recipient_name": "Test Buyer",
+            "phone": "9876543210",
+            "line1": "1 Market Road",
+            "city": "Pune",
+            "state": "Maharashtra",
+            "postal_code": postal_code
+        }));
+        let (_, body) = send(&app, address("560001"), Some(&buyer_session)).await;
+        let far_away = body["address"]["id"].clone();
+        let (_, body) = send(&app, address("411 002"), Some(&buyer_session)).await;
+        let nearby = body["address"]["id"].clone();
+
+        // Only the seller who doesn't deliver there is named
+        let (status, body) = send(&app, TestRequest::post().uri("/api/orders").set_json(json!({
+            "address_id": far_away
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 400, "{}", body);
+        let sellers = body["sellers"].as_array().unwrap();
+        assert_eq!(sellers.len(), 1, "{}", body);
+        assert_eq!(sellers[0]["seller_id"], json!(by_pincode.id));
+        assert!(sellers[0]["message"].as_str().unwrap().starts_with("Pincode Traders doesn't deliver to 560001"));
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/orders").set_json(json!({
+            "address_id": nearby
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["order_ids"].as_array().unwrap().len(), 2);
+    }).await;
+}
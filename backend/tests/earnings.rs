@@ -0,0 +1,70 @@
+// tests/earnings.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use bigdecimal::BigDecimal;
+use serde_json::json;
+use sqlx::PgPool;
+
+use backend::models::OrderStatus;
+use common::{init_app_with, local, login, send, test_config, OrderBuilder, ProductBuilder, UserBuilder};
+
+fn amount(value: &serde_json::Value) -> f64 {
+    value.as_str().expect("decimal string").parse().unwrap()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn delivery_credits_the_seller_less_commission(pool: PgPool) {
+    local(async {
+        let mut config = test_config();
+        config.platform_commission_percent = BigDecimal::from(10);
+        let app = init_app_with(pool.clone(), config).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).create(&pool).await;
+        let order_id = OrderBuilder::new(buyer.id, seller.id)
+            .item(product_id, 4, "50.00")
+            .status(OrderStatus::Shipped)
+            .create(&pool)
+            .await;
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "delivered" })), Some(&session)).await;
+        assert_eq!(status, 200, "deliver order: {}", body);
+
+        let (status, body) = send(&app, TestRequest::get().uri("/api/seller/earnings"), Some(&session)).await;
+        assert_eq!(status, 200, "earnings: {}", body);
+        let balance = &body["balances"][0];
+        assert_eq!(balance["currency"], json!("INR"));
+        assert_eq!(amount(&balance["gross"]), 200.0);
+        assert_eq!(amount(&balance["commission"]), 20.0);
+        assert_eq!(amount(&balance["balance"]), 180.0);
+        assert_eq!(body["entries"][0]["kind"], json!("sale"));
+
+        let admin_session = login(&app, &admin.email).await;
+        let payouts_uri = format!("/api/admin/sellers/{}/payouts", seller.id);
+        let (status, _) = send(&app, TestRequest::post()
+            .uri(&payouts_uri)
+            .set_json(json!({ "amount": "200.00" })), Some(&admin_session)).await;
+        assert_eq!(status, 409);
+
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&payouts_uri)
+            .set_json(json!({ "amount": "150.00", "reference": "NEFT-001" })), Some(&admin_session)).await;
+        assert_eq!(status, 201, "record payout: {}", body);
+        assert_eq!(amount(&body["balance"]), 30.0);
+
+        let (status, _) = send(&app, TestRequest::post()
+            .uri(&payouts_uri)
+            .set_json(json!({ "amount": "10.00" })), Some(&session)).await;
+        assert_eq!(status, 403, "sellers can't record their own payouts");
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/seller/earnings"), Some(&session)).await;
+        assert_eq!(amount(&body["balances"][0]["paid_out"]), 150.0);
+        assert_eq!(amount(&body["balances"][0]["balance"]), 30.0);
+        assert_eq!(body["entries"].as_array().map(Vec::len), Some(2));
+    }).await;
+}
@@ -0,0 +1,121 @@
+// tests/impersonation.rs
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::test::{self, TestRequest};
+use backend::models::AuditAction;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, UserBuilder, PASSWORD};
+
+fn profile() -> TestRequest {
+    TestRequest::get().uri("/api/user/profile")
+}
+
+/// Send a request that signs the session in as someone else, returning the new cookie
+async fn switch<S, B>(app: &S, request: TestRequest, session: &Cookie<'static>) -> Cookie<'static>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, request.cookie(session.clone()).to_request()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.response().cookies().next().expect("no new session cookie").into_owned()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn admins_impersonate_users_under_audit(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+        let other_admin = UserBuilder::new().admin().create(&pool).await;
+        let buyer = UserBuilder::new().name("Meena").create(&pool).await;
+        let admin_session = login(&app, &admin.email).await;
+
+        let impersonate = |user_id: uuid::Uuid| TestRequest::post().uri(&format!("/api/admin/impersonate/{}", user_id));
+
+        let (status, _) = send(&app, impersonate(admin.id), Some(&admin_session)).await;
+        assert_eq!(status, 400);
+        let (status, _) = send(&app, impersonate(other_admin.id), Some(&admin_session)).await;
+        assert_eq!(status, 403);
+
+        let session = switch(&app, impersonate(buyer.id), &admin_session).await;
+
+        let response = test::call_service(&app, profile().cookie(session.clone()).to_request()).await;
+        let flag = response.headers().get("x-impersonated-by").and_then(|value| value.to_str().ok()).map(str::to_string);
+        assert_eq!(flag, Some(admin.id.to_string()));
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["id"], json!(buyer.id));
+        assert_eq!(body["impersonated_by"], json!(admin.id));
+
+        let (status, _) = send(&app, TestRequest::put()
+            .uri("/api/user/profile")
+            .set_json(json!({ "name": "Meena K" })), Some(&session)).await;
+        assert_eq!(status, 200);
+
+        // Account security stays the user's, and admin routes are out of reach
+        let (status, _) = send(&app, TestRequest::put()
+            .uri("/api/user/password")
+            .set_json(json!({ "current_password": PASSWORD, "new_password": "Another-pass-123" })), Some(&session)).await;
+        assert_eq!(status, 403);
+        let (status, _) = send(&app, TestRequest::get().uri("/api/admin/users"), Some(&session)).await;
+        assert_eq!(status, 403);
+
+        let session = switch(&app, TestRequest::post().uri("/api/admin/impersonate/stop"), &session).await;
+        let (_, body) = send(&app, profile(), Some(&session)).await;
+        assert_eq!(body["id"], json!(admin.id));
+        assert_eq!(body["impersonated_by"], Value::Null);
+
+        let (status, _) = send(&app, TestRequest::post().uri("/api/admin/impersonate/stop"), Some(&session)).await;
+        assert_eq!(status, 400);
+
+        let logged = sqlx::query!(
+            r#"
+            SELECT action as "action: AuditAction", details
+            FROM audit_log
+            WHERE actor_id = $1 AND subject_id = $2
+            ORDER BY created_at, action
+            "#,
+            admin.id,
+            buyer.id
+        )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let actions: Vec<AuditAction> = logged.iter().map(|entry| entry.action).collect();
+        assert!(actions.contains(&AuditAction::ImpersonationStarted), "{:?}", actions);
+        assert!(actions.contains(&AuditAction::ImpersonationStopped), "{:?}", actions);
+        let changed_profile = logged.iter().any(|entry| {
+            entry.action == AuditAction::ImpersonatedRequest
+                && entry.details["path"] == "/api/user/profile"
+                && entry.details["status"] == 200
+        });
+        assert!(changed_profile, "{:?}", actions);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn an_impersonation_ends_with_the_admins_rights(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let admin_session = login(&app, &admin.email).await;
+
+        let session = switch(&app, TestRequest::post()
+            .uri(&format!("/api/admin/impersonate/{}", seller.id)), &admin_session).await;
+        let (status, _) = send(&app, profile(), Some(&session)).await;
+        assert_eq!(status, 200);
+
+        sqlx::query!("UPDATE users SET is_admin = FALSE WHERE id = $1", admin.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (status, _) = send(&app, profile(), Some(&session)).await;
+        assert_eq!(status, 401);
+    }).await;
+}
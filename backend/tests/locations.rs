@@ -0,0 +1,191 @@
+// tests/locations.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use backend::models::OrderStatus;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder};
+
+fn add_location(name: &str) -> TestRequest {
+    TestRequest::post().uri("/api/seller/locations").set_json(json!({ "name": name }))
+}
+
+fn set_stock(product_id: Uuid, location: &Value, quantity: i32) -> TestRequest {
+    TestRequest::put()
+        .uri(&format!("/api/products/{}/locations/{}", product_id, location["id"].as_str().unwrap()))
+        .set_json(json!({ "quantity": quantity }))
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn stock_is_kept_per_location_and_summed_for_buyers(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let jaggery = ProductBuilder::new(seller.id).name("Jaggery").stock(0).create(&pool).await;
+        let session = login(&app, &seller.email).await;
+
+        let (status, body) = send(&app, add_location("Market Stall"), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let stall = body["location"].clone();
+        assert_eq!(stall["is_default"], true, "the first location is the default");
+
+        let (status, body) = send(&app, add_location("Warehouse"), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let warehouse = body["location"].clone();
+        assert_eq!(warehouse["is_default"], false);
+
+        let (status, _) = send(&app, add_location("market stall"), Some(&session)).await;
+        assert_eq!(status, 409);
+
+        let (status, body) = send(&app, set_stock(jaggery, &stall, 6), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        let (_, body) = send(&app, set_stock(jaggery, &warehouse, 4), Some(&session)).await;
+        assert_eq!(body["stock_qty"], 10, "{}", body);
+
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", jaggery)), None).await;
+        assert_eq!(product["stock_qty"], 10, "{}", product);
+        assert_eq!(product["stock_locations"], 2);
+
+        // Stock kept by location isn't set as a whole any more
+        let (status, _) = send(&app, TestRequest::post()
+            .uri(&format!("/api/products/{}/stock_adjust", jaggery))
+            .set_json(json!({ "quantity_change": 5, "note": "Recount" })), Some(&session)).await;
+        assert_eq!(status, 400);
+
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&format!("/api/products/{}/locations/transfer", jaggery))
+            .set_json(json!({
+                "from_location_id": warehouse["id"],
+                "to_location_id": stall["id"],
+                "quantity": 4
+            })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["to"]["quantity"], 10);
+
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/products/{}/locations", jaggery)), Some(&session)).await;
+        assert_eq!(body["stock_qty"], 10, "{}", body);
+        assert_eq!(body["locations"][0]["quantity"], 10);
+        assert_eq!(body["locations"][1]["quantity"], 0);
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", jaggery)), None).await;
+        assert_eq!(product["stock_locations"], 1);
+
+        // Only an empty location can go; the default passes on to what's left
+        let (status, _) = send(&app, TestRequest::delete()
+            .uri(&format!("/api/seller/locations/{}", stall["id"].as_str().unwrap())), Some(&session)).await;
+        assert_eq!(status, 409);
+        let (status, _) = send(&app, TestRequest::put()
+            .uri(&format!("/api/seller/locations/{}", warehouse["id"].as_str().unwrap()))
+            .set_json(json!({ "is_default": true })), Some(&session)).await;
+        assert_eq!(status, 200);
+        let (status, _) = send(&app, TestRequest::delete()
+            .uri(&format!("/api/seller/locations/{}", warehouse["id"].as_str().unwrap())), Some(&session)).await;
+        assert_eq!(status, 200);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/seller/locations"), Some(&session)).await;
+        assert_eq!(body["locations"].as_array().unwrap().len(), 1, "{}", body);
+        assert_eq!(body["locations"][0]["is_default"], true);
+        assert_eq!(body["locations"][0]["units_on_hand"], 10);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn shipping_takes_stock_from_the_chosen_location(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let millet = ProductBuilder::new(seller.id).name("Foxtail Millet").stock(0).create(&pool).await;
+        let session = login(&app, &seller.email).await;
+
+        let (_, body) = send(&app, add_location("Market Stall"), Some(&session)).await;
+        let stall = body["location"].clone();
+        let (_, body) = send(&app, add_location("Warehouse"), Some(&session)).await;
+        let warehouse = body["location"].clone();
+        send(&app, set_stock(millet, &warehouse, 5), Some(&session)).await;
+
+        let order_id = OrderBuilder::new(buyer.id, seller.id)
+            .item(millet, 3, "80.00")
+            .status(OrderStatus::Paid)
+            .create(&pool)
+            .await;
+
+        // Sold units stay on hand until they ship, but aren't for sale
+        let (status, body) = send(&app, set_stock(millet, &stall, 2), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["stock_qty"], 4);
+        let (status, _) = send(&app, set_stock(millet, &warehouse, 0), Some(&session)).await;
+        assert_eq!(status, 409);
+
+        let ship = |location_id: Option<&Value>| TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "shipped", "location_id": location_id }));
+
+        // The default location doesn't hold enough
+        let (status, body) = send(&app, ship(None), Some(&session)).await;
+        assert_eq!(status, 409, "{}", body);
+
+        let (status, body) = send(&app, ship(Some(&warehouse["id"])), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/products/{}/locations", millet)), Some(&session)).await;
+        assert_eq!(body["stock_qty"], 4, "{}", body);
+        assert_eq!(body["on_hand"], 4);
+
+        let shipped_from = sqlx::query_scalar!(
+            "SELECT fulfilled_from_location_id FROM order_items WHERE order_id = $1",
+            order_id
+        )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(shipped_from.map(|id| id.to_string()), warehouse["id"].as_str().map(str::to_string));
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn a_transferred_product_takes_its_stock_to_the_new_owner(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let owner = UserBuilder::new().supplier().create(&pool).await;
+        let successor = UserBuilder::new().supplier().create(&pool).await;
+        let jaggery = ProductBuilder::new(owner.id).name("Jaggery").stock(0).create(&pool).await;
+        let owner_session = login(&app, &owner.email).await;
+        let successor_session = login(&app, &successor.email).await;
+
+        let (_, body) = send(&app, add_location("Market Stall"), Some(&owner_session)).await;
+        let stall = body["location"].clone();
+        let (_, body) = send(&app, add_location("Warehouse"), Some(&owner_session)).await;
+        let warehouse = body["location"].clone();
+        send(&app, set_stock(jaggery, &stall, 6), Some(&owner_session)).await;
+        send(&app, set_stock(jaggery, &warehouse, 4), Some(&owner_session)).await;
+
+        let transfer = || TestRequest::post()
+            .uri(&format!("/api/products/{}/transfer", jaggery))
+            .set_json(json!({ "target_user_id": successor.id }));
+
+        let (status, body) = send(&app, transfer(), Some(&owner_session)).await;
+        assert_eq!(status, 400, "nowhere to put the stock: {}", body);
+
+        let (_, body) = send(&app, add_location("Cold Store"), Some(&successor_session)).await;
+        let cold_store = body["location"].clone();
+        let (status, body) = send(&app, transfer(), Some(&owner_session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/products/{}/locations", jaggery)), Some(&successor_session)).await;
+        assert_eq!(body["stock_qty"], 10, "{}", body);
+        assert_eq!(body["locations"][0]["location_id"], cold_store["id"]);
+        assert_eq!(body["locations"][0]["quantity"], 10);
+
+        let left_behind = sqlx::query_scalar!(
+            "SELECT COUNT(*) as \"count!\" FROM stock_by_location s JOIN locations l ON l.id = s.location_id WHERE l.seller_id = $1",
+            owner.id
+        )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left_behind, 0);
+    }).await;
+}
@@ -0,0 +1,66 @@
+// tests/order_comments.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder};
+
+fn comment(order_id: Uuid, body: &str) -> TestRequest {
+    TestRequest::post()
+        .uri(&format!("/api/orders/{}/comments", order_id))
+        .set_json(json!({ "body": body }))
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn buyer_and_seller_share_an_order_thread(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let stranger = UserBuilder::new().create(&pool).await;
+        let coriander = ProductBuilder::new(seller.id).name("Coriander").create(&pool).await;
+        let order_id = OrderBuilder::new(buyer.id, seller.id).item(coriander, 2, "10.00").create(&pool).await;
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let seller_session = login(&app, &seller.email).await;
+
+        let (status, body) = send(&app, comment(order_id, "  Leave it at the blue cart  "), Some(&buyer_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["comment"]["body"], "Leave it at the blue cart");
+        assert_eq!(body["comment"]["author_id"], json!(buyer.id));
+
+        let (status, _) = send(&app, comment(order_id, "Out of coriander, is mint ok?"), Some(&seller_session)).await;
+        assert_eq!(status, 201);
+
+        let (status, body) = send(&app, comment(order_id, "   "), Some(&buyer_session)).await;
+        assert_eq!(status, 422, "{}", body);
+
+        let (status, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/orders/{}/comments", order_id)), Some(&seller_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        let bodies: Vec<&str> = body["comments"].as_array().unwrap().iter().map(|c| c["body"].as_str().unwrap()).collect();
+        assert_eq!(bodies, ["Leave it at the blue cart", "Out of coriander, is mint ok?"]);
+
+        // Each side hears about the other's comment, not their own
+        let (_, body) = send(&app, TestRequest::get().uri("/api/notifications"), Some(&seller_session)).await;
+        let seller_notes = body["notifications"].as_array().unwrap();
+        assert_eq!(seller_notes.len(), 1, "{}", body);
+        assert_eq!(seller_notes[0]["data"]["order_id"], json!(order_id));
+
+        let (_, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/orders/{}/timeline", order_id)), Some(&buyer_session)).await;
+        let comments: Vec<_> = body["events"].as_array().unwrap().iter().filter(|e| e["type"] == "comment").collect();
+        assert_eq!(comments.len(), 2, "{}", body);
+        assert_eq!(comments[1]["author_id"], json!(seller.id));
+
+        let stranger_session = login(&app, &stranger.email).await;
+        let (status, _) = send(&app, comment(order_id, "Hello"), Some(&stranger_session)).await;
+        assert_eq!(status, 403);
+        let (status, _) = send(&app, TestRequest::get()
+            .uri(&format!("/api/orders/{}/comments", order_id)), Some(&stranger_session)).await;
+        assert_eq!(status, 403);
+    }).await;
+}
@@ -0,0 +1,79 @@
+// tests/order_eta.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use backend::models::OrderStatus;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder};
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn orders_are_due_within_the_sellers_lead_time(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).create(&pool).await;
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::put().uri("/api/user/settings").set_json(json!({
+            "lead_time_hours": 0
+        })), Some(&session)).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(body["fields"][0]["field"], "lead_time_hours");
+
+        let (status, body) = send(&app, TestRequest::put().uri("/api/user/settings").set_json(json!({
+            "lead_time_hours": 24
+        })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let on_time = OrderBuilder::new(buyer.id, seller.id).item(rice, 1, "50.00").status(OrderStatus::Shipped).create(&pool).await;
+        let late = OrderBuilder::new(buyer.id, seller.id).item(rice, 1, "50.00").status(OrderStatus::Shipped).create(&pool).await;
+        let waiting = OrderBuilder::new(buyer.id, seller.id).item(rice, 2, "50.00").create(&pool).await;
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (_, body) = send(&app, TestRequest::get().uri("/api/orders"), Some(&buyer_session)).await;
+        let expected: DateTime<Utc> = serde_json::from_value(body["orders"][0]["expected_delivery_at"].clone()).unwrap();
+        let lead_time = expected - Utc::now();
+        assert!(lead_time > Duration::hours(23) && lead_time <= Duration::hours(24), "{}", body);
+
+        sqlx::query!(
+            "UPDATE orders SET expected_delivery_at = NOW() - INTERVAL '1 hour' WHERE id = ANY($1)",
+            &[late, waiting]
+        )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (status, body) = send(&app, TestRequest::get().uri("/api/orders/seller/pending"), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["overdue_count"], 1);
+        assert_eq!(body["orders"][0]["id"], json!(waiting));
+        assert_eq!(body["orders"][0]["overdue"], true);
+
+        for order_id in [on_time, late] {
+            let (status, body) = send(&app, TestRequest::put()
+                .uri(&format!("/api/orders/{}/status", order_id))
+                .set_json(json!({ "status": "delivered" })), Some(&session)).await;
+            assert_eq!(status, 200, "deliver order: {}", body);
+        }
+
+        // Delivered orders are no longer overdue, however late they were
+        let (_, body) = send(&app, TestRequest::get().uri("/api/orders/seller"), Some(&session)).await;
+        let overdue: Vec<_> = body["orders"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|order| order["overdue"] == true)
+            .map(|order| order["id"].clone())
+            .collect();
+        assert_eq!(overdue, [json!(waiting)]);
+
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/sellers/{}", seller.id)), None).await;
+        assert_eq!(body["seller"]["on_time_delivery_percent"], 50.0, "{}", body);
+        assert_eq!(body["seller"]["lead_time_hours"], 24);
+        assert_eq!(body["seller"]["total_deliveries"], 2);
+    }).await;
+}
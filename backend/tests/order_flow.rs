@@ -0,0 +1,412 @@
+// tests/order_flow.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::json;
+use sqlx::PgPool;
+use std::cell::Cell;
+use std::fmt;
+use std::future::Future;
+use std::sync::Once;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+use backend::models::OrderStatus;
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder, PASSWORD};
+
+thread_local! {
+    static ITEM_QUERIES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The SQL of a statement sqlx logged: its summary, and the full text when that's longer
+#[derive(Default)]
+struct Statement(String);
+
+impl Visit for Statement {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if matches!(field.name(), "summary" | "db.statement") {
+            self.0.push_str(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Counts the statements reading order items that sqlx runs on each thread, each of which
+/// it logs under `sqlx::query`
+struct CountItemQueries;
+
+impl<S: Subscriber> Layer<S> for CountItemQueries {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != "sqlx::query" {
+            return;
+        }
+        let mut statement = Statement::default();
+        event.record(&mut statement);
+        if statement.0.contains("order_items") {
+            ITEM_QUERIES.with(|count| count.set(count.get() + 1));
+        }
+    }
+}
+
+/// Run `work` and count its statements that read order items. Every test runs on its own
+/// thread, so only this test's statements are counted.
+async fn item_queries_run<F: Future>(work: F) -> (F::Output, usize) {
+    static SUBSCRIBER: Once = Once::new();
+    SUBSCRIBER.call_once(|| {
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(CountItemQueries))
+            .expect("no other global subscriber in this test binary");
+    });
+
+    let before = ITEM_QUERIES.with(Cell::get);
+    let output = work.await;
+    (output, ITEM_QUERIES.with(Cell::get) - before)
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn register_checkout_and_ship(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().name("Test Supplier").create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).price("40.00").stock(20).create(&pool).await;
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/register").set_json(json!({
+            "email": "buyer@example.com",
+            "password": PASSWORD,
+            "is_supplier": false,
+            "name": "Test Buyer"
+        })), None).await;
+        assert_eq!(status, 201, "register: {}", body);
+
+        let buyer = login(&app, "buyer@example.com").await;
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/cart/add").set_json(json!({
+            "product_id": product_id,
+            "quantity": 3
+        })), Some(&buyer)).await;
+        assert_eq!(status, 200, "add to cart: {}", body);
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/user/addresses").set_json(json!({
+            "recipient_name": "Test Buyer",
+            "phone": "9876543210",
+            "line1": "1 Market Road",
+            "city": "Pune",
+            "state": "Maharashtra",
+            "postal_code": "411001"
+        })), Some(&buyer)).await;
+        assert_eq!(status, 201, "add address: {}", body);
+        let address_id = body["address"]["id"].clone();
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/orders").set_json(json!({
+            "address_id": address_id
+        })), Some(&buyer)).await;
+        assert_eq!(status, 201, "create order: {}", body);
+        let order_id = body["order_ids"][0].as_str().expect("order id").to_string();
+
+        let (_, cart) = send(&app, TestRequest::get().uri("/api/cart"), Some(&buyer)).await;
+        assert_eq!(cart["items"], json!([]), "checkout should empty the cart");
+
+        let stock = sqlx::query_scalar!("SELECT stock_qty FROM products WHERE id = $1", product_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stock, 17);
+
+        let seller_session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "shipped" })), Some(&seller_session)).await;
+        assert_eq!(status, 200, "ship order: {}", body);
+
+        let (status, body) = send(&app, TestRequest::get().uri("/api/orders"), Some(&buyer)).await;
+        assert_eq!(status, 200);
+        let order = &body["orders"][0];
+        assert_eq!(order["id"], json!(order_id));
+        assert_eq!(order["status"], json!("shipped"));
+        let total: f64 = order["total_price"].as_str().expect("total price").parse().unwrap();
+        assert_eq!(total, 120.0);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn checkout_requires_login(pool: PgPool) {
+    local(async {
+        let app = init_app(pool).await;
+
+        let (status, _) = send(&app, TestRequest::post().uri("/api/orders").set_json(json!({
+            "address_id": uuid::Uuid::new_v4()
+        })), None).await;
+        assert_eq!(status, 401);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn only_the_seller_can_update_an_order(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let other_seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).create(&pool).await;
+        let order_id = OrderBuilder::new(buyer.id, seller.id)
+            .item(product_id, 2, "50.00")
+            .status(OrderStatus::Paid)
+            .create(&pool)
+            .await;
+
+        let session = login(&app, &other_seller.email).await;
+        let (status, _) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "shipped" })), Some(&session)).await;
+        assert_eq!(status, 403);
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "shipped" })), Some(&session)).await;
+        assert_eq!(status, 200, "ship order: {}", body);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn delivered_orders_cannot_go_back(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).create(&pool).await;
+        let order_id = OrderBuilder::new(buyer.id, seller.id)
+            .item(product_id, 1, "50.00")
+            .status(OrderStatus::Delivered)
+            .create(&pool)
+            .await;
+
+        let session = login(&app, &seller.email).await;
+        let (status, _) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "shipped" })), Some(&session)).await;
+        assert_eq!(status, 409);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn shipping_with_tracking_fills_the_timeline(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).create(&pool).await;
+        let order_id = OrderBuilder::new(buyer.id, seller.id)
+            .item(product_id, 1, "50.00")
+            .status(OrderStatus::Paid)
+            .create(&pool)
+            .await;
+        let expected = (chrono::Utc::now() + chrono::Duration::days(3)).date_naive();
+
+        let seller_session = login(&app, &seller.email).await;
+        let (status, _) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/shipment", order_id))
+            .set_json(json!({ "tracking_number": "TRK-1" })), Some(&seller_session)).await;
+        assert_eq!(status, 409, "not shipped yet");
+
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({
+                "status": "shipped",
+                "courier_name": "Blue Dart",
+                "tracking_number": "TRK-1",
+                "expected_delivery_date": expected
+            })), Some(&seller_session)).await;
+        assert_eq!(status, 200, "ship with tracking: {}", body);
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (_, orders) = send(&app, TestRequest::get().uri("/api/orders"), Some(&buyer_session)).await;
+        assert_eq!(orders["orders"][0]["shipment"]["courier_name"], json!("Blue Dart"));
+        assert_eq!(orders["orders"][0]["shipment"]["expected_delivery_date"], json!(expected));
+
+        let (status, _) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/shipment", order_id))
+            .set_json(json!({ "tracking_number": "TRK-2" })), Some(&buyer_session)).await;
+        assert_eq!(status, 403);
+
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/shipment", order_id))
+            .set_json(json!({ "tracking_number": "TRK-2" })), Some(&seller_session)).await;
+        assert_eq!(status, 200, "correct tracking: {}", body);
+        assert_eq!(body["shipment"]["courier_name"], json!("Blue Dart"), "other details are kept");
+        assert_eq!(body["shipment"]["tracking_number"], json!("TRK-2"));
+
+        let (status, timeline) = send(&app, TestRequest::get()
+            .uri(&format!("/api/orders/{}/timeline", order_id)), Some(&buyer_session)).await;
+        assert_eq!(status, 200, "timeline: {}", timeline);
+        let events: Vec<_> = timeline["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| (event["type"].as_str().unwrap(), event["status"].clone(), event["tracking_number"].clone()))
+            .collect();
+        let shipped = events.iter().position(|event| event.1 == json!("shipped")).expect("the shipped status");
+        assert_eq!(events[shipped + 1..], [
+            ("shipment", serde_json::Value::Null, json!("TRK-1")),
+            ("shipment", serde_json::Value::Null, json!("TRK-2")),
+        ]);
+
+        let notified = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND kind = 'order'",
+            buyer.id
+        )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(notified, Some(2), "told of the shipment and of the correction");
+
+        let (status, _) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "delivered", "tracking_number": "TRK-3" })), Some(&seller_session)).await;
+        assert_eq!(status, 422, "shipment details only when shipping");
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn reordering_reports_what_changed(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).name("Rice").price("50.00").create(&pool).await;
+        let dal = ProductBuilder::new(seller.id).name("Dal").price("30.00").stock(1).create(&pool).await;
+        let oil = ProductBuilder::new(seller.id).name("Oil").create(&pool).await;
+        let order_id = OrderBuilder::new(buyer.id, seller.id)
+            .item(rice, 2, "40.00")
+            .item(dal, 3, "30.00")
+            .item(oil, 1, "50.00")
+            .status(OrderStatus::Delivered)
+            .create(&pool)
+            .await;
+        sqlx::query!("UPDATE products SET taken_down_at = NOW() WHERE id = $1", oil)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let uri = format!("/api/orders/{}/reorder", order_id);
+
+        let stranger = UserBuilder::new().create(&pool).await;
+        let stranger_session = login(&app, &stranger.email).await;
+        let (status, _) = send(&app, TestRequest::post().uri(&uri).set_json(json!({})), Some(&stranger_session)).await;
+        assert_eq!(status, 403, "only the buyer can reorder");
+
+        let session = login(&app, &buyer.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri(&uri).set_json(json!({})), Some(&session)).await;
+        assert_eq!(status, 200, "reorder into the cart: {}", body);
+        assert_eq!(body["cart_size"], json!(2));
+        assert_eq!(body["unavailable_items"][0]["product_id"], json!(oil));
+        assert_eq!(body["adjusted_items"][0]["product_id"], json!(dal));
+        assert_eq!(body["adjusted_items"][0]["quantity"], json!(1));
+        assert_eq!(body["price_changes"].as_array().unwrap().len(), 1, "{}", body["price_changes"]);
+        assert_eq!(body["price_changes"][0]["product_id"], json!(rice));
+        let unit_price: f64 = body["price_changes"][0]["unit_price"].as_str().unwrap().parse().unwrap();
+        assert_eq!(unit_price, 50.0);
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/user/addresses").set_json(json!({
+            "recipient_name": "Test Buyer",
+            "phone": "9876543210",
+            "line1": "1 Market Road",
+            "city": "Pune",
+            "state": "Maharashtra",
+            "postal_code": "411001"
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "add address: {}", body);
+        let address_id = body["address"]["id"].clone();
+
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&uri)
+            .set_json(json!({ "address_id": address_id })), Some(&session)).await;
+        assert_eq!(status, 201, "reorder straight into an order: {}", body);
+        let new_order_id = body["order_id"].as_str().expect("order id");
+
+        let lines = sqlx::query!(
+            "SELECT product_id, quantity, unit_price FROM order_items WHERE order_id = $1::uuid ORDER BY quantity DESC",
+            new_order_id as _
+        )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let lines: Vec<_> = lines.iter().map(|line| (line.product_id, line.quantity, line.unit_price.to_string().parse::<f64>().unwrap())).collect();
+        assert_eq!(lines, [(rice, 2, 50.0), (dal, 1, 30.0)]);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn order_history_lists_each_orders_own_items(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let other = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).name("Rice").create(&pool).await;
+        let dal = ProductBuilder::new(seller.id).name("Dal").create(&pool).await;
+        let oil = ProductBuilder::new(seller.id).name("Oil").create(&pool).await;
+
+        let two_lines = OrderBuilder::new(buyer.id, seller.id)
+            .item(rice, 2, "50.00")
+            .item(dal, 5, "50.00")
+            .create(&pool)
+            .await;
+        let one_line = OrderBuilder::new(buyer.id, seller.id).item(oil, 1, "50.00").create(&pool).await;
+        let empty = OrderBuilder::new(buyer.id, seller.id).create(&pool).await;
+        OrderBuilder::new(other.id, seller.id).item(rice, 9, "50.00").create(&pool).await;
+
+        let session = login(&app, &buyer.email).await;
+        let (status, body) = send(&app, TestRequest::get().uri("/api/orders"), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["pagination"]["total"], 3);
+
+        let orders = body["orders"].as_array().unwrap();
+        let items = |order_id| {
+            let order = orders.iter().find(|order| order["id"] == json!(order_id)).expect("order listed");
+            let mut items: Vec<_> = order["items"]
+                .as_array()
+                .expect("items")
+                .iter()
+                .map(|item| (item["product_name"].as_str().unwrap().to_string(), item["quantity"].as_i64().unwrap()))
+                .collect();
+            items.sort();
+            items
+        };
+        assert_eq!(items(two_lines), [("Dal".to_string(), 5), ("Rice".to_string(), 2)]);
+        assert_eq!(items(one_line), [("Oil".to_string(), 1)]);
+        assert!(items(empty).is_empty(), "{}", body);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn order_history_loads_items_in_one_query_however_many_orders(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let occasional = UserBuilder::new().create(&pool).await;
+        let regular = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).name("Rice").create(&pool).await;
+
+        OrderBuilder::new(occasional.id, seller.id).item(rice, 1, "50.00").create(&pool).await;
+        for _ in 0..30 {
+            OrderBuilder::new(regular.id, seller.id).item(rice, 1, "50.00").create(&pool).await;
+        }
+
+        let occasional_session = login(&app, &occasional.email).await;
+        let regular_session = login(&app, &regular.email).await;
+        let history = || TestRequest::get().uri("/api/orders?limit=50");
+
+        let ((status, body), one_order) = item_queries_run(send(&app, history(), Some(&occasional_session))).await;
+        assert_eq!(status, 200, "{}", body);
+        let ((status, body), many_orders) = item_queries_run(send(&app, history(), Some(&regular_session))).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["orders"].as_array().unwrap().len(), 30);
+        assert!(body["orders"].as_array().unwrap().iter().all(|order| order["items"].as_array().unwrap().len() == 1));
+
+        // The page of orders and their count, which both filter on items, and one load of
+        // every listed order's items
+        assert_eq!(one_order, 3);
+        assert_eq!(many_orders, 3, "items are loaded in one query, not one per order");
+    }).await;
+}
@@ -0,0 +1,132 @@
+// tests/pagination.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+/// The ids on one page of a feed, and its pagination block
+fn page_ids(body: &Value, key: &str) -> (Vec<String>, Value) {
+    let ids = body[key]
+        .as_array()
+        .unwrap_or_else(|| panic!("no {} in {}", key, body))
+        .iter()
+        .map(|row| row["id"].as_str().unwrap().to_string())
+        .collect();
+    (ids, body["pagination"].clone())
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn favorites_page_through_a_large_set(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+
+        let mut product_ids = vec![];
+        for n in 0..130 {
+            product_ids.push(ProductBuilder::new(seller.id).name(&format!("Rice {}", n)).create(&pool).await);
+        }
+        // One statement, so every favorite has the same created_at
+        sqlx::query!(
+            "INSERT INTO favorites (user_id, product_id) SELECT $1, UNNEST($2::uuid[])",
+            buyer.id,
+            &product_ids
+        )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let session = login(&app, &buyer.email).await;
+        let favorites = |query: &str| TestRequest::get().uri(&format!("/api/user/favorites?{}", query));
+
+        let (status, body) = send(&app, favorites("limit=500"), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        let (first, pagination) = page_ids(&body, "products");
+        assert_eq!(first.len(), 100, "the limit is capped");
+        assert_eq!(pagination["limit"], 100);
+        assert_eq!(pagination["total"], 130);
+        assert_eq!(pagination["pages"], 2);
+
+        let (_, body) = send(&app, favorites("limit=500&page=2"), Some(&session)).await;
+        let (second, pagination) = page_ids(&body, "products");
+        assert_eq!(second.len(), 30);
+        assert_eq!(pagination["page"], 2);
+
+        let seen: HashSet<Uuid> = first.iter().chain(&second).map(|id| id.parse().unwrap()).collect();
+        assert_eq!(seen, product_ids.into_iter().collect(), "every favorite once, on one page or the other");
+
+        let (_, body) = send(&app, favorites("limit=0&page=0"), Some(&session)).await;
+        let (ids, pagination) = page_ids(&body, "products");
+        assert_eq!(ids, first[..1], "page and limit start at 1");
+        assert_eq!(pagination["pages"], 130);
+
+        let (_, body) = send(&app, favorites("page=3&limit=100"), Some(&session)).await;
+        let (ids, pagination) = page_ids(&body, "products");
+        assert!(ids.is_empty());
+        assert_eq!(pagination["total"], 130);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn notifications_page_through_a_large_set(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let user = UserBuilder::new().create(&pool).await;
+        let other = UserBuilder::new().create(&pool).await;
+
+        // All at the same moment; every third one already read
+        sqlx::query!(
+            r#"
+            INSERT INTO notifications (id, user_id, kind, title, read_at)
+            SELECT gen_random_uuid(), $1, 'order', 'Order update ' || n, CASE WHEN n % 3 = 0 THEN NOW() END
+            FROM generate_series(1, 150) n
+            "#,
+            user.id
+        )
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query!(
+            "INSERT INTO notifications (id, user_id, kind, title) VALUES (gen_random_uuid(), $1, 'order', 'Not yours')",
+            other.id
+        )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let session = login(&app, &user.email).await;
+        let notifications = |query: &str| TestRequest::get().uri(&format!("/api/notifications?{}", query));
+
+        let mut seen = HashSet::new();
+        for page in 1..=4 {
+            let (status, body) = send(&app, notifications(&format!("limit=40&page={}", page)), Some(&session)).await;
+            assert_eq!(status, 200, "{}", body);
+            let (ids, pagination) = page_ids(&body, "notifications");
+            assert_eq!(ids.len(), if page < 4 { 40 } else { 30 });
+            assert_eq!(pagination["total"], 150);
+            assert_eq!(pagination["pages"], 4);
+            assert_eq!(body["unread_count"], 100);
+            for id in ids {
+                assert!(seen.insert(id), "pages overlap");
+            }
+        }
+        assert_eq!(seen.len(), 150);
+
+        let (_, body) = send(&app, notifications("limit=1000"), Some(&session)).await;
+        let (ids, pagination) = page_ids(&body, "notifications");
+        assert_eq!(ids.len(), 100, "the limit is capped");
+        assert_eq!(pagination["limit"], 100);
+        assert_eq!(pagination["pages"], 2);
+
+        let (_, body) = send(&app, notifications("unread=true&limit=60&page=2"), Some(&session)).await;
+        let (ids, pagination) = page_ids(&body, "notifications");
+        assert_eq!(ids.len(), 40);
+        assert_eq!(pagination["total"], 100);
+        assert_eq!(pagination["pages"], 2);
+    }).await;
+}
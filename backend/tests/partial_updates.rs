@@ -0,0 +1,200 @@
+// tests/partial_updates.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use backend::update_builder::UpdateBuilder;
+use bigdecimal::BigDecimal;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::str::FromStr;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+/// Each subset of `fields`, as the fields in it
+fn combinations<T: Clone>(fields: &[T]) -> impl Iterator<Item = Vec<T>> + '_ {
+    (0..1u32 << fields.len()).map(move |mask| {
+        fields
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| mask & (1 << bit) != 0)
+            .map(|(_, field)| field.clone())
+            .collect()
+    })
+}
+
+#[test]
+fn builder_binds_one_placeholder_per_column_set() {
+    let columns = ["name", "description", "price_per_unit", "stock_qty"];
+
+    for given in combinations(&columns) {
+        let mut update = UpdateBuilder::new("products");
+        for column in columns {
+            update = update.set(column, given.contains(&column).then_some(1));
+        }
+
+        let expected = given
+            .iter()
+            .enumerate()
+            .map(|(index, column)| format!("{} = ${}", column, index + 1))
+            .collect::<Vec<_>>()
+            .join(", ");
+        assert_eq!(update.sql(), format!("UPDATE products SET {}", expected));
+        assert_eq!(update.is_empty(), given.is_empty());
+    }
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn every_combination_of_product_fields_updates_just_those(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let product_id = ProductBuilder::new(seller.id).name("Rice").price("40.00").stock(10).create(&pool).await;
+        let category_id = sqlx::query_scalar!("INSERT INTO categories (name) VALUES ('Partial Update Pulses') RETURNING id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let session = login(&app, &seller.email).await;
+
+        let changes = [
+            ("name", json!("Basmati")),
+            ("description", json!("Aged two years")),
+            ("price_per_unit", json!("42.50")),
+            ("currency", json!("inr")),
+            ("stock_qty", json!(25)),
+            ("unit", json!("kg")),
+            ("min_increment", json!(5)),
+            ("category_id", json!(category_id)),
+            ("location", json!(null)),
+        ];
+
+        for given in combinations(&changes) {
+            sqlx::query!(
+                r#"
+                UPDATE products
+                SET name = 'Rice', description = NULL, price_per_unit = 40, currency = 'USD', stock_qty = 10,
+                    unit = 'piece', min_increment = 1, category_id = (SELECT MIN(id) FROM categories),
+                    latitude = NULL, longitude = NULL
+                WHERE id = $1
+                "#,
+                product_id
+            )
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            let mut body = serde_json::Map::new();
+            for (field, value) in &given {
+                match *field {
+                    "location" => {
+                        body.insert("latitude".to_string(), json!(18.52));
+                        body.insert("longitude".to_string(), json!(73.85));
+                    }
+                    _ => {
+                        body.insert(field.to_string(), value.clone());
+                    }
+                }
+            }
+
+            let (status, response) = send(&app, TestRequest::put()
+                .uri(&format!("/api/products/{}", product_id))
+                .set_json(Value::Object(body.clone())), Some(&session)).await;
+            if given.is_empty() {
+                assert_eq!(status, 400, "nothing to update: {}", response);
+                continue;
+            }
+            assert_eq!(status, 200, "{:?}: {}", body, response);
+
+            let product = sqlx::query!(
+                r#"
+                SELECT name, description, price_per_unit, currency, stock_qty, unit::text as "unit!",
+                       min_increment, category_id, latitude, longitude
+                FROM products WHERE id = $1
+                "#,
+                product_id
+            )
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+            let has = |field: &str| given.iter().any(|(given_field, _)| *given_field == field);
+            assert_eq!(product.name, if has("name") { "Basmati" } else { "Rice" }, "{:?}", body);
+            assert_eq!(product.description.as_deref(), has("description").then_some("Aged two years"), "{:?}", body);
+            let price = if has("price_per_unit") { "42.50" } else { "40" };
+            assert_eq!(product.price_per_unit, BigDecimal::from_str(price).unwrap(), "{:?}", body);
+            assert_eq!(product.currency, if has("currency") { "INR" } else { "USD" }, "{:?}", body);
+            assert_eq!(product.stock_qty, if has("stock_qty") { 25 } else { 10 }, "{:?}", body);
+            assert_eq!(product.unit, if has("unit") { "kg" } else { "piece" }, "{:?}", body);
+            assert_eq!(product.min_increment, if has("min_increment") { 5 } else { 1 }, "{:?}", body);
+            assert_eq!(product.category_id == category_id, has("category_id"), "{:?}", body);
+            let location = product.latitude.zip(product.longitude);
+            assert_eq!(location, has("location").then_some((18.52, 73.85)), "{:?}", body);
+        }
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn every_combination_of_profile_fields_updates_just_those(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let user = UserBuilder::new().name("Asha").create(&pool).await;
+        let session = login(&app, &user.email).await;
+
+        let changes = [
+            ("name", json!("Asha Rao")),
+            ("phone", json!("9876543210")),
+            ("profile_image_url", json!("https://example.com/asha.png")),
+            ("location", json!(null)),
+        ];
+
+        for given in combinations(&changes) {
+            sqlx::query!(
+                r#"
+                UPDATE users
+                SET name = 'Asha', phone = NULL, profile_image_url = NULL, latitude = NULL, longitude = NULL
+                WHERE id = $1
+                "#,
+                user.id
+            )
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            let mut body = serde_json::Map::new();
+            for (field, value) in &given {
+                match *field {
+                    "location" => {
+                        body.insert("latitude".to_string(), json!(18.52));
+                        body.insert("longitude".to_string(), json!(73.85));
+                    }
+                    _ => {
+                        body.insert(field.to_string(), value.clone());
+                    }
+                }
+            }
+
+            let (status, response) = send(&app, TestRequest::put()
+                .uri("/api/user/profile")
+                .set_json(Value::Object(body.clone())), Some(&session)).await;
+            assert_eq!(status, 200, "{:?}: {}", body, response);
+
+            let (_, profile) = send(&app, TestRequest::get().uri("/api/user/profile"), Some(&session)).await;
+            let has = |field: &str| given.iter().any(|(given_field, _)| *given_field == field);
+            for (field, value) in &changes {
+                match *field {
+                    "location" => {
+                        let expected = if has("location") { json!([18.52, 73.85]) } else { json!([null, null]) };
+                        assert_eq!(json!([profile["latitude"], profile["longitude"]]), expected, "{:?}", body);
+                    }
+                    "name" => {
+                        let expected = if has("name") { value.clone() } else { json!("Asha") };
+                        assert_eq!(profile["name"], expected, "{:?}", body);
+                    }
+                    _ => {
+                        let expected = if has(field) { value.clone() } else { Value::Null };
+                        assert_eq!(profile[*field], expected, "{}: {:?}", field, body);
+                    }
+                }
+            }
+        }
+    }).await;
+}
@@ -0,0 +1,84 @@
+// tests/passwords.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use backend::config::{BreachCheck, CharacterClass, Config};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{init_app_with, local, login, send, test_config, UserBuilder, PASSWORD};
+
+fn strict_policy(breach_list: &str) -> Config {
+    Config {
+        password_min_length: 10,
+        password_required_classes: vec![
+            CharacterClass::Lowercase,
+            CharacterClass::Uppercase,
+            CharacterClass::Digit,
+            CharacterClass::Symbol,
+        ],
+        password_breach_check: BreachCheck::List,
+        password_breach_list: Some(breach_list.to_string()),
+        ..test_config()
+    }
+}
+
+fn messages(body: &Value, field: &str) -> Vec<String> {
+    body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["field"] == field)
+        .map(|entry| entry["message"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn new_passwords_follow_the_policy(pool: PgPool) {
+    local(async {
+        let breach_list = std::env::temp_dir().join(format!("breached-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&breach_list, "123456\npassword\nsummer2024!x\n").unwrap();
+        let app = init_app_with(pool.clone(), strict_policy(breach_list.to_str().unwrap())).await;
+
+        let register = |email: &str, password: &str| {
+            TestRequest::post().uri("/api/register").set_json(json!({ "email": email, "password": password, "is_supplier": false }))
+        };
+
+        // Every broken rule is listed, alongside the request's other problems
+        let (status, body) = send(&app, register("not-an-email", "abc"), None).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(messages(&body, "email"), ["must be a valid email address"]);
+        assert_eq!(messages(&body, "password"), [
+            "must be at least 10 characters",
+            "must contain an uppercase letter",
+            "must contain a digit",
+            "must contain a symbol",
+        ]);
+
+        // Breached passwords are refused whatever their case
+        let (status, body) = send(&app, register("new@example.com", "Summer2024!X"), None).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(messages(&body, "password"), ["appears in a list of breached passwords; choose another"]);
+
+        let (status, body) = send(&app, register("new@example.com", "Kettle-Mango-42"), None).await;
+        assert_eq!(status, 201, "{}", body);
+
+        // Changing a password is held to the same policy
+        let user = UserBuilder::new().create(&pool).await;
+        let session = login(&app, &user.email).await;
+        let (status, body) = send(&app, TestRequest::put().uri("/api/user/password").set_json(json!({
+            "current_password": PASSWORD,
+            "new_password": "summer2024!X"
+        })), Some(&session)).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(messages(&body, "new_password"), ["appears in a list of breached passwords; choose another"]);
+
+        let (status, body) = send(&app, TestRequest::put().uri("/api/user/password").set_json(json!({
+            "current_password": PASSWORD,
+            "new_password": "Kettle-Mango-43"
+        })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        std::fs::remove_file(breach_list).unwrap();
+    }).await;
+}
@@ -0,0 +1,63 @@
+// tests/platform_report.rs
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use backend::models::OrderStatus;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder};
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn admins_get_platform_figures_as_json_or_csv(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).name("Rice").create(&pool).await;
+        OrderBuilder::new(buyer.id, seller.id).item(rice, 2, "100.00").status(OrderStatus::Delivered).create(&pool).await;
+        OrderBuilder::new(buyer.id, seller.id).item(rice, 1, "50.00").create(&pool).await;
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, _) = send(&app, TestRequest::get().uri("/api/admin/reports/platform"), Some(&buyer_session)).await;
+        assert_eq!(status, 403);
+
+        let session = login(&app, &admin.email).await;
+        let (status, body) = send(&app, TestRequest::get().uri("/api/admin/reports/platform?range=7d"), Some(&session)).await;
+        assert_eq!(status, 200, "report: {}", body);
+
+        // Only the delivered order was paid for
+        assert_eq!(body["gmv"][0]["currency"], "INR");
+        assert_eq!(body["gmv"][0]["amount"].as_str().unwrap().parse::<f64>().unwrap(), 200.0);
+        assert_eq!(body["new_users"], 3);
+        assert_eq!(body["active_suppliers"], 1);
+        assert_eq!(body["orders"]["total"], 2);
+        assert_eq!(body["orders"]["paid"], 1);
+        assert_eq!(body["top_categories"][0]["units_sold"], 2, "{}", body["top_categories"]);
+
+        // Every day of the window is listed, quiet ones too
+        let daily = body["daily"].as_array().unwrap();
+        assert!(daily.len() >= 7, "{}", daily.len());
+        let today = daily.last().unwrap();
+        assert_eq!(today["date"], Utc::now().date_naive().to_string());
+        assert_eq!(today["orders"], 2);
+        assert_eq!(today["paid_orders"], 1);
+        assert_eq!(daily[0]["orders"], 0);
+
+        let (status, _) = send(&app, TestRequest::get().uri("/api/admin/reports/platform?range=2w"), Some(&session)).await;
+        assert_eq!(status, 400);
+
+        let request = TestRequest::get()
+            .uri("/api/admin/reports/platform?range=7d&format=csv")
+            .cookie(session.clone())
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers().get("Content-Type").unwrap(), "text/csv; charset=utf-8");
+        let csv = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "date,new_users,orders,paid_orders,active_suppliers,gmv_INR");
+        assert!(lines.last().unwrap().starts_with(&format!("{},3,2,1,1,", Utc::now().date_naive())), "{}", csv);
+    }).await;
+}
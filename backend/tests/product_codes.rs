@@ -0,0 +1,87 @@
+// tests/product_codes.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder};
+
+const BARCODE: &str = "8901262010016";
+
+fn lookup(barcode: &str) -> TestRequest {
+    TestRequest::get().uri(&format!("/api/products/lookup?barcode={}", barcode))
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn barcodes_find_products_to_restock_and_reorder(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let wholesaler = UserBuilder::new().supplier().create(&pool).await;
+        let grocer = UserBuilder::new().supplier().create(&pool).await;
+        let vendor = UserBuilder::new().create(&pool).await;
+        let wholesaler_session = login(&app, &wholesaler.email).await;
+
+        let create = |sku: &str, barcode: &str| TestRequest::post().uri("/api/products").set_json(json!({
+            "name": "Butter 500g",
+            "price_per_unit": "250.00",
+            "stock_qty": 12,
+            "category_id": 1,
+            "sku": sku,
+            "barcode": barcode
+        }));
+
+        let (status, body) = send(&app, create(" BTR-500 ", BARCODE), Some(&wholesaler_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let butter = body["product_id"].as_str().unwrap().to_string();
+
+        // Unique per seller, SKUs regardless of case
+        let (status, _) = send(&app, create("btr-500", ""), Some(&wholesaler_session)).await;
+        assert_eq!(status, 409);
+        let (status, _) = send(&app, create("BTR-501", BARCODE), Some(&wholesaler_session)).await;
+        assert_eq!(status, 409);
+        let (status, body) = send(&app, create("BTR 500!", "89-01"), Some(&wholesaler_session)).await;
+        assert_eq!(status, 422, "{}", body);
+
+        // Another seller stocking the same item shares its barcode
+        let grocer_butter = ProductBuilder::new(grocer.id).name("Butter").price("240.00").create(&pool).await;
+        let grocer_session = login(&app, &grocer.email).await;
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/products/{}", grocer_butter))
+            .set_json(json!({ "barcode": BARCODE, "sku": "G-17" })), Some(&grocer_session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (status, body) = send(&app, lookup(BARCODE), Some(&wholesaler_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["products"][0]["id"], json!(butter));
+        assert_eq!(body["products"][0]["sku"], "BTR-500");
+
+        // A vendor sees the cheapest first, until they've ordered from a seller
+        let vendor_session = login(&app, &vendor.email).await;
+        let (_, body) = send(&app, lookup(BARCODE), Some(&vendor_session)).await;
+        assert_eq!(body["products"].as_array().unwrap().len(), 2, "{}", body);
+        assert_eq!(body["products"][0]["id"], json!(grocer_butter));
+        let wholesaler_product = butter.parse().unwrap();
+        OrderBuilder::new(vendor.id, wholesaler.id).item(wholesaler_product, 1, "250.00").create(&pool).await;
+        let (_, body) = send(&app, lookup(BARCODE), Some(&vendor_session)).await;
+        assert_eq!(body["products"][0]["id"], json!(butter));
+
+        let (status, _) = send(&app, lookup("0000000000000"), Some(&vendor_session)).await;
+        assert_eq!(status, 404);
+        let (status, _) = send(&app, TestRequest::get().uri("/api/products/lookup"), Some(&vendor_session)).await;
+        assert_eq!(status, 400);
+
+        // The grocer can't take on a second product with the barcode; a blank one clears it
+        let (status, _) = send(&app, TestRequest::post()
+            .uri(&format!("/api/products/{}/transfer", butter))
+            .set_json(json!({ "target_user_id": grocer.id })), Some(&wholesaler_session)).await;
+        assert_eq!(status, 409);
+        let (status, _) = send(&app, TestRequest::put()
+            .uri(&format!("/api/products/{}", grocer_butter))
+            .set_json(json!({ "barcode": "" })), Some(&grocer_session)).await;
+        assert_eq!(status, 200);
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", grocer_butter)), None).await;
+        assert_eq!(product["barcode"], json!(null), "{}", product);
+        assert_eq!(product["sku"], "G-17");
+    }).await;
+}
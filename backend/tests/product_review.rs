@@ -0,0 +1,118 @@
+// tests/product_review.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use backend::config::Config;
+use common::{init_app_with, local, login, send, test_config, UserBuilder};
+
+fn review_required() -> Config {
+    Config {
+        product_review_required: true,
+        ..test_config()
+    }
+}
+
+fn listed(body: &Value, product_id: &str) -> bool {
+    body["products"]
+        .as_array()
+        .expect("products")
+        .iter()
+        .any(|product| product["id"] == json!(product_id))
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn new_listings_wait_for_approval(pool: PgPool) {
+    local(async {
+        let app = init_app_with(pool.clone(), review_required()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+
+        let seller_session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/products").set_json(json!({
+            "name": "Basmati Rice",
+            "price_per_unit": 80.0,
+            "stock_qty": 10,
+            "category_id": 1
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 201, "create product: {}", body);
+        assert_eq!(body["review_status"], json!("pending_review"));
+        let product_id = body["product_id"].as_str().expect("product id").to_string();
+        let product_uri = format!("/api/products/{}", product_id);
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (_, body) = send(&app, TestRequest::get().uri("/api/products"), Some(&buyer_session)).await;
+        assert!(!listed(&body, &product_id), "pending product listed to a buyer");
+        let (status, _) = send(&app, TestRequest::get().uri(&product_uri), Some(&buyer_session)).await;
+        assert_eq!(status, 404);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/products"), Some(&seller_session)).await;
+        assert!(listed(&body, &product_id), "seller can't see their pending product");
+
+        let admin_session = login(&app, &admin.email).await;
+        let (status, body) = send(&app, TestRequest::get().uri("/api/admin/products/review"), Some(&admin_session)).await;
+        assert_eq!(status, 200, "review queue: {}", body);
+        assert!(listed(&body, &product_id), "product missing from the review queue");
+
+        let review_uri = format!("/api/admin/products/review/{}", product_id);
+        let (status, _) = send(&app, TestRequest::post().uri(&review_uri)
+            .set_json(json!({ "status": "approved" })), Some(&seller_session)).await;
+        assert_eq!(status, 403);
+
+        let (status, body) = send(&app, TestRequest::post().uri(&review_uri)
+            .set_json(json!({ "status": "approved" })), Some(&admin_session)).await;
+        assert_eq!(status, 200, "approve: {}", body);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/products"), Some(&buyer_session)).await;
+        assert!(listed(&body, &product_id), "approved product not listed");
+
+        let (status, _) = send(&app, TestRequest::post().uri(&review_uri)
+            .set_json(json!({ "status": "rejected", "reason": "Too late" })), Some(&admin_session)).await;
+        assert_eq!(status, 409);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn rejected_listings_return_to_the_queue_when_edited(pool: PgPool) {
+    local(async {
+        let app = init_app_with(pool.clone(), review_required()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+
+        let seller_session = login(&app, &seller.email).await;
+        let (_, body) = send(&app, TestRequest::post().uri("/api/products").set_json(json!({
+            "name": "Counterfeit Saffron",
+            "price_per_unit": 10.0,
+            "stock_qty": 5,
+            "category_id": 1
+        })), Some(&seller_session)).await;
+        let product_id = body["product_id"].as_str().expect("product id").to_string();
+        let product_uri = format!("/api/products/{}", product_id);
+        let review_uri = format!("/api/admin/products/review/{}", product_id);
+
+        let admin_session = login(&app, &admin.email).await;
+        let (status, _) = send(&app, TestRequest::post().uri(&review_uri)
+            .set_json(json!({ "status": "rejected" })), Some(&admin_session)).await;
+        assert_eq!(status, 400, "rejecting needs a reason");
+
+        let (status, body) = send(&app, TestRequest::post().uri(&review_uri)
+            .set_json(json!({ "status": "rejected", "reason": "Name is misleading" })), Some(&admin_session)).await;
+        assert_eq!(status, 200, "reject: {}", body);
+
+        let (status, body) = send(&app, TestRequest::get().uri(&product_uri), Some(&seller_session)).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["review_status"], json!("rejected"));
+        assert_eq!(body["review_reason"], json!("Name is misleading"));
+
+        let (status, body) = send(&app, TestRequest::put().uri(&product_uri)
+            .set_json(json!({ "name": "Kashmiri Saffron" })), Some(&seller_session)).await;
+        assert_eq!(status, 200, "edit: {}", body);
+        assert_eq!(body["resubmitted_for_review"], json!(true));
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/admin/products/review"), Some(&admin_session)).await;
+        assert!(listed(&body, &product_id), "edited product not back in the queue");
+    }).await;
+}
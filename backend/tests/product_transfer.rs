@@ -0,0 +1,140 @@
+// tests/product_transfer.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use backend::models::OrderStatus;
+use common::{init_app, local, login, send, OrderBuilder, ProductBuilder, UserBuilder};
+
+fn transfer(product_id: Uuid, target_user_id: Uuid) -> TestRequest {
+    TestRequest::post()
+        .uri(&format!("/api/products/{}/transfer", product_id))
+        .set_json(json!({ "target_user_id": target_user_id }))
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn only_the_owner_transfers_and_only_to_a_supplier(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let owner = UserBuilder::new().supplier().create(&pool).await;
+        let rival = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let ghee = ProductBuilder::new(owner.id).name("Ghee").create(&pool).await;
+        let owner_session = login(&app, &owner.email).await;
+        let rival_session = login(&app, &rival.email).await;
+
+        let (status, _) = send(&app, transfer(ghee, rival.id), Some(&rival_session)).await;
+        assert_eq!(status, 403, "not theirs to give away");
+        let (status, _) = send(&app, transfer(ghee, rival.id), None).await;
+        assert_eq!(status, 401);
+
+        let (status, body) = send(&app, transfer(ghee, buyer.id), Some(&owner_session)).await;
+        assert_eq!(status, 400, "buyers can't sell: {}", body);
+        let (status, _) = send(&app, transfer(ghee, Uuid::new_v4()), Some(&owner_session)).await;
+        assert_eq!(status, 404);
+        let (status, _) = send(&app, transfer(ghee, owner.id), Some(&owner_session)).await;
+        assert_eq!(status, 400);
+
+        // Closed accounts keep is_supplier, but can't take on products
+        let suspended = UserBuilder::new().supplier().create(&pool).await;
+        let deleted = UserBuilder::new().supplier().create(&pool).await;
+        sqlx::query!("UPDATE users SET suspended_at = NOW() WHERE id = $1", suspended.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query!("UPDATE users SET deleted_at = NOW() WHERE id = $1", deleted.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (status, _) = send(&app, transfer(ghee, suspended.id), Some(&owner_session)).await;
+        assert_eq!(status, 404, "suspended supplier");
+        let (status, _) = send(&app, transfer(ghee, deleted.id), Some(&owner_session)).await;
+        assert_eq!(status, 404, "deleted supplier");
+
+        let owners = sqlx::query_scalar!("SELECT COUNT(*) as \"count!\" FROM product_ownership_history WHERE product_id = $1", ghee)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(owners, 0, "refused transfers leave no history");
+        let seller_id = sqlx::query_scalar!("SELECT seller_id FROM products WHERE id = $1", ghee)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(seller_id, owner.id);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn past_orders_stay_with_the_seller_who_took_them(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let owner = UserBuilder::new().supplier().create(&pool).await;
+        let successor = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let ghee = ProductBuilder::new(owner.id).name("Ghee").price("450.00").create(&pool).await;
+        let delivered = OrderBuilder::new(buyer.id, owner.id)
+            .item(ghee, 2, "450.00")
+            .status(OrderStatus::Delivered)
+            .create(&pool)
+            .await;
+        let pending = OrderBuilder::new(buyer.id, owner.id).item(ghee, 1, "450.00").create(&pool).await;
+
+        let owner_session = login(&app, &owner.email).await;
+        let (status, body) = send(&app, transfer(ghee, successor.id), Some(&owner_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["seller_id"], json!(successor.id));
+
+        let (_, product) = send(&app, TestRequest::get().uri(&format!("/api/products/{}", ghee)), None).await;
+        assert_eq!(product["seller_id"], json!(successor.id), "{}", product);
+        let history = sqlx::query!(
+            "SELECT from_seller_id, to_seller_id FROM product_ownership_history WHERE product_id = $1",
+            ghee
+        )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!((history.from_seller_id, history.to_seller_id), (owner.id, successor.id));
+
+        // The orders keep their seller, totals and lines as placed
+        let orders = sqlx::query!(
+            r#"
+            SELECT o.id, o.seller_id, o.status as "status: OrderStatus", o.total_price::text as "total!",
+                   oi.product_id, oi.quantity, oi.unit_price::text as "unit_price!"
+            FROM orders o JOIN order_items oi ON oi.order_id = o.id
+            WHERE o.id = ANY($1)
+            ORDER BY oi.quantity DESC
+            "#,
+            &[delivered, pending][..]
+        )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(orders.len(), 2);
+        assert_eq!((orders[0].id, orders[0].status.clone()), (delivered, OrderStatus::Delivered));
+        assert_eq!((orders[1].id, orders[1].status.clone()), (pending, OrderStatus::Pending));
+        for order in &orders {
+            assert_eq!(order.seller_id, owner.id);
+            assert_eq!(order.product_id, ghee);
+            assert_eq!(order.unit_price, "450.00");
+        }
+        assert_eq!(orders[0].total, "900.00");
+
+        // So they're still on the original seller's books, not the new owner's
+        let seller_orders = TestRequest::get().uri("/api/orders/seller");
+        let (status, body) = send(&app, seller_orders, Some(&owner_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["orders"].as_array().unwrap().len(), 2, "{}", body);
+        let successor_session = login(&app, &successor.email).await;
+        let (_, body) = send(&app, TestRequest::get().uri("/api/orders/seller"), Some(&successor_session)).await;
+        assert_eq!(body["orders"], json!([]), "{}", body);
+
+        // and the buyer's history still names the original seller
+        let buyer_session = login(&app, &buyer.email).await;
+        let (_, body) = send(&app, TestRequest::get().uri("/api/orders"), Some(&buyer_session)).await;
+        let sellers: Vec<_> = body["orders"].as_array().unwrap().iter().map(|order| order["seller_id"].clone()).collect();
+        assert_eq!(sellers, [json!(owner.id), json!(owner.id)], "{}", body);
+    }).await;
+}
@@ -0,0 +1,119 @@
+// tests/recurring_orders.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use backend::recurring_orders::place_due;
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder};
+
+async fn make_due(pool: &PgPool, id: Uuid) -> DateTime<Utc> {
+    sqlx::query_scalar!(
+        "UPDATE recurring_orders SET next_run_at = NOW() - INTERVAL '1 minute' WHERE id = $1 RETURNING next_run_at",
+        id
+    )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn standing_order_is_placed_every_cycle(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let other_seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let onions = ProductBuilder::new(seller.id).name("Onions").price("30.00").stock(25).create(&pool).await;
+        let elsewhere = ProductBuilder::new(other_seller.id).create(&pool).await;
+        let session = login(&app, &buyer.email).await;
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/user/addresses").set_json(json!({
+            "recipient_name": "Test Buyer",
+            "phone": "9876543210",
+            "line1": "Stall 4, Crawford Market",
+            "city": "Mumbai",
+            "state": "Maharashtra",
+            "postal_code": "400001"
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "add address: {}", body);
+        let address_id = body["address"]["id"].clone();
+
+        let (status, _) = send(&app, TestRequest::post().uri("/api/recurring_orders").set_json(json!({
+            "items": [{ "product_id": onions, "quantity": 10 }, { "product_id": elsewhere, "quantity": 1 }],
+            "interval_days": 1,
+            "address_id": address_id
+        })), Some(&session)).await;
+        assert_eq!(status, 400, "items from two sellers");
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/recurring_orders").set_json(json!({
+            "items": [{ "product_id": onions, "quantity": 10 }],
+            "interval_days": 1,
+            "address_id": address_id
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "create standing order: {}", body);
+        assert_eq!(body["recurring_order"]["status"], json!("active"));
+        let id: Uuid = serde_json::from_value(body["recurring_order"]["id"].clone()).unwrap();
+
+        // Not due yet
+        assert_eq!(place_due(&pool).await.unwrap(), 0);
+
+        let due_at = make_due(&pool, id).await;
+        assert_eq!(place_due(&pool).await.unwrap(), 1);
+
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/recurring_orders/{}", id)), Some(&session)).await;
+        let order_id = body["runs"][0]["order_id"].clone();
+        assert!(order_id.is_string(), "the cycle placed an order: {}", body);
+        let next_run_at: DateTime<Utc> = serde_json::from_value(body["recurring_order"]["next_run_at"].clone()).unwrap();
+        assert_eq!(next_run_at, due_at + Duration::days(1));
+
+        let stock = sqlx::query_scalar!("SELECT stock_qty FROM products WHERE id = $1", onions)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stock, 15);
+        let (_, orders) = send(&app, TestRequest::get().uri("/api/orders"), Some(&session)).await;
+        assert_eq!(orders["orders"][0]["id"], order_id);
+
+        let (status, body) = send(&app, TestRequest::post().uri(&format!("/api/recurring_orders/{}/skip", id)), Some(&session)).await;
+        assert_eq!(status, 200, "skip: {}", body);
+        let skipped_to: DateTime<Utc> = serde_json::from_value(body["recurring_order"]["next_run_at"].clone()).unwrap();
+        assert_eq!(skipped_to, next_run_at + Duration::days(1));
+
+        // Too little stock: the cycle is recorded without an order
+        sqlx::query!("UPDATE products SET stock_qty = 4 WHERE id = $1", onions)
+            .execute(&pool)
+            .await
+            .unwrap();
+        make_due(&pool, id).await;
+        assert_eq!(place_due(&pool).await.unwrap(), 1);
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/recurring_orders/{}", id)), Some(&session)).await;
+        assert_eq!(body["runs"][0]["order_id"], json!(null));
+        assert!(body["runs"][0]["error"].as_str().unwrap().contains("Onions"), "{}", body);
+        let (_, orders) = send(&app, TestRequest::get().uri("/api/orders"), Some(&session)).await;
+        assert_eq!(orders["orders"].as_array().map(Vec::len), Some(1));
+
+        // Paused schedules aren't run; resuming picks up from the next cycle to come
+        let (status, _) = send(&app, TestRequest::post().uri(&format!("/api/recurring_orders/{}/pause", id)), Some(&session)).await;
+        assert_eq!(status, 200);
+        make_due(&pool, id).await;
+        assert_eq!(place_due(&pool).await.unwrap(), 0);
+        let (status, body) = send(&app, TestRequest::post().uri(&format!("/api/recurring_orders/{}/resume", id)), Some(&session)).await;
+        assert_eq!(status, 200);
+        let resumed_at: DateTime<Utc> = serde_json::from_value(body["recurring_order"]["next_run_at"].clone()).unwrap();
+        assert!(resumed_at > Utc::now());
+
+        let (status, _) = send(&app, TestRequest::post().uri(&format!("/api/recurring_orders/{}/cancel", id)), Some(&session)).await;
+        assert_eq!(status, 200);
+        let (status, _) = send(&app, TestRequest::post().uri(&format!("/api/recurring_orders/{}/resume", id)), Some(&session)).await;
+        assert_eq!(status, 409);
+
+        let stranger = UserBuilder::new().create(&pool).await;
+        let stranger_session = login(&app, &stranger.email).await;
+        let (status, _) = send(&app, TestRequest::get().uri(&format!("/api/recurring_orders/{}", id)), Some(&stranger_session)).await;
+        assert_eq!(status, 403);
+    }).await;
+}
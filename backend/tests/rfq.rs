@@ -0,0 +1,255 @@
+// tests/rfq.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use common::{init_app, local, login, send, ProductBuilder, UserBuilder, PASSWORD};
+
+/// A product of the seller's in a category of its own
+async fn product_in_new_category(pool: &PgPool, seller_id: Uuid, category: &str) -> (Uuid, i32) {
+    let category_id = sqlx::query_scalar!("INSERT INTO categories (name) VALUES ($1) RETURNING id", category)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    let product_id = ProductBuilder::new(seller_id).name(category).create(pool).await;
+    sqlx::query!("UPDATE products SET category_id = $2 WHERE id = $1", product_id, category_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    (product_id, category_id)
+}
+
+fn address() -> TestRequest {
+    TestRequest::post().uri("/api/user/addresses").set_json(json!({
+        "recipient_name": "Test Buyer",
+        "phone": "9876543210",
+        "line1": "1 Market Road",
+        "city": "Pune",
+        "state": "Maharashtra",
+        "postal_code": "411001"
+    }))
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn the_accepted_quote_is_ordered_and_the_rest_rejected(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let first = UserBuilder::new().supplier().create(&pool).await;
+        let second = UserBuilder::new().supplier().create(&pool).await;
+        let elsewhere = UserBuilder::new().supplier().create(&pool).await;
+        let (first_product, category_id) = product_in_new_category(&pool, first.id, "RFQ Pulses").await;
+        let second_product = ProductBuilder::new(second.id).name("Toor Dal").create(&pool).await;
+        sqlx::query!("UPDATE products SET category_id = $2 WHERE id = $1", second_product, category_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        product_in_new_category(&pool, elsewhere.id, "RFQ Spices").await;
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/rfq").set_json(json!({
+            "quantity": 40
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(body["fields"][0]["field"], "product_id");
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/rfq").set_json(json!({
+            "category_id": category_id,
+            "quantity": 40,
+            "target_price": "45.00",
+            "note": "Weekly, for a restaurant"
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["supplier_count"], 2);
+        let rfq_id = body["rfq"]["id"].as_str().unwrap().to_string();
+
+        let first_session = login(&app, &first.email).await;
+        let second_session = login(&app, &second.email).await;
+        let elsewhere_session = login(&app, &elsewhere.email).await;
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/rfq/open"), Some(&first_session)).await;
+        assert_eq!(body["rfqs"][0]["id"], rfq_id, "{}", body);
+        let (_, body) = send(&app, TestRequest::get().uri("/api/rfq/open"), Some(&elsewhere_session)).await;
+        assert_eq!(body["rfqs"], json!([]));
+        let (status, _) = send(&app, TestRequest::get().uri(&format!("/api/rfq/{}", rfq_id)), Some(&elsewhere_session)).await;
+        assert_eq!(status, 404);
+
+        let quote = |product_id: Uuid, price: &str| TestRequest::post()
+            .uri(&format!("/api/rfq/{}/quotes", rfq_id))
+            .set_json(json!({ "product_id": product_id, "price_per_unit": price }));
+
+        // Only a product the request covers can be quoted
+        let (other_product, _) = product_in_new_category(&pool, first.id, "RFQ Oils").await;
+        let (status, body) = send(&app, quote(other_product, "44.00"), Some(&first_session)).await;
+        assert_eq!(status, 400, "{}", body);
+
+        let (status, body) = send(&app, quote(first_product, "44.00"), Some(&first_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["quote"]["quantity"], 40);
+        assert_eq!(body["quote"]["offer_status"], "pending");
+        let first_offer = body["quote"]["offer_id"].clone();
+        let (status, _) = send(&app, quote(first_product, "43.00"), Some(&first_session)).await;
+        assert_eq!(status, 409);
+
+        let (status, body) = send(&app, quote(second_product, "42.00"), Some(&second_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let second_quote = body["quote"]["id"].as_str().unwrap().to_string();
+
+        // The buyer sees every quote, cheapest first; a supplier only their own
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/rfq/{}", rfq_id)), Some(&buyer_session)).await;
+        let prices: Vec<f64> = body["quotes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|quote| quote["price_per_unit"].as_str().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(prices, [42.0, 44.0]);
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/rfq/{}", rfq_id)), Some(&first_session)).await;
+        assert_eq!(body["quotes"].as_array().unwrap().len(), 1);
+        assert_eq!(body["quotes"][0]["seller_id"], json!(first.id));
+
+        // Quotes arrive as offers in the buyer's chat
+        let (_, body) = send(&app, TestRequest::get().uri("/api/offers?status=pending"), Some(&buyer_session)).await;
+        assert_eq!(body.as_array().unwrap().len(), 2, "{}", body);
+
+        let (_, body) = send(&app, address(), Some(&buyer_session)).await;
+        let address_id = body["address"]["id"].clone();
+        let accept = || TestRequest::post()
+            .uri(&format!("/api/rfq/{}/quotes/{}/accept", rfq_id, second_quote))
+            .set_json(json!({ "address_id": address_id }));
+        let (status, _) = send(&app, accept(), Some(&first_session)).await;
+        assert_eq!(status, 404);
+
+        let (status, body) = send(&app, accept(), Some(&buyer_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["offer"]["status"], "ordered");
+        let order_id: Uuid = serde_json::from_value(body["order_id"].clone()).unwrap();
+
+        let order = sqlx::query!(
+            "SELECT seller_id, total_price::text as \"total_price!\" FROM orders WHERE id = $1",
+            order_id
+        )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(order.seller_id, second.id);
+        assert_eq!(order.total_price, "1680.00");
+
+        let (_, body) = send(&app, TestRequest::get().uri(&format!("/api/rfq/{}", rfq_id)), Some(&buyer_session)).await;
+        assert_eq!(body["rfq"]["status"], "awarded");
+        assert_eq!(body["rfq"]["awarded_quote_id"], second_quote);
+        let losing = body["quotes"].as_array().unwrap().iter().find(|quote| quote["offer_id"] == first_offer).unwrap();
+        assert_eq!(losing["offer_status"], "rejected");
+
+        let (status, _) = send(&app, accept(), Some(&buyer_session)).await;
+        assert_eq!(status, 409);
+        let (_, body) = send(&app, TestRequest::get().uri("/api/rfq/open"), Some(&first_session)).await;
+        assert_eq!(body["rfqs"], json!([]));
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn cancelling_withdraws_the_pending_quotes(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let rival = UserBuilder::new().supplier().create(&pool).await;
+        let (rice, category_id) = product_in_new_category(&pool, seller.id, "RFQ Grains").await;
+        let rival_rice = ProductBuilder::new(rival.id).create(&pool).await;
+        sqlx::query!("UPDATE products SET category_id = $2 WHERE id = $1", rival_rice, category_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // A seller can't ask for quotes on their own product, but a product's RFQ also
+        // reaches the other sellers in its category
+        let seller_session = login(&app, &seller.email).await;
+        let (status, _) = send(&app, TestRequest::post().uri("/api/rfq").set_json(json!({
+            "product_id": rice,
+            "quantity": 5
+        })), Some(&seller_session)).await;
+        assert_eq!(status, 400);
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/rfq").set_json(json!({
+            "product_id": rice,
+            "quantity": 5
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["supplier_count"], 2);
+        assert_eq!(body["rfq"]["category_id"], category_id);
+        let rfq_id = body["rfq"]["id"].as_str().unwrap().to_string();
+
+        let kind = sqlx::query_scalar!(r#"SELECT kind::text as "kind!" FROM notifications WHERE user_id = $1"#, rival.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(kind, "rfq");
+
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&format!("/api/rfq/{}/quotes", rfq_id))
+            .set_json(json!({ "product_id": rice, "price_per_unit": "48.00", "quantity": 6 })), Some(&seller_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["quote"]["quantity"], 6);
+
+        let (status, body) = send(&app, TestRequest::post().uri(&format!("/api/rfq/{}/cancel", rfq_id)), Some(&buyer_session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["rfq"]["status"], "cancelled");
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/offers"), Some(&seller_session)).await;
+        assert_eq!(body[0]["status"], "rejected", "{}", body);
+
+        let rival_session = login(&app, &rival.email).await;
+        let (status, _) = send(&app, TestRequest::post()
+            .uri(&format!("/api/rfq/{}/quotes", rfq_id))
+            .set_json(json!({ "product_id": rival_rice, "price_per_unit": "47.00" })), Some(&rival_session)).await;
+        assert_eq!(status, 409);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/rfq?status=cancelled"), Some(&buyer_session)).await;
+        assert_eq!(body["pagination"]["total"], 1);
+        assert_eq!(body["rfqs"][0]["quote_count"], 1);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn deleting_the_account_withdraws_its_rfqs(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let (rice, _) = product_in_new_category(&pool, seller.id, "RFQ Millets").await;
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/rfq").set_json(json!({
+            "product_id": rice,
+            "quantity": 5
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let rfq_id = body["rfq"]["id"].as_str().unwrap().to_string();
+
+        let seller_session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&format!("/api/rfq/{}/quotes", rfq_id))
+            .set_json(json!({ "product_id": rice, "price_per_unit": "48.00" })), Some(&seller_session)).await;
+        assert_eq!(status, 201, "{}", body);
+
+        let (status, body) = send(&app, TestRequest::delete().uri("/api/user/account").set_json(json!({
+            "password": PASSWORD
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/rfq/open"), Some(&seller_session)).await;
+        assert_eq!(body["rfqs"], json!([]), "{}", body);
+        let (status, _) = send(&app, TestRequest::post()
+            .uri(&format!("/api/rfq/{}/quotes", rfq_id))
+            .set_json(json!({ "product_id": rice, "price_per_unit": "46.00" })), Some(&seller_session)).await;
+        assert_eq!(status, 409);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/offers"), Some(&seller_session)).await;
+        assert_eq!(body[0]["status"], "rejected", "{}", body);
+    }).await;
+}
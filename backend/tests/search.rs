@@ -0,0 +1,53 @@
+// tests/search.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{init_app, local, send, ProductBuilder, UserBuilder};
+
+fn names(section: &Value) -> Vec<&str> {
+    section["results"]
+        .as_array()
+        .expect("results")
+        .iter()
+        .map(|result| result["name"].as_str().unwrap())
+        .collect()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn one_query_finds_products_sellers_and_categories(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().name("Ramesh Rice Traders").create(&pool).await;
+        UserBuilder::new().name("Rice Lover").create(&pool).await;
+        for name in ["Basmati Rice", "Brown Rice", "Rice Flour"] {
+            ProductBuilder::new(seller.id).name(name).create(&pool).await;
+        }
+        ProductBuilder::new(seller.id).name("Sold Out Rice").stock(0).create(&pool).await;
+        ProductBuilder::new(seller.id).name("Mustard Oil").create(&pool).await;
+
+        let (status, body) = send(&app, TestRequest::get().uri("/api/search?q=rice&limit=2"), None).await;
+        assert_eq!(status, 200, "search: {}", body);
+        assert_eq!(body["products"]["total"], json!(3), "in-stock products only");
+        assert_eq!(names(&body["products"]).len(), 2, "limited per type");
+        assert_eq!(body["products"]["results"][0]["type"], json!("product"));
+        assert_eq!(names(&body["sellers"]), vec!["Ramesh Rice Traders"], "buyers aren't sellers");
+        assert_eq!(body["sellers"]["results"][0]["product_count"], json!(4));
+        assert_eq!(names(&body["categories"]), vec!["Grains & Rice"]);
+
+        // A partly typed seller name still finds them
+        let (_, body) = send(&app, TestRequest::get().uri("/api/search?q=rames"), None).await;
+        assert_eq!(names(&body["sellers"]), vec!["Ramesh Rice Traders"]);
+        assert_eq!(body["products"]["total"], json!(0));
+
+        // Stemmed: the category is "Fresh Vegetables"
+        let (_, body) = send(&app, TestRequest::get().uri("/api/search?q=vegetable"), None).await;
+        assert_eq!(names(&body["categories"]), vec!["Fresh Vegetables"]);
+
+        let (status, body) = send(&app, TestRequest::get().uri("/api/search?q=%20"), None).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["sellers"]["results"], json!([]));
+    }).await;
+}
@@ -0,0 +1,162 @@
+// tests/sessions.rs
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::test::{self, TestRequest};
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{init_app, init_app_with, local, login, send, test_config, UserBuilder, PASSWORD};
+
+fn profile() -> TestRequest {
+    TestRequest::get().uri("/api/user/profile")
+}
+
+fn sign_in(email: &str, remember_me: bool) -> TestRequest {
+    TestRequest::post().uri("/api/login").set_json(json!({
+        "email": email,
+        "password": PASSWORD,
+        "remember_me": remember_me
+    }))
+}
+
+/// Log in with "remember me", returning the session cookie
+async fn remembered_login<S, B>(app: &S, email: &str) -> Cookie<'static>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, sign_in(email, true).to_request()).await;
+    assert_eq!(response.status().as_u16(), 200, "login as {} failed", email);
+    response.response().cookies().next().expect("login set no session cookie").into_owned()
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn remember_me_keeps_the_cookie_until_the_sign_in_ends(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let user = UserBuilder::new().create(&pool).await;
+
+        // A plain sign-in's cookie goes with the browser
+        let response = test::call_service(&app, sign_in(&user.email, false).to_request()).await;
+        assert_eq!(response.status().as_u16(), 200);
+        let cookie = response.response().cookies().next().unwrap().into_owned();
+        assert_eq!(cookie.max_age(), None);
+
+        let cookie = remembered_login(&app, &user.email).await;
+        let max_age = cookie.max_age().expect("a remembered session's cookie has an expiry").whole_seconds();
+        let thirty_days = 30 * 24 * 60 * 60;
+        assert!(max_age > thirty_days - 60 && max_age <= thirty_days, "{}", max_age);
+
+        let (status, _) = send(&app, profile(), Some(&cookie)).await;
+        assert_eq!(status, 200);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn sessions_run_out_unless_remembered(pool: PgPool) {
+    local(async {
+        let user = UserBuilder::new().create(&pool).await;
+
+        // Idle for longer than allowed
+        let mut config = test_config();
+        config.session_idle_timeout_minutes = 0;
+        let app = init_app_with(pool.clone(), config).await;
+        let session = login(&app, &user.email).await;
+        let (status, _) = send(&app, profile(), Some(&session)).await;
+        assert_eq!(status, 401);
+
+        // Idleness doesn't end a remembered one
+        let remembered = remembered_login(&app, &user.email).await;
+        let (status, _) = send(&app, profile(), Some(&remembered)).await;
+        assert_eq!(status, 200);
+
+        // Past the lifetime of a sign-in, however active
+        let mut config = test_config();
+        config.session_timeout_hours = 0;
+        let app = init_app_with(pool.clone(), config).await;
+        let session = login(&app, &user.email).await;
+        let (status, _) = send(&app, profile(), Some(&session)).await;
+        assert_eq!(status, 401);
+
+        // Nor does the lifetime of a plain sign-in, only its own
+        let remembered = remembered_login(&app, &user.email).await;
+        let (status, _) = send(&app, profile(), Some(&remembered)).await;
+        assert_eq!(status, 200);
+
+        let mut config = test_config();
+        config.remember_me_days = 0;
+        let app = init_app_with(pool.clone(), config).await;
+        let (status, _) = send(&app, profile(), Some(&remembered)).await;
+        assert_eq!(status, 401);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn changing_the_password_signs_every_session_out(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let user = UserBuilder::new().create(&pool).await;
+        let laptop = login(&app, &user.email).await;
+        let phone = login(&app, &user.email).await;
+
+        let (status, body) = send(&app, TestRequest::put().uri("/api/user/password").set_json(json!({
+            "current_password": PASSWORD,
+            "new_password": "Kettle-Mango-42"
+        })), Some(&laptop)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        for session in [&laptop, &phone] {
+            let (status, _) = send(&app, profile(), Some(session)).await;
+            assert_eq!(status, 401);
+        }
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/login").set_json(json!({
+            "email": user.email,
+            "password": "Kettle-Mango-42"
+        })), None).await;
+        assert_eq!(status, 200, "{}", body);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn suspension_signs_the_user_out(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let admin = UserBuilder::new().admin().create(&pool).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let session = login(&app, &seller.email).await;
+        let (status, _) = send(&app, profile(), Some(&session)).await;
+        assert_eq!(status, 200);
+
+        let admin_session = login(&app, &admin.email).await;
+        let (status, body) = send(&app, TestRequest::post()
+            .uri(&format!("/api/admin/users/{}/suspend", seller.id))
+            .set_json(json!({ "reason": "Chargebacks" })), Some(&admin_session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (status, _) = send(&app, profile(), Some(&session)).await;
+        assert_eq!(status, 401);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn deleting_the_account_signs_every_session_out(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let user = UserBuilder::new().create(&pool).await;
+        let laptop = login(&app, &user.email).await;
+        let phone = login(&app, &user.email).await;
+
+        let (status, body) = send(&app, TestRequest::delete().uri("/api/user/account").set_json(json!({
+            "password": PASSWORD
+        })), Some(&laptop)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (status, _) = send(&app, profile(), Some(&phone)).await;
+        assert_eq!(status, 401);
+    }).await;
+}
@@ -0,0 +1,96 @@
+// tests/uploads.rs
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use sqlx::PgPool;
+use std::io::Cursor;
+
+use common::{init_app, init_app_with, local, login, send, test_config, UserBuilder};
+
+const BOUNDARY: &str = "streetsource-test-boundary";
+
+/// A multipart form with the file as `file`
+fn multipart_file(filename: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+        BOUNDARY, filename
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    body
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn uploads_are_served_from_local_storage(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let user = UserBuilder::new().create(&pool).await;
+        let session = login(&app, &user.email).await;
+
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::new(400, 300)).write_to(&mut png, ImageFormat::Png).unwrap();
+
+        let request = TestRequest::post()
+            .uri("/api/upload/profile")
+            .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", BOUNDARY)))
+            .set_payload(multipart_file("avatar.png", png.get_ref()));
+        let (status, body) = send(&app, request, Some(&session)).await;
+        assert_eq!(status, 200, "upload: {}", body);
+
+        let thumbnail_url = body["images"]["thumbnail"].as_str().unwrap();
+        let path = thumbnail_url
+            .strip_prefix("http://localhost:8080")
+            .expect("served under LOCAL_STORAGE_URL");
+        assert!(path.starts_with("/uploads/profile-images/"), "{}", path);
+
+        let response = test::call_service(&app, TestRequest::get().uri(path).to_request()).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers().get("Content-Type").unwrap(), "image/png");
+        let thumbnail = image::load_from_memory(&test::read_body(response).await).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (200, 150), "resized to the thumbnail edge");
+
+        for missing in ["/uploads/profile-images/nothing.png", "/uploads/..%2F..%2Fetc%2Fpasswd"] {
+            let (status, _) = send(&app, TestRequest::get().uri(missing), None).await;
+            assert_eq!(status, 404, "{}", missing);
+        }
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn oversized_bodies_and_disguised_files_are_refused(pool: PgPool) {
+    local(async {
+        let config = backend::config::Config {
+            json_body_limit_kb: 1,
+            max_file_size_mb: 1,
+            ..test_config()
+        };
+        let app = init_app_with(pool.clone(), config).await;
+        let user = UserBuilder::new().create(&pool).await;
+        let session = login(&app, &user.email).await;
+
+        let upload = |filename: &str, data: &[u8]| {
+            TestRequest::post()
+                .uri("/api/upload/product")
+                .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", BOUNDARY)))
+                .set_payload(multipart_file(filename, data))
+        };
+
+        let mut too_big = b"\x89PNG\r\n\x1a\n".to_vec();
+        too_big.resize(2 * 1024 * 1024, 0);
+        let (status, body) = send(&app, upload("huge.png", &too_big), Some(&session)).await;
+        assert_eq!(status, 413, "{}", body);
+        assert_eq!(body["error"], "Payload too large: File size exceeds 1MB limit");
+
+        let (status, body) = send(&app, upload("photo.png", b"#!/bin/sh\necho not an image\n"), Some(&session)).await;
+        assert_eq!(status, 400, "{}", body);
+        assert_eq!(body["error"], "Bad request: Invalid image file");
+
+        let request = TestRequest::post()
+            .uri("/api/register")
+            .set_json(serde_json::json!({ "email": "a@example.com", "password": "x".repeat(2048) }));
+        let (status, body) = send(&app, request, None).await;
+        assert_eq!(status, 413, "{}", body);
+    }).await;
+}
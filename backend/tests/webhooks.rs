@@ -0,0 +1,352 @@
+// tests/webhooks.rs
+mod common;
+
+use actix_web::test::TestRequest;
+use backend::models::OrderStatus;
+use backend::webhooks;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+use common::{init_app, init_app_with, local, login, send, test_config, OrderBuilder, ProductBuilder, UserBuilder, PASSWORD};
+
+/// A request the receiver was sent
+struct Received {
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+/// Listen on a local port, answering every request with `status` and passing it on.
+/// Returns the URL to register and the requests as they arrive.
+async fn receiver(status: u16) -> (String, mpsc::UnboundedReceiver<Received>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hooks/orders", listener.local_addr().unwrap());
+    let (sender, received) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut chunk = [0u8; 4096];
+            let (head, body) = loop {
+                let read = stream.read(&mut chunk).await.unwrap();
+                request.extend_from_slice(&chunk[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let headers: HashMap<String, String> = head
+                        .lines()
+                        .skip(1)
+                        .filter_map(|line| line.split_once(": "))
+                        .map(|(name, value)| (name.to_lowercase(), value.to_string()))
+                        .collect();
+                    let length = headers.get("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+                    if body.len() >= length {
+                        break (headers, body.to_string());
+                    }
+                }
+                if read == 0 {
+                    break (HashMap::new(), String::new());
+                }
+            };
+
+            let response = format!("HTTP/1.1 {} Test\r\ncontent-length: 2\r\nconnection: close\r\n\r\nno", status);
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = sender.send(Received { headers: head, body });
+        }
+    });
+
+    (url, received)
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn order_events_are_signed_and_logged(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).create(&pool).await;
+        let (url, mut received) = receiver(200).await;
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/seller/webhooks").set_json(json!({
+            "url": url,
+            "events": ["order.created", "order.status_changed", "order.created"]
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["webhook"]["events"], json!(["order.created", "order.status_changed"]));
+        let webhook_id = body["webhook"]["id"].as_str().unwrap().to_string();
+        let secret = body["secret"].as_str().unwrap().to_string();
+        assert!(secret.starts_with("whsec_"), "{}", secret);
+
+        let (_, body) = send(&app, TestRequest::get().uri("/api/seller/webhooks"), Some(&session)).await;
+        assert_eq!(body["webhooks"].as_array().unwrap().len(), 1);
+        assert!(body["webhooks"][0].get("secret").is_none(), "the secret is only shown once: {}", body);
+
+        let order_id = OrderBuilder::new(buyer.id, seller.id).item(rice, 2, "50.00").status(OrderStatus::Paid).create(&pool).await;
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 1);
+
+        let created = received.recv().await.unwrap();
+        let event: Value = serde_json::from_str(&created.body).unwrap();
+        assert_eq!(event["type"], "order.created");
+        assert_eq!(event["data"]["order_id"], json!(order_id));
+        assert_eq!(event["data"]["buyer_id"], json!(buyer.id));
+        assert_eq!(created.headers["streetsource-event"], "order.created");
+
+        let signature = &created.headers[&webhooks::SIGNATURE_HEADER.to_lowercase()];
+        let timestamp: i64 = signature
+            .strip_prefix("t=")
+            .and_then(|rest| rest.split(',').next())
+            .and_then(|t| t.parse().ok())
+            .unwrap();
+        assert_eq!(*signature, webhooks::sign(&secret, timestamp, created.body.as_bytes()));
+        assert_ne!(*signature, webhooks::sign("whsec_wrong", timestamp, created.body.as_bytes()));
+
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "shipped" })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 1);
+
+        let changed = received.recv().await.unwrap();
+        let event: Value = serde_json::from_str(&changed.body).unwrap();
+        assert_eq!(event["type"], "order.status_changed");
+        assert_eq!(event["data"], json!({ "order_id": order_id, "from": "paid", "to": "shipped" }));
+
+        // Nothing more is due
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 0);
+
+        let (status, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/seller/webhooks/{}/deliveries", webhook_id)), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        let deliveries = body["deliveries"].as_array().unwrap();
+        assert_eq!(deliveries.len(), 2);
+        for delivery in deliveries {
+            assert_eq!(delivery["status"], "delivered", "{}", delivery);
+            assert_eq!(delivery["attempts"], 1);
+            assert_eq!(delivery["last_response_status"], 200);
+            assert!(delivery["delivered_at"].is_string());
+        }
+        assert_eq!(body["pagination"]["total"], 2);
+
+        // Another seller can't see or touch it
+        let other = UserBuilder::new().supplier().create(&pool).await;
+        let other_session = login(&app, &other.email).await;
+        let (status, _) = send(&app, TestRequest::get()
+            .uri(&format!("/api/seller/webhooks/{}/deliveries", webhook_id)), Some(&other_session)).await;
+        assert_eq!(status, 404);
+        let (status, _) = send(&app, TestRequest::delete()
+            .uri(&format!("/api/seller/webhooks/{}", webhook_id)), Some(&other_session)).await;
+        assert_eq!(status, 404);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn failed_deliveries_are_retried_with_backoff(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).create(&pool).await;
+        let (url, mut received) = receiver(503).await;
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/seller/webhooks").set_json(json!({
+            "url": url,
+            "events": ["order.created"]
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+        let webhook_id = body["webhook"]["id"].as_str().unwrap().to_string();
+
+        OrderBuilder::new(buyer.id, seller.id).item(rice, 1, "50.00").create(&pool).await;
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 1);
+        received.recv().await.unwrap();
+
+        let (_, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/seller/webhooks/{}/deliveries?status=pending", webhook_id)), Some(&session)).await;
+        let delivery = &body["deliveries"][0];
+        assert_eq!(delivery["attempts"], 1, "{}", body);
+        assert_eq!(delivery["last_response_status"], 503);
+        assert_eq!(delivery["last_error"], webhooks::ERROR_UNEXPECTED_STATUS, "the body isn't kept: {}", delivery);
+        let next_attempt_at: DateTime<Utc> = serde_json::from_value(delivery["next_attempt_at"].clone()).unwrap();
+        let wait = next_attempt_at - Utc::now();
+        assert!(wait > webhooks::retry_delay(1) - chrono::Duration::seconds(5) && wait <= webhooks::retry_delay(1), "{}", wait);
+
+        // Not due again until the backoff has passed
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 0);
+
+        // The last attempt that fails gives the delivery up
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET attempts = $1, next_attempt_at = NOW() WHERE status = 'pending'",
+            webhooks::MAX_ATTEMPTS - 1
+        )
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 1);
+        received.recv().await.unwrap();
+
+        let (_, body) = send(&app, TestRequest::get()
+            .uri(&format!("/api/seller/webhooks/{}/deliveries?status=failed", webhook_id)), Some(&session)).await;
+        assert_eq!(body["deliveries"][0]["attempts"], webhooks::MAX_ATTEMPTS, "{}", body);
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 0);
+
+        // A paused webhook queues nothing new
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/seller/webhooks/{}", webhook_id))
+            .set_json(json!({ "is_active": false })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["webhook"]["is_active"], false);
+        OrderBuilder::new(buyer.id, seller.id).item(rice, 1, "50.00").create(&pool).await;
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 0);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn webhooks_must_point_at_public_https_urls(pool: PgPool) {
+    local(async {
+        let mut config = test_config();
+        config.webhook_allow_private_urls = false;
+        let app = init_app_with(pool.clone(), config).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+
+        let session = login(&app, &seller.email).await;
+        for url in [
+            "http://hooks.example.com/orders",
+            "https://127.0.0.1/orders",
+            "https://10.0.0.7/orders",
+            "https://[::1]/orders",
+            "https://[::ffff:127.0.0.1]/orders",
+            "https://100.64.0.9/orders",
+            "https://0.0.0.1/orders",
+            "https://169.254.169.254/latest/meta-data",
+            "https://2130706433/orders",
+            "https://localhost:8443/orders",
+            "https://printer.local/orders",
+            "not a url",
+        ] {
+            let (status, body) = send(&app, TestRequest::post().uri("/api/seller/webhooks").set_json(json!({
+                "url": url,
+                "events": ["order.created"]
+            })), Some(&session)).await;
+            assert_eq!(status, 422, "{}: {}", url, body);
+            assert_eq!(body["fields"][0]["field"], "url", "{}", url);
+        }
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/seller/webhooks").set_json(json!({
+            "url": "https://hooks.example.com/orders",
+            "events": []
+        })), Some(&session)).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(body["fields"][0]["field"], "events");
+
+        let (status, body) = send(&app, TestRequest::post().uri("/api/seller/webhooks").set_json(json!({
+            "url": "https://hooks.example.com/orders",
+            "events": ["product.low_stock"]
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+
+        let buyer_session = login(&app, &buyer.email).await;
+        let (status, _) = send(&app, TestRequest::post().uri("/api/seller/webhooks").set_json(json!({
+            "url": "https://hooks.example.com/orders",
+            "events": ["order.created"]
+        })), Some(&buyer_session)).await;
+        assert_eq!(status, 403);
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn deliveries_only_go_to_public_addresses(pool: PgPool) {
+    local(async {
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).create(&pool).await;
+        let (url, mut received) = receiver(200).await;
+        let port = url.split(':').nth(2).and_then(|rest| rest.split('/').next()).unwrap().to_string();
+
+        // As if a public name had been registered and then pointed at this machine, or
+        // the URL had been saved when private URLs were allowed
+        for url in [
+            format!("https://localhost:{}/hooks/orders", port),
+            format!("https://[::ffff:127.0.0.1]:{}/hooks/orders", port),
+        ] {
+            sqlx::query!(
+                r#"
+                INSERT INTO webhooks (id, seller_id, url, secret, events)
+                VALUES (gen_random_uuid(), $1, $2, 'whsec_test', '{order.created}')
+                "#,
+                seller.id,
+                url
+            )
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        OrderBuilder::new(buyer.id, seller.id).item(rice, 1, "50.00").create(&pool).await;
+        assert_eq!(webhooks::deliver_due(&pool, false).await.unwrap(), 2);
+        assert!(received.try_recv().is_err(), "nothing was sent");
+
+        let deliveries = sqlx::query!("SELECT last_response_status, last_error FROM webhook_deliveries")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 2);
+        for delivery in deliveries {
+            assert_eq!(delivery.last_response_status, None);
+            assert_eq!(delivery.last_error.as_deref(), Some(webhooks::ERROR_BLOCKED_ADDRESS));
+        }
+    }).await;
+}
+
+#[sqlx::test(migrator = "backend::MIGRATOR")]
+async fn deleting_the_account_removes_its_webhooks(pool: PgPool) {
+    local(async {
+        let app = init_app(pool.clone()).await;
+        let seller = UserBuilder::new().supplier().create(&pool).await;
+        let buyer = UserBuilder::new().create(&pool).await;
+        let rice = ProductBuilder::new(seller.id).create(&pool).await;
+        let (url, mut received) = receiver(200).await;
+
+        let session = login(&app, &seller.email).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/api/seller/webhooks").set_json(json!({
+            "url": url,
+            "events": ["order.status_changed"]
+        })), Some(&session)).await;
+        assert_eq!(status, 201, "{}", body);
+
+        // A delivered order queues an event, still pending when the account goes
+        let order_id = OrderBuilder::new(buyer.id, seller.id).item(rice, 1, "50.00").status(OrderStatus::Shipped).create(&pool).await;
+        let (status, body) = send(&app, TestRequest::put()
+            .uri(&format!("/api/orders/{}/status", order_id))
+            .set_json(json!({ "status": "delivered" })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        let (status, body) = send(&app, TestRequest::delete().uri("/api/user/account").set_json(json!({
+            "password": PASSWORD
+        })), Some(&session)).await;
+        assert_eq!(status, 200, "{}", body);
+
+        assert_eq!(webhooks::deliver_due(&pool, true).await.unwrap(), 0);
+        assert!(received.try_recv().is_err());
+        let remaining = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM webhooks WHERE seller_id = $1"#, seller.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }).await;
+}
+
+#[test]
+fn only_public_addresses_are_public() {
+    for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "100.127.255.255",
+               "0.1.2.3", "::1", "::ffff:10.0.0.1", "fd00::1", "fe80::1"] {
+        assert!(!webhooks::is_public(ip.parse().unwrap()), "{}", ip);
+    }
+    for ip in ["93.184.216.34", "100.128.0.1", "8.8.8.8", "2606:2800:220:1:248:1893:25c8:1946", "::ffff:93.184.216.34"] {
+        assert!(webhooks::is_public(ip.parse().unwrap()), "{}", ip);
+    }
+}
@@ -48,18 +48,15 @@ const StreetSourceApp: React.FC = () => {
     loadProducts();
   }, []);
 
-  // Load cart when user logs in
+  // Load the cart on start and whenever the user changes; guests get their session cart,
+  // which the server merges into the user's cart at login
   useEffect(() => {
     const loadCart = async () => {
-      if (user) {
-        try {
-          const response = await apiClient.getCart();
-          setCart(response.items);
-        } catch (error) {
-          console.error('Failed to load cart:', error);
-          setCart([]);
-        }
-      } else {
+      try {
+        const response = await apiClient.getCart();
+        setCart(response.items);
+      } catch (error) {
+        console.error('Failed to load cart:', error);
         setCart([]);
       }
     };
@@ -69,11 +66,6 @@ const StreetSourceApp: React.FC = () => {
 
   // Cart Functions
   const addToCart = async (product: Product, quantity = 1) => {
-    if (!user) {
-      setShowAuthModal(true);
-      return;
-    }
-    
     try {
       await apiClient.addToCart(product.id, quantity);
       // Reload cart after adding item
@@ -1,7 +1,17 @@
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import { ShoppingCart, Trash2 } from 'lucide-react';
 import { apiClient } from '../../services/api';
-import type { User, CartItem } from '../../types';
+import type { User, CartItem, Address, AddressFormData } from '../../types';
+
+const EMPTY_ADDRESS: AddressFormData = {
+  recipient_name: '',
+  phone: '',
+  line1: '',
+  line2: '',
+  city: '',
+  state: '',
+  postal_code: ''
+};
 
 interface CartPageProps {
   user: User | null;
@@ -23,6 +33,34 @@ export const CartPage: React.FC<CartPageProps> = ({
   setShowAuthModal
 }) => {
   const [isCheckingOut, setIsCheckingOut] = useState(false);
+  const [addresses, setAddresses] = useState<Address[]>([]);
+  const [selectedAddressId, setSelectedAddressId] = useState('');
+  const [showAddressForm, setShowAddressForm] = useState(false);
+  const [addressForm, setAddressForm] = useState<AddressFormData>(EMPTY_ADDRESS);
+
+  useEffect(() => {
+    if (!user) return;
+    apiClient.getAddresses()
+      .then(({ addresses }) => {
+        setAddresses(addresses);
+        const preferred = addresses.find(address => address.is_default) || addresses[0];
+        setSelectedAddressId(preferred ? preferred.id : '');
+        setShowAddressForm(addresses.length === 0);
+      })
+      .catch(error => console.error('Failed to load addresses:', error));
+  }, [user]);
+
+  const handleSaveAddress = async () => {
+    try {
+      const { address } = await apiClient.createAddress(addressForm);
+      setAddresses(prev => [...prev, address]);
+      setSelectedAddressId(address.id);
+      setAddressForm(EMPTY_ADDRESS);
+      setShowAddressForm(false);
+    } catch (error: any) {
+      alert(error.message || 'Failed to save address');
+    }
+  };
 
   const total = cart.reduce((sum, item) => sum + item.subtotal, 0);
 
@@ -32,9 +70,14 @@ export const CartPage: React.FC<CartPageProps> = ({
       return;
     }
 
+    if (!selectedAddressId) {
+      alert('Please add a delivery address first');
+      return;
+    }
+
     setIsCheckingOut(true);
     try {
-      const response = await apiClient.createOrder();
+      const response = await apiClient.createOrder(selectedAddressId);
       setCart([]);
       alert(`Order placed successfully! Order IDs: ${response.order_ids.join(', ')}`);
       setCurrentPage('orders');
@@ -124,11 +167,62 @@ export const CartPage: React.FC<CartPageProps> = ({
             ))}
             
             <div className="bg-white rounded-lg shadow-md p-6">
+              <div className="mb-6">
+                <h3 className="text-gray-800 text-lg font-semibold mb-3">Delivery Address</h3>
+                {addresses.length > 0 && (
+                  <select
+                    value={selectedAddressId}
+                    onChange={(e) => setSelectedAddressId(e.target.value)}
+                    className="w-full p-3 border rounded-lg text-gray-700 mb-3"
+                  >
+                    {addresses.map(address => (
+                      <option key={address.id} value={address.id}>
+                        {address.label ? `${address.label}: ` : ''}{address.recipient_name}, {address.line1}, {address.city} {address.postal_code}
+                      </option>
+                    ))}
+                  </select>
+                )}
+                {showAddressForm ? (
+                  <div className="grid grid-cols-1 md:grid-cols-2 gap-3">
+                    {([
+                      ['recipient_name', 'Recipient name'],
+                      ['phone', 'Phone'],
+                      ['line1', 'Address line 1'],
+                      ['line2', 'Address line 2 (optional)'],
+                      ['city', 'City'],
+                      ['state', 'State'],
+                      ['postal_code', 'Postal code']
+                    ] as const).map(([field, placeholder]) => (
+                      <input
+                        key={field}
+                        type="text"
+                        placeholder={placeholder}
+                        value={addressForm[field] || ''}
+                        onChange={(e) => setAddressForm({ ...addressForm, [field]: e.target.value })}
+                        className="p-3 border rounded-lg text-gray-700"
+                      />
+                    ))}
+                    <button
+                      onClick={handleSaveAddress}
+                      className="bg-orange-500 hover:bg-orange-600 text-white px-6 py-3 rounded-lg"
+                    >
+                      Save Address
+                    </button>
+                  </div>
+                ) : (
+                  <button
+                    onClick={() => setShowAddressForm(true)}
+                    className="text-orange-600 hover:text-orange-700"
+                  >
+                    + Add new address
+                  </button>
+                )}
+              </div>
               <div className="flex justify-between items-center mb-6">
                 <span className="text-gray-800 text-2xl font-bold">Total: ₹{total.toFixed(2)}</span>
                 <button
                   onClick={handleCheckout}
-                  disabled={isCheckingOut}
+                  disabled={isCheckingOut || !selectedAddressId}
                   className="bg-green-500 hover:bg-green-600 disabled:bg-gray-400 text-white px-8 py-3 rounded-lg text-lg font-semibold"
                 >
                   {isCheckingOut ? 'Processing...' : 'Checkout'}
@@ -1,6 +1,7 @@
 import React, { useState, useEffect } from 'react';
-import { Send, X } from 'lucide-react';
+import { ImagePlus, Send, X } from 'lucide-react';
 import { apiClient } from '../../services/api';
+import { WS_PROTOCOL_VERSION } from '../../types';
 import type { User, Conversation, Message } from '../../types';
 
 interface MessagesPageProps {
@@ -62,6 +63,23 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
     websocket.onmessage = (event) => {
       try {
         const messageData = JSON.parse(event.data);
+
+        if (messageData.type === 'error') {
+          console.error(`WebSocket frame refused (${messageData.code}): ${messageData.message}`);
+          return;
+        }
+
+        // Edits and deletions update a message already in the history
+        if (messageData.type === 'message_edited' || messageData.type === 'message_deleted') {
+          setMessages(prev => prev.map(message =>
+            message.id === messageData.id
+              ? messageData.type === 'message_edited'
+                ? { ...message, content: messageData.content, edited_at: messageData.edited_at }
+                : { ...message, content: null, attachment_url: null, attachment_type: null, deleted_at: messageData.deleted_at }
+              : message
+          ));
+          return;
+        }
         
         // Add new message to current conversation if it matches
         if (selectedConversation && messageData.conv_id === selectedConversation.id) {
@@ -70,6 +88,8 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
             sender_id: messageData.sender_id,
             sender_name: messageData.sender_name,
             content: messageData.content,
+            attachment_url: messageData.attachment_url,
+            attachment_type: messageData.attachment_type,
             sent_at: messageData.sent_at
           }]);
         }
@@ -80,6 +100,7 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
             ? { 
                 ...conv, 
                 last_message: messageData.content,
+                last_message_attachment_type: messageData.attachment_type,
                 last_message_time: messageData.sent_at 
               }
             : conv
@@ -110,7 +131,8 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
     
     try {
       const response = await apiClient.getMessages(conversation.id);
-      setMessages(response.messages);
+      // Pages come newest first; the thread displays oldest at the top
+      setMessages([...response.messages].reverse());
     } catch (error) {
       console.error('Failed to load messages:', error);
       // Use mock messages as fallback
@@ -135,10 +157,51 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
     }
   };
 
+  const sendAttachment = async (file: File) => {
+    if (!selectedConversation) return;
+
+    try {
+      const upload = await apiClient.uploadMessageAttachment(selectedConversation.id, file);
+
+      // Over the socket the echoed event adds it to the thread
+      if (ws && ws.readyState === WebSocket.OPEN) {
+        ws.send(JSON.stringify({
+          version: WS_PROTOCOL_VERSION,
+          type: 'message',
+          conv_id: selectedConversation.id,
+          content: messageText,
+          attachment_url: upload.attachment_url
+        }));
+      } else {
+        const response = await apiClient.sendToConversation(selectedConversation.id, messageText, upload.attachment_url);
+        setMessages(prev => [...prev, response.chat_message]);
+      }
+      setMessageText('');
+    } catch (error) {
+      console.error('Failed to send attachment:', error);
+      alert('Failed to send image');
+    }
+  };
+
   const sendMessage = () => {
-    if (!messageText.trim() || !selectedConversation || !ws) return;
+    if (!messageText.trim() || !selectedConversation) return;
+
+    // Without a live socket, send over REST instead
+    if (!ws || ws.readyState !== WebSocket.OPEN) {
+      apiClient.sendToConversation(selectedConversation.id, messageText)
+        .then(response => {
+          setMessages(prev => [...prev, response.chat_message]);
+          setMessageText('');
+        })
+        .catch(error => {
+          console.error('Failed to send message:', error);
+          alert('Failed to send message');
+        });
+      return;
+    }
     
     const messageData = {
+      version: WS_PROTOCOL_VERSION,
       type: 'message',
       conv_id: selectedConversation.id,
       content: messageText
@@ -168,6 +231,7 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
     if (!offerData.price || !offerData.quantity || !selectedConversation || !ws) return;
     
     const offerMessage = {
+      version: WS_PROTOCOL_VERSION,
       type: 'offer',
       conv_id: selectedConversation.id,
       content: `Special Offer: ₹${offerData.price}/unit for ${offerData.quantity} units`,
@@ -249,13 +313,13 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
                     }`}
                   >
                     <div className="flex justify-between items-start">
-                      <h4 className="font-semibold text-gray-800">{conv.other_user_name}</h4>
+                      <h4 className="font-semibold text-gray-800">{conv.title ?? conv.other_user_name}</h4>
                       <span className="text-xs text-gray-500">
                         {conv.last_message_time ? new Date(conv.last_message_time).toLocaleTimeString() : ''}
                       </span>
                     </div>
                     <p className="text-sm text-gray-600 truncate mt-1">
-                      {conv.last_message || 'No messages yet'}
+                      {conv.last_message || (conv.last_message_attachment_type === 'image' ? 'Photo' : 'No messages yet')}
                     </p>
                   </div>
                 ))
@@ -268,8 +332,10 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
             {selectedConversation ? (
               <>
                 <div className="p-4 border-b border-gray-200 flex justify-between items-center">
-                  <h3 className="font-semibold text-gray-800">{selectedConversation.other_user_name}</h3>
-                  {user.is_supplier && (
+                  <h3 className="font-semibold text-gray-800">
+                    {selectedConversation.title ?? selectedConversation.other_user_name}
+                  </h3>
+                  {user.is_supplier && !selectedConversation.is_group && (
                     <button
                       onClick={() => setShowOfferModal(true)}
                       className="bg-blue-500 hover:bg-blue-600 text-white px-3 py-1 rounded text-sm"
@@ -294,16 +360,26 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
                           className={`max-w-xs px-4 py-2 rounded-lg ${
                             message.sender_id === user.id
                               ? 'bg-orange-400 text-white'
-                              : message.content.includes('Special Offer')
+                              : message.content?.includes('Special Offer')
                               ? 'bg-blue-100 text-blue-800 border-2 border-blue-300'
                               : 'bg-gray-200 text-gray-800'
                           }`}
                         >
-                          <p>{message.content}</p>
+                          {message.attachment_type === 'image' && message.attachment_url && (
+                            <a href={message.attachment_url} target="_blank" rel="noopener noreferrer">
+                              <img src={message.attachment_url} alt="Attachment" className="rounded max-h-60 mb-1" />
+                            </a>
+                          )}
+                          {message.deleted_at ? (
+                            <p className="italic opacity-70">This message was deleted</p>
+                          ) : (
+                            message.content && <p>{message.content}</p>
+                          )}
                           <p className="text-xs mt-1 opacity-70">
                             {new Date(message.sent_at).toLocaleTimeString()}
+                            {message.edited_at && !message.deleted_at && ' (edited)'}
                           </p>
-                          {message.content.includes('Special Offer') && message.sender_id !== user.id && (
+                          {message.content?.includes('Special Offer') && message.sender_id !== user.id && (
                             <div className="mt-2 space-x-2">
                               <button className="bg-green-500 text-white px-2 py-1 rounded text-xs">
                                 Accept
@@ -321,6 +397,19 @@ export const MessagesPage: React.FC<MessagesPageProps> = ({
                 
                 <div className="p-4 border-t border-gray-200">
                   <div className="flex gap-2">
+                    <label className="text-gray-500 hover:text-orange-400 p-2 cursor-pointer" title="Send an image">
+                      <ImagePlus className="w-5 h-5" />
+                      <input
+                        type="file"
+                        accept="image/jpeg,image/png,image/webp"
+                        className="hidden"
+                        onChange={(e) => {
+                          const file = e.target.files?.[0];
+                          if (file) sendAttachment(file);
+                          e.target.value = '';
+                        }}
+                      />
+                    </label>
                     <input
                       type="text"
                       value={messageText}
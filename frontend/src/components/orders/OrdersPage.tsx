@@ -40,8 +40,10 @@ export const OrdersPage: React.FC<OrdersPageProps> = ({
   const getStatusColor = (status: string) => {
     switch (status) {
       case 'pending': return 'bg-yellow-100 text-yellow-800';
+      case 'partially_shipped':
       case 'shipped': return 'bg-blue-100 text-blue-800';
       case 'delivered': return 'bg-green-100 text-green-800';
+      case 'cancelled': return 'bg-red-100 text-red-800';
       default: return 'bg-gray-100 text-gray-800';
     }
   };
@@ -104,7 +106,7 @@ export const OrdersPage: React.FC<OrdersPageProps> = ({
                   </div>
                   <div className="text-right">
                     <span className={`inline-block px-3 py-1 rounded-full text-sm font-medium ${getStatusColor(order.status)}`}>
-                      {order.status.charAt(0).toUpperCase() + order.status.slice(1)}
+                      {order.status.charAt(0).toUpperCase() + order.status.slice(1).replace('_', ' ')}
                     </span>
                     <p className="text-lg font-bold text-gray-800 mt-2">₹{order.total_price.toFixed(2)}</p>
                   </div>
@@ -124,6 +126,11 @@ export const OrdersPage: React.FC<OrdersPageProps> = ({
                           Qty: {item.quantity} × ₹{item.unit_price.toFixed(2)} = ₹{(item.quantity * item.unit_price).toFixed(2)}
                         </p>
                       </div>
+                      {item.fulfillment_status !== 'unfulfilled' && (
+                        <span className={`px-2 py-1 rounded-full text-xs font-medium ${getStatusColor(item.fulfillment_status)}`}>
+                          {item.fulfillment_status === 'delivered' ? 'Delivered' : 'Shipped'}
+                        </span>
+                      )}
                     </div>
                   ))}
                 </div>
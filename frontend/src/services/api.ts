@@ -1,17 +1,83 @@
 // src/services/api.ts
 import type { 
   User, 
+  ApiToken,
+  ApiTokenScope,
+  AppNotification,
+  PushDevice,
+  PushPlatform,
   Product, 
+  ProductReviewItem,
+  ProductReviewStatus,
+  ProductSuggestions,
+  SearchResults,
+  CategoryFacet,
+  ProductVariant,
+  PriceTier,
+  Deal,
+  CreateDealRequest,
+  PriceChange,
+  InventoryMovement,
+  StockAdjustRequest,
   CartItem, 
+  CartIssue,
   Order, 
+  DeliverySlot,
+  DeliveryZone,
+  FulfillmentStatus,
+  Invoice,
   AuthFormData,
   CreateProductRequest,
   UpdateProductRequest,
-  Category 
+  Category,
+  Address,
+  AddressFormData,
+  SellerProfile,
+  SellerAnalytics,
+  PlatformReport,
+  LedgerEntry,
+  SellerBalance,
+  PayoutDue,
+  AuditAction,
+  AuditLogEntry,
+  CartCoupon,
+  Coupon,
+  CreateCouponRequest,
+  Dispute,
+  Shipment,
+  OrderTimeline,
+  OrderComment,
+  ReorderResult,
+  RecurringOrder,
+  RecurringOrderRun,
+  RecurringOrderRequest,
+  BlockedUser,
+  Conversation,
+  ConversationProduct,
+  Message,
+  OrderFilters,
+  PicklistLine,
+  FieldError,
+  StockLocation,
+  ProductLocationStock,
+  Webhook,
+  WebhookDelivery,
+  WebhookEvent,
+  Rfq,
+  Quote
 } from '../types';
 
 const API_BASE_URL = 'http://65.2.22.213/api';
 
+function orderFilterQuery(params: Record<string, string | number | undefined>): string {
+  const searchParams = new URLSearchParams();
+  Object.entries(params).forEach(([key, value]) => {
+    if (value !== undefined && value !== '') searchParams.append(key, value.toString());
+  });
+  const query = searchParams.toString();
+  return query ? `?${query}` : '';
+}
+
 class ApiClient {
   private async request<T>(
     endpoint: string, 
@@ -33,7 +99,11 @@ class ApiClient {
       
       if (!response.ok) {
         const errorData = await response.json().catch(() => ({ message: 'An error occurred' }));
-        throw new Error(errorData.message || `HTTP ${response.status}`);
+        // 422s list each invalid field
+        const fieldErrors = (errorData.fields as FieldError[] | undefined)
+          ?.map((error) => `${error.field} ${error.message}`)
+          .join('; ');
+        throw new Error(fieldErrors || errorData.message || `HTTP ${response.status}`);
       }
 
       return await response.json();
@@ -44,16 +114,27 @@ class ApiClient {
   }
 
   // Authentication endpoints
-  async login(credentials: AuthFormData): Promise<{ message: string; user: User }> {
+  // Accounts with two-factor authentication get a challenge instead of a session
+  async login(credentials: AuthFormData): Promise<
+    { message: string; user: User } | { message: string; '2fa_required': true; challenge_id: string; expires_in: number }
+  > {
     return this.request('/login', {
       method: 'POST',
       body: JSON.stringify({
         email: credentials.email,
         password: credentials.password,
+        remember_me: credentials.remember_me ?? false,
       }),
     });
   }
 
+  async loginTwoFactor(challengeId: string, code: string): Promise<{ message: string; user: User }> {
+    return this.request('/login/2fa', {
+      method: 'POST',
+      body: JSON.stringify({ challenge_id: challengeId, code }),
+    });
+  }
+
   async register(userData: AuthFormData): Promise<{ message: string; user_id: string }> {
     return this.request('/register', {
       method: 'POST',
@@ -85,17 +166,182 @@ class ApiClient {
     });
   }
 
-  async getUserSettings(): Promise<{ is_supplier: boolean }> {
+  async getUserSettings(): Promise<{
+    is_supplier: boolean;
+    min_order_value: number;
+    delivery_fee: number;
+    tax_id: string | null;
+    tax_name: string | null;
+    store_paused: boolean;
+    lead_time_hours: number;
+    push_messages: boolean;
+    push_order_updates: boolean;
+  }> {
     return this.request('/user/settings');
   }
 
-  async updateUserSettings(settings: { become_supplier: boolean }): Promise<{ message: string }> {
+  // An empty tax_id or tax_name clears it; only sellers with a tax_id charge tax
+  async updateUserSettings(settings: {
+    become_supplier?: boolean;
+    min_order_value?: number;
+    delivery_fee?: number;
+    tax_id?: string;
+    tax_name?: string;
+    store_paused?: boolean;
+    // 1 to 720
+    lead_time_hours?: number;
+    push_messages?: boolean;
+    push_order_updates?: boolean;
+  }): Promise<{ message: string }> {
     return this.request('/user/settings', {
       method: 'PUT',
       body: JSON.stringify(settings),
     });
   }
 
+  // Signs out every session, this one included
+  async changePassword(currentPassword: string, newPassword: string): Promise<{ message: string }> {
+    return this.request('/user/password', {
+      method: 'PUT',
+      body: JSON.stringify({ current_password: currentPassword, new_password: newPassword }),
+    });
+  }
+
+  async changeEmail(newEmail: string, password: string): Promise<{ message: string }> {
+    return this.request('/user/email', {
+      method: 'PUT',
+      body: JSON.stringify({ new_email: newEmail, password }),
+    });
+  }
+
+  async confirmEmailChange(token: string): Promise<{ message: string; email: string }> {
+    return this.request('/user/email/confirm', {
+      method: 'POST',
+      body: JSON.stringify({ token }),
+    });
+  }
+
+  async exportAccount(): Promise<Record<string, unknown>> {
+    return this.request('/user/export');
+  }
+
+  async deleteAccount(password: string): Promise<{ message: string }> {
+    return this.request('/user/account', {
+      method: 'DELETE',
+      body: JSON.stringify({ password }),
+    });
+  }
+
+  async setupTwoFactor(): Promise<{ message: string; secret: string; provisioning_uri: string }> {
+    return this.request('/user/2fa/setup', {
+      method: 'POST',
+    });
+  }
+
+  async enableTwoFactor(code: string): Promise<{ message: string; recovery_codes: string[] }> {
+    return this.request('/user/2fa/enable', {
+      method: 'POST',
+      body: JSON.stringify({ code }),
+    });
+  }
+
+  async disableTwoFactor(password: string): Promise<{ message: string }> {
+    return this.request('/user/2fa', {
+      method: 'DELETE',
+      body: JSON.stringify({ password }),
+    });
+  }
+
+  // API tokens for integrations; the token itself is only returned on creation
+  async getApiTokens(): Promise<{ tokens: ApiToken[] }> {
+    return this.request('/user/tokens');
+  }
+
+  async createApiToken(name: string, scopes: ApiTokenScope[], expiresInDays?: number): Promise<{
+    message: string;
+    token: string;
+    api_token: ApiToken;
+  }> {
+    return this.request('/user/tokens', {
+      method: 'POST',
+      body: JSON.stringify({ name, scopes, expires_in_days: expiresInDays }),
+    });
+  }
+
+  async deleteApiToken(id: string): Promise<{ message: string }> {
+    return this.request(`/user/tokens/${id}`, {
+      method: 'DELETE',
+    });
+  }
+
+  // Notification center
+  async getNotifications(page = 1, limit = 20, unreadOnly = false): Promise<{
+    notifications: AppNotification[];
+    unread_count: number;
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/notifications?page=${page}&limit=${limit}${unreadOnly ? '&unread=true' : ''}`);
+  }
+
+  async getUnreadNotificationCount(): Promise<{ unread_count: number }> {
+    return this.request('/notifications/unread_count');
+  }
+
+  async markNotificationRead(id: string): Promise<{ message: string; unread_count: number }> {
+    return this.request(`/notifications/${id}/read`, {
+      method: 'POST',
+    });
+  }
+
+  async markAllNotificationsRead(): Promise<{ message: string; marked: number; unread_count: number }> {
+    return this.request('/notifications/read_all', {
+      method: 'POST',
+    });
+  }
+
+  // The token is the FCM registration token from the Firebase SDK (the web SDK in browsers)
+  async registerPushDevice(platform: PushPlatform, token: string): Promise<{ message: string; device: PushDevice }> {
+    return this.request('/notifications/devices', {
+      method: 'POST',
+      body: JSON.stringify({ platform, token }),
+    });
+  }
+
+  async getPushDevices(): Promise<{ devices: PushDevice[] }> {
+    return this.request('/notifications/devices');
+  }
+
+  async removePushDevice(id: string): Promise<{ message: string }> {
+    return this.request(`/notifications/devices/${id}`, {
+      method: 'DELETE',
+    });
+  }
+
+  // Address book endpoints
+  async getAddresses(): Promise<{ addresses: Address[] }> {
+    return this.request('/user/addresses');
+  }
+
+  async createAddress(addressData: AddressFormData): Promise<{ message: string; address: Address }> {
+    return this.request('/user/addresses', {
+      method: 'POST',
+      body: JSON.stringify(addressData),
+    });
+  }
+
+  async updateAddress(id: string, addressData: Partial<AddressFormData>): Promise<{ message: string; address: Address }> {
+    return this.request(`/user/addresses/${id}`, {
+      method: 'PUT',
+      body: JSON.stringify(addressData),
+    });
+  }
+
+  async deleteAddress(id: string): Promise<{ message: string }> {
+    return this.request(`/user/addresses/${id}`, {
+      method: 'DELETE',
+    });
+  }
+
   // Categories endpoints
   async getCategories(): Promise<{ categories: Category[] }> {
     return this.request('/categories');
@@ -104,11 +350,29 @@ class ApiClient {
   async getCategory(id: number): Promise<{
     id: number;
     name: string;
+    parent_id?: number | null;
+    // The category's own rate, and the one that applies (inherited when it has none)
+    tax_rate: number | null;
+    effective_tax_rate: number;
+    subcategories: Category[];
     product_count: number;
   }> {
     return this.request(`/categories/${id}`);
   }
 
+  // Admin only; null goes back to the parent category's rate
+  async setCategoryTaxRate(id: number, taxRate: number | null): Promise<{
+    message: string;
+    category_id: number;
+    tax_rate: number | null;
+    effective_tax_rate: number;
+  }> {
+    return this.request(`/admin/categories/${id}/tax_rate`, {
+      method: 'PUT',
+      body: JSON.stringify({ tax_rate: taxRate }),
+    });
+  }
+
   // Product endpoints
   async getProducts(params: {
     search?: string;
@@ -116,6 +380,11 @@ class ApiClient {
     sort?: string;
     page?: number;
     limit?: number;
+    lat?: number;
+    lng?: number;
+    radius_km?: number;
+    currency?: string;
+    deliverable_to?: string;
   } = {}): Promise<{
     products: Product[];
     pagination: {
@@ -124,6 +393,9 @@ class ApiClient {
       total: number;
       pages: number;
     };
+    facets: {
+      categories: CategoryFacet[];
+    };
   }> {
     const searchParams = new URLSearchParams();
     
@@ -132,6 +404,13 @@ class ApiClient {
     if (params.sort) searchParams.append('sort', params.sort);
     if (params.page) searchParams.append('page', params.page.toString());
     if (params.limit) searchParams.append('limit', params.limit.toString());
+    if (params.lat !== undefined && params.lng !== undefined) {
+      searchParams.append('lat', params.lat.toString());
+      searchParams.append('lng', params.lng.toString());
+    }
+    if (params.radius_km) searchParams.append('radius_km', params.radius_km.toString());
+    if (params.currency) searchParams.append('currency', params.currency);
+    if (params.deliverable_to) searchParams.append('deliverable_to', params.deliverable_to);
 
     const queryString = searchParams.toString();
     const endpoint = queryString ? `/products?${queryString}` : '/products';
@@ -139,50 +418,577 @@ class ApiClient {
     return this.request(endpoint);
   }
 
-  async getProduct(id: string): Promise<Product> {
-    return this.request(`/products/${id}`);
+  // Products, sellers and categories at once; limit applies to each kind
+  async search(q: string, limit?: number): Promise<SearchResults> {
+    const searchParams = new URLSearchParams({ q });
+    if (limit) searchParams.append('limit', limit.toString());
+    return this.request(`/search?${searchParams.toString()}`);
+  }
+
+  // Type-ahead; queries shorter than three characters return no suggestions
+  async suggestProducts(q: string, limit?: number): Promise<ProductSuggestions> {
+    const searchParams = new URLSearchParams({ q });
+    if (limit) searchParams.append('limit', limit.toString());
+    return this.request(`/products/suggest?${searchParams.toString()}`);
+  }
+
+  // The caller's own product first, then sellers they've ordered from, then the cheapest
+  async lookupProduct(barcode: string): Promise<{ barcode: string; products: Product[] }> {
+    return this.request(`/products/lookup?barcode=${encodeURIComponent(barcode)}`);
+  }
+
+  async getProduct(id: string, currency?: string): Promise<Product> {
+    const query = currency ? `?currency=${encodeURIComponent(currency)}` : '';
+    return this.request(`/products/${id}${query}`);
+  }
+
+  async getRelatedProducts(id: string): Promise<{ product_id: string; products: Product[] }> {
+    return this.request(`/products/${id}/related`);
+  }
+
+  async getPriceHistory(id: string, page?: number, limit?: number): Promise<{
+    product_id: string;
+    current_price: number;
+    history: PriceChange[];
+    pagination: {
+      page: number;
+      limit: number;
+      total: number;
+      pages: number;
+    };
+  }> {
+    const searchParams = new URLSearchParams();
+    if (page) searchParams.append('page', page.toString());
+    if (limit) searchParams.append('limit', limit.toString());
+    return this.request(`/products/${id}/price_history?${searchParams.toString()}`);
+  }
+
+  async adjustStock(id: string, adjustment: StockAdjustRequest): Promise<{
+    message: string;
+    product_id: string;
+    variant_id?: string | null;
+    stock_qty: number;
+  }> {
+    return this.request(`/products/${id}/stock_adjust`, {
+      method: 'POST',
+      body: JSON.stringify(adjustment),
+    });
+  }
+
+  async getProductLocations(id: string): Promise<ProductLocationStock> {
+    return this.request(`/products/${id}/locations`);
+  }
+
+  // Stock becomes what's on hand across the locations less what's sold and unshipped
+  async setLocationStock(id: string, locationId: string, quantity: number, note?: string): Promise<{
+    message: string;
+    product_id: string;
+    location_id: string;
+    quantity: number;
+    stock_qty: number;
+  }> {
+    return this.request(`/products/${id}/locations/${locationId}`, {
+      method: 'PUT',
+      body: JSON.stringify({ quantity, note }),
+    });
+  }
+
+  async transferStock(id: string, fromLocationId: string, toLocationId: string, quantity: number): Promise<{
+    message: string;
+    product_id: string;
+    from: { location_id: string; quantity: number };
+    to: { location_id: string; quantity: number };
+  }> {
+    return this.request(`/products/${id}/locations/transfer`, {
+      method: 'POST',
+      body: JSON.stringify({ from_location_id: fromLocationId, to_location_id: toLocationId, quantity }),
+    });
+  }
+
+  async getInventoryHistory(id: string, page?: number, limit?: number): Promise<{
+    product_id: string;
+    movements: InventoryMovement[];
+    pagination: {
+      page: number;
+      limit: number;
+      total: number;
+      pages: number;
+    };
+  }> {
+    const searchParams = new URLSearchParams();
+    if (page) searchParams.append('page', page.toString());
+    if (limit) searchParams.append('limit', limit.toString());
+    return this.request(`/products/${id}/inventory_history?${searchParams.toString()}`);
   }
 
-  async createProduct(productData: CreateProductRequest): Promise<{ message: string; product_id: string }> {
+  async createProduct(productData: CreateProductRequest): Promise<{
+    message: string;
+    product_id: string;
+    review_status: ProductReviewStatus;
+  }> {
     return this.request('/products', {
       method: 'POST',
       body: JSON.stringify(productData),
     });
   }
 
-  async updateProduct(id: string, productData: UpdateProductRequest): Promise<{ message: string }> {
+  async updateProduct(id: string, productData: UpdateProductRequest): Promise<{
+    message: string;
+    resubmitted_for_review: boolean;
+  }> {
     return this.request(`/products/${id}`, {
       method: 'PUT',
       body: JSON.stringify(productData),
     });
   }
 
-  async deleteProduct(id: string): Promise<{ message: string }> {
+  // Admin only; products waiting for review, oldest first
+  async getProductReviewQueue(page = 1, limit = 20): Promise<{
+    products: ProductReviewItem[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/admin/products/review?page=${page}&limit=${limit}`);
+  }
+
+  // Admin only; a reason is required to reject
+  async reviewProduct(id: string, status: 'approved' | 'rejected', reason?: string): Promise<{
+    message: string;
+    review_status: ProductReviewStatus;
+  }> {
+    return this.request(`/admin/products/review/${id}`, {
+      method: 'POST',
+      body: JSON.stringify({ status, reason }),
+    });
+  }
+
+  async deleteProduct(id: string): Promise<{ message: string; restorable_until: string }> {
     return this.request(`/products/${id}`, {
       method: 'DELETE',
     });
   }
 
+  async restoreProduct(id: string): Promise<{ message: string }> {
+    return this.request(`/products/${id}/restore`, {
+      method: 'POST',
+    });
+  }
+
+  async createVariant(
+    productId: string,
+    variant: { name: string; price_per_unit: number; stock_qty: number }
+  ): Promise<{ message: string; variant: ProductVariant }> {
+    return this.request(`/products/${productId}/variants`, {
+      method: 'POST',
+      body: JSON.stringify(variant),
+    });
+  }
+
+  async updateVariant(
+    productId: string,
+    variantId: string,
+    changes: { name?: string; price_per_unit?: number; stock_qty?: number }
+  ): Promise<{ message: string; variant: ProductVariant }> {
+    return this.request(`/products/${productId}/variants/${variantId}`, {
+      method: 'PUT',
+      body: JSON.stringify(changes),
+    });
+  }
+
+  async deleteVariant(productId: string, variantId: string): Promise<{ message: string }> {
+    return this.request(`/products/${productId}/variants/${variantId}`, {
+      method: 'DELETE',
+    });
+  }
+
+  // Replaces every tier; pass an empty list to remove them
+  async setPriceTiers(productId: string, tiers: PriceTier[]): Promise<{ message: string; price_tiers: PriceTier[] }> {
+    return this.request(`/products/${productId}/price_tiers`, {
+      method: 'PUT',
+      body: JSON.stringify({ tiers }),
+    });
+  }
+
+  async getActiveDeals(page = 1, limit = 20): Promise<{
+    products: Product[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/deals/active?page=${page}&limit=${limit}`);
+  }
+
+  async getProductDeals(productId: string): Promise<{ product_id: string; deals: Deal[] }> {
+    return this.request(`/products/${productId}/deals`);
+  }
+
+  async createDeal(productId: string, deal: CreateDealRequest): Promise<{ message: string; deal: Deal }> {
+    return this.request(`/products/${productId}/deals`, {
+      method: 'POST',
+      body: JSON.stringify(deal),
+    });
+  }
+
+  // A deal that hasn't started yet is removed
+  async endDeal(productId: string, dealId: string): Promise<{ message: string }> {
+    return this.request(`/products/${productId}/deals/${dealId}`, {
+      method: 'DELETE',
+    });
+  }
+
+  async createWsTicket(): Promise<{ ticket: string; expires_in: number }> {
+    return this.request('/ws/ticket', {
+      method: 'POST',
+    });
+  }
+
+  async getPresence(userId: string): Promise<{
+    user_id: string;
+    online: boolean;
+    last_seen_at: string | null;
+  }> {
+    return this.request(`/users/${userId}/presence`);
+  }
+
+  async blockUser(userId: string): Promise<{ message: string }> {
+    return this.request(`/users/${userId}/block`, { method: 'POST' });
+  }
+
+  async unblockUser(userId: string): Promise<{ message: string }> {
+    return this.request(`/users/${userId}/block`, { method: 'DELETE' });
+  }
+
+  async getBlockedUsers(): Promise<{ blocked_users: BlockedUser[] }> {
+    return this.request('/user/blocks');
+  }
+
+  async reportUser(
+    userId: string,
+    reason: string,
+    block = false
+  ): Promise<{ message: string; report_id: string; blocked: boolean }> {
+    return this.request(`/users/${userId}/report`, {
+      method: 'POST',
+      body: JSON.stringify({ reason, block }),
+    });
+  }
+
+  // Seller storefront endpoints
+  async getSeller(id: string): Promise<{
+    seller: SellerProfile;
+    products: Product[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/sellers/${id}`);
+  }
+
+  async getSellerProducts(id: string, page = 1, limit = 20): Promise<{
+    products: Product[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/sellers/${id}/products?page=${page}&limit=${limit}`);
+  }
+
+  // Slots a buyer can still book
+  async getSellerDeliverySlots(sellerId: string): Promise<{ delivery_slots: DeliverySlot[] }> {
+    return this.request(`/sellers/${sellerId}/delivery_slots`);
+  }
+
+  async getDeliveryZone(): Promise<{ delivery_zone: DeliveryZone }> {
+    return this.request('/seller/delivery_zone');
+  }
+
+  async setDeliveryZone(data: { pincodes: string[]; radius_km?: number | null }): Promise<{
+    message: string;
+    delivery_zone: DeliveryZone;
+  }> {
+    return this.request('/seller/delivery_zone', {
+      method: 'PUT',
+      body: JSON.stringify(data),
+    });
+  }
+
+  // Requests for quote
+  async createRfq(data: {
+    product_id?: string;
+    category_id?: number;
+    quantity: number;
+    target_price?: string;
+    note?: string;
+    expires_at?: string;
+  }): Promise<{ message: string; rfq: Rfq; supplier_count: number }> {
+    return this.request('/rfq', {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async getMyRfqs(page = 1, limit = 20, status?: Rfq['status']): Promise<{
+    rfqs: Rfq[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/rfq?page=${page}&limit=${limit}${status ? `&status=${status}` : ''}`);
+  }
+
+  async getOpenRfqs(page = 1, limit = 20): Promise<{
+    rfqs: Rfq[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/rfq/open?page=${page}&limit=${limit}`);
+  }
+
+  async getRfq(id: string): Promise<{ rfq: Rfq; quotes: Quote[] }> {
+    return this.request(`/rfq/${id}`);
+  }
+
+  async cancelRfq(id: string): Promise<{ message: string; rfq: Rfq }> {
+    return this.request(`/rfq/${id}/cancel`, {
+      method: 'POST',
+    });
+  }
+
+  async createQuote(rfqId: string, data: {
+    product_id: string;
+    price_per_unit: string;
+    quantity?: number;
+    note?: string;
+  }): Promise<{ message: string; quote: Quote }> {
+    return this.request(`/rfq/${rfqId}/quotes`, {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async acceptQuote(rfqId: string, quoteId: string, addressId: string): Promise<{
+    message: string;
+    order_id: string;
+  }> {
+    return this.request(`/rfq/${rfqId}/quotes/${quoteId}/accept`, {
+      method: 'POST',
+      body: JSON.stringify({ address_id: addressId }),
+    });
+  }
+
+  async getLocations(): Promise<{ locations: StockLocation[] }> {
+    return this.request('/seller/locations');
+  }
+
+  async createLocation(data: { name: string; address?: string; is_default?: boolean }): Promise<{
+    message: string;
+    location: StockLocation;
+  }> {
+    return this.request('/seller/locations', {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
+  // is_default can only be set to true; that moves the default here
+  async updateLocation(id: string, data: { name?: string; address?: string; is_default?: true }): Promise<{
+    message: string;
+    location: StockLocation;
+  }> {
+    return this.request(`/seller/locations/${id}`, {
+      method: 'PUT',
+      body: JSON.stringify(data),
+    });
+  }
+
+  // Refused while the location holds stock
+  async deleteLocation(id: string): Promise<{ message: string }> {
+    return this.request(`/seller/locations/${id}`, {
+      method: 'DELETE',
+    });
+  }
+
+  async getWebhooks(): Promise<{ webhooks: Webhook[] }> {
+    return this.request('/seller/webhooks');
+  }
+
+  // The secret signs every delivery and is only returned here
+  async createWebhook(data: { url: string; events: WebhookEvent[] }): Promise<{
+    message: string;
+    webhook: Webhook;
+    secret: string;
+  }> {
+    return this.request('/seller/webhooks', {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async updateWebhook(id: string, data: { url?: string; events?: WebhookEvent[]; is_active?: boolean }): Promise<{
+    message: string;
+    webhook: Webhook;
+  }> {
+    return this.request(`/seller/webhooks/${id}`, {
+      method: 'PUT',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async deleteWebhook(id: string): Promise<{ message: string }> {
+    return this.request(`/seller/webhooks/${id}`, {
+      method: 'DELETE',
+    });
+  }
+
+  async getWebhookDeliveries(id: string, page = 1, limit = 20, status?: WebhookDelivery['status']): Promise<{
+    deliveries: WebhookDelivery[];
+    max_attempts: number;
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/seller/webhooks/${id}/deliveries?page=${page}&limit=${limit}${status ? `&status=${status}` : ''}`);
+  }
+
+  async getMyDeliverySlots(): Promise<{ delivery_slots: DeliverySlot[] }> {
+    return this.request('/seller/delivery_slots');
+  }
+
+  async createDeliverySlot(data: { starts_at: string; ends_at: string; capacity: number }): Promise<{
+    message: string;
+    delivery_slot: DeliverySlot;
+  }> {
+    return this.request('/seller/delivery_slots', {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async deleteDeliverySlot(slotId: string): Promise<{ message: string }> {
+    return this.request(`/seller/delivery_slots/${slotId}`, {
+      method: 'DELETE',
+    });
+  }
+
+  async getSellerAnalytics(params?: { range?: '7d' | '30d' | '90d' | '365d' | 'all'; from?: string; to?: string }): Promise<SellerAnalytics> {
+    const searchParams = new URLSearchParams();
+    if (params?.range) searchParams.append('range', params.range);
+    if (params?.from) searchParams.append('from', params.from);
+    if (params?.to) searchParams.append('to', params.to);
+    const query = searchParams.toString();
+    return this.request(`/seller/analytics${query ? `?${query}` : ''}`);
+  }
+
+  async getEarnings(params?: { from?: string; to?: string; page?: number; limit?: number }): Promise<{
+    commission_percent: number;
+    balances: SellerBalance[];
+    entries: LedgerEntry[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/seller/earnings${orderFilterQuery({ ...params })}`);
+  }
+
+  // A link to the CSV statement; the session cookie authenticates the download
+  getEarningsStatementUrl(params?: { from?: string; to?: string }): string {
+    return `${API_BASE_URL}/seller/earnings/statement${orderFilterQuery({ ...params })}`;
+  }
+
+  // Admin only; what each seller is owed, largest first
+  async getPayoutsDue(page = 1, limit = 20): Promise<{
+    balances: PayoutDue[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/admin/payouts?page=${page}&limit=${limit}`);
+  }
+
+  // Admin only; platform-wide figures for the window, chosen as for seller analytics
+  async getPlatformReport(params?: { range?: '7d' | '30d' | '90d' | '365d' | 'all'; from?: string; to?: string }): Promise<PlatformReport> {
+    return this.request(`/admin/reports/platform${orderFilterQuery({ ...params })}`);
+  }
+
+  // A link to the daily figures as CSV; the session cookie authenticates the download
+  getPlatformReportCsvUrl(params?: { range?: '7d' | '30d' | '90d' | '365d' | 'all'; from?: string; to?: string }): string {
+    return `${API_BASE_URL}/admin/reports/platform${orderFilterQuery({ ...params, format: 'csv' })}`;
+  }
+
+  // Admin only; records money already sent, up to what the seller is owed
+  async recordPayout(sellerId: string, payout: { amount: number; currency?: string; reference?: string }): Promise<{
+    message: string;
+    entry: LedgerEntry;
+    balance: number;
+  }> {
+    return this.request(`/admin/sellers/${sellerId}/payouts`, {
+      method: 'POST',
+      body: JSON.stringify(payout),
+    });
+  }
+
+  // Admin only; newest first. user_id matches entries by or about the user, `to` is exclusive
+  async getAuditLog(params: {
+    user_id?: string;
+    action?: AuditAction;
+    from?: string;
+    to?: string;
+    page?: number;
+    limit?: number;
+  } = {}): Promise<{
+    entries: AuditLogEntry[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/admin/audit_log${orderFilterQuery({ ...params })}`);
+  }
+
+  // Signs this session in as the user until stopImpersonation
+  async impersonateUser(userId: string): Promise<{ message: string; user_id: string; impersonated_by: string }> {
+    return this.request(`/admin/impersonate/${userId}`, {
+      method: 'POST',
+    });
+  }
+
+  async stopImpersonation(): Promise<{ message: string; user_id: string }> {
+    return this.request('/admin/impersonate/stop', {
+      method: 'POST',
+    });
+  }
+
+  // Favorites endpoints
+  async addFavorite(productId: string): Promise<{ message: string; is_favorited: boolean }> {
+    return this.request(`/products/${productId}/favorite`, {
+      method: 'POST',
+    });
+  }
+
+  async removeFavorite(productId: string): Promise<{ message: string; is_favorited: boolean }> {
+    return this.request(`/products/${productId}/favorite`, {
+      method: 'DELETE',
+    });
+  }
+
+  async getFavorites(page = 1, limit = 20): Promise<{
+    products: Product[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/user/favorites?page=${page}&limit=${limit}`);
+  }
+
+  // Ranked from the user's order history, or the most popular products before their first order
+  async getRecommendations(): Promise<{ based_on: 'order_history' | 'popular'; products: Product[] }> {
+    return this.request('/user/recommendations');
+  }
+
   // Cart endpoints
   async getCart(): Promise<{
     items: CartItem[];
+    subtotal: number;
+    discount: number;
+    coupon: CartCoupon | null;
     total: number;
   }> {
     return this.request('/cart');
   }
 
-  async addToCart(productId: string, quantity: number): Promise<{ message: string }> {
+  // Works without logging in: a guest's cart is kept in the session until login
+  async addToCart(productId: string, quantity: number, variantId?: string): Promise<{ message: string; cart_size: number }> {
     return this.request('/cart/add', {
       method: 'POST',
       body: JSON.stringify({
         product_id: productId,
+        variant_id: variantId,
         quantity,
       }),
     });
   }
 
-  async removeFromCart(productId: string, quantity?: number): Promise<{ message: string }> {
-    const body: any = { product_id: productId };
+  async removeFromCart(productId: string, quantity?: number, variantId?: string): Promise<{ message: string }> {
+    const body: any = { product_id: productId, variant_id: variantId };
     if (quantity !== undefined) {
       body.quantity = quantity;
     }
@@ -193,52 +999,326 @@ class ApiClient {
     });
   }
 
+  // Sets the line's quantity outright; 0 removes it
+  async setCartQuantity(productId: string, quantity: number, variantId?: string): Promise<{
+    message: string;
+    quantity: number;
+    cart_size: number;
+  }> {
+    return this.request(`/cart/items/${productId}`, {
+      method: 'PUT',
+      body: JSON.stringify({ variant_id: variantId, quantity }),
+    });
+  }
+
+  // Replaces the whole cart; if any line fails, nothing changes
+  async replaceCart(items: Array<{ product_id: string; variant_id?: string; quantity: number }>): Promise<{
+    message: string;
+    cart_size: number;
+  }> {
+    return this.request('/cart', {
+      method: 'PUT',
+      body: JSON.stringify({ items }),
+    });
+  }
+
+  // Fixes the cart to match current stock and prices and lists what changed
+  async validateCart(): Promise<{ message: string; valid: boolean; issues: CartIssue[]; cart_size: number }> {
+    return this.request('/cart/validate', {
+      method: 'POST',
+    });
+  }
+
+  async applyCoupon(code: string): Promise<{ message: string; code: string; discount: number }> {
+    return this.request('/cart/apply_coupon', {
+      method: 'POST',
+      body: JSON.stringify({ code }),
+    });
+  }
+
+  async removeCoupon(): Promise<{ message: string }> {
+    return this.request('/cart/coupon', {
+      method: 'DELETE',
+    });
+  }
+
+  // Coupon endpoints (suppliers)
+  async getCoupons(): Promise<{ coupons: Coupon[] }> {
+    return this.request('/coupons');
+  }
+
+  async createCoupon(data: CreateCouponRequest): Promise<{ message: string; coupon: Coupon }> {
+    return this.request('/coupons', {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
   // Order endpoints
-  async getOrders(): Promise<{ orders: Order[] }> {
-    return this.request('/orders');
+  async getOrders(params: OrderFilters & { seller_id?: string } = {}): Promise<{
+    orders: Order[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/orders${orderFilterQuery(params)}`);
+  }
+
+  // All of a seller's orders, filtered like the buyer's history
+  async getSellerOrders(params: OrderFilters & { buyer_id?: string } = {}): Promise<{
+    orders: Order[];
+    pagination: { page: number; limit: number; total: number; pages: number };
+  }> {
+    return this.request(`/orders/seller${orderFilterQuery(params)}`);
   }
 
-  async createOrder(): Promise<{ message: string; order_ids: string[] }> {
+  // date is YYYY-MM-DD (UTC), today when left out
+  async getPicklist(date?: string): Promise<{
+    date: string;
+    order_count: number;
+    order_ids: string[];
+    items: PicklistLine[];
+  }> {
+    return this.request(`/orders/seller/picklist${orderFilterQuery({ date })}`);
+  }
+
+  // All or nothing: one order that can't move leaves every order as it was
+  async bulkUpdateOrderStatus(orderIds: string[], status: 'shipped' | 'delivered' | 'cancelled', locationId?: string): Promise<{
+    message: string;
+    updated: number;
+    order_ids: string[];
+    status: Order['status'];
+  }> {
+    return this.request('/orders/seller/bulk_status', {
+      method: 'POST',
+      body: JSON.stringify({ order_ids: orderIds, status, location_id: locationId }),
+    });
+  }
+
+  // deliverySlotIds books at most one slot per seller in the cart
+  async createOrder(addressId: string, deliverySlotIds: string[] = []): Promise<{ message: string; order_ids: string[] }> {
     return this.request('/orders', {
       method: 'POST',
+      body: JSON.stringify({ address_id: addressId, delivery_slot_ids: deliverySlotIds }),
+    });
+  }
+
+  // Into the cart, or with an address straight into a new order, at today's prices
+  async reorder(orderId: string, addressId?: string): Promise<ReorderResult> {
+    return this.request(`/orders/${orderId}/reorder`, {
+      method: 'POST',
+      body: JSON.stringify({ address_id: addressId }),
+    });
+  }
+
+  async rescheduleDelivery(orderId: string, deliverySlotId: string): Promise<{
+    message: string;
+    delivery_slot: Order['delivery_slot'];
+  }> {
+    return this.request(`/orders/${orderId}/delivery_slot`, {
+      method: 'PUT',
+      body: JSON.stringify({ delivery_slot_id: deliverySlotId }),
     });
   }
 
-  async getPendingOrders(): Promise<{ orders: Order[] }> {
+  async getPendingOrders(): Promise<{ orders: Order[]; overdue_count: number }> {
     return this.request('/orders/seller/pending');
   }
 
-  async updateOrderStatus(orderId: string, status: 'pending' | 'shipped' | 'delivered'): Promise<{ message: string }> {
+  // Shipment details can be given when marking the order shipped; stock kept by location
+  // ships from locationId, or the seller's default location
+  async updateOrderStatus(
+    orderId: string,
+    status: 'shipped' | 'delivered' | 'cancelled',
+    shipment: Partial<Shipment> = {},
+    locationId?: string
+  ): Promise<{ message: string }> {
     return this.request(`/orders/${orderId}/status`, {
       method: 'PUT',
-      body: JSON.stringify({ status }),
+      body: JSON.stringify({ status, ...shipment, location_id: locationId }),
     });
   }
 
-  // Conversation endpoints
-  async getConversations(): Promise<{
-    conversations: Array<{
-      id: string;
-      other_user_id: string;
-      other_user_name: string;
-      last_message?: string;
-      last_message_time?: string;
-      last_updated: string;
-    }>;
+  // Correct the courier, tracking number or expected date of an order on its way
+  async updateShipment(orderId: string, shipment: Partial<Shipment>): Promise<{ message: string; shipment: Shipment }> {
+    return this.request(`/orders/${orderId}/shipment`, {
+      method: 'PUT',
+      body: JSON.stringify(shipment),
+    });
+  }
+
+  async getOrderTimeline(orderId: string): Promise<OrderTimeline> {
+    return this.request(`/orders/${orderId}/timeline`);
+  }
+
+  async getOrderComments(orderId: string): Promise<{ order_id: string; comments: OrderComment[] }> {
+    return this.request(`/orders/${orderId}/comments`);
+  }
+
+  async addOrderComment(orderId: string, body: string): Promise<{ message: string; comment: OrderComment }> {
+    return this.request(`/orders/${orderId}/comments`, {
+      method: 'POST',
+      body: JSON.stringify({ body }),
+    });
+  }
+
+  // Ship or deliver some lines of an order; the order's status follows its items
+  async updateOrderItems(
+    orderId: string,
+    itemIds: string[],
+    status: Exclude<FulfillmentStatus, 'unfulfilled'>,
+    locationId?: string
+  ): Promise<{
+    message: string;
+    updated_items: number;
+    status: Order['status'];
+  }> {
+    return this.request(`/orders/${orderId}/items/fulfillment`, {
+      method: 'POST',
+      body: JSON.stringify({ item_ids: itemIds, status, location_id: locationId }),
+    });
+  }
+
+  async getInvoice(orderId: string): Promise<Invoice> {
+    return this.request(`/orders/${orderId}/invoice`);
+  }
+
+  async openDispute(orderId: string, reason: string, evidenceUrls: string[] = []): Promise<{ message: string; dispute: Dispute }> {
+    return this.request(`/orders/${orderId}/dispute`, {
+      method: 'POST',
+      body: JSON.stringify({ reason, evidence_urls: evidenceUrls }),
+    });
+  }
+
+  async getDispute(orderId: string): Promise<{
+    dispute: Dispute;
+    refund: { amount: string; provider: string; restocked: boolean; created_at: string } | null;
   }> {
+    return this.request(`/orders/${orderId}/dispute`);
+  }
+
+  async respondToDispute(orderId: string, response: string): Promise<{ message: string; dispute: Dispute }> {
+    return this.request(`/orders/${orderId}/dispute/response`, {
+      method: 'POST',
+      body: JSON.stringify({ response }),
+    });
+  }
+
+  // Standing order endpoints
+  async getRecurringOrders(): Promise<{ recurring_orders: RecurringOrder[] }> {
+    return this.request('/recurring_orders');
+  }
+
+  async createRecurringOrder(data: RecurringOrderRequest): Promise<{ message: string; recurring_order: RecurringOrder }> {
+    return this.request('/recurring_orders', {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async getRecurringOrder(id: string): Promise<{ recurring_order: RecurringOrder; runs: RecurringOrderRun[] }> {
+    return this.request(`/recurring_orders/${id}`);
+  }
+
+  async updateRecurringOrder(
+    id: string,
+    data: Partial<Omit<RecurringOrderRequest, 'starts_at'>>
+  ): Promise<{ message: string; recurring_order: RecurringOrder }> {
+    return this.request(`/recurring_orders/${id}`, {
+      method: 'PUT',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async pauseRecurringOrder(id: string): Promise<{ message: string; recurring_order: RecurringOrder }> {
+    return this.request(`/recurring_orders/${id}/pause`, { method: 'POST' });
+  }
+
+  async resumeRecurringOrder(id: string): Promise<{ message: string; recurring_order: RecurringOrder }> {
+    return this.request(`/recurring_orders/${id}/resume`, { method: 'POST' });
+  }
+
+  async skipRecurringOrder(id: string): Promise<{ message: string; recurring_order: RecurringOrder }> {
+    return this.request(`/recurring_orders/${id}/skip`, { method: 'POST' });
+  }
+
+  async cancelRecurringOrder(id: string): Promise<{ message: string; recurring_order: RecurringOrder }> {
+    return this.request(`/recurring_orders/${id}/cancel`, { method: 'POST' });
+  }
+
+  // Conversation endpoints
+  async getConversations(): Promise<{ conversations: Conversation[] }> {
     return this.request('/conversations');
   }
 
-  async getMessages(convId: string): Promise<{
-    messages: Array<{
-      id: string;
-      sender_id: string;
-      sender_name: string;
-      content: string;
-      sent_at: string;
-    }>;
+  // Open a chat with a seller, about one of their products when started from its page
+  async startConversation(sellerId: string, productId?: string): Promise<{
+    conv_id: string;
+    other_user_id: string;
+    other_user_name: string;
+    product: ConversationProduct | null;
+  }> {
+    return this.request('/conversations', {
+      method: 'POST',
+      body: JSON.stringify({ seller_id: sellerId, product_id: productId }),
+    });
+  }
+
+  // Fallback for when the WebSocket is down; the message is still pushed to the receiver
+  async sendMessage(receiverId: string, content: string, attachmentUrl?: string): Promise<{ message: string; chat_message: Message }> {
+    return this.request('/messages', {
+      method: 'POST',
+      body: JSON.stringify({ receiver_id: receiverId, content, attachment_url: attachmentUrl }),
+    });
+  }
+
+  // Into a conversation, which reaches everyone in it
+  async sendToConversation(convId: string, content: string, attachmentUrl?: string): Promise<{ message: string; chat_message: Message }> {
+    return this.request('/messages', {
+      method: 'POST',
+      body: JSON.stringify({ conv_id: convId, content, attachment_url: attachmentUrl }),
+    });
+  }
+
+  // Announce something to every buyer who has ordered from the seller
+  async sendBroadcast(content: string, attachmentUrl?: string): Promise<{
+    message: string;
+    conv_id: string;
+    recipient_count: number;
+    chat_message: Message;
+  }> {
+    return this.request('/seller/broadcast', {
+      method: 'POST',
+      body: JSON.stringify({ content, attachment_url: attachmentUrl }),
+    });
+  }
+
+  // Newest first; pass next_before as `before` to page further back
+  async getMessages(convId: string, params?: { before?: string; limit?: number }): Promise<{
+    messages: Message[];
+    has_more: boolean;
+    next_before: string | null;
+  }> {
+    const searchParams = new URLSearchParams();
+    if (params?.before) searchParams.append('before', params.before);
+    if (params?.limit) searchParams.append('limit', params.limit.toString());
+    const query = searchParams.toString();
+    return this.request(`/messages/${convId}${query ? `?${query}` : ''}`);
+  }
+
+  // Only the sender can edit or delete, within 15 minutes of sending
+  async editMessage(messageId: string, content: string): Promise<{
+    message: string;
+    chat_message: { type: 'message_edited'; id: string; conv_id: string; content: string; edited_at: string };
   }> {
-    return this.request(`/messages/${convId}`);
+    return this.request(`/messages/${messageId}`, {
+      method: 'PUT',
+      body: JSON.stringify({ content }),
+    });
+  }
+
+  async deleteMessage(messageId: string): Promise<{ message: string }> {
+    return this.request(`/messages/${messageId}`, {
+      method: 'DELETE',
+    });
   }
 
   // File upload endpoints
@@ -263,6 +1343,43 @@ class ApiClient {
       body: formData,
     });
   }
+
+  // Upload an image to a conversation, then send its attachment_url in a message
+  async uploadMessageAttachment(convId: string, file: File): Promise<{
+    message: string;
+    attachment_url: string;
+    attachment_type: 'image';
+  }> {
+    const formData = new FormData();
+    formData.append('conv_id', convId);
+    formData.append('file', file);
+
+    return this.request('/upload/message', {
+      method: 'POST',
+      headers: {}, // Don't set Content-Type for FormData
+      body: formData,
+    });
+  }
+
+  // GraphQL lives at /graphql, outside /api. Query errors come back in `errors`
+  // with a 200, each with the REST status code in `extensions.code`
+  async graphql<T>(query: string, variables?: Record<string, unknown>): Promise<{
+    data: T | null;
+    errors?: { message: string; path?: (string | number)[]; extensions?: { code?: number } }[];
+  }> {
+    const response = await fetch(API_BASE_URL.replace(/\/api$/, '/graphql'), {
+      method: 'POST',
+      credentials: 'include',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ query, variables }),
+    });
+
+    if (!response.ok) {
+      throw new Error(`HTTP ${response.status}`);
+    }
+
+    return response.json();
+  }
 }
 
 export const apiClient = new ApiClient();
\ No newline at end of file
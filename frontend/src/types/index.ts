@@ -1,4 +1,10 @@
 // src/types/index.ts
+// One invalid request field, as listed in a 422 response
+export interface FieldError {
+  field: string;
+  message: string;
+}
+
 export interface User {
   id: string;
   email: string;
@@ -8,6 +14,22 @@ export interface User {
   rating?: number;
   total_deliveries: number;
   profile_image_url?: string;
+  latitude?: number | null;
+  longitude?: number | null;
+  // Set on the profile while an admin is signed in as this user
+  impersonated_by?: string | null;
+}
+
+export type ApiTokenScope = 'products:read' | 'products:write' | 'orders:read' | 'orders:write';
+
+export interface ApiToken {
+  id: string;
+  name: string;
+  token_prefix: string;
+  scopes: ApiTokenScope[];
+  expires_at?: string | null;
+  last_used_at?: string | null;
+  created_at: string;
 }
 
 export interface AuthFormData {
@@ -16,19 +38,95 @@ export interface AuthFormData {
   name?: string;
   phone?: string;
   is_supplier: boolean;
+  /** Stay signed in across browser restarts */
+  remember_me?: boolean;
 }
 
 export interface Category {
   id: number;
   name: string;
+  parent_id?: number | null;
+}
+
+export interface CategoryFacet {
+  id: number;
+  name: string;
+  count: number;
+}
+
+export interface ProductSuggestions {
+  query: string;
+  products: Array<{ id: string; name: string }>;
+  categories: Category[];
+}
+
+export interface ProductSearchResult {
+  type: 'product';
+  id: string;
+  name: string;
+  price_per_unit: string;
+  currency: string;
+  image_url?: string | null;
+  seller_id: string;
+  seller_name?: string | null;
+  category_id: number;
+  category_name: string;
+}
+
+export interface SellerSearchResult {
+  type: 'seller';
+  id: string;
+  name?: string | null;
+  profile_image_url?: string | null;
+  rating?: number | null;
+  total_deliveries: number;
+  store_paused: boolean;
+  product_count: number;
+}
+
+export interface CategorySearchResult {
+  type: 'category';
+  id: number;
+  name: string;
+  parent_id?: number | null;
+  product_count: number;
+}
+
+export type SearchResult = ProductSearchResult | SellerSearchResult | CategorySearchResult;
+
+// One kind of result: the best `limit` of `total` matches
+export interface SearchSection<T extends SearchResult> {
+  total: number;
+  results: T[];
 }
 
+export interface SearchResults {
+  query: string;
+  products: SearchSection<ProductSearchResult>;
+  sellers: SearchSection<SellerSearchResult>;
+  categories: SearchSection<CategorySearchResult>;
+}
+
+export type ProductUnit = 'piece' | 'kg' | 'gram' | 'litre' | 'dozen' | 'bunch' | 'crate' | 'sack';
+
 export interface Product {
   id: string;
   name: string;
   description?: string;
   price_per_unit: number;
+  currency: string;
+  // Only when a display currency was requested
+  display_price?: number | null;
+  display_currency?: string | null;
   stock_qty: number;
+  // How many of the seller's locations hold some of the stock; 0 if it isn't kept by location
+  stock_locations: number;
+  // Each unique among the seller's products
+  sku?: string | null;
+  barcode?: string | null;
+  // Price and stock are per unit; carts and orders take whole multiples of min_increment
+  unit: ProductUnit;
+  min_increment: number;
   image_url?: string;
   seller_id: string;
   category_id: number;
@@ -37,16 +135,270 @@ export interface Product {
   seller_company: string;
   seller_rating?: number;
   seller_deliveries: number;
+  seller_min_order_value: number;
+  seller_delivery_fee: number;
+  images?: ProductImage[];
+  price_tiers: PriceTier[];
+  // The flash deal on now with units left
+  deal: ActiveDeal | null;
+  // Only on the product detail
+  variants?: ProductVariant[];
+  is_favorited?: boolean;
+  // Only when the listing was searched from a location
+  distance_km?: number | null;
+  // Anything but approved is only shown to the product's seller
+  review_status: ProductReviewStatus;
+  // Only for the seller, on a rejected product's detail
+  review_reason?: string;
+  created_at: string;
+}
+
+export type ProductReviewStatus = 'pending_review' | 'approved' | 'rejected';
+
+export interface ProductReviewItem {
+  id: string;
+  name: string;
+  description?: string | null;
+  price_per_unit: number;
+  currency: string;
+  stock_qty: number;
+  category_name: string;
+  seller_id: string;
+  seller_name?: string | null;
+  images: string[];
   created_at: string;
 }
 
+export interface SellerProfile {
+  id: string;
+  name?: string;
+  rating?: number;
+  total_deliveries: number;
+  // Share of delivered orders that arrived by their expected time; null before any
+  on_time_delivery_percent: number | null;
+  lead_time_hours: number;
+  profile_image_url?: string;
+  min_order_value: number;
+  delivery_fee: number;
+  store_paused: boolean;
+  member_since: string;
+  product_count: number;
+}
+
+export interface SellerAnalytics {
+  range: { from: string | null; to: string };
+  revenue: {
+    total: number;
+    discounts: number;
+    paid_orders: number;
+    average_order_value: number;
+    daily: Array<{ date: string; revenue: number; orders: number }>;
+  };
+  orders: {
+    total: number;
+    by_status: Array<{ status: string; count: number }>;
+  };
+  top_products: Array<{
+    product_id: string;
+    name: string;
+    units_sold: number;
+    revenue: number;
+    orders: number;
+  }>;
+  buyers: {
+    total: number;
+    repeat: number;
+    returning: number;
+    repeat_rate: number;
+  };
+}
+
+export interface PlatformReport {
+  range: { from: string | null; to: string };
+  gmv: Array<{ currency: string; amount: number; paid_orders: number }>;
+  new_users: number;
+  active_suppliers: number;
+  orders: {
+    total: number;
+    paid: number;
+    by_status: Array<{ status: string; count: number }>;
+  };
+  top_categories: Array<{
+    category_id: number;
+    name: string;
+    currency: string;
+    orders: number;
+    units_sold: number;
+    gmv: number;
+  }>;
+  daily: Array<{
+    date: string;
+    new_users: number;
+    orders: number;
+    paid_orders: number;
+    active_suppliers: number;
+    // Keyed by currency
+    gmv: Record<string, number>;
+  }>;
+}
+
+export interface LedgerEntry {
+  id: string;
+  order_id?: string | null;
+  kind: 'sale' | 'refund' | 'payout';
+  gross_amount: number;
+  commission_amount: number;
+  amount: number;
+  currency: string;
+  reference?: string | null;
+  created_at: string;
+}
+
+export interface SellerBalance {
+  currency: string;
+  gross: number;
+  commission: number;
+  paid_out: number;
+  balance: number;
+}
+
+export interface PayoutDue {
+  seller_id: string;
+  seller_name: string;
+  seller_email: string;
+  currency: string;
+  balance: number;
+  last_payout_at?: string | null;
+}
+
+export type AuditAction =
+  | 'login'
+  | 'login_failed'
+  | 'password_changed'
+  | 'password_reset_requested'
+  | 'password_reset'
+  | 'email_changed'
+  | 'two_factor_enabled'
+  | 'two_factor_disabled'
+  | 'settings_updated'
+  | 'role_changed'
+  | 'user_suspended'
+  | 'user_unsuspended'
+  | 'user_unlocked'
+  | 'product_price_changed'
+  | 'product_stock_changed'
+  | 'order_status_changed'
+  | 'impersonation_started'
+  | 'impersonation_stopped'
+  | 'impersonated_request';
+
+// actor_id is null for the system and for requests made without a login
+export interface AuditLogEntry {
+  id: string;
+  actor_id?: string | null;
+  actor_email?: string | null;
+  action: AuditAction;
+  subject_id?: string | null;
+  details: Record<string, unknown>;
+  created_at: string;
+}
+
+export interface ProductImage {
+  id: string;
+  url: string;
+}
+
+// From min_qty units in one cart line, each costs unit_price
+export interface PriceTier {
+  min_qty: number;
+  unit_price: number;
+}
+
+// A product's flash deal as products and cart lines show it
+export interface ActiveDeal {
+  id: string;
+  deal_price: number;
+  ends_at: string;
+  remaining: number;
+}
+
+// Sells the product at deal_price from starts_at to ends_at, up to max_quantity units
+export interface Deal {
+  id: string;
+  product_id: string;
+  deal_price: number;
+  starts_at: string;
+  ends_at: string;
+  max_quantity: number;
+  sold_quantity: number;
+  created_at: string;
+}
+
+export interface CreateDealRequest {
+  deal_price: number;
+  starts_at?: string;
+  ends_at: string;
+  max_quantity: number;
+}
+
+export interface ProductVariant {
+  id: string;
+  product_id: string;
+  name: string;
+  price_per_unit: number;
+  // Only when a display currency was requested
+  display_price?: number | null;
+  stock_qty: number;
+  created_at: string;
+}
+
+export interface PriceChange {
+  id: string;
+  variant_id?: string | null;
+  variant_name?: string | null;
+  old_price: number;
+  new_price: number;
+  changed_at: string;
+}
+
+export type InventoryReason = 'order' | 'cancellation' | 'adjustment' | 'import';
+
+export interface InventoryMovement {
+  id: string;
+  product_id: string;
+  variant_id?: string | null;
+  variant_name?: string | null;
+  quantity_change: number;
+  stock_after: number;
+  reason: InventoryReason;
+  note?: string | null;
+  order_id?: string | null;
+  actor_id?: string | null;
+  actor_name?: string | null;
+  created_at: string;
+}
+
+export interface StockAdjustRequest {
+  variant_id?: string;
+  quantity_change: number;
+  note: string;
+}
+
 export interface CreateProductRequest {
   name: string;
   description?: string;
   price_per_unit: number;
   stock_qty: number;
+  // By the piece, one at a time, when omitted
+  unit?: ProductUnit;
+  min_increment?: number;
   category_id: number;
   image_url?: string;
+  // Defaults to the seller's location
+  latitude?: number;
+  longitude?: number;
+  sku?: string;
+  barcode?: string;
 }
 
 export interface UpdateProductRequest {
@@ -54,27 +406,68 @@ export interface UpdateProductRequest {
   description?: string;
   price_per_unit?: number;
   stock_qty?: number;
+  unit?: ProductUnit;
+  min_increment?: number;
   category_id?: number;
   image_url?: string;
+  latitude?: number;
+  longitude?: number;
+  // An empty string clears them
+  sku?: string;
+  barcode?: string;
 }
 
 export interface CartItem {
   product_id: string;
+  variant_id?: string;
   name: string;
+  variant_name?: string;
+  // After any price tier or deal; list_price is before
   price_per_unit: number;
+  list_price: number;
+  deal?: ActiveDeal | null;
   quantity: number;
+  unit: ProductUnit;
+  min_increment: number;
   subtotal: number;
   image_url?: string;
   seller_name: string;
   available_stock: number;
+  reserved_until?: string;
+}
+
+export interface CartIssue {
+  product_id: string;
+  variant_id?: string;
+  name: string;
+  variant_name?: string;
+  issue: 'unavailable' | 'out_of_stock' | 'quantity_reduced' | 'quantity_rounded' | 'price_changed';
+  requested?: number;
+  available?: number;
+  // quantity_rounded: the nearest multiple of the product's increment there is stock for
+  quantity?: number;
+  old_price?: number;
+  new_price?: number;
 }
 
+export type FulfillmentStatus = 'unfulfilled' | 'shipped' | 'delivered';
+
 export interface OrderItem {
+  id: string;
   product_id: string;
   product_name: string;
+  variant_id?: string;
+  variant_name?: string;
   quantity: number;
+  unit: ProductUnit;
   unit_price: number;
+  // Percent; 0 when the seller isn't registered for tax
+  tax_rate: number;
+  tax_amount: number;
   image_url?: string;
+  fulfillment_status: FulfillmentStatus;
+  shipped_at?: string | null;
+  delivered_at?: string | null;
 }
 
 export interface Order {
@@ -84,40 +477,495 @@ export interface Order {
   buyer_id?: string;
   buyer_name?: string;
   buyer_phone?: string;
-  status: 'pending' | 'shipped' | 'delivered';
+  status: 'pending' | 'paid' | 'failed' | 'partially_shipped' | 'shipped' | 'delivered' | 'cancelled' | 'disputed' | 'refunded';
+  subtotal_price: number;
+  discount_amount: number;
+  delivery_fee: number;
+  tax_amount: number;
+  coupon_code?: string | null;
   total_price: number;
+  currency: string;
+  shipping_address?: ShippingAddress | null;
+  delivery_slot?: Pick<DeliverySlot, 'id' | 'starts_at' | 'ends_at'> | null;
+  // Set by the seller when shipping; null before
+  shipment?: Shipment | null;
+  // The end of the delivery slot, or the seller's lead time after the order was placed
+  expected_delivery_at: string | null;
+  // Seller listings only: still open past expected_delivery_at
+  overdue?: boolean;
   created_at: string;
   items: OrderItem[];
 }
 
+export interface Shipment {
+  courier_name: string | null;
+  tracking_number: string | null;
+  // YYYY-MM-DD
+  expected_delivery_date: string | null;
+}
+
+// Each status the order moved to and each change to its shipment, oldest first
+export type OrderTimelineEvent =
+  | { type: 'status'; from_status: Order['status'] | null; status: Order['status']; changed_by: string | null; at: string }
+  | ({ type: 'shipment'; changed_by: string | null; at: string } & Shipment)
+  | { type: 'comment'; id: string; author_id: string; author_name: string | null; body: string; at: string };
+
+// A note on an order between its buyer and seller
+export interface OrderComment {
+  id: string;
+  order_id: string;
+  author_id: string;
+  author_name: string | null;
+  body: string;
+  created_at: string;
+}
+
+export interface OrderTimeline {
+  order_id: string;
+  status: Order['status'];
+  shipment: Shipment | null;
+  events: OrderTimelineEvent[];
+}
+
+// An item of the earlier order that couldn't be ordered again as it was
+export interface ReorderItem {
+  product_id: string;
+  variant_id: string | null;
+  name: string;
+  variant_name: string | null;
+  requested_quantity: number;
+  // What was ordered instead, cut to the stock left; absent for items left out
+  quantity?: number;
+}
+
+export interface ReorderResult {
+  message: string;
+  // cart_size when the items went into the cart, order_id when they were ordered
+  cart_size?: number;
+  order_id?: string;
+  adjusted_items: ReorderItem[];
+  unavailable_items: ReorderItem[];
+  price_changes: Array<{
+    product_id: string;
+    variant_id: string | null;
+    name: string;
+    variant_name: string | null;
+    previous_unit_price: number;
+    unit_price: number;
+  }>;
+}
+
+// A delivery window a seller offers; capacity and booked are only shown to the seller
+export interface DeliverySlot {
+  id: string;
+  starts_at: string;
+  ends_at: string;
+  remaining: number;
+  capacity?: number;
+  booked?: number;
+}
+
+// Where a seller delivers: any listed pincode, or within radius_km of center
+export interface DeliveryZone {
+  pincodes: string[];
+  radius_km: number | null;
+  center: { latitude: number; longitude: number } | null;
+  delivers_anywhere: boolean;
+}
+
+// A buyer's request for quote on a product, or on anything in a category
+export interface Rfq {
+  id: string;
+  buyer_id: string;
+  buyer_name: string | null;
+  product_id: string | null;
+  product_name: string | null;
+  category_id: number | null;
+  category_name: string | null;
+  quantity: number;
+  target_price: string | null;
+  note: string | null;
+  status: 'open' | 'awarded' | 'cancelled';
+  expires_at: string;
+  awarded_quote_id: string | null;
+  quote_count: number;
+  created_at: string;
+}
+
+// A supplier's answer to an RFQ, with the terms of the offer it opened
+export interface Quote {
+  id: string;
+  rfq_id: string;
+  seller_id: string;
+  seller_name: string | null;
+  product_id: string;
+  product_name: string;
+  offer_id: string;
+  price_per_unit: string;
+  quantity: number;
+  currency: string;
+  offer_status: 'pending' | 'accepted' | 'rejected' | 'countered' | 'ordered';
+  note: string | null;
+  created_at: string;
+}
+
+// A stall or warehouse a seller keeps stock at; orders ship from the default unless another is picked
+export interface StockLocation {
+  id: string;
+  name: string;
+  address: string | null;
+  is_default: boolean;
+  product_count: number;
+  units_on_hand: number;
+  created_at: string;
+}
+
+export interface ProductLocationStock {
+  product_id: string;
+  stocked_by_location: boolean;
+  stock_qty: number;
+  on_hand: number;
+  locations: {
+    location_id: string;
+    name: string;
+    is_default: boolean;
+    quantity: number;
+    updated_at: string | null;
+  }[];
+}
+
+export type WebhookEvent = 'order.created' | 'order.status_changed' | 'product.low_stock';
+
+// A URL a seller's events are POSTed to; the secret is only returned on creation
+export interface Webhook {
+  id: string;
+  url: string;
+  events: WebhookEvent[];
+  is_active: boolean;
+  created_at: string;
+}
+
+export interface WebhookDelivery {
+  id: string;
+  event_id: string;
+  event: WebhookEvent;
+  payload: Record<string, unknown>;
+  status: 'pending' | 'delivered' | 'failed';
+  attempts: number;
+  next_attempt_at: string;
+  last_response_status: number | null;
+  // A category, never the receiver's response
+  last_error: 'unexpected_status' | 'timeout' | 'connection_failed' | 'blocked_address' | 'request_failed' | null;
+  created_at: string;
+  delivered_at: string | null;
+}
+
+// A seller refusing a checkout to an address outside their zone
+export interface UndeliverableSeller {
+  seller_id: string;
+  seller_name: string | null;
+  message: string;
+}
+
+// Prices are before tax; tax is charged on each line's share of the discounted subtotal
+export interface Invoice {
+  invoice_number: string;
+  order_id: string;
+  issued_at: string;
+  status: Order['status'];
+  seller: { id: string; name?: string; tax_id?: string | null; tax_name?: string | null };
+  buyer: { id: string; name?: string };
+  shipping_address?: ShippingAddress | null;
+  lines: Array<{
+    product_id: string;
+    product_name: string;
+    variant_name?: string | null;
+    quantity: number;
+    unit_price: number;
+    amount: number;
+    tax_rate: number;
+    tax_amount: number;
+  }>;
+  tax_summary: Array<{ tax_rate: number; taxable_amount: number; tax_amount: number }>;
+  subtotal_price: number;
+  discount_amount: number;
+  tax_amount: number;
+  delivery_fee: number;
+  total_price: number;
+}
+
+// Order history filters; `search` matches product names and `to` is exclusive
+export interface OrderFilters {
+  status?: Order['status'];
+  from?: string;
+  to?: string;
+  search?: string;
+  page?: number;
+  limit?: number;
+}
+
+// A day's unshipped quantity of one product (or variant) across the seller's open orders
+export interface PicklistLine {
+  product_id: string;
+  product_name: string;
+  variant_name?: string | null;
+  unit: ProductUnit;
+  quantity: number;
+  order_count: number;
+}
+
+export interface CartCoupon {
+  code: string;
+  discount: number;
+  // Why the coupon no longer applies, if it doesn't
+  error?: string | null;
+}
+
+export interface Coupon {
+  id: string;
+  code: string;
+  discount_type: 'percentage' | 'fixed';
+  discount_value: number;
+  min_order_value?: number | null;
+  max_uses_per_user?: number | null;
+  expires_at?: string | null;
+  is_active: boolean;
+  times_used?: number;
+  created_at: string;
+}
+
+export interface CreateCouponRequest {
+  code: string;
+  discount_type: 'percentage' | 'fixed';
+  discount_value: number;
+  min_order_value?: number;
+  max_uses_per_user?: number;
+  expires_at?: string;
+}
+
+export interface ShippingAddress {
+  recipient_name: string;
+  phone: string;
+  line1: string;
+  line2?: string | null;
+  city: string;
+  state: string;
+  postal_code: string;
+  country: string;
+  // Checked against sellers who deliver within a radius
+  latitude?: number | null;
+  longitude?: number | null;
+}
+
+export interface Address extends ShippingAddress {
+  id: string;
+  label?: string | null;
+  is_default: boolean;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface AddressFormData extends Omit<ShippingAddress, 'country'> {
+  label?: string;
+  country?: string;
+  is_default?: boolean;
+}
+
+// The listing a conversation was started from
+export interface ConversationProduct {
+  id: string;
+  name: string;
+  image_url?: string | null;
+  price_per_unit: string;
+  currency: string;
+}
+
 export interface Conversation {
   id: string;
-  other_user_id: string;
-  other_user_name: string;
+  // A seller's broadcast to their buyers, shown under its title
+  is_group: boolean;
+  title?: string | null;
+  participant_count: number;
+  // The seller in a broadcast; null in the seller's own
+  other_user_id: string | null;
+  other_user_name: string | null;
   last_message?: string;
+  last_message_attachment_type?: 'image' | null;
   last_message_time?: string;
   last_updated: string;
+  unread_count?: number;
+  product?: ConversationProduct | null;
 }
 
 export interface Message {
   id: string;
+  conv_id?: string;
   sender_id: string;
   sender_name: string;
-  content: string;
+  // null once the message is deleted
+  content: string | null;
+  attachment_url?: string | null;
+  attachment_type?: 'image' | null;
   sent_at: string;
+  read_at?: string | null;
+  edited_at?: string | null;
+  deleted_at?: string | null;
 }
 
+// Every frame the server sends carries the protocol version it speaks
+export const WS_PROTOCOL_VERSION = 1;
+
 export interface WebSocketMessage {
-  type: 'message' | 'offer' | 'error';
+  version: number;
+  type: 'message' | 'offer' | 'typing_start' | 'typing_stop' | 'message_edited' | 'message_deleted';
   id: string;
   conv_id: string;
   sender_id: string;
   sender_name: string;
   content: string;
+  attachment_url?: string | null;
+  attachment_type?: 'image' | null;
   sent_at: string;
+  read_at?: string | null;
+  edited_at?: string | null;
+  deleted_at?: string | null;
+}
+
+export type WebSocketErrorCode =
+  | 'invalid_frame'
+  | 'frame_too_large'
+  | 'unsupported_version'
+  | 'unknown_event'
+  | 'bad_request'
+  | 'forbidden'
+  | 'not_found'
+  | 'conflict'
+  | 'internal_error';
+
+// Sent back for a frame the server refused
+export interface WebSocketError {
+  version: number;
+  type: 'error';
+  code: WebSocketErrorCode;
+  message: string;
+}
+
+export interface BlockedUser {
+  id: string;
+  name?: string;
+  profile_image_url?: string;
+  blocked_at: string;
+}
+
+export interface Dispute {
+  id: string;
+  order_id: string;
+  buyer_id: string;
+  seller_id: string;
+  reason: string;
+  evidence_urls: string[];
+  status: 'open' | 'responded' | 'refunded' | 'rejected';
+  seller_response?: string;
+  seller_responded_at?: string;
+  resolution_note?: string;
+  resolved_by?: string;
+  resolved_at?: string;
+  created_at: string;
+}
+
+export type RecurringOrderStatus = 'active' | 'paused' | 'cancelled';
+
+export interface RecurringOrderItem {
+  product_id: string;
+  variant_id?: string | null;
+  name: string;
+  variant_name?: string | null;
+  quantity: number;
+  // At today's price; each order is charged what the item costs when it's placed
+  unit_price: number;
+  currency: string;
+}
+
+// The same items from one seller every interval_days days
+export interface RecurringOrder {
+  id: string;
+  seller_id: string;
+  seller_name: string;
+  // Null once the address is deleted; no orders are placed until another is chosen
+  address_id: string | null;
+  interval_days: number;
+  status: RecurringOrderStatus;
+  next_run_at: string;
+  items: RecurringOrderItem[];
+  created_at: string;
+  updated_at: string;
+}
+
+// One cycle: the order it placed, or the error that stopped it
+export interface RecurringOrderRun {
+  id: string;
+  scheduled_for: string;
+  order_id: string | null;
+  error: string | null;
+  created_at: string;
+}
+
+export interface RecurringOrderRequest {
+  items: Array<{ product_id: string; variant_id?: string; quantity: number }>;
+  interval_days: number;
+  address_id: string;
+  starts_at?: string;
+}
+
+export interface OrderUpdateEvent {
+  type: 'order_update';
+  order_id: string;
+  status: 'pending' | 'paid' | 'failed' | 'partially_shipped' | 'shipped' | 'delivered' | 'cancelled' | 'disputed' | 'refunded';
+  total_price: number;
+}
+
+export interface OrderCommentEvent {
+  type: 'order_comment';
+  comment: OrderComment;
+}
+
+export interface PriceDropEvent {
+  type: 'price_drop';
+  product_id: string;
+  variant_id?: string | null;
+  product_name: string;
+  old_price: number;
+  new_price: number;
+}
+
+// Named to avoid clashing with the DOM's Notification
+export interface AppNotification {
+  id: string;
+  kind: 'order' | 'message' | 'offer' | 'low_stock' | 'product_review' | 'payout' | 'rfq';
+  title: string;
+  // Ids to link to, e.g. order_id, conv_id, offer_id or product_id
+  data: Record<string, unknown>;
+  read_at?: string | null;
+  created_at: string;
+}
+
+export type PushPlatform = 'android' | 'ios' | 'web';
+
+export interface PushDevice {
+  id: string;
+  platform: PushPlatform;
+  created_at: string;
+  last_used_at?: string | null;
+}
+
+export interface NotificationEvent {
+  type: 'notification';
+  notification: AppNotification;
+  unread_count: number;
 }
 
 export interface Category {
   id: number;
   name: string;
+  parent_id?: number | null;
 }
\ No newline at end of file